@@ -0,0 +1,197 @@
+"""
+Retention time alignment across measurements.
+
+Batch runs drift in retention time. Ions with a detected, integrated peak
+(see calculation.gap_filling.peak_found) in (nearly) every file serve as
+reference peaks: their median apex RT over the batch is the reference, and
+every file gets a shift function ``reference - observed`` of its own RT,
+fitted on its anchors either as a straight line ("linear") or by local
+linear regression ("loess") for non-linear drift.
+
+The raw ``RT`` of every ion is kept; the corrected value is stored next to it
+as ``Aligned RT``.
+"""
+
+import logging
+from dataclasses import dataclass, field
+from typing import Dict, List, Sequence, Tuple
+
+import numpy as np
+
+from calculation.gap_filling import peak_found
+
+logger = logging.getLogger(__name__)
+
+ALIGNMENT_METHODS = ("linear", "loess")
+
+
+def _loess(x: np.ndarray, y: np.ndarray, span: float) -> np.ndarray:
+    """Locally weighted linear fit of y(x), evaluated at every x (tricube weights)."""
+    n = len(x)
+    k = min(n, max(3, int(np.ceil(span * n))))
+    smoothed = np.empty(n, dtype=np.float64)
+    for i in range(n):
+        distances = np.abs(x - x[i])
+        radius = np.sort(distances)[k - 1]
+        if radius == 0:
+            smoothed[i] = np.mean(y[distances == 0])
+            continue
+        weights = (1 - np.clip(distances / radius, 0, 1) ** 3) ** 3
+        if np.count_nonzero(weights) < 2 or np.ptp(x[weights > 0]) == 0:
+            smoothed[i] = np.average(y, weights=weights) if weights.sum() > 0 else y[i]
+            continue
+        slope, intercept = np.polyfit(x, y, 1, w=np.sqrt(weights))
+        smoothed[i] = slope * x[i] + intercept
+    return smoothed
+
+
+@dataclass
+class RTShift:
+    """
+    Retention time shift function of one file.
+
+    Attributes
+    ----------
+    method : str
+        One of ALIGNMENT_METHODS.
+    anchors : list of (float, float)
+        ``(observed, reference)`` RTs of the reference peaks, sorted by the
+        observed RT. With fewer than two distinct ones the shift is constant
+        (zero without anchors).
+    span : float
+        Fraction of the anchors in each local fit for "loess".
+    """
+
+    method: str = "linear"
+    anchors: List[Tuple[float, float]] = field(default_factory=list)
+    span: float = 0.75
+    _knots: Tuple[np.ndarray, np.ndarray] = field(default=None, init=False, repr=False)
+    _slope: float = field(default=0.0, init=False, repr=False)
+
+    def __post_init__(self):
+        if self.method not in ALIGNMENT_METHODS:
+            raise ValueError(
+                f"Unknown alignment method '{self.method}', expected one of {ALIGNMENT_METHODS}"
+            )
+        self.anchors = sorted((float(obs), float(ref)) for obs, ref in self.anchors)
+        observed = np.array([obs for obs, _ in self.anchors], dtype=np.float64)
+        shifts = np.array([ref - obs for obs, ref in self.anchors], dtype=np.float64)
+        if len(np.unique(observed)) < 2:
+            constant = float(np.mean(shifts)) if len(shifts) else 0.0
+            self._knots = (np.zeros(1), np.full(1, constant))
+        elif self.method == "linear":
+            slope, intercept = np.polyfit(observed, shifts, 1)
+            ends = np.array([observed[0], observed[-1]])
+            self._knots = (ends, slope * ends + intercept)
+            self._slope = float(slope)
+        else:
+            self._knots = (observed, _loess(observed, shifts, self.span))
+
+    def __call__(self, rt):
+        """Shift to add to an observed RT (min); accepts scalars and arrays."""
+        rt = np.asarray(rt, dtype=np.float64)
+        knots_x, knots_y = self._knots
+        if self.method == "linear" and len(knots_x) == 2:
+            # Extrapolate the fitted line beyond the anchors
+            shift = knots_y[0] + self._slope * (rt - knots_x[0])
+        else:
+            # Linear between the smoothed anchors, constant beyond them
+            shift = np.interp(rt, knots_x, knots_y)
+        return float(shift) if shift.ndim == 0 else shift
+
+    def correct(self, rt):
+        """Aligned RT for an observed RT."""
+        return rt + self(rt)
+
+
+def reference_rts(
+    results: Sequence[Sequence], min_fraction: float = 1.0
+) -> Dict[Tuple[str, float], float]:
+    """
+    Median apex RT of every ion with a peak found in enough files.
+
+    Every processed ion has an ``RT``, the maximum of its trace, but only
+    those with a detected, integrated peak (see peak_found) count: the RT
+    of a trace without one is that of noise.
+
+    Parameters
+    ----------
+    results : sequence of sequence of Compound
+        Processed compounds, one sequence per file.
+    min_fraction : float
+        Fraction of the files an ion's peak must be found in to become a
+        reference peak.
+
+    Returns
+    -------
+    dict
+        ``{(compound name, ion m/z): reference RT}``.
+    """
+    observed = {}
+    for compounds in results:
+        for compound in compounds:
+            for ion, data in compound.ions.items():
+                rt = data.get("RT")
+                if rt is not None and np.isfinite(rt) and peak_found(data):
+                    observed.setdefault((compound.name, ion), []).append(float(rt))
+    needed = max(1, int(np.ceil(min_fraction * len(results))))
+    return {key: float(np.median(rts)) for key, rts in observed.items() if len(rts) >= needed}
+
+
+def align_retention_times(
+    results: Sequence[Sequence],
+    method: str = "linear",
+    min_fraction: float = 1.0,
+    span: float = 0.75,
+) -> Dict[str, RTShift]:
+    """
+    Align the ion RTs of processed files to each other.
+
+    Sets ``Aligned RT`` on every ion of every compound in *results*.
+
+    Parameters
+    ----------
+    results : sequence of sequence of Compound
+        Processed compounds, one sequence per file (as returned by
+        construct_xics).
+    method : str
+        "linear" or "loess", see RTShift.
+    min_fraction : float
+        Passed on to reference_rts().
+    span : float
+        Fraction of the anchors in each local fit for "loess".
+
+    Returns
+    -------
+    dict
+        ``{file name: RTShift}``.
+    """
+    if method not in ALIGNMENT_METHODS:
+        raise ValueError(f"Unknown alignment method '{method}', expected one of {ALIGNMENT_METHODS}")
+    references = reference_rts(results, min_fraction)
+    shifts = {}
+    for compounds in results:
+        if not compounds:
+            continue
+        anchors = [
+            (data["RT"], references[(compound.name, ion)])
+            for compound in compounds
+            for ion, data in compound.ions.items()
+            if (compound.name, ion) in references
+            and data.get("RT") is not None
+            and np.isfinite(data["RT"])
+            and peak_found(data)
+        ]
+        shift = RTShift(method=method, anchors=anchors, span=span)
+        filename = compounds[0].file
+        if len(anchors) < 2:
+            logger.warning(
+                f"Only {len(anchors)} reference peaks in {filename}, RT alignment is a constant shift"
+            )
+        for compound in compounds:
+            for data in compound.ions.values():
+                rt = data.get("RT")
+                data["Aligned RT"] = shift.correct(rt) if rt is not None else None
+        shifts[filename] = shift
+        logger.info(f"Aligned {filename} on {len(anchors)} reference peaks ({method})")
+    return shifts
@@ -0,0 +1,129 @@
+"""
+Averaging of the spectra in a retention time range.
+
+A single scan is noisy and may miss low-abundance ions, so the averaged MS
+view sums all scans of a time range into one spectrum, divided by the number
+of scans. Two methods:
+
+- "bin": profile binning, every point goes into an m/z bin of
+  ``bin_width``; the bin's m/z is the intensity-weighted mean of its points.
+- "merge": centroid merging, the peaks of all scans are pooled and peaks
+  within ``tolerance_ppm`` of their neighbour merged into one centroid.
+"""
+
+import logging
+from typing import Tuple
+
+import numpy as np
+
+from utils.loading import iter_ms_scans
+
+logger = logging.getLogger(__name__)
+
+AVERAGING_METHODS = ("bin", "merge")
+
+
+def _scans(source, polarity, scan_filter):
+    """Scans of a file path or a calculation.session.LoadedRun."""
+    if isinstance(source, str) or hasattr(source, "__fspath__"):
+        return iter_ms_scans(str(source), polarity=polarity, scan_filter=scan_filter)
+    return source.scans(polarity=polarity, scan_filter=scan_filter)
+
+
+def _bin(mz: np.ndarray, intensity: np.ndarray, bin_width: float):
+    bins = np.floor(mz / bin_width).astype(np.int64)
+    unique, inverse = np.unique(bins, return_inverse=True)
+    summed = np.bincount(inverse, weights=intensity, minlength=len(unique))
+    weighted = np.bincount(inverse, weights=mz * intensity, minlength=len(unique))
+    centers = (unique + 0.5) * bin_width
+    with np.errstate(invalid="ignore", divide="ignore"):
+        bin_mz = np.where(summed > 0, weighted / summed, centers)
+    return bin_mz, summed
+
+
+def _merge(mz: np.ndarray, intensity: np.ndarray, tolerance_ppm: float):
+    order = np.argsort(mz, kind="stable")
+    mz, intensity = mz[order], intensity[order]
+    # A new centroid starts wherever the gap to the previous peak exceeds the tolerance
+    starts = np.concatenate(([0], np.flatnonzero(np.diff(mz) > mz[1:] * tolerance_ppm * 1e-6) + 1))
+    summed = np.add.reduceat(intensity, starts)
+    weighted = np.add.reduceat(mz * intensity, starts)
+    with np.errstate(invalid="ignore", divide="ignore"):
+        merged_mz = np.where(summed > 0, weighted / summed, mz[starts])
+    return merged_mz, summed
+
+
+def average_spectra(
+    source,
+    rt_min: float,
+    rt_max: float,
+    bin_width: float = 0.01,
+    method: str = "bin",
+    tolerance_ppm: float = 10.0,
+    ms_level: int = 1,
+    polarity: str = None,
+    scan_filter: dict = None,
+) -> Tuple[np.ndarray, np.ndarray, int]:
+    """
+    Average the spectra acquired between *rt_min* and *rt_max*.
+
+    Parameters
+    ----------
+    source : str, Path or LoadedRun
+        The MS file, or its scans held in memory.
+    rt_min, rt_max : float
+        Retention time range (min), inclusive.
+    bin_width : float
+        m/z bin width of the "bin" method.
+    method : str
+        One of AVERAGING_METHODS.
+    tolerance_ppm : float
+        Distance up to which the "merge" method combines peaks.
+    ms_level : int
+        Only average scans of this MS level.
+    polarity, scan_filter
+        Scan selection, see utils.loading.iter_ms_scans.
+
+    Returns
+    -------
+    Tuple[np.ndarray, np.ndarray, int]
+        ``(mz, intensity, n_scans)``: the ascending m/z and mean intensity
+        (summed intensity over the number of scans averaged), empty if no
+        scan falls in the range.
+
+    Raises
+    ------
+    ValueError
+        On an unknown method, a reversed range or a non-positive bin width
+        or tolerance.
+    """
+    if method not in AVERAGING_METHODS:
+        raise ValueError(f"Unknown averaging method '{method}', expected one of {AVERAGING_METHODS}")
+    if rt_min > rt_max:
+        raise ValueError(f"rt_min ({rt_min}) is greater than rt_max ({rt_max})")
+    if method == "bin" and bin_width <= 0:
+        raise ValueError(f"Bin width must be positive, got {bin_width}")
+    if method == "merge" and tolerance_ppm <= 0:
+        raise ValueError(f"Merge tolerance must be positive, got {tolerance_ppm}")
+
+    mz_parts, intensity_parts = [], []
+    for scan_time, _, level, mz, intensity in _scans(source, polarity, scan_filter):
+        if scan_time > rt_max:
+            break  # Scans are in acquisition order
+        if scan_time < rt_min or level != ms_level:
+            continue
+        mz_parts.append(np.asarray(mz, dtype=np.float64))
+        intensity_parts.append(np.asarray(intensity, dtype=np.float64))
+
+    n_scans = len(mz_parts)
+    empty = np.zeros(0, dtype=np.float64)
+    if n_scans == 0 or not sum(len(part) for part in mz_parts):
+        logger.info(f"No MS{ms_level} scans with peaks between {rt_min} and {rt_max} min")
+        return empty, empty, n_scans
+    mz = np.concatenate(mz_parts)
+    intensity = np.concatenate(intensity_parts)
+    if method == "bin":
+        mz, intensity = _bin(mz, intensity, bin_width)
+    else:
+        mz, intensity = _merge(mz, intensity, tolerance_ppm)
+    return mz, intensity / n_scans, n_scans
@@ -0,0 +1,150 @@
+"""
+Baseline estimation for chromatographic traces.
+
+Gradient LC runs give an XIC baseline that drifts with the solvent
+composition; subtracting it before integration keeps that drift out of the
+peak areas. Settings are plain dicts, like calculation.smoothing:
+
+    {"method": "als", "lam": 1e5, "p": 0.01}
+    {"method": "rolling_min", "window": 51}
+"""
+
+import logging
+
+import numpy as np
+from scipy import sparse
+from scipy.ndimage import minimum_filter1d, uniform_filter1d
+from scipy.sparse.linalg import spsolve
+
+logger = logging.getLogger(__name__)
+
+BASELINE_METHODS = ("none", "als", "rolling_min")
+
+_DEFAULTS = {
+    "als": {"lam": 1e5, "p": 0.01, "n_iter": 10},
+    "rolling_min": {"window": 51},
+    "none": {},
+}
+
+
+def validate_baseline(settings: dict) -> dict:
+    """
+    Fill in defaults and validate a baseline settings dict.
+
+    Parameters
+    ----------
+    settings : dict
+        ``method`` (one of BASELINE_METHODS) plus its parameters:
+        ``lam`` (smoothness, > 0), ``p`` (asymmetry, 0 < p < 1) and
+        ``n_iter`` for "als"; ``window`` (points, >= 3) for "rolling_min".
+
+    Returns
+    -------
+    dict
+        A new dict with every parameter of the chosen method present.
+
+    Raises
+    ------
+    ValueError
+        If the method is unknown or a parameter is out of range.
+    """
+    settings = dict(settings or {})
+    method = settings.pop("method", "als")
+    if method not in BASELINE_METHODS:
+        raise ValueError(
+            f"Unknown baseline method '{method}', expected one of {BASELINE_METHODS}"
+        )
+    merged = {"method": method, **_DEFAULTS[method], **settings}
+
+    if method == "als":
+        if merged["lam"] <= 0:
+            raise ValueError(f"ALS smoothness lam must be positive, got {merged['lam']}")
+        if not 0 < merged["p"] < 1:
+            raise ValueError(f"ALS asymmetry p must be in (0, 1), got {merged['p']}")
+        merged["n_iter"] = int(merged["n_iter"])
+    elif method == "rolling_min":
+        merged["window"] = int(merged["window"])
+        if merged["window"] < 3:
+            raise ValueError(f"Rolling window must be >= 3, got {merged['window']}")
+    return merged
+
+
+def als_baseline(
+    intensities: np.ndarray, lam: float = 1e5, p: float = 0.01, n_iter: int = 10
+) -> np.ndarray:
+    """
+    Asymmetric least squares baseline (Eilers & Boelens, 2005).
+
+    Parameters
+    ----------
+    intensities : np.ndarray
+        Intensity trace.
+    lam : float
+        Smoothness penalty; larger values give a stiffer baseline.
+    p : float
+        Weight of points above the baseline (peaks); small values keep the
+        baseline under the peaks.
+    n_iter : int
+        Number of reweighting iterations.
+
+    Returns
+    -------
+    np.ndarray
+        Baseline, same length as the input (float64).
+    """
+    y = np.asarray(intensities, dtype=np.float64)
+    n = len(y)
+    if n < 3:
+        return y.copy()
+
+    diff = sparse.diags([1.0, -2.0, 1.0], [0, 1, 2], shape=(n - 2, n))
+    penalty = lam * (diff.T @ diff)
+    weights = np.ones(n)
+    baseline = y
+    for _ in range(n_iter):
+        w = sparse.diags(weights, 0)
+        baseline = spsolve((w + penalty).tocsc(), weights * y)
+        weights = np.where(y > baseline, p, 1 - p)
+    return baseline
+
+
+def rolling_min_baseline(intensities: np.ndarray, window: int = 51) -> np.ndarray:
+    """
+    Rolling-minimum baseline, smoothed with a moving average of the same width.
+
+    Parameters
+    ----------
+    intensities : np.ndarray
+        Intensity trace.
+    window : int
+        Window width in points; should be wider than the widest peak.
+
+    Returns
+    -------
+    np.ndarray
+        Baseline, same length as the input (float64).
+    """
+    y = np.asarray(intensities, dtype=np.float64)
+    if len(y) == 0:
+        return y.copy()
+    window = min(window, len(y))
+    minima = minimum_filter1d(y, size=window, mode="nearest")
+    return np.minimum(uniform_filter1d(minima, size=window, mode="nearest"), y)
+
+
+def estimate_baseline(intensities: np.ndarray, method: str = "als", **params) -> np.ndarray:
+    """Estimate a baseline with the given method and parameters (see validate_baseline)."""
+    settings = validate_baseline({"method": method, **params})
+    settings.pop("method")
+    if method == "als":
+        return als_baseline(intensities, **settings)
+    if method == "rolling_min":
+        return rolling_min_baseline(intensities, **settings)
+    return np.zeros(len(intensities), dtype=np.float64)
+
+
+def subtract_baseline(intensities: np.ndarray, baseline: np.ndarray) -> np.ndarray:
+    """Subtract a baseline, clipping at zero and keeping the input dtype."""
+    intensities = np.asarray(intensities)
+    corrected = np.clip(intensities - baseline, 0, None)
+    return corrected.astype(intensities.dtype, copy=False)
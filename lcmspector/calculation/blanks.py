@@ -0,0 +1,106 @@
+"""
+Blank subtraction and background filtering.
+
+Carryover and solvent contaminants show up in blank injections as well as
+in the samples. The mean peak area of every ion over the blank files is
+compared with the samples: ions whose sample/blank ratio stays below a
+threshold are flagged, and in "subtract" mode the blank area is also taken
+off the sample's baseline corrected peak area (``Integration Data``).
+
+Results are stored per ion under ``Blank``:
+
+    {"area": 1.2e4, "ratio": 2.1, "below_threshold": True}
+"""
+
+import logging
+from typing import Collection, Sequence
+
+import numpy as np
+
+logger = logging.getLogger(__name__)
+
+BLANK_MODES = ("flag", "subtract")
+DEFAULT_BLANK_RATIO = 3.0
+
+
+def _peak_area(data: dict) -> float:
+    return (data.get("Integration Data") or {}).get("baseline_corrected_area", 0) or 0
+
+
+def blank_areas(blank_results: Sequence[Sequence]) -> dict:
+    """
+    Mean baseline corrected peak area of every ion over the blank files.
+
+    Returns
+    -------
+    dict
+        ``{(compound name, ion m/z): mean area}``; ions without a peak in a
+        blank count as zero there.
+    """
+    areas = {}
+    for compounds in blank_results:
+        for compound in compounds:
+            for ion, data in compound.ions.items():
+                areas.setdefault((compound.name, ion), []).append(_peak_area(data))
+    return {key: float(np.mean(values)) for key, values in areas.items()}
+
+
+def apply_blank_correction(
+    results: Sequence[Sequence],
+    blank_files: Collection[str],
+    mode: str = "flag",
+    ratio_threshold: float = DEFAULT_BLANK_RATIO,
+) -> int:
+    """
+    Compare the samples in *results* with their blanks.
+
+    Parameters
+    ----------
+    results : sequence of sequence of Compound
+        Processed compounds, one sequence per file, blanks included.
+    blank_files : collection of str
+        ``Compound.file`` names of the blank files.
+    mode : str
+        "flag" only annotates the ions; "subtract" also takes the mean blank
+        area off the sample's ``baseline_corrected_area`` (clipped at zero,
+        the original is kept as ``blank_uncorrected_area``).
+    ratio_threshold : float
+        Sample/blank area ratio below which an ion is flagged.
+
+    Returns
+    -------
+    int
+        Number of flagged sample ions.
+    """
+    if mode not in BLANK_MODES:
+        raise ValueError(f"Unknown blank mode '{mode}', expected one of {BLANK_MODES}")
+    blank_files = set(blank_files)
+    blanks = [compounds for compounds in results if compounds and compounds[0].file in blank_files]
+    samples = [compounds for compounds in results if compounds and compounds[0].file not in blank_files]
+    if not blanks:
+        logger.warning("No blank files among the results, skipping blank correction")
+        return 0
+
+    reference = blank_areas(blanks)
+    flagged = 0
+    for compounds in samples:
+        for compound in compounds:
+            for ion, data in compound.ions.items():
+                blank_area = reference.get((compound.name, ion), 0.0)
+                area = _peak_area(data)
+                ratio = area / blank_area if blank_area > 0 else float("inf")
+                below = ratio < ratio_threshold
+                flagged += below
+                data["Blank"] = {"area": blank_area, "ratio": ratio, "below_threshold": below}
+                if mode == "subtract" and blank_area > 0 and data.get("Integration Data"):
+                    integration = data["Integration Data"]
+                    integration["blank_uncorrected_area"] = area
+                    integration["baseline_corrected_area"] = max(area - blank_area, 0.0)
+                    if data.get("MS Peak Area"):
+                        # Keep the export copy of a manual re-integration in sync
+                        data["MS Peak Area"] = integration.copy()
+    logger.info(
+        f"Blank correction ({mode}) against {len(blanks)} blanks: "
+        f"{flagged} ions below a sample/blank ratio of {ratio_threshold}"
+    )
+    return flagged
@@ -1,4 +1,5 @@
 import numpy as np
+from calculation.calibration import back_calculate
 
 
 def calculate_concentration(area, curve_params):
@@ -7,7 +8,8 @@ def calculate_concentration(area, curve_params):
 
     Args:
         area (float): Peak area value from chromatogram integration
-        curve_params (dict): Calibration curve parameters containing 'slope' and 'intercept'
+        curve_params (dict): Calibration curve parameters containing 'slope' and 'intercept',
+            plus 'quadratic' for quadratic fits (see calculation.calibration)
 
     Returns:
         float: Calculated concentration rounded to 6 decimal places, or 0 for invalid results
@@ -21,12 +23,14 @@ def calculate_concentration(area, curve_params):
     slope = curve_params["slope"]
     intercept = curve_params["intercept"]
 
+    if curve_params.get("quadratic"):
+        concentration = back_calculate(area, curve_params)
     # Check for zero slope to prevent division by zero
-    if slope == 0:
+    elif slope == 0:
         return 0
-
-    # Calculate concentration
-    concentration = (area - intercept) / slope
+    else:
+        # Calculate concentration
+        concentration = (area - intercept) / slope
 
     # Handle NaN and infinity
     if np.isnan(concentration) or not np.isfinite(concentration):
@@ -0,0 +1,221 @@
+"""
+Calibration curve fitting for quantitation.
+
+Curves are fitted by (weighted) least squares as ``signal = f(concentration)``:
+
+- "linear": ``slope * x + intercept``
+- "quadratic": ``quadratic * x**2 + slope * x + intercept``
+
+Weighting by 1/x or 1/x² keeps the low end of wide calibration ranges from
+being swamped by the high standards, as usual for LC-MS assays.
+
+Limits of detection and quantification follow the ICH Q2 definitions,
+``3.3 * sigma / slope`` and ``10 * sigma / slope``, where sigma is the SD of
+blank replicates if there are enough, otherwise the residual SD of the fit.
+
+Analytes with an internal standard are calibrated on their analyte/IS signal
+ratio instead of the raw signal, see internal_standard_ratio().
+"""
+
+import logging
+from typing import Sequence
+
+import numpy as np
+
+logger = logging.getLogger(__name__)
+
+CALIBRATION_MODELS = ("linear", "quadratic")
+CALIBRATION_WEIGHTINGS = ("none", "1/x", "1/x2")
+LOD_FACTOR = 3.3
+LOQ_FACTOR = 10.0
+
+
+def _weights(x: np.ndarray, weighting: str) -> np.ndarray:
+    """Point weights for a weighting scheme.
+
+    Standards at zero concentration (blanks) get the weight of the lowest
+    non-zero standard instead of an infinite one.
+    """
+    if weighting == "none":
+        return np.ones_like(x)
+    positive = x[x > 0]
+    if len(positive) == 0:
+        raise ValueError(f"Weighting '{weighting}' needs at least one non-zero concentration")
+    safe_x = np.where(x > 0, x, positive.min())
+    return 1.0 / safe_x if weighting == "1/x" else 1.0 / safe_x**2
+
+
+def back_calculate(signal, params: dict):
+    """
+    Invert a fitted curve: concentration for a signal (no clipping).
+
+    For quadratic curves the root closest to the linear solution is used.
+
+    Parameters
+    ----------
+    signal : float or np.ndarray
+        Measured signal(s).
+    params : dict
+        Curve parameters with ``slope``, ``intercept`` and optionally
+        ``quadratic``.
+
+    Returns
+    -------
+    float or np.ndarray
+        Concentration(s); NaN where the curve cannot be inverted.
+    """
+    signal = np.asarray(signal, dtype=np.float64)
+    a = params.get("quadratic", 0.0) or 0.0
+    b = params["slope"]
+    c = params["intercept"] - signal
+    with np.errstate(divide="ignore", invalid="ignore"):
+        if a == 0:
+            result = -c / b if b != 0 else np.full_like(signal, np.nan)
+        else:
+            disc = b**2 - 4 * a * c
+            root = np.sqrt(np.where(disc >= 0, disc, np.nan))
+            plus = (-b + root) / (2 * a)
+            minus = (-b - root) / (2 * a)
+            # The root continuous with the linear solution -c/b
+            linear = -c / b if b != 0 else plus
+            result = np.where(np.abs(plus - linear) <= np.abs(minus - linear), plus, minus)
+    return float(result) if result.ndim == 0 else result
+
+
+def fit_calibration(
+    concentrations: Sequence[float],
+    signals: Sequence[float],
+    model: str = "linear",
+    weighting: str = "none",
+) -> dict:
+    """
+    Fit a calibration curve to standards.
+
+    Parameters
+    ----------
+    concentrations : sequence of float
+        Known concentrations of the standards.
+    signals : sequence of float
+        Measured signal (peak area or intensity sum) per standard.
+    model : str
+        One of CALIBRATION_MODELS.
+    weighting : str
+        One of CALIBRATION_WEIGHTINGS.
+
+    Returns
+    -------
+    dict
+        ``model``, ``weighting``, ``slope``, ``intercept``, ``quadratic``
+        (0.0 for linear fits), ``r_squared`` (weighted), ``r_value``,
+        ``std_err`` (standard error of the slope, linear fits only),
+        ``residual_sd`` (unweighted, None without spare degrees of freedom),
+        ``residuals`` (signal - fit) and ``back_calculated`` (concentration
+        of every standard read back from the curve), the latter two as
+        lists in input order.
+
+    Raises
+    ------
+    ValueError
+        On an unknown model or weighting, or too few standards (2 for
+        linear, 3 for quadratic fits).
+    """
+    if model not in CALIBRATION_MODELS:
+        raise ValueError(f"Unknown calibration model '{model}', expected one of {CALIBRATION_MODELS}")
+    if weighting not in CALIBRATION_WEIGHTINGS:
+        raise ValueError(
+            f"Unknown calibration weighting '{weighting}', expected one of {CALIBRATION_WEIGHTINGS}"
+        )
+    x = np.asarray(concentrations, dtype=np.float64)
+    y = np.asarray(signals, dtype=np.float64)
+    degree = 1 if model == "linear" else 2
+    if len(x) != len(y):
+        raise ValueError(f"{len(x)} concentrations for {len(y)} signals")
+    if len(np.unique(x)) < degree + 1:
+        raise ValueError(
+            f"A {model} calibration needs at least {degree + 1} distinct concentrations"
+        )
+
+    w = _weights(x, weighting)
+    # polyfit weights multiply the residuals, so pass sqrt of the variance weights
+    coefficients = np.polyfit(x, y, degree, w=np.sqrt(w))
+    fitted = np.polyval(coefficients, x)
+    residuals = y - fitted
+
+    y_mean = np.sum(w * y) / np.sum(w)
+    ss_tot = np.sum(w * (y - y_mean) ** 2)
+    ss_res = np.sum(w * residuals**2)
+    r_squared = 1.0 - ss_res / ss_tot if ss_tot > 0 else 0.0
+
+    params = {
+        "model": model,
+        "weighting": weighting,
+        "quadratic": float(coefficients[0]) if degree == 2 else 0.0,
+        "slope": float(coefficients[-2]),
+        "intercept": float(coefficients[-1]),
+        "r_squared": float(r_squared),
+        "r_value": float(np.sign(coefficients[-2]) * np.sqrt(max(r_squared, 0.0))),
+        "std_err": None,
+        "residual_sd": None,
+    }
+    dof = len(x) - (degree + 1)
+    if dof > 0:
+        params["residual_sd"] = float(np.sqrt(np.sum(residuals**2) / dof))
+    if degree == 1 and len(x) > 2:
+        x_mean = np.sum(w * x) / np.sum(w)
+        sxx = np.sum(w * (x - x_mean) ** 2)
+        params["std_err"] = float(np.sqrt(ss_res / (len(x) - 2) / sxx)) if sxx > 0 else None
+
+    params["residuals"] = residuals.tolist()
+    params["back_calculated"] = np.atleast_1d(back_calculate(y, params)).tolist()
+    return params
+
+
+def detection_limits(params: dict, blank_signals: Sequence[float] = None) -> dict:
+    """
+    Limits of detection and quantification for a fitted curve.
+
+    Parameters
+    ----------
+    params : dict
+        Output of fit_calibration().
+    blank_signals : sequence of float, optional
+        Signals of blank replicates. With at least two, their SD is used as
+        sigma; otherwise the residual SD of the fit.
+
+    Returns
+    -------
+    dict
+        ``lod`` and ``loq`` in concentration units (None if sigma or the
+        slope is unavailable) and ``lod_method`` ("blank" or "residual").
+    """
+    blanks = np.asarray(blank_signals if blank_signals is not None else [], dtype=np.float64)
+    if len(blanks) >= 2:
+        sigma, method = float(np.std(blanks, ddof=1)), "blank"
+    else:
+        sigma, method = params.get("residual_sd"), "residual"
+
+    # Sensitivity at the low end of the curve, i.e. the linear term for quadratics
+    slope = abs(params.get("slope") or 0.0)
+    if sigma is None or slope == 0:
+        logger.warning(f"Cannot estimate LOD/LOQ from the {method} SD")
+        return {"lod": None, "loq": None, "lod_method": method}
+    return {
+        "lod": LOD_FACTOR * sigma / slope,
+        "loq": LOQ_FACTOR * sigma / slope,
+        "lod_method": method,
+    }
+
+
+def internal_standard_ratio(signal: float, is_signal: float):
+    """
+    Analyte signal normalized to its internal standard.
+
+    Returns
+    -------
+    float or None
+        ``signal / is_signal``, or None if the internal standard signal is
+        missing, zero or not finite (the ratio would be meaningless).
+    """
+    if is_signal is None or not np.isfinite(is_signal) or is_signal <= 0:
+        return None
+    return float(signal) / float(is_signal)
@@ -0,0 +1,157 @@
+"""
+Charge states from isotope spacing.
+
+The 13C isotopologues of an ion with charge z lie ``ISOTOPE_SPACING / z``
+apart in m/z, so the spacing of the peaks above the monoisotopic one gives
+away its charge. Every charge up to ``max_charge`` is tried and the one
+explaining the longest run of consecutive isotope peaks wins (the higher
+charge on ties, as in calculation.deconvolution.deisotope).
+
+For targeted results the apex spectrum of every integrated ion is checked
+(by construct_xics with *charge_states*, on the processed spectrum, see
+calculation.preprocessing.peak_spectra) and the ion's ``Charge State``
+dict set:
+
+    {"charge": 2, "n_isotopes": 3, "expected": 1, "mismatch": True}
+
+where ``expected`` is the charge of the ion's adduct label. A mismatch means
+the XIC and its isotopologues were extracted at the wrong m/z spacing, e.g.
+a doubly charged peptide listed as [M+H]+. Untargeted features get their
+``charge`` in calculation.features.detect_features.
+"""
+
+import logging
+from typing import Optional, Tuple
+
+import numpy as np
+
+from calculation.isotopes import ISOTOPE_SPACING
+from calculation.mass_error import observed_mz
+
+logger = logging.getLogger(__name__)
+
+DEFAULT_MAX_CHARGE = 6
+# Isotope peaks looked for above the monoisotopic one
+MAX_ISOTOPES = 5
+
+
+def _has_peak(mz: np.ndarray, intensity: np.ndarray, target: float, ppm: float) -> bool:
+    lo = np.searchsorted(mz, target * (1 - ppm * 1e-6), side="left")
+    hi = np.searchsorted(mz, target * (1 + ppm * 1e-6), side="right")
+    return bool(np.any(intensity[lo:hi] > 0))
+
+
+def charge_from_isotopes(
+    mz_array: np.ndarray,
+    intensity_array: np.ndarray,
+    monoisotopic_mz: float,
+    max_charge: int = DEFAULT_MAX_CHARGE,
+    ppm: float = 10.0,
+) -> Tuple[Optional[int], int]:
+    """
+    Charge of the ion at *monoisotopic_mz* from the isotope peaks above it.
+
+    Parameters
+    ----------
+    mz_array, intensity_array : np.ndarray
+        A centroided spectrum, peaks in any order.
+    monoisotopic_mz : float
+        m/z of the monoisotopic peak, as observed.
+    max_charge : int
+        Highest charge tried.
+    ppm : float
+        Tolerance on the isotope peak positions.
+
+    Returns
+    -------
+    Tuple[Optional[int], int]
+        ``(charge, n_isotopes)``: the charge and the number of consecutive
+        isotope peaks found at its spacing; ``(None, 0)`` without any.
+
+    Raises
+    ------
+    ValueError
+        If *max_charge* is below 1.
+    """
+    if max_charge < 1:
+        raise ValueError(f"Maximum charge must be at least 1, got {max_charge}")
+    mz = np.asarray(mz_array, dtype=np.float64)
+    intensity = np.asarray(intensity_array, dtype=np.float64)
+    order = np.argsort(mz, kind="stable")
+    mz, intensity = mz[order], intensity[order]
+
+    best_charge, best_count = None, 0
+    for charge in range(1, max_charge + 1):
+        count = 0
+        while count < MAX_ISOTOPES and _has_peak(
+            mz, intensity, monoisotopic_mz + (count + 1) * ISOTOPE_SPACING / charge, ppm
+        ):
+            count += 1
+        if count > 0 and count >= best_count:
+            best_charge, best_count = charge, count
+    return best_charge, best_count
+
+
+def ion_charge_state(
+    reader,
+    ion: float,
+    data: dict,
+    expected: int = 1,
+    mass_accuracy: float = 0.0001,
+    max_charge: int = DEFAULT_MAX_CHARGE,
+    ppm: float = 10.0,
+    window=None,
+) -> Optional[dict]:
+    """
+    ``Charge State`` of an ion at its apex, see the module docstring.
+
+    *reader* is an indexed reader with ``.time[rt]`` access, see
+    utils.loading.load_ms_data, or the spectra of
+    calculation.preprocessing.peak_spectra. The monoisotopic peak is the
+    most intense one in the ion's m/z window (``+-3 * mass_accuracy * mz``,
+    or its custom ``(lower, upper)`` *window*). Returns None if the ion has
+    no integrated peak or no peak in its window at the apex.
+    """
+    rt = data.get("RT")
+    if not data.get("Integration Data") or rt is None:
+        return None
+    try:
+        spectrum = reader.time[float(rt)]
+    except Exception as e:
+        logger.debug(f"Cannot read the apex spectrum of m/z {ion} at {rt}: {e}")
+        return None
+    mono = observed_mz(spectrum, ion, 3 * mass_accuracy, window)
+    if mono is None:
+        return None
+    charge, n_isotopes = charge_from_isotopes(
+        spectrum["m/z array"], spectrum["intensity array"], mono, max_charge, ppm
+    )
+    mismatch = charge is not None and charge != expected
+    if mismatch:
+        logger.warning(
+            f"Ion m/z {ion} looks {charge}+ from its isotope spacing, listed as {expected}+"
+        )
+    return {"charge": charge, "n_isotopes": n_isotopes, "expected": expected, "mismatch": mismatch}
+
+
+def peak_charge_states(
+    compounds, reader, mass_accuracy: float = 0.0001, custom_ranges: dict = None, **kwargs
+) -> int:
+    """
+    Set ``Charge State`` on every ion of a processed file; returns the number found.
+
+    *custom_ranges* are the ``{mz: (lower, upper)}`` m/z windows of the ions
+    with their own, see calculation.preprocessing.compound_mz_ranges.
+    """
+    from calculation.preprocessing import _ion_charge
+
+    custom_ranges = custom_ranges or {}
+    found = 0
+    for compound in compounds:
+        for index, (ion, data) in enumerate(compound.ions.items()):
+            data["Charge State"] = ion_charge_state(
+                reader, ion, data, _ion_charge(compound, index), mass_accuracy,
+                window=custom_ranges.get(ion), **kwargs,
+            )
+            found += (data["Charge State"] or {}).get("charge") is not None
+    return found
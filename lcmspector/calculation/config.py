@@ -0,0 +1,171 @@
+"""
+Processing settings in one validated object.
+
+ProcessingConfig holds every option of a processing run, with the defaults
+of ProcessingWorker, and checks them with the validators of the modules
+that use them, so a typo fails before any file is read rather than half way
+through a batch. It can be built from a dict or a TOML file:
+
+    config = ProcessingConfig.from_toml("method.toml")
+    ProcessingWorker(model, "MS Only", config=config)
+    construct_xics("sample.mzML", compounds, config={"mass_accuracy": 5e-6})
+
+with a TOML file such as
+
+    mass_accuracy = 0.000005
+    xic_mode = "max"
+    smoothing = { method = "savgol", window = 7 }
+
+TOML has no null, options left out keep their default. to_dict() gives the
+effective settings, defaults filled in, as stored with the results in the
+model's ``processing_config``.
+"""
+
+import logging
+import tomllib
+from pathlib import Path
+from typing import Any, Dict, List, Mapping, Optional, Union
+
+from pydantic import BaseModel, ConfigDict, Field, model_validator
+
+from calculation.alignment import ALIGNMENT_METHODS
+from calculation.baseline import validate_baseline
+from calculation.blanks import BLANK_MODES, DEFAULT_BLANK_RATIO
+from calculation.centroiding import CENTROID_METHODS
+from calculation.deconvolution import validate_deconvolution
+from calculation.drift import DRIFT_CORRECTION_METHODS
+from calculation.noise import validate_noise_threshold
+from calculation.peak_fitting import PEAK_MODELS
+from calculation.preprocessing import precision_dtype, validate_file_range, validate_xic_mode
+from calculation.qc import validate_qc_thresholds
+from calculation.recalibration import validate_lock_mass
+from calculation.smoothing import validate_smoothing
+from utils.memory import parse_memory_size
+from utils.mzml_reader import validate_polarity
+from utils.scan_filter import validate_scan_filter
+
+logger = logging.getLogger(__name__)
+
+# ProcessingConfig options passed on to calculation.preprocessing.construct_xics
+XIC_OPTIONS = (
+    "mass_accuracy", "smoothing", "baseline", "centroiding", "link_ms2", "isotopes", "polarity",
+    "scan_filter", "deconvolution", "peak_fitting", "lock_mass", "scan_cache", "precision",
+    "noise_threshold", "xic_mode", "peak_purity", "mass_errors", "charge_states",
+)
+
+
+def _check_choice(kind: str, value, choices):
+    if value is not None and value not in choices:
+        raise ValueError(f"Unknown {kind} '{value}', expected one of {choices}")
+
+
+class ProcessingConfig(BaseModel):
+    """
+    Options of a processing run, see ProcessingWorker for what each does.
+    Unknown options raise, as do invalid values.
+    """
+
+    model_config = ConfigDict(extra="forbid")
+
+    # XIC extraction, see calculation.preprocessing.construct_xics
+    mass_accuracy: float = Field(default=0.0001, gt=0, description="Relative m/z tolerance")
+    smoothing: Optional[Dict[str, Any]] = None
+    baseline: Optional[Dict[str, Any]] = None
+    centroiding: Optional[str] = None
+    link_ms2: bool = False
+    isotopes: int = Field(default=0, ge=0, description="Isotopologues extracted per ion")
+    polarity: Optional[str] = None
+    scan_filter: Optional[Dict[str, Any]] = None
+    deconvolution: Optional[Dict[str, Any]] = None
+    peak_fitting: Optional[str] = None
+    lock_mass: Optional[Dict[str, Any]] = None
+    scan_cache: bool = False
+    precision: str = "float32"
+    noise_threshold: Optional[Dict[str, Any]] = None
+    xic_mode: str = "sum"
+    file_ranges: Dict[str, Dict[str, Any]] = Field(default_factory=dict)
+    # Per-file checks; all but the QC are computed in construct_xics
+    qc: Union[bool, Dict[str, float]] = False
+    peak_purity: bool = False
+    mass_errors: bool = False
+    charge_states: bool = False
+    # Across the files of a batch
+    rt_alignment: Optional[str] = None
+    gap_filling: bool = False
+    blank_mode: Optional[str] = None
+    blank_ratio: float = Field(default=DEFAULT_BLANK_RATIO, gt=0)
+    injection_order: Optional[List[str]] = None
+    drift_correction: Optional[str] = None
+    # Process pool
+    n_workers: Optional[int] = Field(default=None, ge=1)
+    low_priority: bool = False
+    memory_budget: Optional[Union[int, str]] = None
+
+    @model_validator(mode="after")
+    def _validate_options(self):
+        if self.smoothing is not None:
+            self.smoothing = validate_smoothing(self.smoothing)
+        if self.baseline is not None:
+            self.baseline = validate_baseline(self.baseline)
+        if self.deconvolution is not None:
+            self.deconvolution = validate_deconvolution(self.deconvolution)
+        self.lock_mass = validate_lock_mass(self.lock_mass)
+        self.noise_threshold = validate_noise_threshold(self.noise_threshold)
+        self.scan_filter = validate_scan_filter(self.scan_filter)
+        validate_polarity(self.polarity)
+        validate_xic_mode(self.xic_mode)
+        precision_dtype(self.precision)
+        _check_choice("centroiding method", self.centroiding, CENTROID_METHODS)
+        _check_choice("peak model", self.peak_fitting, PEAK_MODELS)
+        _check_choice("RT alignment method", self.rt_alignment, ALIGNMENT_METHODS)
+        _check_choice("blank mode", self.blank_mode, BLANK_MODES)
+        _check_choice("drift correction method", self.drift_correction, DRIFT_CORRECTION_METHODS)
+        for filename, ranges in self.file_ranges.items():
+            unknown = set(ranges) - {"rt_range", "scan_range"}
+            if unknown:
+                raise ValueError(
+                    f"Unknown range {sorted(unknown)} of {filename}, expected rt_range/scan_range"
+                )
+            validate_file_range(**ranges)
+        if isinstance(self.qc, dict):
+            self.qc = validate_qc_thresholds(self.qc)
+        if self.memory_budget is not None:
+            parse_memory_size(self.memory_budget)
+        return self
+
+    @classmethod
+    def from_dict(cls, options: Optional[Mapping] = None) -> "ProcessingConfig":
+        """A config of the *options* given, defaults for the others."""
+        return cls.model_validate(dict(options or {}))
+
+    @classmethod
+    def from_toml(cls, path) -> "ProcessingConfig":
+        """A config read from a TOML file of options, see the module docstring."""
+        with open(path, "rb") as f:
+            options = tomllib.load(f)
+        logger.debug(f"Processing options {sorted(options)} read from {Path(path).name}")
+        return cls.from_dict(options)
+
+    @classmethod
+    def coerce(cls, config) -> "ProcessingConfig":
+        """*config* as a ProcessingConfig: a config, a dict of options or a TOML path."""
+        if isinstance(config, cls):
+            return config
+        if isinstance(config, (str, Path)):
+            return cls.from_toml(config)
+        return cls.from_dict(config)
+
+    def to_dict(self) -> dict:
+        """The effective options, defaults and validated values filled in."""
+        return self.model_dump()
+
+    def xic_options(self) -> dict:
+        """
+        The construct_xics keyword arguments of this config, see XIC_OPTIONS.
+
+        The QC takes the median mass error from the ions' mass errors, so
+        they are computed with *qc* on as well.
+        """
+        options = {name: getattr(self, name) for name in XIC_OPTIONS}
+        options["mass_errors"] = self.mass_errors or bool(self.qc)
+        return options
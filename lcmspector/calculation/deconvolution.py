@@ -0,0 +1,183 @@
+"""
+Deisotoping and charge-state deconvolution of MS1 spectra.
+
+Peptides and oligomers spread over several charge states, each with its own
+isotope envelope, so their signal is split between many m/z values. Peaks
+are first grouped into envelopes spaced ``ISOTOPE_SPACING / z`` apart, which
+fixes their charge, then envelopes of the same neutral mass are merged.
+
+Settings are plain dicts like the smoothing ones:
+
+    {"ppm": 10.0, "max_charge": 6, "min_isotopes": 2}
+"""
+
+import logging
+from typing import List, Tuple
+
+import numpy as np
+
+from calculation.isotopes import ISOTOPE_SPACING
+
+logger = logging.getLogger(__name__)
+
+PROTON_MASS = 1.007276467
+
+DEFAULT_DECONVOLUTION = {"ppm": 10.0, "max_charge": 6, "min_isotopes": 2}
+
+
+def validate_deconvolution(settings: dict) -> dict:
+    """
+    Fill in defaults and validate a deconvolution settings dict.
+
+    Parameters
+    ----------
+    settings : dict
+        Keys ``ppm`` (peak matching tolerance, > 0), ``max_charge`` (>= 1)
+        and ``min_isotopes`` (peaks an envelope needs to count as charged,
+        >= 2; shorter ones are taken as singly charged).
+
+    Returns
+    -------
+    dict
+        A new dict with every key present.
+
+    Raises
+    ------
+    ValueError
+        If a value is out of range.
+    """
+    merged = {**DEFAULT_DECONVOLUTION, **(settings or {})}
+    ppm = float(merged["ppm"])
+    max_charge = int(merged["max_charge"])
+    min_isotopes = int(merged["min_isotopes"])
+    if ppm <= 0:
+        raise ValueError(f"Deconvolution tolerance must be positive, got {ppm} ppm")
+    if max_charge < 1:
+        raise ValueError(f"Maximum charge must be at least 1, got {max_charge}")
+    if min_isotopes < 2:
+        raise ValueError(f"An isotope envelope needs at least 2 peaks, got {min_isotopes}")
+    return {"ppm": ppm, "max_charge": max_charge, "min_isotopes": min_isotopes}
+
+
+def _find_peak(mz: np.ndarray, target: float, ppm: float, used: np.ndarray):
+    """Index of the unused peak closest to *target* within *ppm*, or None."""
+    tolerance = target * ppm * 1e-6
+    lo = np.searchsorted(mz, target - tolerance, side="left")
+    hi = np.searchsorted(mz, target + tolerance, side="right")
+    candidates = [i for i in range(lo, hi) if not used[i]]
+    if not candidates:
+        return None
+    return min(candidates, key=lambda i: abs(mz[i] - target))
+
+
+def _envelope(mz: np.ndarray, start: int, charge: int, ppm: float, used: np.ndarray) -> List[int]:
+    """Peak indices of the isotope envelope through *start* at *charge*, ascending m/z."""
+    step = ISOTOPE_SPACING / charge
+    taken = used.copy()
+    taken[start] = True
+    chain = [start]
+    for direction in (-1, 1):
+        current = start
+        while True:
+            nxt = _find_peak(mz, mz[current] + direction * step, ppm, taken)
+            if nxt is None:
+                break
+            taken[nxt] = True
+            chain.append(nxt)
+            current = nxt
+    return sorted(chain, key=lambda i: mz[i])
+
+
+def deisotope(
+    mz_array: np.ndarray,
+    intensity_array: np.ndarray,
+    ppm: float = 10.0,
+    max_charge: int = 6,
+    min_isotopes: int = 2,
+) -> List[Tuple[float, int, float]]:
+    """
+    Group the peaks of a centroided spectrum into isotope envelopes.
+
+    Starting from the most intense peak, every charge up to *max_charge* is
+    tried and the one giving the longest envelope wins (the higher charge on
+    ties). Peaks without an envelope of *min_isotopes* are taken as singly
+    charged.
+
+    Returns
+    -------
+    list of (float, int, float)
+        ``(monoisotopic m/z, charge, summed intensity)`` per envelope, i.e.
+        the lowest m/z of the envelope.
+    """
+    mz = np.asarray(mz_array, dtype=np.float64)
+    intensity = np.asarray(intensity_array, dtype=np.float64)
+    order = np.argsort(mz, kind="stable")
+    mz, intensity = mz[order], intensity[order]
+    used = np.zeros(len(mz), dtype=bool)
+
+    envelopes = []
+    for peak in np.argsort(intensity)[::-1]:
+        if used[peak] or intensity[peak] <= 0:
+            continue
+        best_chain, best_charge = [peak], 1
+        for charge in range(1, max_charge + 1):
+            chain = _envelope(mz, peak, charge, ppm, used)
+            if len(chain) >= min_isotopes and len(chain) >= len(best_chain):
+                best_chain, best_charge = chain, charge
+        used[best_chain] = True
+        envelopes.append((float(mz[best_chain[0]]), best_charge, float(intensity[best_chain].sum())))
+    return envelopes
+
+
+def deconvolute_spectrum(
+    mz_array: np.ndarray,
+    intensity_array: np.ndarray,
+    ppm: float = 10.0,
+    max_charge: int = 6,
+    min_isotopes: int = 2,
+    polarity: str = "positive",
+) -> Tuple[np.ndarray, np.ndarray]:
+    """
+    Collapse isotope envelopes and charge states to neutral masses.
+
+    Parameters
+    ----------
+    mz_array, intensity_array : np.ndarray
+        Centroided spectrum.
+    ppm, max_charge, min_isotopes
+        See validate_deconvolution.
+    polarity : str
+        "positive" (protonated, ``[M+zH]z+``) or "negative" (``[M-zH]z-``).
+
+    Returns
+    -------
+    Tuple[np.ndarray, np.ndarray]
+        Neutral monoisotopic masses (ascending) and their summed
+        intensities; envelopes within *ppm* of each other are merged, with
+        the intensity-weighted mean mass.
+    """
+    sign = -1 if polarity == "negative" else 1
+    envelopes = deisotope(mz_array, intensity_array, ppm, max_charge, min_isotopes)
+    if not envelopes:
+        return np.array([], dtype=np.float64), np.array([], dtype=np.float64)
+
+    masses = np.array([(mz - sign * PROTON_MASS) * z for mz, z, _ in envelopes])
+    intensities = np.array([total for _, _, total in envelopes])
+    order = np.argsort(masses)
+    masses, intensities = masses[order], intensities[order]
+
+    merged_masses, merged_intensities = [], []
+    start = 0
+    for i in range(1, len(masses) + 1):
+        if i == len(masses) or masses[i] - masses[i - 1] > masses[i - 1] * ppm * 1e-6:
+            group = slice(start, i)
+            merged_masses.append(np.average(masses[group], weights=intensities[group]))
+            merged_intensities.append(intensities[group].sum())
+            start = i
+    return np.array(merged_masses), np.array(merged_intensities)
+
+
+def singly_charged_mz(neutral_masses: np.ndarray, polarity: str = "positive") -> np.ndarray:
+    """m/z of the ``[M+H]+`` (or ``[M-H]-``) ions of neutral masses."""
+    sign = -1 if polarity == "negative" else 1
+    return np.asarray(neutral_masses, dtype=np.float64) + sign * PROTON_MASS
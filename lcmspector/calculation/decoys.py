@@ -0,0 +1,194 @@
+"""
+False discovery rates of MS2 library matches from decoy spectra.
+
+A cosine cutoff alone says nothing about how many of the matches above it
+are wrong. Searching the same spectra against a decoy library, spectra
+that cannot be the right answer, shows how high wrong matches score: at a
+score threshold, the decoy hits above it estimate the false target hits
+above it, and the false discovery rate is their ratio.
+
+Decoys are built from the reference library itself, see decoy_library:
+
+- "precursor_shift": every spectrum with its precursor m/z moved by
+  *shift* Da, so it only competes for queries of other compounds
+  (SpectraST's precursor swap).
+- "fragment_shuffle": every fragment m/z replaced by one drawn at random
+  from the fragments of the whole library below the spectrum's precursor,
+  intensities kept (the spectrum-based decoys of Scheubert et al., Nat.
+  Commun. 2017).
+
+match_with_decoys scores query spectra (dicts with ``precursor_mz``, ``mz``
+and ``intensity``, as linked by calculation.preprocessing.link_ms2_scans)
+against both, and fdr_threshold turns their scores into the lowest score
+with a false discovery rate of at most *fdr*:
+
+    report = match_with_decoys(spectra, library, fdr=0.01)
+    confident = [hit for hit in report["hits"] if hit["q_value"] <= 0.01]
+"""
+
+import logging
+from typing import Iterable, Optional
+
+import numpy as np
+
+from utils.library import SpectralLibrary
+
+logger = logging.getLogger(__name__)
+
+DECOY_METHODS = ("precursor_shift", "fragment_shuffle")
+DEFAULT_PRECURSOR_SHIFT = 20.0
+DECOY_PREFIX = "DECOY_"
+
+
+def decoy_library(
+    library: SpectralLibrary,
+    method: str = "precursor_shift",
+    shift: float = DEFAULT_PRECURSOR_SHIFT,
+    seed: Optional[int] = 0,
+) -> SpectralLibrary:
+    """
+    Decoy spectra of every spectrum of *library* with a precursor m/z.
+
+    Parameters
+    ----------
+    library : SpectralLibrary
+        The target library.
+    method : str
+        One of DECOY_METHODS, see the module docstring.
+    shift : float
+        Precursor shift in Da for "precursor_shift".
+    seed : int, optional
+        Seed of the random draws of "fragment_shuffle", None for a fresh one.
+
+    Returns
+    -------
+    SpectralLibrary
+        The decoys, named with DECOY_PREFIX and ``"decoy": True`` in their
+        metadata.
+    """
+    if method not in DECOY_METHODS:
+        raise ValueError(f"Unknown decoy method '{method}', expected one of {DECOY_METHODS}")
+    rng = np.random.default_rng(seed)
+    pool = np.sort(library.mz)
+    decoys = []
+    for index in range(len(library)):
+        spectrum = library.spectrum(index)
+        precursor = spectrum["precursor_mz"]
+        if precursor is None:
+            continue
+        mz = np.array(spectrum["mz"], dtype=np.float64)
+        if method == "precursor_shift":
+            precursor += shift
+        elif len(mz):
+            below = pool[: np.searchsorted(pool, precursor, side="left")]
+            if len(below):
+                mz = rng.choice(below, size=len(mz))
+            else:
+                mz = rng.uniform(0.0, precursor, size=len(mz))
+        metadata = spectrum["metadata"]
+        decoys.append(
+            {
+                "precursor_mz": precursor,
+                "mz": mz,
+                "intensity": np.array(spectrum["intensity"], dtype=np.float64),
+                "metadata": {
+                    **metadata,
+                    "name": f"{DECOY_PREFIX}{metadata.get('name', index)}",
+                    "decoy": True,
+                },
+            }
+        )
+    logger.info(f"Built {len(decoys)} {method} decoys of {len(library)} library spectra")
+    return SpectralLibrary.from_spectra(decoys)
+
+
+def q_values(target_scores: Iterable[float], decoy_scores: Iterable[float]) -> np.ndarray:
+    """
+    q-value of every target score: the lowest false discovery rate of a
+    threshold at or below it, with the FDR at threshold *s* estimated as
+    ``#(decoys >= s) / #(targets >= s)``.
+    """
+    targets = np.asarray(list(target_scores), dtype=np.float64)
+    decoys = np.sort(np.asarray(list(decoy_scores), dtype=np.float64))
+    if not targets.size:
+        return np.zeros(0, dtype=np.float64)
+    order = np.argsort(-targets, kind="stable")
+    ranked = targets[order]
+    n_targets = np.searchsorted(-ranked, -ranked, side="right")
+    n_decoys = decoys.size - np.searchsorted(decoys, ranked, side="left")
+    fdr = np.minimum(n_decoys / n_targets, 1.0)
+    # Monotone: the q-value of a score is the best FDR of any threshold below it
+    q = np.minimum.accumulate(fdr[::-1])[::-1]
+    result = np.empty_like(q)
+    result[order] = q
+    return result
+
+
+def fdr_threshold(
+    target_scores: Iterable[float], decoy_scores: Iterable[float], fdr: float = 0.01
+) -> Optional[float]:
+    """
+    Lowest target score whose q_value is at most *fdr*, or None if no
+    threshold reaches it.
+    """
+    if not 0 <= fdr <= 1:
+        raise ValueError(f"FDR must be between 0 and 1, got {fdr}")
+    targets = np.asarray(list(target_scores), dtype=np.float64)
+    accepted = targets[q_values(targets, decoy_scores) <= fdr]
+    return float(accepted.min()) if accepted.size else None
+
+
+def match_with_decoys(
+    spectra: Iterable[dict],
+    library: SpectralLibrary,
+    decoys: SpectralLibrary = None,
+    fdr: float = 0.01,
+    decoy_method: str = "precursor_shift",
+    **match_options,
+) -> dict:
+    """
+    Best target and decoy match of every query spectrum, with q-values.
+
+    Parameters
+    ----------
+    spectra : iterable of dict
+        Query MS2 spectra with ``precursor_mz``, ``mz`` and ``intensity``.
+    library : SpectralLibrary
+        The target library.
+    decoys : SpectralLibrary, optional
+        Decoys of *library*; built with decoy_library(library, decoy_method)
+        if not given.
+    fdr : float
+        Target false discovery rate of the returned ``threshold``.
+    **match_options
+        Passed to SpectralLibrary.match (tolerances, ``method``, ...).
+
+    Returns
+    -------
+    dict
+        ``hits``: the best target hit of every spectrum that has one (see
+        SpectralLibrary.match) with the ``spectrum`` index and its
+        ``q_value``; ``decoy_scores``: the best decoy score of every spectrum
+        with a decoy hit; ``threshold``: see fdr_threshold.
+    """
+    if decoys is None:
+        decoys = decoy_library(library, decoy_method)
+    match_options["top_n"] = 1
+    hits, decoy_scores = [], []
+    for index, spectrum in enumerate(spectra):
+        args = (spectrum["mz"], spectrum["intensity"], spectrum["precursor_mz"])
+        target = library.match(*args, **match_options)
+        if target:
+            hits.append({**target[0], "spectrum": index})
+        decoy = decoys.match(*args, **match_options)
+        if decoy:
+            decoy_scores.append(decoy[0]["score"])
+    scores = [hit["score"] for hit in hits]
+    for hit, q in zip(hits, q_values(scores, decoy_scores)):
+        hit["q_value"] = float(q)
+    threshold = fdr_threshold(scores, decoy_scores, fdr)
+    logger.info(
+        f"{len(hits)} target and {len(decoy_scores)} decoy matches, "
+        f"score threshold {threshold} at {fdr:.0%} FDR"
+    )
+    return {"hits": hits, "decoy_scores": decoy_scores, "threshold": threshold}
@@ -0,0 +1,197 @@
+"""
+Pseudo-MS2 spectra from data-independent acquisition (SWATH, AIF).
+
+DIA fragments every precursor in an isolation window together, so an MS2
+spectrum mixes the fragments of everything that elutes in it. The fragments
+of one precursor are recovered by their elution profile instead:
+
+1. the MS2 scans whose isolation window holds the precursor m/z, within
+   *rt_window* of its RT, are chained into fragment mass traces (see
+   calculation.features.detect_mass_traces);
+2. every fragment trace is correlated (Pearson) with the precursor's MS1
+   XIC, interpolated onto the MS2 scan times;
+3. fragments with a correlation of at least *min_correlation* make up the
+   pseudo-MS2 spectrum, at their intensity at the precursor apex.
+
+The spectra are dicts like the DDA ones of preprocessing.link_ms2_scans,
+``scan_time``, ``precursor_mz``, ``mz`` and ``intensity``, with the
+``window`` and per-fragment ``correlation`` added, so they can be scored
+with utils.library.SpectralLibrary.match.
+"""
+
+import logging
+from typing import Iterable, List, Sequence, Tuple
+
+import numpy as np
+
+from calculation.features import detect_mass_traces
+
+logger = logging.getLogger(__name__)
+
+DEFAULT_DIA_MIN_CORRELATION = 0.8
+
+
+def isolation_windows(scans: Iterable) -> List[Tuple[float, float]]:
+    """Distinct MS2 isolation windows of iter_dia_scans tuples, by lower m/z."""
+    return sorted({tuple(window) for _, ms_level, window, _, _ in scans if window is not None})
+
+
+def _in_window(window, mz: float) -> bool:
+    # Without a recorded window (AIF), an MS2 scan holds every precursor
+    return window is None or window[0] <= mz <= window[1]
+
+
+def _precursor_xic(ms1, mz: float, ppm: float) -> Tuple[np.ndarray, np.ndarray]:
+    """Times and summed intensities within *ppm* of *mz* of the (time, mz, intensity) scans."""
+    tolerance = mz * ppm * 1e-6
+    times = np.array([scan[0] for scan in ms1], dtype=np.float64)
+    intensities = np.array(
+        [
+            float(np.sum(intensity[np.abs(mz_array - mz) <= tolerance], dtype=np.float64))
+            for _, mz_array, intensity in ms1
+        ],
+        dtype=np.float64,
+    )
+    return times, intensities
+
+
+def _correlation(a: np.ndarray, b: np.ndarray) -> float:
+    if len(a) < 3 or np.ptp(a) == 0 or np.ptp(b) == 0:
+        return 0.0
+    return float(np.corrcoef(a, b)[0, 1])
+
+
+def pseudo_ms2_spectrum(
+    ms1: Sequence,
+    ms2: Sequence,
+    precursor_mz: float,
+    rt: float,
+    ppm: float = 10.0,
+    rt_window: float = 0.5,
+    min_correlation: float = DEFAULT_DIA_MIN_CORRELATION,
+    min_scans: int = 3,
+):
+    """
+    Pseudo-MS2 spectrum of one precursor, see the module docstring.
+
+    Parameters
+    ----------
+    ms1 : sequence
+        ``(scan_time, mz_array, intensity_array)`` of the MS1 scans.
+    ms2 : sequence
+        ``(scan_time, window, mz_array, intensity_array)`` of the MS2 scans.
+    precursor_mz, rt : float
+        The precursor and its apex RT (min).
+    ppm : float
+        m/z tolerance of the precursor XIC and of the fragment traces.
+    rt_window : float
+        Scans within this many minutes of *rt* are used.
+    min_correlation : float
+        Minimum Pearson correlation of a fragment with the precursor.
+    min_scans : int
+        Fragment traces spanning fewer MS2 scans are dropped.
+
+    Returns
+    -------
+    dict or None
+        The spectrum, None without precursor signal or correlated fragments.
+    """
+    near = [scan for scan in ms1 if abs(scan[0] - rt) <= rt_window]
+    times, xic = _precursor_xic(near, precursor_mz, ppm)
+    if len(times) < 2 or xic.max() <= 0:
+        return None
+    apex = float(times[np.argmax(xic)])
+
+    scans = [
+        scan for scan in ms2 if abs(scan[0] - rt) <= rt_window and _in_window(scan[1], precursor_mz)
+    ]
+    if len(scans) < min_scans:
+        return None
+    # Fragments of one window only: the one the precursor is most central in
+    windows = {scan[1] for scan in scans}
+    if len(windows) > 1:
+        window = min(
+            windows,
+            key=lambda w: float("inf") if w is None else abs((w[0] + w[1]) / 2 - precursor_mz),
+        )
+        scans = [scan for scan in scans if scan[1] == window]
+    scans.sort(key=lambda scan: scan[0])
+    ms2_times = np.array([scan[0] for scan in scans], dtype=np.float64)
+    profile = np.interp(ms2_times, times, xic, left=0.0, right=0.0)
+    at_apex = int(np.argmin(np.abs(ms2_times - apex)))
+
+    fragments = []
+    for trace in detect_mass_traces([(scan[2], scan[3]) for scan in scans], ppm, 0.0, min_scans):
+        intensities = np.zeros(len(scans), dtype=np.float64)
+        intensities[trace.scans] = trace.intensities
+        correlation = _correlation(profile, intensities)
+        if correlation >= min_correlation and intensities[at_apex] > 0:
+            fragments.append((trace.mz, intensities[at_apex], correlation))
+    if not fragments:
+        return None
+    fragments.sort()
+    mz, intensity, correlation = (np.array(column) for column in zip(*fragments))
+    return {
+        "scan_time": apex,
+        "precursor_mz": float(precursor_mz),
+        "window": scans[0][1],
+        "mz": mz,
+        "intensity": intensity,
+        "correlation": correlation,
+    }
+
+
+def pseudo_ms2_spectra(
+    scans: Iterable, precursors: Iterable[Tuple[float, float]], rt_window: float = 0.5, **kwargs
+) -> List:
+    """
+    pseudo_ms2_spectrum of every ``(precursor_mz, rt)``, in the order given.
+
+    *scans* are iter_dia_scans tuples; only those within *rt_window* of a
+    precursor are kept in memory. *kwargs* are passed on.
+    """
+    precursors = [(float(mz), float(rt)) for mz, rt in precursors]
+    if not precursors:
+        return []
+    rts = np.array([rt for _, rt in precursors])
+    ms1, ms2 = [], []
+    for scan_time, ms_level, window, mz_array, intensity_array in scans:
+        if not np.any(np.abs(rts - scan_time) <= rt_window):
+            continue
+        mz_array = np.asarray(mz_array, dtype=np.float64)
+        intensity_array = np.asarray(intensity_array, dtype=np.float64)
+        if ms_level == 1:
+            ms1.append((scan_time, mz_array, intensity_array))
+        else:
+            ms2.append((scan_time, window, mz_array, intensity_array))
+    return [
+        pseudo_ms2_spectrum(ms1, ms2, mz, rt, rt_window=rt_window, **kwargs)
+        for mz, rt in precursors
+    ]
+
+
+def link_dia_ms2(compounds, path: str, **kwargs):
+    """
+    Attach pseudo-MS2 spectra of a DIA file to the integrated ions of *compounds*.
+
+    Like preprocessing.link_ms2_scans for DDA, the spectra are stored under
+    ``ions[mz]["MS2"]`` (one per ion at most). *kwargs* are passed on to
+    pseudo_ms2_spectrum.
+    """
+    from utils.loading import iter_dia_scans
+
+    ions = [
+        (ion, data)
+        for compound in compounds
+        for ion, data in compound.ions.items()
+        if data.get("Integration Data") and data.get("RT") is not None
+    ]
+    spectra = pseudo_ms2_spectra(
+        iter_dia_scans(path), [(ion, data["RT"]) for ion, data in ions], **kwargs
+    )
+    for (ion, data), spectrum in zip(ions, spectra):
+        data["MS2"] = [spectrum] if spectrum is not None else []
+    logger.info(
+        f"Pseudo-MS2 spectra for {sum(s is not None for s in spectra)} of {len(ions)} ions in {path}"
+    )
+    return compounds
@@ -0,0 +1,111 @@
+"""
+Downsampling of traces for display.
+
+PyQtGraph slows down noticeably on traces with hundreds of thousands of
+points, while a screen only shows a few thousand. Both methods keep the
+shape of the peaks, unlike taking every n-th point:
+
+- "lttb": Largest-Triangle-Three-Buckets (Steinarsson, 2013) keeps per bucket
+  the point spanning the largest triangle with its neighbours.
+- "minmax": keeps the lowest and highest point of every bucket, so apexes
+  and valleys are always drawn.
+
+Downsampled traces are for plotting only; integration uses the full data.
+"""
+
+import logging
+from typing import Tuple
+
+import numpy as np
+
+logger = logging.getLogger(__name__)
+
+DOWNSAMPLING_METHODS = ("lttb", "minmax")
+
+# Points drawn per trace by the plotting functions
+MAX_PLOT_POINTS = 5000
+
+
+def lttb_indices(x: np.ndarray, y: np.ndarray, n_points: int) -> np.ndarray:
+    """Indices of the *n_points* (at least 3) points kept by LTTB, first and last included."""
+    n = len(x)
+    if n <= n_points:
+        return np.arange(n)
+    edges = np.linspace(1, n - 1, n_points - 1).astype(np.int64)  # n_points - 2 buckets
+    selected = np.empty(n_points, dtype=np.int64)
+    selected[0], selected[-1] = 0, n - 1
+    a = 0
+    for i in range(n_points - 2):
+        start, end = edges[i], edges[i + 1]
+        next_start, next_end = (edges[i + 1], edges[i + 2]) if i + 2 < len(edges) else (n - 1, n)
+        mean_x = x[next_start:next_end].mean()
+        mean_y = y[next_start:next_end].mean()
+        area = np.abs(
+            (x[a] - mean_x) * (y[start:end] - y[a]) - (x[a] - x[start:end]) * (mean_y - y[a])
+        )
+        a = start + int(np.argmax(area))
+        selected[i + 1] = a
+    return selected
+
+
+def minmax_indices(y: np.ndarray, n_points: int) -> np.ndarray:
+    """Indices of the minimum and maximum of ``n_points // 2`` equal buckets, ascending."""
+    n = len(y)
+    if n <= n_points:
+        return np.arange(n)
+    n_buckets = n_points // 2
+    size = -(-n // n_buckets)
+    n_buckets = -(-n // size)
+    padded = np.full(n_buckets * size, np.nan)
+    padded[:n] = y
+    buckets = padded.reshape(n_buckets, size)
+    offsets = np.arange(n_buckets) * size
+    indices = np.concatenate(
+        (offsets + np.nanargmin(buckets, axis=1), offsets + np.nanargmax(buckets, axis=1))
+    )
+    return np.unique(indices)
+
+
+def downsample_trace(
+    x: np.ndarray, y: np.ndarray, n_points: int = MAX_PLOT_POINTS, method: str = "lttb"
+) -> Tuple[np.ndarray, np.ndarray]:
+    """
+    Reduce a trace to at most *n_points* points for plotting.
+
+    Parameters
+    ----------
+    x, y : np.ndarray
+        The trace, *x* ascending (e.g. an XIC or TIC).
+    n_points : int
+        Number of points to keep; traces this short are returned as they are.
+    method : str
+        One of DOWNSAMPLING_METHODS.
+
+    Returns
+    -------
+    Tuple[np.ndarray, np.ndarray]
+        The kept (x, y) points, in order; the full trace if *y* has NaNs.
+
+    Raises
+    ------
+    ValueError
+        On an unknown method, or fewer than 3 points (LTTB) or 2 points
+        (min/max) requested.
+    """
+    if method not in DOWNSAMPLING_METHODS:
+        raise ValueError(f"Unknown downsampling method '{method}', expected one of {DOWNSAMPLING_METHODS}")
+    minimum = 3 if method == "lttb" else 2
+    if n_points < minimum:
+        raise ValueError(f"{method} downsampling needs at least {minimum} points, got {n_points}")
+    x = np.asarray(x)
+    y = np.asarray(y)
+    if len(x) <= n_points:
+        return x, y
+    if np.isnan(y).any():
+        logger.debug("Trace with NaN intensities is not downsampled")
+        return x, y
+    if method == "lttb":
+        indices = lttb_indices(x.astype(np.float64), y.astype(np.float64), n_points)
+    else:
+        indices = minmax_indices(y.astype(np.float64), n_points)
+    return x[indices], y[indices]
@@ -0,0 +1,298 @@
+"""
+Signal drift and carryover across a batch of injections.
+
+Both need the files in the order they were injected:
+
+- drift: the areas of the internal standards (or of every compound, if none
+  is used as one) over the sample injections are tested for a monotonic
+  trend with Spearman's rank correlation. The slope of a straight line
+  through them, relative to their mean, gives the drift in percent per
+  injection;
+- carryover: the area of every compound in a blank, in percent of its area
+  in the last sample injected before that blank.
+
+Areas are the summed baseline corrected peak areas of the compound's ions,
+from their ``Integration Data``.
+
+correct_drift() removes the drift using pooled QC injections, which are
+the same sample throughout the batch: a smooth function of the injection
+position is fitted through every compound's QC areas, by local linear
+regression ("loess") or a smoothing spline ("spline"), and the compound's
+areas in every injection are scaled by the median QC area over that
+function. The uncorrected area is kept in the ion's ``Integration Data``
+as ``drift_uncorrected_area``, next to the ``drift_factor`` applied.
+"""
+
+import logging
+from pathlib import Path
+from typing import Collection, Sequence
+
+import numpy as np
+from scipy.interpolate import make_smoothing_spline
+from scipy.stats import spearmanr
+
+from calculation.alignment import _loess
+
+logger = logging.getLogger(__name__)
+
+DEFAULT_DRIFT_P_VALUE = 0.05
+MIN_DRIFT_INJECTIONS = 3
+DRIFT_CORRECTION_METHODS = ("loess", "spline")
+# A smoothing spline needs this many QC injections, fewer fall back to LOESS
+_MIN_SPLINE_POINTS = 5
+
+
+def compound_area(compound) -> float:
+    """Summed baseline corrected peak area of a compound's ions."""
+    return float(
+        sum(
+            (data.get("Integration Data") or {}).get("baseline_corrected_area", 0) or 0
+            for data in compound.ions.values()
+        )
+    )
+
+
+def internal_standards(compounds) -> set:
+    """Names of the compounds used as another compound's internal standard."""
+    names = {compound.name for compound in compounds}
+    return {
+        compound.internal_standard
+        for compound in compounds
+        if getattr(compound, "internal_standard", None) in names
+    }
+
+
+def _in_order(results: Sequence[Sequence], injection_order: Sequence[str]) -> list:
+    """``(file, compounds)`` of the results, in injection order."""
+    files = {compounds[0].file: compounds for compounds in results if compounds}
+    order = [Path(name).name for name in injection_order]
+    missing = set(files) - set(order)
+    if missing:
+        logger.warning(f"Files not in the injection order are ignored: {sorted(missing)}")
+    return [(name, files[name]) for name in order if name in files]
+
+
+def _by_file(results: Sequence[Sequence], injection_order: Sequence[str]) -> list:
+    """``(file, {compound name: area})`` of the results, in injection order."""
+    return [
+        (name, {compound.name: compound_area(compound) for compound in compounds})
+        for name, compounds in _in_order(results, injection_order)
+    ]
+
+
+def detect_drift(
+    results: Sequence[Sequence],
+    injection_order: Sequence[str],
+    blank_files: Collection[str] = (),
+    compounds: Collection[str] = None,
+    max_p_value: float = DEFAULT_DRIFT_P_VALUE,
+) -> dict:
+    """
+    Monotonic trends of compound areas over the sample injections.
+
+    Parameters
+    ----------
+    results : sequence of sequence of Compound
+        Processed compounds, one sequence per file.
+    injection_order : sequence of str
+        ``Compound.file`` names in the order the files were injected.
+    blank_files : collection of str
+        Files left out of the trend.
+    compounds : collection of str, optional
+        Compounds to test, e.g. those of the pooled QC; by default the
+        internal standards, or every compound if there are none.
+    max_p_value : float
+        Significance level of the rank correlation for ``drifting``.
+
+    Returns
+    -------
+    dict
+        ``{compound name: {"slope", "rho", "p_value", "n", "drifting"}}``,
+        *slope* in percent of the mean area per injection. Injections without
+        a peak of the compound are skipped; compounds with fewer than
+        MIN_DRIFT_INJECTIONS peaks are left out.
+    """
+    blank_files = {Path(name).name for name in blank_files}
+    samples = [
+        (position, areas)
+        for position, (name, areas) in enumerate(_by_file(results, injection_order))
+        if name not in blank_files
+    ]
+    if compounds is None:
+        all_compounds = [compound for compounds in results for compound in compounds]
+        compounds = internal_standards(all_compounds) or {c.name for c in all_compounds}
+
+    drift = {}
+    for name in sorted(compounds):
+        points = [
+            (position, areas[name]) for position, areas in samples if areas.get(name, 0) > 0
+        ]
+        if len(points) < MIN_DRIFT_INJECTIONS:
+            continue
+        positions, values = np.array(points, dtype=np.float64).T
+        slope = np.polyfit(positions, values, 1)[0]
+        if np.ptp(values) == 0:
+            rho, p_value = 0.0, 1.0
+        else:
+            rho, p_value = spearmanr(positions, values)
+        drift[name] = {
+            "slope": float(slope / values.mean() * 100),
+            "rho": float(rho),
+            "p_value": float(p_value),
+            "n": len(points),
+            "drifting": bool(p_value < max_p_value),
+        }
+    drifting = [name for name, trend in drift.items() if trend["drifting"]]
+    if drifting:
+        logger.info(f"Signal drift over {len(samples)} injections: {drifting}")
+    return drift
+
+
+def detect_carryover(
+    results: Sequence[Sequence],
+    injection_order: Sequence[str],
+    blank_files: Collection[str],
+) -> dict:
+    """
+    Compound areas in the blanks, in percent of the sample injected before them.
+
+    Consecutive blanks are all compared with the last sample before them,
+    so the carryover can be seen decaying. Blanks injected before any
+    sample are skipped.
+
+    Returns
+    -------
+    dict
+        ``{compound name: {"max_percent": ..., "blanks": {blank file:
+        {"after": sample file, "percent": ...}}}}`` for the compounds with a
+        peak in that sample.
+    """
+    blank_files = {Path(name).name for name in blank_files}
+    carryover = {}
+    previous = None
+    for name, areas in _by_file(results, injection_order):
+        if name not in blank_files:
+            previous = (name, areas)
+            continue
+        if previous is None:
+            continue
+        sample, sample_areas = previous
+        for compound, sample_area in sample_areas.items():
+            if sample_area <= 0:
+                continue
+            percent = areas.get(compound, 0.0) / sample_area * 100
+            entry = carryover.setdefault(compound, {"max_percent": 0.0, "blanks": {}})
+            entry["blanks"][name] = {"after": sample, "percent": percent}
+            entry["max_percent"] = max(entry["max_percent"], percent)
+    return carryover
+
+
+def batch_trends(
+    results: Sequence[Sequence],
+    injection_order: Sequence[str],
+    blank_files: Collection[str] = (),
+    compounds: Collection[str] = None,
+) -> dict:
+    """``{"drift": detect_drift(...), "carryover": detect_carryover(...)}`` of a batch."""
+    return {
+        "drift": detect_drift(results, injection_order, blank_files, compounds),
+        "carryover": detect_carryover(results, injection_order, blank_files),
+    }
+
+
+def _qc_trend(positions: np.ndarray, areas: np.ndarray, method: str, span: float):
+    """The fitted QC areas as a function of the injection position."""
+    if method == "spline" and len(np.unique(positions)) >= _MIN_SPLINE_POINTS:
+        spline = make_smoothing_spline(positions, areas)
+        # Constant beyond the first and last QC injection
+        return lambda at: spline(np.clip(at, positions[0], positions[-1]))
+    smoothed = _loess(positions, areas, span)
+    return lambda at: np.interp(at, positions, smoothed)
+
+
+def correct_drift(
+    results: Sequence[Sequence],
+    injection_order: Sequence[str],
+    qc_files: Collection[str],
+    method: str = "loess",
+    span: float = 0.75,
+) -> dict:
+    """
+    Normalize compound areas to the trend of the pooled QC injections.
+
+    Parameters
+    ----------
+    results : sequence of sequence of Compound
+        Processed compounds, one sequence per file, QC injections included;
+        corrected in place.
+    injection_order : sequence of str
+        ``Compound.file`` names in the order the files were injected.
+    qc_files : collection of str
+        ``Compound.file`` names of the pooled QC injections.
+    method : str
+        One of DRIFT_CORRECTION_METHODS.
+    span : float
+        Fraction of the QC injections in each local fit for "loess".
+
+    Returns
+    -------
+    dict
+        ``{compound name: {file: correction factor}}`` of the corrected
+        compounds; compounds with a peak in fewer than MIN_DRIFT_INJECTIONS
+        QC injections are left as they are.
+    """
+    if method not in DRIFT_CORRECTION_METHODS:
+        raise ValueError(
+            f"Unknown drift correction method '{method}', "
+            f"expected one of {DRIFT_CORRECTION_METHODS}"
+        )
+    qc_files = {Path(name).name for name in qc_files}
+    files = _in_order(results, injection_order)
+    areas = [
+        {compound.name: compound_area(compound) for compound in compounds} for _, compounds in files
+    ]
+    names = {name for file_areas in areas for name in file_areas}
+
+    factors = {}
+    for name in sorted(names):
+        points = [
+            (position, areas[position][name])
+            for position, (file, _) in enumerate(files)
+            if file in qc_files and areas[position].get(name, 0) > 0
+        ]
+        if len(points) < MIN_DRIFT_INJECTIONS:
+            continue
+        positions, qc_areas = np.array(points, dtype=np.float64).T
+        trend = _qc_trend(positions, qc_areas, method, span)(np.arange(len(files), dtype=float))
+        with np.errstate(divide="ignore", invalid="ignore"):
+            file_factors = np.where(trend > 0, np.median(qc_areas) / trend, 1.0)
+        factors[name] = {}
+        for (file, compounds), factor in zip(files, file_factors):
+            factors[name][file] = float(factor)
+            for compound in compounds:
+                if compound.name == name:
+                    _scale_areas(compound, float(factor))
+
+    if not factors:
+        logger.warning(
+            f"No compound found in {MIN_DRIFT_INJECTIONS} QC injections, skipping drift correction"
+        )
+    else:
+        logger.info(
+            f"Drift correction ({method}) of {len(factors)} compounds on {len(qc_files)} QCs"
+        )
+    return factors
+
+
+def _scale_areas(compound, factor: float):
+    for data in compound.ions.values():
+        integration = data.get("Integration Data")
+        if not integration:
+            continue
+        area = integration.get("baseline_corrected_area", 0) or 0
+        integration.setdefault("drift_uncorrected_area", area)
+        integration["baseline_corrected_area"] = area * factor
+        integration["drift_factor"] = factor
+        if data.get("MS Peak Area"):
+            # Keep the export copy of a manual re-integration in sync
+            data["MS Peak Area"] = integration.copy()
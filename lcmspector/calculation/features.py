@@ -0,0 +1,254 @@
+"""
+Untargeted feature detection.
+
+A simplified centWave: centroided MS1 peaks are first chained into mass
+traces (consecutive scans whose m/z agree within a ppm tolerance), then every
+trace is split into chromatographic peaks with detect_peaks. Each peak is a
+feature with an m/z, a retention time window and an intensity.
+"""
+
+import logging
+from typing import Dict, List
+
+import numpy as np
+import pandas as pd
+
+from calculation.charge import charge_from_isotopes
+from calculation.grouping import group_features
+from calculation.peak_detection import detect_peaks
+from utils.loading import iter_ms_scans
+
+logger = logging.getLogger(__name__)
+
+FEATURE_COLUMNS = (
+    "mz",
+    "mz_min",
+    "mz_max",
+    "rt",
+    "rt_min",
+    "rt_max",
+    "height",
+    "area",
+    "n_scans",
+    "charge",
+)
+
+
+class _MassTrace:
+    """Peaks of one m/z across consecutive scans, growing scan by scan."""
+
+    __slots__ = ("scans", "mzs", "intensities", "_mz_sum", "_weight")
+
+    def __init__(self, scan, mz, intensity):
+        self.scans = [scan]
+        self.mzs = [mz]
+        self.intensities = [intensity]
+        self._mz_sum = mz * intensity
+        self._weight = intensity
+
+    @property
+    def mz(self) -> float:
+        """Intensity-weighted mean m/z so far."""
+        return self._mz_sum / self._weight if self._weight > 0 else self.mzs[-1]
+
+    def add(self, scan, mz, intensity):
+        self.scans.append(scan)
+        self.mzs.append(mz)
+        self.intensities.append(intensity)
+        self._mz_sum += mz * intensity
+        self._weight += intensity
+
+
+def detect_mass_traces(
+    scans,
+    ppm: float = 10.0,
+    min_intensity: float = 0.0,
+    min_scans: int = 5,
+    max_gap: int = 1,
+) -> List[_MassTrace]:
+    """
+    Chain centroided peaks of consecutive scans into mass traces.
+
+    Parameters
+    ----------
+    scans : iterable of (mz_array, intensity_array)
+        One centroided spectrum per scan, in acquisition order.
+    ppm : float
+        m/z tolerance between a peak and the trace it extends.
+    min_intensity : float
+        Peaks below this intensity are ignored (noise level).
+    min_scans : int
+        Traces spanning fewer scans are dropped.
+    max_gap : int
+        Scans a trace may miss before it is closed.
+
+    Returns
+    -------
+    list of _MassTrace
+        The kept traces, with ``scans`` holding indices into *scans*.
+    """
+    active: List[_MassTrace] = []
+    finished: List[_MassTrace] = []
+
+    for scan_index, (mz_array, intensity_array) in enumerate(scans):
+        mz_array = np.asarray(mz_array, dtype=np.float64)
+        intensity_array = np.asarray(intensity_array, dtype=np.float64)
+        keep = intensity_array > min_intensity
+        mz_array, intensity_array = mz_array[keep], intensity_array[keep]
+
+        # Close traces that missed too many scans
+        still_active = []
+        for trace in active:
+            if scan_index - trace.scans[-1] > max_gap + 1:
+                if len(trace.scans) >= min_scans:
+                    finished.append(trace)
+            else:
+                still_active.append(trace)
+        active = still_active
+
+        trace_mzs = np.array([trace.mz for trace in active], dtype=np.float64)
+        order = np.argsort(trace_mzs)
+        sorted_mzs = trace_mzs[order]
+        taken = np.zeros(len(active), dtype=bool)
+
+        # Most intense peaks claim their trace first
+        for peak in np.argsort(intensity_array)[::-1]:
+            mz, intensity = mz_array[peak], intensity_array[peak]
+            tolerance = mz * ppm * 1e-6
+            best = None
+            if len(sorted_mzs):
+                lo = np.searchsorted(sorted_mzs, mz - tolerance, side="left")
+                hi = np.searchsorted(sorted_mzs, mz + tolerance, side="right")
+                candidates = [order[j] for j in range(lo, hi) if not taken[order[j]]]
+                if candidates:
+                    best = min(candidates, key=lambda t: abs(trace_mzs[t] - mz))
+            if best is None:
+                active.append(_MassTrace(scan_index, mz, intensity))
+            else:
+                taken[best] = True
+                active[best].add(scan_index, mz, intensity)
+
+    finished.extend(trace for trace in active if len(trace.scans) >= min_scans)
+    return finished
+
+
+def detect_features(
+    filepath: str,
+    ppm: float = 10.0,
+    min_intensity: float = 0.0,
+    min_scans: int = 5,
+    max_gap: int = 1,
+    min_peak_width_scans: int = 3,
+    polarity: str = None,
+    scan_filter: dict = None,
+    group_adducts: bool = False,
+) -> pd.DataFrame:
+    """
+    Find untargeted m/z-RT features in an MS file.
+
+    Parameters
+    ----------
+    filepath : str
+        Path to the mzML, mzXML or MGF file; profile data should be
+        centroided by the vendor converter first.
+    ppm, min_intensity, min_scans, max_gap
+        Mass trace settings, see detect_mass_traces.
+    min_peak_width_scans : int
+        Minimum FWHM of a chromatographic peak, see detect_peaks.
+    polarity : str, optional
+        "positive" or "negative", see utils.loading.iter_ms_scans.
+    scan_filter : dict, optional
+        Scan selection (see utils.scan_filter); restricted to MS1 unless it
+        sets ``ms_levels``.
+    group_adducts : bool
+        Group co-eluting adducts and in-source fragments of one compound,
+        adding the calculation.grouping.GROUP_COLUMNS; assumes positive mode
+        unless *polarity* says otherwise.
+
+    Returns
+    -------
+    pd.DataFrame
+        One row per feature with FEATURE_COLUMNS: intensity-weighted m/z and
+        its range over the peak, apex RT and peak boundaries (min), baseline
+        corrected height and area, the number of scans in the peak and the
+        charge from the isotope spacing in the apex scan (None without
+        isotope peaks), see calculation.charge. Sorted by m/z, then RT.
+    """
+    scan_filter = dict(scan_filter or {})
+    scan_filter.setdefault("ms_levels", [1])
+    if scan_filter["ms_levels"] is None:
+        scan_filter["ms_levels"] = [1]
+
+    scan_times = []
+
+    def spectra():
+        # Only the scan times are kept; the traces take the spectra one by one
+        for scan in iter_ms_scans(filepath, polarity=polarity, scan_filter=scan_filter):
+            scan_time, _, _, mz_array, intensity_array = scan[:5]
+            scan_times.append(scan_time)
+            yield mz_array, intensity_array
+
+    traces = detect_mass_traces(spectra(), ppm, min_intensity, min_scans, max_gap)
+    scan_times = np.asarray(scan_times, dtype=np.float64)
+    logger.info(f"Found {len(traces)} mass traces in {filepath}")
+
+    features: List[Dict] = []
+    # Apex scan index -> (feature, monoisotopic m/z) for the charge states
+    apexes: Dict[int, List] = {}
+    for trace in traces:
+        first, last = trace.scans[0], trace.scans[-1]
+        # Dense trace over its scan range; missed scans count as zero
+        intensities = np.zeros(last - first + 1, dtype=np.float64)
+        mzs = np.full(last - first + 1, np.nan)
+        positions = np.asarray(trace.scans) - first
+        intensities[positions] = trace.intensities
+        mzs[positions] = trace.mzs
+        times = scan_times[first : last + 1]
+
+        for peak in detect_peaks(times, intensities, min_width_scans=min_peak_width_scans):
+            window = slice(peak["start_index"], peak["end_index"] + 1)
+            peak_mzs, peak_intensities = mzs[window], intensities[window]
+            present = ~np.isnan(peak_mzs) & (peak_intensities > 0)
+            if not np.any(present):
+                continue
+            feature_mz = float(np.average(peak_mzs[present], weights=peak_intensities[present]))
+            apex_mz = mzs[peak["apex_index"]]
+            apexes.setdefault(first + peak["apex_index"], []).append(
+                (len(features), feature_mz if np.isnan(apex_mz) else float(apex_mz))
+            )
+            features.append(
+                {
+                    "mz": feature_mz,
+                    "mz_min": float(peak_mzs[present].min()),
+                    "mz_max": float(peak_mzs[present].max()),
+                    "rt": peak["apex_rt"],
+                    "rt_min": peak["start_time"],
+                    "rt_max": peak["end_time"],
+                    "height": peak["height"],
+                    "area": peak["baseline_corrected_area"],
+                    "n_scans": int(np.count_nonzero(present)),
+                    "charge": None,
+                }
+            )
+
+    # Second pass over the file for the isotope patterns of the apex scans only
+    if apexes:
+        last_apex = max(apexes)
+        scans = iter_ms_scans(filepath, polarity=polarity, scan_filter=scan_filter)
+        try:
+            for scan_index, scan in enumerate(scans):
+                for feature, mz in apexes.get(scan_index, ()):
+                    features[feature]["charge"], _ = charge_from_isotopes(
+                        scan[3], scan[4], mz, ppm=ppm
+                    )
+                if scan_index >= last_apex:
+                    break
+        finally:
+            scans.close()
+
+    table = pd.DataFrame(features, columns=list(FEATURE_COLUMNS))
+    logger.info(f"Detected {len(table)} features in {filepath}")
+    table = table.sort_values(["mz", "rt"], ignore_index=True)
+    if group_adducts:
+        table = group_features(table, polarity or "positive")
+    return table
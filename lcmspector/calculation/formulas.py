@@ -0,0 +1,228 @@
+"""
+Molecular formula generation from an accurate mass.
+
+For an unknown peak, e.g. an untargeted feature, every elemental
+composition within the element limits whose mass lies within the tolerance
+of the observed one is enumerated. Candidates are then filtered on their
+ring and double bond equivalents (RDBE):
+
+    RDBE = 1 + sum(n_i * (valence_i - 2)) / 2
+
+which must lie within a range and, for the even-electron neutral molecules
+that [M+H]+ or [M-H]- ions come from, be a whole number (which implies the
+nitrogen rule). If the observed isotope pattern is given (``[1.0, M+1/M,
+M+2/M]``, see calculation.isotopes), the candidates are ranked on how well
+their predicted pattern matches it, otherwise on the mass error.
+
+Element limits are ``{element: (min, max)}`` dicts:
+
+    {"C": (0, 40), "H": (0, 80), "N": (0, 5), "O": (0, 10)}
+"""
+
+import logging
+from typing import Dict, List, Sequence, Tuple
+
+import numpy as np
+from pyteomics.mass import nist_mass
+
+from calculation.isotopes import isotope_pattern_score
+from utils.theoretical_spectrum import (
+    ADDUCT_DEFINITIONS,
+    ELECTRON_MASS,
+    adduct_mz_from_mass,
+    isotope_ratios,
+    monoisotopic_mass,
+)
+
+logger = logging.getLogger(__name__)
+
+# Valences used for the RDBE; halogens and alkali metals count as hydrogen
+VALENCES = {
+    "C": 4, "Si": 4, "H": 1, "F": 1, "Cl": 1, "Br": 1, "I": 1, "Na": 1, "K": 1,
+    "N": 3, "P": 3, "O": 2, "S": 2,
+}
+DEFAULT_ELEMENT_LIMITS = {
+    "C": (0, 60), "H": (0, 120), "N": (0, 10), "O": (0, 20), "P": (0, 3), "S": (0, 3),
+}
+DEFAULT_RDBE_RANGE = (-0.5, 40.0)
+DEFAULT_FORMULA_PPM = 5.0
+
+
+def validate_element_limits(limits: dict = None) -> Dict[str, Tuple[int, int]]:
+    """
+    Validate element limits, DEFAULT_ELEMENT_LIMITS if None.
+
+    Raises
+    ------
+    ValueError
+        On elements without a valence in VALENCES, negative counts or a
+        minimum above the maximum.
+    """
+    limits = DEFAULT_ELEMENT_LIMITS if limits is None else limits
+    unknown = set(limits) - set(VALENCES)
+    if unknown:
+        raise ValueError(f"Unknown elements {sorted(unknown)}, expected any of {tuple(VALENCES)}")
+    validated = {}
+    for element, (low, high) in limits.items():
+        low, high = int(low), int(high)
+        if low < 0 or low > high:
+            raise ValueError(f"Invalid limits ({low}, {high}) for {element}")
+        validated[element] = (low, high)
+    if not validated:
+        raise ValueError("No elements to generate formulas from")
+    return validated
+
+
+def rdbe(counts: Dict[str, int]) -> float:
+    """Ring and double bond equivalents of ``{element: count}``."""
+    return 1 + sum(n * (VALENCES[element] - 2) for element, n in counts.items()) / 2
+
+
+def hill_formula(counts: Dict[str, int]) -> str:
+    """Formula string in Hill order: C, H, then the other elements alphabetically."""
+    carbon = counts.get("C", 0) > 0
+    elements = sorted(counts, key=lambda e: (e != "C", e != "H" or not carbon, e))
+    return "".join(
+        f"{element}{counts[element] if counts[element] > 1 else ''}"
+        for element in elements
+        if counts[element] > 0
+    )
+
+
+def neutral_mass(mz: float, adduct: str = "[M+H]+") -> float:
+    """Neutral monoisotopic mass of an ion, inverting adduct_mz_from_mass."""
+    defn = ADDUCT_DEFINITIONS.get(adduct)
+    if defn is None:
+        raise ValueError(f"Unknown adduct '{adduct}', expected one of {list(ADDUCT_DEFINITIONS)}")
+    sign = 1 if defn.polarity == "positive" else -1
+    mass = mz * defn.charge + sign * defn.charge * ELECTRON_MASS
+    if defn.add_formula:
+        mass -= monoisotopic_mass(defn.add_formula)
+    if defn.subtract_formula:
+        mass += monoisotopic_mass(defn.subtract_formula)
+    return mass / defn.multiplier
+
+
+def _compositions(target: float, tolerance: float, limits: Dict[str, Tuple[int, int]]):
+    """``{element: count}`` of every composition within *tolerance* (Da) of *target*."""
+    # Heaviest elements first; the lightest one is solved for directly
+    elements = sorted(limits, key=lambda e: nist_mass[e][0][0], reverse=True)
+    masses = [nist_mass[e][0][0] for e in elements]
+    counts = [0] * len(elements)
+
+    def search(k: int, mass: float):
+        low, high = limits[elements[k]]
+        if k == len(elements) - 1:
+            first = max(low, int(np.ceil((target - tolerance - mass) / masses[k])))
+            last = min(high, int(np.floor((target + tolerance - mass) / masses[k])))
+            for n in range(first, last + 1):
+                counts[k] = n
+                yield dict(zip(elements, counts))
+            return
+        # What the lighter elements can add at least and at most
+        lighter = list(zip(elements[k + 1 :], masses[k + 1 :]))
+        rest_min = sum(limits[e][0] * m for e, m in lighter)
+        rest_max = sum(limits[e][1] * m for e, m in lighter)
+        for n in range(low, high + 1):
+            added = mass + n * masses[k]
+            if added + rest_min > target + tolerance:
+                break
+            if added + rest_max < target - tolerance:
+                continue
+            counts[k] = n
+            yield from search(k + 1, added)
+
+    yield from search(0, 0.0)
+
+
+def generate_formulas(
+    mz: float,
+    adduct: str = "[M+H]+",
+    ppm: float = DEFAULT_FORMULA_PPM,
+    element_limits: dict = None,
+    rdbe_range: Tuple[float, float] = DEFAULT_RDBE_RANGE,
+    integer_rdbe: bool = True,
+    isotope_pattern: Sequence[float] = None,
+    max_candidates: int = 20,
+) -> List[dict]:
+    """
+    Elemental compositions of an observed ion, best candidate first.
+
+    Parameters
+    ----------
+    mz : float
+        Observed monoisotopic m/z.
+    adduct : str
+        Label from ADDUCT_DEFINITIONS the ion was formed as.
+    ppm : float
+        Mass tolerance, relative to the neutral mass.
+    element_limits : dict, optional
+        ``{element: (min, max)}``, DEFAULT_ELEMENT_LIMITS if None.
+    rdbe_range : tuple of float
+        Allowed RDBE of the neutral molecule.
+    integer_rdbe : bool
+        Keep only whole-number RDBE, i.e. even-electron molecules.
+    isotope_pattern : sequence of float, optional
+        Observed ``[1.0, M+1/M, ...]`` to rank the candidates on.
+    max_candidates : int
+        Number of candidates returned.
+
+    Returns
+    -------
+    list of dict
+        ``formula``, ``mass`` (neutral), ``mz``, ``error_ppm``, ``rdbe`` and
+        ``isotope_score`` (None without *isotope_pattern*), sorted by
+        isotope score, then by absolute mass error.
+    """
+    if ppm <= 0:
+        raise ValueError(f"Mass tolerance must be positive, got {ppm} ppm")
+    limits = validate_element_limits(element_limits)
+    target = neutral_mass(mz, adduct)
+    tolerance = target * ppm * 1e-6
+
+    candidates = []
+    for counts in _compositions(target, tolerance, limits):
+        equivalents = rdbe(counts)
+        if not rdbe_range[0] <= equivalents <= rdbe_range[1]:
+            continue
+        if integer_rdbe and equivalents != int(equivalents):
+            continue
+        formula = hill_formula(counts)
+        if not formula:
+            continue
+        mass = sum(n * nist_mass[element][0][0] for element, n in counts.items())
+        candidates.append(
+            {
+                "formula": formula,
+                "mass": mass,
+                "mz": adduct_mz_from_mass(mass, ADDUCT_DEFINITIONS[adduct]),
+                "error_ppm": (target - mass) / mass * 1e6,
+                "rdbe": equivalents,
+                "isotope_score": None,
+            }
+        )
+    candidates.sort(key=lambda candidate: abs(candidate["error_ppm"]))
+
+    if isotope_pattern is not None:
+        observed = np.asarray(isotope_pattern, dtype=np.float64)
+        for candidate in candidates:
+            predicted = isotope_ratios(candidate["formula"], adduct, n_isotopes=len(observed) - 1)
+            candidate["isotope_score"] = isotope_pattern_score(observed, predicted)
+        candidates.sort(key=lambda candidate: -candidate["isotope_score"])
+    logger.debug(f"{len(candidates)} formulas within {ppm} ppm of m/z {mz:.4f} ({adduct})")
+    return candidates[:max_candidates]
+
+
+def annotate_features(features, adduct: str = "[M+H]+", **kwargs):
+    """
+    Copy of a feature table with the best formula of every feature.
+
+    *features* is a calculation.features.detect_features table; the
+    ``formula`` and ``formula_error_ppm`` columns are None where no formula
+    fits. *kwargs* are passed on to generate_formulas.
+    """
+    table = features.copy()
+    best = [generate_formulas(mz, adduct, max_candidates=1, **kwargs) for mz in table["mz"]]
+    table["formula"] = [found[0]["formula"] if found else None for found in best]
+    table["formula_error_ppm"] = [found[0]["error_ppm"] if found else None for found in best]
+    return table
@@ -0,0 +1,131 @@
+"""
+Gap filling of peaks missed in some files of a batch.
+
+An ion whose peak was found in most files but not in one is usually there
+too, just below the peak picker's thresholds. Its XIC, which every file
+keeps, is then integrated over the consensus peak boundaries: the median
+start and end times of the peaks found in the other files (in aligned time
+if the files were RT aligned, see calculation.alignment).
+
+Filled values replace the ion's ``Integration Data`` and ``MS Peak Area``,
+with ``integration_method`` "gap_filled" and ``gap_filled`` True, so they
+can be told apart from detected peaks.
+"""
+
+import logging
+from typing import Dict, Sequence
+
+import numpy as np
+
+from calculation.peak_integration import integrate_peak_manual_boundaries
+
+logger = logging.getLogger(__name__)
+
+DEFAULT_GAP_FILL_FRACTION = 0.5
+
+
+def peak_found(data: dict) -> bool:
+    """Whether an ion has an integrated peak with a positive area."""
+    integration = data.get("Integration Data")
+    if not integration or (integration.get("baseline_corrected_area") or 0) <= 0:
+        return False
+    # Integration always takes the trace maximum; without a detected peak, that is noise
+    peaks = data.get("Peaks")
+    return peaks is None or len(peaks) > 0
+
+
+def _trace(data: dict):
+    """The (2, N) trace peaks were integrated on: smoothed, baseline subtracted."""
+    xic = data.get("MS Intensity")
+    if xic is None or xic.shape[1] == 0:
+        return None
+    trace = data.get("MS Intensity Smoothed")
+    trace = xic if trace is None else trace
+    baseline = data.get("MS Baseline")
+    if baseline is not None:
+        trace = np.array((trace[0], np.clip(trace[1] - baseline[1], 0, None)))
+    return trace
+
+
+def _observed(aligned: float, shift) -> float:
+    """Observed RT of an aligned RT, inverting ``shift.correct`` by fixed-point steps."""
+    observed = aligned - shift(aligned)
+    for _ in range(3):
+        observed = aligned - shift(observed)
+    return float(observed)
+
+
+def fill_gaps(
+    results: Sequence[Sequence],
+    min_fraction: float = DEFAULT_GAP_FILL_FRACTION,
+    rt_shifts: Dict = None,
+) -> int:
+    """
+    Integrate the missing peaks of every ion at its consensus boundaries.
+
+    Parameters
+    ----------
+    results : sequence of sequence of Compound
+        Processed compounds, one sequence per file; filled in place.
+    min_fraction : float
+        Fraction of the files an ion's peak must be found in for the other
+        files to be filled.
+    rt_shifts : dict, optional
+        ``{file name: RTShift}`` of align_retention_times; the boundaries
+        are then taken in aligned time and mapped back to every file.
+
+    Returns
+    -------
+    int
+        Number of gap-filled ions.
+    """
+    if not 0 < min_fraction <= 1:
+        raise ValueError(f"Gap filling fraction must be in (0, 1], got {min_fraction}")
+    rt_shifts = rt_shifts or {}
+    files = [compounds for compounds in results if compounds]
+    needed = max(1, int(np.ceil(min_fraction * len(files))))
+
+    # Boundaries of the found peaks, in aligned time where available
+    boundaries = {}
+    for compounds in files:
+        shift = rt_shifts.get(compounds[0].file)
+        for compound in compounds:
+            for ion, data in compound.ions.items():
+                if not peak_found(data):
+                    continue
+                integration = data["Integration Data"]
+                start, end = integration["start_time"], integration["end_time"]
+                if shift is not None:
+                    start, end = shift.correct(start), shift.correct(end)
+                boundaries.setdefault((compound.name, ion), []).append((start, end))
+
+    filled = 0
+    for compounds in files:
+        shift = rt_shifts.get(compounds[0].file)
+        for compound in compounds:
+            for ion, data in compound.ions.items():
+                found = boundaries.get((compound.name, ion), [])
+                if len(found) < needed or peak_found(data):
+                    continue
+                trace = _trace(data)
+                if trace is None:
+                    continue
+                start, end = np.median(np.array(found), axis=0)
+                if shift is not None:
+                    start, end = _observed(start, shift), _observed(end, shift)
+                try:
+                    integration = integrate_peak_manual_boundaries(
+                        trace[0], trace[1], float(start), float(end)
+                    )
+                except Exception as e:
+                    logger.debug(
+                        f"Cannot gap fill {compound.name} (m/z {ion}) in {compound.file}: {e}"
+                    )
+                    continue
+                integration["integration_method"] = "gap_filled"
+                integration["gap_filled"] = True
+                data["Integration Data"] = integration
+                data["MS Peak Area"] = integration.copy()
+                filled += 1
+    logger.info(f"Gap filled {filled} ions found in at least {needed} of {len(files)} files")
+    return filled
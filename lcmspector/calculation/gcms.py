@@ -0,0 +1,231 @@
+"""
+GC-MS with electron ionization (EI).
+
+EI fragments every molecule in the source, so a GC-EI run is a series of
+full scans without precursor selection: a compound is identified by the
+whole fragment spectrum at its apex, and quantified on the XIC of a
+selected fragment (the quantifier ion) like any other ion list m/z, its
+other fragments (qualifier ions) confirming it by their intensity ratios.
+
+The MS1 loading, XIC extraction and integration are the same as for LC-MS.
+On top of them:
+
+- ei_spectrum: the background-subtracted apex spectrum of a peak (see
+  calculation.subtraction);
+- identify_ei: that spectrum matched against an EI library (e.g. a NIST
+  MSP export) without a precursor filter, see SpectralLibrary.match;
+- ei_ion_list: ion list entries with the most intense library fragments
+  as quantifier and qualifier ions;
+- identify_compounds: ``EI Hits`` of the quantifier ion of every processed
+  compound, and qualifier_ratios its qualifier ion ratios against the
+  library spectrum.
+
+    ion_list = ei_ion_list(library, ["Naphthalene"], n_ions=3)
+    compounds = compounds_from_ion_list(ion_list)
+    # ... process the run as usual ...
+    identify_compounds("run.mzML", compounds, library)
+
+EI libraries are at unit mass resolution, hence the default fragment
+tolerance of EI_FRAGMENT_TOLERANCE Da.
+"""
+
+import logging
+from typing import Iterable, Optional, Sequence, Tuple
+
+import numpy as np
+
+from calculation.subtraction import background_subtracted_spectrum
+
+logger = logging.getLogger(__name__)
+
+EI_FRAGMENT_TOLERANCE = 0.5  # Da
+DEFAULT_QUALIFIER_TOLERANCE = 0.3  # relative deviation of an ion ratio
+QUANTIFIER_LABEL = "quantifier"
+QUALIFIER_LABEL = "qualifier"
+
+
+def ei_spectrum(
+    source,
+    apex_rt: float,
+    start_time: float = None,
+    apex_window: float = 0.02,
+    background_width: float = 0.1,
+    bin_width: float = 0.1,
+) -> Tuple[np.ndarray, np.ndarray]:
+    """
+    Full-scan EI spectrum of a peak at *apex_rt*, background subtracted.
+
+    See calculation.subtraction.background_subtracted_spectrum for the
+    parameters; the windows are narrower than its defaults as GC peaks only
+    last seconds, and the background is subtracted at *bin_width* Da.
+    """
+    return background_subtracted_spectrum(
+        source,
+        apex_rt,
+        start_time=start_time,
+        apex_window=apex_window,
+        background_width=background_width,
+        tolerance=bin_width,
+        tolerance_unit="Da",
+        bin_width=bin_width,
+    )
+
+
+def identify_ei(
+    mz: np.ndarray,
+    intensity: np.ndarray,
+    library,
+    top_n: int = 5,
+    min_score: float = 0.0,
+    fragment_tolerance: float = EI_FRAGMENT_TOLERANCE,
+    **match_options,
+) -> list:
+    """
+    Library hits of an EI spectrum, best first.
+
+    *library* is a SpectralLibrary or LibraryCollection; every spectrum in it
+    is a candidate. *match_options* (``method``, ``rt``, ``ri``, ...) are
+    passed to its match().
+    """
+    if not len(mz):
+        return []
+    return library.match(
+        mz,
+        intensity,
+        None,
+        fragment_tolerance=fragment_tolerance,
+        top_n=top_n,
+        min_score=min_score,
+        **match_options,
+    )
+
+
+def _library_spectrum(library, name: str) -> dict:
+    for index, metadata in enumerate(library.metadata):
+        if str(metadata.get("name", "")).lower() == name.lower():
+            return library.spectrum(index)
+    raise KeyError(f"Compound '{name}' is not in the library")
+
+
+def ei_ion_list(
+    library, names: Iterable[str], n_ions: int = 3, min_mz: float = 50.0
+) -> dict:
+    """
+    Ion list entries of library compounds for quantification on fragments.
+
+    The *n_ions* most intense fragments of each compound's library spectrum
+    above *min_mz* (below it are mostly unspecific fragments and column
+    bleed) become its ``ions``, the most intense one labelled
+    QUANTIFIER_LABEL and the others QUALIFIER_LABEL in ``info``.
+
+    Raises
+    ------
+    KeyError
+        If a name is not in the library (a SpectralLibrary).
+    """
+    if n_ions < 1:
+        raise ValueError(f"At least one ion per compound is needed, got {n_ions}")
+    ion_list = {}
+    for name in names:
+        spectrum = _library_spectrum(library, name)
+        keep = spectrum["mz"] >= min_mz
+        mz, intensity = spectrum["mz"][keep], spectrum["intensity"][keep]
+        if not len(mz):
+            raise KeyError(f"Library spectrum of '{name}' has no fragment above m/z {min_mz}")
+        order = np.argsort(-intensity, kind="stable")[:n_ions]
+        ion_list[spectrum["metadata"].get("name", name)] = {
+            "ions": [float(mz[k]) for k in order],
+            "info": [QUANTIFIER_LABEL] + [QUALIFIER_LABEL] * (len(order) - 1),
+        }
+    return ion_list
+
+
+def _intensity_at(spectrum: dict, mz: float, tolerance: float) -> float:
+    lo = np.searchsorted(spectrum["mz"], mz - tolerance, side="left")
+    hi = np.searchsorted(spectrum["mz"], mz + tolerance, side="right")
+    return float(spectrum["intensity"][lo:hi].max()) if hi > lo else 0.0
+
+
+def _quantifier(compound) -> Optional[float]:
+    """m/z of a compound's quantifier ion: the one labelled so, else the first ion."""
+    for index, ion in enumerate(compound.ions):
+        if compound.get_ion_label(index) == QUANTIFIER_LABEL:
+            return ion
+    return next(iter(compound.ions), None)
+
+
+def qualifier_ratios(
+    compound,
+    library_spectrum: dict,
+    tolerance: float = DEFAULT_QUALIFIER_TOLERANCE,
+    fragment_tolerance: float = EI_FRAGMENT_TOLERANCE,
+) -> dict:
+    """
+    Area ratios of a processed compound's qualifier ions to its quantifier,
+    against the intensity ratios in *library_spectrum* (see
+    SpectralLibrary.spectrum).
+
+    Returns
+    -------
+    dict
+        ``{qualifier m/z: {"ratio", "expected", "deviation", "ok"}}``, the
+        deviation relative to the expected ratio and ``ok`` if it is within
+        *tolerance*; ratios are None without integrated areas, and ``ok``
+        None then.
+    """
+    quantifier = _quantifier(compound)
+    area = (compound.ions[quantifier].get("Integration Data") or {}).get(
+        "baseline_corrected_area"
+    )
+    reference = _intensity_at(library_spectrum, quantifier, fragment_tolerance)
+    ratios = {}
+    for ion, data in compound.ions.items():
+        if ion == quantifier:
+            continue
+        expected = (
+            _intensity_at(library_spectrum, ion, fragment_tolerance) / reference
+            if reference > 0
+            else None
+        )
+        qualifier_area = (data.get("Integration Data") or {}).get("baseline_corrected_area")
+        ratio = qualifier_area / area if area and qualifier_area is not None else None
+        deviation = (
+            (ratio - expected) / expected if ratio is not None and expected else None
+        )
+        ratios[ion] = {
+            "ratio": ratio,
+            "expected": expected,
+            "deviation": deviation,
+            "ok": None if deviation is None else abs(deviation) <= tolerance,
+        }
+    return ratios
+
+
+def identify_compounds(
+    source,
+    compounds: Sequence,
+    library,
+    top_n: int = 5,
+    **match_options,
+) -> int:
+    """
+    Set ``EI Hits`` (see identify_ei) on the quantifier ion of every
+    processed compound with an integrated quantifier peak, from the EI
+    spectrum at its apex; returns the number of compounds with a hit.
+
+    *source* is the MS file or its scans held in memory, see ei_spectrum.
+    """
+    identified = 0
+    for compound in compounds:
+        quantifier = _quantifier(compound)
+        if quantifier is None:
+            continue
+        data = compound.ions[quantifier]
+        integration = data.get("Integration Data")
+        if not integration or data.get("RT") is None:
+            continue
+        mz, intensity = ei_spectrum(source, float(data["RT"]), integration.get("start_time"))
+        data["EI Hits"] = identify_ei(mz, intensity, library, top_n, **match_options)
+        identified += bool(data["EI Hits"])
+    logger.info(f"Identified {identified} of {len(compounds)} compounds from their EI spectra")
+    return identified
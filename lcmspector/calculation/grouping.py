@@ -0,0 +1,197 @@
+"""
+Grouping of untargeted features into compounds.
+
+One compound shows up as several features: adducts ([M+H]+, [M+Na]+,
+[M+NH4]+), dimers ([2M+H]+), other charge states and in-source fragments.
+As in CAMERA, features that co-elute are related by the m/z differences
+these imply:
+
+- adducts: two features whose neutral masses agree, each under its own
+  adduct of utils.theoretical_spectrum.ADDUCT_DEFINITIONS;
+- in-source losses: a lighter feature whose m/z lies a common neutral loss
+  (water, ammonia, CO2, formic acid) below a heavier one.
+
+Related features form a compound group. Within a group the neutral mass
+explaining the most features wins, and those features are annotated with
+their adduct; fragments point at the feature they were lost from.
+"""
+
+import logging
+from collections import defaultdict
+from typing import Dict, List, Sequence
+
+import numpy as np
+import pandas as pd
+
+from utils.mzml_reader import POLARITIES
+from utils.theoretical_spectrum import ADDUCT_DEFINITIONS, adduct_mz_from_mass, monoisotopic_mass
+
+logger = logging.getLogger(__name__)
+
+GROUP_COLUMNS = ("group", "adduct", "neutral_mass", "fragment_of", "neutral_loss")
+DEFAULT_LOSSES = ("H2O", "NH3", "CO2", "CH2O2")
+
+
+def _neutral_masses(mz: np.ndarray, labels: Sequence[str]) -> np.ndarray:
+    """Neutral mass of every feature under every adduct, shape (adducts, features)."""
+    masses = np.empty((len(labels), len(mz)), dtype=np.float64)
+    for k, label in enumerate(labels):
+        defn = ADDUCT_DEFINITIONS[label]
+        offset = adduct_mz_from_mass(0.0, defn) * defn.charge
+        masses[k] = (mz * defn.charge - offset) / defn.multiplier
+    return masses
+
+
+def _coeluting_pairs(rt: np.ndarray, rt_tolerance: float):
+    """Index pairs (i, j), i < j, of features whose apexes lie within *rt_tolerance*."""
+    order = np.argsort(rt, kind="stable")
+    sorted_rt = rt[order]
+    hi = np.searchsorted(sorted_rt, sorted_rt + rt_tolerance, side="right")
+    counts = hi - np.arange(len(rt)) - 1
+    rows = np.repeat(np.arange(len(rt)), counts)
+    cols = rows + 1 + (np.arange(counts.sum()) - np.repeat(np.cumsum(counts) - counts, counts))
+    first, second = order[rows], order[cols]
+    return np.minimum(first, second), np.maximum(first, second)
+
+
+class _Groups:
+    """Union-find over feature indices."""
+
+    def __init__(self, n: int):
+        self.parent = list(range(n))
+
+    def find(self, i: int) -> int:
+        while self.parent[i] != i:
+            self.parent[i] = self.parent[self.parent[i]]
+            i = self.parent[i]
+        return i
+
+    def union(self, i: int, j: int):
+        self.parent[self.find(i)] = self.find(j)
+
+
+def group_features(
+    features: pd.DataFrame,
+    polarity: str = "positive",
+    rt_tolerance: float = 0.05,
+    ppm: float = 10.0,
+    adducts: Sequence[str] = None,
+    losses: Sequence[str] = DEFAULT_LOSSES,
+) -> pd.DataFrame:
+    """
+    Copy of a feature table with its features grouped into compounds.
+
+    Parameters
+    ----------
+    features : pd.DataFrame
+        A calculation.features.detect_features table (``mz``, ``rt``,
+        ``height``).
+    polarity : str
+        "positive" or "negative"; selects the adducts considered.
+    rt_tolerance : float
+        Apex RT difference (min) up to which features co-elute.
+    ppm : float
+        Tolerance on neutral masses and neutral losses.
+    adducts : sequence of str, optional
+        Labels from ADDUCT_DEFINITIONS; by default every adduct of *polarity*.
+    losses : sequence of str
+        Molecular formulas of the in-source neutral losses looked for.
+
+    Returns
+    -------
+    pd.DataFrame
+        The table with GROUP_COLUMNS added: ``group`` (number of the compound
+        group, ungrouped features have one of their own), ``adduct`` and
+        ``neutral_mass`` (None unless explained by an adduct relation),
+        ``fragment_of`` (row label of the feature an in-source fragment came
+        from) and ``neutral_loss`` (its formula).
+
+    Raises
+    ------
+    ValueError
+        On an unknown polarity or adduct, an adduct of the other polarity or
+        an invalid loss formula.
+    """
+    if polarity not in POLARITIES:
+        raise ValueError(f"Unknown polarity '{polarity}', expected one of {POLARITIES}")
+    if adducts is None:
+        adducts = [label for label, defn in ADDUCT_DEFINITIONS.items() if defn.polarity == polarity]
+    for label in adducts:
+        defn = ADDUCT_DEFINITIONS.get(label)
+        if defn is None:
+            raise ValueError(
+                f"Unknown adduct '{label}', expected one of {list(ADDUCT_DEFINITIONS)}"
+            )
+        if defn.polarity != polarity:
+            raise ValueError(f"Adduct {label} is not a {polarity} ion")
+    loss_masses = {formula: monoisotopic_mass(formula) for formula in losses}
+
+    table = features.copy()
+    n = len(table)
+    mz = table["mz"].to_numpy(dtype=np.float64)
+    height = table["height"].to_numpy(dtype=np.float64) if "height" in table else np.ones(n)
+    masses = _neutral_masses(mz, adducts)
+    first, second = _coeluting_pairs(table["rt"].to_numpy(dtype=np.float64), rt_tolerance)
+
+    groups = _Groups(n)
+    # Adduct hypotheses: (feature, adduct index, neutral mass), keyed by group later
+    hypotheses = []
+    if len(first):
+        a, b = masses[:, first], masses[:, second]  # (adducts, pairs)
+        diff = np.abs(a[:, None, :] - b[None, :, :])  # (adducts, adducts, pairs)
+        tolerance = np.maximum(a[:, None, :], b[None, :, :]) * ppm * 1e-6
+        same = np.eye(len(adducts), dtype=bool)[:, :, None]
+        k_a, k_b, pairs = np.nonzero((diff <= tolerance) & ~same)
+        for ka, kb, p in zip(k_a, k_b, pairs):
+            i, j = int(first[p]), int(second[p])
+            groups.union(i, j)
+            mass = (masses[ka, i] + masses[kb, j]) / 2
+            hypotheses += [(i, int(ka), mass), (j, int(kb), mass)]
+
+    fragment_of: Dict[int, tuple] = {}
+    if len(first) and loss_masses:
+        heavy = np.where(mz[first] >= mz[second], first, second)
+        light = np.where(mz[first] >= mz[second], second, first)
+        delta = mz[heavy] - mz[light]
+        for formula, loss in loss_masses.items():
+            for p in np.flatnonzero(np.abs(delta - loss) <= mz[heavy] * ppm * 1e-6):
+                i, j = int(heavy[p]), int(light[p])
+                if j not in fragment_of or height[i] > height[fragment_of[j][0]]:
+                    fragment_of[j] = (i, formula)
+                groups.union(i, j)
+
+    # Per group, the neutral mass explaining the most features
+    by_group: Dict[int, List[tuple]] = defaultdict(list)
+    for feature, k, mass in hypotheses:
+        by_group[groups.find(feature)].append((feature, k, mass))
+    adduct_of: Dict[int, tuple] = {}
+    for candidates in by_group.values():
+        best, best_key = None, None
+        for _, _, mass in candidates:
+            support = {
+                f: (k, m) for f, k, m in candidates if abs(m - mass) <= mass * ppm * 1e-6
+            }
+            key = (len(support), sum(height[f] for f in support))
+            if best_key is None or key > best_key:
+                best, best_key = support, key
+        neutral = float(np.mean([m for _, m in best.values()]))
+        for feature, (k, _) in best.items():
+            adduct_of[feature] = (adducts[k], neutral)
+    # An adduct explains a feature better than a loss, e.g. [M+H-H2O]+ of [M+H]+
+    fragment_of = {j: parent for j, parent in fragment_of.items() if j not in adduct_of}
+
+    roots = [groups.find(i) for i in range(n)]
+    numbers = {root: number for number, root in enumerate(dict.fromkeys(roots))}
+    labels = table.index
+    table["group"] = [numbers[root] for root in roots]
+    table["adduct"] = [adduct_of[i][0] if i in adduct_of else None for i in range(n)]
+    table["neutral_mass"] = [adduct_of[i][1] if i in adduct_of else None for i in range(n)]
+    table["fragment_of"] = [
+        labels[fragment_of[i][0]] if i in fragment_of else None for i in range(n)
+    ]
+    table["neutral_loss"] = [fragment_of[i][1] if i in fragment_of else None for i in range(n)]
+    logger.info(
+        f"Grouped {n} features into {len(numbers)} compound groups, "
+        f"{len(adduct_of)} with an adduct and {len(fragment_of)} in-source fragments"
+    )
+    return table
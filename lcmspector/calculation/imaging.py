@@ -0,0 +1,102 @@
+"""
+Ion images from mass spectrometry imaging (imzML) data.
+
+An ion image is the XIC of an MSI run laid out on the pixel grid instead of
+the time axis: every pixel's spectrum is summed in the ion's m/z window,
+using the same windows and summation as calculation.preprocessing.build_xics.
+"""
+
+import logging
+
+import numpy as np
+
+from calculation.preprocessing import compound_mz_ranges, mz_windows, sum_mz_windows
+from utils.errors import IonListError
+from utils.imzml_reader import iter_pixels
+
+logger = logging.getLogger(__name__)
+
+
+def ion_images(
+    filepath,
+    ion_list,
+    mass_accuracy: float = 0.0001,
+    custom_ranges: dict = None,
+    ibd=None,
+    polarity: str = None,
+) -> np.ndarray:
+    """
+    Extract the ion images of a list of m/z values from an imzML dataset.
+
+    Parameters
+    ----------
+    filepath : str or Path
+        The .imzML file (see utils.imzml_reader.iter_pixels for *ibd* and
+        *polarity*).
+    ion_list : array-like
+        Target m/z values.
+    mass_accuracy : float
+        Relative mass accuracy, see calculation.preprocessing.mz_windows.
+    custom_ranges : dict, optional
+        Per-ion m/z range overrides: ``{mz_float: (lower, upper)}``.
+
+    Returns
+    -------
+    np.ndarray
+        float32 array of shape ``(n_ions, height, width)``, i.e. indexed
+        ``[ion, y - 1, x - 1]``. Pixels without a spectrum are NaN.
+    """
+    lower, upper = mz_windows(ion_list, mass_accuracy, custom_ranges)
+    positions, rows = [], []
+    for x, y, mz_array, intensity_array in iter_pixels(filepath, ibd=ibd, polarity=polarity):
+        if len(mz_array) > 1 and np.any(np.diff(mz_array) < 0):
+            order = np.argsort(mz_array, kind="stable")
+            mz_array, intensity_array = mz_array[order], intensity_array[order]
+        positions.append((x, y))
+        rows.append(sum_mz_windows(mz_array, intensity_array, lower, upper))
+
+    if not positions:
+        logger.warning(f"No pixel spectra found in {filepath}")
+        return np.full((len(lower), 0, 0), np.nan, dtype=np.float32)
+    positions = np.asarray(positions, dtype=np.int64)
+    width, height = positions.max(axis=0)
+    images = np.full((len(lower), height, width), np.nan, dtype=np.float32)
+    images[:, positions[:, 1] - 1, positions[:, 0] - 1] = np.asarray(rows).T
+    logger.info(f"Extracted {len(lower)} ion images of {width}x{height} pixels from {filepath}")
+    return images
+
+
+def compound_ion_images(
+    filepath, compounds, mass_accuracy: float = 0.0001, ibd=None, polarity: str = None
+) -> dict:
+    """
+    Extract the ion image of every ion of every compound.
+
+    Ion list tolerances and m/z ranges drawn in the UI apply as in
+    calculation.preprocessing.construct_xics. *compounds* may also be a
+    plain ion list (see utils.classes.compounds_from_ion_list).
+
+    Returns
+    -------
+    dict
+        ``{compound name: {ion m/z: image}}``, images as in ion_images.
+
+    Raises
+    ------
+    IonListError
+        If there are no compounds.
+    """
+    from utils.classes import compounds_from_ion_list
+
+    compounds = tuple(compounds_from_ion_list(compounds or ()))
+    if not compounds:
+        raise IonListError(f"No compounds to extract from {filepath}, pass an ion list")
+    target_mzs = np.unique([mz for cmpd in compounds for mz in cmpd.ions])
+    images = ion_images(
+        filepath, target_mzs, mass_accuracy,
+        custom_ranges=compound_mz_ranges(compounds) or None,
+        ibd=ibd,
+        polarity=polarity,
+    )
+    column = {mz: index for index, mz in enumerate(target_mzs)}
+    return {cmpd.name: {mz: images[column[mz]] for mz in cmpd.ions} for cmpd in compounds}
@@ -0,0 +1,74 @@
+"""
+Isotopologue traces and isotope pattern scoring.
+
+The M+1/M+2 traces of a compound co-elute with the monoisotopic one at
+intensities fixed by its elemental composition (mostly 13C), so comparing
+the observed ratios to the ones predicted from a formula is a cheap check
+that an XIC peak really is the target and not an isobaric interference.
+"""
+
+import logging
+from typing import Optional, Sequence, Tuple
+
+import numpy as np
+
+logger = logging.getLogger(__name__)
+
+# 13C - 12C mass difference, the dominant isotope spacing for organic molecules
+ISOTOPE_SPACING = 1.0033548
+
+
+def isotopologue_mzs(mz: float, charge: int = 1, n_isotopes: int = 2) -> list:
+    """m/z of the M+1 ... M+n isotopologues of an ion with the given charge."""
+    return [mz + k * ISOTOPE_SPACING / abs(charge) for k in range(1, n_isotopes + 1)]
+
+
+def observed_isotope_ratios(
+    monoisotopic: np.ndarray,
+    isotopologues: Sequence[np.ndarray],
+    window: Optional[Tuple[float, float]] = None,
+) -> Optional[np.ndarray]:
+    """
+    Intensity ratios of the isotopologue traces to the monoisotopic trace.
+
+    Parameters
+    ----------
+    monoisotopic : np.ndarray
+        (2, N) XIC of the monoisotopic ion.
+    isotopologues : sequence of np.ndarray
+        (2, N) XICs of M+1, M+2, ... on the same time axis.
+    window : tuple of float, optional
+        ``(start, end)`` in minutes, e.g. the integrated peak; intensities are
+        summed over it. Defaults to the whole trace.
+
+    Returns
+    -------
+    np.ndarray or None
+        ``[1.0, M+1/M, M+2/M, ...]``, or None without monoisotopic signal.
+    """
+    times = monoisotopic[0]
+    mask = np.ones(len(times), dtype=bool)
+    if window is not None:
+        mask = (times >= window[0]) & (times <= window[1])
+    mono_sum = float(np.sum(monoisotopic[1][mask], dtype=np.float64))
+    if mono_sum <= 0:
+        return None
+    ratios = [float(np.sum(trace[1][mask], dtype=np.float64)) / mono_sum for trace in isotopologues]
+    return np.array([1.0, *ratios])
+
+
+def isotope_pattern_score(observed: np.ndarray, predicted: np.ndarray) -> float:
+    """
+    Agreement between observed and predicted isotope patterns.
+
+    Both patterns are relative to the monoisotopic peak (first entry 1.0).
+    The score is ``1 - sum|observed - predicted| / sum(predicted)``, clipped
+    to [0, 1]; 1.0 is a perfect match.
+    """
+    observed = np.asarray(observed, dtype=np.float64)
+    predicted = np.asarray(predicted, dtype=np.float64)
+    n = min(len(observed), len(predicted))
+    if n == 0 or predicted[:n].sum() <= 0:
+        return 0.0
+    deviation = np.abs(observed[:n] - predicted[:n]).sum() / predicted[:n].sum()
+    return float(np.clip(1.0 - deviation, 0.0, 1.0))
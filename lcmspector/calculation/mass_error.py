@@ -0,0 +1,103 @@
+"""
+Mass error statistics of the integrated ions.
+
+A compound's m/z error beyond the instrument's accuracy hints at a wrong
+identification; the same error on every compound hints at a calibration
+problem instead. For every integrated ion, the most intense peak in its m/z
+window (``+-3 * mass_accuracy * mz`` or its own tolerance, as for the XIC)
+is taken from every spectrum across the integrated peak, and its error
+relative to the target m/z summarized in the ion's ``Mass Error`` dict:
+
+    {"apex_mz": 200.0006, "apex_error_ppm": 3.0, "mean_ppm": 2.8,
+     "median_ppm": 2.9, "sd_ppm": 0.6, "n": 14}
+
+construct_xics computes them with *mass_errors* on the processed spectra
+the XICs were extracted from (lock-mass corrected, centroided, ...), see
+calculation.preprocessing.peak_spectra.
+"""
+
+import logging
+
+import numpy as np
+
+logger = logging.getLogger(__name__)
+
+
+def observed_mz(spectrum, ion: float, tolerance: float, window=None):
+    """
+    m/z of the most intense peak within *tolerance* (relative) of *ion*, or
+    within its ``(lower, upper)`` m/z *window* if given; None without one.
+    """
+    mz = np.asarray(spectrum["m/z array"], dtype=np.float64)
+    intensity = np.asarray(spectrum["intensity array"], dtype=np.float64)
+    lower, upper = window if window is not None else (ion - ion * tolerance, ion + ion * tolerance)
+    in_window = (mz >= lower) & (mz <= upper) & (intensity > 0)
+    if not np.any(in_window):
+        return None
+    return float(mz[in_window][np.argmax(intensity[in_window])])
+
+
+def ion_mass_error(
+    reader, ion: float, data: dict, mass_accuracy: float = 0.0001, window=None
+):
+    """
+    ``Mass Error`` of an ion's integrated peak, see the module docstring.
+
+    *reader* is an indexed reader with ``.time[rt]`` access, see
+    utils.loading.load_ms_data, or the spectra of
+    calculation.preprocessing.peak_spectra. *window* is the ion's custom
+    ``(lower, upper)`` m/z range, if it has one. Returns None if the ion has
+    no integrated peak or no spectrum across it has a peak in the window.
+    """
+    integration = data.get("Integration Data")
+    xic = data.get("MS Intensity")
+    rt = data.get("RT")
+    if not integration or rt is None or xic is None or xic.shape[1] == 0:
+        return None
+    times = xic[0][
+        (xic[0] >= integration["start_time"]) & (xic[0] <= integration["end_time"]) & (xic[1] > 0)
+    ]
+    errors, apex_mz = [], None
+    apex_time = times[np.argmin(np.abs(times - rt))] if len(times) else None
+    for time in times:
+        try:
+            mz = observed_mz(reader.time[float(time)], ion, 3 * mass_accuracy, window)
+        except Exception as e:
+            logger.debug(f"Cannot read the spectrum at {time} for m/z {ion}: {e}")
+            continue
+        if mz is None:
+            continue
+        errors.append((mz - ion) / ion * 1e6)
+        if time == apex_time:
+            apex_mz = mz
+    if not errors:
+        return None
+    errors = np.asarray(errors)
+    return {
+        "apex_mz": apex_mz,
+        "apex_error_ppm": None if apex_mz is None else (apex_mz - ion) / ion * 1e6,
+        "mean_ppm": float(errors.mean()),
+        "median_ppm": float(np.median(errors)),
+        "sd_ppm": float(errors.std(ddof=1)) if len(errors) > 1 else 0.0,
+        "n": len(errors),
+    }
+
+
+def peak_mass_errors(
+    compounds, reader, mass_accuracy: float = 0.0001, custom_ranges: dict = None
+) -> int:
+    """
+    Set ``Mass Error`` on every ion of a processed file; returns the number set.
+
+    *custom_ranges* are the ``{mz: (lower, upper)}`` m/z windows of the ions
+    with their own, see calculation.preprocessing.compound_mz_ranges.
+    """
+    custom_ranges = custom_ranges or {}
+    found = 0
+    for compound in compounds:
+        for ion, data in compound.ions.items():
+            data["Mass Error"] = ion_mass_error(
+                reader, ion, data, mass_accuracy, custom_ranges.get(ion)
+            )
+            found += data["Mass Error"] is not None
+    return found
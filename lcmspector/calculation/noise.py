@@ -0,0 +1,64 @@
+"""
+Noise thresholds applied to every scan before XIC extraction.
+
+Summing an m/z window over chemical noise gives every XIC of a
+low-abundance target a baseline of its own. Peaks below the threshold of
+their scan are dropped before the windows are summed: an absolute
+intensity, a percentage of the scan's base peak, or the higher of both.
+
+Settings are plain dicts like the smoothing ones:
+
+    {"absolute": 500.0, "relative": 0.1}
+"""
+
+from typing import Optional
+
+import numpy as np
+
+DEFAULT_NOISE_THRESHOLD = {"absolute": 0.0, "relative": 0.0}
+
+
+def validate_noise_threshold(settings: dict) -> Optional[dict]:
+    """
+    Fill in defaults and validate noise threshold settings.
+
+    Parameters
+    ----------
+    settings : dict or None
+        ``absolute`` (intensity) and ``relative`` (percent of the base peak
+        intensity of each scan, 0-100).
+
+    Returns
+    -------
+    dict or None
+        A new dict with every key present, or None if both thresholds are 0.
+
+    Raises
+    ------
+    ValueError
+        On unknown keys, negative thresholds or a relative one above 100 %.
+    """
+    unknown = set(settings or {}) - set(DEFAULT_NOISE_THRESHOLD)
+    if unknown:
+        raise ValueError(
+            f"Unknown noise threshold settings {sorted(unknown)}, "
+            f"expected any of {tuple(DEFAULT_NOISE_THRESHOLD)}"
+        )
+    merged = {**DEFAULT_NOISE_THRESHOLD, **(settings or {})}
+    absolute, relative = float(merged["absolute"]), float(merged["relative"])
+    if absolute < 0 or relative < 0:
+        raise ValueError(f"Noise thresholds must not be negative, got {merged}")
+    if relative > 100:
+        raise ValueError(f"Relative noise threshold must be at most 100 %, got {relative}")
+    if absolute == 0 and relative == 0:
+        return None
+    return {"absolute": absolute, "relative": relative}
+
+
+def above_noise(intensity: np.ndarray, absolute: float = 0.0, relative: float = 0.0) -> np.ndarray:
+    """Boolean mask of the peaks at or above the noise threshold of their scan."""
+    intensity = np.asarray(intensity)
+    threshold = absolute
+    if relative > 0 and len(intensity):
+        threshold = max(threshold, relative / 100 * float(intensity.max()))
+    return intensity >= threshold
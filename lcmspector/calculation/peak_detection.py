@@ -0,0 +1,155 @@
+"""
+Chromatographic peak detection for XIC traces.
+
+Unlike integrate_ms_xic_peak, which integrates the single peak closest to a
+target retention time, detect_peaks reports every peak in a trace together
+with its shape descriptors, so isomers and interferences can be inspected
+and quantified without a user-picked target RT.
+"""
+
+import logging
+from typing import Dict, List, Union
+
+import numpy as np
+from scipy.signal import find_peaks, peak_widths
+
+from calculation.peak_integration import (
+    calculate_baseline_linear,
+    estimate_noise,
+    integrate_peak_area_trapezoidal,
+)
+
+logger = logging.getLogger(__name__)
+
+# Relative heights (measured down from the apex) used for the peak descriptors
+_FWHM_REL_HEIGHT = 0.5
+_ASYMMETRY_REL_HEIGHT = 0.9  # i.e. at 10 % of the peak height
+_BOUNDARY_REL_HEIGHT = 0.98
+
+
+def _index_to_time(times: np.ndarray, positions: np.ndarray) -> np.ndarray:
+    """Convert fractional sample positions (as returned by peak_widths) to times."""
+    return np.interp(positions, np.arange(len(times)), times)
+
+
+def detect_peaks(
+    times: np.ndarray,
+    intensities: np.ndarray,
+    min_height: float = None,
+    min_prominence: float = None,
+    min_width_scans: int = 3,
+    max_peaks: int = None,
+) -> List[Dict[str, Union[float, int]]]:
+    """
+    Detect and characterize all chromatographic peaks in a trace.
+
+    Parameters
+    ----------
+    times : np.ndarray
+        Retention times in minutes, ascending.
+    intensities : np.ndarray
+        Intensities corresponding to *times*.
+    min_height : float, optional
+        Minimum apex intensity. Defaults to twice the standard deviation of
+        the trace.
+    min_prominence : float, optional
+        Minimum prominence. Defaults to the same adaptive threshold used by
+        find_peak_maximum: ``max(3 * std, 0.5 % of max)``.
+    min_width_scans : int
+        Minimum full width at half maximum, in scans.
+    max_peaks : int, optional
+        Only keep the *max_peaks* most intense peaks.
+
+    Returns
+    -------
+    list of dict
+        One dict per peak, sorted by apex retention time, with keys
+        ``apex_rt``, ``apex_index``, ``start_time``, ``end_time``,
+        ``start_index``, ``end_index``, ``height``, ``area``,
+        ``baseline_corrected_area``, ``fwhm`` (min), ``asymmetry``
+        (tailing side over fronting side at 10 % height; 1.0 is symmetric)
+        and ``snr`` (height over the local noise, see
+        calculation.peak_integration.estimate_noise; NaN if the trace has too
+        few peak-free points, inf for a noise-free trace).
+    """
+    times = np.asarray(times, dtype=np.float64)
+    intensities = np.asarray(intensities, dtype=np.float64)
+    if len(times) != len(intensities):
+        raise ValueError("times and intensities must have same length")
+    if len(times) < 3 or not np.any(intensities > 0):
+        return []
+
+    signal_std = np.std(intensities)
+    if min_height is None:
+        min_height = signal_std * 2
+    if min_prominence is None:
+        min_prominence = max(signal_std * 3, np.max(intensities) * 0.005)
+
+    peaks, _ = find_peaks(
+        intensities,
+        height=min_height,
+        prominence=min_prominence,
+        width=min_width_scans,
+        rel_height=_FWHM_REL_HEIGHT,
+    )
+    if len(peaks) == 0:
+        return []
+
+    if max_peaks is not None and len(peaks) > max_peaks:
+        keep = np.argsort(intensities[peaks])[::-1][:max_peaks]
+        peaks = np.sort(peaks[keep])
+
+    fwhm_widths = peak_widths(intensities, peaks, rel_height=_FWHM_REL_HEIGHT)
+    asym_widths = peak_widths(intensities, peaks, rel_height=_ASYMMETRY_REL_HEIGHT)
+    bounds = peak_widths(intensities, peaks, rel_height=_BOUNDARY_REL_HEIGHT)
+
+    fwhm_left = _index_to_time(times, fwhm_widths[2])
+    fwhm_right = _index_to_time(times, fwhm_widths[3])
+    asym_left = _index_to_time(times, asym_widths[2])
+    asym_right = _index_to_time(times, asym_widths[3])
+
+    results = []
+    for i, apex in enumerate(peaks):
+        start_idx = int(np.floor(bounds[2][i]))
+        end_idx = min(int(np.ceil(bounds[3][i])), len(times) - 1)
+        if end_idx <= start_idx:
+            continue
+
+        baseline = calculate_baseline_linear(intensities, start_idx, end_idx)
+        area, corrected_area = integrate_peak_area_trapezoidal(
+            times, intensities, baseline, start_idx, end_idx
+        )
+
+        apex_rt = times[apex]
+        front = apex_rt - asym_left[i]
+        tail = asym_right[i] - apex_rt
+        asymmetry = tail / front if front > 0 else float("nan")
+
+        results.append(
+            {
+                "apex_rt": float(apex_rt),
+                "apex_index": int(apex),
+                "start_time": float(times[start_idx]),
+                "end_time": float(times[end_idx]),
+                "start_index": start_idx,
+                "end_index": end_idx,
+                "height": float(intensities[apex] - baseline[apex - start_idx]),
+                "area": float(area),
+                "baseline_corrected_area": float(corrected_area),
+                "fwhm": float(fwhm_right[i] - fwhm_left[i]),
+                "asymmetry": float(asymmetry),
+            }
+        )
+
+    # Noise from the points outside all of the peaks
+    peak_ranges = [(peak["start_index"], peak["end_index"]) for peak in results]
+    for peak in results:
+        noise = estimate_noise(intensities, peak_ranges, peak["start_index"], peak["end_index"])
+        if noise is None:
+            peak["snr"] = float("nan")
+        elif noise == 0:
+            peak["snr"] = float("inf")
+        else:
+            peak["snr"] = peak["height"] / noise
+
+    return results
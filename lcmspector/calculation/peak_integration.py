@@ -7,7 +7,7 @@ with automatic boundary detection.
 """
 
 import numpy as np
-from typing import Dict, Tuple, Union
+from typing import Dict, Optional, Sequence, Tuple, Union
 from scipy.signal import find_peaks
 
 try:
@@ -20,6 +20,13 @@ import copy
 
 logger = logging.getLogger(__name__)
 
+# Points on either side of a peak used for its local noise estimate
+NOISE_WINDOW = 20
+# Fewer peak-free points than this give no reliable noise estimate
+MIN_NOISE_POINTS = 5
+# Scales the median absolute deviation to the standard deviation of Gaussian noise
+MAD_TO_SD = 1.4826
+
 
 class PeakIntegrationError(Exception):
     """Base exception for peak integration errors."""
@@ -491,6 +498,53 @@ def calculate_baseline_linear(
     return baseline
 
 
+def estimate_noise(
+    intensities: np.ndarray,
+    peak_ranges: Sequence[Tuple[int, int]],
+    start_index: int = None,
+    end_index: int = None,
+    window: int = NOISE_WINDOW,
+) -> Optional[float]:
+    """
+    Robust noise level of a trace from its peak-free points.
+
+    Parameters
+    ----------
+    intensities : np.ndarray
+        Trace intensities.
+    peak_ranges : sequence of (int, int)
+        Inclusive ``(start, end)`` indices of every peak; excluded from the
+        estimate.
+    start_index, end_index : int, optional
+        Peak the estimate is for. Only the *window* points on either side of
+        it are used, unless fewer than MIN_NOISE_POINTS of them are peak-free;
+        then (as without a peak) the whole trace is.
+    window : int
+        Points on either side of the peak, see above.
+
+    Returns
+    -------
+    float or None
+        ``MAD_TO_SD`` times the median absolute deviation of the peak-free
+        intensities, or None with fewer than MIN_NOISE_POINTS of them.
+    """
+    intensities = np.asarray(intensities, dtype=np.float64)
+    peak_free = np.ones(len(intensities), dtype=bool)
+    for start, end in peak_ranges:
+        peak_free[max(start, 0) : end + 1] = False
+
+    values = intensities[peak_free]
+    if start_index is not None and end_index is not None:
+        local = np.zeros(len(intensities), dtype=bool)
+        local[max(0, start_index - window) : end_index + window + 1] = True
+        if np.count_nonzero(local & peak_free) >= MIN_NOISE_POINTS:
+            values = intensities[local & peak_free]
+
+    if len(values) < MIN_NOISE_POINTS:
+        return None
+    return float(MAD_TO_SD * np.median(np.abs(values - np.median(values))))
+
+
 def calculate_peak_quality_metrics(
     times: np.ndarray,
     intensities: np.ndarray,
@@ -553,7 +607,10 @@ def calculate_peak_quality_metrics(
         noise_regions.extend(intensities[right_start:right_end])
 
     if len(noise_regions) > 0:
-        noise = np.std(noise_regions)
+        # MAD of the peak-free surroundings, robust against neighbouring peaks
+        noise = estimate_noise(intensities, [(start_index, end_index)], start_index, end_index)
+        if noise is None:
+            noise = np.std(noise_regions)
         # Ensure noise estimate is reasonable (not too small)
         noise = max(noise, np.mean(noise_regions) * 0.01)
     else:
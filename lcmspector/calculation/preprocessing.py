@@ -1,14 +1,67 @@
 import logging
+from collections import deque
+from concurrent.futures import ThreadPoolExecutor
 import numpy as np
 import pandas as pd
 import static_frame as sf
 from pathlib import Path
 from typing import Tuple
+from calculation.centroiding import centroid_spectrum
+from calculation.deconvolution import (
+    deconvolute_spectrum,
+    singly_charged_mz,
+    validate_deconvolution,
+)
+from calculation.isotopes import (
+    isotope_pattern_score,
+    isotopologue_mzs,
+    observed_isotope_ratios,
+)
+from calculation.baseline import estimate_baseline, subtract_baseline, validate_baseline
+from calculation.noise import above_noise, validate_noise_threshold
+from calculation.peak_detection import detect_peaks
+from calculation.peak_fitting import PEAK_MODELS, fit_overlapping_peaks
 from calculation.peak_integration import integrate_ms_xic_peak
-from utils.mzml_reader import iter_scans
+from calculation.recalibration import LockMassCorrector, validate_lock_mass
+from calculation.smoothing import smooth_trace, validate_smoothing
+from utils.loading import iter_ms2_scans, iter_ms_scans, iter_srm_chromatograms
+from utils.errors import IonListError
+from utils.mzml_reader import validate_polarity
+from utils.scan_filter import validate_scan_filter
+from utils.theoretical_spectrum import adduct_definition, isotope_ratios
 
 logger = logging.getLogger(__name__)
 
+# Number of scans between two checks of the cancellation event
+_CANCEL_CHECK_INTERVAL = 50
+# Scans handed to an extraction thread at a time when build_xics runs in parallel
+_SCAN_CHUNK_SIZE = 64
+
+# MS2 spectra kept per ion by link_ms2_spectra, closest to the XIC apex first
+MAX_LINKED_MS2 = 3
+
+# m/z (Da) below and above an ion kept in the spectra across its peak by
+# peak_spectra: the purity window of calculation.purity and the isotope peaks
+# of a singly charged ion looked for by calculation.charge
+PEAK_SPECTRUM_WINDOW = (-2.0, 6.0)
+
+# Q1/Q3 matching tolerance (Da) for SRM transitions; triple quads run at unit resolution
+SRM_TOLERANCE = 0.5
+
+# Floating-point precision of the target m/z windows, XIC times and intensities.
+# float32 resolves m/z to about 0.06 ppm (6e-5 Da at m/z 1000), float64 for
+# high-resolution data at high m/z
+PRECISIONS = ("float32", "float64")
+# Intensity of a target in a scan: summed over its m/z window, the window's
+# most intense peak, or the peak closest to the target m/z
+XIC_MODES = ("sum", "max", "nearest")
+
+
+class ProcessingCancelled(Exception):
+    """Raised inside a worker when XIC extraction was cancelled from the UI."""
+
+    pass
+
 
 def baseline_correction(dataframe: pd.DataFrame) -> sf.FrameHE:
     """
@@ -97,9 +150,176 @@ def baseline_correction(dataframe: pd.DataFrame) -> sf.FrameHE:
 #     return mz_axis
 
 
+def precision_dtype(precision: str) -> np.dtype:
+    """The numpy dtype of one of PRECISIONS, raising ValueError on others."""
+    if precision not in PRECISIONS:
+        raise ValueError(f"Unknown precision '{precision}', expected one of {PRECISIONS}")
+    return np.dtype(precision)
+
+
+def mz_windows(
+    target_mzs: np.ndarray, mass_accuracy: float, custom_ranges: dict = None,
+    dtype=np.float32,
+) -> Tuple[np.ndarray, np.ndarray]:
+    """
+    The (lower, upper) m/z bounds summed into the XIC of every target.
+
+    Targets are extracted within three times *mass_accuracy* (relative) on
+    either side, unless *custom_ranges* holds a ``{mz: (lower, upper)}``
+    override for them. Bounds are computed in *dtype*; the overrides are
+    looked up by the float64 values of *target_mzs*, as ion list m/z are
+    keyed.
+    """
+    keys = np.asarray(target_mzs, dtype=np.float64)
+    target_mzs = keys.astype(dtype)
+    delta = target_mzs * mass_accuracy * 3
+    lower = target_mzs - delta
+    upper = target_mzs + delta
+    if custom_ranges:
+        for i, mz in enumerate(keys):
+            if mz in custom_ranges:
+                lower[i], upper[i] = custom_ranges[mz]
+    return lower, upper
+
+
+def _sum_mz_windows_loop(
+    intensity_array: np.ndarray, left_idx, right_idx, dtype=np.float32
+) -> np.ndarray:
+    """Reference implementation of sum_mz_windows, one slice per window."""
+    row = np.zeros(len(left_idx), dtype=dtype)
+    for ion_idx, (left, right) in enumerate(zip(left_idx, right_idx)):
+        if left < right:  # Only sum if we have values in range
+            row[ion_idx] = np.sum(intensity_array[left:right])
+    return row
+
+
+def sum_mz_windows(
+    mz_array: np.ndarray, intensity_array: np.ndarray, lower: np.ndarray, upper: np.ndarray,
+    dtype=np.float32,
+) -> np.ndarray:
+    """
+    Summed intensity of an m/z-sorted spectrum in every [lower, upper] window, as *dtype*.
+
+    The window bounds are binary searched, and the sums taken as differences
+    of the running total of the intensities: two vectorized passes over the
+    spectrum instead of a slice and sum per target, which dominates
+    extraction time with long ion lists (see scripts/benchmark_extraction.py).
+    Spectra with non-finite intensities, which would spoil the running total
+    of every later window, are summed window by window.
+    """
+    left_idx = np.searchsorted(mz_array, lower, side="left")
+    right_idx = np.searchsorted(mz_array, upper, side="right")
+    totals = np.empty(len(intensity_array) + 1, dtype=np.float64)
+    totals[0] = 0.0
+    np.cumsum(intensity_array, dtype=np.float64, out=totals[1:])
+    if not np.isfinite(totals[-1]):
+        return _sum_mz_windows_loop(intensity_array, left_idx, right_idx, dtype)
+    left_idx = np.minimum(left_idx, right_idx)  # Empty (or reversed) windows sum to 0
+    return (totals[right_idx] - totals[left_idx]).astype(dtype)
+
+
+def validate_xic_mode(mode: str) -> str:
+    """Return *mode* if it is one of XIC_MODES, else raise ValueError."""
+    if mode not in XIC_MODES:
+        raise ValueError(f"Unknown XIC mode '{mode}', expected one of {XIC_MODES}")
+    return mode
+
+
+def _window_intensity(mz_array, intensity_array, target: float, mode: str) -> float:
+    """Intensity of the peaks of one window (non-empty), see XIC_MODES."""
+    if mode == "sum":
+        return np.sum(intensity_array)
+    if mode == "max":
+        return np.max(intensity_array)
+    return intensity_array[np.argmin(np.abs(mz_array - target))]
+
+
+def extract_mz_windows(
+    mz_array: np.ndarray, intensity_array: np.ndarray, lower: np.ndarray, upper: np.ndarray,
+    mode: str = "sum", targets: np.ndarray = None, dtype=np.float32,
+) -> np.ndarray:
+    """
+    Intensity of an m/z-sorted spectrum in every [lower, upper] window, as *dtype*.
+
+    *mode* is one of XIC_MODES; "sum" is sum_mz_windows. For "nearest",
+    *targets* are the m/z values the peaks should be closest to, by default
+    the window centres. Empty windows are 0.
+    """
+    if mode == "sum":
+        return sum_mz_windows(mz_array, intensity_array, lower, upper, dtype)
+    if targets is None:
+        targets = (np.asarray(lower) + np.asarray(upper)) / 2
+    left_idx = np.searchsorted(mz_array, lower, side="left")
+    right_idx = np.searchsorted(mz_array, upper, side="right")
+    row = np.zeros(len(left_idx), dtype=dtype)
+    for ion_idx, (left, right) in enumerate(zip(left_idx, right_idx)):
+        if left < right:
+            row[ion_idx] = _window_intensity(
+                mz_array[left:right], intensity_array[left:right], targets[ion_idx], mode
+            )
+    return row
+
+
+def compound_mz_ranges(compounds) -> dict:
+    """Per-ion m/z ranges of the compounds; ranges drawn in the UI win over ion list tolerances."""
+    custom_ranges = {}
+    for cmpd in compounds:
+        custom_ranges.update(cmpd.tolerance_ranges())
+    for cmpd in compounds:
+        custom_ranges.update(cmpd.custom_mz_ranges)
+    return custom_ranges
+
+
+def process_scan(
+    mz_array: np.ndarray,
+    intensity_array: np.ndarray,
+    centroiding: str = None,
+    corrector=None,
+    noise_threshold: dict = None,
+    deconvolution: dict = None,
+    polarity: str = None,
+) -> Tuple[np.ndarray, np.ndarray]:
+    """
+    The m/z and intensity arrays of a scan as XICs are extracted from them.
+
+    The scan is centroided, recalibrated by *corrector* (a LockMassCorrector),
+    stripped of the peaks below *noise_threshold* and deconvoluted, each
+    step only if set; see build_xics.
+    """
+    if centroiding is not None:
+        mz_array, intensity_array = centroid_spectrum(mz_array, intensity_array, method=centroiding)
+    if corrector is not None:
+        mz_array = corrector(mz_array, intensity_array)
+    if noise_threshold is not None:
+        keep = above_noise(intensity_array, **noise_threshold)
+        mz_array, intensity_array = mz_array[keep], intensity_array[keep]
+    if deconvolution is not None:
+        neutral_masses, intensity_array = deconvolute_spectrum(
+            mz_array, intensity_array, polarity=polarity or "positive", **deconvolution
+        )
+        mz_array = singly_charged_mz(neutral_masses, polarity or "positive")
+    return mz_array, intensity_array
+
+
 def build_xics(
     filepath: str, ion_list: np.typing.NDArray[np.float32], mass_accuracy: np.float64,
     custom_ranges: dict = None,
+    progress_callback=None,
+    cancel_event=None,
+    rt_range: Tuple[float, float] = None,
+    centroiding: str = None,
+    polarity: str = None,
+    scan_filter: dict = None,
+    mobility_windows: dict = None,
+    deconvolution: dict = None,
+    lock_mass: dict = None,
+    n_workers: int = None,
+    scan_cache: bool = False,
+    run=None,
+    scan_range: Tuple[int, int] = None,
+    precision: str = "float32",
+    noise_threshold: dict = None,
+    xic_mode: str = "sum",
 ) -> Tuple[np.typing.NDArray[np.float32], np.typing.NDArray[np.float32]]:
     """
     Creates XICs (extracted ion chromatograms) for a list of ions and Scan objects for a given data file.
@@ -107,52 +327,238 @@ def build_xics(
     Parameters
     ----------
     filepath : str
-        Path to the mzML file.
+        Path to the mzML or mzXML file.
     ion_list : np.ndarray
         Array of target m/z values.
     mass_accuracy : float
         The mass accuracy to use for XIC extraction.
     custom_ranges : dict, optional
         Per-ion m/z range overrides: ``{mz_float: (lower, upper)}``.
+    progress_callback : callable, optional
+        Called with the fraction of the file processed so far (0.0-1.0).
+    cancel_event : threading.Event-like, optional
+        Checked every few scans; once set, extraction stops.
+    rt_range : tuple of float, optional
+        ``(rt_min, rt_max)`` in minutes; scans outside are skipped entirely
+        and the file is not read past *rt_max*. Either end may be None.
+    centroiding : str, optional
+        Centroid every scan before extraction with the given method
+        ("local_max" or "gaussian", see calculation.centroiding). Use for
+        profile-mode data; None leaves the scans as they are.
+    polarity : str, optional
+        Only use "positive" or "negative" scans, for files acquired with
+        polarity switching. Scans of unknown polarity are always used.
+    scan_filter : dict, optional
+        Only use scans with the given MS levels, filter string regex and/or
+        precursor m/z range, see utils.scan_filter.
+    mobility_windows : dict, optional
+        Per-ion ion mobility windows ``{mz_float: (low, high)}`` (1/K0 for
+        TIMS, compensation voltage for FAIMS). Peaks of those ions outside
+        the window are not summed; scans without mobility data are used as
+        they are. Scans with mobility data are not centroided, to keep the
+        peaks aligned with their mobility values.
+    deconvolution : dict, optional
+        Validated deconvolution settings (see calculation.deconvolution).
+        Every scan is collapsed to neutral masses, which are matched as their
+        ``[M+H]+`` (``[M-H]-`` with negative *polarity*) m/z, so the XIC of
+        that m/z sums all charge states and isotopes of the molecule. Not
+        applied to scans with mobility data.
+    lock_mass : dict, optional
+        Validated lock-mass settings (see calculation.recalibration). The
+        m/z array of every scan is corrected by its lock-mass error first.
+    n_workers : int, optional
+        Threads to centroid, correct and sum the scans in, fed with chunks
+        of ``_SCAN_CHUNK_SIZE`` scans while the file is still being parsed;
+        results are merged in scan order. None or 1 keeps everything on the
+        calling thread. In parallel, the lock-mass error of the last scan
+        is only carried over within a chunk.
+    scan_cache : bool
+        Read the scans through the on-disk scan cache (see
+        utils.scan_cache), so that re-running on the same file skips the
+        XML parsing.
+    run : calculation.session.LoadedRun, optional
+        Take the scans from this run held in memory instead of reading
+        *filepath*.
+    scan_range : tuple of int, optional
+        ``(first, last)`` 0-based indices, both included, of the scans to
+        use, counted in file order over the scans left by *polarity* and
+        *scan_filter*; the file is not read past *last*. Either end may be
+        None.
+    precision : str
+        One of PRECISIONS: the dtype of the target m/z windows and of the
+        returned arrays.
+    noise_threshold : dict, optional
+        Validated noise threshold settings (see calculation.noise). Peaks
+        below the threshold of their scan are not summed; applied after
+        centroiding and lock-mass correction.
+    xic_mode : str
+        One of XIC_MODES: the intensity of a target in a scan is the sum of
+        the peaks in its m/z window ("sum"), the most intense of them
+        ("max") or the one closest to the target m/z ("nearest").
 
     Returns
     -------
     Tuple of np.ndarray
         (intensities, scan_times) arrays.
+
+    Raises
+    ------
+    ProcessingCancelled
+        If *cancel_event* was set before the file was fully read.
     """
 
-    target_mzs = np.asarray(ion_list, dtype=np.float32)
+    dtype = precision_dtype(precision)
+    validate_xic_mode(xic_mode)
+    target_mzs = np.asarray(ion_list, dtype=dtype)
+    lower, upper = mz_windows(ion_list, mass_accuracy, custom_ranges, dtype)
 
-    # Compute tolerance windows for each target mz
-    delta = target_mzs * mass_accuracy * 3
-    lower = target_mzs - delta
-    upper = target_mzs + delta
+    # Optional per-ion mobility windows; +-inf where an ion has none
+    with_mobility = bool(mobility_windows)
+    mobility_lower = np.full(len(target_mzs), -np.inf)
+    mobility_upper = np.full(len(target_mzs), np.inf)
+    if with_mobility:
+        for i, mz in enumerate(np.asarray(ion_list, dtype=np.float64)):
+            if mz in mobility_windows:
+                mobility_lower[i], mobility_upper[i] = mobility_windows[mz]
+    has_mobility_window = np.isfinite(mobility_lower) | np.isfinite(mobility_upper)
 
-    # Apply per-ion custom m/z range overrides
-    if custom_ranges:
-        for i, mz in enumerate(target_mzs):
-            if mz in custom_ranges:
-                lower[i], upper[i] = custom_ranges[mz]
+    rt_min, rt_max = rt_range if rt_range is not None else (None, None)
+    first_scan, last_scan = scan_range if scan_range is not None else (None, None)
+    lock_mass_corrector = LockMassCorrector(lock_mass) if lock_mass else None
 
-    # Collect results via lists (scan count unknown with streaming parser)
-    times_list = []
-    intensities_list = []
+    def extract_row(scan, corrector):
+        mz_array, intensity_array = scan[3], scan[4]
+        mobility = scan[5] if with_mobility else None
+        if mobility is None:
+            mz_array, intensity_array = process_scan(
+                mz_array, intensity_array, centroiding, corrector, noise_threshold,
+                deconvolution, polarity,
+            )
+            return extract_mz_windows(
+                mz_array, intensity_array, lower, upper, xic_mode, target_mzs, dtype
+            )
 
-    for scan_time, tic, ms_level, mz_array, intensity_array in iter_scans(filepath):
-        times_list.append(scan_time)
+        # TIMS frames list peaks by mobility first; searchsorted needs m/z order
+        order = np.argsort(mz_array, kind="stable")
+        mz_array, intensity_array, mobility = (
+            mz_array[order], intensity_array[order], mobility[order]
+        )
+        if corrector is not None:
+            mz_array = corrector(mz_array, intensity_array)
+        if noise_threshold is not None:
+            # Zeroed, to stay aligned with the mobility values
+            keep = above_noise(intensity_array, **noise_threshold)
+            intensity_array = np.where(keep, intensity_array, 0)
 
         # Binary search the arrays for mz ranges to sum in
         left_idx = np.searchsorted(mz_array, lower, side="left")
         right_idx = np.searchsorted(mz_array, upper, side="right")
 
-        row = np.zeros(len(target_mzs), dtype=np.float32)
+        row = np.zeros(len(target_mzs), dtype=dtype)
         for ion_idx, (left, right) in enumerate(zip(left_idx, right_idx)):
-            if left < right:  # Only sum if we have values in range
-                row[ion_idx] = np.sum(intensity_array[left:right])
-        intensities_list.append(row)
+            if left >= right:  # Only sum if we have values in range
+                continue
+            window_mzs, window_intensities = mz_array[left:right], intensity_array[left:right]
+            if has_mobility_window[ion_idx]:
+                in_mobility = (mobility[left:right] >= mobility_lower[ion_idx]) & (
+                    mobility[left:right] <= mobility_upper[ion_idx]
+                )
+                if not np.any(in_mobility):
+                    continue
+                window_mzs = window_mzs[in_mobility]
+                window_intensities = window_intensities[in_mobility]
+            row[ion_idx] = _window_intensity(
+                window_mzs, window_intensities, target_mzs[ion_idx], xic_mode
+            )
+        return row
+
+    def extract_chunk(chunk):
+        corrector = LockMassCorrector(lock_mass) if lock_mass else None
+        rows = [extract_row(scan, corrector) for scan in chunk]
+        return rows, corrector.errors if corrector is not None else []
+
+    # Collect results via lists (scan count unknown with streaming parser)
+    times_list = []
+    intensities_list = []
+
+    executor = ThreadPoolExecutor(n_workers) if n_workers and n_workers > 1 else None
+    pending = deque()  # Chunk futures in scan order
+    chunk = []
+
+    def collect(future):
+        rows, errors = future.result()
+        intensities_list.extend(rows)
+        if lock_mass_corrector is not None:
+            lock_mass_corrector.errors.extend(errors)
+
+    def submit(chunk):
+        pending.append(executor.submit(extract_chunk, chunk))
+        # Bound the scans held in memory to a few chunks per thread
+        while len(pending) > 2 * n_workers:
+            collect(pending.popleft())
+
+    if run is not None:
+        scans = run.scans(
+            progress_callback=progress_callback,
+            polarity=polarity,
+            scan_filter=scan_filter,
+            with_mobility=with_mobility,
+        )
+    else:
+        scans = iter_ms_scans(
+            filepath,
+            progress_callback=progress_callback,
+            polarity=polarity,
+            scan_filter=scan_filter,
+            with_mobility=with_mobility,
+            cache=scan_cache,
+            decode_threads=n_workers,
+        )
+
+    try:
+        for scan_idx, scan in enumerate(scans):
+            scan_time = scan[0]
+            if (
+                cancel_event is not None
+                and scan_idx % _CANCEL_CHECK_INTERVAL == 0
+                and cancel_event.is_set()
+            ):
+                raise ProcessingCancelled(f"Processing of {filepath} was cancelled")
+
+            if (last_scan is not None and scan_idx > last_scan) or (
+                rt_max is not None and scan_time > rt_max
+            ):
+                break  # Scans are in acquisition order
+            if (first_scan is not None and scan_idx < first_scan) or (
+                rt_min is not None and scan_time < rt_min
+            ):
+                continue
+
+            times_list.append(scan_time)
+            if executor is None:
+                intensities_list.append(extract_row(scan, lock_mass_corrector))
+                continue
+            chunk.append(scan)
+            if len(chunk) == _SCAN_CHUNK_SIZE:
+                submit(chunk)
+                chunk = []
+
+        if executor is not None:
+            if chunk:
+                submit(chunk)
+            while pending:
+                collect(pending.popleft())
+    finally:
+        if hasattr(scans, "close"):
+            scans.close()  # Release the file when stopping early
+        if executor is not None:
+            executor.shutdown(wait=True, cancel_futures=True)
 
-    scan_times = np.array(times_list, dtype=np.float32)
-    xic_intensities = np.array(intensities_list, dtype=np.float32) if intensities_list else np.zeros((0, len(target_mzs)), dtype=np.float32)
+    if lock_mass_corrector is not None:
+        logger.info(f"Lock-mass recalibration of {filepath}: {lock_mass_corrector.summary()}")
+
+    scan_times = np.array(times_list, dtype=dtype)
+    xic_intensities = np.array(intensities_list, dtype=dtype) if intensities_list else np.zeros((0, len(target_mzs)), dtype=dtype)
 
     return xic_intensities, scan_times
 
@@ -161,47 +567,741 @@ def construct_xics(
     filepath: str,
     compounds: tuple,
     mass_accuracy: np.float64 = np.float64(0.0001),
+    progress_queue=None,
+    file_index: int = 0,
+    cancel_event=None,
+    smoothing: dict = None,
+    baseline: dict = None,
+    centroiding: str = None,
+    link_ms2: bool = False,
+    isotopes: int = 0,
+    polarity: str = None,
+    scan_filter: dict = None,
+    deconvolution: dict = None,
+    peak_fitting: str = None,
+    lock_mass: dict = None,
+    n_workers: int = None,
+    scan_cache: bool = False,
+    run=None,
+    rt_range: Tuple[float, float] = None,
+    scan_range: Tuple[int, int] = None,
+    precision: str = "float32",
+    noise_threshold: dict = None,
+    xic_mode: str = "sum",
+    peak_purity: bool = False,
+    mass_errors: bool = False,
+    charge_states: bool = False,
+    config=None,
 ):
     """Wrapper around build_xics for calling from ProcessPoolExecutor.
-    Returns a list of *filled* Compound objects."""
-    target_mzs = _extract_target_mzs(compounds)
+    Returns a list of *filled* Compound objects.
 
-    # Collect custom m/z ranges from compounds
-    custom_ranges = {}
-    for cmpd in compounds:
-        custom_ranges.update(cmpd.custom_mz_ranges)
+    If *progress_queue* (e.g. a ``multiprocessing.Manager().Queue()``) is
+    given, ``(file_index, filepath, fraction_done)`` tuples are put on it
+    while the file is being read. Setting *cancel_event* (e.g. a
+    ``multiprocessing.Manager().Event()``) aborts the file with
+    ProcessingCancelled.
 
-    intensities, rts = build_xics(
-        filepath, target_mzs, mass_accuracy,
-        custom_ranges=custom_ranges or None,
-    )
+    *smoothing* (see calculation.smoothing) is applied to every XIC unless a
+    compound carries its own ``smoothing`` settings. The smoothed trace is
+    stored next to the raw one and used for peak picking and integration.
+    *baseline* (see calculation.baseline) is estimated on the (smoothed)
+    trace, stored as ``MS Baseline`` and subtracted before integration.
+    *centroiding* is passed on to build_xics for profile-mode data.
+    With *link_ms2*, DDA MS2 scans are attached to the ions afterwards, see
+    link_ms2_spectra. With *isotopes* > 0, the M+1 ... M+n isotopologue XICs
+    of every ion are extracted as well and scored, see _attach_isotopes.
+    *polarity* ("positive"/"negative") restricts XICs to scans of that
+    polarity unless a compound sets its own ``polarity``; compounds are then
+    extracted in one pass over the file per polarity. Compounds with
+    ``transitions`` are taken from the SRM chromatograms instead, see
+    fill_srm_compounds. A compound's ``mobility_range`` restricts its XICs
+    to peaks in that ion mobility window (TIMS/FAIMS data), see build_xics.
+    *scan_filter* (see
+    utils.scan_filter) limits extraction to the matching scans, e.g. to
+    leave the MSn scans of a DDA run out of the XICs. *deconvolution* (see
+    calculation.deconvolution) collapses every scan to neutral masses first,
+    so the ``[M+H]+``/``[M-H]-`` ions of multiply charged targets catch all
+    their charge states. With *peak_fitting* ("gaussian" or "emg"),
+    overlapping peaks of an ion are also fitted as model components, see
+    calculation.peak_fitting, and stored as ``Peak Fit``. *lock_mass* (see
+    calculation.recalibration) corrects every scan's m/z by its lock-mass
+    error before extraction. *n_workers* > 1 spreads the scans of the file
+    over that many extraction threads, see build_xics. With *scan_cache*,
+    scans are read from (and on the first run written to) the file's
+    on-disk scan cache. With a *run* (calculation.session.LoadedRun), the
+    scans it holds in memory are used instead of reading the file again.
+    *rt_range* ``(rt_min, rt_max)`` and *scan_range* ``(first, last)``
+    restrict extraction to a segment of the run, e.g. to leave out a late
+    wash, see build_xics; compound RT windows are clipped to *rt_range*.
+    SRM chromatograms are always read in full. With *precision* "float64"
+    (see PRECISIONS), the m/z windows and the stored XICs of scan-based
+    compounds are double precision, for high-resolution data at high m/z.
+    *noise_threshold* (see calculation.noise) drops the peaks below an
+    absolute or base-peak-relative intensity from every scan before
+    extraction. *xic_mode* (see XIC_MODES) sets how the peaks in an ion's
+    m/z window make up its XIC intensity, summed by default. With
+    *peak_purity*, the ``Purity`` of every integrated peak is computed from
+    the processed spectra across it, read in a second pass over the file
+    (see peak_spectra), after MS2 linking; see calculation.purity. With
+    *mass_errors*, the ``Mass Error`` statistics of every integrated ion
+    are computed from the same spectra, see calculation.mass_error, and
+    with *charge_states* the charge of every ion from the isotope spacing
+    of its apex spectrum, see calculation.charge.
+
+    A *config* (calculation.config.ProcessingConfig, a dict of its options
+    or a TOML file) replaces *mass_accuracy* and the extraction options
+    above, see ProcessingConfig.xic_options; it is validated as a whole.
+
+    *compounds* may also be a plain ion list (see
+    utils.classes.compounds_from_ion_list), which is converted first, or the
+    name of a bundled ion list (see utils.ion_lists). An empty or missing
+    ion list raises IonListError rather than returning nothing."""
+    from utils.classes import compounds_from_ion_list  # classes imports this module
+
+    if config is not None:
+        from calculation.config import ProcessingConfig  # config imports this module
+
+        return construct_xics(
+            filepath, compounds,
+            progress_queue=progress_queue,
+            file_index=file_index,
+            cancel_event=cancel_event,
+            n_workers=n_workers,
+            run=run,
+            rt_range=rt_range,
+            scan_range=scan_range,
+            **ProcessingConfig.coerce(config).xic_options(),
+        )
+    if isinstance(compounds, str):
+        from utils.ion_lists import load_ion_list
+
+        compounds = load_ion_list(compounds)
+    compounds = tuple(compounds_from_ion_list(compounds or ()))
+    if not compounds:
+        raise IonListError(f"No compounds to extract from {filepath}, pass an ion list")
+    validate_polarity(polarity)
+    scan_filter = validate_scan_filter(scan_filter)
+    if deconvolution is not None:
+        deconvolution = validate_deconvolution(deconvolution)
+    lock_mass = validate_lock_mass(lock_mass)
+    noise_threshold = validate_noise_threshold(noise_threshold)
+    validate_xic_mode(xic_mode)
+    if peak_fitting is not None and peak_fitting not in PEAK_MODELS:
+        raise ValueError(f"Unknown peak model '{peak_fitting}', expected one of {PEAK_MODELS}")
+    if smoothing is not None:
+        smoothing = validate_smoothing(smoothing)
+    if baseline is not None:
+        baseline = validate_baseline(baseline)
+    rt_range, scan_range = validate_file_range(rt_range, scan_range)
+    dtype = precision_dtype(precision)
+
+    custom_ranges = compound_mz_ranges(compounds)
+
+    # Compounds with SRM/MRM transitions come from the stored chromatograms
+    srm_compounds = tuple(cmpd for cmpd in compounds if cmpd.transitions)
+    if srm_compounds:
+        fill_srm_compounds(
+            filepath, srm_compounds, mass_accuracy, smoothing, baseline,
+            peak_fitting=peak_fitting,
+        )
+
+    # One pass over the file per polarity in use, usually just one
+    scan_compounds = tuple(cmpd for cmpd in compounds if not cmpd.transitions)
+    groups = _group_by_polarity(scan_compounds, polarity)
+    held_spectra = []  # (compounds, PeakSpectra) for the peak analyses
+    for group_index, (group_polarity, group) in enumerate(groups.items()):
+        progress_callback = None
+        if progress_queue is not None:
 
-    # Map results onto Compound objects
-    mz_to_column = {mz: idx for idx, mz in enumerate(target_mzs)}  # lookup index
+            def progress_callback(fraction, offset=group_index):
+                progress_queue.put((file_index, filepath, (offset + fraction) / len(groups)))
 
+        target_mzs = _extract_target_mzs(group)
+        if isotopes:
+            isotope_mzs = [
+                mz
+                for cmpd in group
+                for index, ion in enumerate(cmpd.ions)
+                for mz in isotopologue_mzs(ion, _ion_charge(cmpd, index), isotopes)
+            ]
+            target_mzs = np.unique(np.concatenate((target_mzs, isotope_mzs)))
+
+        intensities, rts = build_xics(
+            filepath, target_mzs, mass_accuracy,
+            custom_ranges=custom_ranges or None,
+            progress_callback=progress_callback,
+            cancel_event=cancel_event,
+            rt_range=_clip_rt_range(_union_rt_range(group), rt_range),
+            centroiding=centroiding,
+            polarity=group_polarity,
+            scan_filter=scan_filter,
+            mobility_windows=_mobility_windows(group),
+            deconvolution=deconvolution,
+            lock_mass=lock_mass,
+            n_workers=n_workers,
+            scan_cache=scan_cache,
+            run=run,
+            scan_range=scan_range,
+            precision=precision,
+            noise_threshold=noise_threshold,
+            xic_mode=xic_mode,
+        )
+
+        # Map results onto Compound objects
+        mz_to_column = {mz: idx for idx, mz in enumerate(target_mzs)}  # lookup index
+        for compound in group:
+            _fill_compound(
+                compound, filepath, intensities, rts, mz_to_column,
+                mass_accuracy, smoothing, baseline, isotopes, peak_fitting, dtype,
+            )
+        if peak_purity or mass_errors or charge_states:
+            spectra = peak_spectra(
+                filepath, group, mass_accuracy, custom_ranges,
+                polarity=group_polarity,
+                scan_filter=scan_filter,
+                centroiding=centroiding,
+                lock_mass=lock_mass,
+                noise_threshold=noise_threshold,
+                deconvolution=deconvolution,
+                scan_cache=scan_cache,
+                run=run,
+                cancel_event=cancel_event,
+                dtype=dtype,
+            )
+            held_spectra.append((group, spectra))
+
+    if link_ms2:
+        link_ms2_spectra(
+            filepath, compounds, mass_accuracy, custom_ranges, cancel_event=cancel_event
+        )
+
+    # After MS2 linking, purity compares the linked MS2 spectra as well
+    for group, spectra in held_spectra:
+        analyse_peaks(
+            group, spectra, mass_accuracy, custom_ranges,
+            peak_purity=peak_purity,
+            mass_errors=mass_errors,
+            charge_states=charge_states,
+        )
+
+    return compounds
+
+
+def _fill_compound(
+    compound, filepath, intensities, rts, mz_to_column,
+    mass_accuracy, smoothing, baseline, isotopes, peak_fitting=None, dtype=np.float32,
+):
+    """Store the XICs of one compound's ions and pick and integrate their peaks."""
+    compound.file = Path(filepath).name
+    # Restrict each compound to its own elution window, if it has one
+    in_window = compound.rt_window_mask(rts)
+    compound_rts = rts[in_window]
+    compound_smoothing = _compound_smoothing(compound, smoothing)
+    for ion_index, ion in enumerate(compound.ions):
+        col = mz_to_column[ion]
+        xic = np.array((compound_rts, intensities[in_window, col]), dtype=dtype)
+        if not _process_ion_trace(
+            compound, ion, xic, compound_smoothing, baseline, mass_accuracy, peak_fitting
+        ):
+            continue
+
+        if isotopes:
+            isotope_xics = [
+                np.array(
+                    (compound_rts, intensities[in_window, mz_to_column[mz]]),
+                    dtype=dtype,
+                )
+                for mz in isotopologue_mzs(ion, _ion_charge(compound, ion_index), isotopes)
+            ]
+            _attach_isotopes(compound, ion_index, ion, xic, isotope_xics)
+
+
+def _compound_smoothing(compound, smoothing: dict):
+    """The compound's own smoothing settings if it has any, else the global ones."""
+    if compound.smoothing is not None:
+        return validate_smoothing(compound.smoothing)
+    return smoothing
+
+
+def _process_ion_trace(
+    compound, ion, xic, smoothing, baseline, mass_accuracy, peak_fitting=None
+) -> bool:
+    """Store an ion's (2, N) XIC, then pick and integrate its peaks.
+
+    With *peak_fitting*, overlapping peaks are fitted as well.
+    Returns False if the XIC is empty, in which case only the raw XIC is stored.
+    """
+    compound.ions[ion]["MS Intensity"] = xic
+    compound.ions[ion]["MS Intensity Smoothed"] = None
+    compound.ions[ion]["MS Baseline"] = None
+    if xic.shape[1] == 0:
+        logger.warning(
+            f"No scans within the RT window of {compound.name} in {compound.file}"
+        )
+        return False
+
+    trace, smoothed, trace_baseline = _prepare_trace(xic, smoothing, baseline)
+    compound.ions[ion]["MS Intensity Smoothed"] = smoothed
+    compound.ions[ion]["MS Baseline"] = trace_baseline
+
+    max_idx = np.argmax(trace[1])
+    compound.ions[ion]["RT"] = trace[0][max_idx]
+    compound.ions[ion]["Peaks"] = detect_peaks(trace[0], trace[1])
+    if peak_fitting is not None:
+        compound.ions[ion]["Peak Fit"] = fit_overlapping_peaks(
+            trace[0], trace[1], compound.ions[ion]["Peaks"], peak_fitting
+        )
+
+    try:
+        compound.ions[ion]["Integration Data"] = integrate_ms_xic_peak(
+            scan_times=trace[0],
+            intensities=trace[1],
+            rt_target=float(trace[0][max_idx]),
+            mass_accuracy=mass_accuracy,
+        )
+    except Exception as e:
+        logger.error(e)
+    return True
+
+
+def fill_srm_compounds(
+    filepath: str,
+    compounds: tuple,
+    mass_accuracy: float = 0.0001,
+    smoothing: dict = None,
+    baseline: dict = None,
+    tolerance: float = SRM_TOLERANCE,
+    peak_fitting: str = None,
+):
+    """
+    Fill compounds with SRM/MRM transitions from the file's SRM chromatograms.
+
+    Transition *i* of a compound is matched to the chromatogram whose Q1 and
+    Q3 m/z both lie within *tolerance* (Da) of it (the closest one if several
+    do) and stored as the XIC of the compound's ion *i*, then processed like
+    a spectrum-derived XIC. Transitions without a chromatogram keep an empty
+    XIC.
+    """
+    chromatograms = list(iter_srm_chromatograms(filepath))
     for compound in compounds:
         compound.file = Path(filepath).name
-        for ion in compound.ions:
-            col = mz_to_column[ion]
-            xic = np.array((rts, intensities[:, col]), dtype=np.float32)
-            compound.ions[ion]["MS Intensity"] = xic
-            max_idx = np.argmax(xic[1])
-            compound.ions[ion]["RT"] = rts[max_idx]
-
-            try:
-                compound.ions[ion]["Integration Data"] = integrate_ms_xic_peak(
-                    scan_times=xic[0],
-                    intensities=xic[1],
-                    rt_target=float(rts[max_idx]),
-                    mass_accuracy=mass_accuracy,
+        compound_smoothing = _compound_smoothing(compound, smoothing)
+        for ion, (q1, q3) in zip(compound.target_list, compound.transitions):
+            best, best_delta = None, None
+            for chrom in chromatograms:
+                delta = max(abs(chrom["q1"] - q1), abs(chrom["q3"] - q3))
+                if delta <= tolerance and (best_delta is None or delta < best_delta):
+                    best, best_delta = chrom, delta
+            if best is None:
+                logger.warning(
+                    f"No SRM chromatogram for {compound.name} {q1}>{q3} in {compound.file}"
+                )
+                xic = np.zeros((2, 0), dtype=np.float32)
+            else:
+                in_window = compound.rt_window_mask(best["time"])
+                xic = np.array(
+                    (best["time"][in_window], best["intensity"][in_window]), dtype=np.float32
                 )
-            except Exception as e:
-                logger.error(e)
+            _process_ion_trace(
+                compound, ion, xic, compound_smoothing, baseline, mass_accuracy, peak_fitting
+            )
+    return compounds
+
+
+def link_ms2_spectra(
+    filepath: str,
+    compounds: tuple,
+    mass_accuracy: float,
+    custom_ranges: dict = None,
+    max_spectra: int = MAX_LINKED_MS2,
+    cancel_event=None,
+):
+    """
+    Attach DDA MS2 scans to the ions of already integrated compounds.
+
+    An MS2 scan belongs to an ion if its scan time lies within the integrated
+    XIC peak and the ion was isolated for it: the ion's m/z lies in the
+    recorded isolation window (target minus lower offset to target plus
+    upper offset) or, for files without one, the precursor m/z (the
+    monoisotopic one where the instrument recorded it, else the selected
+    ion) lies in the ion's XIC extraction window (``+-3 * mass_accuracy *
+    mz`` or its custom m/z range). Matches are stored under
+    ``ions[mz]["MS2"]`` as a list of dicts with ``scan_time``,
+    ``precursor_mz`` (the selected ion), ``selected_mz``,
+    ``monoisotopic_mz``, ``isolation_window``, ``mz`` and ``intensity``,
+    closest to the apex first, at most *max_spectra* per ion.
+
+    Raises
+    ------
+    ProcessingCancelled
+        If *cancel_event* was set before the file was fully read.
+    """
+    custom_ranges = custom_ranges or {}
+    targets = []  # (ion dict, lower, upper, start, end, apex, ion m/z)
+    for compound in compounds:
+        for ion, ion_data in compound.ions.items():
+            ion_data["MS2"] = []
+            integration = ion_data.get("Integration Data")
+            if not integration or ion_data.get("RT") is None:
                 continue
+            if ion in custom_ranges:
+                lower, upper = custom_ranges[ion]
+            else:
+                delta = ion * mass_accuracy * 3
+                lower, upper = ion - delta, ion + delta
+            targets.append(
+                (
+                    ion_data,
+                    lower,
+                    upper,
+                    integration["start_time"],
+                    integration["end_time"],
+                    float(ion_data["RT"]),
+                    float(ion),
+                )
+            )
+    if not targets:
+        return compounds
 
+    lowers, uppers, starts, ends = (
+        np.array([t[k] for t in targets], dtype=np.float64) for k in range(1, 5)
+    )
+    ion_mzs = np.array([t[6] for t in targets], dtype=np.float64)
+    for scan_idx, scan in enumerate(iter_ms2_scans(filepath, with_precursor_info=True)):
+        scan_time, precursor_mz, mz_array, intensity_array = scan[:4]
+        info = scan[4] if len(scan) > 4 else {}
+        if (
+            cancel_event is not None
+            and scan_idx % _CANCEL_CHECK_INTERVAL == 0
+            and cancel_event.is_set()
+        ):
+            raise ProcessingCancelled(f"Processing of {filepath} was cancelled")
+        window = info.get("isolation_window")
+        monoisotopic_mz = info.get("monoisotopic_mz")
+        if window is not None:
+            isolated = (window[0] <= ion_mzs) & (ion_mzs <= window[1])
+        else:
+            mz = monoisotopic_mz if monoisotopic_mz is not None else precursor_mz
+            isolated = (lowers <= mz) & (mz <= uppers)
+        matches = np.flatnonzero(isolated & (starts <= scan_time) & (scan_time <= ends))
+        for k in matches:
+            targets[k][0]["MS2"].append(
+                {
+                    "scan_time": float(scan_time),
+                    "precursor_mz": float(precursor_mz),
+                    "selected_mz": float(precursor_mz),
+                    "monoisotopic_mz": monoisotopic_mz,
+                    "isolation_window": window,
+                    "mz": mz_array,
+                    "intensity": intensity_array,
+                }
+            )
+
+    for ion_data, *_, apex, _ in targets:
+        ion_data["MS2"].sort(key=lambda spectrum: abs(spectrum["scan_time"] - apex))
+        del ion_data["MS2"][max_spectra:]
     return compounds
 
 
+class PeakSpectra:
+    """
+    Processed MS1 spectra across the integrated peaks of a file, see peak_spectra.
+
+    Spectra are looked up like those of an indexed reader (see
+    utils.loading.load_ms_data): ``spectra.time[rt]`` is the held spectrum
+    closest to *rt*, a dict with ``m/z array`` and ``intensity array``. The
+    peak analyses written against a reader (calculation.purity,
+    calculation.mass_error, calculation.charge) thus run on the spectra
+    the XICs were extracted from.
+    """
+
+    def __init__(self, times=(), spectra=()):
+        self.times = np.asarray(times, dtype=np.float64)
+        self.spectra = list(spectra)
+
+    def __len__(self):
+        return len(self.spectra)
+
+    @property
+    def time(self):
+        return self
+
+    def __getitem__(self, rt: float) -> dict:
+        if not len(self.spectra):
+            raise KeyError(f"No spectrum held at {rt}")
+        return self.spectra[int(np.argmin(np.abs(self.times - rt)))]
+
+
+def peak_spectra(
+    filepath: str,
+    compounds: tuple,
+    mass_accuracy: float,
+    custom_ranges: dict = None,
+    polarity: str = None,
+    scan_filter: dict = None,
+    centroiding: str = None,
+    lock_mass: dict = None,
+    noise_threshold: dict = None,
+    deconvolution: dict = None,
+    scan_cache: bool = False,
+    run=None,
+    cancel_event=None,
+    dtype=np.float32,
+) -> PeakSpectra:
+    """
+    Read the spectra across the integrated peaks of *compounds* in a second pass.
+
+    Every scan on the XIC of an integrated ion, between its peak boundaries,
+    is processed as in build_xics (centroided, lock-mass corrected, noise
+    filtered and deconvoluted with the same settings, from the scans of
+    the same *polarity* and *scan_filter*) and kept within
+    PEAK_SPECTRUM_WINDOW of each such ion, its XIC window (``+-3 *
+    mass_accuracy * mz`` or its custom m/z range) included. Scans are
+    matched by their time in *dtype*, as stored in the XICs, and reading
+    stops after the last one needed.
+
+    Raises
+    ------
+    ProcessingCancelled
+        If *cancel_event* was set before the scans were read.
+    """
+    custom_ranges = custom_ranges or {}
+    windows = {}  # XIC scan time: [(lower, upper)] of the ions whose peak it is in
+    for compound in compounds:
+        for ion, ion_data in compound.ions.items():
+            integration = ion_data.get("Integration Data")
+            xic = ion_data.get("MS Intensity")
+            if not integration or xic is None or xic.shape[1] == 0:
+                continue
+            if ion in custom_ranges:
+                lower, upper = custom_ranges[ion]
+            else:
+                delta = ion * mass_accuracy * 3
+                lower, upper = ion - delta, ion + delta
+            window = (
+                min(lower, ion + PEAK_SPECTRUM_WINDOW[0]),
+                max(upper, ion + PEAK_SPECTRUM_WINDOW[1]),
+            )
+            inside = (xic[0] >= integration["start_time"]) & (xic[0] <= integration["end_time"])
+            for time in xic[0][inside]:
+                windows.setdefault(float(time), []).append(window)
+    if not windows:
+        return PeakSpectra()
+
+    last_time = max(windows)
+    dtype = np.dtype(dtype).type
+    # Lock-mass errors carry over scans without lock masses, so it sees every scan
+    corrector = LockMassCorrector(lock_mass) if lock_mass else None
+    if run is not None:
+        scans = run.scans(polarity=polarity, scan_filter=scan_filter)
+    else:
+        scans = iter_ms_scans(
+            filepath, polarity=polarity, scan_filter=scan_filter, cache=scan_cache
+        )
+    times, spectra = [], []
+    try:
+        for scan_idx, scan in enumerate(scans):
+            if (
+                cancel_event is not None
+                and scan_idx % _CANCEL_CHECK_INTERVAL == 0
+                and cancel_event.is_set()
+            ):
+                raise ProcessingCancelled(f"Processing of {filepath} was cancelled")
+            time = float(dtype(scan[0]))
+            if time > last_time:
+                break  # Scans are in acquisition order
+            needed = windows.get(time)
+            if needed is None and corrector is None:
+                continue
+            mz_array, intensity_array = process_scan(
+                scan[3], scan[4], centroiding, corrector, noise_threshold, deconvolution,
+                polarity,
+            )
+            if needed is None:
+                continue
+            keep = np.zeros(len(mz_array), dtype=bool)
+            for lower, upper in needed:
+                keep |= (mz_array >= lower) & (mz_array <= upper)
+            times.append(scan[0])
+            spectra.append({"m/z array": mz_array[keep], "intensity array": intensity_array[keep]})
+    finally:
+        if hasattr(scans, "close"):
+            scans.close()
+    return PeakSpectra(times, spectra)
+
+
+def analyse_peaks(
+    compounds: tuple,
+    spectra: PeakSpectra,
+    mass_accuracy: float,
+    custom_ranges: dict = None,
+    peak_purity: bool = False,
+    mass_errors: bool = False,
+    charge_states: bool = False,
+):
+    """
+    Peak analyses of integrated *compounds* on their PeakSpectra.
+
+    With *peak_purity*, ``Purity`` (see calculation.purity); with
+    *mass_errors*, ``Mass Error`` in the ions' XIC windows (see
+    calculation.mass_error); with *charge_states*, ``Charge State`` (see
+    calculation.charge).
+    """
+    from calculation import charge, mass_error, purity
+
+    if mass_errors:
+        mass_error.peak_mass_errors(compounds, spectra, mass_accuracy, custom_ranges)
+    if charge_states:
+        charge.peak_charge_states(compounds, spectra, mass_accuracy, custom_ranges)
+    if peak_purity:
+        impure = purity.peak_purity(compounds, spectra, mass_accuracy=mass_accuracy)
+        if impure:
+            logger.info(f"{impure} possibly coeluting peaks in {compounds[0].file}")
+
+
+def _ion_charge(compound, index: int) -> int:
+    """Charge of an ion, taken from its adduct label in ion_info (default 1)."""
+    defn = adduct_definition(compound.get_ion_label(index))
+    return defn.charge if defn is not None else 1
+
+
+def _attach_isotopes(compound, index: int, ion: float, xic: np.ndarray, isotope_xics: list):
+    """Store isotopologue XICs and the isotope pattern score of one ion.
+
+    ``ions[mz]["Isotopes"]`` maps "M+1", "M+2", ... to (2, N) XICs.
+    ``ions[mz]["Isotope Score"]`` holds the ``observed`` ratios (summed over
+    the integrated peak, or the whole trace), the ``predicted`` ratios from
+    the compound formula and their ``score``; the latter two are None
+    without a (valid) formula.
+    """
+    ion_data = compound.ions[ion]
+    ion_data["Isotopes"] = {f"M+{k}": trace for k, trace in enumerate(isotope_xics, start=1)}
+
+    integration = ion_data.get("Integration Data")
+    window = (integration["start_time"], integration["end_time"]) if integration else None
+    observed = observed_isotope_ratios(xic, isotope_xics, window)
+
+    predicted = None
+    if compound.formula:
+        label = compound.get_ion_label(index)
+        try:
+            predicted = isotope_ratios(
+                compound.formula,
+                label if adduct_definition(label) else None,
+                len(isotope_xics),
+            )
+        except ValueError as e:
+            logger.warning(f"No isotope prediction for {compound.name}: {e}")
+
+    score = None
+    if observed is not None and predicted is not None:
+        score = isotope_pattern_score(observed, predicted)
+    ion_data["Isotope Score"] = {
+        "observed": observed,
+        "predicted": predicted,
+        "score": score,
+    }
+
+
+def _prepare_trace(xic: np.ndarray, smoothing: dict, baseline: dict):
+    """Apply optional smoothing and baseline subtraction to a (2, N) XIC.
+
+    Returns ``(trace, smoothed, baseline)`` where *trace* is what peak
+    picking and integration should use, *smoothed* the smoothed XIC (or None)
+    and *baseline* the estimated (2, N) baseline (or None).
+    """
+    trace = xic
+    smoothed = None
+    if smoothing is not None and smoothing["method"] != "none":
+        smoothed = np.array(
+            (xic[0], smooth_trace(xic[1], **smoothing)), dtype=xic.dtype
+        )
+        trace = smoothed
+
+    trace_baseline = None
+    if baseline is not None and baseline["method"] != "none":
+        values = estimate_baseline(trace[1], **baseline)
+        trace_baseline = np.array((xic[0], values), dtype=xic.dtype)
+        trace = np.array((xic[0], subtract_baseline(trace[1], values)), dtype=xic.dtype)
+
+    return trace, smoothed, trace_baseline
+
+
+def _mobility_windows(compounds) -> dict:
+    """``{mz: (low, high)}`` for the ions of compounds with a mobility range, or None."""
+    windows = {}
+    for cmpd in compounds:
+        if cmpd.mobility_range is not None:
+            for ion in cmpd.ions:
+                windows[ion] = cmpd.mobility_range
+    return windows or None
+
+
+def _group_by_polarity(compounds: tuple, polarity: str = None) -> dict:
+    """Group compounds by their own polarity, falling back to *polarity*."""
+    groups = {}
+    for cmpd in compounds:
+        effective = cmpd.polarity if cmpd.polarity is not None else polarity
+        groups.setdefault(effective, []).append(cmpd)
+    return groups
+
+
+def _union_rt_range(compounds: tuple):
+    """Smallest (rt_min, rt_max) covering every compound, or None if any is unbounded."""
+    if not compounds:
+        return None
+    rt_mins = [c.rt_min for c in compounds]
+    rt_maxs = [c.rt_max for c in compounds]
+    rt_min = None if None in rt_mins else min(rt_mins)
+    rt_max = None if None in rt_maxs else max(rt_maxs)
+    if rt_min is None and rt_max is None:
+        return None
+    return rt_min, rt_max
+
+
+def validate_file_range(rt_range=None, scan_range=None):
+    """
+    Check the per-file *rt_range* ``(rt_min, rt_max)`` and *scan_range*
+    ``(first, last)`` of construct_xics, either end of each may be None.
+
+    Returns
+    -------
+    tuple
+        ``(rt_range, scan_range)``, as float and int tuples or None.
+
+    Raises
+    ------
+    ValueError
+        On ranges that are not pairs, reversed ranges or negative scan indices.
+    """
+    ranges = []
+    for name, value, cast in (("rt_range", rt_range, float), ("scan_range", scan_range, int)):
+        if value is None:
+            ranges.append(None)
+            continue
+        if len(value) != 2:
+            raise ValueError(f"{name} must be a (start, end) pair, got {value!r}")
+        start, end = (None if bound is None else cast(bound) for bound in value)
+        if start is not None and end is not None and start > end:
+            raise ValueError(f"{name} start ({start}) is greater than its end ({end})")
+        if cast is int and any(bound is not None and bound < 0 for bound in (start, end)):
+            raise ValueError(f"scan_range indices must not be negative, got {value!r}")
+        ranges.append(None if start is None and end is None else (start, end))
+    return tuple(ranges)
+
+
+def _clip_rt_range(rt_range, limits):
+    """Intersection of two (rt_min, rt_max) ranges, None standing for unbounded."""
+    if limits is None:
+        return rt_range
+    if rt_range is None:
+        return limits
+    rt_mins = [bound for bound in (rt_range[0], limits[0]) if bound is not None]
+    rt_maxs = [bound for bound in (rt_range[1], limits[1]) if bound is not None]
+    return (max(rt_mins) if rt_mins else None, min(rt_maxs) if rt_maxs else None)
+
+
 def _extract_target_mzs(compounds: tuple) -> np.ndarray:
     """Collect the m/z of every ion that appears in the supplied compounds."""
     mzs = []
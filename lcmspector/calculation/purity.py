@@ -0,0 +1,158 @@
+"""
+Peak purity from the consistency of the spectra across a peak.
+
+The spectra of a pure peak only change in scale from its start to its end.
+A coeluting interference, which rises or falls at a different time, changes
+their shape instead. Every integrated ion gets three MS1 spectra, limited to
+the m/z window around the ion where an interference would disturb the XIC
+or the MS2 isolation:
+
+- leading: on the rising edge, where the trace first reaches half its apex;
+- apex: at the ion's RT;
+- tailing: on the falling edge, where it drops below half the apex again.
+
+The cosine similarity of the leading and of the tailing spectrum to the
+apex one (see calculation.spectral_similarity) give the MS1 purity. If MS2
+scans were linked to the ion (see preprocessing.link_ms2_scans), those away
+from the apex are compared with the one closest to it as well. The purity is
+the lowest of these scores; below *min_purity*, the peak is marked impure.
+
+Results are stored in the ion's ``Purity`` dict:
+
+    {"purity": 0.97, "leading": 0.99, "tailing": 0.97, "ms2": None, "impure": False}
+"""
+
+import logging
+
+import numpy as np
+
+from calculation.spectral_similarity import cosine_similarity
+
+logger = logging.getLogger(__name__)
+
+DEFAULT_MIN_PURITY = 0.9
+# m/z window (Da) on either side of the ion the MS1 spectra are compared in
+DEFAULT_PURITY_WINDOW = 2.0
+DEFAULT_MS2_TOLERANCE = 0.01
+
+
+def edge_times(times: np.ndarray, intensity: np.ndarray, start: float, end: float, apex: float):
+    """
+    Leading and tailing half-height times of the peak at *apex* within (*start*, *end*).
+
+    These are the outermost scans at or above half the apex height next to
+    it; edges that never drop below it fall back to the first and last scan
+    within the boundaries.
+    """
+    times, intensity = np.asarray(times, dtype=np.float64), np.asarray(intensity, dtype=np.float64)
+    inside = np.flatnonzero((times >= start) & (times <= end))
+    if len(inside) == 0:
+        return float(start), float(end)
+    top = inside[np.argmin(np.abs(times[inside] - apex))]
+    half = intensity[top] / 2
+    before = inside[(inside < top) & (intensity[inside] <= half)]
+    after = inside[(inside > top) & (intensity[inside] <= half)]
+    leading = times[before[-1] + 1] if len(before) else times[inside[0]]
+    tailing = times[after[0] - 1] if len(after) else times[inside[-1]]
+    return float(leading), float(tailing)
+
+
+def _window(spectrum, ion: float, window: float):
+    mz = np.asarray(spectrum["m/z array"], dtype=np.float64)
+    intensity = np.asarray(spectrum["intensity array"], dtype=np.float64)
+    keep = (np.abs(mz - ion) <= window) & (intensity > 0)
+    return mz[keep], intensity[keep]
+
+
+def ms2_consistency(spectra, tolerance: float = DEFAULT_MS2_TOLERANCE):
+    """
+    Lowest cosine similarity of linked MS2 spectra to the one closest to the apex.
+
+    *spectra* are an ion's ``MS2`` entries, closest to the apex first. None
+    with fewer than two.
+    """
+    if len(spectra or ()) < 2:
+        return None
+    reference = spectra[0]
+    scores = [
+        cosine_similarity(
+            reference["mz"], reference["intensity"], spectrum["mz"], spectrum["intensity"],
+            tolerance,
+        )[0]
+        for spectrum in spectra[1:]
+    ]
+    return float(min(scores))
+
+
+def ion_purity(
+    reader,
+    ion: float,
+    data: dict,
+    mass_accuracy: float = 0.0001,
+    window: float = DEFAULT_PURITY_WINDOW,
+    min_purity: float = DEFAULT_MIN_PURITY,
+):
+    """
+    Purity of an ion's integrated peak, see the module docstring.
+
+    *reader* is an indexed reader with ``.time[rt]`` access, see
+    utils.loading.load_ms_data, or the processed spectra of
+    calculation.preprocessing.peak_spectra, as construct_xics passes it.
+    Returns the ``Purity`` dict, or None if the ion has no integrated peak
+    or its spectra cannot be read.
+    """
+    integration = data.get("Integration Data")
+    trace = data.get("MS Intensity Smoothed")
+    trace = data.get("MS Intensity") if trace is None else trace
+    rt = data.get("RT")
+    if not integration or rt is None or trace is None or trace.shape[1] == 0:
+        return None
+    leading, tailing = edge_times(
+        trace[0], trace[1], integration["start_time"], integration["end_time"], float(rt)
+    )
+    try:
+        apex, lead, tail = (
+            _window(reader.time[time], ion, window) for time in (float(rt), leading, tailing)
+        )
+    except Exception as e:
+        logger.debug(f"Cannot read the spectra across the peak at m/z {ion}: {e}")
+        return None
+    if len(apex[0]) == 0:
+        return None
+    tolerance = 3 * mass_accuracy * ion
+    scores = {
+        name: cosine_similarity(*apex, *spectrum, tolerance)[0] if len(spectrum[0]) else 0.0
+        for name, spectrum in (("leading", lead), ("tailing", tail))
+    }
+    ms2 = ms2_consistency(data.get("MS2"))
+    purity = min(list(scores.values()) + ([ms2] if ms2 is not None else []))
+    return {
+        "purity": float(purity),
+        "leading": float(scores["leading"]),
+        "tailing": float(scores["tailing"]),
+        "ms2": ms2,
+        "impure": bool(purity < min_purity),
+    }
+
+
+def peak_purity(
+    compounds,
+    reader,
+    mass_accuracy: float = 0.0001,
+    window: float = DEFAULT_PURITY_WINDOW,
+    min_purity: float = DEFAULT_MIN_PURITY,
+) -> int:
+    """
+    Set ``Purity`` on every integrated ion of a processed file, see ion_purity.
+
+    Returns the number of impure peaks.
+    """
+    if not 0 < min_purity <= 1:
+        raise ValueError(f"Minimum purity must be in (0, 1], got {min_purity}")
+    impure = 0
+    for compound in compounds:
+        for ion, data in compound.ions.items():
+            data["Purity"] = ion_purity(reader, ion, data, mass_accuracy, window, min_purity)
+            if data["Purity"] is not None and data["Purity"]["impure"]:
+                impure += 1
+    return impure
@@ -0,0 +1,247 @@
+"""
+Quality control metrics of one processed file.
+
+Computed from a file's processed compounds and its TIC, to flag injections
+that went wrong before their results are trusted:
+
+- ``ms1_scans`` and ``rt_range``: number of MS1 scans and the ``[first,
+  last]`` scan time (min), None without scans;
+- ``ms1_scan_rate``: MS1 scans per second;
+- ``median_mass_error_ppm``: median m/z error of the most intense peak in
+  every ion's window at the ion's apex, relative to the target m/z: the
+  ``apex_error_ppm`` of the ions' ``Mass Error``, which construct_xics
+  computes on the processed scans (see calculation.mass_error);
+- ``tic_cv`` and ``tic_median_change``: coefficient of variation of the MS1
+  TIC and median relative change between consecutive MS1 scans;
+- ``peak_width``: distribution (``median``, ``q1``, ``q3``, ``n``) of the
+  FWHM (min) of the integrated peaks;
+- ``dropouts``: ``[{"start_time", "end_time"}]`` stretches where the TIC
+  fell below a fraction of its median, e.g. when the spray failed;
+- ``flags``: human-readable descriptions of the metrics outside their
+  thresholds (see QC_THRESHOLDS), also added to the file's FileStatus
+  warnings so the UI shows them next to the file.
+"""
+
+import logging
+
+import numpy as np
+
+logger = logging.getLogger(__name__)
+
+QC_THRESHOLDS = {
+    "min_scan_rate": 0.5,  # MS1 scans per second
+    "max_mass_error_ppm": 10.0,  # Absolute median mass error
+    "max_peak_width": 0.5,  # Median FWHM (min)
+    "dropout_fraction": 0.1,  # TIC below this fraction of its median is a dropout
+    "min_dropout_scans": 3,  # Consecutive MS1 scans below the fraction
+}
+
+# Precomputed TIC points count as MS1 scans when this close to an XIC time (min)
+_TIME_TOLERANCE = 1e-4
+
+
+def validate_qc_thresholds(thresholds: dict = None) -> dict:
+    """
+    QC_THRESHOLDS updated with *thresholds*.
+
+    Raises
+    ------
+    ValueError
+        If a key is unknown or a threshold is negative.
+    """
+    merged = dict(QC_THRESHOLDS)
+    for key, value in (thresholds or {}).items():
+        if key not in QC_THRESHOLDS:
+            raise ValueError(
+                f"Unknown QC threshold '{key}', expected one of {tuple(QC_THRESHOLDS)}"
+            )
+        if value < 0:
+            raise ValueError(f"QC threshold {key} must not be negative, got {value}")
+        merged[key] = value
+    merged["min_dropout_scans"] = max(1, int(merged["min_dropout_scans"]))
+    return merged
+
+
+def ms1_times(compounds) -> np.ndarray:
+    """Sorted MS1 scan times (min) of the XICs of the processed compounds."""
+    traces = [
+        data["MS Intensity"][0]
+        for compound in compounds
+        for data in compound.ions.values()
+        if data.get("MS Intensity") is not None
+    ]
+    if not traces:
+        return np.zeros(0, dtype=np.float64)
+    return np.unique(np.concatenate(traces).astype(np.float64))
+
+
+def scan_rate(times) -> float:
+    """Scans per second over *times* (min), NaN for fewer than two scans."""
+    times = np.asarray(times, dtype=np.float64)
+    if len(times) < 2 or times[-1] <= times[0]:
+        return float("nan")
+    return float((len(times) - 1) / ((times[-1] - times[0]) * 60.0))
+
+
+def ms1_tic(tic_times, tic_values, times) -> tuple:
+    """
+    (times, values) of the TIC points at the MS1 scan *times*.
+
+    Precomputed TIC chromatograms usually cover the MS2 scans as well, whose
+    low TIC would read as dropouts. If fewer than half of the MS1 scans are
+    found in the TIC, the whole TIC is returned.
+    """
+    tic_times = np.asarray(tic_times, dtype=np.float64)
+    tic_values = np.asarray(tic_values, dtype=np.float64)
+    if len(tic_times) < 2 or len(times) == 0:
+        return tic_times, tic_values
+    nearest = np.clip(np.searchsorted(tic_times, times), 1, len(tic_times) - 1)
+    left_closer = np.abs(times - tic_times[nearest - 1]) < np.abs(times - tic_times[nearest])
+    nearest = np.where(left_closer, nearest - 1, nearest)
+    matched = np.unique(nearest[np.abs(tic_times[nearest] - times) <= _TIME_TOLERANCE])
+    if len(matched) < len(times) / 2:
+        return tic_times, tic_values
+    return tic_times[matched], tic_values[matched]
+
+
+def tic_stability(tic_values) -> dict:
+    """``tic_cv`` and ``tic_median_change`` of a TIC, NaN if it is too short."""
+    tic_values = np.asarray(tic_values, dtype=np.float64)
+    mean = tic_values.mean() if len(tic_values) else 0.0
+    if len(tic_values) < 2 or mean <= 0:
+        return {"tic_cv": float("nan"), "tic_median_change": float("nan")}
+    previous = tic_values[:-1]
+    valid = previous > 0
+    changes = np.abs(np.diff(tic_values))[valid] / previous[valid]
+    return {
+        "tic_cv": float(tic_values.std() / mean),
+        "tic_median_change": float(np.median(changes)) if len(changes) else float("nan"),
+    }
+
+
+def spray_dropouts(times, tic_values, fraction: float = 0.1, min_scans: int = 3) -> list:
+    """
+    Stretches of at least *min_scans* scans whose TIC is below *fraction* of the median.
+
+    Returns
+    -------
+    list of dict
+        ``{"start_time": ..., "end_time": ...}`` (min) per dropout.
+    """
+    times = np.asarray(times, dtype=np.float64)
+    tic_values = np.asarray(tic_values, dtype=np.float64)
+    if len(tic_values) == 0:
+        return []
+    low = tic_values < fraction * np.median(tic_values)
+    # Starts and ends of the runs of low scans
+    edges = np.diff(np.concatenate(([0], low.astype(np.int8), [0])))
+    starts, ends = np.flatnonzero(edges == 1), np.flatnonzero(edges == -1)
+    return [
+        {"start_time": float(times[start]), "end_time": float(times[end - 1])}
+        for start, end in zip(starts, ends)
+        if end - start >= min_scans
+    ]
+
+
+def _apex_peak(data: dict):
+    """The detected peak of an ion at its apex RT, or None."""
+    peaks = data.get("Peaks") or []
+    rt = data.get("RT")
+    if not peaks or rt is None:
+        return None
+    return min(peaks, key=lambda peak: abs(peak["apex_rt"] - rt))
+
+
+def peak_width_distribution(compounds) -> dict:
+    """``median``, ``q1``, ``q3`` (min) and ``n`` of the FWHM of every ion's apex peak."""
+    widths = []
+    for compound in compounds:
+        for data in compound.ions.values():
+            peak = _apex_peak(data)
+            if peak is not None and np.isfinite(peak.get("fwhm", np.nan)):
+                widths.append(peak["fwhm"])
+    if not widths:
+        return {"median": float("nan"), "q1": float("nan"), "q3": float("nan"), "n": 0}
+    q1, median, q3 = np.percentile(widths, [25, 50, 75])
+    return {"median": float(median), "q1": float(q1), "q3": float(q3), "n": len(widths)}
+
+
+def mass_errors(compounds) -> np.ndarray:
+    """
+    m/z errors (ppm) of the most intense peak in every ion's window at its apex.
+
+    Taken from the ions' ``Mass Error`` (see calculation.mass_error); ions
+    without one, or without a peak in their apex spectrum, are skipped.
+    """
+    errors = [
+        data["Mass Error"]["apex_error_ppm"]
+        for compound in compounds
+        for data in compound.ions.values()
+        if (data.get("Mass Error") or {}).get("apex_error_ppm") is not None
+    ]
+    return np.asarray(errors, dtype=np.float64)
+
+
+def qc_flags(qc: dict, thresholds: dict = None) -> list:
+    """Descriptions of the metrics in *qc* outside *thresholds*, see QC_THRESHOLDS."""
+    thresholds = validate_qc_thresholds(thresholds)
+    flags = []
+    if qc["ms1_scan_rate"] < thresholds["min_scan_rate"]:
+        flags.append(f"low MS1 scan rate ({qc['ms1_scan_rate']:.2f} Hz)")
+    error = qc["median_mass_error_ppm"]
+    if error is not None and abs(error) > thresholds["max_mass_error_ppm"]:
+        flags.append(f"median mass error of {error:.1f} ppm")
+    if qc["peak_width"]["median"] > thresholds["max_peak_width"]:
+        flags.append(f"broad peaks (median FWHM {qc['peak_width']['median']:.2f} min)")
+    for dropout in qc["dropouts"]:
+        flags.append(
+            f"TIC dropout at {dropout['start_time']:.2f}-{dropout['end_time']:.2f} min"
+        )
+    return flags
+
+
+def qc_metrics(
+    compounds,
+    tic_times=None,
+    tic_values=None,
+    thresholds: dict = None,
+) -> dict:
+    """
+    QC metrics of one processed file, see the module docstring.
+
+    Parameters
+    ----------
+    compounds : sequence of Compound
+        construct_xics results for the file; without ``Mass Error`` on its
+        ions (construct_xics with *mass_errors*), ``median_mass_error_ppm``
+        is None.
+    tic_times, tic_values : np.ndarray, optional
+        The file's TIC, e.g. MSMeasurement.tic_times / tic_values; without
+        it the TIC metrics are NaN and no dropouts are detected.
+    thresholds : dict, optional
+        Overrides of QC_THRESHOLDS for the flags.
+    """
+    thresholds = validate_qc_thresholds(thresholds)
+    times = ms1_times(compounds)
+    qc = {
+        "ms1_scans": len(times),
+        "rt_range": [float(times[0]), float(times[-1])] if len(times) else None,
+        "ms1_scan_rate": scan_rate(times),
+        "median_mass_error_ppm": None,
+    }
+
+    errors = mass_errors(compounds)
+    if len(errors):
+        qc["median_mass_error_ppm"] = float(np.median(errors))
+
+    if tic_times is not None and tic_values is not None:
+        tic_times, tic_values = ms1_tic(tic_times, tic_values, times)
+    else:
+        tic_times = tic_values = np.zeros(0, dtype=np.float64)
+    qc.update(tic_stability(tic_values))
+    qc["peak_width"] = peak_width_distribution(compounds)
+    qc["dropouts"] = spray_dropouts(
+        tic_times, tic_values, thresholds["dropout_fraction"], thresholds["min_dropout_scans"]
+    )
+    qc["flags"] = qc_flags(qc, thresholds)
+    return qc
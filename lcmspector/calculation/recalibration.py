@@ -0,0 +1,137 @@
+"""
+Lock-mass recalibration of m/z arrays.
+
+TOF instruments drift by a few ppm over a run, which matters once XICs are
+extracted with narrow tolerances. Reference compounds infused or present as
+background (lock masses) give the mass error of every scan, which is then
+divided out of the whole m/z array. Scans in which none of the lock masses
+is found keep the correction of the last scan that had one.
+
+Settings are plain dicts like the smoothing ones:
+
+    {"lock_masses": [556.2771], "window_ppm": 20.0, "min_intensity": 0.0}
+"""
+
+import logging
+from typing import Optional, Sequence
+
+import numpy as np
+
+logger = logging.getLogger(__name__)
+
+DEFAULT_LOCK_MASS = {"lock_masses": [], "window_ppm": 20.0, "min_intensity": 0.0}
+
+
+def validate_lock_mass(settings: dict) -> Optional[dict]:
+    """
+    Fill in defaults and validate lock-mass settings.
+
+    Parameters
+    ----------
+    settings : dict or None
+        ``lock_masses`` (reference m/z values), ``window_ppm`` (search window
+        around each of them, > 0) and ``min_intensity`` (weaker lock-mass
+        peaks are ignored).
+
+    Returns
+    -------
+    dict or None
+        A new dict with every key present, or None without lock masses.
+
+    Raises
+    ------
+    ValueError
+        On non-positive lock masses or search window.
+    """
+    merged = {**DEFAULT_LOCK_MASS, **(settings or {})}
+    lock_masses = tuple(sorted(float(mz) for mz in merged["lock_masses"] or ()))
+    if not lock_masses:
+        return None
+    window_ppm = float(merged["window_ppm"])
+    if lock_masses[0] <= 0:
+        raise ValueError(f"Lock masses must be positive, got {lock_masses}")
+    if window_ppm <= 0:
+        raise ValueError(f"Lock-mass window must be positive, got {window_ppm} ppm")
+    return {
+        "lock_masses": lock_masses,
+        "window_ppm": window_ppm,
+        "min_intensity": float(merged["min_intensity"]),
+    }
+
+
+def lock_mass_error(
+    mz_array: np.ndarray,
+    intensity_array: np.ndarray,
+    lock_masses: Sequence[float],
+    window_ppm: float = 20.0,
+    min_intensity: float = 0.0,
+) -> Optional[float]:
+    """
+    Mass error of a scan from its lock-mass peaks.
+
+    The most intense peak within *window_ppm* of each lock mass is taken as
+    its observed m/z.
+
+    Returns
+    -------
+    float or None
+        Median ``(observed - reference) / reference`` in ppm over the lock
+        masses found, None if none is.
+    """
+    mz_array = np.asarray(mz_array, dtype=np.float64)
+    intensity_array = np.asarray(intensity_array, dtype=np.float64)
+    errors = []
+    for reference in lock_masses:
+        tolerance = reference * window_ppm * 1e-6
+        lo = np.searchsorted(mz_array, reference - tolerance, side="left")
+        hi = np.searchsorted(mz_array, reference + tolerance, side="right")
+        if lo >= hi:
+            continue
+        best = lo + int(np.argmax(intensity_array[lo:hi]))
+        if intensity_array[best] <= min_intensity:
+            continue
+        errors.append((mz_array[best] - reference) / reference * 1e6)
+    return float(np.median(errors)) if errors else None
+
+
+def recalibrate_mz(mz_array: np.ndarray, error_ppm: float) -> np.ndarray:
+    """Divide a relative mass error (ppm) out of an m/z array."""
+    return np.asarray(mz_array, dtype=np.float64) / (1 + error_ppm * 1e-6)
+
+
+class LockMassCorrector:
+    """
+    Per-scan lock-mass correction, carrying the last error over scans
+    without lock-mass peaks.
+
+    Attributes
+    ----------
+    errors : list of float or None
+        Mass error (ppm) measured in every scan seen so far, None where no
+        lock mass was found.
+    """
+
+    def __init__(self, settings: dict):
+        self.settings = settings
+        self.errors = []
+        self._last_error = None
+
+    def __call__(self, mz_array: np.ndarray, intensity_array: np.ndarray) -> np.ndarray:
+        """Recalibrated copy of a (sorted) scan m/z array."""
+        error = lock_mass_error(mz_array, intensity_array, **self.settings)
+        self.errors.append(error)
+        if error is not None:
+            self._last_error = error
+        if self._last_error is None:
+            return mz_array
+        return recalibrate_mz(mz_array, self._last_error)
+
+    def summary(self) -> str:
+        found = [error for error in self.errors if error is not None]
+        if not found:
+            return f"no lock-mass peaks in {len(self.errors)} scans"
+        return (
+            f"lock masses in {len(found)}/{len(self.errors)} scans, "
+            f"median error {np.median(found):+.2f} ppm "
+            f"(range {min(found):+.2f} to {max(found):+.2f})"
+        )
@@ -0,0 +1,191 @@
+"""
+Resampling of spectra onto a common m/z axis.
+
+Spectra of different scans or files have their peaks at slightly different
+m/z, so heatmaps and spectral arithmetic (differences, ratios, averages)
+first put them onto shared bins. Two bin spacings:
+
+- "linear": bins of a constant ``bin_width`` in m/z.
+- "ppm": bins of a constant relative width ``bin_ppm``, geometrically
+  spaced, matching the constant relative resolution of most analyzers.
+
+    edges = mz_bin_edges(100.0, 1000.0, spacing="ppm", bin_ppm=10.0)
+    centers, matrix = resample_spectra([(mz_a, int_a), (mz_b, int_b)], edges)
+    difference = matrix[0] - matrix[1]
+
+Peaks outside the edges are dropped; every row of the matrix is one
+spectrum, every column one bin.
+"""
+
+import logging
+from typing import Iterable, Tuple
+
+import numpy as np
+
+logger = logging.getLogger(__name__)
+
+BIN_SPACINGS = ("linear", "ppm")
+RESAMPLE_STATISTICS = ("sum", "max")
+
+# Bins of one axis at most, so a typo in the width cannot exhaust memory
+MAX_BINS = 10_000_000
+
+
+def mz_bin_edges(
+    mz_min: float,
+    mz_max: float,
+    spacing: str = "linear",
+    bin_width: float = 0.01,
+    bin_ppm: float = 10.0,
+) -> np.ndarray:
+    """
+    Edges of the m/z bins covering ``[mz_min, mz_max]``.
+
+    Parameters
+    ----------
+    mz_min, mz_max : float
+        The m/z range; *mz_min* must be positive for "ppm" spacing.
+    spacing : str
+        One of BIN_SPACINGS.
+    bin_width : float
+        Bin width (m/z) of "linear" spacing.
+    bin_ppm : float
+        Relative bin width (ppm) of "ppm" spacing.
+
+    Returns
+    -------
+    np.ndarray
+        The ascending float64 edges, one more than there are bins; the last
+        edge is at or above *mz_max*.
+
+    Raises
+    ------
+    ValueError
+        On an unknown spacing, a reversed or non-positive range, a
+        non-positive bin width or more than MAX_BINS bins.
+    """
+    if spacing not in BIN_SPACINGS:
+        raise ValueError(f"Unknown bin spacing '{spacing}', expected one of {BIN_SPACINGS}")
+    if mz_min >= mz_max:
+        raise ValueError(f"mz_min ({mz_min}) must be less than mz_max ({mz_max})")
+    if spacing == "linear":
+        if bin_width <= 0:
+            raise ValueError(f"Bin width must be positive, got {bin_width}")
+        n_bins = int(np.ceil((mz_max - mz_min) / bin_width))
+    else:
+        if bin_ppm <= 0:
+            raise ValueError(f"Bin width must be positive, got {bin_ppm} ppm")
+        if mz_min <= 0:
+            raise ValueError(f"ppm spaced bins need a positive mz_min, got {mz_min}")
+        n_bins = int(np.ceil(np.log(mz_max / mz_min) / np.log1p(bin_ppm * 1e-6)))
+    n_bins = max(n_bins, 1)
+    if n_bins > MAX_BINS:
+        raise ValueError(f"{n_bins} bins exceed the maximum of {MAX_BINS}, use wider bins")
+    steps = np.arange(n_bins + 1, dtype=np.float64)
+    if spacing == "linear":
+        edges = mz_min + steps * bin_width
+    else:
+        edges = mz_min * np.exp(steps * np.log1p(bin_ppm * 1e-6))
+    edges[-1] = max(edges[-1], mz_max)  # Rounding must not drop peaks at mz_max
+    return edges
+
+
+def bin_centers(edges: np.ndarray) -> np.ndarray:
+    """The m/z in the middle of every bin of *edges*."""
+    edges = np.asarray(edges, dtype=np.float64)
+    return (edges[:-1] + edges[1:]) / 2
+
+
+def _bin_indices(mz: np.ndarray, edges: np.ndarray) -> np.ndarray:
+    """Bin of every m/z, -1 outside the edges; the last edge belongs to the last bin."""
+    indices = np.searchsorted(edges, mz, side="right") - 1
+    indices[mz == edges[-1]] = len(edges) - 2
+    indices[(indices < 0) | (indices >= len(edges) - 1)] = -1
+    return indices
+
+
+def resample_spectra(
+    spectra: Iterable[Tuple[np.ndarray, np.ndarray]],
+    edges: np.ndarray = None,
+    statistic: str = "sum",
+    spacing: str = "linear",
+    bin_width: float = 0.01,
+    bin_ppm: float = 10.0,
+) -> Tuple[np.ndarray, np.ndarray]:
+    """
+    Bin spectra onto a common m/z axis.
+
+    Parameters
+    ----------
+    spectra : iterable of (mz, intensity)
+        The spectra, peaks in any order.
+    edges : np.ndarray, optional
+        Bin edges, see mz_bin_edges; by default the range of all peaks, binned
+        with *spacing*, *bin_width* and *bin_ppm*.
+    statistic : str
+        One of RESAMPLE_STATISTICS: "sum" adds the intensities of the peaks
+        in a bin, "max" keeps the highest.
+
+    Returns
+    -------
+    Tuple[np.ndarray, np.ndarray]
+        ``(centers, matrix)``: the bin centers and a float64 matrix of one row
+        per spectrum and one column per bin, zero where a spectrum has no peak.
+
+    Raises
+    ------
+    ValueError
+        On an unknown statistic, invalid bins (see mz_bin_edges, fewer than
+        two *edges*) or spectra whose m/z and intensity arrays differ in
+        length.
+    """
+    if statistic not in RESAMPLE_STATISTICS:
+        raise ValueError(
+            f"Unknown resampling statistic '{statistic}', expected one of {RESAMPLE_STATISTICS}"
+        )
+    spectra = [
+        (np.asarray(mz, dtype=np.float64), np.asarray(intensity, dtype=np.float64))
+        for mz, intensity in spectra
+    ]
+    for mz, intensity in spectra:
+        if len(mz) != len(intensity):
+            raise ValueError(f"Spectrum has {len(mz)} m/z but {len(intensity)} intensities")
+    if edges is None:
+        peaks = [mz for mz, _ in spectra if len(mz)]
+        if not peaks:
+            return np.zeros(0, dtype=np.float64), np.zeros((len(spectra), 0), dtype=np.float64)
+        mz_min = min(float(mz.min()) for mz in peaks)
+        mz_max = max(float(mz.max()) for mz in peaks)
+        if mz_min == mz_max:  # A single m/z still gets one bin around it
+            mz_max = np.nextafter(mz_min, np.inf)
+        edges = mz_bin_edges(mz_min, mz_max, spacing, bin_width, bin_ppm)
+    edges = np.asarray(edges, dtype=np.float64)
+    n_bins = len(edges) - 1
+    if n_bins < 1:
+        raise ValueError(f"Need at least two bin edges, got {len(edges)}")
+    matrix = np.zeros((len(spectra), n_bins), dtype=np.float64)
+    if not spectra:
+        return bin_centers(edges), matrix
+
+    # All spectra at once: flat index row * n_bins + bin into the matrix
+    mz = np.concatenate([mz for mz, _ in spectra])
+    intensity = np.concatenate([intensity for _, intensity in spectra])
+    rows = np.repeat(np.arange(len(spectra)), [len(mz) for mz, _ in spectra])
+    bins = _bin_indices(mz, edges)
+    inside = bins >= 0
+    flat = rows[inside] * n_bins + bins[inside]
+    if statistic == "sum":
+        matrix.ravel()[:] = np.bincount(flat, weights=intensity[inside], minlength=matrix.size)
+    else:
+        np.maximum.at(matrix.ravel(), flat, intensity[inside])
+    dropped = len(mz) - int(np.count_nonzero(inside))
+    if dropped:
+        logger.debug(f"{dropped} peaks outside m/z {edges[0]:.4f}-{edges[-1]:.4f} dropped")
+    return bin_centers(edges), matrix
+
+
+def resample_spectrum(
+    mz: np.ndarray, intensity: np.ndarray, edges: np.ndarray, statistic: str = "sum"
+) -> np.ndarray:
+    """The intensities of one spectrum on the bins of *edges*, see resample_spectra."""
+    return resample_spectra([(mz, intensity)], edges, statistic)[1][0]
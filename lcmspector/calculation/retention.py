@@ -0,0 +1,208 @@
+"""
+Retention time and retention index agreement for identification.
+
+An MS2 match or an m/z hit is more convincing when the compound also
+elutes where it should. Expected retention is given either as a retention
+time (minutes, which only carries over between runs of the same method) or
+as a retention index: the RT placed on the scale of reference compounds
+eluting around it, which absorbs shifts of the gradient and column.
+
+With references ``{retention index: RT}`` (e.g. alkanes at 100 times their
+carbon number, or spiked standards), the index of an RT is interpolated
+linearly between the references around it (van den Dool and Kratz),
+extrapolated from the outer ones beyond them:
+
+    references = reference_points(compounds)  # ion list entries with an "ri"
+    ri = retention_index(4.2, references)
+
+The agreement is a Gaussian score, 1 at the expected retention and
+``exp(-0.5)`` at one tolerance off, blended into the identification score
+with weight *rt_weight* by identification_score:
+
+    score = (1 - rt_weight) * spectral score + rt_weight * rt score
+
+Ion list compounds take their expected ``rt`` and ``ri`` from the ion list;
+library spectra from their metadata (library_retention).
+"""
+
+import logging
+import math
+from typing import Iterable, Mapping, Optional, Tuple
+
+import numpy as np
+
+logger = logging.getLogger(__name__)
+
+DEFAULT_RT_TOLERANCE = 0.2  # min
+DEFAULT_RI_TOLERANCE = 20.0
+DEFAULT_RT_WEIGHT = 0.2
+
+# Library metadata keys of the expected retention (compared lower-cased), first found wins
+_RT_KEYS = ("retentiontime", "retention_time", "rt")
+_RT_SECONDS_KEYS = ("rtinseconds",)
+_RI_KEYS = ("retentionindex", "retention_index", "ri")
+_SECONDS = ("s", "sec", "secs", "second", "seconds")
+
+
+def _leading_number(value) -> Optional[float]:
+    """The number a metadata value starts with ("5.2 min" -> 5.2), None without."""
+    if isinstance(value, (int, float)):
+        return float(value)
+    fields = str(value or "").replace(",", " ").split()
+    try:
+        return float(fields[0]) if fields else None
+    except ValueError:
+        return None
+
+
+def library_retention(metadata: Mapping) -> Tuple[Optional[float], Optional[float]]:
+    """``(rt, ri)`` of a library spectrum from its metadata, RT in minutes, None if absent.
+
+    RTs given with a "s"/"sec" unit, or as ``rtinseconds``, are converted
+    from seconds.
+    """
+    rt = None
+    for key in _RT_KEYS:
+        rt = _leading_number(metadata.get(key))
+        if rt is not None:
+            unit = str(metadata[key]).lower().split()[1:2]
+            if unit and unit[0] in _SECONDS:
+                rt /= 60
+            break
+    if rt is None:
+        for key in _RT_SECONDS_KEYS:
+            seconds = _leading_number(metadata.get(key))
+            if seconds is not None:
+                rt = seconds / 60
+                break
+    ri = None
+    for key in _RI_KEYS:
+        ri = _leading_number(metadata.get(key))
+        if ri is not None:
+            break
+    return rt, ri
+
+
+def retention_index(rt: float, references: Mapping[float, float]) -> float:
+    """
+    Retention index of *rt* against *references* ``{retention index: RT}``.
+
+    Raises
+    ------
+    ValueError
+        With fewer than two references, or references whose RTs do not
+        increase with their index.
+    """
+    if len(references) < 2:
+        raise ValueError(f"Retention indices need two reference compounds, got {len(references)}")
+    indices = np.array(sorted(references), dtype=np.float64)
+    times = np.array([references[ri] for ri in sorted(references)], dtype=np.float64)
+    if np.any(np.diff(times) <= 0):
+        raise ValueError("Reference retention times must increase with their retention index")
+    k = int(np.clip(np.searchsorted(times, rt, side="right") - 1, 0, len(times) - 2))
+    return float(
+        indices[k] + (indices[k + 1] - indices[k]) * (rt - times[k]) / (times[k + 1] - times[k])
+    )
+
+
+def reference_points(compounds: Iterable) -> dict:
+    """
+    ``{retention index: observed RT}`` of processed compounds with a
+    ``retention_index``, at the apex of their first integrated ion.
+    """
+    references = {}
+    for compound in compounds:
+        if getattr(compound, "retention_index", None) is None:
+            continue
+        for data in compound.ions.values():
+            if data.get("Integration Data") and data.get("RT") is not None:
+                references[float(compound.retention_index)] = float(data["RT"])
+                break
+    return references
+
+
+def rt_score(observed: float, expected: float, tolerance: float) -> float:
+    """Gaussian agreement of an observed and an expected retention, see the module docstring."""
+    if tolerance <= 0:
+        raise ValueError(f"Retention tolerance must be positive, got {tolerance}")
+    return math.exp(-0.5 * ((observed - expected) / tolerance) ** 2)
+
+
+def identification_score(
+    spectral_score: float, retention_score: Optional[float], rt_weight: float = DEFAULT_RT_WEIGHT
+) -> float:
+    """*spectral_score* blended with *retention_score*, unchanged without one."""
+    if not 0 <= rt_weight <= 1:
+        raise ValueError(f"RT weight must be between 0 and 1, got {rt_weight}")
+    if retention_score is None:
+        return spectral_score
+    return (1 - rt_weight) * spectral_score + rt_weight * retention_score
+
+
+def retention_agreement(
+    rt: Optional[float],
+    expected_rt: Optional[float],
+    ri: Optional[float] = None,
+    expected_ri: Optional[float] = None,
+    rt_tolerance: float = DEFAULT_RT_TOLERANCE,
+    ri_tolerance: float = DEFAULT_RI_TOLERANCE,
+) -> Optional[dict]:
+    """
+    Agreement of an observed with an expected retention, the retention index
+    if both are known, else the RT.
+
+    Returns
+    -------
+    dict or None
+        ``{"expected", "observed", "error", "score", "unit"}`` with the unit
+        "RI" or "min"; None if neither pair is known.
+    """
+    if ri is not None and expected_ri is not None:
+        observed, expected, tolerance, unit = ri, expected_ri, ri_tolerance, "RI"
+    elif rt is not None and expected_rt is not None:
+        observed, expected, tolerance, unit = rt, expected_rt, rt_tolerance, "min"
+    else:
+        return None
+    return {
+        "expected": float(expected),
+        "observed": float(observed),
+        "error": float(observed - expected),
+        "score": rt_score(observed, expected, tolerance),
+        "unit": unit,
+    }
+
+
+def compound_rt_scores(
+    compounds: Iterable,
+    references: Mapping[float, float] = None,
+    rt_tolerance: float = DEFAULT_RT_TOLERANCE,
+    ri_tolerance: float = DEFAULT_RI_TOLERANCE,
+) -> int:
+    """
+    Set ``RT Score`` (see retention_agreement) on every integrated ion of
+    processed compounds with an ``expected_rt`` or ``retention_index``;
+    returns the number scored.
+
+    The expected RT is compared with the ``Aligned RT`` where the files were
+    aligned, see calculation.alignment. With *references* (default:
+    reference_points of *compounds* if two or more have a retention index),
+    compounds with a retention index are scored on the index of their apex
+    RT.
+    """
+    compounds = list(compounds)
+    if references is None:
+        references = reference_points(compounds)
+    use_ri = len(references) >= 2
+    scored = 0
+    for compound in compounds:
+        for data in compound.ions.values():
+            rt = data.get("RT") if data.get("Integration Data") else None
+            ri = retention_index(rt, references) if use_ri and rt is not None else None
+            if rt is not None and data.get("Aligned RT") is not None:
+                rt = data["Aligned RT"]
+            data["RT Score"] = retention_agreement(
+                rt, compound.expected_rt, ri, compound.retention_index, rt_tolerance, ri_tolerance
+            )
+            scored += data["RT Score"] is not None
+    logger.info(f"Scored the retention of {scored} ions")
+    return scored
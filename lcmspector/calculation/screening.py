@@ -0,0 +1,139 @@
+"""
+Neutral loss and diagnostic fragment screening of MS2 spectra.
+
+Compound classes often share a fragmentation: phosphorylated peptides and
+phospholipids lose phosphoric acid, glucuronides lose 176.03 Da, acyl
+chains give their acylium ions. Screening every MS2 spectrum of a file for
+such losses from the precursor (``precursor m/z - loss``) or for fixed
+fragment m/z values finds the members of a class without knowing them in
+advance.
+
+Losses and fragments are ``{name: mass}`` dicts or plain sequences of
+masses, named after their value then. COMMON_NEUTRAL_LOSSES has a few
+frequent ones:
+
+    screen_file("run.mzML", neutral_losses={"H3PO4": 97.9769}, fragments=[184.0733])
+"""
+
+import logging
+from typing import Dict, Iterable, List, Mapping, Sequence, Union
+
+import numpy as np
+
+from utils.tolerance import parse_tolerance, tolerance_window
+
+logger = logging.getLogger(__name__)
+
+COMMON_NEUTRAL_LOSSES = {
+    "H2O": 18.010565,
+    "NH3": 17.026549,
+    "CO2": 43.989829,
+    "HPO3": 79.966331,
+    "H3PO4": 97.976896,
+    "glucuronide": 176.032088,
+    "hexose": 162.052824,
+}
+SCREENING_MODES = ("any", "all")
+DEFAULT_SCREENING_TOLERANCE = "10 mDa"
+
+
+def _named(masses: Union[Mapping[str, float], Sequence[float], None]) -> Dict[str, float]:
+    if not masses:
+        return {}
+    if isinstance(masses, Mapping):
+        return {str(name): float(mass) for name, mass in masses.items()}
+    return {f"{float(mass):.4f}": float(mass) for mass in masses}
+
+
+def _match(mz: np.ndarray, intensity: np.ndarray, target: float, tolerance, min_intensity: float):
+    """Most intense peak within the tolerance of *target* as ``(mz, intensity)``, or None."""
+    lower, upper = tolerance_window(target, tolerance)
+    inside = (mz >= lower) & (mz <= upper) & (intensity >= min_intensity)
+    if not np.any(inside):
+        return None
+    best = np.flatnonzero(inside)[np.argmax(intensity[inside])]
+    return float(mz[best]), float(intensity[best])
+
+
+def screen_spectra(
+    spectra: Iterable,
+    neutral_losses: Union[Mapping[str, float], Sequence[float]] = None,
+    fragments: Union[Mapping[str, float], Sequence[float]] = None,
+    tolerance=DEFAULT_SCREENING_TOLERANCE,
+    min_relative_intensity: float = 0.01,
+    mode: str = "any",
+) -> List[dict]:
+    """
+    MS2 spectra containing any (or all) of the neutral losses and fragments.
+
+    Parameters
+    ----------
+    spectra : iterable
+        ``(scan_time, precursor_mz, mz_array, intensity_array)`` tuples, as
+        from utils.loading.iter_ms2_scans.
+    neutral_losses, fragments : dict or sequence of float, optional
+        Losses from the precursor (Da) and fragment m/z values to look for.
+    tolerance
+        Fragment m/z tolerance, see utils.tolerance.parse_tolerance.
+    min_relative_intensity : float
+        Matching peaks must be at least this fraction of the base peak.
+    mode : str
+        "any" to report spectra with at least one match, "all" for those
+        matching every loss and fragment.
+
+    Returns
+    -------
+    list of dict
+        ``scan_time``, ``precursor_mz``, ``neutral_losses`` and ``fragments``
+        per hit; the matches are ``{"name", "mass", "mz", "intensity"}``
+        dicts with the expected mass and the m/z and relative intensity of
+        the matching peak.
+    """
+    if mode not in SCREENING_MODES:
+        raise ValueError(f"Unknown screening mode '{mode}', expected one of {SCREENING_MODES}")
+    losses, diagnostic = _named(neutral_losses), _named(fragments)
+    if not losses and not diagnostic:
+        raise ValueError("Nothing to screen for: give neutral losses or fragments")
+    tolerance = parse_tolerance(tolerance)
+    n_targets = len(losses) + len(diagnostic)
+
+    hits = []
+    for scan_time, precursor_mz, mz_array, intensity_array in spectra:
+        mz = np.asarray(mz_array, dtype=np.float64)
+        intensity = np.asarray(intensity_array, dtype=np.float64)
+        if len(mz) == 0 or intensity.max() <= 0:
+            continue
+        base_peak = float(intensity.max())
+        min_intensity = min_relative_intensity * base_peak
+        matches = {"neutral_losses": [], "fragments": []}
+        targets = [("fragments", name, mass, mass) for name, mass in diagnostic.items()]
+        if precursor_mz is not None:
+            targets += [
+                ("neutral_losses", name, mass, precursor_mz - mass) for name, mass in losses.items()
+            ]
+        for kind, name, mass, target in targets:
+            match = _match(mz, intensity, target, tolerance, min_intensity)
+            if match is not None:
+                matches[kind].append(
+                    {"name": name, "mass": mass, "mz": match[0], "intensity": match[1] / base_peak}
+                )
+        found = len(matches["neutral_losses"]) + len(matches["fragments"])
+        if found == 0 or (mode == "all" and found < n_targets):
+            continue
+        hits.append(
+            {
+                "scan_time": float(scan_time),
+                "precursor_mz": None if precursor_mz is None else float(precursor_mz),
+                **matches,
+            }
+        )
+    return hits
+
+
+def screen_file(path: str, neutral_losses=None, fragments=None, **kwargs) -> List[dict]:
+    """screen_spectra of every MS2 scan of an MS file; *kwargs* are passed on."""
+    from utils.loading import iter_ms2_scans
+
+    hits = screen_spectra(iter_ms2_scans(path), neutral_losses, fragments, **kwargs)
+    logger.info(f"{len(hits)} MS2 spectra in {path} matched the screening")
+    return hits
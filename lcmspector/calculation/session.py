@@ -0,0 +1,137 @@
+"""
+Re-extraction from scans kept in memory.
+
+construct_xics reads the whole file for every run, which dominates the time
+when only the ion list or the extraction parameters change. A LoadedRun
+reads the scans of a file once and hands them to construct_xics on every
+``extract()`` call instead:
+
+    run = LoadedRun("sample.mzML")
+    compounds = run.extract(ion_list, mass_accuracy=0.0001)
+    compounds = run.extract(other_ion_list, mass_accuracy=0.0002, smoothing=...)
+
+Scans are kept per polarity and scan filter, each selection read from the
+file the first time it is asked for. MS2 linking and SRM chromatograms still
+go to the file, as they are small next to the survey scans. Once the scans
+held exceed the memory budget (see utils.memory), further selections are
+spilled to temporary memory-mapped files.
+"""
+
+import logging
+from typing import Dict, Tuple
+
+from calculation.preprocessing import construct_xics
+from utils.loading import iter_ms_scans
+from utils.memory import memory_budget
+from utils.scan_cache import SpilledScans
+from utils.scan_filter import validate_scan_filter
+
+logger = logging.getLogger(__name__)
+
+
+class LoadedRun:
+    """
+    The scans of one MS file, held in memory for repeated extraction.
+
+    Parameters
+    ----------
+    path : str
+        The mzML, mzXML or MGF file.
+    scan_cache : bool
+        Read the scans through the on-disk scan cache (see utils.scan_cache).
+    memory_budget : int or str, optional
+        Bytes of scan arrays to hold in memory before spilling to disk, see
+        utils.memory.parse_memory_size; defaults to the global budget.
+    """
+
+    def __init__(self, path: str, scan_cache: bool = False, memory_budget=None):
+        self.path = path
+        self.scan_cache = scan_cache
+        self.memory_budget = memory_budget
+        self._scans: Dict[Tuple, list] = {}
+        self._nbytes = 0  # Scan arrays held in memory, spilled ones excluded
+
+    def __repr__(self):
+        return f"LoadedRun({self.path!r}, {len(self._scans)} scan selections)"
+
+    def __getstate__(self):
+        # The scans are re-read from the file on demand; pickling or copying
+        # them would duplicate the whole run
+        state = self.__dict__.copy()
+        state["_scans"] = {}
+        state["_nbytes"] = 0
+        return state
+
+    def scans(
+        self,
+        progress_callback=None,
+        polarity: str = None,
+        scan_filter: dict = None,
+        with_mobility: bool = False,
+    ):
+        """
+        Iterate over the scans of a selection, like utils.loading.iter_ms_scans.
+
+        The selection is read from the file (with mobility) the first time.
+        """
+        scan_filter = validate_scan_filter(scan_filter)
+        key = (polarity, repr(sorted((scan_filter or {}).items())))
+        if key not in self._scans:
+            self._scans[key] = self._read(polarity, scan_filter)
+        scans = self._scans[key]
+        for i, scan in enumerate(scans):
+            yield scan if with_mobility else scan[:5]
+            if progress_callback is not None and i % 100 == 0:
+                progress_callback(i / len(scans))
+        if progress_callback is not None:
+            progress_callback(1.0)
+
+    def _read(self, polarity, scan_filter):
+        """Read a selection into memory, spilling to disk once over the budget."""
+        budget = memory_budget(self.memory_budget)
+        scans, nbytes, spilled = [], 0, None
+        for scan in iter_ms_scans(
+            self.path,
+            polarity=polarity,
+            scan_filter=scan_filter,
+            with_mobility=True,
+            cache=self.scan_cache,
+        ):
+            if spilled is not None:
+                spilled.append(scan)
+                continue
+            scans.append(scan)
+            nbytes += sum(array.nbytes for array in scan[3:] if array is not None)
+            if budget is not None and self._nbytes + nbytes > budget:
+                spilled = SpilledScans()
+                for held in scans:
+                    spilled.append(held)
+                scans = []
+        if spilled is not None:
+            logger.info(
+                f"Spilled {len(spilled)} scans of {self.path} to {spilled.directory} "
+                "to stay within the memory budget"
+            )
+            return spilled.finish()
+        self._nbytes += nbytes
+        logger.info(f"Loaded {len(scans)} scans of {self.path} into memory")
+        return scans
+
+    def extract(self, compounds, mass_accuracy: float = 0.0001, **options) -> tuple:
+        """
+        Build the XICs of *compounds* from the scans in memory.
+
+        *compounds* and the keyword *options* (smoothing, baseline,
+        centroiding, polarity, scan_filter, ...) are those of
+        calculation.preprocessing.construct_xics. Returns the filled Compound
+        tuple.
+        """
+        return construct_xics(self.path, compounds, mass_accuracy, run=self, **options)
+
+    def release(self):
+        """Drop the scans held in memory and delete spilled ones."""
+        for scans in self._scans.values():
+            if isinstance(scans, SpilledScans):
+                scans.release()
+        self._scans.clear()
+        self._nbytes = 0
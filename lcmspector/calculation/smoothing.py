@@ -0,0 +1,107 @@
+"""
+Smoothing of chromatographic traces prior to peak picking.
+
+Settings are plain dicts so they pickle cheaply into the processing pool and
+can be stored per compound in the ion list:
+
+    {"method": "savgol", "window": 7, "polyorder": 2}
+"""
+
+import logging
+
+import numpy as np
+from scipy.signal import savgol_filter
+
+logger = logging.getLogger(__name__)
+
+SMOOTHING_METHODS = ("none", "savgol", "moving_average")
+
+DEFAULT_SMOOTHING = {"method": "savgol", "window": 5, "polyorder": 2}
+
+
+def validate_smoothing(settings: dict) -> dict:
+    """
+    Fill in defaults and validate a smoothing settings dict.
+
+    Parameters
+    ----------
+    settings : dict
+        Keys ``method`` (one of SMOOTHING_METHODS), ``window`` (odd number of
+        points, >= 3) and ``polyorder`` (Savitzky-Golay only, < window).
+
+    Returns
+    -------
+    dict
+        A new dict with every key present.
+
+    Raises
+    ------
+    ValueError
+        If the method is unknown or the window/order combination is invalid.
+    """
+    merged = {**DEFAULT_SMOOTHING, **(settings or {})}
+    method = merged["method"]
+    window = int(merged["window"])
+    polyorder = int(merged["polyorder"])
+
+    if method not in SMOOTHING_METHODS:
+        raise ValueError(
+            f"Unknown smoothing method '{method}', expected one of {SMOOTHING_METHODS}"
+        )
+    if method != "none":
+        if window < 3 or window % 2 == 0:
+            raise ValueError(f"Smoothing window must be an odd number >= 3, got {window}")
+        if method == "savgol" and not 0 <= polyorder < window:
+            raise ValueError(
+                f"Polynomial order must be in [0, window), got {polyorder} for window {window}"
+            )
+    return {"method": method, "window": window, "polyorder": polyorder}
+
+
+def smooth_trace(
+    intensities: np.ndarray,
+    method: str = "savgol",
+    window: int = 5,
+    polyorder: int = 2,
+) -> np.ndarray:
+    """
+    Smooth an intensity trace.
+
+    Parameters
+    ----------
+    intensities : np.ndarray
+        Intensity values, evenly spaced in time (or close enough).
+    method : str
+        "savgol" (Savitzky-Golay), "moving_average" or "none".
+    window : int
+        Window length in points; odd, at least 3.
+    polyorder : int
+        Polynomial order for Savitzky-Golay.
+
+    Returns
+    -------
+    np.ndarray
+        Smoothed trace with the same length and dtype as the input. Negative
+        overshoots from Savitzky-Golay are clipped to zero. Traces shorter
+        than the window are returned unchanged.
+    """
+    settings = validate_smoothing(
+        {"method": method, "window": window, "polyorder": polyorder}
+    )
+    intensities = np.asarray(intensities)
+    if settings["method"] == "none" or len(intensities) < settings["window"]:
+        return intensities.copy()
+
+    values = intensities.astype(np.float64)
+    if settings["method"] == "savgol":
+        smoothed = savgol_filter(
+            values, settings["window"], settings["polyorder"], mode="interp"
+        )
+        smoothed = np.clip(smoothed, 0, None)
+    else:
+        half = settings["window"] // 2
+        padded = np.pad(values, half, mode="edge")
+        kernel = np.full(settings["window"], 1.0 / settings["window"])
+        smoothed = np.convolve(padded, kernel, mode="valid")
+
+    return smoothed.astype(intensities.dtype, copy=False)
@@ -0,0 +1,278 @@
+"""
+Similarity scores between MS2 spectra.
+
+Peaks are matched greedily: all peak pairs within the fragment tolerance
+are ranked by the product of their intensities and accepted as long as
+neither peak has been used yet, as in matchms' CosineGreedy.
+
+Three scores are available through match_ms2_spectra():
+
+- "cosine": normalized dot product of the matched intensities.
+- "modified_cosine": as cosine, but peaks may also match after shifting by
+  the precursor m/z difference, which catches analogues differing by a
+  modification.
+- "entropy": spectral entropy similarity (Li et al., Nat. Methods 2021).
+"""
+
+import logging
+from typing import Iterable, List, Tuple
+
+import numpy as np
+
+logger = logging.getLogger(__name__)
+
+SIMILARITY_METHODS = ("cosine", "modified_cosine", "entropy")
+TOLERANCE_UNITS = ("Da", "ppm")
+
+
+def _absolute_tolerance(mz: np.ndarray, tolerance: float, unit: str):
+    """Return the tolerance in Da, per peak of *mz* for ppm."""
+    if unit not in TOLERANCE_UNITS:
+        raise ValueError(f"Unknown tolerance unit '{unit}', expected one of {TOLERANCE_UNITS}")
+    if unit == "ppm":
+        return mz * tolerance * 1e-6
+    return tolerance
+
+
+def _candidate_pairs(
+    mz_a: np.ndarray, mz_b: np.ndarray, tolerance
+) -> Tuple[np.ndarray, np.ndarray]:
+    """Return index pairs (i, j) with ``|mz_a[i] - mz_b[j]| <= tolerance``; mz_b ascending.
+
+    *tolerance* is a scalar or one value per peak of *mz_a*.
+    """
+    lo = np.searchsorted(mz_b, mz_a - tolerance, side="left")
+    hi = np.searchsorted(mz_b, mz_a + tolerance, side="right")
+    counts = hi - lo
+    rows = np.repeat(np.arange(len(mz_a)), counts)
+    # Offsets within each row's [lo, hi) slice
+    starts = np.repeat(lo - np.concatenate(([0], np.cumsum(counts)[:-1])), counts)
+    cols = starts + np.arange(counts.sum())
+    return rows, cols
+
+
+def _greedy_match(
+    rows: np.ndarray, cols: np.ndarray, scores: np.ndarray
+) -> Tuple[np.ndarray, np.ndarray]:
+    """Keep the highest-scoring pairs such that every peak is used at most once."""
+    used_a, used_b = set(), set()
+    keep = []
+    for k in np.argsort(scores, kind="stable")[::-1]:
+        i, j = rows[k], cols[k]
+        if i in used_a or j in used_b:
+            continue
+        used_a.add(i)
+        used_b.add(j)
+        keep.append(k)
+    keep = np.asarray(keep, dtype=np.intp)
+    return rows[keep], cols[keep]
+
+
+def _sorted_spectrum(mz, intensity) -> Tuple[np.ndarray, np.ndarray]:
+    mz = np.asarray(mz, dtype=np.float64)
+    intensity = np.asarray(intensity, dtype=np.float64)
+    order = np.argsort(mz, kind="stable")
+    return mz[order], intensity[order]
+
+
+def _matched_peaks(mz_a, intensity_a, mz_b, intensity_b, tolerance, unit, shift=None):
+    """Greedily matched index pairs, optionally also allowing ``mz_a - shift`` to match."""
+    tol = _absolute_tolerance(mz_a, tolerance, unit)
+    rows, cols = _candidate_pairs(mz_a, mz_b, tol)
+    if shift is not None and shift != 0:
+        shifted_rows, shifted_cols = _candidate_pairs(mz_a - shift, mz_b, tol)
+        rows = np.concatenate((rows, shifted_rows))
+        cols = np.concatenate((cols, shifted_cols))
+    if len(rows) == 0:
+        return rows, cols
+    return _greedy_match(rows, cols, intensity_a[rows] * intensity_b[cols])
+
+
+def cosine_similarity(
+    mz_a: np.ndarray,
+    intensity_a: np.ndarray,
+    mz_b: np.ndarray,
+    intensity_b: np.ndarray,
+    tolerance: float = 0.01,
+    tolerance_unit: str = "Da",
+) -> Tuple[float, int]:
+    """
+    Cosine (normalized dot product) similarity between two spectra.
+
+    Parameters
+    ----------
+    mz_a, intensity_a : np.ndarray
+        First spectrum.
+    mz_b, intensity_b : np.ndarray
+        Second spectrum.
+    tolerance : float
+        Maximum m/z difference for two peaks to match.
+    tolerance_unit : str
+        "Da" or "ppm" (relative to the peak of the first spectrum).
+
+    Returns
+    -------
+    Tuple[float, int]
+        (score in [0, 1], number of matched peaks).
+    """
+    return modified_cosine_similarity(
+        mz_a, intensity_a, mz_b, intensity_b, None, None, tolerance, tolerance_unit
+    )
+
+
+def modified_cosine_similarity(
+    mz_a: np.ndarray,
+    intensity_a: np.ndarray,
+    mz_b: np.ndarray,
+    intensity_b: np.ndarray,
+    precursor_a: float,
+    precursor_b: float,
+    tolerance: float = 0.01,
+    tolerance_unit: str = "Da",
+) -> Tuple[float, int]:
+    """
+    Cosine similarity where peaks may also match shifted by the precursor difference.
+
+    Falls back to the plain cosine when either precursor m/z is None.
+    Parameters and return value as for cosine_similarity.
+    """
+    mz_a, intensity_a = _sorted_spectrum(mz_a, intensity_a)
+    mz_b, intensity_b = _sorted_spectrum(mz_b, intensity_b)
+    norm = np.linalg.norm(intensity_a) * np.linalg.norm(intensity_b)
+    if norm == 0:
+        return 0.0, 0
+
+    shift = None
+    if precursor_a is not None and precursor_b is not None:
+        shift = precursor_a - precursor_b
+    rows, cols = _matched_peaks(
+        mz_a, intensity_a, mz_b, intensity_b, tolerance, tolerance_unit, shift
+    )
+    if len(rows) == 0:
+        return 0.0, 0
+    score = float(np.sum(intensity_a[rows] * intensity_b[cols]) / norm)
+    return min(score, 1.0), len(rows)
+
+
+def _entropy(p: np.ndarray) -> float:
+    p = p[p > 0]
+    return float(-np.sum(p * np.log(p)))
+
+
+def _entropy_weighted(intensity: np.ndarray) -> np.ndarray:
+    """Normalize to unit sum and apply the entropy-based intensity weighting."""
+    p = intensity / intensity.sum()
+    entropy = _entropy(p)
+    if entropy < 3:
+        p = p ** (0.25 + 0.25 * entropy)
+        p = p / p.sum()
+    return p
+
+
+def entropy_similarity(
+    mz_a: np.ndarray,
+    intensity_a: np.ndarray,
+    mz_b: np.ndarray,
+    intensity_b: np.ndarray,
+    tolerance: float = 0.01,
+    tolerance_unit: str = "Da",
+) -> Tuple[float, int]:
+    """
+    Spectral entropy similarity, ``1 - (2 S_AB - S_A - S_B) / ln 4``.
+
+    S_A and S_B are the entropies of the weighted, sum-normalized spectra and
+    S_AB that of their 1:1 mixture, with matched peaks merged. Parameters and
+    return value as for cosine_similarity.
+    """
+    mz_a, intensity_a = _sorted_spectrum(mz_a, intensity_a)
+    mz_b, intensity_b = _sorted_spectrum(mz_b, intensity_b)
+    if intensity_a.sum() <= 0 or intensity_b.sum() <= 0:
+        return 0.0, 0
+
+    p_a = _entropy_weighted(intensity_a)
+    p_b = _entropy_weighted(intensity_b)
+    rows, cols = _matched_peaks(mz_a, p_a, mz_b, p_b, tolerance, tolerance_unit)
+    if len(rows) == 0:
+        return 0.0, 0
+
+    unmatched_a = np.ones(len(p_a), dtype=bool)
+    unmatched_a[rows] = False
+    unmatched_b = np.ones(len(p_b), dtype=bool)
+    unmatched_b[cols] = False
+    mixture = np.concatenate(
+        ((p_a[rows] + p_b[cols]) / 2, p_a[unmatched_a] / 2, p_b[unmatched_b] / 2)
+    )
+    score = 1 - (2 * _entropy(mixture) - _entropy(p_a) - _entropy(p_b)) / np.log(4)
+    return float(np.clip(score, 0.0, 1.0)), len(rows)
+
+
+def match_ms2_spectra(
+    mz: np.ndarray,
+    intensity: np.ndarray,
+    candidates: Iterable[dict],
+    precursor_mz: float = None,
+    method: str = "cosine",
+    tolerance: float = 0.01,
+    tolerance_unit: str = "Da",
+    min_matched_peaks: int = 0,
+    top_n: int = None,
+) -> List[dict]:
+    """
+    Score an experimental MS2 spectrum against candidate spectra and rank them.
+
+    Parameters
+    ----------
+    mz, intensity : np.ndarray
+        Experimental spectrum.
+    candidates : iterable of dict
+        Candidate spectra with ``mz`` and ``intensity`` arrays and, for the
+        modified cosine, ``precursor_mz`` (e.g. SpectralLibrary.spectrum()).
+    precursor_mz : float, optional
+        Precursor m/z of the experimental spectrum (modified cosine only).
+    method : str
+        One of SIMILARITY_METHODS.
+    tolerance : float
+        Fragment m/z tolerance.
+    tolerance_unit : str
+        "Da" or "ppm".
+    min_matched_peaks : int
+        Candidates with fewer matched peaks are dropped.
+    top_n : int, optional
+        Only return the best *top_n* candidates.
+
+    Returns
+    -------
+    list of dict
+        ``{"index", "score", "matched_peaks"}`` per candidate, where index is
+        the position in *candidates*, sorted by descending score.
+    """
+    if method not in SIMILARITY_METHODS:
+        raise ValueError(
+            f"Unknown similarity method '{method}', expected one of {SIMILARITY_METHODS}"
+        )
+    _absolute_tolerance(np.zeros(0), tolerance, tolerance_unit)  # validate unit early
+
+    results = []
+    for index, candidate in enumerate(candidates):
+        if method == "modified_cosine":
+            score, matched = modified_cosine_similarity(
+                mz,
+                intensity,
+                candidate["mz"],
+                candidate["intensity"],
+                precursor_mz,
+                candidate.get("precursor_mz"),
+                tolerance,
+                tolerance_unit,
+            )
+        else:
+            scorer = entropy_similarity if method == "entropy" else cosine_similarity
+            score, matched = scorer(
+                mz, intensity, candidate["mz"], candidate["intensity"], tolerance, tolerance_unit
+            )
+        if matched < min_matched_peaks:
+            continue
+        results.append({"index": index, "score": score, "matched_peaks": matched})
+
+    results.sort(key=lambda result: result["score"], reverse=True)
+    return results if top_n is None else results[:top_n]
@@ -0,0 +1,102 @@
+"""
+Per-file outcome of a processing run.
+
+Problems such as a compound without signal or a DDA run without MS2 scans
+only show up in the logs otherwise. After a run every file gets a FileStatus
+instead, "ok", "warnings" (with a list of human-readable warnings) or
+"failed" (with the error), which the UI can show next to the file. With QC
+enabled it also carries the file's QC metrics (see calculation.qc), whose
+flags count as warnings, as do peaks found impure (see calculation.purity).
+"""
+
+import logging
+from dataclasses import dataclass, field
+from pathlib import Path
+from typing import List, Optional
+
+import numpy as np
+
+logger = logging.getLogger(__name__)
+
+FILE_STATUSES = ("ok", "warnings", "failed")
+
+
+@dataclass
+class FileStatus:
+    """Outcome of processing one file."""
+
+    path: str
+    status: str = "ok"
+    warnings: List[str] = field(default_factory=list)
+    error: Optional[str] = None
+    qc: Optional[dict] = None
+
+    @property
+    def filename(self) -> str:
+        return Path(self.path).name
+
+    @classmethod
+    def failed(cls, path: str, error) -> "FileStatus":
+        return cls(path, "failed", error=str(error))
+
+
+def _has_signal(trace) -> bool:
+    return trace is not None and trace.shape[1] > 0 and bool(np.any(trace[1] > 0))
+
+
+def _ion_data(compounds):
+    for compound in compounds:
+        for ion, data in compound.ions.items():
+            yield compound, ion, data
+
+
+def result_warnings(compounds, link_ms2: bool = False) -> List[str]:
+    """
+    Warnings about the filled compounds of one file.
+
+    Parameters
+    ----------
+    compounds : sequence of Compound
+        construct_xics results for the file.
+    link_ms2 : bool
+        Whether MS2 linking was requested, to warn if no MS2 scan was found.
+
+    Returns
+    -------
+    list of str
+        E.g. ``"no scans were extracted"``, ``"compound X matched 0 scans"``,
+        ``"no MS2 scans found"`` or ``"possible coelution under X (m/z ...)"``.
+    """
+    traces = [data.get("MS Intensity") for _, _, data in _ion_data(compounds)]
+    if traces and all(trace is None or trace.shape[1] == 0 for trace in traces):
+        return ["no scans were extracted"]
+
+    warnings = []
+    for compound in compounds:
+        ions = compound.ions
+        if not any(_has_signal(data.get("MS Intensity")) for data in ions.values()):
+            warnings.append(f"compound {compound.name} matched 0 scans")
+            continue
+        for ion, data in ions.items():
+            if _has_signal(data.get("MS Intensity")) and not data.get("Integration Data"):
+                warnings.append(f"peak integration failed for {compound.name} (m/z {ion:.4f})")
+            purity = data.get("Purity")
+            if purity and purity["impure"]:
+                warnings.append(
+                    f"possible coelution under {compound.name} (m/z {ion:.4f}, "
+                    f"purity {purity['purity']:.2f})"
+                )
+    if link_ms2 and not any(data.get("MS2") for _, _, data in _ion_data(compounds)):
+        warnings.append("no MS2 scans found")
+    return warnings
+
+
+def file_status(path: str, compounds, link_ms2: bool = False, qc: dict = None) -> FileStatus:
+    """FileStatus of a successfully processed file, see result_warnings.
+
+    The ``flags`` of *qc* (see calculation.qc.qc_metrics) are added to the warnings.
+    """
+    warnings = result_warnings(compounds, link_ms2=link_ms2)
+    if qc is not None:
+        warnings.extend(qc["flags"])
+    return FileStatus(path, "warnings" if warnings else "ok", warnings, qc=qc)
@@ -0,0 +1,151 @@
+"""
+Background subtraction of spectra.
+
+Spectra of dirty matrices carry the ions of the co-eluting background
+(column bleed, plasticizers, matrix) alongside those of the analyte, which
+spoils library matching. subtract_spectrum removes a background spectrum
+from a sample spectrum: every sample peak loses the scaled intensity of the
+nearest background peak within the tolerance, and peaks left at zero or
+below are dropped. background_subtracted_spectrum does this automatically
+for a chromatographic peak, subtracting the spectra averaged just before the
+peak from those averaged around its apex:
+
+    mz, intensity = background_subtracted_spectrum(
+        "sample.mzML", apex_rt=5.12, start_time=5.02, background_width=0.2
+    )
+"""
+
+import logging
+from typing import Tuple
+
+import numpy as np
+
+from calculation.averaging import average_spectra
+from calculation.spectral_similarity import _absolute_tolerance, _sorted_spectrum
+
+logger = logging.getLogger(__name__)
+
+
+def subtract_spectrum(
+    sample: Tuple[np.ndarray, np.ndarray],
+    background: Tuple[np.ndarray, np.ndarray],
+    scale: float = 1.0,
+    tolerance: float = 10.0,
+    tolerance_unit: str = "ppm",
+) -> Tuple[np.ndarray, np.ndarray]:
+    """
+    Subtract *background* from *sample*.
+
+    Parameters
+    ----------
+    sample, background : (mz, intensity)
+        The spectra, e.g. averaged with calculation.averaging.average_spectra;
+        extra items (such as its scan count) are ignored.
+    scale : float
+        Factor applied to the background intensities before subtracting.
+    tolerance : float
+        Distance up to which a background peak is the same ion as a sample peak.
+    tolerance_unit : str
+        "Da" or "ppm".
+
+    Returns
+    -------
+    Tuple[np.ndarray, np.ndarray]
+        The ascending m/z and remaining intensity of the sample peaks with
+        intensity left over.
+
+    Raises
+    ------
+    ValueError
+        On a negative scale or tolerance, or an unknown tolerance unit.
+    """
+    if scale < 0:
+        raise ValueError(f"Background scale must not be negative, got {scale}")
+    if tolerance < 0:
+        raise ValueError(f"Tolerance must not be negative, got {tolerance}")
+    mz, intensity = _sorted_spectrum(sample[0], sample[1])
+    bg_mz, bg_intensity = _sorted_spectrum(background[0], background[1])
+    tol = _absolute_tolerance(mz, tolerance, tolerance_unit)
+    if len(mz) == 0 or len(bg_mz) == 0:
+        return mz, intensity
+
+    # Nearest background peak of every sample peak: the one at or after it, or the one before
+    after = np.clip(np.searchsorted(bg_mz, mz), 0, len(bg_mz) - 1)
+    before = np.clip(after - 1, 0, len(bg_mz) - 1)
+    nearest = np.where(np.abs(bg_mz[before] - mz) < np.abs(bg_mz[after] - mz), before, after)
+    matched = np.abs(bg_mz[nearest] - mz) <= tol
+    remaining = intensity - np.where(matched, scale * bg_intensity[nearest], 0.0)
+    keep = remaining > 0
+    return mz[keep], remaining[keep]
+
+
+def background_subtracted_spectrum(
+    source,
+    apex_rt: float,
+    start_time: float = None,
+    apex_window: float = 0.05,
+    background_width: float = 0.2,
+    scale: float = 1.0,
+    tolerance: float = 10.0,
+    tolerance_unit: str = "ppm",
+    bin_width: float = 0.01,
+    ms_level: int = 1,
+    polarity: str = None,
+    scan_filter: dict = None,
+) -> Tuple[np.ndarray, np.ndarray]:
+    """
+    The apex spectrum of a chromatographic peak with the pre-peak background
+    subtracted.
+
+    Parameters
+    ----------
+    source : str, Path or LoadedRun
+        The MS file, or its scans held in memory.
+    apex_rt : float
+        Apex retention time (min) of the peak.
+    start_time : float, optional
+        Start (min) of the peak, e.g. the ``start_time`` of an ion's
+        Integration Data; the background window ends there. Without it, the
+        window ends *apex_window* before the apex.
+    apex_window : float
+        Width (min) of the window around the apex averaged as the sample.
+    background_width : float
+        Width (min) of the window before the peak averaged as the background.
+    scale, tolerance, tolerance_unit
+        See subtract_spectrum.
+    bin_width, ms_level, polarity, scan_filter
+        Averaging and scan selection, see calculation.averaging.average_spectra.
+
+    Returns
+    -------
+    Tuple[np.ndarray, np.ndarray]
+        ``(mz, intensity)`` of the cleaned spectrum, the apex spectrum as is if
+        no scan falls into the background window.
+
+    Raises
+    ------
+    ValueError
+        On a non-positive window width, or see subtract_spectrum.
+    """
+    if apex_window <= 0 or background_width <= 0:
+        raise ValueError(
+            f"Window widths must be positive, got {apex_window} and {background_width}"
+        )
+    options = {
+        "bin_width": bin_width, "ms_level": ms_level, "polarity": polarity,
+        "scan_filter": scan_filter,
+    }
+    sample = average_spectra(
+        source, apex_rt - apex_window / 2, apex_rt + apex_window / 2, **options
+    )
+    background_end = apex_rt - apex_window if start_time is None else min(start_time, apex_rt)
+    background = average_spectra(
+        source, background_end - background_width, background_end, **options
+    )
+    if background[2] == 0:
+        logger.info(
+            f"No background scans between {background_end - background_width:.2f} and "
+            f"{background_end:.2f} min, apex spectrum at {apex_rt:.2f} min left as is"
+        )
+        return sample[0], sample[1]
+    return subtract_spectrum(sample, background, scale, tolerance, tolerance_unit)
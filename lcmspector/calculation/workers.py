@@ -8,22 +8,181 @@ processing of LC/GC-MS measurement data. It supports:
 - Emitting progress and result signals for UI updates
 
 The module uses ProcessPoolExecutor for efficient parallel processing and
-leverages PyQt6's signal-slot mechanism for thread communication.
+leverages PyQt6's signal-slot mechanism for thread communication. The pools
+use every core unless limited with set_num_threads() or a worker's
+``n_workers``; ``low_priority`` runs them at a lower scheduling priority.
+With a memory budget (utils.memory.set_memory_budget or a worker's
+``memory_budget``), fewer files run at a time, down to one by one.
 """
 
+import os
+import sys
 import time
 import traceback
 import logging
 import multiprocessing
-from concurrent.futures import ProcessPoolExecutor, as_completed
+import queue
+from pathlib import Path
+from concurrent.futures import (
+    FIRST_COMPLETED,
+    ProcessPoolExecutor,
+    as_completed,
+    wait,
+)
 
 from PySide6.QtCore import QThread, QObject, Signal
 from utils.classes import LCMeasurement, MSMeasurement
-from utils.mzml_reader import find_nearest_ms2
-from calculation.preprocessing import construct_xics
+from utils.loading import find_nearest_ms2
+from utils.manifest import build_manifest, file_checksum
+from utils.memory import files_in_parallel
+from calculation.alignment import align_retention_times
+from calculation.blanks import DEFAULT_BLANK_RATIO, apply_blank_correction
+from calculation.config import ProcessingConfig
+from calculation.drift import batch_trends, correct_drift
+from calculation.gap_filling import fill_gaps
+from calculation.features import detect_features
+from calculation.preprocessing import ProcessingCancelled, construct_xics
+from calculation.qc import qc_metrics
+from calculation.retention import compound_rt_scores
+from calculation.status import FileStatus, file_status
+from utils.errors import LCMSpectorError, ProcessingError
 
 logger = logging.getLogger(__name__)
 
+# Cores the workers below may use, None for all of them; see set_num_threads
+_NUM_THREADS = None
+# Niceness increment of the pool processes with low_priority
+_LOW_PRIORITY_NICENESS = 10
+# Windows priority class of low priority pools
+_BELOW_NORMAL_PRIORITY_CLASS = 0x4000
+
+
+def set_num_threads(n: int = None):
+    """Limit the cores used for loading and processing, None to use all of them.
+
+    The all-cores default can starve the UI and other applications on
+    laptops; workers given their own ``n_workers`` ignore this limit.
+    """
+    global _NUM_THREADS
+    if n is not None and int(n) < 1:
+        raise ValueError(f"Number of threads must be at least 1, got {n}")
+    _NUM_THREADS = None if n is None else int(n)
+
+
+def num_threads(n_workers: int = None) -> int:
+    """Cores to use: *n_workers* if given, else the set_num_threads limit, else all."""
+    return max(1, int(n_workers or _NUM_THREADS or os.cpu_count() or 1))
+
+
+def _lower_priority():
+    """Process pool initializer running the pool processes at a lower priority.
+
+    POSIX processes are niced, Windows ones get the below normal priority
+    class (os.nice does not exist there).
+    """
+    try:
+        if sys.platform == "win32":
+            import ctypes
+
+            kernel32 = ctypes.windll.kernel32
+            if not kernel32.SetPriorityClass(
+                kernel32.GetCurrentProcess(), _BELOW_NORMAL_PRIORITY_CLASS
+            ):
+                raise ctypes.WinError()
+        else:
+            os.nice(_LOW_PRIORITY_NICENESS)
+    except (AttributeError, OSError) as e:
+        logger.warning(f"Cannot lower the priority of worker process {os.getpid()}: {e}")
+
+
+def _file_error(path: str, error: Exception) -> LCMSpectorError:
+    """*error* as raised for *path*, unexpected ones wrapped in ProcessingError."""
+    if isinstance(error, LCMSpectorError):
+        return error
+    return ProcessingError(path, f"{type(error).__name__}: {error}")
+
+
+def _process_pool(n_workers=None, low_priority=False, mp_context=None):
+    """ProcessPoolExecutor with num_threads(*n_workers*) spawned processes."""
+    return ProcessPoolExecutor(
+        max_workers=num_threads(n_workers),
+        mp_context=mp_context or multiprocessing.get_context("spawn"),
+        initializer=_lower_priority if low_priority else None,
+    )
+
+
+def _process_file(path, compounds, **options):
+    """Pool task of ProcessingWorker: the filled compounds of *path* and the file's SHA-256.
+
+    The raw file is hashed for its manifest here rather than in the worker
+    thread, whose loop would stop polling for cancellation and progress
+    while a large file is read.
+    """
+    return construct_xics(path, compounds, **options), file_checksum(path)
+
+
+def process_files_iter(
+    paths,
+    compounds,
+    mass_accuracy=0.0001,
+    n_workers=None,
+    low_priority=False,
+    memory_budget=None,
+    pool=None,
+    **options,
+):
+    """
+    Build the XICs of several MS files in parallel, yielding each file as it finishes.
+
+    Parameters
+    ----------
+    paths : iterable of str
+        The MS files.
+    compounds : tuple
+        Compounds or ion list, see calculation.preprocessing.construct_xics.
+    mass_accuracy : float
+        Mass accuracy for XIC extraction.
+    n_workers, low_priority, memory_budget
+        Size and priority of the process pool, see the module docstring.
+    pool : concurrent.futures.Executor, optional
+        Submit to this executor instead of a new process pool.
+    **options
+        Further construct_xics keyword arguments (smoothing, polarity, ...),
+        or its *config* (see calculation.config.ProcessingConfig).
+
+    Yields
+    ------
+    tuple
+        ``(path, result)`` in order of completion, *result* being the filled
+        Compound tuple, or the LCMSpectorError the file failed with.
+        Closing the generator early cancels the files not yet started.
+    """
+    paths = list(paths)
+    if not paths:
+        return
+    executor = pool
+    if executor is None:
+        n_parallel = files_in_parallel(paths, num_threads(n_workers), memory_budget)
+        executor = _process_pool(n_parallel, low_priority)
+    futures = {}
+    try:
+        for path in paths:
+            future = executor.submit(construct_xics, path, compounds, mass_accuracy, **options)
+            futures[future] = path
+        for future in as_completed(futures):
+            path = futures[future]
+            try:
+                result = future.result()
+            except Exception as e:
+                logger.error(f"Error processing {path}: {traceback.format_exc()}")
+                result = _file_error(path, e)
+            yield path, result
+    finally:
+        for future in futures:
+            future.cancel()
+        if pool is None:
+            executor.shutdown(wait=True, cancel_futures=True)
+
 
 class WorkerSignals(QObject):
     """
@@ -50,13 +209,19 @@ class LoadingWorker(QThread):
     finished = Signal(dict)
     error = Signal(str)
 
-    def __init__(self, model, mode, file_paths, file_type):
+    def __init__(
+        self, model, mode, file_paths, file_type, n_workers=None, low_priority=False,
+        memory_budget=None,
+    ):
         super().__init__()
         self.model = model
         self.mode = mode
         self.file_paths = file_paths
         self.file_type = file_type
         self.file_count = len(file_paths)
+        self.n_workers = n_workers
+        self.low_priority = low_priority
+        self.memory_budget = memory_budget
         self._cancelled = False
 
     def cancel(self):
@@ -89,8 +254,11 @@ class LoadingWorker(QThread):
             logger.error(f"Invalid mode: {self.mode}")
             return
 
+        n_parallel = files_in_parallel(
+            self.file_paths, num_threads(self.n_workers), self.memory_budget
+        )
         try:
-            with ProcessPoolExecutor(mp_context=multiprocessing.get_context("spawn")) as executor:
+            with _process_pool(n_parallel, self.low_priority) as executor:
                 futures = {}
 
                 if self.file_type == "LC":
@@ -119,10 +287,9 @@ class LoadingWorker(QThread):
                         results[result_obj.filename] = result_obj
                         update_progress(result_obj.filename)
                     except Exception as e:
-                        logger.error(
-                            f"Error loading {filename}", traceback.format_exc()
-                        )
-                        self.error.emit(str(e))
+                        error = _file_error(filename, e)
+                        logger.error(f"Error loading {filename}: {traceback.format_exc()}")
+                        self.error.emit(str(error))
         except Exception as e:
             logger.error("Error in loading pool: %s", traceback.format_exc())
             self.error.emit(str(e))
@@ -135,20 +302,160 @@ class LoadingWorker(QThread):
 
 
 class ProcessingWorker(QThread):
+    """Builds XICs for every loaded MS file in a process pool.
+
+    Signals
+    -------
+    progressUpdated : int
+        Overall progress in percent, averaged over all files.
+    fileProgress : int, str, float
+        ``(file_index, file_path, fraction_done)`` for the file that advanced.
+    fileFinished : str, object
+        ``(filename, compounds)`` as soon as a file has been processed, so
+        its results can be shown before the slowest file completes. RT
+        alignment, gap filling, drift and blank correction, which compare
+        the files, are only in the results of ``finished``.
+    finished : list
+        The filled Compound tuples, one per processed file.
+    cancelled : list
+        Emitted instead of ``finished`` after cancel(), with the results of
+        the files that completed before the cancellation.
+    fileStatuses : dict
+        ``{filename: FileStatus}`` of every processed file, emitted before
+        ``finished``; also stored as the model's ``file_statuses``.
+    error : str
+        Emits an error message string on failure.
+
+    *file_ranges* maps a measurement's filename to ``{"rt_range": ...,
+    "scan_range": ...}`` to process only that segment of the file, see
+    calculation.preprocessing.construct_xics.
+
+    With *qc* (True, or a dict of QC_THRESHOLDS overrides) the QC metrics of
+    every processed file are computed as well and stored in its FileStatus,
+    see calculation.qc. With *peak_purity* the spectra across every
+    integrated peak are compared to flag coeluting interferences, see
+    calculation.purity; with *mass_errors* the m/z error of every ion is
+    summarized across its peak, see calculation.mass_error. With
+    *charge_states* the charge of every ion is inferred from the isotope
+    spacing at its apex and flagged where it differs from its adduct's, see
+    calculation.charge. All three are computed in the pool worker along with
+    the XICs, on the processed spectra, see
+    calculation.preprocessing.peak_spectra.
+
+    Signal drift and carryover are detected over the files in
+    *injection_order* (measurement filenames), by default the order they
+    were loaded in, and stored as the model's ``batch_trends``, see
+    calculation.drift. With *drift_correction* ("loess" or "spline") the
+    areas are then normalized to the trend of the pooled QC files.
+
+    With *gap_filling*, peaks missing from some files are integrated at the
+    consensus boundaries of the others (after RT alignment, before any area
+    correction), see calculation.gap_filling. The apex RT of every ion of a
+    compound with an expected ``rt`` or ``ri`` is then scored against it,
+    see calculation.retention.compound_rt_scores.
+
+    A *config* (calculation.config.ProcessingConfig, a dict of its options
+    or a TOML file) replaces the keyword arguments above. Either way the
+    options are validated up front, raising ValueError, and the effective
+    ones are kept as ``config`` and stored as the model's
+    ``processing_config`` with the results. Every processed measurement
+    gets the ``manifest`` of its run, see utils.manifest.
+    """
+
     progressUpdated = Signal(int)
+    fileProgress = Signal(int, str, float)
+    fileFinished = Signal(str, object)
     finished = Signal(list)
+    cancelled = Signal(list)
+    fileStatuses = Signal(dict)
     error = Signal(str)
 
-    def __init__(self, model, mode, mass_accuracy):
+    # Seconds between two polls of the progress queue; also throttles UI updates
+    POLL_INTERVAL = 0.1
+
+    def __init__(
+        self,
+        model,
+        mode,
+        mass_accuracy=0.0001,
+        smoothing=None,
+        baseline=None,
+        centroiding=None,
+        link_ms2=False,
+        isotopes=0,
+        polarity=None,
+        scan_filter=None,
+        rt_alignment=None,
+        blank_mode=None,
+        blank_ratio=DEFAULT_BLANK_RATIO,
+        deconvolution=None,
+        peak_fitting=None,
+        lock_mass=None,
+        n_workers=None,
+        low_priority=False,
+        scan_cache=False,
+        file_ranges=None,
+        memory_budget=None,
+        precision="float32",
+        qc=False,
+        injection_order=None,
+        drift_correction=None,
+        noise_threshold=None,
+        gap_filling=False,
+        peak_purity=False,
+        mass_errors=False,
+        charge_states=False,
+        xic_mode="sum",
+        config=None,
+    ):
         super().__init__()
         self.model = model
         self.mode = mode
         self.mass_accuracy = mass_accuracy
+        self.smoothing = smoothing
+        self.baseline = baseline
+        self.centroiding = centroiding
+        self.link_ms2 = link_ms2
+        self.isotopes = isotopes
+        self.polarity = polarity
+        self.scan_filter = scan_filter
+        self.rt_alignment = rt_alignment
+        self.blank_mode = blank_mode
+        self.blank_ratio = blank_ratio
+        self.gap_filling = gap_filling
+        self.deconvolution = deconvolution
+        self.peak_fitting = peak_fitting
+        self.lock_mass = lock_mass
+        self.n_workers = n_workers
+        self.low_priority = low_priority
+        self.scan_cache = scan_cache
+        self.file_ranges = file_ranges or {}
+        self.memory_budget = memory_budget
+        self.precision = precision
+        self.qc = qc
+        self.peak_purity = peak_purity
+        self.mass_errors = mass_errors
+        self.charge_states = charge_states
+        self.injection_order = injection_order
+        self.drift_correction = drift_correction
+        self.noise_threshold = noise_threshold
+        self.xic_mode = xic_mode
+        if config is None:
+            config = {name: getattr(self, name) for name in ProcessingConfig.model_fields}
+        self.config = ProcessingConfig.coerce(config)
+        for name, value in self.config.to_dict().items():
+            setattr(self, name, value)
         self._cancelled = False
+        self._cancel_event = None
 
     def cancel(self):
-        """Request cancellation of the worker."""
+        """Request cancellation of the worker and of the files still running."""
         self._cancelled = True
+        if self._cancel_event is not None:
+            try:
+                self._cancel_event.set()
+            except (EOFError, BrokenPipeError, ConnectionError):
+                pass  # Manager already shut down, nothing left to cancel
 
     def run(self):
         st = time.time()
@@ -163,56 +470,275 @@ class ProcessingWorker(QThread):
             logger.warning("No files to process.")
             return
 
-        progress = 0
-
-        def update_progress():
-            nonlocal progress
-            progress += 1
-            self.progressUpdated.emit(int(progress / total_files * 100))
-
         if self.mode not in {"LC/GC-MS", "LC/GC Only", "MS Only"}:
             logger.error(f"Invalid mode: {self.mode}")
             return
+
+        fractions = [0.0] * total_files
+        last_pct = -1
+        # Cores the pool leaves idle go to extraction threads within each file
+        threads_per_file = max(1, num_threads(self.n_workers) // total_files)
+
+        def update_progress():
+            nonlocal last_pct
+            pct = int(sum(fractions) / total_files * 100)
+            if pct != last_pct:
+                last_pct = pct
+                self.progressUpdated.emit(pct)
+
+        def drain(progress_queue):
+            while True:
+                try:
+                    file_index, file_path, fraction = progress_queue.get_nowait()
+                except queue.Empty:
+                    break
+                fractions[file_index] = max(fractions[file_index], fraction)
+                self.fileProgress.emit(file_index, file_path, fraction)
+            update_progress()
+
         results = []
+        statuses = {}
+        n_parallel = files_in_parallel(
+            [ms_file.path for ms_file in ms_measurements],
+            num_threads(self.n_workers),
+            self.memory_budget,
+        )
+        ctx = multiprocessing.get_context("spawn")
         try:
-            with ProcessPoolExecutor(mp_context=multiprocessing.get_context("spawn")) as executor:
+            with ctx.Manager() as manager, _process_pool(
+                n_parallel, self.low_priority, ctx
+            ) as executor:
+                progress_queue = manager.Queue()
+                self._cancel_event = manager.Event()
+                if self._cancelled:
+                    self._cancel_event.set()
                 futures = {}
                 if self.mode in {"LC/GC-MS", "MS Only"}:
-                    for ms_file in ms_measurements:
+                    for file_index, ms_file in enumerate(ms_measurements):
                         future = executor.submit(
-                            construct_xics,
+                            _process_file,
                             ms_file.path,
                             self.model.compounds,
-                            self.mass_accuracy,
+                            config=self.config,
+                            progress_queue=progress_queue,
+                            file_index=file_index,
+                            cancel_event=self._cancel_event,
+                            n_workers=threads_per_file,
+                            **self.file_ranges.get(ms_file.filename, {}),
                         )
-                        futures[future] = ms_file.filename
-                for future in as_completed(futures):
+                        futures[future] = file_index
+
+                pending = set(futures)
+                while pending:
                     # Check for cancellation
                     if self._cancelled:
-                        logger.info("Processing worker cancelled")
+                        logger.info(
+                            f"Processing worker cancelled, {len(results)} of "
+                            f"{total_files} files completed"
+                        )
                         executor.shutdown(wait=False, cancel_futures=True)
+                        self.cancelled.emit(results)
                         return
 
+                    done, pending = wait(
+                        pending, timeout=self.POLL_INTERVAL, return_when=FIRST_COMPLETED
+                    )
+                    for future in done:
+                        path = ms_measurements[futures[future]].path
+                        fractions[futures[future]] = 1.0
+                        try:
+                            result, input_sha256 = future.result()
+                        except ProcessingCancelled:
+                            continue  # Reported once the loop sees the flag
+                        except Exception as e:
+                            error = _file_error(path, e)
+                            logger.error(
+                                f"Error in processing pool: {traceback.format_exc()}"
+                            )
+                            self.error.emit(str(error))
+                            status = FileStatus.failed(path, error)
+                        else:
+                            results.append(result)
+                            ms_file = ms_measurements[futures[future]]
+                            ms_file.manifest = build_manifest(
+                                ms_file.path,
+                                self.model.compounds,
+                                self.config.to_dict(),
+                                input_sha256=input_sha256,
+                            )
+                            status = file_status(
+                                path,
+                                result,
+                                link_ms2=self.link_ms2,
+                                qc=self._file_qc(ms_file, result),
+                            )
+                            filename = ms_file.filename
+                            self.fileFinished.emit(filename, result)
+                        statuses[status.filename] = status
+                    drain(progress_queue)
+        except Exception as e:
+            logger.error(f"Error in processing pool: {traceback.format_exc()}")
+            self.error.emit(str(e))
+            return
+        finally:
+            self._cancel_event = None
+
+        if self._cancelled:
+            logger.info("Processing worker cancelled after all files completed")
+            self.cancelled.emit(results)
+            return
+
+        if self.rt_alignment and len(results) > 1:
+            try:
+                self.model.rt_shifts = align_retention_times(results, method=self.rt_alignment)
+            except Exception:
+                logger.error(f"RT alignment failed: {traceback.format_exc()}")
+
+        if self.gap_filling and len(results) > 1:
+            try:
+                fill_gaps(results, rt_shifts=self.model.rt_shifts if self.rt_alignment else None)
+            except Exception:
+                logger.error(f"Gap filling failed: {traceback.format_exc()}")
+
+        for compounds in results:
+            try:
+                compound_rt_scores(compounds)
+            except Exception:
+                logger.error(f"Retention scoring failed: {traceback.format_exc()}")
+
+        blank_files = {Path(ms_file.path).name for ms_file in ms_measurements if ms_file.blank}
+        injection_order = self._injection_order(ms_measurements)
+        if len(results) > 1:
+            # Before correcting the areas
+            try:
+                self.model.batch_trends = batch_trends(results, injection_order, blank_files)
+            except Exception:
+                logger.error(f"Drift and carryover detection failed: {traceback.format_exc()}")
+
+        if self.drift_correction:
+            qc_files = {
+                Path(ms_file.path).name
+                for ms_file in ms_measurements
+                if getattr(ms_file, "pooled_qc", False)
+            }
+            try:
+                correct_drift(results, injection_order, qc_files, self.drift_correction)
+            except Exception:
+                logger.error(f"Drift correction failed: {traceback.format_exc()}")
+
+        if self.blank_mode:
+            try:
+                apply_blank_correction(results, blank_files, self.blank_mode, self.blank_ratio)
+            except Exception:
+                logger.error(f"Blank correction failed: {traceback.format_exc()}")
+
+        for status in statuses.values():
+            for warning in status.warnings:
+                logger.warning(f"{status.filename}: {warning}")
+        self.model.file_statuses = statuses
+        self.model.processing_config = self.config.to_dict()
+        self.fileStatuses.emit(statuses)
+
+        logger.info(f"Processed {len(results)} MS files in {time.time() - st:.2f} s.")
+        self.finished.emit(results)
+
+    def _injection_order(self, ms_measurements) -> list:
+        """File names in injection order, files missing from it in loading order."""
+        position = {name: i for i, name in enumerate(self.injection_order or ())}
+        ordered = sorted(
+            ms_measurements, key=lambda ms_file: position.get(ms_file.filename, len(position))
+        )
+        return [Path(ms_file.path).name for ms_file in ordered]
+
+    def _file_qc(self, ms_file, compounds):
+        """QC metrics of a processed file if QC is enabled, else None."""
+        if not self.qc:
+            return None
+        try:
+            # Mass errors come from construct_xics, ProcessingConfig.xic_options turns them on
+            return qc_metrics(
+                compounds,
+                getattr(ms_file, "tic_times", None),
+                getattr(ms_file, "tic_values", None),
+                thresholds=self.qc if isinstance(self.qc, dict) else None,
+            )
+        except Exception:
+            logger.error(f"QC of {ms_file.filename} failed: {traceback.format_exc()}")
+            return None
+
+
+class FeatureDetectionWorker(QThread):
+    """Runs untargeted feature detection on every loaded MS file in a process pool.
+
+    Signals
+    -------
+    progressUpdated : int, str
+        Percentage of files done and the file that just finished.
+    finished : dict
+        ``{filename: feature table}``, see calculation.features.detect_features.
+    error : str
+        Emits an error message string on failure.
+    """
+
+    progressUpdated = Signal(int, str)
+    finished = Signal(dict)
+    error = Signal(str)
+
+    def __init__(self, model, settings=None, n_workers=None, low_priority=False):
+        super().__init__()
+        self.model = model
+        self.settings = dict(settings or {})
+        self.n_workers = n_workers
+        self.low_priority = low_priority
+        self._cancelled = False
+
+    def cancel(self):
+        """Request cancellation of the worker."""
+        self._cancelled = True
+
+    def run(self):
+        st = time.time()
+        ms_measurements = list(self.model.ms_measurements.values())
+        if not ms_measurements:
+            logger.warning("No files to process.")
+            return
+
+        results = {}
+        try:
+            with _process_pool(self.n_workers, self.low_priority) as executor:
+                futures = {
+                    executor.submit(detect_features, ms_file.path, **self.settings): ms_file
+                    for ms_file in ms_measurements
+                }
+                for future in as_completed(futures):
+                    if self._cancelled:
+                        logger.info("Feature detection worker cancelled")
+                        executor.shutdown(wait=False, cancel_futures=True)
+                        return
+
+                    ms_file = futures[future]
                     try:
-                        result = future.result()
-                        results.append(result)
+                        results[ms_file.filename] = future.result()
                     except Exception as e:
+                        error = _file_error(ms_file.path, e)
                         logger.error(
-                            f"Error in processing pool: {traceback.format_exc()}"
+                            f"Error detecting features in {ms_file.filename}: {traceback.format_exc()}"
                         )
-                        self.error.emit(str(e))
-                    update_progress()
+                        self.error.emit(str(error))
+                    self.progressUpdated.emit(
+                        int(len(results) / len(ms_measurements) * 100), ms_file.filename
+                    )
         except Exception as e:
-            logger.error(f"Error in processing pool: {traceback.format_exc()}")
+            logger.error(f"Error in feature detection pool: {traceback.format_exc()}")
             self.error.emit(str(e))
             return
 
-        logger.info(f"Processed {len(results)} MS files in {time.time() - st:.2f} s.")
+        logger.info(f"Detected features in {len(results)} MS files in {time.time() - st:.2f} s.")
         self.finished.emit(results)
 
 
 class MS2LookupWorker(QThread):
-    """Lightweight QThread that searches an mzML file for the MS2 scan
+    """Lightweight QThread that searches an mzML/mzXML file for the MS2 scan
     nearest to *target_rt* whose precursor m/z matches *precursor_mz*.
 
     Signals
@@ -44,6 +44,7 @@ class Controller:
     def _connect_signals(self):
         """Connect controller to current view widgets."""
         self.view.processButton.clicked.connect(self.process_data)
+        self.view.cancelButton.clicked.connect(self.cancel_processing)
         self.view.comboBox_currentfile.currentIndexChanged.connect(
             self.display_selected_plots
         )
@@ -72,6 +73,10 @@ class Controller:
             self.view.processButton.clicked.disconnect(self.process_data)
         except (RuntimeError, TypeError):
             pass  # Already disconnected or widget deleted
+        try:
+            self.view.cancelButton.clicked.disconnect(self.cancel_processing)
+        except (RuntimeError, TypeError):
+            pass
 
         # Disconnect comboBox_currentfile
         try:
@@ -182,6 +187,7 @@ class Controller:
             logger.info("Starting the processing...")
             # Handle pre-processing UI events
             self.view.processButton.setEnabled(False)
+            self._show_cancel_button(True)
             self.view.progressBar.setVisible(True)
             self.view.progressLabel.setVisible(True)
             self.view.progressLabel.setText("0%")
@@ -191,6 +197,7 @@ class Controller:
                 self.model.process(mode=self.mode)
             except Exception:
                 logger.error(f"Error processing data: {traceback.format_exc()}")
+                self._show_cancel_button(False)
                 self.view.show_critical_error(
                     f"Error processing data: {traceback.format_exc()}"
                 )
@@ -203,11 +210,55 @@ class Controller:
                 "Nothing to process. Please load LC files and either corresponding MS files or manual annotations before proceeding."
             )
 
+    def on_file_processed(self, filename, compounds):
+        """Attach the results of one file as soon as it is done, while others still run."""
+        measurement = self.model.ms_measurements.get(filename)
+        if measurement is None:
+            return
+        measurement.xics = compounds
+        self.view.statusbar.showMessage(
+            f"{datetime.now().strftime('%Y-%m-%d %H:%M:%S')} -- Processed {filename}.", 3000
+        )
+
+    def cancel_processing(self):
+        """Cancel the running processing; on_processing_cancelled follows."""
+        self.view.cancelButton.setEnabled(False)
+        self.view.statusbar.showMessage(
+            f"{datetime.now().strftime('%Y-%m-%d %H:%M:%S')} -- Cancelling processing..."
+        )
+        self.model.cancel_processing()
+
+    def _show_cancel_button(self, visible):
+        """Swap the process button for the cancel button while processing."""
+        self.view.processButton.setVisible(not visible)
+        self.view.cancelButton.setVisible(visible)
+        self.view.cancelButton.setEnabled(visible)
+
+    def on_processing_cancelled(self, compound_results):
+        """Display the results of the files that completed before the cancellation."""
+        logger.info(f"Processing cancelled, {len(compound_results)} files completed.")
+        if compound_results:
+            self.on_processing_finished(compound_results)
+            message = (
+                f"Processing cancelled, displaying the results of "
+                f"{len(compound_results)} files."
+            )
+        else:
+            self._show_cancel_button(False)
+            self.view.progressBar.setVisible(False)
+            self.view.progressLabel.setVisible(False)
+            self.view.processButton.setEnabled(True)
+            message = "Processing cancelled before any file completed."
+        self.view.statusbar.showMessage(
+            f"{datetime.now().strftime('%Y-%m-%d %H:%M:%S')} -- {message}", 5000
+        )
+
     def on_processing_finished(self, compound_results):
         # iterate over the compound results and match them with their respective MS file
         for compound in compound_results:
             self.model.ms_measurements[compound[0].file.split(".")[0]].xics = compound
 
+        self._show_cancel_button(False)
         self.view.progressBar.setVisible(False)
         self.view.progressLabel.setVisible(False)
         self.view.processButton.setEnabled(True)
@@ -225,8 +276,14 @@ class Controller:
         self.view.tabWidget.setTabEnabled(
             self.view.tabWidget.indexOf(self.view.tabQuantitation), True
         )
+        # The first file with results; after a cancellation not every file has them
         self.view.setup_dock_area(
-            next(iter(self.model.ms_measurements.values())).xics, self.view.canvas_XICs
+            next(
+                measurement.xics
+                for measurement in self.model.ms_measurements.values()
+                if measurement.xics
+            ),
+            self.view.canvas_XICs,
         )
         self.update_filenames()
         self.view.actionExport.setEnabled(True)
@@ -305,6 +362,7 @@ class Controller:
         logger.error(f"Worker error: {error_message}")
 
         # Reset UI elements
+        self._show_cancel_button(False)
         self.view.progressBar.setVisible(False)
         self.view.progressLabel.setVisible(False)
         self.view.processButton.setEnabled(False)
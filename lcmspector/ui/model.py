@@ -4,15 +4,33 @@ import traceback
 import threading
 import os
 import numpy as np
-from scipy.stats import linregress
 import pandas as pd
+from calculation.blanks import DEFAULT_BLANK_RATIO
 from calculation.calc_conc import calculate_concentration
+from calculation.config import ProcessingConfig
+from calculation.calibration import (
+    detection_limits,
+    fit_calibration,
+    internal_standard_ratio,
+)
 from calculation.peak_integration import integrate_peak_manual_boundaries
-from calculation.workers import LoadingWorker, ProcessingWorker
+from calculation.workers import FeatureDetectionWorker, LoadingWorker, ProcessingWorker
 from PySide6.QtCore import QObject
+from utils.manifest import batch_manifest
+from utils.session_file import load_session, save_session
 
 logger = logging.getLogger(__name__)
 
+# Model attributes saved with a session next to the measurements and compounds
+SESSION_SETTINGS = (
+    "annotations", "mass_accuracy", "smoothing", "baseline", "centroiding", "link_ms2",
+    "isotopes", "polarity", "scan_filter", "calibration_model", "calibration_weighting",
+    "rt_alignment", "rt_shifts", "gap_filling", "feature_tables", "blank_mode", "blank_ratio",
+    "deconvolution", "peak_fitting", "lock_mass", "noise_threshold", "xic_mode", "scan_cache",
+    "file_ranges", "precision", "qc", "peak_purity", "mass_errors", "charge_states",
+    "injection_order", "batch_trends", "drift_correction", "file_statuses", "processing_config",
+)
+
 
 class Model(QObject):
     """
@@ -51,6 +69,41 @@ class Model(QObject):
         "compounds",
         "worker",
         "mass_accuracy",
+        "smoothing",
+        "baseline",
+        "centroiding",
+        "link_ms2",
+        "isotopes",
+        "polarity",
+        "scan_filter",
+        "calibration_model",
+        "calibration_weighting",
+        "rt_alignment",
+        "rt_shifts",
+        "gap_filling",
+        "feature_tables",
+        "blank_mode",
+        "blank_ratio",
+        "deconvolution",
+        "peak_fitting",
+        "lock_mass",
+        "noise_threshold",
+        "xic_mode",
+        "n_workers",
+        "low_priority",
+        "memory_budget",
+        "scan_cache",
+        "file_ranges",
+        "precision",
+        "qc",
+        "peak_purity",
+        "mass_errors",
+        "charge_states",
+        "injection_order",
+        "batch_trends",
+        "drift_correction",
+        "file_statuses",
+        "processing_config",
         "_current_worker_id",
     ]
 
@@ -61,6 +114,41 @@ class Model(QObject):
         self.annotations = tuple()
         self.compounds = tuple()
         self.mass_accuracy = 0.0001
+        self.smoothing = None  # XIC smoothing settings, see calculation.smoothing
+        self.baseline = None  # XIC baseline subtraction, see calculation.baseline
+        self.centroiding = None  # Centroid profile scans first: "local_max" / "gaussian"
+        self.link_ms2 = False  # Attach DDA MS2 scans to the integrated ions
+        self.isotopes = 0  # Isotopologues (M+1 ... M+n) to extract and score per ion
+        self.polarity = None  # Only use "positive" / "negative" scans (polarity switching)
+        self.scan_filter = None  # MS level / filter string / precursor selection, see utils.scan_filter
+        self.calibration_model = "linear"  # "linear" / "quadratic", see calculation.calibration
+        self.calibration_weighting = "none"  # "none" / "1/x" / "1/x2"
+        self.rt_alignment = None  # Align RTs across files: "linear" / "loess", see calculation.alignment
+        self.rt_shifts = dict()  # {filename: RTShift} from the last aligned run
+        self.gap_filling = False  # Integrate peaks missed in some files, see calculation.gap_filling
+        self.feature_tables = dict()  # {filename: untargeted feature table}, see calculation.features
+        self.blank_mode = None  # Compare with blank files: "flag" / "subtract", see calculation.blanks
+        self.blank_ratio = DEFAULT_BLANK_RATIO  # Sample/blank area ratio below which ions are flagged
+        self.deconvolution = None  # Charge-state deconvolution settings, see calculation.deconvolution
+        self.peak_fitting = None  # Fit overlapping peaks: "gaussian" / "emg", see calculation.peak_fitting
+        self.lock_mass = None  # Lock-mass recalibration settings, see calculation.recalibration
+        self.noise_threshold = None  # Absolute / base-peak-relative peak threshold, see calculation.noise
+        self.xic_mode = "sum"  # XIC intensity per scan: "sum" / "max" / "nearest", see preprocessing.XIC_MODES
+        self.n_workers = None  # Cores for loading/processing, None for all (see workers.set_num_threads)
+        self.low_priority = False  # Run the worker processes at a lower priority to keep the UI responsive
+        self.memory_budget = None  # Bytes or e.g. "4GB" for loading/processing, see utils.memory
+        self.scan_cache = False  # Reuse decoded scans across runs, see utils.scan_cache
+        self.file_ranges = dict()  # {filename: {"rt_range"/"scan_range": (start, end)}} to process
+        self.precision = "float32"  # "float64" for high-resolution data, see preprocessing.PRECISIONS
+        self.qc = False  # Per-file QC metrics: True or QC_THRESHOLDS overrides, see calculation.qc
+        self.peak_purity = False  # Flag coeluting interferences, see calculation.purity
+        self.mass_errors = False  # Per-ion m/z error statistics, see calculation.mass_error
+        self.charge_states = False  # Per-ion charge from the isotope spacing, see calculation.charge
+        self.injection_order = None  # Measurement filenames in injection order, None for loading order
+        self.batch_trends = dict()  # {"drift", "carryover"} of the last run, see calculation.drift
+        self.drift_correction = None  # Normalize areas to the pooled QC files: "loess" / "spline"
+        self.file_statuses = dict()  # {filename: FileStatus} from the last run, see calculation.status
+        self.processing_config = None  # Effective options of the last run, see ProcessingConfig.to_dict
         self.controller = None
         self.worker = None
         self._current_worker_id = 0  # Track worker identity to prevent stale callbacks
@@ -73,7 +161,11 @@ class Model(QObject):
         self._current_worker_id += 1
         worker_id = self._current_worker_id
 
-        self.worker = LoadingWorker(self, mode, file_paths, file_type)
+        self.worker = LoadingWorker(
+            self, mode, file_paths, file_type,
+            n_workers=self.n_workers, low_priority=self.low_priority,
+            memory_budget=self.memory_budget,
+        )
         self.worker.worker_id = worker_id  # Tag worker with its ID
         self.worker.progressUpdated.connect(self.controller.view.update_progressBar)
         self.worker.progressUpdated.connect(
@@ -83,13 +175,69 @@ class Model(QObject):
         self.worker.error.connect(self.controller.on_worker_error)
         self.worker.start()
 
+    def config(self) -> ProcessingConfig:
+        """The processing options set on the model as a ProcessingConfig."""
+        return ProcessingConfig.from_dict(
+            {name: getattr(self, name) for name in ProcessingConfig.model_fields}
+        )
+
+    def apply_config(self, config):
+        """
+        Set the processing options of a ProcessingConfig, a dict of its
+        options or a TOML file on the model; options not given are reset
+        to their defaults.
+        """
+        for name, value in ProcessingConfig.coerce(config).to_dict().items():
+            setattr(self, name, value)
+
     def process(self, mode):
-        self.worker = ProcessingWorker(self, mode, self.mass_accuracy)
+        try:
+            config = self.config()
+        except ValueError as e:
+            logger.error(f"Invalid processing options: {e}")
+            self.controller.on_worker_error(str(e))
+            return
+        self.worker = ProcessingWorker(self, mode, config=config)
         self.worker.progressUpdated.connect(self.controller.view.update_progressBar)
+        self.worker.fileFinished.connect(self.controller.on_file_processed)
         self.worker.finished.connect(self.controller.on_processing_finished)
+        self.worker.cancelled.connect(self.controller.on_processing_cancelled)
         self.worker.error.connect(self.controller.on_worker_error)
         self.worker.start()
 
+    def cancel_processing(self):
+        """Cancel a running ProcessingWorker; the files it completed are kept."""
+        if isinstance(self.worker, ProcessingWorker) and self.worker.isRunning():
+            logger.info("Cancelling the processing...")
+            self.worker.cancel()
+
+    def detect_features(self, settings=None):
+        """
+        Start untargeted feature detection on every loaded MS file.
+
+        *settings* are passed on to calculation.features.detect_features;
+        the tables end up in ``feature_tables``.
+        """
+        self._current_worker_id += 1
+        worker_id = self._current_worker_id
+
+        self.worker = FeatureDetectionWorker(
+            self, settings, n_workers=self.n_workers, low_priority=self.low_priority
+        )
+        self.worker.worker_id = worker_id
+        self.worker.progressUpdated.connect(self.controller.view.update_progressBar)
+        self.worker.finished.connect(self._on_features_detected)
+        self.worker.error.connect(self.controller.on_worker_error)
+        self.worker.start()
+
+    def _on_features_detected(self, feature_tables):
+        self.feature_tables = feature_tables
+        logger.info(
+            f"Feature detection finished: "
+            f"{sum(len(table) for table in feature_tables.values())} features "
+            f"in {len(feature_tables)} files"
+        )
+
     def get_plots(self, filename):
         # Find the corresponding MS and LC files
         ms_file = self.ms_measurements.get(filename, None)
@@ -120,6 +268,36 @@ class Model(QObject):
 
         return compound_signal, peak_area_was_used
 
+    def _get_calibration_signal(self, ms_file, index, use_peak_area=True):
+        """
+        Signal of compound ``index`` in ``ms_file`` for calibration.
+
+        Compounds with an internal standard give the analyte/IS ratio; None if
+        the internal standard has no signal in that file.
+        """
+        ms_compound = ms_file.xics[index]
+        compound_signal, peak_area_was_used = self._get_compound_signal(
+            ms_compound, use_peak_area=use_peak_area
+        )
+        is_name = self.compounds[index].internal_standard
+        if not is_name:
+            return compound_signal, peak_area_was_used
+
+        is_signal = None
+        for is_compound in ms_file.xics:
+            if is_compound.name == is_name:
+                is_signal, _ = self._get_compound_signal(
+                    is_compound, use_peak_area=use_peak_area
+                )
+                break
+        ratio = internal_standard_ratio(compound_signal, is_signal)
+        if ratio is None:
+            logger.warning(
+                f"Internal standard {is_name} has no signal in {ms_file.filename}, "
+                f"{ms_compound.name} cannot be normalized and is skipped."
+            )
+        return ratio, peak_area_was_used
+
     def calibrate(self, selected_files):
         for i, compound in enumerate(self.compounds):
             if not compound.ions:
@@ -129,6 +307,7 @@ class Model(QObject):
             # 1. Initial calibration attempt using peak areas
             use_peak_area_calibration = True
             compound.calibration_curve.clear()
+            blank_signals = []  # Zero-concentration replicates, for the LOD/LOQ
             for file, concentration_str in selected_files.items():
                 if not concentration_str.strip():
                     continue
@@ -151,24 +330,30 @@ class Model(QObject):
                     logger.error(f"No xics found for file {file}.")
                     continue
 
-                ms_compound = ms_file.xics[i]
-                compound_signal, _ = self._get_compound_signal(
-                    ms_compound, use_peak_area=True
+                compound_signal, _ = self._get_calibration_signal(
+                    ms_file, i, use_peak_area=True
                 )
+                if compound_signal is None:
+                    continue
                 compound.calibration_curve[concentration] = compound_signal
+                if concentration == 0:
+                    blank_signals.append(compound_signal)
 
-            # 2. Perform linear regression and check R²
+            # 2. Fit the calibration curve and check R²
             concentrations = list(compound.calibration_curve.keys())
             signals = list(compound.calibration_curve.values())
 
-            if len(concentrations) < 2:
-                logger.error(f"Not enough calibration points for {compound.name}.")
+            try:
+                fit = fit_calibration(
+                    concentrations,
+                    signals,
+                    model=self.calibration_model,
+                    weighting=self.calibration_weighting,
+                )
+            except ValueError as e:
+                logger.error(f"Cannot calibrate {compound.name}: {e}")
                 continue
-
-            slope, intercept, r_value, p_value, std_err = linregress(
-                concentrations, signals
-            )
-            r_squared = r_value**2
+            r_squared = fit["r_squared"]
 
             # 3. Fallback to intensity sum if R² is poor
             if r_squared < 0.75:
@@ -177,6 +362,7 @@ class Model(QObject):
                 )
                 use_peak_area_calibration = False
                 compound.calibration_curve.clear()
+                blank_signals = []
                 for file, concentration_str in selected_files.items():
                     if not concentration_str.strip():
                         continue
@@ -198,29 +384,35 @@ class Model(QObject):
                     if not ms_file or not ms_file.xics:
                         continue
 
-                    ms_compound = ms_file.xics[i]
-                    compound_signal, _ = self._get_compound_signal(
-                        ms_compound, use_peak_area=False
+                    compound_signal, _ = self._get_calibration_signal(
+                        ms_file, i, use_peak_area=False
                     )
+                    if compound_signal is None:
+                        continue
                     compound.calibration_curve[concentration] = compound_signal
+                    if concentration == 0:
+                        blank_signals.append(compound_signal)
 
                 concentrations = list(compound.calibration_curve.keys())
                 signals = list(compound.calibration_curve.values())
-                slope, intercept, r_value, p_value, std_err = linregress(
-                    concentrations, signals
-                )
-                r_squared = r_value**2
+                try:
+                    fit = fit_calibration(
+                        concentrations,
+                        signals,
+                        model=self.calibration_model,
+                        weighting=self.calibration_weighting,
+                    )
+                except ValueError as e:
+                    logger.error(f"Cannot calibrate {compound.name}: {e}")
+                    continue
+                r_squared = fit["r_squared"]
                 logger.info(
                     f"Recalibrated {compound.name} with intensity sum, new R²: {r_squared:.2f}"
                 )
 
             compound.calibration_parameters = {
-                "slope": slope,
-                "intercept": intercept,
-                "r_value": r_value,
-                "r_squared": r_squared,
-                "p_value": p_value,
-                "std_err": std_err,
+                **fit,
+                **detection_limits(fit, blank_signals),
                 "use_peak_area": use_peak_area_calibration,
             }
 
@@ -231,7 +423,9 @@ class Model(QObject):
                     f"Skipping concentration calculation for {ms_file.filename}: no XIC data"
                 )
                 continue
-            for ms_compound, model_compound in zip(ms_file.xics, self.compounds):
+            for i, (ms_compound, model_compound) in enumerate(
+                zip(ms_file.xics, self.compounds)
+            ):
                 try:
                     if not model_compound.calibration_parameters:
                         continue
@@ -239,9 +433,13 @@ class Model(QObject):
                     use_peak_area_for_calc = model_compound.calibration_parameters.get(
                         "use_peak_area", True
                     )
-                    compound_signal, peak_area_was_used = self._get_compound_signal(
-                        ms_compound, use_peak_area=use_peak_area_for_calc
+                    compound_signal, peak_area_was_used = self._get_calibration_signal(
+                        ms_file, i, use_peak_area=use_peak_area_for_calc
                     )
+                    if compound_signal is None:
+                        # Missing internal standard: not quantifiable in this file
+                        ms_compound.concentration = None
+                        continue
 
                     if peak_area_was_used:
                         logger.info(
@@ -258,6 +456,11 @@ class Model(QObject):
                     ms_compound.calibration_parameters = (
                         model_compound.calibration_parameters
                     )
+                    if ms_compound.below_loq:
+                        logger.info(
+                            f"{ms_compound.name} in {ms_file.filename} is below the LOQ "
+                            f"({model_compound.calibration_parameters['loq']:.6g})"
+                        )
                 except Exception:
                     logger.error(
                         f"Error calculating concentration for {ms_compound.name} in {ms_file.filename}: {traceback.format_exc()}"
@@ -282,6 +485,11 @@ class Model(QObject):
                         "Ion (m/z)": ion,
                         "Compound": compound.name,
                         "RT (min)": np.round(data["RT"], 3),
+                        "Aligned RT (min)": (
+                            np.round(data["Aligned RT"], 3)
+                            if data.get("Aligned RT") is not None
+                            else None
+                        ),
                         "MS Intensity (cps)": np.round(
                             np.sum(data["MS Intensity"][1])
                             if data["MS Intensity"] is not None
@@ -289,6 +497,17 @@ class Model(QObject):
                             0,
                         ),
                         "LC Intensity (a.u.)": data.get("LC Intensity", 0),
+                        "Blank Area": (data.get("Blank") or {}).get("area"),
+                        "Sample/Blank Ratio": (data.get("Blank") or {}).get("ratio"),
+                        "Below Blank Threshold": (data.get("Blank") or {}).get(
+                            "below_threshold"
+                        ),
+                        "Peak Purity": (data.get("Purity") or {}).get("purity"),
+                        "Apex m/z": (data.get("Mass Error") or {}).get("apex_mz"),
+                        "Mass Error (ppm)": (data.get("Mass Error") or {}).get("median_ppm"),
+                        "Mass Error SD (ppm)": (data.get("Mass Error") or {}).get("sd_ppm"),
+                        "Charge": (data.get("Charge State") or {}).get("charge"),
+                        "RT Score": (data.get("RT Score") or {}).get("score"),
                         "Ion name": str(ion_name).strip() if ion_name else ion,
                     }
 
@@ -360,6 +579,9 @@ class Model(QObject):
                         results_dict["Calibration R2"] = (
                             compound.calibration_parameters.get("r_squared", 0)
                         )
+                        results_dict["LOD (mM)"] = compound.calibration_parameters.get("lod")
+                        results_dict["LOQ (mM)"] = compound.calibration_parameters.get("loq")
+                        results_dict["Below LOQ"] = compound.below_loq
                     except Exception as e:
                         logger.error(
                             f"Error exporting concentration information for {ms_measurement.filename}: {e}"
@@ -371,9 +593,34 @@ class Model(QObject):
                     results.append(results_dict)
 
         df = pd.DataFrame(results)
+        # Written next to the exported table, see utils.manifest
+        df.attrs["manifest"] = batch_manifest(self.ms_measurements.values())
         logger.info(f"Exported {len(df)} rows with peak area information")
         return df
 
+    def save_session(self, path):
+        """Save the loaded files, results and settings, see utils.session_file."""
+        return save_session(
+            path,
+            self.ms_measurements,
+            self.lc_measurements,
+            self.compounds,
+            settings={name: getattr(self, name) for name in SESSION_SETTINGS},
+        )
+
+    def load_session(self, path):
+        """Replace the current analysis with a saved session."""
+        session = load_session(path)
+        self.clear_measurements()
+        self.ms_measurements.update(session.ms_measurements)
+        self.lc_measurements.update(session.lc_measurements)
+        self.compounds = session.compounds
+        for name, value in session.settings.items():
+            if name in SESSION_SETTINGS:
+                setattr(self, name, value)
+        logger.info(f"Restored session {path} with {len(self.ms_measurements)} MS files")
+        return session
+
     def shutdown(self):
         """Gracefully stop any running workers and threads."""
         logger.debug("Trying to shut down model and workers...")
@@ -17,7 +17,9 @@ from static_frame import FrameHE
 from pyteomics.mzml import MzML
 
 # Assuming these exist in your project structure
+from calculation.downsampling import downsample_trace
 from ui import fonts
+from utils.loading import spectrum_metadata, spectrum_ms_level
 
 logger = logging.getLogger(__name__)
 logger.propagate = False
@@ -79,6 +81,22 @@ class PlotStyle:
 # --- Plotting Functions ---
 
 
+def describe_scan(metadata: dict) -> str:
+    """One-line acquisition context of a scan, e.g. "MS2, IT 35.0 ms, NCE 30 %, R 30000"."""
+    parts = [f"MS{metadata.get('ms_level') or 1}"]
+    if metadata.get("injection_time") is not None:
+        parts.append(f"IT {metadata['injection_time']:.1f} ms")
+    if metadata.get("normalized_collision_energy") is not None:
+        parts.append(f"NCE {metadata['normalized_collision_energy']:g} %")
+    elif metadata.get("collision_energy") is not None:
+        parts.append(f"CE {metadata['collision_energy']:g} eV")
+    if metadata.get("resolution") is not None:
+        parts.append(f"R {metadata['resolution']:g}")
+    if metadata.get("filter_string"):
+        parts.append(metadata["filter_string"])
+    return ", ".join(parts)
+
+
 def plot_absorbance_data(
     path: str,
     dataframe: pd.DataFrame,
@@ -170,11 +188,11 @@ def plot_average_ms_data(
     """
     try:
         spectrum = data_matrix.time[rt]
-        if spectrum["ms level"] > 1:
+        if spectrum_ms_level(spectrum) > 1:
             # DDA shield
             spec_range = data_matrix.time[rt - 0.1 : rt + 0.1]
             for spec in spec_range:
-                if spec["ms level"] == 1:
+                if spectrum_ms_level(spec) == 1:
                     spectrum = spec
                     break
 
@@ -189,7 +207,7 @@ def plot_average_ms_data(
     if clear:
         PlotStyle.apply_standard_style(
             widget,
-            title="Mass Spectrometry Data",
+            title=f"Mass Spectrometry Data ({describe_scan(spectrum_metadata(spectrum))})",
             x_label="m/z",
             y_label="Intensity / a.u.",
         )
@@ -344,8 +362,7 @@ def plot_annotated_XICs(xics: tuple, widget: DockArea):
 
                 # Plot trace
                 plot_widget.plot(
-                    x_data,
-                    y_data,
+                    *downsample_trace(x_data, y_data),
                     pen=mkPen(current_color, width=1),
                     name=f"{ion_key} {info_str}",
                 )
@@ -385,7 +402,11 @@ def plot_calibration_curve(compound, widget: pg.PlotWidget):
         widget,
         title=f"Calibration: {compound.name}",
         x_label="Concentration (mM)",
-        y_label="Intensity / a.u.",
+        y_label=(
+            f"Signal ratio to {compound.internal_standard}"
+            if getattr(compound, "internal_standard", None)
+            else "Intensity / a.u."
+        ),
     )
 
     # Validate Data
@@ -410,14 +431,22 @@ def plot_calibration_curve(compound, widget: pg.PlotWidget):
         try:
             m = params["slope"]
             b = params["intercept"]
-            r2 = params.get("r_value", 0) ** 2
+            a = params.get("quadratic", 0.0) or 0.0
+            r2 = params.get("r_squared", params.get("r_value", 0) ** 2)
 
-            # Plot Line
-            curve_y = m * x + b
-            widget.plot(x, curve_y, pen=mkPen("r", width=2), name="Fit")
+            # Plot Line (sampled densely so quadratic fits draw as curves)
+            curve_x = np.linspace(np.min(x), np.max(x), 200)
+            curve_y = a * curve_x**2 + m * curve_x + b
+            widget.plot(curve_x, curve_y, pen=mkPen("r", width=2), name="Fit")
 
             # Annotation
-            eq_text = f"y = {m:.2f}x + {b:.2f}\nR² = {r2:.4f}"
+            eq_text = f"y = {m:.2f}x + {b:.2f}"
+            if a:
+                eq_text = f"y = {a:.4g}x² + {m:.2f}x + {b:.2f}"
+            weighting = params.get("weighting", "none")
+            if weighting != "none":
+                eq_text += f" (weight {weighting.replace('x2', 'x²')})"
+            eq_text += f"\nR² = {r2:.4f}"
             text_item = pg.TextItem(
                 text=eq_text, color="#3c5488", border=mkPen("#3c5488"), anchor=(0, 0)
             )
@@ -447,8 +476,7 @@ def plot_total_ion_current(
     # Use pre-extracted TIC data (no iteration needed - instant)
     if ms_measurement.tic_times is not None and len(ms_measurement.tic_times) > 0:
         widget.plot(
-            ms_measurement.tic_times,
-            ms_measurement.tic_values,
+            *downsample_trace(ms_measurement.tic_times, ms_measurement.tic_values),
             pen=mkPen("#3c5488ff", width=1),
         )
 
@@ -58,6 +58,7 @@ def retranslateUi(MainWindow):
                 )
             )
         MainWindow.processButton.setText(_translate("MainWindow", "Process"))
+        MainWindow.cancelButton.setText(_translate("MainWindow", "Cancel"))
 
         # Tab titles
         MainWindow.tabWidget.setTabText(
@@ -23,6 +23,9 @@ from ui.widgets import (
 )
 from ui.plotting import plot_placeholder, update_labels_avgMS
 from ui.utils import clear_layout, create_crosshair_lines, create_crosshair_proxy
+from utils.classes import compounds_from_ion_list
+from utils.errors import IonListError
+from utils.loading import spectrum_ms_level
 
 logger = logging.getLogger(__name__)
 
@@ -86,6 +89,11 @@ class UploadTab(TabBase):
         """Access the process button widget."""
         return self.processButton
 
+    @property
+    def cancel_button(self):
+        """Access the cancel button widget."""
+        return self.cancelButton
+
     @property
     def ion_table(self):
         """Access the ion table widget."""
@@ -328,6 +336,11 @@ class UploadTab(TabBase):
         self.processButton.setDefault(True)
         self.processButton.setEnabled(False)
 
+        # Cancel button, shown in place of the process button while processing
+        self.cancelButton = QtWidgets.QPushButton("Cancel")
+        self.cancelButton.setObjectName("cancelButton")
+        self.cancelButton.setVisible(False)
+
     def _build_canvas_widgets(self):
         """Create canvas widgets for plotting."""
         from ui.plotting import PlotStyle
@@ -396,7 +409,7 @@ class UploadTab(TabBase):
         self.browseLC = QtWidgets.QPushButton("Browse")
         self.help_icon_lc = self._create_help_icon(
             "<b>Add Chromatography Files</b><br>"
-            "Supported formats: .txt, .csv<br><br>"
+            "Supported formats: .txt, .csv, .cdf<br><br>"
             "<b>How to add files:</b><br>"
             "- Click Browse to select files<br>"
             "- Drag & drop files directly<br>"
@@ -410,7 +423,7 @@ class UploadTab(TabBase):
         self.browseMS = QtWidgets.QPushButton("Browse")
         self.help_icon_ms = self._create_help_icon(
             "<b>Add Mass Spectrometry Files</b><br>"
-            "Supported formats: .mzML<br><br>"
+            "Supported formats: .mzML, .mzXML, .mgf, .cdf<br><br>"
             "<b>How to add files:</b><br>"
             "- Click Browse to select files<br>"
             "- Drag & drop files directly<br>"
@@ -469,6 +482,7 @@ class UploadTab(TabBase):
         # Processing row
         self._main_layout.addWidget(self.mass_accuracy_slider, 7, 4, 1, 3)
         self._main_layout.addWidget(self.processButton, 7, 2, 1, 2)
+        self._main_layout.addWidget(self.cancelButton, 7, 2, 1, 2)
 
         # Canvas pane (middle)
         self.resultsPane = QtWidgets.QWidget(parent=self)
@@ -495,7 +509,7 @@ class UploadTab(TabBase):
         self.browseMS = QtWidgets.QPushButton("Browse")
         self.help_icon_ms = self._create_help_icon(
             "<b>Add Mass Spectrometry Files</b><br>"
-            "Supported formats: .mzML<br><br>"
+            "Supported formats: .mzML, .mzXML, .mgf, .cdf<br><br>"
             "<b>How to add files:</b><br>"
             "- Click Browse to select files<br>"
             "- Drag & drop files directly<br>"
@@ -538,6 +552,7 @@ class UploadTab(TabBase):
         # Processing row
         self._main_layout.addWidget(self.mass_accuracy_slider, 7, 4, 1, 3)
         self._main_layout.addWidget(self.processButton, 7, 2, 1, 2)
+        self._main_layout.addWidget(self.cancelButton, 7, 2, 1, 2)
 
         # Canvas pane (middle)
         self.resultsPane = QtWidgets.QWidget(parent=self)
@@ -565,7 +580,7 @@ class UploadTab(TabBase):
         self.browseLC = QtWidgets.QPushButton("Browse")
         self.help_icon_lc = self._create_help_icon(
             "<b>Add Chromatography Files</b><br>"
-            "Supported formats: .txt, .csv<br><br>"
+            "Supported formats: .txt, .csv, .cdf<br><br>"
             "<b>How to add files:</b><br>"
             "- Click Browse to select files<br>"
             "- Drag & drop files directly<br>"
@@ -612,6 +627,7 @@ class UploadTab(TabBase):
         # Processing row
         self._main_layout.addWidget(self.mass_accuracy_slider, 7, 4, 1, 3)
         self._main_layout.addWidget(self.processButton, 7, 2, 1, 2)
+        self._main_layout.addWidget(self.cancelButton, 7, 2, 1, 2)
 
         # Stretch settings
         self._main_layout.setRowStretch(2, 3)
@@ -709,8 +725,8 @@ class UploadTab(TabBase):
         Validates and adds dropped files, then emits signal for Controller.
         """
         valid_extensions = {
-            "LC": [".txt", ".csv"],
-            "MS": [".mzml"],
+            "LC": [".txt", ".csv", ".cdf"],
+            "MS": [".mzml", ".mzxml", ".mgf", ".cdf"],
             "Annotations": [".txt"],
         }
 
@@ -758,7 +774,7 @@ class UploadTab(TabBase):
             self,
             "Select LC Files",
             str(QtCore.QDir.homePath()),
-            "Text Files (*.txt);;CSV Files (*.csv);;All Files (*)",
+            "Text Files (*.txt);;CSV Files (*.csv);;ANDI/netCDF Files (*.cdf);;All Files (*)",
         )
         if files:
             self.handle_files_dropped(files, "LC")
@@ -769,7 +785,7 @@ class UploadTab(TabBase):
             self,
             "Select MS Files",
             str(QtCore.QDir.homePath()),
-            "MzML Files (*.mzML);;All Files (*)",
+            "MS Files (*.mzML *.mzXML *.mgf *.cdf);;All Files (*)",
         )
         if files:
             self.handle_files_dropped(files, "MS")
@@ -1287,6 +1303,7 @@ class UploadTab(TabBase):
         self._remove_theoretical_plots()
         self._color_index_theo = 0
         self.ionTable._theoretical_spectra.clear()
+        self.ionTable._compound_options.clear()
         self.ionTable.clearContents()
         self.ionTable.setRowCount(0)
 
@@ -1319,6 +1336,7 @@ class UploadTab(TabBase):
         # Always clear theoretical plots when switching lists
         self._remove_theoretical_plots()
         self.ionTable._theoretical_spectra.clear()
+        self.ionTable._compound_options.clear()
 
         # Handle "Create new" or empty selection
         if not selection or selection == "Create new ion list...":
@@ -1356,8 +1374,9 @@ class UploadTab(TabBase):
                     # Name
                     self.ionTable.setItem(row, 0, QtWidgets.QTableWidgetItem(str(name)))
 
+                    ions, info = self._entry_ions(name, details)
+
                     # Ions (Handle list or string legacy format)
-                    ions = details.get("ions", [])
                     if isinstance(ions, list):
                         ions_str = ", ".join(map(str, ions))
                     else:
@@ -1365,7 +1384,6 @@ class UploadTab(TabBase):
                     self.ionTable.setItem(row, 1, QtWidgets.QTableWidgetItem(ions_str))
 
                     # Info (Handle list or string legacy format)
-                    info = details.get("info", [])
                     if isinstance(info, list):
                         info_str = ", ".join(map(str, info))
                     else:
@@ -1377,6 +1395,16 @@ class UploadTab(TabBase):
             # Clear custom m/z ranges when ion list changes
             self.ionTable._custom_mz_ranges.clear()
 
+            # Options without a table column (RT window, smoothing, ...)
+            for name, details in compound_data.items():
+                options = {
+                    key: details[key]
+                    for key in self.ionTable.COMPOUND_OPTION_KEYS
+                    if details.get(key) is not None
+                }
+                if options:
+                    self.ionTable._compound_options[name] = options
+
             # Compute theoretical spectra for compounds with formulas
             self._compute_theoretical_spectra_for_ion_list(ion_data)
 
@@ -1384,6 +1412,25 @@ class UploadTab(TabBase):
             logger.error(f"Error updating ion list: {e}")
             self.status_message.emit(f"Error updating ion list: {e}", 3000)
 
+    def _entry_ions(self, name, details):
+        """
+        Ions and labels of an ion list entry as the processing expands them.
+
+        Masses, formula-only entries, charge ranges and SRM transitions are
+        expanded by compounds_from_ion_list, with the checked adducts as the
+        list's ``_adducts``. Invalid entries keep their listed ions.
+        """
+        # The internal standard is checked against the whole list, not one entry
+        entry = {key: value for key, value in details.items() if key != "internal_standard"}
+        try:
+            (compound,) = compounds_from_ion_list(
+                {"_adducts": self.adduct_dropdown.checked_adducts(), name: entry}
+            )
+        except IonListError as e:
+            logger.warning(f"Invalid ion list entry {name}: {e}")
+            return details.get("ions", []), details.get("info", [])
+        return compound.target_list, compound.ion_info
+
     # --- Plotting Methods ---
 
     def _plot_raw_chromatography(self, lc_file):
@@ -1582,10 +1629,10 @@ class UploadTab(TabBase):
         time_x = float(self.line_marker.pos().x()) if self.line_marker.isVisible() else 0.0
         try:
             spectrum = ms_data.data.time[time_x]
-            if spectrum["ms level"] > 1:
+            if spectrum_ms_level(spectrum) > 1:
                 spec_range = ms_data.data.time[time_x - 0.1 : time_x + 0.1]
                 for spec in spec_range:
-                    if spec["ms level"] == 1:
+                    if spectrum_ms_level(spec) == 1:
                         spectrum = spec
                         break
             return spectrum["m/z array"], spectrum["intensity array"]
@@ -28,6 +28,7 @@ from ui.tabs.upload_tab import UploadTab
 from ui.tabs.results_tab import ResultsTab
 from ui.tabs.quantitation_tab import QuantitationTab
 from ui.retranslate_ui import retranslateUi
+from utils.manifest import write_manifest
 
 
 pg.setConfigOptions(antialias=True)
@@ -66,6 +67,11 @@ class View(QtWidgets.QMainWindow):
         """Access process button from UploadTab."""
         return self.upload_tab.processButton
 
+    @property
+    def cancelButton(self):
+        """Access cancel button from UploadTab."""
+        return self.upload_tab.cancelButton
+
     @property
     def ionTable(self):
         """Access ion table from UploadTab."""
@@ -282,6 +288,8 @@ class View(QtWidgets.QMainWindow):
             if file_name:
                 with open(file_name, "w") as f:
                     f.write(results.to_csv(index=False))
+                if results.attrs.get("manifest"):
+                    write_manifest(file_name, results.attrs["manifest"])
                 output_folder = os.path.dirname(file_name)
                 self.statusbar.showMessage(
                     f"{datetime.now().strftime('%Y-%m-%d %H:%M:%S')} -- Saved results to {output_folder}",
@@ -7,7 +7,7 @@ import pyqtgraph as pg
 from pyqtgraph import mkPen
 from PySide6 import QtCore, QtGui, QtWidgets
 from PySide6.QtCore import Qt
-from utils.classes import Compound
+from utils.classes import compounds_from_ion_list
 
 
 class DragDropListWidget(QtWidgets.QListWidget):
@@ -347,6 +347,21 @@ class IonTable(GenericTable):
     # Signal emitted when a compound is removed from the table
     compound_removed = QtCore.Signal(str)  # compound_name
 
+    # Ion list keys carried through to Compound without their own table column
+    COMPOUND_OPTION_KEYS = (
+        "rt_min",
+        "rt_max",
+        "rt",
+        "ri",
+        "smoothing",
+        "polarity",
+        "transitions",
+        "mobility_range",
+        "internal_standard",
+        "tolerance",
+        "tolerances",
+    )
+
     def __init__(self, view, parent=None):
         super().__init__(50, 3, parent)
         self.setHorizontalHeaderLabels(["Compound", "Expected m/z", "Add. info"])
@@ -360,6 +375,9 @@ class IonTable(GenericTable):
 
         # Custom m/z range storage: {compound_name: {mz: (left, right)}}
         self._custom_mz_ranges = {}
+        # Per-compound ion list keys without a table column (see COMPOUND_OPTION_KEYS)
+        # Structure: {compound_name: {"rt_min": 1.2, "smoothing": {...}, ...}}
+        self._compound_options = {}
 
         # Theoretical spectra storage: {compound_name: TheoreticalSpectrum}
         self._theoretical_spectra = {}
@@ -967,8 +985,20 @@ class IonTable(GenericTable):
             # Create list, filtering out empty strings
             ion_info = [x.strip() for x in info_text.split(",") if x.strip()]
 
+            # Formula of a looked-up compound, for isotope pattern scoring
+            formula = getattr(self._theoretical_spectra.get(name), "formula", None)
+
+            # Options are parsed like a backend ion list entry; the internal
+            # standard is checked against the other rows by the processing
+            options = dict(self._compound_options.get(name, {}))
+            internal_standard = options.pop("internal_standard", None)
+            entry = {"name": name, "ions": ions, "info": ion_info, **options}
+            if formula and ions:
+                entry["formula"] = formula
+
             try:
-                compound = Compound(name=name, target_list=ions, ion_info=ion_info)
+                (compound,) = compounds_from_ion_list([entry])
+                compound.internal_standard = internal_standard or None
                 if name in self._custom_mz_ranges:
                     compound.custom_mz_ranges = dict(self._custom_mz_ranges[name])
                 items.append(compound)
@@ -1021,7 +1051,10 @@ class IonTable(GenericTable):
                 x.strip() for x in info_text.split(",") if x.strip()
             ]
 
-            # 4. Persist formula or sequence if available (for auto-plotting on reload)
+            # 4. Persist options loaded with the list (RT window, smoothing, ...)
+            ions_data[name].update(self._compound_options.get(name, {}))
+
+            # 5. Persist formula or sequence if available (for auto-plotting on reload)
             if name in self._theoretical_spectra:
                 from utils.theoretical_spectrum import PeptideSpectrum
 
@@ -1031,7 +1064,7 @@ class IonTable(GenericTable):
                 else:
                     ions_data[name]["formula"] = spectrum.formula
 
-        # 5. Persist adduct selection
+        # 6. Persist adduct selection
         active_adducts = self._get_active_adducts()
         from utils.theoretical_spectrum import DEFAULT_ADDUCTS
 
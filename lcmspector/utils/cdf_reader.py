@@ -0,0 +1,294 @@
+"""
+Reader for ANDI/netCDF (AIA) chromatography and MS files.
+
+Many older LC and GC instruments export the ASTM Analytical Data
+Interchange format, a netCDF 3 file with a ``.cdf`` extension. Two
+flavours exist:
+
+- ANDI-MS (ASTM E2078): one survey scan per acquisition, with the peaks of
+  all scans concatenated in ``mass_values`` / ``intensity_values`` and
+  located through ``scan_index`` and ``point_count``. Times are in seconds.
+- ANDI-Chrom (ASTM E1947): a single detector trace in ``ordinate_values``,
+  sampled every ``actual_sampling_interval`` seconds from
+  ``actual_delay_time`` on, or at the times in ``raw_data_retention``.
+
+iter_scans / iter_ms2_scans / find_nearest_ms2 follow the streaming
+interface of utils.mzml_reader; read_chromatogram returns the same
+DataFrame as utils.loading.load_absorbance_data.
+"""
+
+import logging
+from contextlib import contextmanager
+
+import numpy as np
+import pandas as pd
+from scipy.io import netcdf_file
+
+from utils.mzml_reader import validate_polarity
+from utils.scan_filter import scan_matches
+
+logger = logging.getLogger(__name__)
+
+_MS_VARIABLES = ("scan_acquisition_time", "scan_index", "mass_values", "intensity_values")
+_CHROMATOGRAM_VARIABLES = ("ordinate_values",)
+
+
+@contextmanager
+def _open(source):
+    """Open a netCDF 3 file from a path or an open binary file."""
+    try:
+        dataset = netcdf_file(source, "r", mmap=False)
+    except (TypeError, ValueError, OSError) as e:
+        raise ValueError(f"Not a netCDF file: {getattr(source, 'name', source)} ({e})") from e
+    try:
+        yield dataset
+    finally:
+        dataset.close()
+
+
+def _attribute(dataset, name: str, default=None):
+    """A global attribute as a stripped string, or *default* if absent or empty."""
+    value = dataset._attributes.get(name)
+    if value is None:
+        return default
+    if isinstance(value, bytes):
+        value = value.decode("latin-1")
+    value = str(value).strip("\x00 ")
+    return value or default
+
+
+def _values(dataset, name: str) -> np.ndarray:
+    """A variable's data as float64, with its scale_factor applied."""
+    variable = dataset.variables[name]
+    values = np.array(variable.data, dtype=np.float64)
+    scale = getattr(variable, "scale_factor", None)
+    if scale is not None and float(np.asarray(scale).ravel()[0]) not in (0.0, 1.0):
+        values *= float(np.asarray(scale).ravel()[0])
+    return values
+
+
+def _scalar(dataset, name: str):
+    """A scalar stored as a variable or a global attribute, or None."""
+    if name in dataset.variables:
+        return float(_values(dataset, name).ravel()[0])
+    value = _attribute(dataset, name)
+    try:
+        return float(value) if value is not None else None
+    except ValueError:
+        return None
+
+
+def _andi_kind(dataset) -> str:
+    if all(name in dataset.variables for name in _MS_VARIABLES):
+        return "ms"
+    if all(name in dataset.variables for name in _CHROMATOGRAM_VARIABLES):
+        return "chromatogram"
+    return None
+
+
+def andi_kind(source) -> str:
+    """Return "ms" for ANDI-MS, "chromatogram" for ANDI-Chrom, or None for other netCDF files."""
+    with _open(source) as dataset:
+        return _andi_kind(dataset)
+
+
+def _file_polarity(dataset):
+    """'positive', 'negative' or None from the ``test_ionization_polarity`` attribute."""
+    polarity = (_attribute(dataset, "test_ionization_polarity") or "").lower()
+    if polarity.startswith("pos"):
+        return "positive"
+    if polarity.startswith("neg"):
+        return "negative"
+    return None
+
+
+def _read_scans(dataset) -> list:
+    missing = [name for name in _MS_VARIABLES if name not in dataset.variables]
+    if missing:
+        raise ValueError(f"Not an ANDI-MS file, missing variables: {', '.join(missing)}")
+    times = _values(dataset, "scan_acquisition_time") / 60.0
+    starts = np.array(dataset.variables["scan_index"].data, dtype=np.int64)
+    mz_values = _values(dataset, "mass_values")
+    intensity_values = _values(dataset, "intensity_values")
+    if "point_count" in dataset.variables:
+        counts = np.array(dataset.variables["point_count"].data, dtype=np.int64)
+    else:
+        counts = np.diff(np.append(starts, len(mz_values)))
+    tics = _values(dataset, "total_intensity") if "total_intensity" in dataset.variables else None
+
+    scans = []
+    for i, (scan_time, start, count) in enumerate(zip(times, starts, counts)):
+        mz = mz_values[start : start + count]
+        intensity = intensity_values[start : start + count]
+        tic = float(tics[i]) if tics is not None else float(intensity.sum())
+        scans.append((float(scan_time), tic, 1, mz, intensity))
+    return scans
+
+
+def read_scans(source) -> list:
+    """
+    Read all scans of an ANDI-MS file into memory.
+
+    Returns
+    -------
+    list of tuple
+        (scan_time, tic, ms_level, mz_array, intensity_array) per scan, in
+        acquisition order, times in minutes. ANDI-MS only stores survey
+        scans, so the MS level is always 1.
+
+    Raises
+    ------
+    ValueError
+        If *source* is not an ANDI-MS file.
+    """
+    with _open(source) as dataset:
+        return _read_scans(dataset)
+
+
+def iter_scans(
+    filepath,
+    polarity: str = None,
+    scan_filter: dict = None,
+    with_mobility: bool = False,
+    with_precursor: bool = False,
+):
+    """Yield (scan_time, tic, ms_level, mz_array, intensity_array) per scan.
+
+    Same tuple layout as utils.mzml_reader.iter_scans, times in minutes.
+    Polarity is a file-wide attribute in ANDI-MS: a *polarity* filter keeps
+    all scans or none, and files that do not record it are always kept. A
+    *scan_filter* sees no filter string or precursor. With *with_mobility*,
+    None is appended as the (unknown) ion mobility, with *with_precursor*
+    None as the precursor.
+    """
+    validate_polarity(polarity)
+    with _open(filepath) as dataset:
+        file_polarity = _file_polarity(dataset)
+        scans = _read_scans(dataset)
+    if polarity is not None and file_polarity is not None and file_polarity != polarity:
+        return
+    if not scan_matches(scan_filter, 1):
+        return  # All scans are MS1 without a filter string or precursor
+    extra = (None,) * (with_mobility + with_precursor)
+    for scan in scans:
+        yield (*scan, *extra) if extra else scan
+
+
+def iter_ms2_scans(filepath):
+    """ANDI-MS has no MS/MS scans; yields nothing."""
+    return
+    yield
+
+
+def find_nearest_ms2(
+    filepath: str,
+    precursor_mz: float,
+    target_rt: float,
+    mz_tolerance: float = 0.5,
+    rt_window: float = 2.0,
+):
+    """ANDI-MS has no MS/MS scans; always returns None."""
+    return None
+
+
+def read_chromatogram(filepath) -> pd.DataFrame:
+    """
+    Read the detector trace of an ANDI-Chrom file, or the TIC of an ANDI-MS file.
+
+    Returns
+    -------
+    pd.DataFrame
+        "Time (min)" and "Value (mAU)" columns, like
+        utils.loading.load_absorbance_data. The values are in the file's
+        ``detector_unit``, which is logged when it is not mAU.
+
+    Raises
+    ------
+    ValueError
+        If the file holds neither a chromatogram nor MS scans.
+    """
+    with _open(filepath) as dataset:
+        if "ordinate_values" not in dataset.variables:
+            if _andi_kind(dataset) != "ms":
+                raise ValueError("Not an ANDI-Chrom or ANDI-MS file")
+            scans = _read_scans(dataset)
+            return pd.DataFrame(
+                {
+                    "Time (min)": [scan[0] for scan in scans],
+                    "Value (mAU)": [scan[1] for scan in scans],
+                }
+            )
+
+        values = _values(dataset, "ordinate_values")
+        if "raw_data_retention" in dataset.variables:
+            times = _values(dataset, "raw_data_retention")
+        else:
+            interval = _scalar(dataset, "actual_sampling_interval")
+            if not interval or interval <= 0:
+                raise ValueError("ANDI-Chrom file has no sampling interval or retention times")
+            delay = _scalar(dataset, "actual_delay_time") or 0.0
+            times = delay + interval * np.arange(len(values))
+        unit = _attribute(dataset, "detector_unit")
+    if unit is not None and unit.lower() != "mau":
+        logger.info(f"Chromatogram of {getattr(filepath, 'name', filepath)} is in {unit}")
+    return pd.DataFrame({"Time (min)": times / 60.0, "Value (mAU)": values})
+
+
+class _TimeIndex:
+    """``reader.time[rt]`` / ``reader.time[a:b]`` lookups as in pyteomics' indexed readers."""
+
+    def __init__(self, reader):
+        self._reader = reader
+
+    def __getitem__(self, key):
+        times = self._reader._times
+        if isinstance(key, slice):
+            start = np.searchsorted(times, key.start if key.start is not None else -np.inf)
+            stop = np.searchsorted(times, key.stop if key.stop is not None else np.inf, side="right")
+            return [self._reader[i] for i in range(start, stop)]
+        if not len(times):
+            raise IndexError("ANDI-MS file has no scans")
+        return self._reader[int(np.argmin(np.abs(times - float(key))))]
+
+
+class AndiMSReader:
+    """
+    In-memory access to the scans of an ANDI-MS file, standing in for the
+    pyteomics readers returned by utils.loading.load_ms_data.
+
+    Scans are returned as pyteomics-style dicts with "m/z array",
+    "intensity array", "ms level", "total ion current" and
+    "scan start time" (min) keys.
+    """
+
+    def __init__(self, path: str):
+        self.path = path
+        self._scans = read_scans(path)
+        self._times = np.array([scan[0] for scan in self._scans], dtype=np.float64)
+        self.time = _TimeIndex(self)
+
+    def __len__(self):
+        return len(self._scans)
+
+    def __getitem__(self, index: int) -> dict:
+        scan_time, tic, ms_level, mz, intensity = self._scans[index]
+        return {
+            "index": index,
+            "ms level": ms_level,
+            "scan start time": scan_time,
+            "total ion current": tic,
+            "m/z array": mz,
+            "intensity array": intensity,
+        }
+
+    def __iter__(self):
+        return (self[i] for i in range(len(self)))
+
+    def __enter__(self):
+        return self
+
+    def __exit__(self, *exc):
+        self.close()
+
+    def close(self):
+        """Nothing to release, the file is closed after reading."""
@@ -1,13 +1,22 @@
-from typing import List, Dict, Optional, Any
-from pydantic import BaseModel, Field, PrivateAttr
+from typing import List, Dict, Literal, Optional, Any, Iterable, Mapping, Tuple, Union
+from pydantic import BaseModel, Field, PrivateAttr, model_validator
 import os
 import logging
 import re
 import numpy as np
 from pathlib import Path
 from abc import abstractmethod
-from utils.loading import load_absorbance_data, load_ms_data, extract_tic_data
+from utils.loading import (
+    load_absorbance_data,
+    load_ms_data,
+    load_spectra_data,
+    extract_chromatogram_data,
+    iter_detector_chromatograms,
+)
 from calculation.preprocessing import baseline_correction
+from utils.theoretical_spectrum import charge_state_adducts, expand_adducts, monoisotopic_mass
+from utils.errors import FileParseError, IonListError
+from utils.tolerance import parse_tolerance, tolerance_window
 
 logger = logging.getLogger(__name__)
 logger.propagate = False
@@ -16,7 +25,9 @@ logger.propagate = False
 class Measurement:
     """
     Abstract class representing a single measurement. Constructor takes the path to the data file as an argument.
-    Upon construction, if the filename contains "STMIX", the calibration flag is set to True.
+    Upon construction, if the filename contains "STMIX", the calibration flag is set to True,
+    if it contains "blank" (any case), the blank flag is, and if it contains "QC" (any case), the
+    pooled_qc flag is. All can be changed afterwards.
     Parameters
     ----------
     path : str
@@ -41,6 +52,8 @@ class Measurement:
             self.calibration = True
         else:
             self.calibration = False
+        self.blank = "BLANK" in self.filename.upper()
+        self.pooled_qc = "QC" in self.filename.upper()
 
     @abstractmethod
     def load_data(self):
@@ -67,11 +80,18 @@ class Measurement:
 class LCMeasurement(Measurement):
     """
     Subclass of the abstract Measurement class. Represents a single .mzML LC file.
+
+    Raises FileParseError if the file cannot be read.
     """
 
     def __init__(self, path):
         super().__init__(path)
-        self.data = load_absorbance_data(path)
+        try:
+            self.data = load_absorbance_data(path)
+        except Exception as e:
+            raise FileParseError(path, f"cannot read LC data ({e})") from e
+        if self.data.empty:
+            raise FileParseError(path, "no numeric time/absorbance rows found")
         self.annotations = None
         self.baseline_corrected = baseline_correction(self.data)
         self.file_type = "LC"
@@ -216,19 +236,65 @@ class MSMeasurement(Measurement):
         A tuple containing the m/z and intensity values of the XICs.
     ms2_data : set
         A set containing the m/z and intensity values of the MS2 spectra.
+    tic_times : np.ndarray
+        Scan times (min) shared by tic_values and bpc_values.
+    tic_values : np.ndarray
+        Total ion current per scan.
+    bpc_values : np.ndarray
+        Base peak (most intense peak) intensity per scan.
+    spectra_data : dict
+        ``{scan_index: (scan_time, mz_array, intensity_array)}`` when loaded
+        with ``load_spectra=True``, otherwise empty; see
+        utils.loading.load_spectra_data.
+    detector_channels : list of dict
+        UV/DAD and other non-MS chromatograms stored in the file, read on
+        first access; see utils.loading.iter_detector_chromatograms.
+    manifest : dict or None
+        Software version and hashes of the file, ion list and options of the
+        last processing run, see utils.manifest.
+
+    Raises
+    ------
+    FileParseError
+        If the file cannot be read.
     """
 
-    def __init__(self, path, mass_accuracy=0.001):
+    def __init__(self, path, mass_accuracy=0.001, load_spectra=False, max_points=None):
         super().__init__(path)
         self.mass_accuracy = mass_accuracy
         self.xics = []
+        self.manifest = None
+        self._detector_channels = None
         self.file_type = "MS"
 
-        # Extract TIC data first (runs in worker process during loading)
-        self.tic_times, self.tic_values = extract_tic_data(path)
+        try:
+            # Opt-in: keep all (optionally downsampled) scans in memory so they can
+            # be viewed without going back to the file
+            self.spectra_data = (
+                load_spectra_data(path, max_points=max_points) if load_spectra else {}
+            )
+
+            # Extract TIC and BPC first (runs in worker process during loading)
+            self.tic_times, self.tic_values, self.bpc_values = extract_chromatogram_data(path)
 
-        # Keep lazy reader for indexed scan access (used by show_scan_at_time_x)
-        self.data = load_ms_data(path)
+            # Keep lazy reader for indexed scan access (used by show_scan_at_time_x)
+            self.data = load_ms_data(path)
+        except Exception as e:
+            raise FileParseError(path, f"cannot read MS data ({e})") from e
+
+    @property
+    def detector_channels(self) -> list:
+        if getattr(self, "_detector_channels", None) is None:
+            try:
+                self._detector_channels = list(iter_detector_chromatograms(self.path))
+            except Exception as e:
+                logger.warning(f"Cannot read detector chromatograms of {self.filename}: {e}")
+                self._detector_channels = []
+            if self._detector_channels:
+                logger.info(
+                    f"Found {len(self._detector_channels)} detector channels in {self.filename}"
+                )
+        return self._detector_channels
 
     def get_compound_by_name(self, name: str):
         """
@@ -261,6 +327,46 @@ class Compound(BaseModel):
     ion_info: List[str] = Field(
         default_factory=list, description="Optional list of additional info strings"
     )
+    rt_min: Optional[float] = Field(
+        default=None, description="Start of the expected elution window (min)"
+    )
+    rt_max: Optional[float] = Field(
+        default=None, description="End of the expected elution window (min)"
+    )
+    expected_rt: Optional[float] = Field(
+        default=None, description="Expected retention time for identification (min)"
+    )
+    retention_index: Optional[float] = Field(
+        default=None, description="Expected retention index, see calculation.retention"
+    )
+    smoothing: Optional[Dict[str, Any]] = Field(
+        default=None,
+        description="XIC smoothing settings overriding the global ones, see calculation.smoothing",
+    )
+    formula: Optional[str] = Field(
+        default=None,
+        description="Molecular formula, used to predict the isotope pattern",
+    )
+    polarity: Optional[Literal["positive", "negative"]] = Field(
+        default=None,
+        description="Scan polarity to extract from, overriding the global one",
+    )
+    mobility_range: Optional[Tuple[float, float]] = Field(
+        default=None,
+        description="Ion mobility window (1/K0 or FAIMS CV) the ions are extracted from",
+    )
+    transitions: Optional[List[Tuple[float, float]]] = Field(
+        default=None,
+        description="SRM/MRM (Q1, Q3) m/z pairs, one per entry of target_list",
+    )
+    internal_standard: Optional[str] = Field(
+        default=None,
+        description="Name of the compound whose signal this one is normalized to",
+    )
+    mass_tolerance: Optional[List[Optional[Tuple[float, Literal["ppm", "mDa"]]]]] = Field(
+        default=None,
+        description="Per-ion (value, unit) extraction tolerance, None for the global one",
+    )
 
     # Internal state attributes (Excluded from __init__ arguments and validation)
     _file: Optional[Any] = PrivateAttr(default=None)
@@ -271,13 +377,61 @@ class Compound(BaseModel):
     _custom_mz_ranges: Dict = PrivateAttr(default_factory=dict)
     # Structure: {mz_float: (lower_bound, upper_bound)}
 
+    @model_validator(mode="after")
+    def _check_rt_window(self):
+        if self.rt_min is not None and self.rt_max is not None and self.rt_min > self.rt_max:
+            raise ValueError(
+                f"rt_min ({self.rt_min}) is greater than rt_max ({self.rt_max})"
+            )
+        return self
+
+    @model_validator(mode="after")
+    def _check_mobility_range(self):
+        if self.mobility_range is not None and self.mobility_range[0] > self.mobility_range[1]:
+            raise ValueError(f"Mobility range is reversed: {self.mobility_range}")
+        return self
+
+    @model_validator(mode="after")
+    def _check_mass_tolerance(self):
+        if self.mass_tolerance is not None and len(self.mass_tolerance) != len(self.target_list):
+            raise ValueError(
+                f"{len(self.mass_tolerance)} mass tolerances given for {len(self.target_list)} ions"
+            )
+        return self
+
+    @model_validator(mode="after")
+    def _check_transitions(self):
+        if self.transitions is not None and len(self.transitions) != len(self.target_list):
+            raise ValueError(
+                f"{len(self.transitions)} transitions given for {len(self.target_list)} ions"
+            )
+        return self
+
     def model_post_init(self, __context):
         """
         Post-initialization hook (Pydantic V2).
         Constructs the internal dictionary structure from the input list.
         """
         self._ions = {
-            ion: {"RT": None, "MS Intensity": None, "LC Intensity": None}
+            ion: {
+                "RT": None,
+                "MS Intensity": None,
+                "MS Intensity Smoothed": None,
+                "MS Baseline": None,
+                "LC Intensity": None,
+                "Peaks": [],
+                "MS2": [],
+                "Isotopes": None,
+                "Isotope Score": None,
+                "Aligned RT": None,
+                "Blank": None,
+                "Peak Fit": None,
+                "Purity": None,
+                "Mass Error": None,
+                "Charge State": None,
+                "RT Score": None,
+                "EI Hits": None,
+            }
             for ion in self.target_list
         }
 
@@ -318,6 +472,14 @@ class Compound(BaseModel):
     def concentration(self, value):
         self._concentration = value
 
+    @property
+    def below_loq(self) -> Optional[bool]:
+        """Whether the concentration lies below the calibration LOQ (None if unknown)."""
+        loq = self._calibration_parameters.get("loq")
+        if self._concentration is None or loq is None:
+            return None
+        return self._concentration < loq
+
     @property
     def custom_mz_ranges(self):
         return self._custom_mz_ranges
@@ -326,6 +488,31 @@ class Compound(BaseModel):
     def custom_mz_ranges(self, value):
         self._custom_mz_ranges = value
 
+    def tolerance_ranges(self) -> Dict:
+        """``{mz: (lower, upper)}`` extraction windows of the ions with their own tolerance."""
+        if self.mass_tolerance is None:
+            return {}
+        return {
+            mz: tolerance_window(mz, tolerance)
+            for mz, tolerance in zip(self.target_list, self.mass_tolerance)
+            if tolerance is not None
+        }
+
+    @property
+    def has_rt_window(self) -> bool:
+        """True if either end of the elution window is set."""
+        return self.rt_min is not None or self.rt_max is not None
+
+    def rt_window_mask(self, scan_times: np.ndarray) -> np.ndarray:
+        """Boolean mask of the scan times that fall inside the elution window."""
+        scan_times = np.asarray(scan_times)
+        mask = np.ones(len(scan_times), dtype=bool)
+        if self.rt_min is not None:
+            mask &= scan_times >= self.rt_min
+        if self.rt_max is not None:
+            mask &= scan_times <= self.rt_max
+        return mask
+
     def get_ion_label(self, index: int) -> str:
         """
         Get a label for the ion at the given index.
@@ -349,3 +536,200 @@ class Compound(BaseModel):
 
     def __str__(self):
         return f"Compound: {self.name}, ions: {self.target_list}, ion info: {self.ion_info}"
+
+
+def _as_list(value, cast) -> list:
+    """Normalize a list or legacy comma-separated string to a list of *cast*."""
+    if value is None:
+        return []
+    if isinstance(value, str):
+        value = [x for x in value.split(",") if x.strip()]
+    return [cast(x.strip() if isinstance(x, str) else x) for x in value]
+
+
+def _expand_adduct_ions(name, neutral_mass, adducts, ions, info):
+    """Append one ion per adduct of *neutral_mass*, skipping m/z already listed."""
+    try:
+        adduct_mzs = expand_adducts(neutral_mass, _as_list(adducts, str) or None)
+    except ValueError as e:
+        raise IonListError(f"Invalid adduct for compound '{name}': {e}") from None
+    ions = list(ions)
+    info = list(info) + [""] * (len(ions) - len(info))
+    for label, mz in adduct_mzs.items():
+        if any(abs(mz - existing) < 1e-4 for existing in ions):
+            continue
+        ions.append(mz)
+        info.append(label)
+    return ions, info
+
+
+def _charge_range(name, value) -> Tuple[int, int]:
+    """``(low, high)`` of a ``charges`` entry: ``2``, ``"1-3"`` or ``[1, 3]``."""
+    try:
+        if isinstance(value, str):
+            low, sep, high = value.strip().partition("-")
+            bounds = (int(low), int(high) if sep else int(low))
+        elif isinstance(value, (list, tuple)):
+            if len(value) != 2:
+                raise ValueError(f"expected [low, high], got {value}")
+            bounds = (int(value[0]), int(value[1]))
+        else:
+            bounds = (int(value), int(value))
+    except (TypeError, ValueError) as e:
+        raise IonListError(f"Invalid charge range for compound '{name}': {e}") from None
+    return bounds
+
+
+def compounds_from_ion_list(
+    ion_list: Union[Mapping[str, Mapping], Iterable[Union[Mapping, Compound]]],
+) -> List[Compound]:
+    """
+    Build Compound objects from an in-memory ion list.
+
+    Accepts the same structure as a single ion list in config.json, so callers
+    don't need to serialize it to disk first.
+
+    Parameters
+    ----------
+    ion_list : dict or list
+        Either a mapping ``{name: {"ions": [...], "info": [...]}}`` (the
+        config.json layout; metadata keys starting with ``_`` are skipped), or
+        a list of ``{"name": ..., "ions": [...], "info": [...]}`` dicts and/or
+        ready-made Compound objects. ``ions`` and ``info`` may also be
+        comma-separated strings. Optional ``rt_min``/``rt_max`` keys restrict
+        extraction to the expected elution window (minutes), and an optional
+        ``smoothing`` dict and ``polarity`` ("positive"/"negative") override
+        the global XIC settings, ``mobility_range`` (``[low, high]``) keeps
+        only peaks in that ion mobility window, and ``formula`` is kept for
+        isotope pattern scoring. An expected retention time ``rt`` (minutes)
+        or retention index ``ri`` is scored against the observed one by
+        calculation.retention.
+        A neutral monoisotopic ``mass`` is expanded into one ion per adduct
+        in ``adducts`` (labels from ADDUCT_DEFINITIONS; defaults to the ion
+        list's ``_adducts``, then to DEFAULT_ADDUCTS), labelled with the
+        adduct in ``info``. Entries with a ``formula`` but neither ``ions``
+        nor ``mass`` are expanded the same way from the formula's exact mass.
+        A ``charges`` range (``2``, ``"1-3"`` or ``[1, 3]``) adds the
+        [M+zH]z+ ions of every charge in it to those of ``adducts`` (only
+        these when ``adducts`` is not given), [M-zH]z- for a "negative"
+        ``polarity``; it needs a ``mass`` or ``formula``.
+        SRM/MRM ``transitions`` (``[[q1, q3], ...]``) are read from the
+        file's SRM chromatograms; without ``ions`` their Q3 m/z become the
+        ions, labelled ``"q1>q3"``. ``internal_standard`` names another
+        entry of the list that the compound's signal is divided by before
+        calibration. ``tolerance`` (e.g. ``"5 ppm"`` or ``"2 mDa"``, see
+        utils.tolerance) overrides the global mass accuracy for all of the
+        entry's ions, ``tolerances`` lists one per ion (None keeps the
+        global one).
+
+    Returns
+    -------
+    list of Compound
+        One Compound per entry, in input order.
+
+    Raises
+    ------
+    IonListError
+        If an entry has no name, its ion m/z values are not numeric, its
+        formula cannot be parsed, its charge range is invalid or lacks a
+        mass, its retention time window is invalid, or
+        its internal standard is not in the list (or is itself), or its
+        mass tolerance is malformed or does not match its ions, or the
+        Compound fails validation.
+    """
+    default_adducts = None
+    if isinstance(ion_list, Mapping):
+        default_adducts = ion_list.get("_adducts")
+        entries = [
+            {"name": name, **details}
+            for name, details in ion_list.items()
+            if not str(name).startswith("_")
+        ]
+    else:
+        entries = list(ion_list)
+
+    compounds = []
+    for entry in entries:
+        if isinstance(entry, Compound):
+            compounds.append(entry)
+            continue
+        name = str(entry.get("name", "")).strip()
+        if not name:
+            raise IonListError(f"Ion list entry without a name: {entry}")
+        try:
+            ions = _as_list(entry.get("ions"), float)
+        except (TypeError, ValueError) as e:
+            raise IonListError(f"Invalid m/z value for compound '{name}': {e}") from None
+        info = _as_list(entry.get("info"), str)
+        transitions = entry.get("transitions")
+        if transitions is not None:
+            try:
+                transitions = [(float(q1), float(q3)) for q1, q3 in transitions]
+            except (TypeError, ValueError) as e:
+                raise IonListError(f"Invalid transition for compound '{name}': {e}") from None
+            if not ions:
+                ions = [q3 for _, q3 in transitions]
+                info = [f"{q1:g}>{q3:g}" for q1, q3 in transitions]
+        mass = entry.get("mass")
+        charges = entry.get("charges")
+        if mass is None and (not ions or charges is not None) and entry.get("formula"):
+            try:
+                mass = monoisotopic_mass(entry["formula"])
+            except ValueError as e:
+                raise IonListError(f"Compound '{name}': {e}") from None
+        adducts = entry.get("adducts", default_adducts)
+        if charges is not None:
+            if mass is None:
+                raise IonListError(f"Compound '{name}' has a charge range but no mass or formula")
+            try:
+                charge_adducts = charge_state_adducts(
+                    *_charge_range(name, charges), entry.get("polarity") or "positive"
+                )
+            except ValueError as e:
+                raise IonListError(f"Compound '{name}': {e}") from None
+            explicit = _as_list(entry.get("adducts"), str)
+            adducts = explicit + [label for label in charge_adducts if label not in explicit]
+        if mass is not None:
+            ions, info = _expand_adduct_ions(name, float(mass), adducts, ions, info)
+        try:
+            if entry.get("tolerances") is not None:
+                mass_tolerance = [parse_tolerance(value) for value in entry["tolerances"]]
+            elif entry.get("tolerance") is not None:
+                mass_tolerance = [parse_tolerance(entry["tolerance"])] * len(ions)
+            else:
+                mass_tolerance = None
+        except ValueError as e:
+            raise IonListError(f"Compound '{name}': {e}") from None
+        try:
+            compound = Compound(
+                name=name,
+                target_list=ions,
+                ion_info=info,
+                rt_min=entry.get("rt_min"),
+                rt_max=entry.get("rt_max"),
+                expected_rt=entry.get("rt"),
+                retention_index=entry.get("ri"),
+                smoothing=entry.get("smoothing"),
+                formula=entry.get("formula"),
+                polarity=entry.get("polarity"),
+                transitions=transitions,
+                mobility_range=entry.get("mobility_range"),
+                internal_standard=entry.get("internal_standard") or None,
+                mass_tolerance=mass_tolerance,
+            )
+        except ValueError as e:
+            raise IonListError(f"Invalid entry for compound '{name}': {e}") from None
+        compounds.append(compound)
+
+    names = {compound.name for compound in compounds}
+    for compound in compounds:
+        if compound.internal_standard is None:
+            continue
+        if compound.internal_standard == compound.name:
+            raise IonListError(f"Compound '{compound.name}' is its own internal standard")
+        if compound.internal_standard not in names:
+            raise IonListError(
+                f"Internal standard '{compound.internal_standard}' of compound "
+                f"'{compound.name}' is not in the ion list"
+            )
+    return compounds
@@ -0,0 +1,279 @@
+"""
+Local compound database.
+
+A SQLite file of compounds (name, formula, monoisotopic mass, InChIKey and
+the source they came from, e.g. subsets of HMDB or PubChem) answers
+accurate mass lookups offline, to annotate untargeted features and to build
+ion lists:
+
+    with CompoundDatabase("compounds.sqlite") as database:
+        database.import_table("hmdb_subset.csv", source="HMDB")
+        hits = database.search_mz(195.0877, adducts=["[M+H]+"], ppm=5.0)
+        ion_list = database.ion_list(["Caffeine"], adducts=["[M+H]+", "[M+Na]+"])
+
+Masses are indexed, so a lookup is a range query on the mass column
+whatever the size of the database. Imported tables are CSV or TSV files
+with a header row, see _IMPORT_COLUMNS for the accepted headers; masses
+missing from a table are computed from the formula.
+"""
+
+import csv
+import logging
+import sqlite3
+from pathlib import Path
+from typing import Iterable, List, Mapping, Sequence
+
+import pandas as pd
+
+from calculation.formulas import neutral_mass
+from utils.theoretical_spectrum import monoisotopic_mass
+
+logger = logging.getLogger(__name__)
+
+COMPOUND_FIELDS = ("name", "formula", "monoisotopic_mass", "inchikey", "source", "accession")
+ANNOTATION_COLUMNS = ("db_name", "db_formula", "db_inchikey", "db_adduct", "db_error_ppm")
+
+_SCHEMA = """
+CREATE TABLE IF NOT EXISTS compounds (
+    id INTEGER PRIMARY KEY,
+    name TEXT NOT NULL,
+    formula TEXT,
+    monoisotopic_mass REAL NOT NULL,
+    inchikey TEXT,
+    source TEXT,
+    accession TEXT
+);
+CREATE INDEX IF NOT EXISTS compounds_mass ON compounds (monoisotopic_mass);
+CREATE INDEX IF NOT EXISTS compounds_name ON compounds (name COLLATE NOCASE);
+CREATE INDEX IF NOT EXISTS compounds_inchikey ON compounds (inchikey);
+"""
+
+# Table columns imported, by the accepted headers (compared without case and "_")
+_IMPORT_COLUMNS = {
+    "name": ("name", "compound_name", "common_name", "iupac_name", "title"),
+    "formula": ("formula", "molecular_formula", "chemical_formula"),
+    "monoisotopic_mass": (
+        "monoisotopic_mass", "monisotopic_molecular_weight", "monoisotopic_molecular_weight",
+        "exact_mass", "mass",
+    ),
+    "inchikey": ("inchikey", "inchi_key"),
+    "accession": ("accession", "hmdb_id", "accession_id", "cid", "id"),
+}
+
+
+def _normalize(header: str) -> str:
+    return str(header).lower().replace("_", "").replace(" ", "")
+
+
+class CompoundDatabase:
+    """
+    Compounds in a SQLite file, looked up by mass, name, formula or InChIKey.
+
+    Parameters
+    ----------
+    path : str or Path
+        The database file, created if missing; ":memory:" for a temporary
+        database.
+    """
+
+    def __init__(self, path=":memory:"):
+        self.path = str(path)
+        self._connection = sqlite3.connect(self.path)
+        self._connection.row_factory = sqlite3.Row
+        self._connection.executescript(_SCHEMA)
+
+    def __enter__(self):
+        return self
+
+    def __exit__(self, *exc):
+        self.close()
+
+    def __len__(self):
+        return self._connection.execute("SELECT COUNT(*) FROM compounds").fetchone()[0]
+
+    def close(self):
+        self._connection.close()
+
+    def add(self, records: Iterable[Mapping]) -> int:
+        """
+        Insert compounds, dicts with the COMPOUND_FIELDS (``name`` and a
+        ``monoisotopic_mass`` or ``formula`` needed); returns the number added.
+
+        Raises
+        ------
+        ValueError
+            If a record has no name, or neither a mass nor a valid formula.
+        """
+        rows = []
+        for record in records:
+            name = str(record.get("name") or "").strip()
+            if not name:
+                raise ValueError(f"Compound without a name: {dict(record)}")
+            formula = record.get("formula") or None
+            mass = record.get("monoisotopic_mass")
+            if mass is None or mass == "":
+                if formula is None:
+                    raise ValueError(f"Compound '{name}' has neither a mass nor a formula")
+                mass = monoisotopic_mass(formula)
+            rows.append(
+                (
+                    name, formula, float(mass), record.get("inchikey") or None,
+                    record.get("source") or None,
+                    None if record.get("accession") is None else str(record["accession"]),
+                )
+            )
+        with self._connection:
+            self._connection.executemany(
+                "INSERT INTO compounds (name, formula, monoisotopic_mass, inchikey, source, "
+                "accession) VALUES (?, ?, ?, ?, ?, ?)",
+                rows,
+            )
+        return len(rows)
+
+    def import_table(self, path, source: str = None) -> int:
+        """
+        Add the compounds of a CSV or TSV table, e.g. a subset of HMDB or
+        PubChem; *source* is recorded with every compound. Returns the
+        number added.
+
+        Raises
+        ------
+        ValueError
+            If the table has no name column, or see add.
+        """
+        with open(path, "r", newline="", encoding="utf-8") as f:
+            sample = f.read(4096)
+            f.seek(0)
+            delimiter = "\t" if sample.count("\t") > sample.count(",") else ","
+            reader = csv.DictReader(f, delimiter=delimiter)
+            present = {_normalize(name): name for name in reader.fieldnames or [] if name}
+            columns = {}
+            for key, headers in _IMPORT_COLUMNS.items():
+                found = [present[_normalize(h)] for h in headers if _normalize(h) in present]
+                if found:
+                    columns[key] = found[0]
+            if "name" not in columns:
+                raise ValueError(f"Compound table '{path}' has no name column")
+            records = (
+                {key: (row.get(header) or "").strip() or None for key, header in columns.items()}
+                for row in reader
+            )
+            added = self.add({**record, "source": source} for record in records)
+        logger.info(f"Imported {added} compounds from {Path(path).name} into {self.path}")
+        return added
+
+    def _rows(self, query: str, parameters: Sequence) -> List[dict]:
+        return [dict(row) for row in self._connection.execute(query, parameters)]
+
+    def search_mass(self, mass: float, ppm: float = 5.0, limit: int = None) -> List[dict]:
+        """
+        Compounds whose monoisotopic mass lies within *ppm* of *mass*, closest
+        first, with the COMPOUND_FIELDS, ``id`` and ``error_ppm`` (of *mass*
+        relative to the compound's).
+        """
+        if ppm < 0:
+            raise ValueError(f"Mass tolerance must not be negative, got {ppm} ppm")
+        tolerance = mass * ppm * 1e-6
+        query = (
+            "SELECT * FROM compounds WHERE monoisotopic_mass BETWEEN ? AND ? "
+            "ORDER BY ABS(monoisotopic_mass - ?)"
+        )
+        parameters = [mass - tolerance, mass + tolerance, mass]
+        if limit is not None:
+            query += " LIMIT ?"
+            parameters.append(int(limit))
+        hits = self._rows(query, parameters)
+        for hit in hits:
+            hit["error_ppm"] = (mass - hit["monoisotopic_mass"]) / hit["monoisotopic_mass"] * 1e6
+        return hits
+
+    def search_mz(
+        self, mz: float, adducts: Sequence[str] = ("[M+H]+",), ppm: float = 5.0, limit: int = None
+    ) -> List[dict]:
+        """
+        search_mass of the neutral mass of an ion under each of *adducts*
+        (labels from ADDUCT_DEFINITIONS), the hits labelled with their
+        ``adduct``, closest first.
+        """
+        hits = []
+        for adduct in adducts:
+            for hit in self.search_mass(neutral_mass(mz, adduct), ppm):
+                hits.append({**hit, "adduct": adduct})
+        hits.sort(key=lambda hit: abs(hit["error_ppm"]))
+        return hits if limit is None else hits[:limit]
+
+    def by_name(self, name: str) -> List[dict]:
+        """Compounds named *name*, ignoring case."""
+        return self._rows("SELECT * FROM compounds WHERE name = ? COLLATE NOCASE", [name])
+
+    def by_formula(self, formula: str) -> List[dict]:
+        """Compounds with the molecular formula *formula*, as stored."""
+        return self._rows("SELECT * FROM compounds WHERE formula = ?", [formula])
+
+    def by_inchikey(self, inchikey: str) -> List[dict]:
+        """Compounds with the InChIKey *inchikey*; a 14-character first block matches
+        every stereoisomer."""
+        key = inchikey.strip().upper()
+        if len(key) == 14:
+            return self._rows("SELECT * FROM compounds WHERE inchikey LIKE ?", [f"{key}-%"])
+        return self._rows("SELECT * FROM compounds WHERE inchikey = ?", [key])
+
+    def ion_list(self, names: Iterable[str], adducts: Sequence[str] = None) -> dict:
+        """
+        Ion list entries (``mass``, ``formula`` and ``adducts``) of the
+        compounds named *names*, ready for utils.classes.compounds_from_ion_list.
+
+        Raises
+        ------
+        KeyError
+            If a name is not in the database.
+        """
+        ion_list = {}
+        for name in names:
+            found = self.by_name(name)
+            if not found:
+                raise KeyError(f"Compound '{name}' is not in {self.path}")
+            entry = {"mass": found[0]["monoisotopic_mass"], "formula": found[0]["formula"]}
+            if adducts is not None:
+                entry["adducts"] = list(adducts)
+            ion_list[found[0]["name"]] = {k: v for k, v in entry.items() if v is not None}
+        return ion_list
+
+
+def annotate_features(
+    features: pd.DataFrame,
+    database: CompoundDatabase,
+    adducts: Sequence[str] = ("[M+H]+",),
+    ppm: float = 5.0,
+) -> pd.DataFrame:
+    """
+    Copy of a feature table with the closest database compound of every
+    feature in the ANNOTATION_COLUMNS, None where nothing is within *ppm*.
+
+    Features grouped by calculation.grouping.group_features are looked up by
+    their ``neutral_mass`` and ``adduct``; the others by their ``mz`` under
+    each of *adducts*.
+    """
+    table = features.copy()
+    annotations = []
+    for _, feature in table.iterrows():
+        grouped_mass = feature.get("neutral_mass")
+        if grouped_mass is not None and not pd.isna(grouped_mass):
+            hits = [
+                {**hit, "adduct": feature.get("adduct")}
+                for hit in database.search_mass(float(grouped_mass), ppm, limit=1)
+            ]
+        else:
+            hits = database.search_mz(float(feature["mz"]), adducts, ppm, limit=1)
+        hit = hits[0] if hits else {}
+        annotations.append(
+            (
+                hit.get("name"), hit.get("formula"), hit.get("inchikey"), hit.get("adduct"),
+                hit.get("error_ppm"),
+            )
+        )
+    for k, column in enumerate(ANNOTATION_COLUMNS):
+        table[column] = [annotation[k] for annotation in annotations]
+    found = sum(annotation[0] is not None for annotation in annotations)
+    logger.info(f"Annotated {found} of {len(table)} features from {database.path}")
+    return table
@@ -0,0 +1,50 @@
+"""
+Exceptions reported to the user instead of being logged and swallowed.
+
+Every error the backend expects to happen on bad input derives from
+LCMSpectorError, so callers of the Python API can catch them together and
+the workers can report them per file. They pickle across the process pools.
+
+- FileParseError: a data file cannot be read
+- IonListError: an ion list is malformed (also a ValueError, which is what
+  ion list problems raised before)
+- ProcessingError: extraction of a file failed
+"""
+
+from pathlib import Path
+
+
+class LCMSpectorError(Exception):
+    """Base exception for errors in user-supplied files and settings."""
+
+    pass
+
+
+class _FileError(LCMSpectorError):
+    """An error tied to one input file, shown with its file name."""
+
+    def __init__(self, path: str, message: str):
+        super().__init__(path, message)
+        self.path = str(path)
+        self.message = message
+
+    def __str__(self):
+        return f"{Path(self.path).name}: {self.message}"
+
+
+class FileParseError(_FileError):
+    """Raised when an MS or LC data file cannot be read."""
+
+    pass
+
+
+class ProcessingError(_FileError):
+    """Raised when extracting the compounds of a file fails."""
+
+    pass
+
+
+class IonListError(LCMSpectorError, ValueError):
+    """Raised when an ion list entry is malformed or inconsistent."""
+
+    pass
@@ -0,0 +1,520 @@
+"""
+Export of processed results as tidy tables.
+
+The per-ion result dicts of every Compound are flattened into one row per
+file, compound and ion, and the XICs into one row per scan, so that the
+results can be read back with pandas, R or any Arrow-capable tool without
+knowing the Compound layout:
+
+    export_results(model.ms_measurements, "batch.parquet", include_traces=True)
+    # -> batch.parquet and batch_traces.parquet
+
+export_long_table() streams the results to CSV or TSV in long format, one
+row per file, compound, ion and detected peak, with numbers written the same
+whatever the locale and an explicit decimal separator and encoding:
+
+    export_long_table(model.ms_measurements, "peaks.csv", decimal=",", encoding="utf-8-sig")
+
+consensus_table() instead lays the whole batch out as one compound x file
+matrix of peak areas, after RT alignment and gap filling, ready for
+statistics:
+
+    export_consensus_table(model.ms_measurements, "areas.csv", format="csv")
+
+Parquet and Arrow (Feather v2) files are written through pandas and need the
+optional ``pyarrow`` package. Every file carries the manifest of the
+measurements (utils.manifest.batch_manifest) as JSON, in the schema metadata
+under MANIFEST_KEY for Parquet and Arrow and in a ``.manifest.json``
+sidecar for CSV; read_export_manifest reads it back.
+"""
+
+import codecs
+import copy
+import csv
+import json
+import logging
+import math
+from pathlib import Path
+from typing import Iterable, Mapping, Optional, Union
+
+import numpy as np
+import pandas as pd
+
+from utils.manifest import batch_manifest, canonical_json, read_manifest, write_manifest
+
+logger = logging.getLogger(__name__)
+
+EXPORT_FORMATS = ("parquet", "arrow")
+CONSENSUS_FORMATS = ("csv",) + EXPORT_FORMATS
+# Schema metadata key of the manifest in Parquet and Arrow files
+MANIFEST_KEY = b"lcmspector.manifest"
+
+RESULT_COLUMNS = (
+    "file", "compound", "ion_mz", "ion_name", "rt", "aligned_rt", "intensity_sum",
+    "intensity_max", "peak_area", "peak_area_baseline_corrected", "peak_start",
+    "peak_end", "peak_height", "snr", "quality_score", "n_peaks", "concentration",
+    "below_loq", "blank_area", "blank_ratio", "below_blank_threshold", "gap_filled",
+    "purity", "apex_mz", "mass_error_mean_ppm", "mass_error_median_ppm", "mass_error_sd_ppm",
+    "charge", "charge_mismatch", "rt_score",
+)
+TRACE_COLUMNS = ("file", "compound", "ion_mz", "rt", "intensity", "intensity_smoothed", "baseline")
+PEAK_COLUMNS = (
+    "file", "compound", "ion_mz", "ion_name", "peak", "apex_rt", "peak_start", "peak_end",
+    "peak_height", "peak_area", "peak_area_baseline_corrected", "fwhm", "asymmetry", "snr",
+    "integrated",
+)
+# Long table formats -> field delimiter
+LONG_FORMATS = {"csv": ",", "tsv": "\t"}
+DECIMAL_SEPARATORS = (".", ",")
+
+
+def _measurements(measurements) -> list:
+    if isinstance(measurements, Mapping):
+        return list(measurements.values())
+    return list(measurements)
+
+
+def _ion_rows(measurement, compound):
+    info = list(getattr(compound, "ion_info", []) or [])
+    for index, (ion, data) in enumerate(compound.ions.items()):
+        trace = data.get("MS Intensity")
+        has_trace = trace is not None and trace.shape[1] > 0
+        integration = data.get("Integration Data") or {}
+        blank = data.get("Blank") or {}
+        mass_error = data.get("Mass Error") or {}
+        charge_state = data.get("Charge State") or {}
+        yield {
+            "file": measurement.filename,
+            "compound": compound.name,
+            "ion_mz": float(ion),
+            "ion_name": info[index] if index < len(info) and info[index] else None,
+            "rt": data.get("RT"),
+            "aligned_rt": data.get("Aligned RT"),
+            "intensity_sum": float(np.sum(trace[1])) if has_trace else None,
+            "intensity_max": float(np.max(trace[1])) if has_trace else None,
+            "peak_area": integration.get("total_area"),
+            "peak_area_baseline_corrected": integration.get("baseline_corrected_area"),
+            "peak_start": integration.get("start_time"),
+            "peak_end": integration.get("end_time"),
+            "peak_height": integration.get("peak_height"),
+            "snr": integration.get("snr"),
+            "quality_score": integration.get("quality_score"),
+            "n_peaks": len(data.get("Peaks") or []),
+            "concentration": compound.concentration,
+            "below_loq": compound.below_loq,
+            "blank_area": blank.get("area"),
+            "blank_ratio": blank.get("ratio"),
+            "below_blank_threshold": blank.get("below_threshold"),
+            "gap_filled": bool(integration.get("gap_filled", False)),
+            "purity": (data.get("Purity") or {}).get("purity"),
+            "apex_mz": mass_error.get("apex_mz"),
+            "mass_error_mean_ppm": mass_error.get("mean_ppm"),
+            "mass_error_median_ppm": mass_error.get("median_ppm"),
+            "mass_error_sd_ppm": mass_error.get("sd_ppm"),
+            "charge": charge_state.get("charge"),
+            "charge_mismatch": charge_state.get("mismatch"),
+            "rt_score": (data.get("RT Score") or {}).get("score"),
+        }
+
+
+def _peak_rows(measurement, compound):
+    """One row per detected peak of every ion, a row without a peak for ions without."""
+    info = list(getattr(compound, "ion_info", []) or [])
+    for index, (ion, data) in enumerate(compound.ions.items()):
+        base = {
+            "file": measurement.filename,
+            "compound": compound.name,
+            "ion_mz": float(ion),
+            "ion_name": info[index] if index < len(info) and info[index] else None,
+        }
+        peaks = data.get("Peaks") or []
+        if not peaks:
+            yield {**base, "integrated": False}
+            continue
+        integrated_rt = data.get("RT") if data.get("Integration Data") else None
+        for number, peak in enumerate(peaks, start=1):
+            yield {
+                **base,
+                "peak": number,
+                "apex_rt": peak.get("apex_rt"),
+                "peak_start": peak.get("start_time"),
+                "peak_end": peak.get("end_time"),
+                "peak_height": peak.get("height"),
+                "peak_area": peak.get("area"),
+                "peak_area_baseline_corrected": peak.get("baseline_corrected_area"),
+                "fwhm": peak.get("fwhm"),
+                "asymmetry": peak.get("asymmetry"),
+                "snr": peak.get("snr"),
+                "integrated": integrated_rt is not None
+                and peak.get("apex_rt") is not None
+                and abs(peak["apex_rt"] - integrated_rt) < 1e-9,
+            }
+
+
+def results_table(measurements: Union[Mapping, Iterable]) -> pd.DataFrame:
+    """
+    One row per file, compound and ion with the RESULT_COLUMNS.
+
+    Parameters
+    ----------
+    measurements : dict or iterable of MSMeasurement
+        Processed measurements (``xics`` filled), e.g. the model's
+        ``ms_measurements``.
+    """
+    rows = [
+        row
+        for measurement in _measurements(measurements)
+        for compound in measurement.xics
+        for row in _ion_rows(measurement, compound)
+    ]
+    return pd.DataFrame(rows, columns=list(RESULT_COLUMNS))
+
+
+def peaks_table(measurements: Union[Mapping, Iterable]) -> pd.DataFrame:
+    """
+    One row per file, compound, ion and detected peak with the PEAK_COLUMNS.
+
+    ``integrated`` marks the peak whose apex is the ion's integrated RT;
+    ions without peaks get a single row without peak values.
+    """
+    rows = [
+        row
+        for measurement in _measurements(measurements)
+        for compound in measurement.xics
+        for row in _peak_rows(measurement, compound)
+    ]
+    return pd.DataFrame(rows, columns=list(PEAK_COLUMNS))
+
+
+def traces_table(measurements: Union[Mapping, Iterable]) -> pd.DataFrame:
+    """One row per file, compound, ion and scan with the TRACE_COLUMNS (raw, smoothed and baseline XIC)."""
+    frames = []
+    for measurement in _measurements(measurements):
+        for compound in measurement.xics:
+            for ion, data in compound.ions.items():
+                trace = data.get("MS Intensity")
+                if trace is None or trace.shape[1] == 0:
+                    continue
+                n_scans = trace.shape[1]
+                smoothed = data.get("MS Intensity Smoothed")
+                baseline = data.get("MS Baseline")
+                frames.append(
+                    pd.DataFrame(
+                        {
+                            "file": measurement.filename,
+                            "compound": compound.name,
+                            "ion_mz": float(ion),
+                            "rt": trace[0],
+                            "intensity": trace[1],
+                            "intensity_smoothed": (
+                                smoothed[1] if smoothed is not None else np.full(n_scans, np.nan)
+                            ),
+                            "baseline": baseline if baseline is not None else np.full(n_scans, np.nan),
+                        }
+                    )
+                )
+    if not frames:
+        return pd.DataFrame(columns=list(TRACE_COLUMNS))
+    return pd.concat(frames, ignore_index=True)
+
+
+def _compound_area(compound) -> float:
+    """Summed baseline corrected area of the integrated ions, NaN if none is integrated."""
+    areas = [
+        (data.get("Integration Data") or {}).get("baseline_corrected_area")
+        for data in compound.ions.values()
+    ]
+    areas = [area for area in areas if area is not None]
+    return float(np.sum(areas)) if areas else np.nan
+
+
+def _compound_rt(compound) -> float:
+    """Median (aligned, if available) RT of the compound's ions, NaN if none has one."""
+    rts = [
+        data.get("Aligned RT") if data.get("Aligned RT") is not None else data.get("RT")
+        for data in compound.ions.values()
+    ]
+    rts = [rt for rt in rts if rt is not None]
+    return float(np.median(rts)) if rts else np.nan
+
+
+def consensus_table(
+    measurements: Union[Mapping, Iterable],
+    rt_alignment: str = "linear",
+    gap_filling: bool = True,
+) -> pd.DataFrame:
+    """
+    Compound x file matrix of the peak areas of a processed batch.
+
+    RT alignment (see calculation.alignment) and gap filling (see
+    calculation.gap_filling) are applied to copies of the compounds, the
+    measurements are left as they are.
+
+    Parameters
+    ----------
+    measurements : dict or iterable of MSMeasurement
+        Processed measurements, see results_table.
+    rt_alignment : str or None
+        One of calculation.alignment.ALIGNMENT_METHODS, None to skip it; the
+        ``rt`` column then holds the unaligned RTs.
+    gap_filling : bool
+        Integrate the peaks missed in some files first.
+
+    Returns
+    -------
+    pd.DataFrame
+        One row per compound: ``compound``, ``rt`` (median RT over the files,
+        min) and one column per file with the summed baseline corrected
+        area of the compound's ions, NaN where none was integrated.
+    """
+    from calculation.alignment import align_retention_times
+    from calculation.gap_filling import fill_gaps
+
+    measurements = _measurements(measurements)
+    results = [copy.deepcopy(tuple(measurement.xics)) for measurement in measurements]
+    shifts = None
+    if rt_alignment is not None and len(results) > 1:
+        shifts = align_retention_times(results, method=rt_alignment)
+    if gap_filling and len(results) > 1:
+        fill_gaps(results, rt_shifts=shifts)
+
+    names = list(dict.fromkeys(c.name for compounds in results for c in compounds))
+    rts = {name: [] for name in names}
+    columns = {}
+    for measurement, compounds in zip(measurements, results):
+        areas = {compound.name: _compound_area(compound) for compound in compounds}
+        columns[measurement.filename] = [areas.get(name, np.nan) for name in names]
+        for compound in compounds:
+            rts[compound.name].append(_compound_rt(compound))
+    consensus_rts = [
+        float(np.nanmedian(rts[name])) if np.isfinite(rts[name]).any() else np.nan
+        for name in names
+    ]
+    return pd.DataFrame({"compound": names, "rt": consensus_rts, **columns})
+
+
+def _write(table: pd.DataFrame, path: Path, format: str, manifest: Optional[Mapping] = None):
+    if format == "csv":
+        table.to_csv(path, index=False)
+        if manifest is not None:
+            write_manifest(path, manifest)
+        return
+    import pyarrow as pa
+
+    arrow_table = pa.Table.from_pandas(table, preserve_index=False)
+    if manifest is not None:
+        metadata = dict(arrow_table.schema.metadata or {})
+        metadata[MANIFEST_KEY] = canonical_json(manifest).encode("utf-8")
+        arrow_table = arrow_table.replace_schema_metadata(metadata)
+    if format == "parquet":
+        import pyarrow.parquet as pq
+
+        pq.write_table(arrow_table, path)
+    else:
+        import pyarrow.feather as feather
+
+        feather.write_feather(arrow_table, path)
+
+
+def read_export_manifest(path) -> Optional[dict]:
+    """The manifest of an exported file, None if it has none."""
+    path = Path(path)
+    suffix = path.suffix.lower()
+    if suffix == ".parquet":
+        import pyarrow.parquet as pq
+
+        schema = pq.read_schema(path)
+    elif suffix in (".arrow", ".feather"):
+        import pyarrow as pa
+        import pyarrow.ipc as ipc
+
+        with pa.OSFile(str(path), "rb") as source:
+            schema = ipc.open_file(source).schema
+    else:
+        return read_manifest(path)
+    value = (schema.metadata or {}).get(MANIFEST_KEY)
+    return None if value is None else json.loads(value)
+
+
+def _require_pyarrow(format: str):
+    try:
+        import pyarrow  # noqa: F401
+    except ImportError:
+        raise ImportError(f"Exporting to {format} needs the 'pyarrow' package (lcmspector[export])") from None
+
+
+def export_results(
+    measurements: Union[Mapping, Iterable],
+    path,
+    format: str = "parquet",
+    include_traces: bool = False,
+) -> list:
+    """
+    Write the results of processed measurements as Parquet or Arrow files.
+
+    Parameters
+    ----------
+    measurements : dict or iterable of MSMeasurement
+        Processed measurements, see results_table.
+    path : str or Path
+        Output file of the results table.
+    format : str
+        One of EXPORT_FORMATS; "arrow" writes an Arrow IPC (Feather v2) file.
+    include_traces : bool
+        Also write traces_table next to it, as ``<stem>_traces<suffix>``.
+
+    Returns
+    -------
+    list of Path
+        The files written.
+
+    Raises
+    ------
+    ValueError
+        On an unknown format.
+    ImportError
+        If pyarrow is not installed.
+    """
+    if format not in EXPORT_FORMATS:
+        raise ValueError(f"Unknown export format '{format}', expected one of {EXPORT_FORMATS}")
+    _require_pyarrow(format)
+
+    path = Path(path)
+    measurements = _measurements(measurements)
+    manifest = batch_manifest(measurements)
+    results = results_table(measurements)
+    _write(results, path, format, manifest)
+    written = [path]
+    if include_traces:
+        traces_path = path.with_name(f"{path.stem}_traces{path.suffix}")
+        _write(traces_table(measurements), traces_path, format, manifest)
+        written.append(traces_path)
+    logger.info(f"Exported {len(results)} result rows to {', '.join(str(p) for p in written)}")
+    return written
+
+
+def _text(value, decimal: str) -> str:
+    """A locale-independent cell: empty for missing values, shortest round-trip floats."""
+    if value is None:
+        return ""
+    if isinstance(value, (bool, np.bool_)):
+        return "true" if value else "false"
+    if isinstance(value, (float, np.floating)):
+        value = float(value)
+        if math.isnan(value):
+            return ""
+        text = repr(value)
+        return text.replace(".", decimal) if decimal != "." else text
+    if isinstance(value, np.integer):
+        return str(int(value))
+    return str(value)
+
+
+def export_long_table(
+    measurements: Union[Mapping, Iterable],
+    path,
+    format: str = None,
+    decimal: str = ".",
+    encoding: str = "utf-8",
+    peaks: bool = True,
+) -> Path:
+    """
+    Stream the results of processed measurements to a CSV or TSV file.
+
+    Rows are written as they are produced, without building a table first.
+    Numbers are written with Python's shortest round-trip representation,
+    never with the thousands separators or decimal commas of the locale.
+
+    Parameters
+    ----------
+    measurements : dict or iterable of MSMeasurement
+        Processed measurements, see results_table.
+    path : str or Path
+        Output file.
+    format : str, optional
+        One of LONG_FORMATS; by default "tsv" for ``.tsv``/``.tab`` files,
+        else "csv".
+    decimal : str
+        One of DECIMAL_SEPARATORS; "," needs the "tsv" format.
+    encoding : str
+        Text encoding, e.g. "utf-8-sig" for spreadsheet programs that need
+        a byte order mark.
+    peaks : bool
+        One row per detected peak with the PEAK_COLUMNS; False writes one
+        row per ion with the RESULT_COLUMNS instead.
+
+    Returns
+    -------
+    Path
+        The file written; the manifest of the measurements goes into a
+        ``.manifest.json`` sidecar.
+
+    Raises
+    ------
+    ValueError
+        On an unknown format, decimal separator or encoding, or a decimal
+        separator equal to the delimiter.
+    """
+    path = Path(path)
+    if format is None:
+        format = "tsv" if path.suffix.lower() in (".tsv", ".tab") else "csv"
+    if format not in LONG_FORMATS:
+        raise ValueError(f"Unknown export format '{format}', expected one of {tuple(LONG_FORMATS)}")
+    if decimal not in DECIMAL_SEPARATORS:
+        raise ValueError(
+            f"Unknown decimal separator '{decimal}', expected one of {DECIMAL_SEPARATORS}"
+        )
+    if decimal == LONG_FORMATS[format]:
+        raise ValueError(f"Decimal separator '{decimal}' is also the {format} delimiter")
+    try:
+        codecs.lookup(encoding)
+    except LookupError:
+        raise ValueError(f"Unknown encoding '{encoding}'") from None
+
+    measurements = _measurements(measurements)
+    columns = PEAK_COLUMNS if peaks else RESULT_COLUMNS
+    row_source = _peak_rows if peaks else _ion_rows
+    n_rows = 0
+    with open(path, "w", encoding=encoding, newline="") as f:
+        writer = csv.writer(f, delimiter=LONG_FORMATS[format], lineterminator="\n")
+        writer.writerow(columns)
+        for measurement in measurements:
+            for compound in measurement.xics:
+                for row in row_source(measurement, compound):
+                    writer.writerow([_text(row.get(column), decimal) for column in columns])
+                    n_rows += 1
+    write_manifest(path, batch_manifest(measurements))
+    logger.info(f"Exported {n_rows} rows to {path}")
+    return path
+
+
+def export_consensus_table(
+    measurements: Union[Mapping, Iterable],
+    path,
+    format: str = "csv",
+    rt_alignment: str = "linear",
+    gap_filling: bool = True,
+) -> Path:
+    """
+    Write the consensus_table of processed measurements.
+
+    *format* is one of CONSENSUS_FORMATS; "arrow" and "parquet" need
+    pyarrow. Returns the file written.
+
+    Raises
+    ------
+    ValueError
+        On an unknown format.
+    ImportError
+        If pyarrow is needed but not installed.
+    """
+    if format not in CONSENSUS_FORMATS:
+        raise ValueError(f"Unknown export format '{format}', expected one of {CONSENSUS_FORMATS}")
+    if format != "csv":
+        _require_pyarrow(format)
+    path = Path(path)
+    measurements = _measurements(measurements)
+    table = consensus_table(measurements, rt_alignment=rt_alignment, gap_filling=gap_filling)
+    _write(table, path, format, batch_manifest(measurements))
+    logger.info(f"Exported {len(table)} compounds x {len(table.columns) - 2} files to {path}")
+    return path
@@ -0,0 +1,263 @@
+"""
+HDF5 export and reload of full processing results.
+
+Every processed file is a group under ``/files``, with one group per
+compound holding its ion list fields, calibration and the per-ion result
+dicts. The ion dicts are stored as they are, so an exported batch reads back
+to the same Compound objects:
+
+    export_hdf5(model.ms_measurements, "batch.h5")
+    results = load_hdf5("batch.h5")
+    results["sample"]["compounds"]  # [Compound, ...] with XICs, peaks and MS2
+
+Arrays (traces, baselines, MS2 spectra) become gzip-compressed datasets,
+lists of flat dicts such as the detected peaks structured datasets with one
+field per key, and scalars attributes. Group and attribute names are the
+escaped dict keys; the ``@``-prefixed attributes are bookkeeping, among them
+the JSON ``@manifest`` of the batch (root) and of every file (see
+utils.manifest). Needs the optional ``h5py`` package.
+"""
+
+import json
+import logging
+from numbers import Number
+from pathlib import Path
+from typing import Dict, Iterable, Mapping, Union
+
+import numpy as np
+
+from utils.classes import Compound
+from utils.errors import FileParseError
+from utils.manifest import batch_manifest, canonical_json
+
+logger = logging.getLogger(__name__)
+
+HDF5_FORMAT = "lcmspector-results"
+HDF5_VERSION = 1
+
+# Arrays this small are stored uncompressed
+_MIN_COMPRESSED_SIZE = 64
+# Per-file summary arrays stored next to the compounds, if the measurement has them
+_MEASUREMENT_ARRAYS = ("tic_times", "tic_values", "bpc_values")
+
+
+def _h5py():
+    try:
+        import h5py
+    except ImportError:
+        raise ImportError("HDF5 export needs the 'h5py' package (lcmspector[export])") from None
+    return h5py
+
+
+def _escape(key) -> str:
+    """A valid, unique HDF5 name for a dict key."""
+    name = str(key).replace("%", "%25").replace("/", "%2F").replace("@", "%40")
+    if name in ("", "."):
+        return "%2E" if name else "%00"
+    return name
+
+
+def _is_scalar(value) -> bool:
+    return isinstance(value, (str, bool, Number, np.generic))
+
+
+def _records(values) -> np.ndarray:
+    """Structured array of a list of dicts with the same numeric keys, else None."""
+    if not values or not all(isinstance(value, Mapping) for value in values):
+        return None
+    keys = list(values[0])
+    if not keys or any(list(value) != keys for value in values):
+        return None
+    columns = []
+    for key in keys:
+        column = [value[key] for value in values]
+        if not all(isinstance(v, (Number, np.number)) for v in column):
+            return None
+        columns.append(np.asarray(column))
+    return np.rec.fromarrays(columns, names=[_escape(key) for key in keys])
+
+
+def _create_dataset(group, name: str, data: np.ndarray, kind: str):
+    compression = "gzip" if data.size >= _MIN_COMPRESSED_SIZE else None
+    dataset = group.create_dataset(name, data=data, compression=compression)
+    dataset.attrs["@type"] = kind
+    return dataset
+
+
+def _write_value(group, name: str, value):
+    """Store *value* under *name* in *group*; None is stored by leaving it out."""
+    if value is None:
+        return
+    if isinstance(value, np.ndarray):
+        _create_dataset(group, name, value, "array")
+    elif isinstance(value, Mapping):
+        child = group.create_group(name, track_order=True)
+        child.attrs["@type"] = "dict"
+        child.attrs["@keys"] = json.dumps(list(value))
+        for key, item in value.items():
+            _write_value(child, _escape(key), item)
+    elif isinstance(value, (list, tuple)):
+        kind = "tuple" if isinstance(value, tuple) else "list"
+        records = _records(value)
+        if records is not None:
+            dataset = _create_dataset(group, name, records, "records")
+            dataset.attrs["@keys"] = json.dumps(list(value[0]))
+        elif all(isinstance(item, (Number, np.number)) for item in value):
+            _create_dataset(group, name, np.asarray(value, dtype=np.float64 if not value else None), kind)
+        else:
+            child = group.create_group(name, track_order=True)
+            child.attrs["@type"] = kind
+            child.attrs["@length"] = len(value)
+            for index, item in enumerate(value):
+                _write_value(child, str(index), item)
+    elif _is_scalar(value):
+        group.attrs[name] = value
+    else:
+        raise TypeError(f"Cannot store {type(value).__name__} '{name}' in HDF5")
+
+
+def _attr(value):
+    return value.item() if isinstance(value, np.generic) else value
+
+
+def _read_member(group, name: str):
+    if name in group:
+        return _read_value(group[name])
+    if name in group.attrs:
+        return _attr(group.attrs[name])
+    return None
+
+
+def _read_value(obj):
+    kind = obj.attrs.get("@type")
+    if kind == "array":
+        return obj[()]
+    if kind == "records":
+        data = obj[()]
+        keys = json.loads(obj.attrs["@keys"])
+        return [{key: row[_escape(key)].item() for key in keys} for row in data]
+    if kind in ("list", "tuple"):
+        if "@length" in obj.attrs:
+            values = [_read_member(obj, str(index)) for index in range(int(obj.attrs["@length"]))]
+        else:
+            values = obj[()].tolist()
+        return tuple(values) if kind == "tuple" else values
+    if kind == "dict":
+        return {key: _read_member(obj, _escape(key)) for key in json.loads(obj.attrs["@keys"])}
+    raise ValueError(f"Unknown HDF5 entry type '{kind}' at {obj.name}")
+
+
+def _measurements(measurements) -> list:
+    if isinstance(measurements, Mapping):
+        return list(measurements.values())
+    return list(measurements)
+
+
+def _write_compound(group, compound):
+    group.attrs["@fields"] = compound.model_dump_json()
+    _write_value(group, "concentration", compound.concentration)
+    _write_value(group, "calibration_parameters", dict(compound.calibration_parameters))
+    _write_value(group, "calibration_curve", dict(compound.calibration_curve))
+    _write_value(group, "custom_mz_ranges", dict(compound.custom_mz_ranges))
+    _write_value(group, "ions", compound.ions)
+
+
+def _read_compound(group):
+    compound = Compound(**json.loads(group.attrs["@fields"]))
+    compound.concentration = _read_member(group, "concentration")
+    compound.calibration_parameters = _read_member(group, "calibration_parameters") or {}
+    compound._calibration_curve = _read_member(group, "calibration_curve") or {}
+    compound.custom_mz_ranges = _read_member(group, "custom_mz_ranges") or {}
+    compound.ions = _read_member(group, "ions") or {}
+    return compound
+
+
+def export_hdf5(measurements: Union[Mapping, Iterable], path) -> Path:
+    """
+    Write processed measurements to an HDF5 file.
+
+    Parameters
+    ----------
+    measurements : dict or iterable of MSMeasurement
+        Processed measurements (``xics`` filled), e.g. the model's
+        ``ms_measurements``.
+    path : str or Path
+        Output file, overwritten if it exists.
+
+    Returns
+    -------
+    Path
+        The file written.
+
+    Raises
+    ------
+    ImportError
+        If h5py is not installed.
+    TypeError
+        If a result holds a value that cannot be stored.
+    """
+    h5py = _h5py()
+    path = Path(path)
+    measurements = _measurements(measurements)
+    with h5py.File(path, "w", track_order=True) as f:
+        f.attrs["@format"] = HDF5_FORMAT
+        f.attrs["@version"] = HDF5_VERSION
+        f.attrs["@manifest"] = canonical_json(batch_manifest(measurements))
+        files = f.create_group("files", track_order=True)
+        for measurement in measurements:
+            group = files.create_group(_escape(measurement.filename), track_order=True)
+            group.attrs["@path"] = str(measurement.path)
+            group.attrs["@filename"] = measurement.filename
+            manifest = getattr(measurement, "manifest", None)
+            if manifest is not None:
+                group.attrs["@manifest"] = canonical_json(manifest)
+            for name in _MEASUREMENT_ARRAYS:
+                _write_value(group, name, getattr(measurement, name, None))
+            compounds = group.create_group("compounds", track_order=True)
+            for compound in measurement.xics:
+                _write_compound(compounds.create_group(_escape(compound.name), track_order=True), compound)
+    logger.info(f"Exported processing results to {path}")
+    return path
+
+
+def load_hdf5(path) -> Dict[str, Dict]:
+    """
+    Read processing results written by export_hdf5.
+
+    Returns
+    -------
+    dict
+        ``{filename: {"path": ..., "compounds": [Compound, ...], ...}}``
+        in export order, with the measurement's ``tic_times``,
+        ``tic_values`` and ``bpc_values`` where they were exported and its
+        ``manifest`` (None if it had none).
+
+    Raises
+    ------
+    ImportError
+        If h5py is not installed.
+    FileParseError
+        If the file cannot be read or was not written by export_hdf5.
+    """
+    h5py = _h5py()
+    try:
+        with h5py.File(path, "r") as f:
+            if f.attrs.get("@format") != HDF5_FORMAT:
+                raise FileParseError(path, "not an LCMSpector results file")
+            if int(f.attrs.get("@version", 0)) > HDF5_VERSION:
+                raise FileParseError(
+                    path, f"written by a newer version (format {int(f.attrs['@version'])})"
+                )
+            results = {}
+            for group in f["files"].values():
+                entry = {"path": str(group.attrs["@path"])}
+                manifest = group.attrs.get("@manifest")
+                entry["manifest"] = None if manifest is None else json.loads(manifest)
+                for name in _MEASUREMENT_ARRAYS:
+                    if name in group:
+                        entry[name] = _read_value(group[name])
+                entry["compounds"] = [_read_compound(g) for g in group["compounds"].values()]
+                results[str(group.attrs["@filename"])] = entry
+    except OSError as e:
+        raise FileParseError(path, f"cannot read HDF5 results ({e})") from e
+    return results
@@ -0,0 +1,220 @@
+"""
+Reader for imzML mass spectrometry imaging (MSI) files.
+
+An imzML dataset is a pair of files: the ``.imzML`` metadata, an mzML
+document with one spectrum per pixel carrying its ``position x/y``, and the
+``.ibd`` binary file holding the arrays, referenced by byte offset. The
+.ibd starts with the 16-byte UUID also stored in the .imzML. In
+"continuous" mode all pixels share one m/z array; in "processed" mode every
+pixel has its own.
+
+Array types, data types and compression are often declared once in a
+``referenceableParamGroup`` and only referenced by the arrays, so these are
+resolved here as well.
+"""
+
+import logging
+import zlib
+from pathlib import Path
+
+import numpy as np
+from lxml.etree import iterparse
+
+from utils.mapped_file import open_input
+from utils.mzml_reader import release_element, validate_polarity
+
+logger = logging.getLogger(__name__)
+
+_NS = "http://psi.hupo.org/ms/mzml"
+_SPECTRUM_TAG = f"{{{_NS}}}spectrum"
+_CVPARAM_TAG = f"{{{_NS}}}cvParam"
+_BINARY_DATA_ARRAY_TAG = f"{{{_NS}}}binaryDataArray"
+_SCAN_TAG = f"{{{_NS}}}scan"
+_FILE_CONTENT_TAG = f"{{{_NS}}}fileContent"
+_GROUP_TAG = f"{{{_NS}}}referenceableParamGroup"
+_GROUP_REF_TAG = f"{{{_NS}}}referenceableParamGroupRef"
+
+# Accession constants
+_CONTINUOUS = "IMS:1000030"
+_PROCESSED = "IMS:1000031"
+_UUID = "IMS:1000080"
+_POSITION_X = "IMS:1000050"
+_POSITION_Y = "IMS:1000051"
+_EXTERNAL_OFFSET = "IMS:1000102"
+_EXTERNAL_ARRAY_LENGTH = "IMS:1000103"
+_EXTERNAL_ENCODED_LENGTH = "IMS:1000104"
+_MZ_ARRAY = "MS:1000514"
+_INTENSITY_ARRAY = "MS:1000515"
+_ZLIB = "MS:1000574"
+_POSITIVE_SCAN = "MS:1000130"
+_NEGATIVE_SCAN = "MS:1000129"
+# Binary data types, all little-endian
+_DTYPES = {
+    "MS:1000521": np.dtype("<f4"),
+    "MS:1000523": np.dtype("<f8"),
+    "MS:1000519": np.dtype("<i4"),
+    "MS:1000522": np.dtype("<i8"),
+    "IMS:1000141": np.dtype("<i4"),
+    "IMS:1000142": np.dtype("<i8"),
+}
+
+IMZML_MODES = ("continuous", "processed")
+
+
+def ibd_path(filepath) -> Path:
+    """The .ibd file next to an .imzML file, matching ``.ibd`` or ``.IBD``."""
+    path = Path(filepath)
+    for suffix in (".ibd", ".IBD"):
+        candidate = path.with_suffix(suffix)
+        if candidate.exists():
+            return candidate
+    return path.with_suffix(".ibd")
+
+
+def _params(elem, groups: dict) -> dict:
+    """Accession -> value of an element's cvParams, referenced groups included."""
+    params = {}
+    for ref in elem.iterchildren(_GROUP_REF_TAG):
+        params.update(groups.get(ref.get("ref"), {}))
+    for cv in elem.iterchildren(_CVPARAM_TAG):
+        params[cv.get("accession")] = cv.get("value")
+    return params
+
+
+def _position(spectrum_elem, groups: dict):
+    """The 1-based (x, y) pixel position of a spectrum, or None."""
+    for scan_elem in spectrum_elem.iter(_SCAN_TAG):
+        params = _params(scan_elem, groups)
+        if _POSITION_X in params and _POSITION_Y in params:
+            return int(params[_POSITION_X]), int(params[_POSITION_Y])
+    return None
+
+
+def _read_array(handle, params: dict, cache: dict) -> tuple:
+    """Return (array type, array) of one binaryDataArray, read from the .ibd."""
+    array_type = "mz" if _MZ_ARRAY in params else "intensity" if _INTENSITY_ARRAY in params else None
+    if array_type is None or _EXTERNAL_OFFSET not in params:
+        return None, None
+    offset = int(params[_EXTERNAL_OFFSET])
+    if offset in cache:  # Continuous mode: every pixel points at the same m/z array
+        return array_type, cache[offset]
+    dtype = next((_DTYPES[acc] for acc in params if acc in _DTYPES), np.dtype("<f4"))
+    length = int(params.get(_EXTERNAL_ARRAY_LENGTH, 0))
+    encoded_length = int(params.get(_EXTERNAL_ENCODED_LENGTH, length * dtype.itemsize))
+    raw = handle.view(offset, encoded_length)
+    if _ZLIB in params:
+        raw = zlib.decompress(raw)
+    array = np.frombuffer(raw, dtype=dtype)
+    if len(array) != length:
+        logger.warning(f"Array at offset {offset} has {len(array)} values, expected {length}")
+    array = array.astype(np.float64)
+    if array_type == "mz":
+        cache[offset] = array
+    return array_type, array
+
+
+def _check_uuid(handle, uuid: str, path):
+    if not uuid:
+        return
+    expected = uuid.strip("{}").replace("-", "").lower()
+    found = handle.read(16).hex()
+    if found != expected:
+        logger.warning(f"UUID of {path} does not match its .imzML file; wrong .ibd file?")
+
+
+def iter_pixels(filepath, ibd=None, polarity: str = None):
+    """
+    Yield (x, y, mz_array, intensity_array) per pixel of an imzML dataset.
+
+    Parameters
+    ----------
+    filepath : str or Path
+        The .imzML file.
+    ibd : str or Path, optional
+        The binary file; by default the .ibd with the same name.
+    polarity : str, optional
+        Skip pixels acquired in the other polarity, see
+        utils.mzml_reader.iter_scans.
+
+    Notes
+    -----
+    Coordinates are 1-based as in the file. Spectra without a position are
+    skipped. The arrays of continuous-mode files share one m/z array, which
+    must not be modified in place.
+
+    Raises
+    ------
+    FileNotFoundError
+        If the .ibd file does not exist.
+    """
+    validate_polarity(polarity)
+    ibd = Path(ibd) if ibd is not None else ibd_path(filepath)
+    groups = {}
+    mz_cache = {}
+    with open_input(ibd) as handle:
+        for _, elem in iterparse(
+            str(filepath), tag=(_FILE_CONTENT_TAG, _GROUP_TAG, _SPECTRUM_TAG)
+        ):
+            if elem.tag == _GROUP_TAG:
+                groups[elem.get("id")] = _params(elem, groups)
+                continue
+            if elem.tag == _FILE_CONTENT_TAG:
+                content = _params(elem, groups)
+                _check_uuid(handle, content.get(_UUID), ibd)
+                continue
+
+            params = _params(elem, groups)
+            scan_polarity = (
+                "positive" if _POSITIVE_SCAN in params
+                else "negative" if _NEGATIVE_SCAN in params
+                else None
+            )
+            position = _position(elem, groups)
+            if position is None or polarity is not None and scan_polarity not in (None, polarity):
+                release_element(elem)
+                continue
+            arrays = {}
+            for bda in elem.iter(_BINARY_DATA_ARRAY_TAG):
+                array_type, array = _read_array(handle, _params(bda, groups), mz_cache)
+                if array_type is not None:
+                    arrays[array_type] = array
+            release_element(elem)
+            if "mz" in arrays and "intensity" in arrays:
+                yield position[0], position[1], arrays["mz"], arrays["intensity"]
+
+
+def read_layout(filepath) -> dict:
+    """
+    Read the pixel layout of an imzML file without touching the .ibd.
+
+    Returns
+    -------
+    dict
+        ``mode`` (one of IMZML_MODES, or None if undeclared), ``uuid``,
+        ``coordinates`` (an (n, 2) int array of the 1-based x, y of every
+        spectrum) and the image ``width`` and ``height`` in pixels.
+    """
+    groups = {}
+    layout = {"mode": None, "uuid": None}
+    coordinates = []
+    for _, elem in iterparse(str(filepath), tag=(_FILE_CONTENT_TAG, _GROUP_TAG, _SPECTRUM_TAG)):
+        if elem.tag == _GROUP_TAG:
+            groups[elem.get("id")] = _params(elem, groups)
+            continue
+        if elem.tag == _FILE_CONTENT_TAG:
+            content = _params(elem, groups)
+            if _CONTINUOUS in content:
+                layout["mode"] = "continuous"
+            elif _PROCESSED in content:
+                layout["mode"] = "processed"
+            layout["uuid"] = content.get(_UUID)
+            continue
+        position = _position(elem, groups)
+        release_element(elem)
+        if position is not None:
+            coordinates.append(position)
+
+    layout["coordinates"] = np.array(coordinates, dtype=np.int64).reshape(-1, 2)
+    maxima = layout["coordinates"].max(axis=0) if coordinates else (0, 0)
+    layout["width"], layout["height"] = int(maxima[0]), int(maxima[1])
+    return layout
@@ -0,0 +1,216 @@
+"""
+Registry of the ion lists bundled in config.json.
+
+Scripts using the Python API pick targets by name instead of reading
+config.json themselves, and have to pick them explicitly: there is no
+default ion list to fall back to, as processing with the wrong targets
+fails silently.
+
+    list_ion_lists()                  # ["Aminoacids", "Bile acids", ...]
+    load_ion_list("Bile acids")       # the ion list dict, see compounds_from_ion_list
+
+validate_ion_list() checks a list before processing and reports every
+problem it finds at once, rather than stopping at the first like
+compounds_from_ion_list does.
+"""
+
+import json
+import logging
+from collections import Counter
+from dataclasses import dataclass, field
+from pathlib import Path
+from typing import Dict, List, Mapping, Optional, Tuple
+
+from utils.errors import IonListError
+from utils.resources import get_resource_path
+
+logger = logging.getLogger(__name__)
+
+# m/z outside this range is accepted but most likely a typo
+PLAUSIBLE_MZ_RANGE = (10.0, 5000.0)
+
+
+def _read_config(config_path: Optional[str] = None) -> Dict:
+    path = config_path or get_resource_path("config.json")
+    try:
+        with open(path, "r") as f:
+            return json.load(f)
+    except (OSError, ValueError) as e:
+        raise IonListError(f"Cannot read ion lists from {path}: {e}") from None
+
+
+def list_ion_lists(config_path: Optional[str] = None) -> List[str]:
+    """Sorted names of the ion lists in config.json (or *config_path*)."""
+    return sorted(_read_config(config_path))
+
+
+def load_ion_list(name: str, config_path: Optional[str] = None) -> Dict:
+    """
+    An ion list from config.json by name.
+
+    Returns
+    -------
+    dict
+        ``{compound: {"ions": [...], ...}}``, ready for
+        utils.classes.compounds_from_ion_list.
+
+    Raises
+    ------
+    IonListError
+        If no name is given or there is no ion list of that name.
+    """
+    if not name:
+        raise IonListError("No ion list given, choose one of the bundled ion lists")
+    config = _read_config(config_path)
+    if name not in config:
+        raise IonListError(f"Unknown ion list '{name}', expected one of {sorted(config)}")
+    return config[name]
+
+
+@dataclass
+class IonListIssue:
+    """One problem found by validate_ion_list."""
+
+    level: str  # "error" or "warning"
+    message: str
+    compound: Optional[str] = None
+
+    def __str__(self):
+        prefix = f"{self.compound}: " if self.compound else ""
+        return f"{self.level.capitalize()}: {prefix}{self.message}"
+
+
+@dataclass
+class IonListReport:
+    """Result of validate_ion_list; the list can be processed if ``ok``."""
+
+    issues: List[IonListIssue] = field(default_factory=list)
+
+    @property
+    def errors(self) -> List[IonListIssue]:
+        return [issue for issue in self.issues if issue.level == "error"]
+
+    @property
+    def warnings(self) -> List[IonListIssue]:
+        return [issue for issue in self.issues if issue.level == "warning"]
+
+    @property
+    def ok(self) -> bool:
+        return not self.errors
+
+    def _add(self, level, message, compound=None):
+        self.issues.append(IonListIssue(level, message, compound))
+
+
+def _read_entries(ion_list, report: IonListReport) -> list:
+    """Ion list entries as ``{"name": ..., ...}`` dicts, noting duplicate names."""
+    names = []
+    if isinstance(ion_list, (str, Path)):
+        duplicates = []
+
+        def no_duplicates(pairs):
+            counts = Counter(key for key, _ in pairs)
+            duplicates.extend(key for key, count in counts.items() if count > 1)
+            return dict(pairs)
+
+        try:
+            with open(ion_list, "r") as f:
+                ion_list = json.load(f, object_pairs_hook=no_duplicates)
+        except (OSError, ValueError) as e:
+            report._add("error", f"Cannot read ion list {ion_list}: {e}")
+            return []
+        names.extend(duplicates)
+    if isinstance(ion_list, Mapping):
+        entries = [
+            {"name": name, **details}
+            for name, details in ion_list.items()
+            if not str(name).startswith("_") and isinstance(details, Mapping)
+        ]
+    else:
+        entries = [dict(entry) for entry in ion_list]
+    counts = Counter(str(entry.get("name", "")).strip() for entry in entries)
+    names.extend(name for name, count in counts.items() if name and count > 1)
+    for name in sorted(set(names)):
+        report._add("error", "duplicate compound name", name)
+    return entries
+
+
+def validate_ion_list(
+    ion_list, mass_accuracy: float = 0.0001, mz_range: Tuple[float, float] = PLAUSIBLE_MZ_RANGE
+) -> IonListReport:
+    """
+    Check an ion list for problems before processing.
+
+    Parameters
+    ----------
+    ion_list : str, Path, dict or list
+        A JSON file holding one ion list, or an in-memory ion list in any
+        layout accepted by utils.classes.compounds_from_ion_list.
+    mass_accuracy : float
+        Global mass accuracy, giving the extraction window (``+-3 *
+        mass_accuracy * mz``) of ions without their own tolerance.
+    mz_range : tuple of float
+        m/z values outside it are reported as warnings.
+
+    Returns
+    -------
+    IonListReport
+        Errors (duplicate names, entries without a name or targets, non-
+        numeric or non-positive m/z, invalid formulas, adducts, windows or
+        tolerances, unknown internal standards) and warnings (implausible
+        m/z, ions of different compounds whose extraction windows overlap).
+    """
+    from utils.classes import compounds_from_ion_list
+
+    report = IonListReport()
+    entries = _read_entries(ion_list, report)
+    compounds = []
+    standards = {}  # Checked against the whole list below
+    for entry in entries:
+        name = str(entry.get("name", "")).strip()
+        if not name:
+            report._add("error", f"entry without a name: {entry}")
+            continue
+        if not any(entry.get(key) for key in ("ions", "mass", "formula", "transitions")):
+            report._add("error", "no ions, mass, formula or transitions", name)
+            continue
+        try:
+            (compound,) = compounds_from_ion_list([{**entry, "internal_standard": None}])
+        except IonListError as e:
+            report._add("error", str(e), name)
+            continue
+        standards[name] = entry.get("internal_standard") or None
+        compounds.append(compound)
+
+    names = {compound.name for compound in compounds}
+    names.update(str(entry.get("name", "")).strip() for entry in entries)
+    windows = []
+    for compound in compounds:
+        standard = standards[compound.name]
+        if standard is not None and (standard == compound.name or standard not in names):
+            report._add("error", f"invalid internal standard '{standard}'", compound.name)
+        custom = compound.tolerance_ranges()
+        for mz in compound.target_list:
+            if mz <= 0:
+                report._add("error", f"non-positive m/z {mz}", compound.name)
+                continue
+            if not mz_range[0] <= mz <= mz_range[1]:
+                report._add(
+                    "warning", f"m/z {mz} outside {mz_range[0]:g}-{mz_range[1]:g}", compound.name
+                )
+            delta = mz * mass_accuracy * 3
+            lower, upper = custom.get(mz, (mz - delta, mz + delta))
+            windows.append((lower, upper, mz, compound.name))
+
+    windows.sort()
+    for i, (lower, upper, mz, name) in enumerate(windows):
+        for other_lower, _, other_mz, other_name in windows[i + 1 :]:
+            if other_lower > upper:
+                break
+            if other_name != name:
+                report._add(
+                    "warning",
+                    f"m/z {mz} overlaps m/z {other_mz} of {other_name} within the mass tolerance",
+                    name,
+                )
+    return report
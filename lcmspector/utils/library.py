@@ -0,0 +1,676 @@
+"""
+In-memory MS2 spectral library.
+
+Spectra from an MSP (e.g. the MoNA export in resources/) or MGF file are
+kept in flat NumPy arrays sorted by precursor m/z, so precursor lookups are
+a binary search and no per-spectrum Python objects have to be built. Since
+parsing the ~400 MB MoNA file takes a while, from_msp() stores the parsed
+arrays in a ``.npz`` cache next to the library and reuses it while the
+library file is unchanged.
+
+update_library() downloads the MoNA export on demand and builds that index
+right away, so the first search does not have to parse the file. Later
+calls only fetch the export again when the server reports a newer one
+(conditional requests on its ETag and Last-Modified, kept in a
+``.source.json`` next to the library). Spectra from other files are added
+to a built index with SpectralLibrary.merged, without parsing the library
+again.
+
+To keep in-house spectra apart from the public ones, LibraryCollection
+searches several libraries (MoNA, a NIST MSP export, in-house MSP or MGF
+files) side by side and labels every hit with the library it came from:
+
+    libraries = LibraryCollection()
+    libraries.add_file("resources/MoNA-export-All_LC-MS-MS_Orbitrap.msp", name="MoNA")
+    libraries.add_file("standards.mgf", name="in-house", priority=1)
+    hits = libraries.match(mz, intensity, precursor_mz=195.0877)
+"""
+
+from __future__ import annotations
+
+import json
+import logging
+import os
+import shutil
+import ssl
+import tempfile
+from pathlib import Path
+from typing import Callable, Iterable, Optional, Tuple
+from urllib.error import HTTPError
+from urllib.request import Request, urlopen
+from zipfile import BadZipFile, ZipFile
+
+import numpy as np
+
+from calculation.retention import (
+    DEFAULT_RI_TOLERANCE,
+    DEFAULT_RT_TOLERANCE,
+    DEFAULT_RT_WEIGHT,
+    identification_score,
+    library_retention,
+    retention_agreement,
+)
+from calculation.spectral_similarity import match_ms2_spectra
+
+logger = logging.getLogger(__name__)
+
+_PRECURSOR_KEYS = ("precursormz", "precursor_mz", "pepmass")
+_CACHE_VERSION = 1
+
+# Public download of the MoNA Orbitrap MSP (as used in CI)
+MSP_ZIP_URL = "https://polybox.ethz.ch/index.php/s/CrnWdgwX5canNxL/download"
+MSP_FILENAME = "MoNA-export-All_LC-MS-MS_Orbitrap.msp"
+SOURCE_SUFFIX = ".source.json"
+
+# Metadata keys identifying a spectrum across library versions, first found wins
+_ID_KEYS = ("db#", "accession", "spectrumid", "id")
+_DOWNLOAD_CHUNK = 1 << 20
+
+LIBRARY_FORMATS = (".msp", ".mgf")
+
+
+def _parse_peak_line(line: str):
+    """Yield (mz, intensity) pairs from an MSP peak line.
+
+    Handles one pair per line as well as NIST-style ``mz int; mz int;``
+    lines, with optional quoted annotations.
+    """
+    if '"' in line:
+        line = "".join(line.split('"')[::2])  # drop annotations
+    for pair in line.split(";"):
+        fields = pair.replace(":", " ").split()
+        if len(fields) >= 2:
+            yield float(fields[0]), float(fields[1])
+
+
+def iter_msp(path: str):
+    """
+    Yield one dict per MSP record.
+
+    Parameters
+    ----------
+    path : str
+        Path to the .msp file.
+
+    Yields
+    ------
+    dict
+        ``precursor_mz`` (float or None), ``mz`` and ``intensity`` (float64
+        arrays) and ``metadata`` with all header fields, keys lower-cased.
+    """
+    metadata, peaks, in_peaks = {}, [], False
+
+    def _record():
+        precursor = next(
+            (metadata[k] for k in _PRECURSOR_KEYS if k in metadata), None
+        )
+        try:
+            precursor_mz = float(precursor.split()[0]) if precursor else None
+        except ValueError:
+            precursor_mz = None
+        values = np.array(peaks, dtype=np.float64).reshape(-1, 2)
+        return {
+            "precursor_mz": precursor_mz,
+            "mz": values[:, 0].copy(),
+            "intensity": values[:, 1].copy(),
+            "metadata": metadata,
+        }
+
+    with open(path, "r", encoding="utf-8", errors="replace") as handle:
+        for raw in handle:
+            line = raw.strip()
+            if not line:
+                if metadata:
+                    yield _record()
+                metadata, peaks, in_peaks = {}, [], False
+                continue
+            if in_peaks and (line[0].isdigit() or line[0] == "."):
+                try:
+                    peaks.extend(_parse_peak_line(line))
+                except ValueError:
+                    logger.warning(f"Skipping malformed MSP peak line: {line}")
+                continue
+            key, sep, value = line.partition(":")
+            if not sep:
+                continue
+            key = key.strip().lower()
+            if key == "name" and metadata:
+                # Records not separated by a blank line
+                yield _record()
+                metadata, peaks = {}, []
+            if key == "num peaks":
+                in_peaks = True
+                continue
+            metadata[key] = value.strip()
+    if metadata:
+        yield _record()
+
+
+class SpectralLibrary:
+    """
+    MS2 library stored as flat arrays sorted by precursor m/z.
+
+    Attributes
+    ----------
+    precursor_mz : np.ndarray
+        Precursor m/z per spectrum, ascending; NaN (sorted last) if unknown.
+    offsets : np.ndarray
+        Peaks of spectrum ``i`` are ``mz[offsets[i]:offsets[i + 1]]``.
+    mz, intensity : np.ndarray
+        Concatenated fragment peaks of all spectra, each spectrum m/z sorted.
+    metadata : list of dict
+        Header fields per spectrum (name, precursor type, InChIKey, ...).
+    """
+
+    def __init__(self, precursor_mz, offsets, mz, intensity, metadata):
+        self.precursor_mz = np.asarray(precursor_mz, dtype=np.float64)
+        self.offsets = np.asarray(offsets, dtype=np.int64)
+        self.mz = np.asarray(mz, dtype=np.float64)
+        self.intensity = np.asarray(intensity, dtype=np.float64)
+        self.metadata = list(metadata)
+
+    def __len__(self):
+        return len(self.precursor_mz)
+
+    @classmethod
+    def from_spectra(cls, spectra: Iterable[dict]) -> "SpectralLibrary":
+        """Build a library from dicts with ``precursor_mz``, ``mz``, ``intensity``
+        and optionally ``metadata`` (as yielded by iter_msp)."""
+        spectra = list(spectra)
+        precursors = np.array(
+            [np.nan if s["precursor_mz"] is None else s["precursor_mz"] for s in spectra],
+            dtype=np.float64,
+        )
+        order = np.argsort(precursors, kind="stable")
+
+        mz_parts, intensity_parts, metadata = [], [], []
+        lengths = np.zeros(len(spectra), dtype=np.int64)
+        for k, idx in enumerate(order):
+            spectrum = spectra[idx]
+            mz = np.asarray(spectrum["mz"], dtype=np.float64)
+            peak_order = np.argsort(mz, kind="stable")
+            mz_parts.append(mz[peak_order])
+            intensity_parts.append(
+                np.asarray(spectrum["intensity"], dtype=np.float64)[peak_order]
+            )
+            lengths[k] = len(mz)
+            metadata.append(spectrum.get("metadata", {}))
+
+        offsets = np.concatenate(([0], np.cumsum(lengths)))
+        empty = np.zeros(0, dtype=np.float64)
+        return cls(
+            precursors[order],
+            offsets,
+            np.concatenate(mz_parts) if mz_parts else empty,
+            np.concatenate(intensity_parts) if intensity_parts else empty,
+            metadata,
+        )
+
+    @classmethod
+    def from_msp(cls, path: str, use_cache: bool = True) -> "SpectralLibrary":
+        """
+        Load an MSP library, using (and refreshing) the ``.npz`` cache.
+
+        Parameters
+        ----------
+        path : str
+            Path to the .msp file.
+        use_cache : bool
+            Read ``<path>.npz`` if it is newer than the library, and write it
+            after parsing. Write failures (e.g. read-only installs) are logged
+            and otherwise ignored.
+        """
+        cache_path = Path(f"{path}.npz")
+        if use_cache and cache_path.exists():
+            if os.path.getmtime(cache_path) >= os.path.getmtime(path):
+                try:
+                    library = cls.load(cache_path)
+                    logger.info(f"Loaded {len(library)} library spectra from cache.")
+                    return library
+                except (OSError, ValueError, KeyError) as e:
+                    logger.warning(f"Ignoring unreadable library cache {cache_path}: {e}")
+
+        library = cls.from_spectra(iter_msp(path))
+        logger.info(f"Parsed {len(library)} library spectra from {Path(path).name}.")
+        if use_cache:
+            try:
+                library.save(cache_path)
+            except OSError as e:
+                logger.warning(f"Could not write library cache {cache_path}: {e}")
+        return library
+
+    @classmethod
+    def from_mgf(cls, path: str) -> "SpectralLibrary":
+        """Load an MGF file as a library (see utils.mgf_reader.iter_spectra)."""
+        from utils.mgf_reader import iter_spectra
+
+        return cls.from_spectra(
+            {
+                "precursor_mz": s["precursor_mz"],
+                "mz": s["mz"],
+                "intensity": s["intensity"],
+                "metadata": {"name": s["title"], **s["params"]},
+            }
+            for s in iter_spectra(path)
+        )
+
+    def save(self, path) -> None:
+        """Write the library arrays and metadata to an ``.npz`` file."""
+        with open(path, "wb") as handle:
+            np.savez(
+                handle,
+                version=np.array(_CACHE_VERSION),
+                precursor_mz=self.precursor_mz,
+                offsets=self.offsets,
+                mz=self.mz,
+                intensity=self.intensity,
+                metadata=np.array(json.dumps(self.metadata)),
+            )
+
+    @classmethod
+    def load(cls, path) -> "SpectralLibrary":
+        """Read a library written by save()."""
+        with np.load(path, allow_pickle=False) as data:
+            if int(data["version"]) != _CACHE_VERSION:
+                raise ValueError(f"Unsupported library cache version {int(data['version'])}")
+            return cls(
+                data["precursor_mz"],
+                data["offsets"],
+                data["mz"],
+                data["intensity"],
+                json.loads(str(data["metadata"])),
+            )
+
+    def merged(self, other: "SpectralLibrary") -> "SpectralLibrary":
+        """
+        Library with the spectra of *other* added to these, still sorted by
+        precursor m/z. Spectra of *other* replace the ones here with the same
+        spectrum_id (e.g. updated MoNA records); spectra without an id are
+        always added.
+        """
+        replaced = {spectrum_id(metadata) for metadata in other.metadata} - {None}
+        kept = [
+            self.spectrum(index)
+            for index, metadata in enumerate(self.metadata)
+            if spectrum_id(metadata) not in replaced
+        ]
+        added = [other.spectrum(index) for index in range(len(other))]
+        logger.info(
+            f"Merged {len(added)} spectra into the library, "
+            f"{len(self) - len(kept)} of them replacing existing ones"
+        )
+        return SpectralLibrary.from_spectra(kept + added)
+
+    def spectrum(self, index: int) -> dict:
+        """Return spectrum *index* as a dict like the ones yielded by iter_msp."""
+        start, end = self.offsets[index], self.offsets[index + 1]
+        precursor = self.precursor_mz[index]
+        return {
+            "precursor_mz": None if np.isnan(precursor) else float(precursor),
+            "mz": self.mz[start:end],
+            "intensity": self.intensity[start:end],
+            "metadata": self.metadata[index],
+        }
+
+    def search(self, precursor_mz: float, tolerance: float = 0.01) -> np.ndarray:
+        """Indices of all spectra with a precursor within *tolerance* Da."""
+        lo = np.searchsorted(self.precursor_mz, precursor_mz - tolerance, side="left")
+        hi = np.searchsorted(self.precursor_mz, precursor_mz + tolerance, side="right")
+        return np.arange(lo, hi)
+
+    def match(
+        self,
+        mz: np.ndarray,
+        intensity: np.ndarray,
+        precursor_mz: Optional[float],
+        precursor_tolerance: float = 0.01,
+        fragment_tolerance: float = 0.01,
+        top_n: int = 10,
+        min_score: float = 0.0,
+        method: str = "cosine",
+        tolerance_unit: str = "Da",
+        rt: float = None,
+        ri: float = None,
+        rt_tolerance: float = DEFAULT_RT_TOLERANCE,
+        ri_tolerance: float = DEFAULT_RI_TOLERANCE,
+        rt_weight: float = DEFAULT_RT_WEIGHT,
+    ) -> list[dict]:
+        """
+        Score an experimental MS2 spectrum against all precursor candidates.
+
+        Without a *precursor_mz* (e.g. an EI spectrum, see calculation.gcms)
+        every spectrum of the library is a candidate. *method* and
+        *tolerance_unit* (for the fragment tolerance) are passed
+        to calculation.spectral_similarity.match_ms2_spectra.
+
+        With the spectrum's *rt* (minutes) or retention index *ri*, the
+        agreement with the retention in the metadata of a candidate (see
+        calculation.retention) is blended into its score with *rt_weight*;
+        *min_score* applies to the spectral score alone.
+
+        Returns
+        -------
+        list of dict
+            Up to *top_n* hits with ``index``, ``name``, ``precursor_mz``,
+            ``score`` and ``matched_peaks``, best first. With *rt* or *ri*,
+            also the ``spectral_score`` and the ``rt_score`` (None for
+            candidates without a retention).
+        """
+        if precursor_mz is None:
+            indices = np.arange(len(self))
+        else:
+            indices = self.search(precursor_mz, precursor_tolerance)
+        candidates = [self.spectrum(index) for index in indices]
+        hits = []
+        for result in match_ms2_spectra(
+            mz,
+            intensity,
+            candidates,
+            precursor_mz=precursor_mz,
+            method=method,
+            tolerance=fragment_tolerance,
+            tolerance_unit=tolerance_unit,
+        ):
+            if result["score"] < min_score:
+                continue
+            candidate = candidates[result["index"]]
+            hit = {
+                "index": int(indices[result["index"]]),
+                "name": candidate["metadata"].get("name"),
+                "precursor_mz": candidate["precursor_mz"],
+                "score": result["score"],
+                "matched_peaks": result["matched_peaks"],
+            }
+            if rt is not None or ri is not None:
+                expected_rt, expected_ri = library_retention(candidate["metadata"])
+                agreement = retention_agreement(
+                    rt, expected_rt, ri, expected_ri, rt_tolerance, ri_tolerance
+                )
+                hit["spectral_score"] = result["score"]
+                hit["rt_score"] = None if agreement is None else agreement["score"]
+                hit["score"] = identification_score(result["score"], hit["rt_score"], rt_weight)
+            hits.append(hit)
+        if rt is not None or ri is not None:
+            hits.sort(key=lambda hit: -hit["score"])
+        return hits[:top_n]
+
+
+class LibraryCollection:
+    """
+    Several spectral libraries searched together.
+
+    Every library has a unique name and a priority. match() scores a
+    spectrum against all of them and ranks the hits by score; on equal
+    scores (e.g. the same reference spectrum in two libraries) the library
+    with the higher priority comes first, then the one added first. A
+    collection can be passed wherever a SpectralLibrary is matched against,
+    e.g. to utils.mztab.export_mztab.
+    """
+
+    def __init__(self):
+        self._libraries = {}  # {name: (priority, SpectralLibrary)}, in the order added
+
+    def __len__(self):
+        return len(self._libraries)
+
+    def __contains__(self, name):
+        return name in self._libraries
+
+    @property
+    def names(self) -> list:
+        """Library names, highest priority first."""
+        order = {name: k for k, name in enumerate(self._libraries)}
+        return sorted(self._libraries, key=lambda name: (-self._libraries[name][0], order[name]))
+
+    def library(self, name: str) -> SpectralLibrary:
+        """The library registered as *name*; raises KeyError if there is none."""
+        if name not in self._libraries:
+            raise KeyError(f"No spectral library named '{name}'")
+        return self._libraries[name][1]
+
+    def priority(self, name: str) -> int:
+        """Priority of the library registered as *name*."""
+        self.library(name)
+        return self._libraries[name][0]
+
+    def add(self, name: str, library: SpectralLibrary, priority: int = 0) -> SpectralLibrary:
+        """
+        Register *library* as *name*; returns the library.
+
+        Raises
+        ------
+        ValueError
+            If a library of that name is registered already.
+        """
+        if name in self._libraries:
+            raise ValueError(f"A spectral library named '{name}' is registered already")
+        self._libraries[name] = (int(priority), library)
+        logger.info(
+            f"Registered spectral library {name} ({len(library)} spectra, priority {priority})"
+        )
+        return library
+
+    def add_file(
+        self, path, name: str = None, priority: int = 0, use_cache: bool = True
+    ) -> SpectralLibrary:
+        """
+        Load and register an MSP or MGF library file, named after the file
+        (without suffix) unless *name* is given; returns the library.
+        *use_cache* is passed to SpectralLibrary.from_msp.
+
+        Raises
+        ------
+        ValueError
+            If the file is not one of LIBRARY_FORMATS, or see add.
+        """
+        path = Path(path)
+        suffix = path.suffix.lower()
+        if suffix not in LIBRARY_FORMATS:
+            raise ValueError(
+                f"Unknown library format '{path.suffix}', expected one of {LIBRARY_FORMATS}"
+            )
+        name = name or path.stem
+        if name in self._libraries:
+            raise ValueError(f"A spectral library named '{name}' is registered already")
+        if suffix == ".msp":
+            library = SpectralLibrary.from_msp(str(path), use_cache=use_cache)
+        else:
+            library = SpectralLibrary.from_mgf(str(path))
+        return self.add(name, library, priority)
+
+    def remove(self, name: str) -> SpectralLibrary:
+        """Unregister the library *name*; returns it."""
+        library = self.library(name)
+        del self._libraries[name]
+        return library
+
+    def match(
+        self,
+        mz: np.ndarray,
+        intensity: np.ndarray,
+        precursor_mz: Optional[float],
+        precursor_tolerance: float = 0.01,
+        fragment_tolerance: float = 0.01,
+        top_n: int = 10,
+        min_score: float = 0.0,
+        method: str = "cosine",
+        tolerance_unit: str = "Da",
+        rt: float = None,
+        ri: float = None,
+        rt_tolerance: float = DEFAULT_RT_TOLERANCE,
+        ri_tolerance: float = DEFAULT_RI_TOLERANCE,
+        rt_weight: float = DEFAULT_RT_WEIGHT,
+    ) -> list[dict]:
+        """
+        SpectralLibrary.match over every library.
+
+        Returns
+        -------
+        list of dict
+            Up to *top_n* hits, best first, with the keys of
+            SpectralLibrary.match (``index`` within its library) and the
+            ``library`` name and ``priority`` they came from.
+        """
+        hits = []
+        for order, (name, (priority, library)) in enumerate(self._libraries.items()):
+            for hit in library.match(
+                mz,
+                intensity,
+                precursor_mz,
+                precursor_tolerance=precursor_tolerance,
+                fragment_tolerance=fragment_tolerance,
+                top_n=top_n,
+                min_score=min_score,
+                method=method,
+                tolerance_unit=tolerance_unit,
+                rt=rt,
+                ri=ri,
+                rt_tolerance=rt_tolerance,
+                ri_tolerance=ri_tolerance,
+                rt_weight=rt_weight,
+            ):
+                hits.append((order, {**hit, "library": name, "priority": priority}))
+        hits.sort(key=lambda item: (-item[1]["score"], -item[1]["priority"], item[0]))
+        return [hit for _, hit in hits[:top_n]]
+
+
+def spectrum_id(metadata: dict) -> Optional[str]:
+    """Identifier of a library spectrum from its metadata (see _ID_KEYS), None without."""
+    for key in _ID_KEYS:
+        if metadata.get(key):
+            return str(metadata[key])
+    return None
+
+
+def _source_path(msp_path: Path) -> Path:
+    return msp_path.with_name(msp_path.name + SOURCE_SUFFIX)
+
+
+def _read_source(msp_path: Path) -> dict:
+    try:
+        with open(_source_path(msp_path), encoding="utf-8") as f:
+            return json.load(f)
+    except (OSError, ValueError):
+        return {}
+
+
+def download_msp(
+    directory,
+    url: str = MSP_ZIP_URL,
+    filename: str = MSP_FILENAME,
+    progress: Callable[[int], None] = None,
+    force: bool = False,
+    timeout: float = 60,
+) -> Tuple[Path, bool]:
+    """
+    Download the zipped MSP library at *url* into *directory*.
+
+    The archive is streamed to a temporary file and its ``.msp`` member
+    extracted next to the library, which is only replaced once complete. If
+    the library exists and the server reports it unchanged since the last
+    download, nothing is fetched.
+
+    Parameters
+    ----------
+    directory : str or Path
+        Where the library is stored, created if missing.
+    url : str
+        The zip archive holding the MSP file.
+    filename : str
+        Name the library is stored under.
+    progress : callable, optional
+        Called with the download percentage while the archive arrives.
+    force : bool
+        Download even if the server reports the library unchanged.
+    timeout : float
+        Socket timeout (s).
+
+    Returns
+    -------
+    Tuple[Path, bool]
+        The library file and whether it was (re)downloaded.
+
+    Raises
+    ------
+    OSError
+        If the download fails (urllib.error.URLError is one).
+    ValueError
+        If the archive is not a zip file or holds no ``.msp`` file.
+    """
+    directory = Path(directory)
+    directory.mkdir(parents=True, exist_ok=True)
+    msp_path = directory / filename
+    source = _read_source(msp_path) if msp_path.exists() and not force else {}
+
+    request = Request(url)
+    if source.get("url") == url:
+        if source.get("etag"):
+            request.add_header("If-None-Match", source["etag"])
+        if source.get("last_modified"):
+            request.add_header("If-Modified-Since", source["last_modified"])
+    # The public share is served with a certificate chain some platforms cannot verify
+    context = ssl._create_unverified_context()
+    archive = tempfile.NamedTemporaryFile(dir=directory, suffix=".zip.part", delete=False)
+    try:
+        try:
+            response = urlopen(request, context=context, timeout=timeout)
+        except HTTPError as e:
+            if e.code == 304:
+                logger.info(f"MS2 library {msp_path.name} is up to date")
+                return msp_path, False
+            raise
+        with response, archive:
+            total = int(response.headers.get("content-length") or 0)
+            received = 0
+            for chunk in iter(lambda: response.read(_DOWNLOAD_CHUNK), b""):
+                archive.write(chunk)
+                received += len(chunk)
+                if progress is not None and total > 0:
+                    progress(min(100, int(received / total * 100)))
+            headers = {
+                "etag": response.headers.get("ETag"),
+                "last_modified": response.headers.get("Last-Modified"),
+            }
+        _extract_msp(Path(archive.name), msp_path)
+    finally:
+        archive.close()
+        if os.path.exists(archive.name):
+            os.remove(archive.name)
+
+    with open(_source_path(msp_path), "w", encoding="utf-8") as f:
+        json.dump({"url": url, **headers}, f)
+    logger.info(f"MS2 library downloaded to {msp_path}")
+    return msp_path, True
+
+
+def _extract_msp(archive: Path, msp_path: Path):
+    try:
+        with ZipFile(archive) as zf:
+            member = next((n for n in zf.namelist() if n.lower().endswith(".msp")), None)
+            if member is None:
+                raise ValueError("Downloaded archive does not contain an .msp file")
+            part = msp_path.with_name(msp_path.name + ".part")
+            with zf.open(member) as src, open(part, "wb") as dst:
+                shutil.copyfileobj(src, dst, _DOWNLOAD_CHUNK)
+            os.replace(part, msp_path)
+    except BadZipFile as e:
+        raise ValueError(f"Downloaded library is not a zip archive: {e}") from None
+
+
+def update_library(
+    directory,
+    url: str = MSP_ZIP_URL,
+    filename: str = MSP_FILENAME,
+    progress: Callable[[int], None] = None,
+    force: bool = False,
+) -> Tuple[SpectralLibrary, bool]:
+    """
+    Download (or refresh) the MSP library and load its index, building it
+    after a download; see download_msp for the arguments.
+
+    Returns the library and whether a new version was downloaded. The index
+    is written as the from_msp cache, so later loads skip the parsing.
+    """
+    msp_path, downloaded = download_msp(directory, url, filename, progress, force)
+    library = SpectralLibrary.from_msp(str(msp_path))
+    return library, downloaded
@@ -1,14 +1,24 @@
 import csv
+import json
 import re
 import logging
+import os
 import time
+import tomllib
 from pathlib import Path
 import numpy as np
 import pandas as pd
-from pyteomics import mzml
+from pyteomics import mgf, mzml, mzxml
+
+from utils.mapped_file import open_input
+from utils.scan_cache import read_scan_cache, write_scan_cache
+from utils.scan_filter import validate_scan_filter
 
 logger = logging.getLogger(__name__)
 
+# Lower-case file extension -> MS data format
+MS_FILE_FORMATS = {".mzml": "mzML", ".mzxml": "mzXML", ".mgf": "MGF", ".cdf": "ANDI"}
+
 
 def detect_delimiter(line):
     """Detect the delimiter used in the text file.
@@ -30,6 +40,11 @@ def detect_delimiter(line):
 
 
 def load_absorbance_data(file_path):
+    if Path(file_path).suffix.lower() == ".cdf":
+        from utils.cdf_reader import read_chromatogram
+
+        return read_chromatogram(file_path)
+
     time_values = []
     intensity_values = []
 
@@ -122,14 +137,468 @@ def load_annotated_peaks(file_path):
     return df
 
 
-def load_ms_data(path: str) -> mzml.MzML:
+# Ion list file extensions -> format
+ION_LIST_FORMATS = {".json": "json", ".toml": "toml", ".csv": "csv"}
+# Flat CSV ion list columns; list columns hold ";"-separated values
+ION_LIST_CSV_COLUMNS = (
+    "name", "ions", "info", "mass", "formula", "adducts", "charges", "rt_min", "rt_max", "rt",
+    "ri", "polarity", "internal_standard", "tolerance",
+)
+_ION_LIST_CSV_LISTS = ("ions", "info", "adducts")
+_ION_LIST_CSV_NUMBERS = ("mass", "rt_min", "rt_max", "rt", "ri")
+
+
+def _ion_list_format(path) -> str:
+    suffix = Path(path).suffix.lower()
+    if suffix not in ION_LIST_FORMATS:
+        raise ValueError(
+            f"Unsupported ion list file '{path}', expected one of {sorted(ION_LIST_FORMATS)}"
+        )
+    return ION_LIST_FORMATS[suffix]
+
+
+def load_ion_list_file(path) -> dict:
+    """
+    Read an ion list from a JSON, TOML or CSV file.
+
+    JSON and TOML files hold one ion list in the config.json layout, a table
+    per compound with the keys documented in
+    utils.classes.compounds_from_ion_list, e.g. in TOML::
+
+        _adducts = ["[M+H]+", "[M+Na]+"]
+
+        ["Caffeine"]
+        mass = 194.0804
+        formula = "C8H10N4O2"
+        rt_min = 4.5
+        rt_max = 5.5
+        internal_standard = "Caffeine-d9"
+        tolerance = "5 ppm"
+
+    CSV files have one row per compound with the ION_LIST_CSV_COLUMNS
+    (only ``name`` is required); ``ions``, ``info`` and ``adducts`` hold
+    ``;``-separated values. Nested settings (smoothing, transitions, ...)
+    need JSON or TOML.
+
+    Returns
+    -------
+    dict
+        ``{name: entry}``, ready for utils.classes.compounds_from_ion_list.
+
+    Raises
+    ------
+    ValueError
+        On an unsupported extension or a malformed file.
+    """
+    file_format = _ion_list_format(path)
+    if file_format == "csv":
+        return _read_ion_list_csv(path)
+    try:
+        if file_format == "json":
+            with open(path, "r") as f:
+                ion_list = json.load(f)
+        else:
+            with open(path, "rb") as f:
+                ion_list = tomllib.load(f)
+    except (json.JSONDecodeError, tomllib.TOMLDecodeError) as e:
+        raise ValueError(f"Malformed ion list file '{path}': {e}") from None
+    if not isinstance(ion_list, dict):
+        raise ValueError(f"Ion list file '{path}' must hold a table of compounds")
+    return ion_list
+
+
+def save_ion_list_file(ion_list: dict, path):
+    """
+    Write an ion list in the format given by the extension of *path*.
+
+    The inverse of load_ion_list_file: ``None`` values are left out, as
+    neither TOML nor CSV has them, and CSV keeps only its flat columns.
+    """
+    file_format = _ion_list_format(path)
+    if file_format == "json":
+        with open(path, "w") as f:
+            json.dump(ion_list, f, indent=4)
+    elif file_format == "toml":
+        with open(path, "w") as f:
+            f.write(_ion_list_to_toml(ion_list))
+    else:
+        _write_ion_list_csv(ion_list, path)
+
+
+def _toml_value(value) -> str:
+    if isinstance(value, bool):
+        return "true" if value else "false"
+    if isinstance(value, (int, float, np.number)):
+        return repr(value.item() if isinstance(value, np.number) else value)
+    if isinstance(value, str):
+        return json.dumps(value)  # JSON string escapes are valid TOML
+    if isinstance(value, dict):
+        items = ", ".join(
+            f"{json.dumps(str(k))} = {_toml_value(v)}" for k, v in value.items() if v is not None
+        )
+        return "{" + items + "}"
+    if isinstance(value, (list, tuple, np.ndarray)):
+        return "[" + ", ".join(_toml_value(v) for v in value) + "]"
+    raise ValueError(f"Cannot write {type(value).__name__} value {value!r} to TOML")
+
+
+def _ion_list_to_toml(ion_list: dict) -> str:
+    lines = [
+        f"{json.dumps(key)} = {_toml_value(value)}"
+        for key, value in ion_list.items()
+        if not isinstance(value, dict) and value is not None
+    ]
+    for name, entry in ion_list.items():
+        if not isinstance(entry, dict):
+            continue
+        lines += ["", f"[{json.dumps(str(name))}]"]
+        lines += [
+            f"{json.dumps(str(key))} = {_toml_value(value)}"
+            for key, value in entry.items()
+            if value is not None
+        ]
+    return "\n".join(lines) + "\n"
+
+
+def _read_ion_list_csv(path) -> dict:
+    ion_list = {}
+    with open(path, "r", newline="") as f:
+        reader = csv.DictReader(f)
+        if reader.fieldnames is None or "name" not in reader.fieldnames:
+            raise ValueError(f"Ion list file '{path}' has no 'name' column")
+        for row in reader:
+            name = (row.pop("name") or "").strip()
+            if not name:
+                continue
+            entry = {}
+            for key, value in row.items():
+                value = (value or "").strip()
+                if key is None or not value:
+                    continue
+                if key in _ION_LIST_CSV_LISTS:
+                    values = [v.strip() for v in value.split(";") if v.strip()]
+                    entry[key] = [float(v) for v in values] if key == "ions" else values
+                elif key in _ION_LIST_CSV_NUMBERS:
+                    entry[key] = float(value)
+                else:
+                    entry[key] = value
+            ion_list[name] = entry
+    return ion_list
+
+
+def _write_ion_list_csv(ion_list: dict, path):
+    with open(path, "w", newline="") as f:
+        writer = csv.DictWriter(f, fieldnames=ION_LIST_CSV_COLUMNS, extrasaction="ignore")
+        writer.writeheader()
+        for name, entry in ion_list.items():
+            if not isinstance(entry, dict):
+                continue
+            row = {"name": name}
+            for key, value in entry.items():
+                if value is None:
+                    continue
+                if key in _ION_LIST_CSV_LISTS:
+                    value = ";".join(str(v) for v in value)
+                elif key == "charges" and isinstance(value, (list, tuple)):
+                    value = "-".join(str(v) for v in value)  # "low-high"
+                row[key] = value
+            writer.writerow(row)
+
+
+def detect_ms_format(path: str) -> str:
+    """
+    Determine whether an MS file is mzML, mzXML, MGF or ANDI/netCDF.
+
+    The file extension is checked first; for unknown extensions the first few
+    kilobytes of the file are sniffed for the root element.
+
+    Parameters
+    ----------
+    path : str
+        The path to the MS data file.
+
+    Returns
+    -------
+    str
+        "mzML", "mzXML", "MGF" or "ANDI".
+
+    Raises
+    ------
+    ValueError
+        If the format cannot be determined.
+    """
+    ms_format = MS_FILE_FORMATS.get(Path(path).suffix.lower())
+    if ms_format is not None:
+        return ms_format
+
+    with open(path, "rb") as f:
+        header = f.read(4096)
+    if b"<mzXML" in header:
+        return "mzXML"
+    if b"<mzML" in header or b"<indexedmzML" in header:
+        return "mzML"
+    if b"BEGIN IONS" in header:
+        return "MGF"
+    if header[:4] in (b"CDF\x01", b"CDF\x02"):
+        return "ANDI"
+    raise ValueError(f"Unrecognized MS file format: {path}")
+
+
+def spectrum_ms_level(spectrum: dict) -> int:
+    """Return the MS level of a pyteomics spectrum dict from either MzML or MzXML."""
+    return int(spectrum.get("ms level", spectrum.get("msLevel", 1)))
+
+
+def _unit_value(value):
+    """A pyteomics value (unitfloat, str or number) as a float, None if it is not one."""
+    try:
+        return float(value)
+    except (TypeError, ValueError):
+        return None
+
+
+def spectrum_metadata(spectrum: dict) -> dict:
+    """
+    Acquisition metadata of a pyteomics spectrum dict from either MzML or
+    MzXML, with the keys of utils.mzml_reader.iter_scan_metadata but
+    ``index`` and ``id``; None where not recorded.
+    """
+    scan = (spectrum.get("scanList", {}).get("scan") or [{}])[0]
+    precursor = (spectrum.get("precursorList", {}).get("precursor") or [{}])[0]
+    activation = precursor.get("activation", {})
+    selected_ion = (precursor.get("selectedIonList", {}).get("selectedIon") or [{}])[0]
+    mzxml_precursor = (spectrum.get("precursorMz") or [{}])[0]
+    if "positive scan" in spectrum or spectrum.get("polarity") == "+":
+        polarity = "positive"
+    elif "negative scan" in spectrum or spectrum.get("polarity") == "-":
+        polarity = "negative"
+    else:
+        polarity = None
+    params = {**spectrum, **activation, **scan}
+
+    def find(words):
+        # Matched by name, these come as cvParams or as vendor userParams
+        return next((_unit_value(v) for k, v in params.items() if words in k.lower()), None)
+
+    return {
+        "scan_time": _unit_value(scan.get("scan start time", spectrum.get("retentionTime"))),
+        "ms_level": spectrum_ms_level(spectrum),
+        "polarity": polarity,
+        "filter_string": scan.get("filter string", spectrum.get("filterLine")),
+        "precursor_mz": _unit_value(
+            selected_ion.get("selected ion m/z", mzxml_precursor.get("precursorMz"))
+        ),
+        "injection_time": find("injection time"),
+        "collision_energy": _unit_value(
+            activation.get("collision energy", spectrum.get("collisionEnergy"))
+        ),
+        "normalized_collision_energy": _unit_value(activation.get("normalized collision energy")),
+        "resolution": find("resolution"),
+    }
+
+
+def _get_reader_module(path: str):
+    """Return the streaming reader module (mzml_reader, mzxml_reader, mgf_reader or cdf_reader) for a file."""
+    ms_format = detect_ms_format(path)
+    if ms_format == "ANDI":
+        from utils import cdf_reader
+
+        return cdf_reader
+    if ms_format == "mzXML":
+        from utils import mzxml_reader
+
+        return mzxml_reader
+    if ms_format == "MGF":
+        from utils import mgf_reader
+
+        return mgf_reader
+    from utils import mzml_reader
+
+    return mzml_reader
+
+
+def iter_ms_scans(
+    path: str,
+    progress_callback=None,
+    progress_step: float = 0.01,
+    polarity: str = None,
+    scan_filter: dict = None,
+    with_mobility: bool = False,
+    cache: bool = False,
+    decode_threads: int = None,
+    with_precursor: bool = False,
+):
+    """
+    Stream (scan_time, tic, ms_level, mz_array, intensity_array) tuples from an
+    mzML, mzXML, MGF or ANDI-MS file, picking the reader by detect_ms_format().
+
+    Parameters
+    ----------
+    path : str
+        The path to the .mzML, .mzXML, .mgf or .cdf file.
+    progress_callback : callable, optional
+        Called as ``progress_callback(fraction_done)`` with the share of the
+        file read so far (0.0-1.0), at most once per ``progress_step`` and
+        always with 1.0 once the file is exhausted.
+    progress_step : float
+        Minimum increase in fraction between two callback invocations.
+    polarity : str, optional
+        "positive" or "negative" to skip scans of the other polarity, for
+        files acquired with polarity switching. Scans of unknown polarity are
+        kept.
+    scan_filter : dict, optional
+        Only yield scans passing this filter on MS level, filter string and
+        precursor m/z, see utils.scan_filter.validate_scan_filter.
+    with_mobility : bool
+        Append the per-peak ion mobility array (or None if the file has
+        none) to every tuple, see utils.mzml_reader.iter_scans.
+    cache : bool
+        Read the scans from the file's on-disk scan cache, writing it on
+        the first full pass; see utils.scan_cache.
+    decode_threads : int, optional
+        Threads decoding the binary arrays of mzML files while they are
+        parsed, see utils.mzml_reader.iter_scans; other formats ignore it.
+    with_precursor : bool
+        Append the precursor m/z of every scan (None for MS1 scans and
+        formats without precursors) as the last item. The scan cache does
+        not keep precursors, so this cannot be combined with *cache*.
+    """
+    scan_filter = validate_scan_filter(scan_filter)
+    if cache and with_precursor:
+        raise ValueError("The scan cache does not keep precursors, read them without cache")
+    if cache:
+        cached = read_scan_cache(path, polarity, scan_filter, with_mobility, progress_callback)
+        if cached is None:
+            scans = iter_ms_scans(
+                path, progress_callback, progress_step, polarity, scan_filter, with_mobility=True,
+                decode_threads=decode_threads,
+            )
+            cached = write_scan_cache(path, scans, polarity, scan_filter, with_mobility)
+        yield from cached
+        return
+
+    reader = _get_reader_module(path)
+    options = {"polarity": polarity, "scan_filter": scan_filter, "with_mobility": with_mobility}
+    if with_precursor:
+        options["with_precursor"] = True
+    if decode_threads and detect_ms_format(path) == "mzML":
+        options["decode_threads"] = decode_threads
+    if progress_callback is None:
+        yield from reader.iter_scans(path, **options)
+        return
+
+    total_bytes = os.path.getsize(path) or 1
+    last_reported = 0.0
+    with open_input(path) as handle:
+        for scan in reader.iter_scans(handle, **options):
+            # Byte offset of the parser is a good proxy since scans are streamed in order
+            fraction = min(handle.tell() / total_bytes, 1.0)
+            if fraction - last_reported >= progress_step:
+                progress_callback(fraction)
+                last_reported = fraction
+            yield scan
+    progress_callback(1.0)
+
+
+def iter_ms2_scans(path: str, with_precursor_info: bool = False):
+    """
+    Stream (scan_time, precursor_mz, mz_array, intensity_array) tuples for the
+    MS2 scans of an mzML, mzXML, MGF or ANDI-MS file, picking the reader by
+    detect_ms_format().
+
+    With *with_precursor_info*, a fifth item holds the selected and
+    monoisotopic precursor m/z and the isolation window, see
+    utils.mzml_reader.iter_ms2_scans; formats without them give None.
+    """
+    reader = _get_reader_module(path)
+    if not with_precursor_info:
+        yield from reader.iter_ms2_scans(path)
+    elif detect_ms_format(path) in ("mzML", "mzXML"):
+        yield from reader.iter_ms2_scans(path, with_precursor_info=True)
+    else:
+        for scan in reader.iter_ms2_scans(path):
+            info = {"selected_mz": scan[1], "monoisotopic_mz": None, "isolation_window": None}
+            yield (*scan, info)
+
+
+def iter_dia_scans(path: str):
+    """
+    Stream the MS1 and MS2 scans of a DIA file with the MS2 isolation
+    windows, see utils.mzml_reader.iter_dia_scans. Only mzML records the
+    windows; other formats yield nothing.
+    """
+    if detect_ms_format(path) != "mzML":
+        return
+    from utils.mzml_reader import iter_dia_scans as iter_mzml_dia
+
+    yield from iter_mzml_dia(path)
+
+
+def iter_scan_metadata(path: str):
+    """
+    Stream the acquisition metadata (injection time, collision energy, filter
+    string, resolution, ...) of every scan of an mzML or mzXML file as dicts,
+    see utils.mzml_reader.iter_scan_metadata; other formats yield nothing.
+    """
+    ms_format = detect_ms_format(path)
+    if ms_format not in ("mzML", "mzXML"):
+        return
+    yield from _get_reader_module(path).iter_scan_metadata(path)
+
+
+def iter_srm_chromatograms(path: str):
+    """
+    Stream the SRM/MRM chromatograms of an MS file, see
+    utils.mzml_reader.iter_srm_chromatograms. Only mzML stores chromatograms;
+    other formats yield nothing.
+    """
+    if detect_ms_format(path) != "mzML":
+        return
+    from utils.mzml_reader import iter_srm_chromatograms as iter_mzml_srm
+
+    yield from iter_mzml_srm(path)
+
+
+def iter_detector_chromatograms(path: str):
+    """
+    Stream the UV/DAD and other non-MS chromatograms of an MS file, see
+    utils.mzml_reader.iter_detector_chromatograms. Only mzML stores
+    chromatograms; other formats yield nothing.
+    """
+    if detect_ms_format(path) != "mzML":
+        return
+    from utils.mzml_reader import iter_detector_chromatograms as iter_mzml_detector
+
+    yield from iter_mzml_detector(path)
+
+
+def find_nearest_ms2(
+    path: str,
+    precursor_mz: float,
+    target_rt: float,
+    mz_tolerance: float = 0.5,
+    rt_window: float = 2.0,
+):
+    """Format-agnostic wrapper around the readers' find_nearest_ms2()."""
+    return _get_reader_module(path).find_nearest_ms2(
+        path,
+        precursor_mz,
+        target_rt,
+        mz_tolerance=mz_tolerance,
+        rt_window=rt_window,
+    )
+
+
+def load_ms_data(path: str) -> mzml.MzML | mzxml.MzXML | mgf.IndexedMGF:
     """
-    Using the pyteomics library, load the data from the .mzML file.
+    Using the pyteomics library, load the data from the .mzML, .mzXML or .mgf file.
+    ANDI-MS (.cdf) files are read into a utils.cdf_reader.AndiMSReader, which
+    offers the same ``len()`` and ``.time[...]`` access.
 
     Parameters
     ----------
     path : str
-        The path to the .mzML file.
+        The path to the .mzML, .mzXML or .mgf file.
 
     Returns
     -------
@@ -138,7 +607,17 @@ def load_ms_data(path: str) -> mzml.MzML:
     """
     start_time = time.time()
 
-    f = mzml.MzML(path)
+    ms_format = detect_ms_format(path)
+    if ms_format == "mzXML":
+        f = mzxml.MzXML(path)
+    elif ms_format == "MGF":
+        f = mgf.IndexedMGF(path)
+    elif ms_format == "ANDI":
+        from utils.cdf_reader import AndiMSReader
+
+        f = AndiMSReader(path)
+    else:
+        f = mzml.MzML(path)
 
     logger.info(f"Loaded {len(f)} MSn scans in {time.time() - start_time:.2f} seconds.")
     return f
@@ -146,38 +625,130 @@ def load_ms_data(path: str) -> mzml.MzML:
 
 def extract_tic_data(path: str) -> tuple[np.ndarray, np.ndarray]:
     """
-    Extract Total Ion Current data from an mzML file.
+    Extract Total Ion Current data from an mzML or mzXML file.
 
-    Tries to read pre-computed TIC from the chromatogramList element first
-    (instant, no scan iteration). Falls back to iterating all scans if absent.
+    For mzML, tries to read pre-computed TIC from the chromatogramList element
+    first (instant, no scan iteration). Falls back to iterating all scans if
+    absent, which is always the case for mzXML.
 
     Runs in worker process during loading - no UI blocking.
 
     Parameters
     ----------
     path : str
-        The path to the .mzML file.
+        The path to the .mzML or .mzXML file.
 
     Returns
     -------
     tuple[np.ndarray, np.ndarray]
         (times, tic_values) arrays
     """
-    from utils.mzml_reader import extract_tic_chromatogram, iter_scans
+    from utils.mzml_reader import extract_tic_chromatogram
 
     # Fast path: read pre-computed TIC from chromatogramList
-    result = extract_tic_chromatogram(path)
-    if result is not None:
-        return result
+    if detect_ms_format(path) == "mzML":
+        result = extract_tic_chromatogram(path)
+        if result is not None:
+            return result
 
     # Fallback: iterate all scans
     logger.info("TIC chromatogram not found, falling back to scan iteration")
     times = []
     tic_values = []
-    for scan_time, tic, ms_level, mz, intensity in iter_scans(path):
+    for scan_time, tic, ms_level, mz, intensity in iter_ms_scans(path):
         times.append(scan_time)
         tic_values.append(tic)
 
     return np.array(times, dtype=np.float64), np.array(tic_values, dtype=np.float64)
 
 
+def extract_chromatogram_data(path: str) -> tuple[np.ndarray, np.ndarray, np.ndarray]:
+    """
+    Extract the Total Ion Current and Base Peak Chromatogram in one go.
+
+    For mzML, pre-computed TIC and BPC chromatograms are used when both are
+    present. Otherwise a single pass over the scans computes both, taking the
+    scan's total ion current attribute (or the summed intensities if it is
+    missing) and its most intense peak.
+
+    Runs in worker process during loading - no UI blocking.
+
+    Parameters
+    ----------
+    path : str
+        The path to the .mzML or .mzXML file.
+
+    Returns
+    -------
+    tuple[np.ndarray, np.ndarray, np.ndarray]
+        (times, tic_values, bpc_values) arrays, all on the same time axis.
+    """
+    from utils.mzml_reader import extract_summary_chromatograms
+
+    if detect_ms_format(path) == "mzML":
+        found = extract_summary_chromatograms(path)
+        if "tic" in found and "bpc" in found:
+            times, tic_values = found["tic"]
+            bpc_times, bpc_values = found["bpc"]
+            if not np.array_equal(times, bpc_times):
+                bpc_values = np.interp(times, bpc_times, bpc_values)
+            return times, tic_values, bpc_values
+
+    logger.info("TIC/BPC chromatograms not found, computing them from the scans")
+    times = []
+    tic_values = []
+    bpc_values = []
+    for scan_time, tic, ms_level, mz, intensity in iter_ms_scans(path):
+        times.append(scan_time)
+        if tic <= 0 and len(intensity):
+            tic = float(np.sum(intensity))
+        tic_values.append(tic)
+        bpc_values.append(float(np.max(intensity)) if len(intensity) else 0.0)
+
+    return (
+        np.array(times, dtype=np.float64),
+        np.array(tic_values, dtype=np.float64),
+        np.array(bpc_values, dtype=np.float64),
+    )
+
+
+def downsample_spectrum(
+    mz: np.ndarray, intensity: np.ndarray, max_points: int
+) -> tuple[np.ndarray, np.ndarray]:
+    """Keep the *max_points* most intense peaks of a spectrum, in m/z order."""
+    if max_points is None or len(mz) <= max_points:
+        return mz, intensity
+    keep = np.sort(np.argpartition(intensity, -max_points)[-max_points:])
+    return mz[keep], intensity[keep]
+
+
+def load_spectra_data(
+    path: str, ms_level: int = None, max_points: int = None
+) -> dict[int, tuple[float, np.ndarray, np.ndarray]]:
+    """
+    Read every scan of an mzML or mzXML file into memory.
+
+    Parameters
+    ----------
+    path : str
+        The path to the .mzML or .mzXML file.
+    ms_level : int, optional
+        Only keep scans of this MS level.
+    max_points : int, optional
+        Downsample each scan to its *max_points* most intense peaks.
+
+    Returns
+    -------
+    dict[int, tuple[float, np.ndarray, np.ndarray]]
+        ``{scan_index: (scan_time, mz_array, intensity_array)}``, where
+        scan_index counts all scans in file order (also the skipped ones).
+    """
+    spectra = {}
+    for scan_index, (scan_time, tic, level, mz, intensity) in enumerate(
+        iter_ms_scans(path)
+    ):
+        if ms_level is not None and level != ms_level:
+            continue
+        spectra[scan_index] = (scan_time, *downsample_spectrum(mz, intensity, max_points))
+    logger.info(f"Loaded {len(spectra)} spectra from {Path(path).name} into memory.")
+    return spectra
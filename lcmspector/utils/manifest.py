@@ -0,0 +1,208 @@
+"""
+Reproducibility manifests of processed files.
+
+Every processed MS file gets a manifest, stored as its ``manifest``, that
+pins down what produced its results:
+
+    {"software": "LCMSpector", "version": "0.12.3", "created": "2026-...Z",
+     "input_file": "sample.mzML", "input_sha256": "…",
+     "ion_list_hash": "…", "config_hash": "…", "config": {...}}
+
+The hashes are SHA-256 digests: of the raw file (of every file in it, for
+directory formats such as Bruker .d), of the ion list definitions and of
+the effective processing options (calculation.config.ProcessingConfig),
+serialized as canonical JSON, so two analyses with the same hashes ran the
+same method on the same data. Exported files carry the batch_manifest of
+their measurements, see utils.export, utils.hdf5 and utils.mztab; formats
+without room for metadata get a ``<file>.manifest.json`` next to them.
+"""
+
+import hashlib
+import json
+import logging
+import os
+from datetime import datetime, timezone
+from importlib import metadata
+from pathlib import Path
+from typing import Iterable, Mapping, Optional, Union
+
+import numpy as np
+
+from utils.mapped_file import open_input
+
+logger = logging.getLogger(__name__)
+
+SOFTWARE_NAME = "LCMSpector"
+MANIFEST_SUFFIX = ".manifest.json"
+
+_CHUNK_SIZE = 1 << 20
+# {path: ((size, mtime_ns), sha256)}, files are only hashed again once they change
+_CHECKSUMS = {}
+
+
+def software_version() -> str:
+    """Version of the installed package, else of the source tree, else "unknown"."""
+    try:
+        return metadata.version("lcmspector")
+    except metadata.PackageNotFoundError:
+        pass
+    pyproject = Path(__file__).resolve().parents[2] / "pyproject.toml"
+    try:
+        import tomllib
+
+        with open(pyproject, "rb") as f:
+            return str(tomllib.load(f)["project"]["version"])
+    except (OSError, KeyError, ValueError):
+        return "unknown"
+
+
+def _json_default(value):
+    if isinstance(value, np.generic):
+        return value.item()
+    if isinstance(value, np.ndarray):
+        return value.tolist()
+    if isinstance(value, (set, frozenset)):
+        return sorted(value)
+    return str(value)
+
+
+def canonical_json(value) -> str:
+    """*value* as JSON with sorted keys and no whitespace, numpy values as Python ones."""
+    return json.dumps(value, sort_keys=True, separators=(",", ":"), default=_json_default)
+
+
+def _digest(value) -> str:
+    return hashlib.sha256(canonical_json(value).encode("utf-8")).hexdigest()
+
+
+def config_hash(config: Optional[Mapping]) -> Optional[str]:
+    """SHA-256 of processing options, e.g. ProcessingConfig.to_dict(); None without."""
+    return None if config is None else _digest(dict(config))
+
+
+def ion_list_hash(compounds: Iterable) -> str:
+    """SHA-256 of the ion list definitions of Compounds (or of a plain ion list)."""
+    if isinstance(compounds, Mapping):
+        return _digest(compounds)
+    return _digest(
+        [
+            compound.model_dump() if hasattr(compound, "model_dump") else compound
+            for compound in compounds
+        ]
+    )
+
+
+def _hash_file(path: Path, sha) -> None:
+    with open_input(path) as handle:
+        if handle.mapped:
+            sha.update(handle.view())  # Straight from the page cache
+            return
+        for chunk in iter(lambda: handle.read(_CHUNK_SIZE), b""):
+            sha.update(chunk)
+
+
+def file_checksum(path: Union[str, Path]) -> Optional[str]:
+    """
+    SHA-256 of a file, None if it cannot be read.
+
+    Directories (Bruker .d, Waters .raw) are hashed over their files in
+    order of their relative paths, names included.
+    """
+    path = Path(path)
+    try:
+        stat = path.stat()
+        key = (stat.st_size, stat.st_mtime_ns)
+        cached = _CHECKSUMS.get(str(path))
+        if cached is not None and cached[0] == key and not path.is_dir():
+            return cached[1]
+        sha = hashlib.sha256()
+        if path.is_dir():
+            for member in sorted(p for p in path.rglob("*") if p.is_file()):
+                sha.update(member.relative_to(path).as_posix().encode("utf-8") + b"\0")
+                _hash_file(member, sha)
+        else:
+            _hash_file(path, sha)
+    except OSError as e:
+        logger.warning(f"Cannot compute the checksum of {path}: {e}")
+        return None
+    digest = sha.hexdigest()
+    _CHECKSUMS[str(path)] = (key, digest)
+    return digest
+
+
+def _created() -> str:
+    return datetime.now(timezone.utc).isoformat(timespec="seconds").replace("+00:00", "Z")
+
+
+def build_manifest(
+    path,
+    compounds: Iterable,
+    config: Optional[Mapping] = None,
+    input_sha256: Optional[str] = None,
+) -> dict:
+    """
+    Manifest of one processed file, see the module docstring.
+
+    Parameters
+    ----------
+    path : str or Path
+        The raw MS file.
+    compounds : iterable of Compound
+        The ion list it was processed with.
+    config : dict, optional
+        The effective processing options, ProcessingConfig.to_dict().
+    input_sha256 : str, optional
+        The file_checksum of *path* if it was already computed, e.g. in
+        the process that processed the file; computed here otherwise.
+    """
+    return {
+        "software": SOFTWARE_NAME,
+        "version": software_version(),
+        "created": _created(),
+        "input_file": Path(path).name,
+        "input_sha256": file_checksum(path) if input_sha256 is None else input_sha256,
+        "ion_list_hash": ion_list_hash(compounds),
+        "config_hash": config_hash(config),
+        "config": None if config is None else dict(config),
+    }
+
+
+def batch_manifest(measurements: Iterable) -> dict:
+    """
+    Manifest of an export: the software and the manifests of the
+    *measurements* by filename (None for ones processed without one).
+    """
+    return {
+        "software": SOFTWARE_NAME,
+        "version": software_version(),
+        "created": _created(),
+        "measurements": {
+            measurement.filename: getattr(measurement, "manifest", None)
+            for measurement in measurements
+        },
+    }
+
+
+def manifest_path(path: Union[str, Path]) -> Path:
+    """The ``<file>.manifest.json`` sidecar of an exported file."""
+    path = Path(path)
+    return path.with_name(path.name + MANIFEST_SUFFIX)
+
+
+def write_manifest(path: Union[str, Path], manifest: Mapping) -> Path:
+    """Write the sidecar of the exported file *path*; returns the sidecar written."""
+    sidecar = manifest_path(path)
+    tmp = sidecar.with_name(sidecar.name + ".tmp")
+    with open(tmp, "w", encoding="utf-8") as f:
+        json.dump(manifest, f, indent=2, sort_keys=True, default=_json_default)
+    os.replace(tmp, sidecar)
+    return sidecar
+
+
+def read_manifest(path: Union[str, Path]) -> Optional[dict]:
+    """The sidecar manifest of the exported file *path*, None if there is none."""
+    try:
+        with open(manifest_path(path), encoding="utf-8") as f:
+            return json.load(f)
+    except FileNotFoundError:
+        return None
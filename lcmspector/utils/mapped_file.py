@@ -0,0 +1,96 @@
+"""
+Memory-mapped access to input files.
+
+Reading a raw file through a read-only memory map serves it straight from
+the OS page cache, shared between the worker processes and kept across
+reprocessing runs, without a read buffer per pass; slices of it are
+zero-copy (InputFile.view). Where mapping fails (empty files, some network
+and FUSE filesystems, address space exhausted on 32-bit systems),
+open_input falls back to a plain buffered file with the same interface:
+
+    with open_input("run.ibd") as handle:
+        raw = handle.view(offset, length)  # memoryview, or bytes when not mapped
+"""
+
+import io
+import logging
+import mmap
+import os
+from pathlib import Path
+from typing import Union
+
+logger = logging.getLogger(__name__)
+
+
+class InputFile:
+    """A read-only binary file, memory-mapped if ``mapped``; see open_input."""
+
+    def __init__(self, path, use_mmap: bool = True):
+        self.name = str(path)
+        self._file = open(path, "rb")
+        self._map = None
+        if use_mmap:
+            try:
+                self._map = mmap.mmap(self._file.fileno(), 0, access=mmap.ACCESS_READ)
+            except (OSError, ValueError) as e:
+                logger.debug(f"Cannot memory-map {Path(path).name}, reading it instead: {e}")
+
+    @property
+    def mapped(self) -> bool:
+        return self._map is not None
+
+    @property
+    def closed(self) -> bool:
+        return self._file.closed
+
+    @property
+    def _source(self):
+        return self._map if self._map is not None else self._file
+
+    def read(self, size: int = -1) -> bytes:
+        return self._source.read(size)
+
+    def readline(self) -> bytes:
+        return self._source.readline()
+
+    def tell(self) -> int:
+        return self._source.tell()
+
+    def seek(self, offset: int, whence: int = io.SEEK_SET) -> int:
+        self._source.seek(offset, whence)
+        return self._source.tell()
+
+    def size(self) -> int:
+        return len(self._map) if self._map is not None else os.fstat(self._file.fileno()).st_size
+
+    def view(self, offset: int = 0, length: int = None):
+        """*length* bytes from *offset* (to the end by default), zero-copy when mapped."""
+        if length is None:
+            length = self.size() - offset
+        if self._map is not None:
+            return memoryview(self._map)[offset : offset + length]
+        self._file.seek(offset)
+        return self._file.read(length)
+
+    def __iter__(self):
+        return iter(self.readline, b"")
+
+    def close(self):
+        if self._map is not None:
+            try:
+                self._map.close()
+            except BufferError:
+                pass  # A view is still in use; the map goes with the last one
+            self._map = None
+        self._file.close()
+
+    def __enter__(self):
+        return self
+
+    def __exit__(self, *exc):
+        self.close()
+
+
+def open_input(path: Union[str, Path], use_mmap: bool = True) -> InputFile:
+    """Open a file for reading, memory-mapped unless *use_mmap* is False or mapping fails."""
+    return InputFile(path, use_mmap)
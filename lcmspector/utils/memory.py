@@ -0,0 +1,100 @@
+"""
+Memory budget for loading and in-memory scan data.
+
+Loading several large files in parallel, or holding their decoded scans
+for re-extraction (calculation.session.LoadedRun), can exhaust the memory
+of a laptop. With a budget set (set_memory_budget, or a worker's
+``memory_budget``):
+
+- the loading and processing workers run fewer files at a time, down to
+  one after the other, so that the estimated memory of the files running
+  together stays within the budget;
+- a LoadedRun spills its decoded scan arrays to temporary memory-mapped
+  files (utils.scan_cache.SpilledScans) once they outgrow the budget.
+
+Without a budget (the default) memory use is not limited.
+"""
+
+import logging
+import os
+import re
+
+logger = logging.getLogger(__name__)
+
+# Budget in bytes, None for no limit; see set_memory_budget
+_MEMORY_BUDGET = None
+
+# Peak memory of loading or processing one file, relative to its size on disk:
+# base64 text shrinks when decoded, zlib-compressed arrays grow
+FILE_MEMORY_FACTOR = 1.5
+
+_SIZE_RE = re.compile(r"^\s*(\d+(?:\.\d+)?)\s*([kmgt]?)i?b?\s*$", re.IGNORECASE)
+_SIZE_UNITS = {"": 1, "k": 1024, "m": 1024**2, "g": 1024**3, "t": 1024**4}
+
+
+def parse_memory_size(size) -> int:
+    """
+    Bytes of a memory size given as a number or a string such as "512MB" or "8 GiB".
+
+    Raises
+    ------
+    ValueError
+        If *size* cannot be parsed or is not positive.
+    """
+    if isinstance(size, str):
+        match = _SIZE_RE.match(size)
+        if match is None:
+            raise ValueError(f"Cannot parse memory size '{size}', expected e.g. '512MB' or '8GB'")
+        n_bytes = int(float(match.group(1)) * _SIZE_UNITS[match.group(2).lower()])
+    else:
+        n_bytes = int(size)
+    if n_bytes <= 0:
+        raise ValueError(f"Memory size must be positive, got {size!r}")
+    return n_bytes
+
+
+def set_memory_budget(size=None):
+    """Limit the memory used for scan data (see parse_memory_size), None for no limit."""
+    global _MEMORY_BUDGET
+    _MEMORY_BUDGET = None if size is None else parse_memory_size(size)
+
+
+def memory_budget(size=None):
+    """Budget in bytes: *size* if given, else the set_memory_budget limit, else None."""
+    return parse_memory_size(size) if size is not None else _MEMORY_BUDGET
+
+
+def estimate_file_memory(path: str) -> int:
+    """Estimated peak memory (bytes) of loading or processing one file."""
+    try:
+        return int(os.path.getsize(path) * FILE_MEMORY_FACTOR)
+    except OSError:
+        return 0
+
+
+def files_in_parallel(paths, n_workers: int, budget=None) -> int:
+    """
+    How many of *paths* to run at a time, at most *n_workers*.
+
+    The largest files are assumed to run together, so the estimate holds
+    whatever order they finish in. Always at least 1, even if a single file
+    exceeds the budget.
+    """
+    budget = memory_budget(budget)
+    if budget is None or n_workers <= 1:
+        return n_workers
+    sizes = sorted((estimate_file_memory(path) for path in paths), reverse=True)
+    total = 0
+    n_parallel = 0
+    for size in sizes[:n_workers]:
+        if n_parallel and total + size > budget:
+            break
+        total += size
+        n_parallel += 1
+    n_parallel = max(1, n_parallel)
+    if n_parallel < min(n_workers, len(sizes)):
+        logger.info(
+            f"Running {n_parallel} of {len(sizes)} files at a time to stay within the "
+            f"memory budget of {budget / 1024**3:.1f} GB"
+        )
+    return n_parallel
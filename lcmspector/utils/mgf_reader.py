@@ -0,0 +1,197 @@
+"""
+Lightweight Mascot Generic Format (MGF) reader.
+
+MGF is a plain-text MS/MS peak list: one ``BEGIN IONS`` ... ``END IONS``
+block per spectrum, with ``KEY=value`` header lines followed by
+``m/z intensity [charge]`` peak lines. It carries no survey scans, so every
+spectrum is reported as MS level 2.
+
+Besides the streaming interface shared with utils.mzml_reader, iter_spectra
+exposes the precursor information needed to use an MGF file as a spectral
+library.
+"""
+
+import logging
+import re
+
+import numpy as np
+
+from utils.mzml_reader import validate_polarity
+from utils.scan_filter import scan_matches
+
+logger = logging.getLogger(__name__)
+
+_COMMENT_PREFIXES = ("#", ";", "!", "/")
+_CHARGE_RE = re.compile(r"^\s*(\d+)\s*([+-]?)")
+
+
+def _iter_lines(source):
+    """Yield stripped text lines from a path or an open (binary or text) file."""
+    if isinstance(source, str):
+        with open(source, "r", encoding="utf-8", errors="replace") as handle:
+            for line in handle:
+                yield line.strip()
+        return
+    for line in source:
+        if isinstance(line, bytes):
+            line = line.decode("utf-8", errors="replace")
+        yield line.strip()
+
+
+def _parse_charge(value: str):
+    """Parse an MGF charge such as ``2+``, ``3-`` or ``2+ and 3+`` (first wins)."""
+    match = _CHARGE_RE.match(value or "")
+    if match is None:
+        return None
+    charge = int(match.group(1))
+    return -charge if match.group(2) == "-" else charge
+
+
+def _parse_rt_seconds(value: str):
+    """Return retention time in minutes from an RTINSECONDS value (ranges use the start)."""
+    try:
+        return float(value.split("-")[0] if "-" in value[1:] else value) / 60.0
+    except ValueError:
+        logger.warning(f"Could not parse MGF retention time: {value}")
+        return None
+
+
+def _make_spectrum(params: dict, mz: list, intensity: list) -> dict:
+    pepmass = params.get("pepmass", "").split()
+    rt = _parse_rt_seconds(params["rtinseconds"]) if "rtinseconds" in params else None
+    return {
+        "title": params.get("title"),
+        "precursor_mz": float(pepmass[0]) if pepmass else None,
+        "charge": _parse_charge(params.get("charge")),
+        "rt": rt,
+        "mz": np.array(mz, dtype=np.float64),
+        "intensity": np.array(intensity, dtype=np.float64),
+        "params": params,
+    }
+
+
+def iter_spectra(filepath):
+    """
+    Yield one dict per ``BEGIN IONS`` block of an MGF file.
+
+    Parameters
+    ----------
+    filepath : str or file object
+        Path to the .mgf file, or an open file (used for progress reporting).
+
+    Yields
+    ------
+    dict
+        ``title``, ``precursor_mz``, ``charge`` (signed int or None), ``rt``
+        (minutes or None), ``mz`` and ``intensity`` (float64 arrays, in file
+        order) and ``params`` with all header lines, keys lower-cased.
+    """
+    in_block = False
+    params, mz, intensity = {}, [], []
+    for line in _iter_lines(filepath):
+        if not line or line.startswith(_COMMENT_PREFIXES):
+            continue
+        upper = line.upper()
+        if upper == "BEGIN IONS":
+            in_block = True
+            params, mz, intensity = {}, [], []
+        elif upper == "END IONS":
+            if in_block:
+                yield _make_spectrum(params, mz, intensity)
+            in_block = False
+        elif not in_block:
+            continue  # global parameters are not used
+        elif line[0].isdigit() or line[0] in "+-.":
+            fields = line.split()
+            try:
+                mz.append(float(fields[0]))
+                intensity.append(float(fields[1]) if len(fields) > 1 else 0.0)
+            except ValueError:
+                logger.warning(f"Skipping malformed MGF peak line: {line}")
+        elif "=" in line:
+            key, value = line.split("=", 1)
+            params[key.strip().lower()] = value.strip()
+
+    if in_block:
+        logger.warning("MGF file ended inside a BEGIN IONS block; last spectrum dropped.")
+
+
+def iter_scans(
+    filepath,
+    polarity: str = None,
+    scan_filter: dict = None,
+    with_mobility: bool = False,
+    with_precursor: bool = False,
+):
+    """Yield (scan_time, tic, ms_level, mz_array, intensity_array) per spectrum.
+
+    Same tuple layout as utils.mzml_reader.iter_scans. Spectra without a
+    retention time get 0.0 and the TIC is the summed fragment intensity.
+    The *polarity* filter uses the sign of the precursor charge; spectra
+    without a charge are always kept. A *scan_filter* sees the TITLE as
+    filter string. With *with_mobility*, None is appended as the (unknown)
+    ion mobility, with *with_precursor* the PEPMASS.
+    """
+    validate_polarity(polarity)
+    for spectrum in iter_spectra(filepath):
+        charge = spectrum["charge"]
+        if polarity is not None and charge:
+            if (charge > 0) != (polarity == "positive"):
+                continue
+        if scan_filter is not None and not scan_matches(
+            scan_filter, 2, spectrum["title"], spectrum["precursor_mz"]
+        ):
+            continue
+        intensity = spectrum["intensity"]
+        scan_time = spectrum["rt"] if spectrum["rt"] is not None else 0.0
+        scan = (scan_time, float(intensity.sum()), 2, spectrum["mz"], intensity)
+        if with_mobility:
+            scan += (None,)
+        yield (*scan, spectrum["precursor_mz"]) if with_precursor else scan
+
+
+def iter_ms2_scans(filepath):
+    """Yield (scan_time, precursor_mz, mz_array, intensity_array) per spectrum.
+
+    Same tuple layout as utils.mzml_reader.iter_ms2_scans; spectra without a
+    retention time or precursor m/z are skipped.
+    """
+    for spectrum in iter_spectra(filepath):
+        if spectrum["rt"] is None or spectrum["precursor_mz"] is None:
+            continue
+        yield spectrum["rt"], spectrum["precursor_mz"], spectrum["mz"], spectrum["intensity"]
+
+
+def find_nearest_ms2(
+    filepath: str,
+    precursor_mz: float,
+    target_rt: float,
+    mz_tolerance: float = 0.5,
+    rt_window: float = 2.0,
+):
+    """Find the spectrum nearest to target_rt whose precursor matches precursor_mz.
+
+    See utils.mzml_reader.find_nearest_ms2 for parameter documentation.
+    Spectra without a retention time are ignored.
+
+    Returns
+    -------
+    tuple or None
+        (scan_time, mz_array, intensity_array) of the best match, or None.
+    """
+    best = None
+    best_rt_delta = float("inf")
+
+    for spectrum in iter_spectra(filepath):
+        if spectrum["rt"] is None or spectrum["precursor_mz"] is None:
+            continue
+        rt_delta = abs(spectrum["rt"] - target_rt)
+        if rt_delta > rt_window or rt_delta >= best_rt_delta:
+            continue
+        if abs(spectrum["precursor_mz"] - precursor_mz) > mz_tolerance:
+            continue
+        if len(spectrum["mz"]) > 0:
+            best = (spectrum["rt"], spectrum["mz"], spectrum["intensity"])
+            best_rt_delta = rt_delta
+
+    return best
@@ -4,15 +4,27 @@ Lightweight mzML reader using lxml iterparse.
 Provides streaming access to mzML spectra without the overhead of pyteomics'
 CV term resolution, unit conversion, and full dict construction. Designed for
 performance-critical paths like TIC extraction and XIC building.
+
+Reading the XML and decoding the binary arrays (base64, zlib, numpress) are
+separate steps, so iter_scans can decode on *decode_threads* threads while
+the file is parsed: zlib and numpy release the GIL, which pays off most on
+compressed files.
 """
 
-import base64
+import binascii
+import itertools
 import logging
+import re
 import zlib
+from collections import deque
+from concurrent.futures import ThreadPoolExecutor
 
 import numpy as np
 from lxml.etree import iterparse
 
+from utils import numpress
+from utils.scan_filter import needs_acquisition, scan_matches
+
 logger = logging.getLogger(__name__)
 
 _NS = "http://psi.hupo.org/ms/mzml"
@@ -24,8 +36,12 @@ _SCAN_TAG = f"{{{_NS}}}scan"
 _CHROMATOGRAM_TAG = f"{{{_NS}}}chromatogram"
 _PRECURSOR_LIST_TAG = f"{{{_NS}}}precursorList"
 _PRECURSOR_TAG = f"{{{_NS}}}precursor"
+_PRODUCT_TAG = f"{{{_NS}}}product"
 _SELECTED_ION_LIST_TAG = f"{{{_NS}}}selectedIonList"
+_USERPARAM_TAG = f"{{{_NS}}}userParam"
 _SELECTED_ION_TAG = f"{{{_NS}}}selectedIon"
+_ISOLATION_WINDOW_TAG = f"{{{_NS}}}isolationWindow"
+_ACTIVATION_TAG = f"{{{_NS}}}activation"
 
 # Accession constants
 _MS_LEVEL = "MS:1000511"
@@ -38,25 +54,90 @@ _FLOAT_64 = "MS:1000523"
 _FLOAT_32 = "MS:1000521"
 _ZLIB = "MS:1000574"
 _TIC_CHROMATOGRAM = "MS:1000235"
+_BPC_CHROMATOGRAM = "MS:1000628"
+_SRM_CHROMATOGRAM = "MS:1001473"
+# Chromatograms of the mass spectrometer, as opposed to the UV/DAD, fluorescence, pressure, ... channels
+_MS_CHROMATOGRAMS = {
+    _TIC_CHROMATOGRAM, _BPC_CHROMATOGRAM, _SRM_CHROMATOGRAM, "MS:1000627", "MS:1001472",
+}
+_DETECTOR_CHROMATOGRAMS = {
+    "MS:1000811": "electromagnetic radiation",
+    "MS:1000812": "absorption",
+    "MS:1000813": "emission",
+}
+# Per-peak ion mobility arrays (TIMS 1/K0, drift time, ...)
+_MOBILITY_ARRAYS = {"MS:1002816", "MS:1002893", "MS:1003006", "MS:1003007", "MS:1003008"}
+# Per-scan mobility values: FAIMS compensation voltage, 1/K0, drift time
+_SCAN_MOBILITY = {"MS:1001581", "MS:1002815", "MS:1002476"}
+_SECOND_UNIT = "UO:0000010"
 _SELECTED_ION_MZ = "MS:1000744"
+_POSITIVE_SCAN = "MS:1000130"
+_NEGATIVE_SCAN = "MS:1000129"
+_FILTER_STRING = "MS:1000512"
+_ISOLATION_TARGET_MZ = "MS:1000827"
+_ISOLATION_LOWER_OFFSET = "MS:1000828"
+_ISOLATION_UPPER_OFFSET = "MS:1000829"
+_ION_INJECTION_TIME = "MS:1000927"
+_COLLISION_ENERGY = "MS:1000045"
+_NORMALIZED_COLLISION_ENERGY = "MS:1000138"
+_MASS_RESOLUTION = "MS:1000011"
+
+# Scan polarities accepted by the iter_scans() readers
+POLARITIES = ("positive", "negative")
+
+# Spectra decoded ahead of the one being yielded, per decoding thread
+_DECODE_QUEUE_PER_THREAD = 4
+
+# MS-Numpress compression accessions -> (numpress method, zlib applied on top)
+_NUMPRESS = {
+    "MS:1002312": (numpress.NUMPRESS_LINEAR, False),
+    "MS:1002313": (numpress.NUMPRESS_PIC, False),
+    "MS:1002314": (numpress.NUMPRESS_SLOF, False),
+    "MS:1002746": (numpress.NUMPRESS_LINEAR, True),
+    "MS:1002747": (numpress.NUMPRESS_PIC, True),
+    "MS:1002748": (numpress.NUMPRESS_SLOF, True),
+}
+
+
+def release_element(elem):
+    """Clear a fully processed element and drop the siblings parsed before it.
+
+    ``elem.clear()`` alone empties the element but leaves it attached to its
+    parent, so a multi-GB file still accumulates one placeholder per spectrum.
+    """
+    elem.clear()
+    parent = elem.getparent()
+    if parent is not None:
+        while elem.getprevious() is not None:
+            del parent[0]
 
 
-def _decode_binary(raw_base64: str, is_zlib: bool, dtype: np.dtype) -> np.ndarray:
-    """Decode base64 (+ optional zlib) binary data to numpy array."""
-    decoded = base64.b64decode(raw_base64)
+def _decode_binary(
+    raw_base64: str, is_zlib: bool, dtype: np.dtype, numpress_method: str = None
+) -> np.ndarray:
+    """Decode base64 (+ optional zlib, + optional numpress) binary data to numpy array.
+
+    Whitespace around *raw_base64* is skipped by binascii, without the copy
+    a strip() or an ASCII encode would make of every array.
+    """
+    decoded = binascii.a2b_base64(raw_base64)
     if is_zlib:
         decoded = zlib.decompress(decoded)
+    if numpress_method is not None:
+        return numpress.decode(decoded, numpress_method).astype(dtype, copy=False)
     return np.frombuffer(decoded, dtype=dtype)
 
 
-def _parse_binary_arrays(elem):
-    """Extract binary data arrays from an element (spectrum or chromatogram).
+def _binary_array_specs(elem):
+    """Read the binary data arrays of an element without decoding them.
 
-    Returns a dict mapping array type ('mz', 'intensity', 'time') to numpy arrays.
+    Returns ``(array_type, base64_text, is_zlib, dtype, numpress_method)``
+    tuples for _decode_arrays; they stay valid once the element is released.
     """
-    arrays = {}
+    specs = []
     for bda in elem.iter(_BINARY_DATA_ARRAY_TAG):
         is_zlib = False
+        numpress_method = None
         dtype = np.float64
         array_type = None
 
@@ -64,6 +145,9 @@ def _parse_binary_arrays(elem):
             acc = cv.get("accession")
             if acc == _ZLIB:
                 is_zlib = True
+            elif acc in _NUMPRESS:
+                numpress_method, numpress_zlib = _NUMPRESS[acc]
+                is_zlib = is_zlib or numpress_zlib
             elif acc == _FLOAT_64:
                 dtype = np.float64
             elif acc == _FLOAT_32:
@@ -74,14 +158,307 @@ def _parse_binary_arrays(elem):
                 array_type = "intensity"
             elif acc == _TIME_ARRAY:
                 array_type = "time"
+            elif acc in _MOBILITY_ARRAYS:
+                array_type = "mobility"
 
         binary_elem = bda.find(_BINARY_TAG)
         if binary_elem is not None and binary_elem.text and array_type:
-            arrays[array_type] = _decode_binary(
-                binary_elem.text.strip(), is_zlib, dtype
-            )
+            specs.append((array_type, binary_elem.text, is_zlib, dtype, numpress_method))
+
+    return specs
+
+
+def _decode_arrays(specs) -> dict:
+    """Decode _binary_array_specs to a dict of array type -> numpy array."""
+    return {
+        array_type: _decode_binary(text, is_zlib, dtype, numpress_method)
+        for array_type, text, is_zlib, dtype, numpress_method in specs
+    }
+
+
+def _parse_binary_arrays(elem):
+    """Extract binary data arrays from an element (spectrum or chromatogram).
+
+    Returns a dict mapping array type ('mz', 'intensity', 'time') to numpy arrays.
+    """
+    return _decode_arrays(_binary_array_specs(elem))
+
+
+def _decode_in_order(records, decode_threads: int):
+    """Yield ``(record, arrays)`` for records ending in _binary_array_specs, in order.
+
+    The arrays are decoded on *decode_threads* threads, a bounded number of
+    records ahead of the one yielded.
+    """
+    executor = ThreadPoolExecutor(decode_threads)
+    pending = deque()
+    try:
+        for record in records:
+            pending.append((record, executor.submit(_decode_arrays, record[-1])))
+            if len(pending) > _DECODE_QUEUE_PER_THREAD * decode_threads:
+                record, future = pending.popleft()
+                yield record, future.result()
+        while pending:
+            record, future = pending.popleft()
+            yield record, future.result()
+    finally:
+        executor.shutdown(wait=True, cancel_futures=True)
+
+
+def validate_polarity(polarity):
+    """Return *polarity* if it is None or one of POLARITIES, else raise ValueError."""
+    if polarity is not None and polarity not in POLARITIES:
+        raise ValueError(f"Unknown polarity '{polarity}', expected one of {POLARITIES}")
+    return polarity
+
+
+def _precursor_mz(spectrum_elem):
+    """Selected ion m/z of the first precursor, else its isolation window target."""
+    isolation_target = None
+    for precursor in spectrum_elem.iter(_PRECURSOR_TAG):
+        for cv in precursor.iter(_CVPARAM_TAG):
+            acc = cv.get("accession")
+            if acc == _SELECTED_ION_MZ:
+                return float(cv.get("value"))
+            if acc == _ISOLATION_TARGET_MZ and isolation_target is None:
+                isolation_target = float(cv.get("value"))
+        break
+    return isolation_target
+
+
+def _isolation_window(spectrum_elem):
+    """``(lower, upper)`` m/z of the first precursor's isolation window, or None."""
+    for precursor in spectrum_elem.iter(_PRECURSOR_TAG):
+        window = precursor.find(_ISOLATION_WINDOW_TAG)
+        if window is None:
+            return None
+        values = {
+            cv.get("accession"): float(cv.get("value"))
+            for cv in window.iterchildren(_CVPARAM_TAG)
+            if cv.get("value")
+        }
+        target = values.get(_ISOLATION_TARGET_MZ)
+        if target is None:
+            return None
+        return (
+            target - values.get(_ISOLATION_LOWER_OFFSET, 0.0),
+            target + values.get(_ISOLATION_UPPER_OFFSET, 0.0),
+        )
+    return None
+
+
+def _param_float(value):
+    try:
+        return float(value)
+    except (TypeError, ValueError):
+        return None
+
+
+def _acquisition(spectrum_elem) -> dict:
+    """
+    Ion injection time (ms), collision energies and resolution of a spectrum,
+    None where not recorded.
+
+    Converters write these inconsistently: as cvParams of the <scan> (injection
+    time) and of the precursor's <activation> (collision energies), or as
+    Thermo trailer userParams such as ``[Thermo Trailer Extra]FT Resolution:``.
+    """
+    acquisition = {
+        "injection_time": None,
+        "collision_energy": None,
+        "normalized_collision_energy": None,
+        "resolution": None,
+    }
+    params = []
+    for scan_elem in spectrum_elem.iter(_SCAN_TAG):
+        params += scan_elem.iterchildren(_CVPARAM_TAG, _USERPARAM_TAG)
+        break
+    for activation in spectrum_elem.iter(_ACTIVATION_TAG):
+        params += activation.iterchildren(_CVPARAM_TAG, _USERPARAM_TAG)
+        break
+    params += spectrum_elem.iterchildren(_CVPARAM_TAG, _USERPARAM_TAG)
+    for param in params:
+        acc = param.get("accession")
+        name = (param.get("name") or "").lower()
+        if acc == _ION_INJECTION_TIME or (acc is None and "injection time" in name):
+            key = "injection_time"
+        elif acc == _NORMALIZED_COLLISION_ENERGY:
+            key = "normalized_collision_energy"
+        elif acc == _COLLISION_ENERGY:
+            key = "collision_energy"
+        elif acc == _MASS_RESOLUTION or (acc is None and "resolution" in name):
+            key = "resolution"
+        else:
+            continue
+        if acquisition[key] is None:
+            acquisition[key] = _param_float(param.get("value"))
+    return acquisition
+
+
+def _chromatogram_kind(elem):
+    """Return "tic", "bpc" or None for a <chromatogram> element."""
+    for cv in elem.iterchildren(_CVPARAM_TAG):
+        acc = cv.get("accession")
+        if acc == _TIC_CHROMATOGRAM:
+            return "tic"
+        if acc == _BPC_CHROMATOGRAM:
+            return "bpc"
+    # Also check by id attribute
+    return {"TIC": "tic", "BPC": "bpc"}.get(elem.get("id"))
+
+
+def _isolation_target(elem):
+    """Isolation window target m/z of a <precursor> or <product> element, or None."""
+    if elem is None:
+        return None
+    for cv in elem.iter(_CVPARAM_TAG):
+        if cv.get("accession") == _ISOLATION_TARGET_MZ:
+            return float(cv.get("value"))
+    return None
+
+
+def _time_in_seconds(elem) -> bool:
+    """Whether the time array of a chromatogram is given in seconds."""
+    for bda in elem.iter(_BINARY_DATA_ARRAY_TAG):
+        for cv in bda.iterchildren(_CVPARAM_TAG):
+            if cv.get("accession") == _TIME_ARRAY:
+                return cv.get("unitAccession") == _SECOND_UNIT
+    return False
+
+
+def iter_srm_chromatograms(filepath: str):
+    """
+    Yield the SRM/MRM chromatograms of an mzML file, as written by triple quads.
+
+    A chromatogram counts as SRM if it carries the "selected reaction
+    monitoring chromatogram" term or both a precursor and a product
+    isolation target.
+
+    Yields
+    ------
+    dict
+        ``id``, ``q1`` and ``q3`` (precursor and product m/z), ``time``
+        (minutes) and ``intensity`` (float64 arrays).
+    """
+    for event, elem in iterparse(filepath, tag=_CHROMATOGRAM_TAG):
+        is_srm = any(
+            cv.get("accession") == _SRM_CHROMATOGRAM for cv in elem.iterchildren(_CVPARAM_TAG)
+        )
+        q1 = _isolation_target(elem.find(_PRECURSOR_TAG))
+        q3 = _isolation_target(elem.find(_PRODUCT_TAG))
+        if q1 is None or q3 is None:
+            if is_srm:
+                logger.warning(f"SRM chromatogram {elem.get('id')} without Q1/Q3, skipped")
+            release_element(elem)
+            continue
 
-    return arrays
+        arrays = _parse_binary_arrays(elem)
+        seconds = _time_in_seconds(elem)
+        chrom_id = elem.get("id")
+        release_element(elem)
+        if "time" not in arrays or "intensity" not in arrays:
+            continue
+        times = arrays["time"].astype(np.float64)
+        yield {
+            "id": chrom_id,
+            "q1": q1,
+            "q3": q3,
+            "time": times / 60.0 if seconds else times,
+            "intensity": arrays["intensity"].astype(np.float64),
+        }
+
+
+def _wavelength(elem):
+    """Detection wavelength (nm) of a detector chromatogram, from a "wavelength" param or its id."""
+    for param in itertools.chain(elem.iter(_CVPARAM_TAG), elem.iter(_USERPARAM_TAG)):
+        if "wavelength" in (param.get("name") or "").lower():
+            try:
+                return float(param.get("value"))
+            except (TypeError, ValueError):
+                pass
+    chrom_id = elem.get("id") or ""
+    # Agilent DAD ids look like "DAD1 A, Sig=254,4 Ref=360,100", others like "UV 254nm"
+    match = re.search(r"Sig=(\d+(?:\.\d+)?)", chrom_id) or re.search(r"(\d+(?:\.\d+)?)\s*nm\b", chrom_id)
+    return float(match.group(1)) if match else None
+
+
+def _intensity_unit(elem):
+    """Unit name of the intensity array of a chromatogram, or None."""
+    for bda in elem.iter(_BINARY_DATA_ARRAY_TAG):
+        for cv in bda.iterchildren(_CVPARAM_TAG):
+            if cv.get("accession") == _INTENSITY_ARRAY:
+                return cv.get("unitName") or None
+    return None
+
+
+def iter_detector_chromatograms(filepath: str):
+    """
+    Yield the chromatograms of an mzML file that do not come from the mass
+    spectrometer, such as UV/DAD, fluorescence or pressure traces.
+
+    Every chromatogram without an MS chromatogram term (TIC, BPC, SIC, SIM,
+    SRM), a precursor or a "TIC"/"BPC" id counts.
+
+    Yields
+    ------
+    dict
+        ``id``, ``kind`` ("absorption", "emission", "electromagnetic
+        radiation" or "other"), ``wavelength`` (nm, None if unknown),
+        ``unit`` (of the intensities, e.g. "absorbance unit", or None),
+        ``time`` (minutes) and ``intensity`` (float64 arrays).
+    """
+    for event, elem in iterparse(filepath, tag=_CHROMATOGRAM_TAG):
+        accessions = {cv.get("accession") for cv in elem.iterchildren(_CVPARAM_TAG)}
+        if (
+            accessions & _MS_CHROMATOGRAMS
+            or elem.find(_PRECURSOR_TAG) is not None
+            or elem.get("id") in ("TIC", "BPC")
+        ):
+            release_element(elem)
+            continue
+        kind = next(
+            (name for accession, name in _DETECTOR_CHROMATOGRAMS.items() if accession in accessions),
+            "other",
+        )
+        wavelength = _wavelength(elem)
+        unit = _intensity_unit(elem)
+        arrays = _parse_binary_arrays(elem)
+        seconds = _time_in_seconds(elem)
+        chrom_id = elem.get("id")
+        release_element(elem)
+        if "time" not in arrays or "intensity" not in arrays:
+            continue
+        times = arrays["time"].astype(np.float64)
+        yield {
+            "id": chrom_id,
+            "kind": kind,
+            "wavelength": wavelength,
+            "unit": unit,
+            "time": times / 60.0 if seconds else times,
+            "intensity": arrays["intensity"].astype(np.float64),
+        }
+
+
+def extract_summary_chromatograms(filepath: str) -> dict:
+    """Extract the pre-computed TIC and base peak chromatograms, if present.
+
+    Returns a dict with optional keys "tic" and "bpc", each mapping to a
+    (times, intensities) tuple of float64 arrays.
+    """
+    found = {}
+    for event, elem in iterparse(filepath, tag=_CHROMATOGRAM_TAG):
+        kind = _chromatogram_kind(elem)
+        if kind is not None and kind not in found:
+            arrays = _parse_binary_arrays(elem)
+            if "time" in arrays and "intensity" in arrays:
+                found[kind] = (
+                    arrays["time"].astype(np.float64),
+                    arrays["intensity"].astype(np.float64),
+                )
+        release_element(elem)
+        if len(found) == 2:
+            break
+    return found
 
 
 def extract_tic_chromatogram(filepath: str):
@@ -104,7 +481,7 @@ def extract_tic_chromatogram(filepath: str):
 
         if is_tic:
             arrays = _parse_binary_arrays(elem)
-            elem.clear()
+            release_element(elem)
             if "time" in arrays and "intensity" in arrays:
                 logger.info(
                     "TIC extracted from chromatogram element "
@@ -115,46 +492,274 @@ def extract_tic_chromatogram(filepath: str):
                     arrays["intensity"].astype(np.float64),
                 )
 
-        elem.clear()
+        release_element(elem)
 
     return None
 
 
-def iter_scans(filepath: str):
-    """Yield (scan_time, tic, ms_level, mz_array, intensity_array) per spectrum.
+def _iter_scan_records(filepath, polarity, scan_filter, with_mobility, with_precursor=False):
+    """Yield (scan_time, tic, ms_level, scan_mobility, precursor_mz, specs) per spectrum
+    iter_scans keeps.
 
-    Uses lxml iterparse for streaming — constant memory regardless of file size.
-    Skips CV term resolution, unit conversion, and full dict construction.
+    Only the XML is read, *specs* are the spectrum's still encoded
+    _binary_array_specs. The precursor m/z is only looked up for
+    *with_precursor* or a *scan_filter*, None otherwise.
     """
     for event, spectrum_elem in iterparse(filepath, tag=_SPECTRUM_TAG):
         scan_time = 0.0
         tic = 0.0
         ms_level = 1
+        scan_polarity = None
 
-        # Extract spectrum-level cvParams (ms level, TIC)
+        # Extract spectrum-level cvParams (ms level, TIC, polarity)
         for cv in spectrum_elem.iterchildren(_CVPARAM_TAG):
             acc = cv.get("accession")
             if acc == _MS_LEVEL:
                 ms_level = int(cv.get("value"))
             elif acc == _TIC_ACC:
                 tic = float(cv.get("value"))
+            elif acc == _POSITIVE_SCAN:
+                scan_polarity = "positive"
+            elif acc == _NEGATIVE_SCAN:
+                scan_polarity = "negative"
 
-        # Extract scan start time from <scan> element
+        if polarity is not None and scan_polarity not in (None, polarity):
+            release_element(spectrum_elem)
+            continue
+
+        # Extract scan start time (filter string, mobility) from <scan> element
+        filter_string = None
+        scan_mobility = None
         for scan_elem in spectrum_elem.iter(_SCAN_TAG):
             for cv in scan_elem.iterchildren(_CVPARAM_TAG):
-                if cv.get("accession") == _SCAN_START_TIME:
+                acc = cv.get("accession")
+                if acc == _SCAN_START_TIME:
                     scan_time = float(cv.get("value"))
+                elif acc == _FILTER_STRING:
+                    filter_string = cv.get("value")
+                elif acc in _SCAN_MOBILITY:
+                    scan_mobility = float(cv.get("value"))
             break  # Only need first scan element
+        if with_mobility and scan_mobility is None:
+            # FAIMS CV is often a spectrum-level term
+            for cv in spectrum_elem.iterchildren(_CVPARAM_TAG):
+                if cv.get("accession") in _SCAN_MOBILITY:
+                    scan_mobility = float(cv.get("value"))
+                    break
+
+        precursor_mz = (
+            _precursor_mz(spectrum_elem) if with_precursor or scan_filter is not None else None
+        )
+        if scan_filter is not None and not scan_matches(
+            scan_filter,
+            ms_level,
+            filter_string,
+            precursor_mz,
+            _acquisition(spectrum_elem) if needs_acquisition(scan_filter) else None,
+        ):
+            release_element(spectrum_elem)
+            continue
+
+        specs = _binary_array_specs(spectrum_elem)
+        release_element(spectrum_elem)  # Free memory
+        yield scan_time, tic, ms_level, scan_mobility, precursor_mz, specs
+
+
+def iter_scans(
+    filepath: str,
+    polarity: str = None,
+    scan_filter: dict = None,
+    with_mobility: bool = False,
+    decode_threads: int = None,
+    with_precursor: bool = False,
+):
+    """Yield (scan_time, tic, ms_level, mz_array, intensity_array) per spectrum.
+
+    Uses lxml iterparse for streaming — constant memory regardless of file size.
+    Skips CV term resolution, unit conversion, and full dict construction.
+
+    With *polarity* ("positive" or "negative"), spectra acquired in the other
+    polarity are skipped before decoding; spectra without a polarity term are
+    always kept. Likewise for spectra failing a *scan_filter* (validated, see
+    utils.scan_filter), which is matched against the scan's filter string
+    and precursor m/z.
+
+    With *with_mobility*, a sixth item holds the ion mobility of every peak:
+    the spectrum's mobility array (TIMS), the scan's single mobility value
+    such as a FAIMS compensation voltage repeated per peak, or None.
+
+    With *with_precursor*, a last item holds the precursor m/z (the selected
+    ion, else the isolation window target), None for MS1 spectra.
+
+    With *decode_threads* > 1, the binary arrays are decoded on that many
+    threads while the XML is parsed; the spectra are yielded in the same
+    order either way.
+    """
+    validate_polarity(polarity)
+    records = _iter_scan_records(filepath, polarity, scan_filter, with_mobility, with_precursor)
+    if decode_threads and decode_threads > 1:
+        decoded = _decode_in_order(records, decode_threads)
+    else:
+        decoded = ((record, _decode_arrays(record[-1])) for record in records)
+    try:
+        for (scan_time, tic, ms_level, scan_mobility, precursor_mz, _), arrays in decoded:
+            mz_array = arrays.get("mz")
+            intensity_array = arrays.get("intensity")
+
+            if mz_array is None or intensity_array is None:
+                continue
+            if not with_mobility and not with_precursor:
+                yield scan_time, tic, ms_level, mz_array, intensity_array
+                continue
+            scan = (scan_time, tic, ms_level, mz_array, intensity_array)
+            if with_mobility:
+                mobility = arrays.get("mobility")
+                if mobility is None and scan_mobility is not None:
+                    mobility = np.full(len(mz_array), scan_mobility)
+                scan += (mobility,)
+            yield (*scan, precursor_mz) if with_precursor else scan
+    finally:
+        # Stops the decoding threads and releases the file when stopping early
+        decoded.close()
+        records.close()
+
+
+def iter_scan_metadata(filepath: str):
+    """Yield the acquisition metadata of every spectrum as a dict, in file order.
+
+    Keys: ``index``, ``id`` (the nativeID), ``scan_time``, ``ms_level``,
+    ``polarity``, ``filter_string``, ``precursor_mz``, ``injection_time``
+    (ms), ``collision_energy`` (eV), ``normalized_collision_energy`` (%) and
+    ``resolution``, None where the file does not record them. No binary
+    array is decoded, so this is a quick pass even over large files.
+    """
+    for index, (event, spectrum_elem) in enumerate(iterparse(filepath, tag=_SPECTRUM_TAG)):
+        metadata = {
+            "index": int(spectrum_elem.get("index", index)),
+            "id": spectrum_elem.get("id"),
+            "scan_time": 0.0,
+            "ms_level": 1,
+            "polarity": None,
+            "filter_string": None,
+        }
+        for cv in spectrum_elem.iterchildren(_CVPARAM_TAG):
+            acc = cv.get("accession")
+            if acc == _MS_LEVEL:
+                metadata["ms_level"] = int(cv.get("value"))
+            elif acc == _POSITIVE_SCAN:
+                metadata["polarity"] = "positive"
+            elif acc == _NEGATIVE_SCAN:
+                metadata["polarity"] = "negative"
+        for scan_elem in spectrum_elem.iter(_SCAN_TAG):
+            for cv in scan_elem.iterchildren(_CVPARAM_TAG):
+                acc = cv.get("accession")
+                if acc == _SCAN_START_TIME:
+                    metadata["scan_time"] = float(cv.get("value"))
+                elif acc == _FILTER_STRING:
+                    metadata["filter_string"] = cv.get("value")
+            break
+        metadata["precursor_mz"] = _precursor_mz(spectrum_elem)
+        metadata.update(_acquisition(spectrum_elem))
+        release_element(spectrum_elem)
+        yield metadata
+
+
+def _monoisotopic_mz(scan_elem):
+    """Monoisotopic precursor m/z the instrument determined (Thermo trailer), or None."""
+    for param in scan_elem.iterchildren(_USERPARAM_TAG):
+        if "Monoisotopic M/Z" in (param.get("name") or ""):
+            value = float(param.get("value") or 0)
+            return value if value > 0 else None
+    return None
 
-        # Extract binary arrays
-        arrays = _parse_binary_arrays(spectrum_elem)
-        spectrum_elem.clear()  # Free memory
+
+def iter_ms2_scans(filepath: str, with_precursor_info: bool = False):
+    """Yield (scan_time, precursor_mz, mz_array, intensity_array) per MS2 spectrum.
+
+    MS1 spectra are skipped before their binary arrays are decoded, as are
+    MS2 spectra without a selected ion m/z.
+
+    With *with_precursor_info*, a fifth item holds ``selected_mz``,
+    ``monoisotopic_mz`` (None unless the instrument recorded it) and
+    ``isolation_window`` (``(lower, upper)`` m/z or None).
+    """
+    for event, spectrum_elem in iterparse(filepath, tag=_SPECTRUM_TAG):
+        ms_level = 1
+        for cv in spectrum_elem.iterchildren(_CVPARAM_TAG):
+            if cv.get("accession") == _MS_LEVEL:
+                ms_level = int(cv.get("value"))
+                break
+        if ms_level != 2:
+            release_element(spectrum_elem)
+            continue
+
+        scan_time = 0.0
+        monoisotopic_mz = None
+        for scan_elem in spectrum_elem.iter(_SCAN_TAG):
+            for cv in scan_elem.iterchildren(_CVPARAM_TAG):
+                if cv.get("accession") == _SCAN_START_TIME:
+                    scan_time = float(cv.get("value"))
+            if with_precursor_info:
+                monoisotopic_mz = _monoisotopic_mz(scan_elem)
+            break
+
+        precursor_mz = None
+        for selected_ion in spectrum_elem.iter(_SELECTED_ION_TAG):
+            for cv in selected_ion.iterchildren(_CVPARAM_TAG):
+                if cv.get("accession") == _SELECTED_ION_MZ:
+                    precursor_mz = float(cv.get("value"))
+                    break
+            break
+
+        arrays = _parse_binary_arrays(spectrum_elem) if precursor_mz is not None else {}
+        window = _isolation_window(spectrum_elem) if with_precursor_info else None
+        release_element(spectrum_elem)
 
         mz_array = arrays.get("mz")
         intensity_array = arrays.get("intensity")
+        if mz_array is None or intensity_array is None:
+            continue
+        if not with_precursor_info:
+            yield scan_time, precursor_mz, mz_array, intensity_array
+            continue
+        info = {
+            "selected_mz": precursor_mz,
+            "monoisotopic_mz": monoisotopic_mz,
+            "isolation_window": window,
+        }
+        yield scan_time, precursor_mz, mz_array, intensity_array, info
+
 
+def iter_dia_scans(filepath: str):
+    """Yield (scan_time, ms_level, isolation_window, mz_array, intensity_array) per spectrum.
+
+    For data-independent acquisition (SWATH, AIF): *isolation_window* is the
+    ``(lower, upper)`` m/z range of an MS2 spectrum's precursor isolation
+    window, None for MS1 spectra and MS2 spectra without one (all-ion
+    fragmentation with no window recorded).
+    """
+    for event, spectrum_elem in iterparse(filepath, tag=_SPECTRUM_TAG):
+        ms_level = 1
+        for cv in spectrum_elem.iterchildren(_CVPARAM_TAG):
+            if cv.get("accession") == _MS_LEVEL:
+                ms_level = int(cv.get("value"))
+                break
+
+        scan_time = 0.0
+        for scan_elem in spectrum_elem.iter(_SCAN_TAG):
+            for cv in scan_elem.iterchildren(_CVPARAM_TAG):
+                if cv.get("accession") == _SCAN_START_TIME:
+                    scan_time = float(cv.get("value"))
+            break
+
+        window = _isolation_window(spectrum_elem) if ms_level > 1 else None
+        arrays = _parse_binary_arrays(spectrum_elem)
+        release_element(spectrum_elem)
+
+        mz_array = arrays.get("mz")
+        intensity_array = arrays.get("intensity")
         if mz_array is not None and intensity_array is not None:
-            yield scan_time, tic, ms_level, mz_array, intensity_array
+            yield scan_time, ms_level, window, mz_array, intensity_array
 
 
 def find_nearest_ms2(
@@ -203,7 +808,7 @@ def find_nearest_ms2(
 
         # Skip MS1 scans immediately
         if ms_level != 2:
-            spectrum_elem.clear()
+            release_element(spectrum_elem)
             continue
 
         # Extract scan start time
@@ -216,7 +821,7 @@ def find_nearest_ms2(
         # Skip if outside RT window
         rt_delta = abs(scan_time - target_rt)
         if rt_delta > rt_window:
-            spectrum_elem.clear()
+            release_element(spectrum_elem)
             continue
 
         # Parse precursor m/z from <precursorList>
@@ -234,7 +839,7 @@ def find_nearest_ms2(
             break
 
         if not precursor_match:
-            spectrum_elem.clear()
+            release_element(spectrum_elem)
             continue
 
         # Only decode binary for matching candidates
@@ -246,6 +851,6 @@ def find_nearest_ms2(
                 best = (scan_time, mz_array, intensity_array)
                 best_rt_delta = rt_delta
 
-        spectrum_elem.clear()
+        release_element(spectrum_elem)
 
     return best
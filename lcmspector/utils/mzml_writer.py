@@ -0,0 +1,308 @@
+"""
+mzML writer for processed spectra and extracted chromatograms.
+
+Spectra (e.g. averaged or centroided with calculation.centroiding) and XICs
+are written as a standalone mzML 1.1 file, so that they can be opened in
+Skyline, MZmine or read back with utils.mzml_reader:
+
+    write_mzml(
+        "processed.mzML",
+        spectra=[{"scan_time": 2.1, "mz": mz, "intensity": intensity, "centroided": True}],
+        chromatograms=xic_chromatograms(measurement.xics),
+    )
+
+Times are written in minutes, binary arrays as 64-bit floats, zlib-
+compressed by default. The file is not indexed (no indexedmzML wrapper).
+The manifest of the measurements the data is from goes into a
+``.manifest.json`` sidecar, see utils.manifest.
+"""
+
+import base64
+import logging
+import zlib
+from pathlib import Path
+from typing import Dict, Iterable, List
+
+import numpy as np
+from lxml import etree
+
+from utils.manifest import batch_manifest, software_version, write_manifest
+
+logger = logging.getLogger(__name__)
+
+_NS = "http://psi.hupo.org/ms/mzml"
+_DATA_PROCESSING = "lcmspector_processing"
+_INSTRUMENT = "IC1"
+_SOFTWARE = "lcmspector"
+
+COMPRESSIONS = ("zlib", None)
+
+# Chromatogram kinds -> (accession, name)
+CHROMATOGRAM_KINDS = {
+    "tic": ("MS:1000235", "total ion current chromatogram"),
+    "bpc": ("MS:1000628", "basepeak chromatogram"),
+    "sim": ("MS:1001472", "selected ion monitoring chromatogram"),
+    "srm": ("MS:1001473", "selected reaction monitoring chromatogram"),
+}
+_POLARITIES = {
+    "positive": ("MS:1000130", "positive scan"),
+    "negative": ("MS:1000129", "negative scan"),
+}
+_MINUTE = {"unitCvRef": "UO", "unitAccession": "UO:0000031", "unitName": "minute"}
+_MZ_UNIT = {"unitCvRef": "MS", "unitAccession": "MS:1000040", "unitName": "m/z"}
+_COUNTS = {"unitCvRef": "MS", "unitAccession": "MS:1000131", "unitName": "number of detector counts"}
+
+
+def _element(parent, tag: str, **attrib):
+    return etree.SubElement(parent, f"{{{_NS}}}{tag}", {k: str(v) for k, v in attrib.items()})
+
+
+def _cv(parent, accession: str, name: str, value="", cv_ref: str = "MS", **unit):
+    return _element(parent, "cvParam", cvRef=cv_ref, accession=accession, name=name, value=value, **unit)
+
+
+def _binary_array(parent, values, accession: str, name: str, unit: Dict, compression):
+    data = np.ascontiguousarray(values, dtype="<f8").tobytes()
+    if compression == "zlib":
+        data = zlib.compress(data)
+    encoded = base64.b64encode(data).decode("ascii")
+    array = _element(parent, "binaryDataArray", encodedLength=len(encoded))
+    _cv(array, "MS:1000523", "64-bit float")
+    if compression == "zlib":
+        _cv(array, "MS:1000574", "zlib compression")
+    else:
+        _cv(array, "MS:1000576", "no compression")
+    _cv(array, accession, name, **unit)
+    _element(array, "binary").text = encoded
+
+
+def _isolation_window(parent, mz: float):
+    window = _element(parent, "isolationWindow")
+    _cv(window, "MS:1000827", "isolation window target m/z", mz, **_MZ_UNIT)
+
+
+def _chromatogram_kind(chromatogram: Dict) -> str:
+    kind = chromatogram.get("kind")
+    if kind is None:
+        if chromatogram.get("product_mz") is not None:
+            return "srm"
+        return "sim" if chromatogram.get("precursor_mz") is not None else "tic"
+    if kind not in CHROMATOGRAM_KINDS:
+        raise ValueError(f"Unknown chromatogram kind '{kind}', expected one of {tuple(CHROMATOGRAM_KINDS)}")
+    return kind
+
+
+def _write_spectrum(parent, index: int, spectrum: Dict, compression):
+    mz = np.asarray(spectrum["mz"], dtype=np.float64)
+    intensity = np.asarray(spectrum["intensity"], dtype=np.float64)
+    if len(mz) != len(intensity):
+        raise ValueError(f"Spectrum {index} has {len(mz)} m/z values for {len(intensity)} intensities")
+    ms_level = int(spectrum.get("ms_level", 1))
+    element = _element(
+        parent,
+        "spectrum",
+        index=index,
+        id=spectrum.get("id") or f"index={index}",
+        defaultArrayLength=len(mz),
+    )
+    _cv(element, "MS:1000511", "ms level", ms_level)
+    if ms_level == 1:
+        _cv(element, "MS:1000579", "MS1 spectrum")
+    else:
+        _cv(element, "MS:1000580", "MSn spectrum")
+    if spectrum.get("centroided", False):
+        _cv(element, "MS:1000127", "centroid spectrum")
+    else:
+        _cv(element, "MS:1000128", "profile spectrum")
+    if spectrum.get("polarity") is not None:
+        _cv(element, *_POLARITIES[spectrum["polarity"]])
+    _cv(element, "MS:1000285", "total ion current", float(intensity.sum()))
+    if len(mz):
+        _cv(element, "MS:1000528", "lowest observed m/z", float(mz.min()), **_MZ_UNIT)
+        _cv(element, "MS:1000527", "highest observed m/z", float(mz.max()), **_MZ_UNIT)
+
+    scan_list = _element(element, "scanList", count=1)
+    _cv(scan_list, "MS:1000795", "no combination")
+    scan = _element(scan_list, "scan")
+    _cv(scan, "MS:1000016", "scan start time", float(spectrum.get("scan_time", 0.0)), **_MINUTE)
+
+    if spectrum.get("precursor_mz") is not None:
+        precursor = _element(_element(element, "precursorList", count=1), "precursor")
+        selected = _element(_element(precursor, "selectedIonList", count=1), "selectedIon")
+        _cv(selected, "MS:1000744", "selected ion m/z", float(spectrum["precursor_mz"]), **_MZ_UNIT)
+        _element(precursor, "activation")
+
+    arrays = _element(element, "binaryDataArrayList", count=2)
+    _binary_array(arrays, mz, "MS:1000514", "m/z array", _MZ_UNIT, compression)
+    _binary_array(arrays, intensity, "MS:1000515", "intensity array", _COUNTS, compression)
+
+
+def _write_chromatogram(parent, index: int, chromatogram: Dict, compression):
+    time = np.asarray(chromatogram["time"], dtype=np.float64)
+    intensity = np.asarray(chromatogram["intensity"], dtype=np.float64)
+    if len(time) != len(intensity):
+        raise ValueError(
+            f"Chromatogram {index} has {len(time)} time points for {len(intensity)} intensities"
+        )
+    kind = _chromatogram_kind(chromatogram)
+    element = _element(
+        parent,
+        "chromatogram",
+        index=index,
+        id=chromatogram.get("id") or f"{kind} {index}",
+        defaultArrayLength=len(time),
+    )
+    _cv(element, *CHROMATOGRAM_KINDS[kind])
+    if chromatogram.get("precursor_mz") is not None:
+        precursor = _element(element, "precursor")
+        _isolation_window(precursor, float(chromatogram["precursor_mz"]))
+        _element(precursor, "activation")
+    if chromatogram.get("product_mz") is not None:
+        _isolation_window(_element(element, "product"), float(chromatogram["product_mz"]))
+
+    arrays = _element(element, "binaryDataArrayList", count=2)
+    _binary_array(arrays, time, "MS:1000595", "time array", _MINUTE, compression)
+    _binary_array(arrays, intensity, "MS:1000515", "intensity array", _COUNTS, compression)
+
+
+def xic_chromatograms(compounds, smoothed: bool = False) -> List[Dict]:
+    """
+    The XICs of processed compounds as chromatogram dicts for write_mzml.
+
+    Every ion with an XIC becomes a selected ion monitoring chromatogram with
+    the ion's m/z as precursor (a selected reaction monitoring one with Q1 and
+    Q3 for compounds with transitions), named ``"<compound> <m/z>"``. With
+    *smoothed*, the smoothed XIC is written where there is one.
+    """
+    chromatograms = []
+    for compound in compounds:
+        for index, (ion, data) in enumerate(compound.ions.items()):
+            trace = data.get("MS Intensity")
+            if smoothed and data.get("MS Intensity Smoothed") is not None:
+                trace = data["MS Intensity Smoothed"]
+            if trace is None or trace.shape[1] == 0:
+                continue
+            chromatogram = {
+                "id": f"{compound.name} {float(ion):.4f}",
+                "time": trace[0],
+                "intensity": trace[1],
+                "precursor_mz": float(ion),
+            }
+            if compound.transitions is not None:
+                chromatogram["precursor_mz"], chromatogram["product_mz"] = compound.transitions[index]
+            chromatograms.append(chromatogram)
+    return chromatograms
+
+
+def write_mzml(
+    path,
+    spectra: Iterable[Dict] = (),
+    chromatograms: Iterable[Dict] = (),
+    compression: str = "zlib",
+    run_id: str = None,
+    measurements: Iterable = (),
+) -> Path:
+    """
+    Write spectra and chromatograms to an mzML file.
+
+    Parameters
+    ----------
+    path : str or Path
+        Output file.
+    spectra : iterable of dict
+        ``mz`` and ``intensity`` arrays, and optionally ``scan_time``
+        (minutes, default 0), ``ms_level`` (default 1), ``precursor_mz``,
+        ``centroided`` (default False), ``polarity`` ("positive" or
+        "negative") and ``id``.
+    chromatograms : iterable of dict
+        ``time`` (minutes) and ``intensity`` arrays, and optionally ``id``,
+        ``precursor_mz``, ``product_mz`` and ``kind`` (one of
+        CHROMATOGRAM_KINDS; by default "srm" with a product m/z, "sim" with
+        a precursor m/z and "tic" otherwise), e.g. from xic_chromatograms.
+    compression : str or None
+        One of COMPRESSIONS.
+    run_id : str, optional
+        ``id`` of the run, the file name by default.
+    measurements : iterable of MSMeasurement, optional
+        The measurements the data is from, for the batch_manifest written
+        next to the file.
+
+    Returns
+    -------
+    Path
+        The file written.
+
+    Raises
+    ------
+    ValueError
+        On an unknown compression or chromatogram kind, or arrays of
+        different lengths.
+    """
+    if compression not in COMPRESSIONS:
+        raise ValueError(f"Unknown compression '{compression}', expected one of {COMPRESSIONS}")
+    path = Path(path)
+    spectra = list(spectra)
+    chromatograms = list(chromatograms)
+
+    root = etree.Element(f"{{{_NS}}}mzML", nsmap={None: _NS}, version="1.1.0")
+    cv_list = _element(root, "cvList", count=2)
+    _element(
+        cv_list,
+        "cv",
+        id="MS",
+        fullName="Proteomics Standards Initiative Mass Spectrometry Ontology",
+        version="4.1.0",
+        URI="https://raw.githubusercontent.com/HUPO-PSI/psi-ms-CV/master/psi-ms.obo",
+    )
+    _element(
+        cv_list,
+        "cv",
+        id="UO",
+        fullName="Unit Ontology",
+        version="releases/2020-03-10",
+        URI="http://purl.obolibrary.org/obo/uo.owl",
+    )
+
+    content = _element(_element(root, "fileDescription"), "fileContent")
+    levels = {int(spectrum.get("ms_level", 1)) for spectrum in spectra}
+    if 1 in levels:
+        _cv(content, "MS:1000579", "MS1 spectrum")
+    if levels - {1}:
+        _cv(content, "MS:1000580", "MSn spectrum")
+    for kind in sorted({_chromatogram_kind(chromatogram) for chromatogram in chromatograms}):
+        _cv(content, *CHROMATOGRAM_KINDS[kind])
+
+    software = _element(
+        _element(root, "softwareList", count=1), "software", id=_SOFTWARE, version=software_version()
+    )
+    _cv(software, "MS:1000799", "custom unreleased software tool", "LCMSpector")
+    configuration = _element(
+        _element(root, "instrumentConfigurationList", count=1), "instrumentConfiguration", id=_INSTRUMENT
+    )
+    _cv(configuration, "MS:1000031", "instrument model")
+    method = _element(
+        _element(_element(root, "dataProcessingList", count=1), "dataProcessing", id=_DATA_PROCESSING),
+        "processingMethod",
+        order=1,
+        softwareRef=_SOFTWARE,
+    )
+    _cv(method, "MS:1000544", "Conversion to mzML")
+
+    run = _element(root, "run", id=run_id or path.stem, defaultInstrumentConfigurationRef=_INSTRUMENT)
+    if spectra:
+        spectrum_list = _element(
+            run, "spectrumList", count=len(spectra), defaultDataProcessingRef=_DATA_PROCESSING
+        )
+        for index, spectrum in enumerate(spectra):
+            _write_spectrum(spectrum_list, index, spectrum, compression)
+    if chromatograms:
+        chromatogram_list = _element(
+            run, "chromatogramList", count=len(chromatograms), defaultDataProcessingRef=_DATA_PROCESSING
+        )
+        for index, chromatogram in enumerate(chromatograms):
+            _write_chromatogram(chromatogram_list, index, chromatogram, compression)
+
+    etree.ElementTree(root).write(str(path), xml_declaration=True, encoding="utf-8", pretty_print=True)
+    write_manifest(path, batch_manifest(measurements))
+    logger.info(f"Wrote {len(spectra)} spectra and {len(chromatograms)} chromatograms to {path}")
+    return path
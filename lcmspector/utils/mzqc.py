@@ -0,0 +1,227 @@
+"""
+Export of QC metrics to HUPO-PSI mzQC 1.0.
+
+The QC metrics of every processed file (FileStatus.qc, see calculation.qc)
+become one run quality of an mzQC document, so that runs can be tracked in
+external QC dashboards:
+
+    export_mzqc(model.file_statuses, "batch.mzQC", measurements=model.ms_measurements)
+
+Metrics with a PSI-MS term are written under its accession; the others have
+no counterpart in the vocabulary yet and use terms of their own, declared
+as the "LCMSPECTOR" vocabulary of the document. Values are JSON: numbers,
+lists, and tables as ``{column: [values]}``; NaN is written as null. With
+the measurements, their manifests (see utils.manifest) give the SHA-256 of
+every input file.
+"""
+
+import json
+import logging
+import math
+from pathlib import Path
+from typing import Iterable, Mapping, Optional, Union
+
+import numpy as np
+
+from utils.manifest import SOFTWARE_NAME, _created, software_version
+
+logger = logging.getLogger(__name__)
+
+MZQC_VERSION = "1.0.0"
+_HOMEPAGE = "https://github.com/MateuszFido/LCMSpector"
+
+# {calculation.qc key: (accession, name)}, in document order
+MZQC_METRICS = {
+    "ms1_scans": ("MS:4000059", "number of MS1 spectra"),
+    "rt_range": ("MS:4000070", "retention time acquisition range"),
+    "ms1_scan_rate": ("LCMSPECTOR:0000001", "MS1 scan rate"),
+    "median_mass_error_ppm": ("LCMSPECTOR:0000002", "median precursor mass error"),
+    "tic_cv": ("LCMSPECTOR:0000003", "MS1 TIC coefficient of variation"),
+    "tic_median_change": ("LCMSPECTOR:0000004", "MS1 TIC median relative change"),
+    "peak_width": ("LCMSPECTOR:0000005", "chromatographic peak FWHM distribution"),
+    "dropouts": ("LCMSPECTOR:0000006", "spray dropout table"),
+    "flags": ("LCMSPECTOR:0000007", "QC flags"),
+}
+_UNITS = {
+    "rt_range": {"accession": "UO:0000010", "name": "second"},
+    "ms1_scan_rate": {"accession": "UO:0000106", "name": "hertz"},
+    "median_mass_error_ppm": {"accession": "UO:0000169", "name": "parts per million"},
+    "peak_width": {"accession": "UO:0000010", "name": "second"},
+}
+_CONTROLLED_VOCABULARIES = [
+    {
+        "name": "Proteomics Standards Initiative Mass Spectrometry Ontology",
+        "uri": "https://raw.githubusercontent.com/HUPO-PSI/psi-ms-CV/master/psi-ms.obo",
+        "version": "4.1.0",
+    },
+    {
+        "name": "Unit Ontology",
+        "uri": "http://purl.obolibrary.org/obo/uo.owl",
+        "version": "releases/2020-03-10",
+    },
+    {
+        "name": "LCMSpector QC metrics",
+        "uri": _HOMEPAGE,
+        "version": "1",
+    },
+]
+_FILE_FORMATS = {
+    ".mzml": {"accession": "MS:1000584", "name": "mzML format"},
+    ".mzxml": {"accession": "MS:1000566", "name": "ISB mzXML format"},
+    ".mgf": {"accession": "MS:1001062", "name": "Mascot MGF format"},
+    ".cdf": {"accession": "MS:1002441", "name": "Andi-MS format"},
+}
+_UNKNOWN_FORMAT = {"accession": "MS:1000560", "name": "mass spectrometer file format"}
+_SOFTWARE_ACCESSION = "MS:1000799"  # custom unreleased software tool
+_SHA256 = {"accession": "MS:1003151", "name": "SHA-256"}
+
+
+def _json_value(value):
+    """*value* with numpy values as Python ones and NaN as None."""
+    if isinstance(value, Mapping):
+        return {str(k): _json_value(v) for k, v in value.items()}
+    if isinstance(value, (list, tuple, np.ndarray)):
+        return [_json_value(v) for v in value]
+    if isinstance(value, np.generic):
+        value = value.item()
+    if isinstance(value, float) and not math.isfinite(value):
+        return None
+    return value
+
+
+def _metric_value(key: str, value):
+    """The mzQC value of one calculation.qc metric, times in seconds."""
+    if key == "rt_range" and value is not None:
+        return [t * 60 for t in value]
+    if key == "peak_width":
+        value = {k: v * 60 if k != "n" else v for k, v in value.items()}
+    if key == "dropouts":
+        return {
+            "start_time": [d["start_time"] * 60 for d in value],
+            "end_time": [d["end_time"] * 60 for d in value],
+        }
+    return value
+
+
+def _quality_metrics(qc: Mapping) -> list:
+    metrics = []
+    for key, (accession, name) in MZQC_METRICS.items():
+        if key not in qc:
+            continue
+        metric = {
+            "accession": accession,
+            "name": name,
+            "value": _json_value(_metric_value(key, qc[key])),
+        }
+        if key in _UNITS:
+            metric["unit"] = _UNITS[key]
+        metrics.append(metric)
+    return metrics
+
+
+def _input_file(path: Path, manifest: Mapping) -> dict:
+    input_file = {
+        "location": path.resolve().as_uri(),
+        "name": path.name,
+        "fileFormat": _FILE_FORMATS.get(path.suffix.lower(), _UNKNOWN_FORMAT),
+        "fileProperties": [],
+    }
+    if manifest.get("input_sha256"):
+        input_file["fileProperties"].append({**_SHA256, "value": manifest["input_sha256"]})
+    return input_file
+
+
+def _statuses(file_statuses) -> list:
+    if isinstance(file_statuses, Mapping):
+        return list(file_statuses.values())
+    return list(file_statuses)
+
+
+def mzqc_document(
+    file_statuses: Union[Mapping, Iterable],
+    measurements: Optional[Union[Mapping, Iterable]] = None,
+    description: str = None,
+) -> dict:
+    """
+    The mzQC document of the QC metrics of processed files.
+
+    Parameters
+    ----------
+    file_statuses : dict or iterable of FileStatus
+        Outcomes of a run, e.g. the model's ``file_statuses``; files without
+        QC metrics (QC disabled, or failed) are left out.
+    measurements : dict or iterable of MSMeasurement, optional
+        The processed measurements, for the checksums in their manifests.
+    description : str, optional
+        Free-text description of the document.
+
+    Returns
+    -------
+    dict
+        ``{"mzQC": {...}}``, ready for json.dump.
+    """
+    manifests = {}
+    if measurements is not None:
+        if isinstance(measurements, Mapping):
+            measurements = measurements.values()
+        manifests = {
+            measurement.filename: getattr(measurement, "manifest", None) or {}
+            for measurement in measurements
+        }
+    software = {
+        "accession": _SOFTWARE_ACCESSION,
+        "name": SOFTWARE_NAME,
+        "version": software_version(),
+        "uri": _HOMEPAGE,
+    }
+    run_qualities = []
+    for status in _statuses(file_statuses):
+        if status.qc is None:
+            continue
+        path = Path(status.path)
+        run_qualities.append(
+            {
+                "metadata": {
+                    "label": path.stem,
+                    "inputFiles": [_input_file(path, manifests.get(path.name, {}))],
+                    "analysisSoftware": [software],
+                },
+                "qualityMetrics": _quality_metrics(status.qc),
+            }
+        )
+    return {
+        "mzQC": {
+            "version": MZQC_VERSION,
+            "creationDate": _created(),
+            "description": description or f"QC metrics of {len(run_qualities)} runs",
+            "runQualities": run_qualities,
+            "setQualities": [],
+            "controlledVocabularies": _CONTROLLED_VOCABULARIES,
+        }
+    }
+
+
+def export_mzqc(
+    file_statuses: Union[Mapping, Iterable],
+    path,
+    measurements: Optional[Union[Mapping, Iterable]] = None,
+    description: str = None,
+) -> Path:
+    """
+    Write the mzqc_document of processed files, conventionally as ``.mzQC``.
+
+    Returns the file written.
+
+    Raises
+    ------
+    ValueError
+        If no file has QC metrics.
+    """
+    document = mzqc_document(file_statuses, measurements, description)
+    if not document["mzQC"]["runQualities"]:
+        raise ValueError("No file has QC metrics, enable QC before exporting mzQC")
+    path = Path(path)
+    with open(path, "w", encoding="utf-8") as f:
+        json.dump(document, f, indent=2)
+    logger.info(f"Exported QC metrics of {len(document['mzQC']['runQualities'])} runs to {path}")
+    return path
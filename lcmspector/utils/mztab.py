@@ -0,0 +1,370 @@
+"""
+Export of quantification results to mzTab-M 2.0.
+
+The processed measurements are written as the three small-molecule tables
+of mzTab-M: every file is an assay (and, with its ms_run, part of a single
+study variable), every compound of the ion list a Small Molecule (SML) row,
+every ion of a compound a Small Molecule Feature (SMF) row and one Small
+Molecule Evidence (SME) row per feature backs its identification:
+
+    export_mztab(model.ms_measurements, "batch.mzTab", library=library)
+
+SML abundances are the calculated concentrations if any compound was
+calibrated (the table shares one unit), the summed feature areas otherwise;
+SMF abundances are the baseline-corrected peak areas. With a
+SpectralLibrary, the MS2 spectra linked to a feature are searched and the
+best hit goes into its evidence. Linked MS2 spectra are referenced by scan
+time, as scan numbers are not kept. The manifests of the measurements (see
+utils.manifest) give the software version, the SHA-256 ``ms_run`` hashes
+and, as software settings, the config and ion list hashes.
+"""
+
+import logging
+import math
+from pathlib import Path
+from typing import Dict, Iterable, List, Mapping, Optional, Union
+
+import numpy as np
+
+from utils.manifest import software_version
+from utils.theoretical_spectrum import adduct_definition, monoisotopic_mass
+
+logger = logging.getLogger(__name__)
+
+MZTAB_VERSION = "2.0.0-M"
+
+_MS_RUN_FORMATS = {
+    ".mzml": "[MS, MS:1000584, mzML format, ]",
+    ".mzxml": "[MS, MS:1000566, ISB mzXML format, ]",
+    ".mgf": "[MS, MS:1001062, Mascot MGF format, ]",
+    ".cdf": "[MS, MS:1002441, Andi-MS format, ]",
+}
+_SCAN_POLARITIES = {
+    "positive": "[MS, MS:1000130, positive scan, ]",
+    "negative": "[MS, MS:1000129, negative scan, ]",
+}
+_FEATURE_AREA_UNIT = "[MS, MS:1001844, MS1 feature area, ]"
+_CONCENTRATION_UNIT = "[UO, UO:0000051, concentration unit, ]"
+_LIBRARY_SCORE = "[,, spectral library match score, ]"
+_MS_LEVEL = "[MS, MS:1000511, ms level, {}]"
+_TARGETED_METHOD = "[,, targeted m/z and retention time match, ]"
+_LIBRARY_METHOD = "[,, spectral library search, ]"
+_SHA256 = "[MS, MS:1003151, SHA-256, ]"
+
+
+def _measurements(measurements) -> list:
+    if isinstance(measurements, Mapping):
+        return list(measurements.values())
+    return list(measurements)
+
+
+def _value(value) -> str:
+    """A table cell, "null" for missing values."""
+    if value is None:
+        return "null"
+    if isinstance(value, (float, np.floating)):
+        return "null" if math.isnan(value) else f"{float(value):.10g}"
+    return str(value)
+
+
+def _row(prefix: str, values) -> str:
+    return "\t".join([prefix, *(_value(value) for value in values)])
+
+
+def _adduct(label: str, polarity: str):
+    """(adduct_ion, charge) of an ion labelled *label* in the ion list."""
+    definition = adduct_definition(str(label).strip()) if label else None
+    if definition is None:
+        return None, 1 if polarity == "positive" else -1
+    sign = 1 if definition.polarity == "positive" else -1
+    return definition.label, sign * definition.charge
+
+
+def _best_library_hit(data: Dict, library, **match_options) -> Optional[Dict]:
+    """
+    Best library hit over the MS2 spectra linked to one ion, with the spectrum's scan time.
+
+    The scan time is scored against the retention in the library metadata,
+    see SpectralLibrary.match.
+    """
+    best = None
+    for spectrum in data.get("MS2") or []:
+        hits = library.match(
+            spectrum["mz"],
+            spectrum["intensity"],
+            spectrum["precursor_mz"],
+            top_n=1,
+            rt=spectrum["scan_time"],
+            **match_options,
+        )
+        if hits and (best is None or hits[0]["score"] > best["score"]):
+            best = {**hits[0], "scan_time": spectrum["scan_time"]}
+    return best
+
+
+class _Feature:
+    """One ion of a compound, gathered over all files."""
+
+    def __init__(self, compound, index: int, mz: float, polarity: str):
+        self.compound = compound.name
+        self.mz = mz
+        info = compound.ion_info[index] if index < len(compound.ion_info) else None
+        self.adduct, self.charge = _adduct(info, compound.polarity or polarity)
+        self.areas: Dict[int, float] = {}
+        self.rts: List[float] = []
+        self.starts: List[float] = []
+        self.ends: List[float] = []
+        self.hit = None  # (library hit, ms_run index)
+        self.ms2_run = None  # (ms_run index, scan time) of the first linked MS2 spectrum
+
+    def add(self, run: int, data: Dict, library=None, **match_options):
+        integration = data.get("Integration Data") or {}
+        self.areas[run] = integration.get("baseline_corrected_area")
+        if data.get("RT") is not None:
+            self.rts.append(float(data["RT"]))
+        if integration.get("start_time") is not None:
+            self.starts.append(float(integration["start_time"]))
+            self.ends.append(float(integration["end_time"]))
+        if data.get("MS2") and self.ms2_run is None:
+            self.ms2_run = (run, data["MS2"][0]["scan_time"])
+        if library is not None:
+            hit = _best_library_hit(data, library, **match_options)
+            if hit is not None and (self.hit is None or hit["score"] > self.hit[0]["score"]):
+                self.hit = (hit, run)
+
+
+def _metadata(runs, title, polarity, calibrated) -> List[str]:
+    lines = [
+        ("mzTab-version", MZTAB_VERSION),
+        ("mzTab-ID", title),
+        ("title", title),
+        ("software[1]", f"[,, LCMSpector, {software_version()}]"),
+    ]
+    manifests = [getattr(measurement, "manifest", None) or {} for measurement in runs]
+    settings = dict.fromkeys(
+        f"{key}={manifest[key]}"
+        for manifest in manifests
+        for key in ("config_hash", "ion_list_hash")
+        if manifest.get(key)
+    )
+    for n, setting in enumerate(settings, start=1):
+        lines.append((f"software[1]-setting[{n}]", setting))
+    lines.append(
+        ("quantification_method", "[MS, MS:1001834, LC-MS label-free quantitation analysis, ]")
+    )
+    for k, (measurement, manifest) in enumerate(zip(runs, manifests), start=1):
+        path = Path(measurement.path)
+        lines.append((f"ms_run[{k}]-location", path.resolve().as_uri()))
+        if path.suffix.lower() in _MS_RUN_FORMATS:
+            lines.append((f"ms_run[{k}]-format", _MS_RUN_FORMATS[path.suffix.lower()]))
+        lines.append((f"ms_run[{k}]-scan_polarity[1]", _SCAN_POLARITIES[polarity]))
+        if manifest.get("input_sha256"):
+            lines.append((f"ms_run[{k}]-hash", manifest["input_sha256"]))
+            lines.append((f"ms_run[{k}]-hash_method", _SHA256))
+    for k, measurement in enumerate(runs, start=1):
+        lines.append((f"assay[{k}]", measurement.filename))
+        lines.append((f"assay[{k}]-ms_run_ref", f"ms_run[{k}]"))
+    lines += [
+        ("study_variable[1]", "all files"),
+        ("study_variable[1]-assay_refs", "|".join(f"assay[{k}]" for k in range(1, len(runs) + 1))),
+        ("study_variable[1]-description", "All processed files"),
+        ("cv[1]-label", "MS"),
+        ("cv[1]-full_name", "PSI-MS controlled vocabulary"),
+        ("cv[1]-version", "4.1.0"),
+        ("cv[1]-uri", "https://raw.githubusercontent.com/HUPO-PSI/psi-ms-CV/master/psi-ms.obo"),
+        ("cv[2]-label", "UO"),
+        ("cv[2]-full_name", "Units of Measurement Ontology"),
+        ("cv[2]-version", "releases/2020-03-10"),
+        ("cv[2]-uri", "http://purl.obolibrary.org/obo/uo.owl"),
+        ("database[1]", '[,, "no database", null ]'),
+        ("database[1]-prefix", "null"),
+        ("database[1]-version", "Unknown"),
+        ("database[1]-uri", "null"),
+        ("small_molecule-quantification_unit", _CONCENTRATION_UNIT if calibrated else _FEATURE_AREA_UNIT),
+        ("small_molecule_feature-quantification_unit", _FEATURE_AREA_UNIT),
+        ("small_molecule-identification_reliability", "[MS, MS:1002896, compound identification confidence level, ]"),
+        ("id_confidence_measure[1]", _LIBRARY_SCORE),
+    ]
+    return [_row("MTD", line) for line in lines]
+
+
+def mztab_lines(
+    measurements: Union[Mapping, Iterable],
+    title: str = "LCMSpector results",
+    polarity: str = "positive",
+    library=None,
+    **match_options,
+) -> List[str]:
+    """
+    The lines of an mzTab-M document, see export_mztab.
+    """
+    if polarity not in _SCAN_POLARITIES:
+        raise ValueError(f"Unknown polarity '{polarity}', expected one of {tuple(_SCAN_POLARITIES)}")
+    runs = _measurements(measurements)
+    assays = range(1, len(runs) + 1)
+
+    compounds: Dict[str, Dict] = {}  # name -> formula, {mz: _Feature} and {run: concentration}
+    for run, measurement in enumerate(runs, start=1):
+        for compound in measurement.xics:
+            entry = compounds.setdefault(
+                compound.name,
+                {"formula": compound.formula, "features": {}, "concentrations": {}},
+            )
+            entry["concentrations"][run] = compound.concentration
+            for index, (mz, data) in enumerate(compound.ions.items()):
+                feature = entry["features"].get(mz)
+                if feature is None:
+                    feature = entry["features"][mz] = _Feature(compound, index, float(mz), polarity)
+                feature.add(run, data, library, **match_options)
+    calibrated = any(
+        value is not None for entry in compounds.values() for value in entry["concentrations"].values()
+    )
+
+    sml = [
+        "SMH\tSML_ID\tSMF_ID_REFS\tdatabase_identifier\tchemical_formula\tsmiles\tinchi\tchemical_name"
+        "\turi\ttheoretical_neutral_mass\tadduct_ions\treliability\tbest_id_confidence_measure"
+        "\tbest_id_confidence_value\t"
+        + "\t".join(f"abundance_assay[{k}]" for k in assays)
+        + "\tabundance_study_variable[1]\tabundance_variation_study_variable[1]"
+    ]
+    smf = [
+        "SFH\tSMF_ID\tSME_ID_REFS\tSME_ID_REF_ambiguity_code\tadduct_ion\tisotopomer\texp_mass_to_charge"
+        "\tcharge\tretention_time_in_seconds\tretention_time_in_seconds_start"
+        "\tretention_time_in_seconds_end\t" + "\t".join(f"abundance_assay[{k}]" for k in assays)
+    ]
+    sme = [
+        "SEH\tSME_ID\tevidence_input_id\tdatabase_identifier\tchemical_formula\tsmiles\tinchi"
+        "\tchemical_name\turi\tderivatized_form\tadduct_ion\texp_mass_to_charge\tcharge"
+        "\ttheoretical_mass_to_charge\tspectra_ref\tidentification_method\tms_level"
+        "\tid_confidence_measure[1]\trank"
+    ]
+    feature_id = 0
+    for sml_id, (name, entry) in enumerate(compounds.items(), start=1):
+        features = list(entry["features"].values())
+        feature_ids = list(range(feature_id + 1, feature_id + len(features) + 1))
+        feature_id += len(features)
+        for smf_id, feature in zip(feature_ids, features):
+            hit, hit_run = feature.hit or (None, None)
+            if hit is not None:
+                spectra_ref = f"ms_run[{hit_run}]:scan_time={hit['scan_time'] * 60:.3f}"
+            elif feature.ms2_run is not None:
+                spectra_ref = f"ms_run[{feature.ms2_run[0]}]:scan_time={feature.ms2_run[1] * 60:.3f}"
+            else:
+                spectra_ref = None
+            smf.append(
+                _row(
+                    "SMF",
+                    [
+                        smf_id, smf_id, None, feature.adduct, None, feature.mz, feature.charge,
+                        float(np.median(feature.rts)) * 60 if feature.rts else None,
+                        min(feature.starts) * 60 if feature.starts else None,
+                        max(feature.ends) * 60 if feature.ends else None,
+                        *(feature.areas.get(k) for k in assays),
+                    ],
+                )
+            )
+            sme.append(
+                _row(
+                    "SME",
+                    [
+                        smf_id, smf_id, None, entry["formula"], None, None,
+                        hit["name"] if hit is not None and hit.get("name") else name,
+                        None, None, feature.adduct, feature.mz, feature.charge, feature.mz,
+                        spectra_ref,
+                        _LIBRARY_METHOD if hit is not None else _TARGETED_METHOD,
+                        _MS_LEVEL.format(2 if spectra_ref else 1),
+                        hit["score"] if hit is not None else None,
+                        1,
+                    ],
+                )
+            )
+
+        if entry["formula"]:
+            try:
+                neutral_mass = monoisotopic_mass(entry["formula"])
+            except ValueError:
+                neutral_mass = None
+        else:
+            neutral_mass = None
+        if calibrated:
+            abundances = [entry["concentrations"].get(k) for k in assays]
+        else:
+            abundances = [
+                sum(f.areas[k] for f in features if f.areas.get(k) is not None)
+                if any(f.areas.get(k) is not None for f in features)
+                else None
+                for k in assays
+            ]
+        present = np.array([a for a in abundances if a is not None], dtype=np.float64)
+        mean = float(present.mean()) if present.size else None
+        variation = (
+            float(present.std(ddof=1) / mean * 100) if present.size > 1 and mean else None
+        )
+        scores = [f.hit[0]["score"] for f in features if f.hit is not None]
+        if calibrated and any(value is not None for value in entry["concentrations"].values()):
+            reliability = 1  # Confirmed against calibration standards
+        else:
+            reliability = 2 if scores else 3
+        adducts = [f.adduct for f in features if f.adduct]
+        sml.append(
+            _row(
+                "SML",
+                [
+                    sml_id, "|".join(str(i) for i in feature_ids), None, entry["formula"], None, None,
+                    name, None, neutral_mass, "|".join(adducts) if adducts else None, reliability,
+                    _LIBRARY_SCORE if scores else None, max(scores) if scores else None,
+                    *abundances, mean, variation,
+                ],
+            )
+        )
+
+    return _metadata(runs, title, polarity, calibrated) + [""] + sml + [""] + smf + [""] + sme
+
+
+def export_mztab(
+    measurements: Union[Mapping, Iterable],
+    path,
+    title: str = None,
+    polarity: str = "positive",
+    library=None,
+    **match_options,
+) -> Path:
+    """
+    Write processed measurements as an mzTab-M file.
+
+    Parameters
+    ----------
+    measurements : dict or iterable of MSMeasurement
+        Processed measurements (``xics`` filled, concentrations calculated
+        where calibrated), e.g. the model's ``ms_measurements``.
+    path : str or Path
+        Output file, conventionally ``.mzTab``.
+    title : str, optional
+        mzTab-ID and title of the document, the file name by default.
+    polarity : str
+        "positive" or "negative": scan polarity of the runs, and the charge
+        sign of ions without an adduct label.
+    library : SpectralLibrary, optional
+        Library to identify the linked MS2 spectra against.
+    **match_options
+        Passed to SpectralLibrary.match (precursor_tolerance,
+        fragment_tolerance, min_score, method, ...).
+
+    Returns
+    -------
+    Path
+        The file written.
+
+    Raises
+    ------
+    ValueError
+        On an unknown polarity.
+    """
+    path = Path(path)
+    lines = mztab_lines(
+        measurements, title=title or path.stem, polarity=polarity, library=library, **match_options
+    )
+    with open(path, "w", encoding="utf-8", newline="\n") as f:
+        f.write("\n".join(lines) + "\n")
+    logger.info(f"Exported mzTab-M to {path}")
+    return path
@@ -0,0 +1,252 @@
+"""
+Lightweight mzXML reader using lxml iterparse.
+
+Mirrors the streaming interface of utils.mzml_reader for the older mzXML
+format still produced by legacy Agilent/Thermo conversions. Peaks are stored
+as interleaved, network byte order m/z-intensity pairs, optionally
+zlib-compressed.
+"""
+
+import base64
+import logging
+import re
+import zlib
+
+import numpy as np
+from lxml.etree import QName, iterparse
+
+from utils.mzml_reader import release_element, validate_polarity
+from utils.scan_filter import needs_acquisition, scan_matches
+
+logger = logging.getLogger(__name__)
+
+# mzXML namespaces differ between schema revisions, so match any of them
+_SCAN_TAG = "{*}scan"
+_PEAKS_TAG = "{*}peaks"
+_PRECURSOR_MZ_TAG = "{*}precursorMz"
+
+# mzXML scan polarity attribute values
+_POLARITY = {"+": "positive", "-": "negative"}
+
+_DURATION_RE = re.compile(
+    r"^-?P(?:T)?(?:(?P<h>[\d.]+)H)?(?:(?P<m>[\d.]+)M)?(?:(?P<s>[\d.]+)S)?$"
+)
+
+
+def _parse_retention_time(value) -> float:
+    """Convert an xs:duration retention time (e.g. ``PT123.45S``) to minutes."""
+    if not value:
+        return 0.0
+    match = _DURATION_RE.match(value.strip())
+    if match is None:
+        # Some converters write plain seconds
+        try:
+            return float(value) / 60.0
+        except ValueError:
+            logger.warning(f"Could not parse mzXML retention time: {value}")
+            return 0.0
+    hours = float(match.group("h") or 0.0)
+    minutes = float(match.group("m") or 0.0)
+    seconds = float(match.group("s") or 0.0)
+    return hours * 60.0 + minutes + seconds / 60.0
+
+
+def _decode_peaks(peaks_elem) -> tuple[np.ndarray, np.ndarray]:
+    """Decode an mzXML <peaks> element into (mz_array, intensity_array)."""
+    precision = int(peaks_elem.get("precision", 32))
+    dtype = np.float64 if precision == 64 else np.float32
+
+    text = peaks_elem.text
+    if not text or not text.strip():
+        return np.zeros(0, dtype=dtype), np.zeros(0, dtype=dtype)
+
+    decoded = base64.b64decode(text.strip())
+    if peaks_elem.get("compressionType", "none") == "zlib":
+        decoded = zlib.decompress(decoded)
+
+    big_endian = peaks_elem.get("byteOrder", "network") in ("network", "big")
+    byte_order = ">" if big_endian else "<"
+    values = np.frombuffer(decoded, dtype=np.dtype(dtype).newbyteorder(byte_order))
+    values = values[: len(values) - len(values) % 2].astype(dtype)
+
+    pairs = values.reshape(-1, 2)
+    return pairs[:, 0].copy(), pairs[:, 1].copy()
+
+
+def _iter_scan_elements(filepath: str):
+    """Yield <scan> elements in acquisition order.
+
+    Older mzXML files nest MSn scans inside their parent survey scan. Since
+    iterparse reports the inner scans first, they are held back until the
+    parent has been yielded, and the whole subtree is cleared afterwards.
+    """
+    pending = []
+    for event, scan_elem in iterparse(filepath, tag=_SCAN_TAG):
+        parent = scan_elem.getparent()
+        if parent is not None and QName(parent).localname == "scan":
+            pending.append(scan_elem)
+            continue
+
+        yield scan_elem
+        yield from pending
+        pending.clear()
+        release_element(scan_elem)  # Free memory
+
+
+def _scan_precursor_mz(scan_elem):
+    """Precursor m/z of an MSn <scan>, or None."""
+    precursor_elem = scan_elem.find(_PRECURSOR_MZ_TAG)
+    if precursor_elem is None or not precursor_elem.text:
+        return None
+    return float(precursor_elem.text)
+
+
+def _acquisition(scan_elem) -> dict:
+    """Acquisition metadata of a <scan>, see utils.mzml_reader._acquisition.
+
+    mzXML only records the ``collisionEnergy``; no injection time or resolution.
+    """
+    collision_energy = scan_elem.get("collisionEnergy")
+    return {
+        "injection_time": None,
+        "collision_energy": float(collision_energy) if collision_energy else None,
+        "normalized_collision_energy": None,
+        "resolution": None,
+    }
+
+
+def iter_scans(
+    filepath: str,
+    polarity: str = None,
+    scan_filter: dict = None,
+    with_mobility: bool = False,
+    with_precursor: bool = False,
+):
+    """Yield (scan_time, tic, ms_level, mz_array, intensity_array) per scan.
+
+    Same tuple layout and *polarity* / *scan_filter* filters as
+    utils.mzml_reader.iter_scans, with retention times converted to minutes.
+    The filter string is the scan's ``filterLine`` attribute. mzXML has no
+    ion mobility, so *with_mobility* appends None to every tuple;
+    *with_precursor* appends the ``precursorMz``.
+    """
+    validate_polarity(polarity)
+    for scan_elem in _iter_scan_elements(filepath):
+        scan_polarity = _POLARITY.get(scan_elem.get("polarity"))
+        if polarity is not None and scan_polarity not in (None, polarity):
+            continue
+        ms_level = int(scan_elem.get("msLevel", 1))
+        if scan_filter is not None and not scan_matches(
+            scan_filter,
+            ms_level,
+            scan_elem.get("filterLine"),
+            _scan_precursor_mz(scan_elem),
+            _acquisition(scan_elem) if needs_acquisition(scan_filter) else None,
+        ):
+            continue
+        scan_time = _parse_retention_time(scan_elem.get("retentionTime"))
+        tic = float(scan_elem.get("totIonCurrent", 0.0))
+
+        peaks_elem = scan_elem.find(_PEAKS_TAG)
+        if peaks_elem is None:
+            continue
+
+        mz_array, intensity_array = _decode_peaks(peaks_elem)
+        scan = (scan_time, tic, ms_level, mz_array, intensity_array)
+        if with_mobility:
+            scan += (None,)
+        yield (*scan, _scan_precursor_mz(scan_elem)) if with_precursor else scan
+
+
+def iter_scan_metadata(filepath: str):
+    """Yield the acquisition metadata of every scan as a dict, in acquisition order.
+
+    Same keys as utils.mzml_reader.iter_scan_metadata, times in minutes; the
+    ``id`` is the scan number and the filter string the ``filterLine``.
+    """
+    for index, scan_elem in enumerate(_iter_scan_elements(filepath)):
+        yield {
+            "index": index,
+            "id": scan_elem.get("num"),
+            "scan_time": _parse_retention_time(scan_elem.get("retentionTime")),
+            "ms_level": int(scan_elem.get("msLevel", 1)),
+            "polarity": _POLARITY.get(scan_elem.get("polarity")),
+            "filter_string": scan_elem.get("filterLine"),
+            "precursor_mz": _scan_precursor_mz(scan_elem),
+            **_acquisition(scan_elem),
+        }
+
+
+def iter_ms2_scans(filepath: str, with_precursor_info: bool = False):
+    """Yield (scan_time, precursor_mz, mz_array, intensity_array) per MS2 scan.
+
+    Same tuple layout as utils.mzml_reader.iter_ms2_scans; the isolation
+    window comes from the ``windowWideness`` of the precursor, centred on it.
+    """
+    for scan_elem in _iter_scan_elements(filepath):
+        if int(scan_elem.get("msLevel", 1)) != 2:
+            continue
+        precursor_elem = scan_elem.find(_PRECURSOR_MZ_TAG)
+        peaks_elem = scan_elem.find(_PEAKS_TAG)
+        if precursor_elem is None or not precursor_elem.text or peaks_elem is None:
+            continue
+        scan_time = _parse_retention_time(scan_elem.get("retentionTime"))
+        mz_array, intensity_array = _decode_peaks(peaks_elem)
+        precursor_mz = float(precursor_elem.text)
+        if not with_precursor_info:
+            yield scan_time, precursor_mz, mz_array, intensity_array
+            continue
+        width = float(precursor_elem.get("windowWideness") or 0)
+        info = {
+            "selected_mz": precursor_mz,
+            "monoisotopic_mz": None,
+            "isolation_window": (
+                (precursor_mz - width / 2, precursor_mz + width / 2) if width > 0 else None
+            ),
+        }
+        yield scan_time, precursor_mz, mz_array, intensity_array, info
+
+
+def find_nearest_ms2(
+    filepath: str,
+    precursor_mz: float,
+    target_rt: float,
+    mz_tolerance: float = 0.5,
+    rt_window: float = 2.0,
+):
+    """Find MS2 scan nearest to target_rt whose precursor matches precursor_mz.
+
+    See utils.mzml_reader.find_nearest_ms2 for parameter documentation.
+
+    Returns
+    -------
+    tuple or None
+        (scan_time, mz_array, intensity_array) of the best match, or None.
+    """
+    best = None
+    best_rt_delta = float("inf")
+
+    for scan_elem in _iter_scan_elements(filepath):
+        if int(scan_elem.get("msLevel", 1)) != 2:
+            continue
+
+        scan_time = _parse_retention_time(scan_elem.get("retentionTime"))
+        rt_delta = abs(scan_time - target_rt)
+        if rt_delta > rt_window or rt_delta >= best_rt_delta:
+            continue
+
+        precursor_elem = scan_elem.find(_PRECURSOR_MZ_TAG)
+        if precursor_elem is None or not precursor_elem.text:
+            continue
+        if abs(float(precursor_elem.text) - precursor_mz) > mz_tolerance:
+            continue
+
+        peaks_elem = scan_elem.find(_PEAKS_TAG)
+        if peaks_elem is None:
+            continue
+        mz_array, intensity_array = _decode_peaks(peaks_elem)
+        if len(mz_array) > 0:
+            best = (scan_time, mz_array, intensity_array)
+            best_rt_delta = rt_delta
+
+    return best
@@ -0,0 +1,255 @@
+"""
+Pure NumPy implementation of the MS-Numpress compression schemes.
+
+Port of the reference MSNumpress library (Teleman et al., MCP 2014) used by
+``msconvert --numpressAll``:
+
+- linear: linear prediction of fixed-point integers (m/z, retention time)
+- pic:    positive integer compression (ion counts)
+- slof:   short logged float (intensities)
+
+The integer-based schemes share a half-byte variable-length encoding, which
+is inherently sequential; everything else is vectorized.
+"""
+
+import math
+import struct
+
+import numpy as np
+
+NUMPRESS_LINEAR = "linear"
+NUMPRESS_PIC = "pic"
+NUMPRESS_SLOF = "slof"
+
+_MASK = 0xF0000000
+
+
+class NumpressError(ValueError):
+    """Raised when a numpress payload is truncated or otherwise corrupt."""
+
+    pass
+
+
+# ---------------------------------------------------------------------------
+# Half-byte integer encoding
+# ---------------------------------------------------------------------------
+
+
+def _to_nibbles(data: bytes) -> list[int]:
+    arr = np.frombuffer(data, dtype=np.uint8)
+    nibbles = np.empty(len(arr) * 2, dtype=np.uint8)
+    nibbles[0::2] = arr >> 4
+    nibbles[1::2] = arr & 0xF
+    return nibbles.tolist()
+
+
+def _pack_nibbles(nibbles: list[int]) -> bytes:
+    if len(nibbles) % 2:
+        nibbles = nibbles + [0]
+    return bytes(
+        (nibbles[i] << 4) | (nibbles[i + 1] & 0xF) for i in range(0, len(nibbles), 2)
+    )
+
+
+def _decode_ints(data: bytes) -> list[int]:
+    """Decode a stream of half-byte encoded unsigned 32-bit integers."""
+    nibbles = _to_nibbles(data)
+    n_nibbles = len(nibbles)
+    values = []
+    pos = 0
+    while pos < n_nibbles:
+        # A lone zero in the very last half byte is padding
+        if pos == n_nibbles - 1 and nibbles[pos] == 0:
+            break
+
+        head = nibbles[pos]
+        pos += 1
+        value = 0
+        if head <= 8:
+            n_leading = head
+        else:
+            # Leading 0xF half bytes (negative numbers)
+            n_leading = head - 8
+            for i in range(n_leading):
+                value |= _MASK >> (4 * i)
+
+        n_remaining = 8 - n_leading
+        if pos + n_remaining > n_nibbles:
+            raise NumpressError("Corrupt numpress data: truncated integer")
+        for i in range(n_remaining):
+            value |= nibbles[pos + i] << (4 * i)
+        pos += n_remaining
+        values.append(value)
+
+    return values
+
+
+def _encode_int(x: int) -> list[int]:
+    """Encode an unsigned 32-bit integer as a list of half bytes."""
+    init = x & _MASK
+    if init == 0:
+        n_leading = 8
+        for i in range(8):
+            if x & (_MASK >> (4 * i)):
+                n_leading = i
+                break
+        head = n_leading
+    elif init == _MASK:
+        n_leading = 7
+        for i in range(8):
+            m = _MASK >> (4 * i)
+            if x & m != m:
+                n_leading = i
+                break
+        head = n_leading + 8
+    else:
+        n_leading = 0
+        head = 0
+    return [head] + [(x >> (4 * i)) & 0xF for i in range(8 - n_leading)]
+
+
+def _to_signed32(value: int) -> int:
+    return value - (1 << 32) if value & 0x80000000 else value
+
+
+# ---------------------------------------------------------------------------
+# Fixed point helpers
+# ---------------------------------------------------------------------------
+
+
+def _decode_fixed_point(data: bytes) -> float:
+    if len(data) < 8:
+        raise NumpressError("Corrupt numpress data: missing fixed point")
+    return struct.unpack(">d", data[:8])[0]
+
+
+def _encode_fixed_point(fixed_point: float) -> bytes:
+    return struct.pack(">d", fixed_point)
+
+
+def optimal_linear_fixed_point(values: np.ndarray) -> float:
+    """Largest fixed point for which linear prediction residuals fit in 32 bits."""
+    values = np.asarray(values, dtype=np.float64)
+    if len(values) == 0:
+        return 0.0
+    if len(values) == 1:
+        return math.floor(0xFFFFFFFF / values[0])
+    max_double = max(values[0], values[1])
+    if len(values) > 2:
+        extrapolated = 2 * values[1:-1] - values[:-2]
+        residuals = np.ceil(np.abs(values[2:] - extrapolated) + 1)
+        max_double = max(max_double, float(np.max(residuals)))
+    return math.floor(0x7FFFFFFF / max_double)
+
+
+def optimal_slof_fixed_point(values: np.ndarray) -> float:
+    """Largest fixed point for which log(x + 1) fits in an unsigned short."""
+    values = np.asarray(values, dtype=np.float64)
+    if len(values) == 0:
+        return 0.0
+    max_double = max(1.0, float(np.max(np.log(values + 1))))
+    return math.floor(0xFFFF / max_double)
+
+
+# ---------------------------------------------------------------------------
+# Decoders
+# ---------------------------------------------------------------------------
+
+
+def decode_linear(data: bytes) -> np.ndarray:
+    """Decode a numpress linear prediction payload to float64."""
+    fixed_point = _decode_fixed_point(data)
+    if len(data) == 8:
+        return np.zeros(0, dtype=np.float64)
+    if len(data) < 12:
+        raise NumpressError("Corrupt numpress data: truncated first value")
+
+    first = int.from_bytes(data[8:12], "little")
+    if len(data) == 12:
+        return np.array([first / fixed_point], dtype=np.float64)
+    if len(data) < 16:
+        raise NumpressError("Corrupt numpress data: truncated second value")
+    second = int.from_bytes(data[12:16], "little")
+
+    residuals = np.array(
+        [_to_signed32(v) for v in _decode_ints(data[16:])], dtype=np.int64
+    )
+    # y[i] = 2 * y[i-1] - y[i-2] + r[i]  =>  first differences are a running sum
+    steps = (second - first) + np.cumsum(residuals)
+    ints = np.concatenate(([first, second], second + np.cumsum(steps)))
+    return ints.astype(np.float64) / fixed_point
+
+
+def decode_pic(data: bytes) -> np.ndarray:
+    """Decode a numpress positive integer payload to float64."""
+    return np.array(_decode_ints(data), dtype=np.float64)
+
+
+def decode_slof(data: bytes) -> np.ndarray:
+    """Decode a numpress short logged float payload to float64."""
+    fixed_point = _decode_fixed_point(data)
+    if (len(data) - 8) % 2:
+        raise NumpressError("Corrupt numpress data: odd slof payload length")
+    shorts = np.frombuffer(data[8:], dtype="<u2")
+    return np.exp(shorts / fixed_point) - 1
+
+
+_DECODERS = {
+    NUMPRESS_LINEAR: decode_linear,
+    NUMPRESS_PIC: decode_pic,
+    NUMPRESS_SLOF: decode_slof,
+}
+
+
+def decode(data: bytes, method: str) -> np.ndarray:
+    """Decode a numpress payload with the given method ("linear", "pic", "slof")."""
+    try:
+        decoder = _DECODERS[method]
+    except KeyError:
+        raise ValueError(f"Unknown numpress method: {method}") from None
+    return decoder(data)
+
+
+# ---------------------------------------------------------------------------
+# Encoders
+# ---------------------------------------------------------------------------
+
+
+def encode_linear(values: np.ndarray, fixed_point: float = None) -> bytes:
+    """Encode values with numpress linear prediction."""
+    values = np.asarray(values, dtype=np.float64)
+    if fixed_point is None:
+        fixed_point = optimal_linear_fixed_point(values)
+    out = bytearray(_encode_fixed_point(fixed_point))
+    if len(values) == 0:
+        return bytes(out)
+
+    ints = [int(v * fixed_point + 0.5) for v in values]
+    out += (ints[0] & 0xFFFFFFFF).to_bytes(4, "little")
+    if len(ints) == 1:
+        return bytes(out)
+    out += (ints[1] & 0xFFFFFFFF).to_bytes(4, "little")
+
+    nibbles = []
+    for i in range(2, len(ints)):
+        extrapolated = 2 * ints[i - 1] - ints[i - 2]
+        nibbles.extend(_encode_int((ints[i] - extrapolated) & 0xFFFFFFFF))
+    out += _pack_nibbles(nibbles)
+    return bytes(out)
+
+
+def encode_pic(values: np.ndarray) -> bytes:
+    """Encode non-negative values with numpress positive integer compression."""
+    nibbles = []
+    for v in np.asarray(values, dtype=np.float64):
+        nibbles.extend(_encode_int(int(v + 0.5) & 0xFFFFFFFF))
+    return _pack_nibbles(nibbles)
+
+
+def encode_slof(values: np.ndarray, fixed_point: float = None) -> bytes:
+    """Encode non-negative values with numpress short logged float."""
+    values = np.asarray(values, dtype=np.float64)
+    if fixed_point is None:
+        fixed_point = optimal_slof_fixed_point(values)
+    shorts = (np.log(values + 1) * fixed_point + 0.5).astype("<u2")
+    return _encode_fixed_point(fixed_point) + shorts.tobytes()
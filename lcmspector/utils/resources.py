@@ -9,21 +9,15 @@ from __future__ import annotations
 
 import os
 import sys
-import ssl
 import json
 from pathlib import Path
-import io
 import logging
-from urllib.request import urlopen
-from zipfile import ZipFile, BadZipFile
 
 from PySide6.QtCore import QObject, Signal
 
-LOGGER = logging.getLogger(__name__)
+from utils.library import MSP_FILENAME, MSP_ZIP_URL, update_library  # noqa: F401
 
-# Public download of the MoNA Orbitrap MSP (as used in CI)
-MSP_ZIP_URL = "https://polybox.ethz.ch/index.php/s/CrnWdgwX5canNxL/download"
-MSP_FILENAME = "MoNA-export-All_LC-MS-MS_Orbitrap.msp"
+LOGGER = logging.getLogger(__name__)
 
 
 def get_resources_dir() -> Path:
@@ -34,6 +28,10 @@ def get_resources_dir() -> Path:
 class DownloadWorker(QObject):
     """
     Worker to download the MS2 library in a separate thread.
+
+    The download and the index of the library are handled by
+    utils.library.update_library, so the library is ready to search once
+    the worker finishes.
     """
 
     progress = Signal(int)
@@ -42,60 +40,14 @@ class DownloadWorker(QObject):
 
     def run(self):
         """
-        Downloads and extracts the MS2 library MSP file from Polybox.
+        Downloads and indexes the MS2 library MSP file from Polybox.
         """
-        resources_dir = get_resources_dir()
-        resources_dir.mkdir(parents=True, exist_ok=True)
-        msp_path = resources_dir / MSP_FILENAME
-
         try:
             LOGGER.info("MS2 library not found; downloading from Polybox...")
-            context = ssl._create_unverified_context()
-            with urlopen(MSP_ZIP_URL, context=context, timeout=60) as resp:
-                total_size = int(resp.headers.get("content-length", 0))
-                chunk_size = 8192
-                data = b""
-                bytes_read = 0
-                while True:
-                    chunk = resp.read(chunk_size)
-                    if not chunk:
-                        break
-                    data += chunk
-                    bytes_read += len(chunk)
-                    if total_size > 0:
-                        percent = int((bytes_read / total_size) * 100)
-                        self.progress.emit(percent)
-
-            with ZipFile(io.BytesIO(data)) as zf:
-                names = zf.namelist()
-                candidate = next((n for n in names if n.endswith(".msp")), None)
-                if not candidate:
-                    msg = "Downloaded archive does not contain an .msp file."
-                    LOGGER.error(msg)
-                    self.error.emit(msg)
-                    return
-                zf.extract(member=candidate, path=str(resources_dir))
-                extracted = resources_dir / candidate
-                if extracted.name != MSP_FILENAME:
-                    try:
-                        extracted.rename(msp_path)
-                    except OSError:
-                        file_data = extracted.read_bytes()
-                        msp_path.write_bytes(file_data)
-                        try:
-                            extracted.unlink()
-                        except OSError:
-                            pass
-
-            if msp_path.exists():
-                LOGGER.info("MS2 library downloaded to %s", msp_path)
-                self.finished.emit()
-            else:
-                msg = "Failed to create MSP file after download."
-                LOGGER.error(msg)
-                self.error.emit(msg)
-
-        except (TimeoutError, BadZipFile, OSError) as e:
+            library, _ = update_library(get_resources_dir(), progress=self.progress.emit)
+            LOGGER.info("MS2 library indexed with %d spectra", len(library))
+            self.finished.emit()
+        except (TimeoutError, ValueError, OSError) as e:
             LOGGER.error("Failed to download/extract MS2 library: %s", e)
             self.error.emit(f"Failed to download/extract MS2 library: {e}")
         except Exception as e:
@@ -0,0 +1,312 @@
+"""
+On-disk cache of decoded scans.
+
+Re-running extraction after tweaking the ion list parses the same multi-GB
+mzML every time, although only the targets changed. The first pass through
+a file can therefore write its decoded scans next to it, into a
+``<file>.lcms-cache`` directory, and later passes read them back as raw
+memory-mapped arrays instead of parsing XML.
+
+Scans are cached per polarity and scan filter, as those decide which scans
+the readers yield. An entry is only used while the size and modification
+time of the source file match the ones it was written for.
+
+Layout of an entry (one subdirectory per polarity/scan filter):
+
+- ``scans.npy``: per-scan time, TIC, MS level, peak offset and mobility flag
+- ``mz.bin``, ``intensity.bin``, ``mobility.bin``: all peaks, float64
+- ``meta.json``: cache version, source size/mtime and the scan selection,
+  written last so that its presence marks a complete entry
+
+SpilledScans uses the same layout in a temporary directory, for scans that
+would otherwise exceed the memory budget (see utils.memory).
+"""
+
+import hashlib
+import json
+import logging
+import os
+import shutil
+import tempfile
+import weakref
+from pathlib import Path
+from typing import Iterator, Optional
+
+import numpy as np
+
+logger = logging.getLogger(__name__)
+
+CACHE_SUFFIX = ".lcms-cache"
+CACHE_VERSION = 1
+
+_SCAN_DTYPE = np.dtype(
+    [
+        ("time", np.float64),
+        ("tic", np.float64),
+        ("ms_level", np.int16),
+        ("offset", np.int64),
+        ("has_mobility", np.bool_),
+    ]
+)
+_PEAK_FILES = ("mz.bin", "intensity.bin", "mobility.bin")
+
+
+def cache_dir(path: str) -> Path:
+    """Cache directory of an MS file."""
+    path = Path(path)
+    return path.with_name(path.name + CACHE_SUFFIX)
+
+
+def clear_scan_cache(path: str):
+    """Delete every cached scan selection of an MS file."""
+    shutil.rmtree(cache_dir(path), ignore_errors=True)
+
+
+def _source_stamp(path: str) -> dict:
+    stat = os.stat(path)
+    return {"size": stat.st_size, "mtime_ns": stat.st_mtime_ns}
+
+
+def _selection(polarity: str, scan_filter: dict) -> dict:
+    return {"polarity": polarity, "scan_filter": scan_filter}
+
+
+def _entry_dir(path: str, polarity: str, scan_filter: dict) -> Path:
+    key = json.dumps(_selection(polarity, scan_filter), sort_keys=True, default=list)
+    return cache_dir(path) / hashlib.sha1(key.encode()).hexdigest()[:16]
+
+
+def _read_meta(entry: Path) -> Optional[dict]:
+    try:
+        with open(entry / "meta.json") as handle:
+            return json.load(handle)
+    except (OSError, ValueError):
+        return None
+
+
+def read_scan_cache(
+    path: str,
+    polarity: str = None,
+    scan_filter: dict = None,
+    with_mobility: bool = False,
+    progress_callback=None,
+) -> Optional[Iterator]:
+    """
+    Cached scans of an MS file, if there is a valid entry for the selection.
+
+    Parameters
+    ----------
+    path : str
+        The MS file.
+    polarity, scan_filter
+        Scan selection (validated), as passed to utils.loading.iter_ms_scans.
+    with_mobility : bool
+        Append the per-peak mobility array (or None) to every tuple.
+    progress_callback : callable, optional
+        Called with the fraction of the cached scans read.
+
+    Returns
+    -------
+    iterator or None
+        The same tuples as utils.loading.iter_ms_scans, or None if the file
+        has no up-to-date entry for this selection.
+    """
+    entry = _entry_dir(path, polarity, scan_filter)
+    meta = _read_meta(entry)
+    try:
+        stamp = _source_stamp(path)
+    except OSError:
+        return None
+    if meta is None or meta.get("version") != CACHE_VERSION or meta.get("source") != stamp:
+        if meta is not None:
+            logger.info(f"Scan cache of {path} is out of date, rebuilding")
+        return None
+    try:
+        scans = np.load(entry / "scans.npy")
+        if meta["n_peaks"]:
+            mz, intensity, mobility = (
+                np.memmap(entry / name, dtype=np.float64, mode="r") for name in _PEAK_FILES
+            )
+        else:
+            mz = intensity = mobility = np.zeros(0, dtype=np.float64)
+    except (OSError, ValueError) as e:
+        logger.warning(f"Could not read scan cache of {path}: {e}")
+        return None
+    logger.info(f"Reading {len(scans)} scans of {path} from the scan cache")
+    return _iter_cached(scans, mz, intensity, mobility, with_mobility, progress_callback)
+
+
+def _iter_cached(scans, mz, intensity, mobility, with_mobility, progress_callback):
+    offsets = np.append(scans["offset"], len(mz))
+    for i, scan in enumerate(scans):
+        peaks = slice(offsets[i], offsets[i + 1])
+        item = (
+            float(scan["time"]), float(scan["tic"]), int(scan["ms_level"]),
+            np.array(mz[peaks]), np.array(intensity[peaks]),
+        )
+        if with_mobility:
+            item += (np.array(mobility[peaks]) if scan["has_mobility"] else None,)
+        yield item
+        if progress_callback is not None and i % 100 == 0:
+            progress_callback(i / len(scans))
+    if progress_callback is not None:
+        progress_callback(1.0)
+
+
+def write_scan_cache(
+    path: str,
+    scans: Iterator,
+    polarity: str = None,
+    scan_filter: dict = None,
+    with_mobility: bool = False,
+):
+    """
+    Pass scans through while writing them to the cache of *path*.
+
+    *scans* must be read with mobility (six-item tuples) for the given
+    selection; the mobility array is only passed on *with_mobility*. The
+    entry is committed once *scans* is exhausted, so a pass that is stopped
+    early (e.g. cancelled) leaves no cache behind. If the cache cannot be
+    written, the scans are passed through regardless.
+    """
+    entry = _entry_dir(path, polarity, scan_filter)
+    tmp = entry.with_name(f"{entry.name}.tmp-{os.getpid()}")
+    try:
+        stamp = _source_stamp(path)
+        shutil.rmtree(tmp, ignore_errors=True)
+        tmp.mkdir(parents=True)
+        handles = [open(tmp / name, "wb") for name in _PEAK_FILES]
+    except OSError as e:
+        logger.warning(f"Scan cache for {path} disabled: {e}")
+        for scan in scans:
+            yield scan if with_mobility else scan[:5]
+        return
+
+    rows = []
+    n_peaks = 0
+    failed = committed = False
+    try:
+        for scan in scans:
+            if not failed:
+                try:
+                    n_peaks += _write_scan(handles, rows, scan, n_peaks)
+                except OSError as e:
+                    logger.warning(f"Could not write scan cache of {path}: {e}")
+                    failed = True
+            yield scan if with_mobility else scan[:5]
+        if not failed:
+            try:
+                for handle in handles:
+                    handle.close()
+                _commit(tmp, entry, rows, n_peaks, stamp, _selection(polarity, scan_filter))
+                committed = True
+                logger.info(f"Cached {len(rows)} scans of {path} in {entry}")
+            except OSError as e:
+                logger.warning(f"Could not write scan cache of {path}: {e}")
+    finally:
+        for handle in handles:
+            handle.close()
+        if not committed:
+            shutil.rmtree(tmp, ignore_errors=True)
+
+
+def _write_scan(handles, rows, scan, offset: int) -> int:
+    """Append one scan to the open peak files; returns its number of peaks."""
+    scan_time, tic, ms_level, mz_array, intensity_array, mobility = scan
+    if mobility is None:
+        mobility_values = np.full(len(mz_array), np.nan)
+    else:
+        mobility_values = np.asarray(mobility, dtype=np.float64)
+    handles[0].write(np.asarray(mz_array, dtype=np.float64).tobytes())
+    handles[1].write(np.asarray(intensity_array, dtype=np.float64).tobytes())
+    handles[2].write(mobility_values.tobytes())
+    rows.append((scan_time, tic, ms_level, offset, mobility is not None))
+    return len(mz_array)
+
+
+def _commit(tmp: Path, entry: Path, rows, n_peaks: int, stamp: dict, selection: dict):
+    """Write the scan table and metadata, then move the entry into place."""
+    np.save(tmp / "scans.npy", np.array(rows, dtype=_SCAN_DTYPE))
+    with open(tmp / "meta.json", "w") as handle:
+        json.dump(
+            {
+                "version": CACHE_VERSION,
+                "source": stamp,
+                "selection": selection,
+                "n_scans": len(rows),
+                "n_peaks": n_peaks,
+            },
+            handle,
+        )
+    shutil.rmtree(entry, ignore_errors=True)
+    os.replace(tmp, entry)
+
+
+class SpilledScans:
+    """
+    Scans held in temporary memory-mapped files instead of in memory.
+
+    Fill with append() (six-item scan tuples, as read with mobility), then
+    call finish(); afterwards it iterates like the list of scans, each scan's
+    arrays copied out of the files as it is reached. The files are deleted
+    by release(), or when the object is garbage collected. Pickled or
+    deep-copied scans are written to new temporary files when restored.
+    """
+
+    def __init__(self, directory: str = None):
+        self.directory = Path(tempfile.mkdtemp(prefix="lcmspector-scans-", dir=directory))
+        self._finalizer = weakref.finalize(self, shutil.rmtree, str(self.directory), True)
+        self._handles = [open(self.directory / name, "wb") for name in _PEAK_FILES]
+        self._rows = []
+        self._n_peaks = 0
+        self._arrays = None
+
+    def __len__(self):
+        return len(self._rows)
+
+    def __repr__(self):
+        return f"SpilledScans({len(self)} scans in {self.directory})"
+
+    def append(self, scan):
+        """Write one (scan_time, tic, ms_level, mz, intensity, mobility) scan to disk."""
+        if self._arrays is not None:
+            raise RuntimeError("Cannot append to SpilledScans after finish()")
+        self._n_peaks += _write_scan(self._handles, self._rows, scan, self._n_peaks)
+
+    def finish(self):
+        """Close the files for writing and map them for reading."""
+        for handle in self._handles:
+            handle.close()
+        if self._n_peaks:
+            peaks = tuple(
+                np.memmap(self.directory / name, dtype=np.float64, mode="r")
+                for name in _PEAK_FILES
+            )
+        else:
+            peaks = (np.zeros(0, dtype=np.float64),) * 3
+        self._arrays = (np.array(self._rows, dtype=_SCAN_DTYPE), *peaks)
+        return self
+
+    def __iter__(self):
+        if self._arrays is None:
+            self.finish()
+        return _iter_cached(*self._arrays, with_mobility=True, progress_callback=None)
+
+    def __reduce__(self):
+        return _restore_spilled, (list(self),)
+
+    def release(self):
+        """Delete the files; the scans can no longer be read."""
+        for handle in self._handles:
+            handle.close()
+        self._arrays = None
+        self._rows = []
+        self._finalizer()
+
+
+def _restore_spilled(scans) -> SpilledScans:
+    """Unpickle SpilledScans, spilling the scans to new temporary files."""
+    spilled = SpilledScans()
+    for scan in scans:
+        spilled.append(scan)
+    return spilled.finish()
@@ -0,0 +1,140 @@
+"""
+Selection of the scans that contribute to XIC extraction.
+
+Methods mixing survey, SIM and MSn scans in one file need some of them
+excluded. A scan filter is a plain dict, like the smoothing settings, so it
+pickles cheaply into the processing pool:
+
+    {"ms_levels": [1], "filter_regex": r"SIM ms", "precursor_range": None}
+
+Every key is optional; a scan passes when it satisfies all of the given ones.
+The acquisition ranges (ion injection time, collision energy) are checked
+against the scan's acquisition metadata, see utils.mzml_reader.iter_scan_metadata.
+"""
+
+import logging
+import re
+
+logger = logging.getLogger(__name__)
+
+SCAN_FILTER_KEYS = (
+    "ms_levels", "filter_regex", "precursor_range", "injection_time_range",
+    "collision_energy_range",
+)
+# Keys that need the acquisition metadata of a scan, not just its MS level,
+# filter string and precursor
+ACQUISITION_FILTER_KEYS = ("injection_time_range", "collision_energy_range")
+
+
+def _validate_range(name: str, value):
+    if value is None:
+        return None
+    low, high = (float(bound) for bound in value)
+    if low > high:
+        raise ValueError(f"{name.replace('_', ' ').capitalize()} is reversed: ({low}, {high})")
+    return (low, high)
+
+
+def validate_scan_filter(settings: dict):
+    """
+    Normalize and validate a scan filter dict.
+
+    Parameters
+    ----------
+    settings : dict or None
+        ``ms_levels`` (int or list of int), ``filter_regex`` (regular
+        expression searched in the scan's filter string, e.g. the Thermo
+        filter line), ``precursor_range`` (``(low, high)`` m/z the
+        precursor must lie in), ``injection_time_range`` (ms) and
+        ``collision_energy_range`` (the normalized collision energy if the
+        scan has one, e.g. 30 for HCD at 30 %, else the absolute one in eV).
+
+    Returns
+    -------
+    dict or None
+        A new dict with every key present, or None if nothing is filtered.
+
+    Raises
+    ------
+    ValueError
+        On unknown keys, non-positive MS levels, an invalid regular
+        expression or a reversed range.
+    """
+    if not settings:
+        return None
+    unknown = set(settings) - set(SCAN_FILTER_KEYS)
+    if unknown:
+        raise ValueError(
+            f"Unknown scan filter keys {sorted(unknown)}, expected {SCAN_FILTER_KEYS}"
+        )
+
+    ms_levels = settings.get("ms_levels")
+    if ms_levels is not None:
+        if isinstance(ms_levels, int):
+            ms_levels = [ms_levels]
+        ms_levels = tuple(sorted({int(level) for level in ms_levels}))
+        if not ms_levels or ms_levels[0] < 1:
+            raise ValueError(f"MS levels must be positive integers, got {settings['ms_levels']}")
+
+    filter_regex = settings.get("filter_regex") or None
+    if filter_regex is not None:
+        try:
+            re.compile(filter_regex)
+        except re.error as e:
+            raise ValueError(f"Invalid scan filter regex '{filter_regex}': {e}") from None
+
+    ranges = {name: _validate_range(name, settings.get(name)) for name in SCAN_FILTER_KEYS[2:]}
+
+    if ms_levels is None and filter_regex is None and all(r is None for r in ranges.values()):
+        return None
+    return {"ms_levels": ms_levels, "filter_regex": filter_regex, **ranges}
+
+
+def needs_acquisition(scan_filter: dict) -> bool:
+    """Whether a validated scan filter checks acquisition metadata, see scan_matches."""
+    return scan_filter is not None and any(
+        scan_filter.get(key) is not None for key in ACQUISITION_FILTER_KEYS
+    )
+
+
+def _in_range(value, bounds) -> bool:
+    return bounds is None or (value is not None and bounds[0] <= value <= bounds[1])
+
+
+def scan_matches(
+    scan_filter: dict,
+    ms_level: int,
+    filter_string: str = None,
+    precursor_mz: float = None,
+    acquisition: dict = None,
+) -> bool:
+    """
+    Whether a scan passes a validated scan filter.
+
+    Scans without a filter string fail a ``filter_regex`` and scans without a
+    precursor (e.g. MS1) fail a ``precursor_range``. *acquisition* holds the
+    scan's ``injection_time``, ``collision_energy`` and
+    ``normalized_collision_energy``; scans without the value filtered on
+    fail its range.
+    """
+    if scan_filter is None:
+        return True
+    ms_levels = scan_filter["ms_levels"]
+    if ms_levels is not None and ms_level not in ms_levels:
+        return False
+    filter_regex = scan_filter["filter_regex"]
+    if filter_regex is not None and (
+        filter_string is None or re.search(filter_regex, filter_string) is None
+    ):
+        return False
+    if not _in_range(precursor_mz, scan_filter["precursor_range"]):
+        return False
+    if not needs_acquisition(scan_filter):
+        return True
+    acquisition = acquisition or {}
+    collision_energy = acquisition.get("normalized_collision_energy")
+    if collision_energy is None:
+        collision_energy = acquisition.get("collision_energy")
+    return _in_range(
+        acquisition.get("injection_time"), scan_filter["injection_time_range"]
+    ) and _in_range(collision_energy, scan_filter["collision_energy_range"])
@@ -0,0 +1,117 @@
+"""
+Scan-by-scan access to MS files for custom analyses.
+
+ScanReader streams the scans of an mzML, mzXML, MGF or ANDI-MS file with
+the same readers as the processing (utils.loading.iter_ms_scans), so a
+script can compute what the backend does not offer yet without parsing the
+file itself:
+
+    with ScanReader("run.mzML", ms_levels=2) as reader:
+        for rt, mz, intensity, ms_level, precursor_mz in reader:
+            ...
+
+Every scan is a ``(rt, mz_array, intensity_array, ms_level, precursor_mz)``
+tuple: the scan time in minutes, float64 NumPy arrays (m/z ascending as
+stored) and the precursor m/z, None for MS1 scans. Scans come in file
+order and are decoded one at a time, so memory stays flat over any file
+size.
+"""
+
+import logging
+from typing import Iterator, Optional, Sequence, Tuple, Union
+
+import numpy as np
+
+from utils.loading import iter_ms_scans
+from utils.scan_filter import validate_scan_filter
+
+logger = logging.getLogger(__name__)
+
+Scan = Tuple[float, np.ndarray, np.ndarray, int, Optional[float]]
+
+
+class ScanReader:
+    """
+    Iterator over the scans of an MS file, see the module docstring.
+
+    Parameters
+    ----------
+    path : str or Path
+        The MS file.
+    ms_levels : int or sequence of int, optional
+        Only yield scans of these MS levels.
+    polarity : str, optional
+        "positive" or "negative", see utils.loading.iter_ms_scans.
+    scan_filter : dict, optional
+        Further scan selection, see utils.scan_filter; *ms_levels* replaces
+        its ``ms_levels``.
+    rt_range : (float, float), optional
+        Only yield scans acquired in this retention time range (min),
+        inclusive.
+    decode_threads : int, optional
+        See utils.loading.iter_ms_scans.
+
+    Raises
+    ------
+    ValueError
+        On an invalid scan filter or a reversed *rt_range*.
+    """
+
+    def __init__(
+        self,
+        path,
+        ms_levels: Union[int, Sequence[int]] = None,
+        polarity: str = None,
+        scan_filter: dict = None,
+        rt_range: Tuple[float, float] = None,
+        decode_threads: int = None,
+    ):
+        self.path = str(path)
+        scan_filter = dict(scan_filter or {})
+        if ms_levels is not None:
+            scan_filter["ms_levels"] = ms_levels
+        self.scan_filter = validate_scan_filter(scan_filter or None)
+        if rt_range is not None and rt_range[0] > rt_range[1]:
+            raise ValueError(f"Retention time range is reversed: {tuple(rt_range)}")
+        self.rt_range = rt_range
+        self.polarity = polarity
+        self.decode_threads = decode_threads
+        self._scans = None
+
+    def __iter__(self) -> Iterator[Scan]:
+        return self
+
+    def __next__(self) -> Scan:
+        if self._scans is None:
+            self._scans = iter_ms_scans(
+                self.path,
+                polarity=self.polarity,
+                scan_filter=self.scan_filter,
+                decode_threads=self.decode_threads,
+                with_precursor=True,
+            )
+        while True:
+            scan_time, _, ms_level, mz, intensity, precursor_mz = next(self._scans)
+            if self.rt_range is not None and not (
+                self.rt_range[0] <= scan_time <= self.rt_range[1]
+            ):
+                continue
+            return (
+                float(scan_time),
+                np.asarray(mz, dtype=np.float64),
+                np.asarray(intensity, dtype=np.float64),
+                int(ms_level),
+                None if precursor_mz is None else float(precursor_mz),
+            )
+
+    def __enter__(self):
+        return self
+
+    def __exit__(self, *exc):
+        self.close()
+
+    def close(self):
+        """Release the file; a later iteration starts over from the first scan."""
+        if self._scans is not None:
+            self._scans.close()
+            self._scans = None
@@ -0,0 +1,319 @@
+"""
+Saving and restoring a whole analysis.
+
+A session file holds the processed measurements (XICs, peaks, MS2 spectra,
+concentrations), the ion list and the processing settings, so that an
+analysis can be reopened without reprocessing the raw files:
+
+    save_session("batch.lcms", model.ms_measurements, model.lc_measurements,
+                 model.compounds, settings={"mass_accuracy": 0.0001})
+    session = load_session("batch.lcms")
+    session.ms_measurements["sample"].xics
+
+The file is a compressed NumPy ``.npz`` archive holding data only: every
+array of the state is a member of its own, and the ``session`` member is
+the JSON of the rest, with references to them. Loading never unpickles
+(``allow_pickle=False``), so opening a session file cannot run code.
+
+In the JSON, values JSON has no type for are tagged objects: ``@array``
+(the archive member), ``@objects`` (an object array, item by item),
+``@tuple``, ``@dict`` (a dict with keys other than strings, as
+``[key, value]`` pairs), ``@frame`` (a pandas DataFrame), ``@compound``
+(the Compound fields of ``model_dump`` and its results), ``@record`` (the
+init fields of one of _RECORD_TYPES) and ``@measurement`` (the attributes
+of an MS or LC measurement). The tags name the kind of data, not the
+Python class, so the format does not change when the classes move. The
+lazy readers of the raw MS files are not saved; load_session reopens them
+where the raw file is still there.
+"""
+
+import dataclasses
+import json
+import logging
+import os
+from dataclasses import dataclass, field
+from numbers import Number
+from pathlib import Path
+from typing import Dict, Mapping, Optional, Sequence
+from zipfile import BadZipFile
+
+import numpy as np
+import pandas as pd
+
+from calculation.alignment import RTShift
+from calculation.status import FileStatus
+from utils.classes import Compound, LCMeasurement, MSMeasurement
+from utils.errors import FileParseError
+from utils.loading import load_ms_data
+
+logger = logging.getLogger(__name__)
+
+SESSION_FORMAT = "lcmspector-session"
+SESSION_VERSION = 2
+
+# Archive member holding the JSON state
+_STATE_MEMBER = "session"
+_ZIP_MAGIC = b"PK\x03\x04"
+# Tags of the dataclasses and measurements a session can hold, see _Encoder
+_RECORD_TYPES = {"rt_shift": RTShift, "file_status": FileStatus}
+_MEASUREMENT_TYPES = {"ms": MSMeasurement, "lc": LCMeasurement}
+
+
+@dataclass
+class Session:
+    """A restored analysis, see load_session."""
+
+    ms_measurements: Dict = field(default_factory=dict)
+    lc_measurements: Dict = field(default_factory=dict)
+    compounds: tuple = ()
+    settings: Dict = field(default_factory=dict)
+
+
+class _Encoder:
+    """Turns the state into JSON values, collecting its arrays as archive members."""
+
+    def __init__(self):
+        self.arrays = {}
+
+    def array(self, value: np.ndarray):
+        if value.dtype.hasobject:
+            return {
+                "@objects": [self(item) for item in value.ravel().tolist()],
+                "shape": list(value.shape),
+            }
+        name = f"a{len(self.arrays)}"
+        self.arrays[name] = value
+        return {"@array": name}
+
+    def __call__(self, value):
+        if value is None or isinstance(value, (str, bool)):
+            return value
+        if isinstance(value, np.ndarray):
+            return self.array(value)
+        if isinstance(value, np.generic):
+            return value.item()
+        if isinstance(value, Number):
+            return value
+        if isinstance(value, Path):
+            return str(value)
+        if isinstance(value, tuple):
+            return {"@tuple": [self(item) for item in value]}
+        if isinstance(value, list):
+            return [self(item) for item in value]
+        if isinstance(value, Mapping):
+            if all(isinstance(key, str) and not key.startswith("@") for key in value):
+                return {key: self(item) for key, item in value.items()}
+            return {"@dict": [[self(key), self(item)] for key, item in value.items()]}
+        if isinstance(value, pd.DataFrame):
+            return {
+                "@frame": {
+                    "columns": [self(name) for name in value.columns],
+                    "index": self.array(value.index.to_numpy()),
+                    "data": [
+                        self.array(value.iloc[:, i].to_numpy()) for i in range(value.shape[1])
+                    ],
+                    "attrs": self(dict(value.attrs)),
+                }
+            }
+        if isinstance(value, Compound):
+            return {
+                "@compound": {
+                    "fields": value.model_dump(mode="json"),
+                    "file": value.file,
+                    "concentration": self(value.concentration),
+                    "calibration_parameters": self(dict(value.calibration_parameters)),
+                    "calibration_curve": self(dict(value.calibration_curve)),
+                    "custom_mz_ranges": self(dict(value.custom_mz_ranges)),
+                    "ions": self(value.ions),
+                }
+            }
+        for tag, cls in _RECORD_TYPES.items():
+            if type(value) is cls:
+                fields = {f.name: getattr(value, f.name) for f in dataclasses.fields(cls) if f.init}
+                return {"@record": tag, "fields": self(fields)}
+        for tag, cls in _MEASUREMENT_TYPES.items():
+            if type(value) is cls:
+                attributes = dict(vars(value))
+                if tag == "ms":
+                    attributes.pop("data", None)  # The lazy raw file reader
+                return {"@measurement": tag, "attributes": self(attributes)}
+        raise TypeError(f"Cannot save {type(value).__name__} in a session")
+
+
+class _Decoder:
+    """Inverse of _Encoder, reading the arrays from the open archive."""
+
+    def __init__(self, archive):
+        self.archive = archive
+
+    def __call__(self, value):
+        if isinstance(value, list):
+            return [self(item) for item in value]
+        if not isinstance(value, dict):
+            return value
+        if "@array" in value:
+            return self.archive[value["@array"]]
+        if "@objects" in value:
+            array = np.empty(value["shape"], dtype=object)
+            flat = array.reshape(-1)
+            for index, item in enumerate(value["@objects"]):
+                flat[index] = self(item)
+            return array
+        if "@tuple" in value:
+            return tuple(self(item) for item in value["@tuple"])
+        if "@dict" in value:
+            return {self(key): self(item) for key, item in value["@dict"]}
+        if "@frame" in value:
+            frame = value["@frame"]
+            data = pd.DataFrame(
+                {i: self(column) for i, column in enumerate(frame["data"])},
+                index=self(frame["index"]),
+            )
+            data.columns = [self(name) for name in frame["columns"]]
+            data.attrs.update(self(frame["attrs"]))
+            return data
+        if "@compound" in value:
+            return self.compound(value["@compound"])
+        if "@record" in value:
+            return _RECORD_TYPES[value["@record"]](**self(value["fields"]))
+        if "@measurement" in value:
+            cls = _MEASUREMENT_TYPES[value["@measurement"]]
+            # Restored as saved, without reading the raw file again
+            measurement = cls.__new__(cls)
+            measurement.__dict__.update(self(value["attributes"]))
+            if cls is MSMeasurement:
+                measurement.data = None
+            return measurement
+        return {key: self(item) for key, item in value.items()}
+
+    def compound(self, state):
+        compound = Compound(**state["fields"])
+        compound.file = state["file"]
+        compound.concentration = self(state["concentration"])
+        compound.calibration_parameters = self(state["calibration_parameters"])
+        compound._calibration_curve = self(state["calibration_curve"])
+        compound.custom_mz_ranges = self(state["custom_mz_ranges"])
+        compound.ions = self(state["ions"])
+        return compound
+
+
+def save_session(
+    path,
+    ms_measurements: Mapping,
+    lc_measurements: Optional[Mapping] = None,
+    compounds: Sequence = (),
+    settings: Optional[Mapping] = None,
+) -> Path:
+    """
+    Write an analysis to a session file.
+
+    Parameters
+    ----------
+    path : str or Path
+        Output file, replaced atomically if it exists.
+    ms_measurements : dict
+        ``{filename: MSMeasurement}``, processed or not.
+    lc_measurements : dict, optional
+        ``{filename: LCMeasurement}``.
+    compounds : sequence of Compound
+        The ion list (with calibration) the measurements were processed with.
+    settings : dict, optional
+        Processing settings to restore, e.g. the Model's mass_accuracy,
+        smoothing, ...
+
+    Returns
+    -------
+    Path
+        The file written.
+
+    Raises
+    ------
+    TypeError
+        If the state holds a value a session cannot store; nothing is
+        written then.
+    """
+    path = Path(path)
+    encode = _Encoder()
+    state = {
+        "format": SESSION_FORMAT,
+        "version": SESSION_VERSION,
+        "ms_measurements": encode(dict(ms_measurements)),
+        "lc_measurements": encode(dict(lc_measurements or {})),
+        "compounds": [encode(compound) for compound in compounds],
+        "settings": encode(dict(settings or {})),
+    }
+    members = dict(encode.arrays)
+    members[_STATE_MEMBER] = np.frombuffer(json.dumps(state).encode("utf-8"), dtype=np.uint8)
+    temporary = path.with_name(path.name + ".tmp")
+    with open(temporary, "wb") as f:
+        np.savez_compressed(f, **members)
+    os.replace(temporary, path)
+    logger.info(
+        f"Saved session with {len(ms_measurements)} MS and {len(lc_measurements or {})} LC "
+        f"files to {path} ({path.stat().st_size / 1e6:.1f} MB)"
+    )
+    return path
+
+
+def load_session(path, reopen: bool = True) -> Session:
+    """
+    Read a session file written by save_session.
+
+    Parameters
+    ----------
+    path : str or Path
+        The session file.
+    reopen : bool
+        Reopen the raw file reader (``data``) of every MS measurement whose
+        file still exists, for the views that read single scans.
+
+    Returns
+    -------
+    Session
+
+    Raises
+    ------
+    FileParseError
+        If the file is not a session file, is corrupt or was written by a
+        newer version.
+    """
+    try:
+        with open(path, "rb") as f:
+            magic = f.read(len(_ZIP_MAGIC))
+        if magic != _ZIP_MAGIC:
+            raise FileParseError(path, "not an LCMSpector session file")
+        with np.load(path, allow_pickle=False) as archive:
+            if _STATE_MEMBER not in archive.files:
+                raise FileParseError(path, "not an LCMSpector session file")
+            state = json.loads(archive[_STATE_MEMBER].tobytes().decode("utf-8"))
+            if not isinstance(state, dict) or state.get("format") != SESSION_FORMAT:
+                raise FileParseError(path, "not an LCMSpector session file")
+            if state.get("version", 0) > SESSION_VERSION:
+                raise FileParseError(
+                    path, f"written by a newer version (session format {state['version']})"
+                )
+            decode = _Decoder(archive)
+            session = Session(
+                ms_measurements=decode(state["ms_measurements"]),
+                lc_measurements=decode(state["lc_measurements"]),
+                compounds=tuple(decode(compound) for compound in state["compounds"]),
+                settings=decode(state["settings"]),
+            )
+    except FileParseError:
+        raise
+    except OSError as e:
+        raise FileParseError(path, f"cannot read session ({e})") from e
+    except (BadZipFile, ValueError, KeyError, TypeError) as e:
+        raise FileParseError(path, f"corrupt session file ({e})") from e
+
+    if reopen:
+        for name, measurement in session.ms_measurements.items():
+            if not os.path.exists(measurement.path):
+                logger.info(f"Raw file of {name} not found, single scans cannot be shown")
+                continue
+            try:
+                measurement.data = load_ms_data(measurement.path)
+            except Exception as e:
+                logger.warning(f"Cannot reopen {measurement.path}: {e}")
+    logger.info(f"Loaded session with {len(session.ms_measurements)} MS files from {path}")
+    return session
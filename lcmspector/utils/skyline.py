@@ -0,0 +1,275 @@
+"""
+Interchange with Skyline.
+
+Skyline transition lists (the CSV files of File > Import > Transition List
+and File > Export > Transition List) are read into an ion list in the
+config.json layout, so that a method built in Skyline can be processed here:
+
+    compounds = compounds_from_ion_list(read_skyline_transition_list("method.csv"))
+
+Rows are grouped by molecule (or peptide) name. Transitions whose product
+m/z equals the precursor m/z are MS1 precursor ions; a molecule with any
+other transition becomes an SRM/MRM entry with ``transitions``. Rows with a
+formula and adduct but no m/z are expanded from the formula.
+
+skyline_report lays the results out as a Skyline transition results report
+("Replicate Name", "Molecule Name", "Area", ...), one row per replicate and
+transition, so that they can be cross-checked against Skyline's own
+integration:
+
+    export_skyline_report(model.ms_measurements, "report.csv")
+
+Areas are baseline-corrected like Skyline's, which reports the baseline
+part as "Background"; missing values are written as ``#N/A``.
+"""
+
+import csv
+import logging
+from pathlib import Path
+from typing import Iterable, Mapping, Optional, Union
+
+import pandas as pd
+
+from utils.errors import IonListError
+from utils.manifest import batch_manifest, write_manifest
+from utils.mztab import _adduct, _measurements
+from utils.theoretical_spectrum import monoisotopic_mass, protonation_adduct
+
+logger = logging.getLogger(__name__)
+
+# Transition list columns read, by the accepted headers (compared without
+# case, spaces, "/" and "_")
+_TRANSITION_COLUMNS = {
+    "name": (
+        "Molecule Name", "Precursor Name", "Compound Name", "Peptide Modified Sequence",
+        "Peptide Sequence", "Peptide",
+    ),
+    "formula": ("Molecule Formula", "Molecular Formula", "Precursor Formula", "Formula"),
+    "adduct": ("Precursor Adduct", "Adduct"),
+    "precursor_mz": ("Precursor m/z", "Precursor Mz", "Q1"),
+    "precursor_charge": ("Precursor Charge", "Charge"),
+    "product_mz": ("Product m/z", "Product Mz", "Q3"),
+    "rt": ("Explicit Retention Time", "Retention Time", "RT"),
+    "rt_window": ("Explicit Retention Time Window", "Retention Time Window"),
+}
+
+SKYLINE_REPORT_COLUMNS = (
+    "Replicate Name", "File Name", "Molecule Name", "Precursor Adduct", "Precursor Mz",
+    "Precursor Charge", "Product Mz", "Retention Time", "Start Time", "End Time", "Area",
+    "Background", "Height", "Calculated Concentration",
+)
+_MISSING = "#N/A"
+
+
+def _normalize(header: str) -> str:
+    return "".join(c for c in str(header).lower() if c not in " /_")
+
+
+def _column_map(fieldnames) -> dict:
+    """{key: header} of the _TRANSITION_COLUMNS present, the first accepted header wins."""
+    present = {_normalize(name): name for name in fieldnames if name is not None}
+    columns = {}
+    for key, headers in _TRANSITION_COLUMNS.items():
+        for header in headers:
+            if _normalize(header) in present:
+                columns[key] = present[_normalize(header)]
+                break
+    return columns
+
+
+def _cell(row: dict, columns: dict, key: str) -> str:
+    return (row.get(columns[key]) or "").strip() if key in columns else ""
+
+
+def _number(value: str) -> Optional[float]:
+    value = (value or "").strip()
+    if not value or value == _MISSING:
+        return None
+    return float(value)
+
+
+def _adduct_label(adduct: str, charge: Optional[float]) -> str:
+    """Skyline adducts ("M+H", "[M+Na]", "[M+2H]2+") as ion list labels ("[M+H]+")."""
+    label = adduct.strip()
+    if not label.startswith("["):
+        label = f"[{label}]"
+    if label.endswith("]"):
+        z = int(charge) if charge else (-1 if label.startswith("[M-") else 1)
+        label += ("" if abs(z) == 1 else str(abs(z))) + ("+" if z > 0 else "-")
+    return label
+
+
+def read_skyline_transition_list(path) -> dict:
+    """
+    Read a Skyline transition list CSV as an ion list.
+
+    Parameters
+    ----------
+    path : str or Path
+        A CSV (or tab-separated) transition list with a header row, for
+        small molecules or peptides; see _TRANSITION_COLUMNS for the columns
+        read.
+
+    Returns
+    -------
+    dict
+        ``{name: entry}``, ready for utils.classes.compounds_from_ion_list.
+        Precursor ions are labelled with their adduct (or charge state), an
+        explicit retention time with a window becomes ``rt_min``/``rt_max`` and
+        negative charges a "negative" ``polarity``.
+
+    Raises
+    ------
+    IonListError
+        If the file has no molecule name column, or a row has no name, a
+        malformed number or formula, or neither an m/z nor a formula.
+    """
+    with open(path, "r", newline="") as f:
+        sample = f.read(4096)
+        f.seek(0)
+        delimiter = "\t" if sample.count("\t") > sample.count(",") else ","
+        reader = csv.DictReader(f, delimiter=delimiter)
+        columns = _column_map(reader.fieldnames or [])
+        if "name" not in columns:
+            raise IonListError(f"Skyline transition list '{path}' has no molecule name column")
+        rows = list(reader)
+
+    molecules = {}
+    for line, row in enumerate(rows, start=2):
+        name = _cell(row, columns, "name")
+        if not name:
+            raise IonListError(f"Row {line} of '{path}' has no molecule name")
+        try:
+            values = {
+                key: _number(_cell(row, columns, key))
+                for key in ("precursor_mz", "precursor_charge", "product_mz", "rt", "rt_window")
+            }
+        except ValueError as e:
+            raise IonListError(f"Row {line} of '{path}' ({name}): {e}") from None
+        formula = _cell(row, columns, "formula")
+        if values["precursor_mz"] is None and not formula:
+            raise IonListError(f"Row {line} of '{path}' ({name}) has neither an m/z nor a formula")
+        molecule = molecules.setdefault(name, {"rows": [], "formula": None, "rt": None})
+        molecule["rows"].append((values, _cell(row, columns, "adduct")))
+        molecule["formula"] = molecule["formula"] or formula or None
+        if values["rt"] is not None and molecule["rt"] is None:
+            molecule["rt"] = (values["rt"], values["rt_window"])
+
+    ion_list = {}
+    for name, molecule in molecules.items():
+        entry = {}
+        ions, info, transitions, adducts = [], [], [], []
+        negative = False
+        for values, adduct in molecule["rows"]:
+            q1, q3 = values["precursor_mz"], values["product_mz"]
+            charge = values["precursor_charge"]
+            negative = negative or (charge is not None and charge < 0)
+            if adduct:
+                label = _adduct_label(adduct, charge)
+                negative = negative or label.endswith("-")
+            elif charge:
+                label = protonation_adduct(
+                    abs(int(charge)), "positive" if charge > 0 else "negative"
+                ).label
+            else:
+                label = ""
+            if q1 is None:
+                if label and label not in adducts:
+                    adducts.append(label)
+                continue
+            if q3 is not None and abs(q3 - q1) > 1e-4:
+                transitions.append([q1, q3])
+            elif all(abs(q1 - mz) >= 1e-4 for mz in ions):
+                ions.append(q1)
+                info.append(label)
+        if transitions:
+            entry["transitions"] = transitions
+        elif ions:
+            entry["ions"], entry["info"] = ions, info
+        if molecule["formula"]:
+            entry["formula"] = molecule["formula"]
+            if adducts and transitions:
+                logger.warning(f"Skyline molecule {name}: rows without m/z beside SRM rows skipped")
+            elif adducts:
+                entry["adducts"] = adducts
+                if ions:  # Expanded from the formula's mass next to the listed ions
+                    try:
+                        entry["mass"] = monoisotopic_mass(molecule["formula"])
+                    except ValueError as e:
+                        raise IonListError(f"Skyline molecule {name}: {e}") from None
+        if molecule["rt"] is not None and molecule["rt"][1]:
+            rt, window = molecule["rt"]
+            entry["rt_min"], entry["rt_max"] = rt - window / 2, rt + window / 2
+        if negative:
+            entry["polarity"] = "negative"
+        ion_list[name] = entry
+    logger.info(f"Read {len(ion_list)} molecules from Skyline transition list {path}")
+    return ion_list
+
+
+def skyline_report(
+    measurements: Union[Mapping, Iterable], polarity: str = "positive"
+) -> pd.DataFrame:
+    """
+    One row per file and ion with the SKYLINE_REPORT_COLUMNS.
+
+    Parameters
+    ----------
+    measurements : dict or iterable of MSMeasurement
+        Processed measurements (``xics`` filled), e.g. the model's
+        ``ms_measurements``.
+    polarity : str
+        Polarity of ions whose compound sets none and whose label is not an
+        adduct, for the sign of their charge.
+    """
+    rows = []
+    for measurement in _measurements(measurements):
+        for compound in measurement.xics:
+            info = list(getattr(compound, "ion_info", []) or [])
+            transitions = getattr(compound, "transitions", None)
+            for index, (ion, data) in enumerate(compound.ions.items()):
+                adduct, charge = _adduct(
+                    info[index] if index < len(info) else None, compound.polarity or polarity
+                )
+                q1, q3 = transitions[index] if transitions else (float(ion), float(ion))
+                integration = data.get("Integration Data") or {}
+                area = integration.get("baseline_corrected_area")
+                total = integration.get("total_area")
+                rows.append(
+                    {
+                        "Replicate Name": Path(measurement.filename).stem,
+                        "File Name": measurement.filename,
+                        "Molecule Name": compound.name,
+                        "Precursor Adduct": adduct,
+                        "Precursor Mz": q1,
+                        "Precursor Charge": charge,
+                        "Product Mz": q3,
+                        "Retention Time": data.get("RT") if integration else None,
+                        "Start Time": integration.get("start_time"),
+                        "End Time": integration.get("end_time"),
+                        "Area": area,
+                        "Background": (
+                            total - area if total is not None and area is not None else None
+                        ),
+                        "Height": integration.get("peak_height"),
+                        "Calculated Concentration": compound.concentration,
+                    }
+                )
+    return pd.DataFrame(rows, columns=list(SKYLINE_REPORT_COLUMNS))
+
+
+def export_skyline_report(
+    measurements: Union[Mapping, Iterable], path, polarity: str = "positive"
+) -> Path:
+    """Write the skyline_report of processed measurements as CSV; returns the file written.
+
+    The manifest of the measurements (utils.manifest.batch_manifest) goes
+    into a ``.manifest.json`` sidecar.
+    """
+    path = Path(path)
+    measurements = _measurements(measurements)
+    report = skyline_report(measurements, polarity)
+    report.to_csv(path, index=False, na_rep=_MISSING)
+    write_manifest(path, batch_manifest(measurements))
+    logger.info(f"Exported {len(report)} transition results to Skyline report {path}")
+    return path
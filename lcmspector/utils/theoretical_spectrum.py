@@ -7,6 +7,7 @@ Supports molecular formulas, peptide sequences, and compound name lookup.
 """
 
 import logging
+import re
 from dataclasses import dataclass, field
 
 import numpy as np
@@ -153,6 +154,138 @@ for _defn in _adduct_list:
 
 DEFAULT_ADDUCTS = [k for k, v in ADDUCT_DEFINITIONS.items() if v.default_checked]
 
+ELECTRON_MASS = 0.000548579909
+
+# Highest charge of the [M+zH]z+ / [M-zH]z- series of charge_state_adducts
+MAX_CHARGE_STATE = 100
+
+_PROTONATION_LABEL = re.compile(r"^\[M([+-])(\d*)H\](\d*)([+-])$")
+
+
+def protonation_adduct(charge: int, polarity: str = "positive") -> AdductDefinition:
+    """The [M+zH]z+ (positive) or [M-zH]z- (negative) adduct of charge *charge*."""
+    if polarity == "positive":
+        label = "[M+H]+" if charge == 1 else f"[M+{charge}H]{charge}+"
+        return ADDUCT_DEFINITIONS.get(label) or AdductDefinition(
+            label, f"H{charge}", "", charge, polarity, 1, False
+        )
+    label = "[M-H]-" if charge == 1 else f"[M-{charge}H]{charge}-"
+    return ADDUCT_DEFINITIONS.get(label) or AdductDefinition(
+        label, "", f"H{charge}", charge, polarity, 1, False
+    )
+
+
+def adduct_definition(label: str | None) -> AdductDefinition | None:
+    """Definition of an adduct label: one of ADDUCT_DEFINITIONS or any
+    [M+zH]z+ / [M-zH]z- charge state; None for anything else."""
+    if not label:
+        return None
+    defn = ADDUCT_DEFINITIONS.get(label)
+    if defn is not None:
+        return defn
+    match = _PROTONATION_LABEL.match(label)
+    if match is None:
+        return None
+    sign, count, charge, polarity_sign = match.groups()
+    if sign != polarity_sign or (count or "1") != (charge or "1") or int(count or 1) < 1:
+        return None
+    return protonation_adduct(int(count or 1), "positive" if sign == "+" else "negative")
+
+
+def charge_state_adducts(low: int, high: int, polarity: str = "positive") -> list[str]:
+    """Labels of the [M+zH]z+ (or [M-zH]z-) adducts for z from *low* to *high*.
+
+    Raises
+    ------
+    ValueError
+        If the range is empty or outside 1..MAX_CHARGE_STATE.
+    """
+    if not 1 <= low <= high <= MAX_CHARGE_STATE:
+        raise ValueError(
+            f"Invalid charge range {low}-{high}, expected 1 <= low <= high <= {MAX_CHARGE_STATE}"
+        )
+    return [protonation_adduct(charge, polarity).label for charge in range(low, high + 1)]
+
+
+def monoisotopic_mass(formula: str) -> float:
+    """Neutral monoisotopic mass of a molecular formula (e.g. "C4H8O2").
+
+    Raises
+    ------
+    ValueError
+        If the formula is empty or cannot be parsed.
+    """
+    try:
+        comp = Composition(formula=formula.strip())
+        if comp:
+            return comp.mass()
+    except (PyteomicsError, KeyError, AttributeError) as e:
+        raise ValueError(f"Invalid formula '{formula}': {e}") from e
+    raise ValueError(f"Invalid formula '{formula}': no elements")
+
+
+def adduct_mz_from_mass(neutral_mass: float, defn: AdductDefinition) -> float:
+    """Monoisotopic m/z of an adduct of a neutral monoisotopic mass.
+
+    Unlike compute_adduct_mz, this needs no formula and accounts for the
+    electrons lost (positive) or gained (negative) on ionization.
+
+    Parameters
+    ----------
+    neutral_mass : float
+        Neutral monoisotopic mass of the molecule (Da).
+    defn : AdductDefinition
+        Adduct definition to apply.
+
+    Returns
+    -------
+    float
+        Monoisotopic m/z value.
+    """
+    mass = neutral_mass * defn.multiplier
+    if defn.add_formula:
+        mass += Composition(formula=defn.add_formula).mass()
+    if defn.subtract_formula:
+        mass -= Composition(formula=defn.subtract_formula).mass()
+    sign = 1 if defn.polarity == "positive" else -1
+    return (mass - sign * defn.charge * ELECTRON_MASS) / defn.charge
+
+
+def expand_adducts(
+    neutral_mass: float, adduct_types: list[str] | None = None
+) -> dict[str, float]:
+    """Dict of {adduct_label: monoisotopic_mz} for a neutral monoisotopic mass.
+
+    Parameters
+    ----------
+    neutral_mass : float
+        Neutral monoisotopic mass (Da).
+    adduct_types : list[str], optional
+        Adduct labels to compute, from ADDUCT_DEFINITIONS or charge states
+        such as "[M+3H]3+" (see adduct_definition). Default: ``DEFAULT_ADDUCTS``.
+
+    Returns
+    -------
+    dict[str, float]
+        Mapping of adduct label to rounded monoisotopic m/z, in input order.
+
+    Raises
+    ------
+    ValueError
+        If an adduct label is unknown.
+    """
+    if adduct_types is None:
+        adduct_types = DEFAULT_ADDUCTS
+    result = {}
+    for label in adduct_types:
+        defn = adduct_definition(label)
+        if defn is None:
+            raise ValueError(
+                f"Unknown adduct '{label}', expected one of {list(ADDUCT_DEFINITIONS)}"
+            )
+        result[label] = round(adduct_mz_from_mass(neutral_mass, defn), 4)
+    return result
+
 
 def compute_adduct_composition(base_comp: Composition, defn: AdductDefinition) -> Composition:
     """Build full composition for an adduct (base * multiplier + add - subtract).
@@ -221,6 +354,59 @@ def calculate_monoisotopic_mz(formula: str, adduct_types: list[str]) -> dict[str
     return result
 
 
+def isotope_ratios(
+    formula: str,
+    adduct_type: str | None = None,
+    n_isotopes: int = 2,
+    abundance_threshold: float = 1e-5,
+) -> np.ndarray:
+    """Predicted nominal isotope pattern ``[1.0, M+1/M, ..., M+n/M]``.
+
+    Isotopologues are grouped by nominal mass shift, so e.g. the 13C2 and
+    18O contributions both count towards M+2.
+
+    Parameters
+    ----------
+    formula : str
+        Molecular formula (e.g. "C8H10N4O2").
+    adduct_type : str, optional
+        Adduct label (see adduct_definition) whose atoms are included.
+    n_isotopes : int
+        Number of isotopologues after the monoisotopic one.
+    abundance_threshold : float
+        Minimum abundance of the isotopologues considered.
+
+    Returns
+    -------
+    np.ndarray
+        Relative abundances, length ``n_isotopes + 1``.
+
+    Raises
+    ------
+    ValueError
+        If the formula cannot be parsed.
+    """
+    try:
+        comp = Composition(formula=formula)
+    except (PyteomicsError, Exception) as e:
+        raise ValueError(f"Invalid formula '{formula}': {e}") from e
+    defn = adduct_definition(adduct_type)
+    if defn is not None:
+        comp = compute_adduct_composition(comp, defn)
+
+    mono_mass = comp.mass()
+    pattern = np.zeros(n_isotopes + 1)
+    for iso_comp, abundance in isotopologues(
+        composition=comp, report_abundance=True, overall_threshold=abundance_threshold
+    ):
+        shift = int(round(iso_comp.mass() - mono_mass))
+        if 0 <= shift <= n_isotopes:
+            pattern[shift] += abundance
+    if pattern[0] <= 0:
+        raise ValueError(f"No monoisotopic peak computed for '{formula}'")
+    return pattern / pattern[0]
+
+
 def is_valid_peptide(text: str) -> bool:
     """Check if text is a valid peptide sequence (standard 20 amino acids).
 
@@ -0,0 +1,62 @@
+"""
+Mass tolerances in relative (ppm) or absolute (mDa) units.
+
+The global ``mass_accuracy`` suits neither end of a wide mass range: a fixed
+ppm window gets very narrow for small ions, a fixed mDa one very wide for
+large ones. Compounds can therefore carry their own tolerance, given in the
+ion list as ``"5 ppm"``, ``"2 mDa"``, ``"0.002 Da"``, ``{"value": 5, "unit":
+"ppm"}`` or a bare number (ppm), and normalized to ``(value, unit)``.
+"""
+
+import logging
+import re
+from typing import Optional, Tuple
+
+logger = logging.getLogger(__name__)
+
+TOLERANCE_UNITS = ("ppm", "mDa")
+
+_TOLERANCE_PATTERN = re.compile(r"^\s*([0-9]*\.?[0-9]+(?:[eE][-+]?[0-9]+)?)\s*(ppm|mda|da)?\s*$", re.I)
+
+
+def parse_tolerance(value) -> Optional[Tuple[float, str]]:
+    """
+    Normalize a tolerance to ``(value, unit)`` with unit in TOLERANCE_UNITS.
+
+    Returns
+    -------
+    tuple or None
+        None for None or an empty string.
+
+    Raises
+    ------
+    ValueError
+        On an unknown unit, a malformed string or a non-positive value.
+    """
+    if value is None or (isinstance(value, str) and not value.strip()):
+        return None
+    if isinstance(value, dict):
+        value = (value.get("value"), value.get("unit", "ppm"))
+    if isinstance(value, (tuple, list)):
+        amount, unit = value
+        value = f"{amount} {unit}"
+    if isinstance(value, (int, float)):
+        amount, unit = float(value), "ppm"
+    else:
+        match = _TOLERANCE_PATTERN.match(str(value))
+        if match is None:
+            raise ValueError(f"Invalid mass tolerance '{value}', expected e.g. '5 ppm' or '2 mDa'")
+        amount, unit = float(match.group(1)), (match.group(2) or "ppm").lower()
+        if unit == "da":
+            amount, unit = amount * 1e3, "mda"
+        unit = "ppm" if unit == "ppm" else "mDa"
+    if amount <= 0:
+        raise ValueError(f"Mass tolerance must be positive, got {value}")
+    return amount, unit
+
+
+def tolerance_window(mz: float, tolerance: Tuple[float, str]) -> Tuple[float, float]:
+    """``(lower, upper)`` m/z bounds of a normalized tolerance around *mz*."""
+    amount, unit = tolerance
+    half_width = mz * amount * 1e-6 if unit == "ppm" else amount * 1e-3
+    return mz - half_width, mz + half_width
@@ -0,0 +1,84 @@
+#!/usr/bin/env python3
+"""
+Benchmark of the m/z window summation in XIC extraction.
+
+Compares calculation.preprocessing.sum_mz_windows (binary search plus
+vectorized running-total differences) with the per-window slice-and-sum
+loop it replaced, on synthetic centroided spectra. Run from the repository
+root:
+
+    python scripts/benchmark_extraction.py --peaks 2000 --targets 10 100 1000
+"""
+
+import argparse
+import sys
+import time
+from pathlib import Path
+
+import numpy as np
+
+sys.path.insert(0, str(Path(__file__).resolve().parent.parent / "lcmspector"))
+
+from calculation.preprocessing import (  # noqa: E402
+    _sum_mz_windows_loop,
+    mz_windows,
+    sum_mz_windows,
+)
+
+
+def _spectra(n_scans: int, n_peaks: int, seed: int = 0):
+    rng = np.random.default_rng(seed)
+    for _ in range(n_scans):
+        mz = np.sort(rng.uniform(100.0, 1500.0, n_peaks))
+        yield mz, rng.exponential(1e4, n_peaks)
+
+
+def _loop(mz, intensity, lower, upper):
+    left = np.searchsorted(mz, lower, side="left")
+    right = np.searchsorted(mz, upper, side="right")
+    return _sum_mz_windows_loop(intensity, left, right)
+
+
+def _time(function, spectra, lower, upper, repeats: int) -> float:
+    """Best time (s) of *repeats* passes over the spectra."""
+    best = float("inf")
+    for _ in range(repeats):
+        start = time.perf_counter()
+        for mz, intensity in spectra:
+            function(mz, intensity, lower, upper)
+        best = min(best, time.perf_counter() - start)
+    return best
+
+
+def main():
+    parser = argparse.ArgumentParser(description=__doc__.splitlines()[1])
+    parser.add_argument("--scans", type=int, default=500, help="Spectra per pass")
+    parser.add_argument("--peaks", type=int, default=2000, help="Peaks per spectrum")
+    parser.add_argument(
+        "--targets", type=int, nargs="+", default=[10, 100, 1000], help="Ion list lengths"
+    )
+    parser.add_argument("--repeats", type=int, default=3, help="Passes, the best is reported")
+    args = parser.parse_args()
+
+    spectra = list(_spectra(args.scans, args.peaks))
+    print(f"{args.scans} spectra of {args.peaks} peaks, best of {args.repeats}")
+    print(f"{'targets':>8} {'loop (ms)':>10} {'vectorized (ms)':>16} {'speedup':>8}")
+    for n_targets in args.targets:
+        targets = np.linspace(150.0, 1400.0, n_targets)
+        lower, upper = mz_windows(targets, 0.0001)
+        mz, intensity = spectra[0]
+        np.testing.assert_allclose(
+            sum_mz_windows(mz, intensity, lower, upper),
+            _loop(mz, intensity, lower, upper),
+            rtol=1e-5,
+        )
+        loop = _time(_loop, spectra, lower, upper, args.repeats)
+        vectorized = _time(sum_mz_windows, spectra, lower, upper, args.repeats)
+        print(
+            f"{n_targets:>8} {loop * 1e3:>10.1f} {vectorized * 1e3:>16.1f} "
+            f"{loop / vectorized:>7.1f}x"
+        )
+
+
+if __name__ == "__main__":
+    main()
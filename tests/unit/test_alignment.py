@@ -0,0 +1,111 @@
+"""
+Tests for calculation/alignment.py.
+
+Covers:
+- reference_rts() median RTs, the min_fraction cut and undetected peaks
+- RTShift linear, loess and constant shift functions
+- align_retention_times() setting "Aligned RT" per ion
+"""
+
+import numpy as np
+import pytest
+
+from calculation.alignment import RTShift, align_retention_times, reference_rts
+from utils.classes import Compound
+
+TRUE_RTS = {"Alanine": 1.0, "Leucine": 2.5, "Tyrosine": 4.0, "Tryptophan": 6.0}
+
+
+def _run(filename, drift=lambda rt: rt, skip=(), undetected=()):
+    """Compounds of one file; *skip* ones have no RT, *undetected* ones no peak."""
+    compounds = []
+    for index, (name, rt) in enumerate(TRUE_RTS.items()):
+        compound = Compound(name=name, target_list=[100.0 + index])
+        compound.file = filename
+        data = compound.ions[100.0 + index]
+        if name not in skip:
+            data["RT"] = drift(rt)
+            data["Integration Data"] = {"baseline_corrected_area": 1.0}
+            data["Peaks"] = [] if name in undetected else [0]
+        compounds.append(compound)
+    return compounds
+
+
+class TestReferenceRts:
+    def test_median_over_files(self):
+        results = [_run("a.mzML"), _run("b.mzML", lambda rt: rt + 0.2), _run("c.mzML")]
+        references = reference_rts(results)
+        assert references[("Leucine", 101.0)] == pytest.approx(2.5)
+
+    def test_min_fraction(self):
+        results = [_run("a.mzML"), _run("b.mzML", skip=("Alanine",))]
+        assert ("Alanine", 100.0) not in reference_rts(results)
+        assert ("Alanine", 100.0) in reference_rts(results, min_fraction=0.5)
+
+    def test_only_detected_peaks(self):
+        # An RT without a detected peak is the maximum of a noise trace
+        results = [_run("a.mzML"), _run("b.mzML", undetected=("Alanine",))]
+        assert ("Alanine", 100.0) not in reference_rts(results)
+        assert ("Leucine", 101.0) in reference_rts(results)
+
+
+class TestRTShift:
+    def test_linear_shift_extrapolates(self):
+        shift = RTShift("linear", anchors=[(1.1, 1.0), (2.2, 2.0), (3.3, 3.0)])
+        assert shift.correct(4.4) == pytest.approx(4.0)
+        np.testing.assert_allclose(shift.correct(np.array([1.1, 5.5])), [1.0, 5.0])
+
+    def test_loess_reproduces_linear_drift(self):
+        anchors = [(rt * 1.05 + 0.1, rt) for rt in [1.0, 2.0, 3.0, 4.0, 5.0, 6.0]]
+        shift = RTShift("loess", anchors=anchors)
+        for observed, reference in anchors:
+            assert shift.correct(observed) == pytest.approx(reference)
+
+    def test_loess_follows_non_linear_drift(self):
+        reference = np.linspace(1.0, 10.0, 10)
+        observed = reference + 0.05 * reference**2
+        shift = RTShift("loess", anchors=list(zip(observed, reference)), span=0.3)
+        linear = RTShift("linear", anchors=list(zip(observed, reference)))
+        loess_error = np.abs(shift.correct(observed) - reference).max()
+        linear_error = np.abs(linear.correct(observed) - reference).max()
+        assert loess_error < linear_error
+
+    def test_single_anchor_is_constant(self):
+        shift = RTShift("linear", anchors=[(2.3, 2.0)])
+        assert shift(1.0) == pytest.approx(-0.3)
+        assert shift(9.0) == pytest.approx(-0.3)
+        assert RTShift("loess")(5.0) == 0.0
+
+    def test_unknown_method_raises(self):
+        with pytest.raises(ValueError):
+            RTShift("dtw")
+
+
+class TestAlignRetentionTimes:
+    def test_aligns_drifted_file(self):
+        results = [
+            _run("a.mzML"),
+            _run("b.mzML", lambda rt: rt * 1.02 + 0.1),
+            _run("c.mzML"),
+        ]
+        shifts = align_retention_times(results)
+        assert set(shifts) == {"a.mzML", "b.mzML", "c.mzML"}
+        assert shifts["a.mzML"](3.0) == pytest.approx(0.0, abs=1e-9)
+        for compound in results[1]:
+            (ion,) = compound.ions.values()
+            assert ion["RT"] != pytest.approx(TRUE_RTS[compound.name])
+            assert ion["Aligned RT"] == pytest.approx(TRUE_RTS[compound.name])
+
+    def test_missing_peak_keeps_no_aligned_rt(self):
+        results = [_run("a.mzML"), _run("b.mzML", skip=("Alanine",))]
+        align_retention_times(results, min_fraction=0.5)
+        assert results[1][0].ions[100.0]["Aligned RT"] is None
+
+    def test_undetected_peak_is_no_anchor(self):
+        noisy = _run("b.mzML", lambda rt: rt + 0.2, undetected=("Alanine",))
+        noisy[0].ions[100.0]["RT"] = 5.0  # Noise maximum far from the peak
+        results = [_run("a.mzML"), noisy]
+        shifts = align_retention_times(results, min_fraction=0.5)
+        assert shifts["b.mzML"](2.5) == pytest.approx(-0.2)
+        # It is still aligned with the file's shift
+        assert noisy[0].ions[100.0]["Aligned RT"] == pytest.approx(4.8)
@@ -0,0 +1,90 @@
+"""
+Tests for spectral averaging in calculation/averaging.py.
+
+Covers:
+- Scan selection by retention time range and MS level
+- Profile binning ("bin") and centroid merging ("merge")
+- Files and LoadedRun-like sources
+- Argument validation
+"""
+
+import numpy as np
+import pytest
+
+from calculation import averaging
+from calculation.averaging import average_spectra
+
+SCANS = [
+    (0.5, 0.0, 1, np.array([100.0, 200.0]), np.array([9.0, 9.0])),
+    (1.0, 0.0, 1, np.array([100.001, 150.002]), np.array([2.0, 4.0])),
+    (1.1, 0.0, 2, np.array([50.0]), np.array([100.0])),
+    (1.2, 0.0, 1, np.array([100.003, 150.0025]), np.array([2.0, 2.0])),
+    (3.0, 0.0, 1, np.array([300.0]), np.array([9.0])),
+]
+
+
+class FakeRun:
+    def __init__(self):
+        self.selections = []
+
+    def scans(self, polarity=None, scan_filter=None):
+        self.selections.append((polarity, scan_filter))
+        return iter(SCANS)
+
+
+class TestAverageSpectra:
+    def test_bin(self):
+        mz, intensity, n_scans = average_spectra(FakeRun(), 0.9, 1.5, bin_width=0.01)
+        assert n_scans == 2
+        np.testing.assert_allclose(mz, [100.002, 150.002167], rtol=1e-7)
+        np.testing.assert_allclose(intensity, [2.0, 3.0])
+
+    def test_merge(self):
+        mz, intensity, n_scans = average_spectra(FakeRun(), 0.9, 1.5, method="merge", tolerance_ppm=5)
+        assert n_scans == 2
+        # 100.001 and 100.003 are 20 ppm apart, 150.002 and 150.0025 about 3 ppm
+        np.testing.assert_allclose(mz, [100.001, 100.003, 150.002167], rtol=1e-7)
+        np.testing.assert_allclose(intensity, [1.0, 1.0, 3.0])
+
+    def test_ms_level(self):
+        mz, intensity, n_scans = average_spectra(FakeRun(), 0.9, 1.5, ms_level=2)
+        assert n_scans == 1
+        np.testing.assert_allclose(mz, [50.0])
+        np.testing.assert_allclose(intensity, [100.0])
+
+    def test_empty_range(self):
+        mz, intensity, n_scans = average_spectra(FakeRun(), 2.0, 2.5)
+        assert n_scans == 0
+        assert len(mz) == len(intensity) == 0
+
+    def test_file(self, monkeypatch):
+        calls = []
+
+        def fake_iter_ms_scans(path, polarity=None, scan_filter=None):
+            calls.append((path, polarity))
+            return iter(SCANS)
+
+        monkeypatch.setattr(averaging, "iter_ms_scans", fake_iter_ms_scans)
+        mz, _, n_scans = average_spectra("run.mzML", 0.0, 10.0, polarity="positive")
+        assert calls == [("run.mzML", "positive")]
+        assert n_scans == 4
+        assert mz[-1] == pytest.approx(300.0)
+
+    def test_selection_passed_to_run(self):
+        run = FakeRun()
+        average_spectra(run, 0.0, 1.0, polarity="negative", scan_filter={"ms_level": 1})
+        assert run.selections == [("negative", {"ms_level": 1})]
+
+    @pytest.mark.parametrize(
+        "kwargs,message",
+        [
+            ({"method": "sum"}, "Unknown averaging method"),
+            ({"rt_min": 2.0, "rt_max": 1.0}, "greater than rt_max"),
+            ({"bin_width": 0}, "Bin width must be positive"),
+            ({"method": "merge", "tolerance_ppm": -1}, "Merge tolerance"),
+        ],
+    )
+    def test_invalid(self, kwargs, message):
+        arguments = {"rt_min": 0.0, "rt_max": 1.0, **kwargs}
+        with pytest.raises(ValueError, match=message):
+            average_spectra(FakeRun(), **arguments)
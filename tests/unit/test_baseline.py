@@ -0,0 +1,77 @@
+"""
+Tests for XIC baseline estimation in calculation/baseline.py.
+"""
+
+import numpy as np
+import pytest
+
+from calculation.baseline import (
+    estimate_baseline,
+    subtract_baseline,
+    validate_baseline,
+)
+
+
+@pytest.fixture
+def drifting_peak():
+    times = np.linspace(0, 10, 501)
+    drift = 100 + 10 * times
+    peak = 1000 * np.exp(-0.5 * ((times - 5.0) / 0.1) ** 2)
+    return times, drift, peak
+
+
+class TestEstimateBaseline:
+    @pytest.mark.parametrize(
+        "settings",
+        [{"method": "als"}, {"method": "rolling_min", "window": 51}],
+    )
+    def test_follows_linear_drift(self, drifting_peak, settings):
+        times, drift, peak = drifting_peak
+        baseline = estimate_baseline(drift + peak, **settings)
+        away = np.abs(times - 5.0) > 1.0
+        assert np.median(np.abs(baseline[away] - drift[away])) < 10
+        # Must stay well below the apex
+        assert baseline[250] < drift[250] + 100
+
+    def test_corrected_area_matches_peak(self, drifting_peak):
+        times, drift, peak = drifting_peak
+        signal = drift + peak
+        corrected = subtract_baseline(signal, estimate_baseline(signal, method="als"))
+        assert np.trapezoid(corrected, times) == pytest.approx(
+            np.trapezoid(peak, times), rel=0.1
+        )
+
+    def test_none_is_zero(self):
+        np.testing.assert_array_equal(
+            estimate_baseline(np.ones(5), method="none"), np.zeros(5)
+        )
+
+
+class TestSubtractBaseline:
+    def test_clips_and_keeps_dtype(self):
+        values = np.array([1.0, 5.0, 2.0], dtype=np.float32)
+        result = subtract_baseline(values, np.array([2.0, 2.0, 2.0]))
+        assert result.dtype == np.float32
+        np.testing.assert_array_equal(result, [0.0, 3.0, 0.0])
+
+
+class TestValidateBaseline:
+    def test_defaults(self):
+        assert validate_baseline({"method": "rolling_min"}) == {
+            "method": "rolling_min",
+            "window": 51,
+        }
+        assert validate_baseline(None)["method"] == "als"
+
+    @pytest.mark.parametrize(
+        "settings",
+        [
+            {"method": "polynomial"},
+            {"method": "als", "p": 1.5},
+            {"method": "als", "lam": 0},
+            {"method": "rolling_min", "window": 1},
+        ],
+    )
+    def test_invalid_settings_raise(self, settings):
+        with pytest.raises(ValueError):
+            validate_baseline(settings)
@@ -0,0 +1,108 @@
+"""
+Tests for calculation/blanks.py.
+
+Compounds are processed by construct_xics from scans fed through a
+monkeypatched iter_ms_scans, so the areas are the ones the pipeline stores.
+
+Covers:
+- blank_areas() mean over blank files
+- apply_blank_correction() flagging and subtraction
+"""
+
+import numpy as np
+import pytest
+
+from calculation import preprocessing
+from calculation.blanks import apply_blank_correction, blank_areas
+from calculation.preprocessing import construct_xics
+from utils.classes import Compound
+
+TIMES = np.arange(60) * 0.1
+
+
+def _run(monkeypatch, filename, heights):
+    """construct_xics output of a file with a Gaussian peak of the given height per compound."""
+    mz = 100.0 + np.arange(len(heights))
+    peak = np.exp(-0.5 * ((TIMES - 3.0) / 0.2) ** 2)
+    scan_heights = np.array([height or 0.0 for height in heights.values()])
+    scans = [(t, 0.0, 1, mz, scan_heights * p) for t, p in zip(TIMES, peak)]
+
+    def fake_iter_ms_scans(path, progress_callback=None, **filters):
+        yield from scans
+
+    monkeypatch.setattr(preprocessing, "iter_ms_scans", fake_iter_ms_scans)
+    compounds = [
+        Compound(name=name, target_list=[float(ion)]) for name, ion in zip(heights, mz)
+    ]
+    return list(construct_xics(filename, compounds))
+
+
+def _area(compound, ion):
+    return (compound.ions[ion]["Integration Data"] or {}).get("baseline_corrected_area", 0) or 0
+
+
+@pytest.fixture
+def results(monkeypatch):
+    return [
+        _run(monkeypatch, "blank_1.mzML", {"Caffeine": 1e4, "Phthalate": 4e5}),
+        _run(monkeypatch, "blank_2.mzML", {"Caffeine": None, "Phthalate": 6e5}),
+        _run(monkeypatch, "sample.mzML", {"Caffeine": 1e6, "Phthalate": 7e5}),
+    ]
+
+
+class TestBlankAreas:
+    def test_mean_counts_missing_peaks_as_zero(self, results):
+        areas = blank_areas(results[:2])
+        caffeine = _area(results[0][0], 100.0)
+        assert caffeine > 0
+        assert areas[("Caffeine", 100.0)] == pytest.approx(caffeine / 2, rel=0.05)
+        phthalate = np.mean([_area(results[k][1], 101.0) for k in (0, 1)])
+        assert areas[("Phthalate", 101.0)] == pytest.approx(phthalate)
+
+
+class TestApplyBlankCorrection:
+    def test_flags_low_ratio(self, results):
+        sample_area = _area(results[2][1], 101.0)
+        flagged = apply_blank_correction(results, {"blank_1.mzML", "blank_2.mzML"})
+        assert flagged == 1
+        caffeine, phthalate = (compound.ions for compound in results[2])
+        assert caffeine[100.0]["Blank"]["ratio"] == pytest.approx(200.0, rel=0.05)
+        assert caffeine[100.0]["Blank"]["below_threshold"] is False
+        assert phthalate[101.0]["Blank"]["below_threshold"] is True
+        # Flag mode leaves the areas alone, blanks get no annotation
+        assert _area(results[2][1], 101.0) == sample_area
+        assert results[0][0].ions[100.0]["Blank"] is None
+
+    def test_subtract(self, results):
+        blank_area = blank_areas(results[:2])[("Phthalate", 101.0)]
+        sample_area = _area(results[2][1], 101.0)
+        apply_blank_correction(results, {"blank_1.mzML", "blank_2.mzML"}, mode="subtract")
+        integration = results[2][1].ions[101.0]["Integration Data"]
+        assert integration["baseline_corrected_area"] == pytest.approx(sample_area - blank_area)
+        assert integration["blank_uncorrected_area"] == pytest.approx(sample_area)
+
+    def test_subtract_clips_at_zero(self, monkeypatch):
+        results = [
+            _run(monkeypatch, "blank.mzML", {"X": 5e5}),
+            _run(monkeypatch, "sample.mzML", {"X": 2e5}),
+        ]
+        apply_blank_correction(results, {"blank.mzML"}, mode="subtract")
+        assert _area(results[1][0], 100.0) == 0.0
+
+    def test_subtract_syncs_manual_integration(self, monkeypatch):
+        results = [
+            _run(monkeypatch, "blank.mzML", {"X": 1e5}),
+            _run(monkeypatch, "sample.mzML", {"X": 5e5}),
+        ]
+        data = results[1][0].ions[100.0]
+        data["MS Peak Area"] = data["Integration Data"].copy()
+        apply_blank_correction(results, {"blank.mzML"}, mode="subtract")
+        assert data["MS Peak Area"] == data["Integration Data"]
+
+    def test_without_blanks(self, results):
+        assert apply_blank_correction(results, set()) == 0
+        assert results[2][0].ions[100.0]["Blank"] is None
+
+    def test_unknown_mode_raises(self, results):
+        with pytest.raises(ValueError):
+            apply_blank_correction(results, {"blank_1.mzML"}, mode="divide")
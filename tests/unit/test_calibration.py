@@ -0,0 +1,148 @@
+"""
+Tests for calculation/calibration.py.
+
+Covers:
+- fit_calibration() linear, quadratic and weighted fits
+- fit_calibration() residuals, back-calculation and errors
+- back_calculate() root selection for quadratic curves
+- calculate_concentration() with quadratic parameters
+- detection_limits() from residual and blank SDs
+- Compound.below_loq flag
+- internal_standard_ratio() normalization
+"""
+
+import numpy as np
+import pytest
+
+from calculation.calc_conc import calculate_concentration
+from calculation.calibration import (
+    back_calculate,
+    detection_limits,
+    fit_calibration,
+    internal_standard_ratio,
+)
+from utils.classes import Compound
+
+
+class TestFitCalibration:
+    def test_linear_fit_recovers_line(self):
+        x = [0.0, 1.0, 2.0, 5.0, 10.0]
+        y = [2 * value + 1 for value in x]
+        fit = fit_calibration(x, y)
+        assert fit["model"] == "linear"
+        assert fit["slope"] == pytest.approx(2.0)
+        assert fit["intercept"] == pytest.approx(1.0)
+        assert fit["quadratic"] == 0.0
+        assert fit["r_squared"] == pytest.approx(1.0)
+        np.testing.assert_allclose(fit["residuals"], 0.0, atol=1e-9)
+        np.testing.assert_allclose(fit["back_calculated"], x, atol=1e-9)
+
+    def test_unweighted_r_value_matches_pearson(self):
+        x = [1.0, 2.0, 3.0, 4.0, 5.0]
+        y = [2.1, 3.9, 6.2, 7.8, 10.3]
+        fit = fit_calibration(x, y)
+        assert fit["r_value"] == pytest.approx(np.corrcoef(x, y)[0, 1])
+        assert fit["r_squared"] == pytest.approx(fit["r_value"] ** 2)
+        assert fit["std_err"] > 0
+
+    def test_quadratic_fit_recovers_curve(self):
+        x = [0.0, 1.0, 2.0, 3.0, 4.0]
+        y = [0.5 * value**2 + 2 * value + 1 for value in x]
+        fit = fit_calibration(x, y, model="quadratic")
+        assert fit["quadratic"] == pytest.approx(0.5)
+        assert fit["slope"] == pytest.approx(2.0)
+        assert fit["intercept"] == pytest.approx(1.0)
+        np.testing.assert_allclose(fit["back_calculated"], x, atol=1e-6)
+
+    def test_weighting_favours_low_standards(self):
+        x = [1.0, 2.0, 4.0, 8.0, 100.0]
+        # Top standard saturates away from y = 2x + 1
+        y = [3.0, 5.0, 9.0, 17.0, 300.0]
+        unweighted = fit_calibration(x, y)
+        weighted = fit_calibration(x, y, weighting="1/x2")
+        assert weighted["weighting"] == "1/x2"
+        low_error = abs(weighted["back_calculated"][0] - 1.0)
+        assert low_error < abs(unweighted["back_calculated"][0] - 1.0)
+
+    def test_weighting_tolerates_blank(self):
+        fit = fit_calibration([0.0, 1.0, 2.0], [1.0, 3.0, 5.0], weighting="1/x")
+        assert fit["slope"] == pytest.approx(2.0)
+
+    @pytest.mark.parametrize(
+        "kwargs",
+        [
+            {"model": "cubic"},
+            {"weighting": "1/y"},
+        ],
+    )
+    def test_unknown_options_raise(self, kwargs):
+        with pytest.raises(ValueError):
+            fit_calibration([1.0, 2.0, 3.0], [1.0, 2.0, 3.0], **kwargs)
+
+    def test_too_few_points_raise(self):
+        with pytest.raises(ValueError):
+            fit_calibration([1.0], [2.0])
+        with pytest.raises(ValueError):
+            fit_calibration([1.0, 2.0], [2.0, 4.0], model="quadratic")
+
+
+class TestBackCalculate:
+    def test_quadratic_picks_root_in_range(self):
+        params = {"quadratic": -0.1, "slope": 3.0, "intercept": 0.0}
+        # y = -0.1x² + 3x reaches y = 20 at x = 10 (rising) and x = 20 (past the apex)
+        assert back_calculate(20.0, params) == pytest.approx(10.0)
+
+    def test_unreachable_signal_is_nan(self):
+        params = {"quadratic": -0.1, "slope": 3.0, "intercept": 0.0}
+        assert np.isnan(back_calculate(100.0, params))
+
+    def test_calculate_concentration_quadratic(self):
+        params = {"quadratic": 0.5, "slope": 2.0, "intercept": 1.0}
+        assert calculate_concentration(17.0, params) == pytest.approx(4.0)
+        assert calculate_concentration(0.0, params) == 0
+
+
+class TestDetectionLimits:
+    def test_residual_sd(self):
+        x = [1.0, 2.0, 3.0, 4.0]
+        y = [2.0, 4.2, 5.8, 8.0]
+        fit = fit_calibration(x, y)
+        residuals = np.asarray(fit["residuals"])
+        sigma = np.sqrt(np.sum(residuals**2) / 2)
+        assert fit["residual_sd"] == pytest.approx(sigma)
+        limits = detection_limits(fit)
+        assert limits["lod_method"] == "residual"
+        assert limits["lod"] == pytest.approx(3.3 * sigma / fit["slope"])
+        assert limits["loq"] == pytest.approx(10 * sigma / fit["slope"])
+
+    def test_blank_replicates_take_precedence(self):
+        fit = fit_calibration([1.0, 2.0, 3.0], [2.0, 4.1, 5.9])
+        limits = detection_limits(fit, blank_signals=[0.1, 0.3, 0.2])
+        assert limits["lod_method"] == "blank"
+        assert limits["lod"] == pytest.approx(3.3 * 0.1 / fit["slope"])
+
+    def test_single_blank_falls_back_to_residuals(self):
+        fit = fit_calibration([1.0, 2.0, 3.0], [2.0, 4.1, 5.9])
+        assert detection_limits(fit, blank_signals=[0.2])["lod_method"] == "residual"
+
+    def test_exact_two_point_fit_has_no_limits(self):
+        limits = detection_limits(fit_calibration([1.0, 2.0], [2.0, 4.0]))
+        assert limits["lod"] is None and limits["loq"] is None
+
+    def test_compound_below_loq(self):
+        compound = Compound(name="caffeine", target_list=[195.0877])
+        assert compound.below_loq is None
+        compound.calibration_parameters = {"slope": 1.0, "intercept": 0.0, "loq": 0.5}
+        compound.concentration = 0.2
+        assert compound.below_loq is True
+        compound.concentration = 0.8
+        assert compound.below_loq is False
+
+
+class TestInternalStandardRatio:
+    def test_ratio(self):
+        assert internal_standard_ratio(500.0, 250.0) == pytest.approx(2.0)
+
+    @pytest.mark.parametrize("is_signal", [None, 0.0, -1.0, np.nan])
+    def test_missing_internal_standard(self, is_signal):
+        assert internal_standard_ratio(500.0, is_signal) is None
@@ -0,0 +1,139 @@
+"""
+Tests for ANDI/netCDF (AIA) support.
+
+Covers:
+- iter_scans() / read_chromatogram() / AndiMSReader in cdf_reader.py
+- detect_ms_format(), iter_ms_scans() and load_absorbance_data() dispatch in loading.py
+"""
+
+import numpy as np
+import pytest
+from scipy.io import netcdf_file
+
+from utils.cdf_reader import AndiMSReader, andi_kind, iter_scans, read_chromatogram
+from utils.loading import detect_ms_format, iter_ms2_scans, iter_ms_scans, load_absorbance_data
+
+
+def _write_andi_ms(path, polarity="Positive Polarity"):
+    with netcdf_file(path, "w") as dataset:
+        dataset.test_ionization_polarity = polarity
+        dataset.createDimension("scan_number", 3)
+        dataset.createDimension("point_number", 5)
+        for name, dtype, dimension, values in (
+            ("scan_acquisition_time", "d", "scan_number", [6.0, 12.0, 18.0]),
+            ("total_intensity", "d", "scan_number", [30.0, 5.0, 0.0]),
+            ("scan_index", "i", "scan_number", [0, 2, 5]),
+            ("point_count", "i", "scan_number", [2, 3, 0]),
+            ("mass_values", "d", "point_number", [100.0, 101.0, 100.0, 150.0, 200.0]),
+            ("intensity_values", "f", "point_number", [10.0, 20.0, 1.0, 2.0, 2.0]),
+        ):
+            variable = dataset.createVariable(name, dtype, (dimension,))
+            variable[:] = values
+    return path
+
+
+def _write_andi_chrom(path, with_times=False):
+    with netcdf_file(path, "w") as dataset:
+        dataset.detector_unit = "mAU"
+        dataset.createDimension("point_number", 4)
+        dataset.createDimension("_1", 1)
+        ordinate = dataset.createVariable("ordinate_values", "f", ("point_number",))
+        ordinate[:] = [0.0, 5.0, 12.5, 1.0]
+        if with_times:
+            times = dataset.createVariable("raw_data_retention", "f", ("point_number",))
+            times[:] = [0.0, 30.0, 90.0, 120.0]
+        else:
+            interval = dataset.createVariable("actual_sampling_interval", "f", ("_1",))
+            interval[:] = [30.0]
+            delay = dataset.createVariable("actual_delay_time", "f", ("_1",))
+            delay[:] = [60.0]
+    return path
+
+
+@pytest.fixture
+def ms_file(tmp_path):
+    return str(_write_andi_ms(tmp_path / "legacy_ms.cdf"))
+
+
+@pytest.fixture
+def chrom_file(tmp_path):
+    return str(_write_andi_chrom(tmp_path / "legacy_uv.cdf"))
+
+
+class TestAndiMS:
+    def test_iter_scans(self, ms_file):
+        scans = list(iter_scans(ms_file))
+        assert [scan[0] for scan in scans] == pytest.approx([0.1, 0.2, 0.3])
+        assert [scan[1] for scan in scans] == [30.0, 5.0, 0.0]
+        assert {scan[2] for scan in scans} == {1}
+        np.testing.assert_allclose(scans[1][3], [100.0, 150.0, 200.0])
+        np.testing.assert_allclose(scans[1][4], [1.0, 2.0, 2.0])
+        assert len(scans[2][3]) == 0
+
+    def test_polarity(self, ms_file):
+        assert len(list(iter_scans(ms_file, polarity="positive"))) == 3
+        assert list(iter_scans(ms_file, polarity="negative")) == []
+
+    def test_unknown_polarity_kept(self, tmp_path):
+        path = str(_write_andi_ms(tmp_path / "unknown.cdf", polarity=""))
+        assert len(list(iter_scans(path, polarity="negative"))) == 3
+
+    def test_with_mobility(self, ms_file):
+        assert all(len(scan) == 6 and scan[5] is None for scan in iter_scans(ms_file, with_mobility=True))
+
+    def test_reader(self, ms_file):
+        reader = AndiMSReader(ms_file)
+        assert len(reader) == 3
+        spectrum = reader.time[0.19]
+        assert spectrum["ms level"] == 1
+        np.testing.assert_allclose(spectrum["m/z array"], [100.0, 150.0, 200.0])
+        assert [s["index"] for s in reader.time[0.15:0.35]] == [1, 2]
+
+    def test_not_ms(self, chrom_file):
+        with pytest.raises(ValueError, match="Not an ANDI-MS file"):
+            list(iter_scans(chrom_file))
+
+
+class TestAndiChrom:
+    def test_sampling_interval(self, chrom_file):
+        data = read_chromatogram(chrom_file)
+        np.testing.assert_allclose(data["Time (min)"], [1.0, 1.5, 2.0, 2.5])
+        np.testing.assert_allclose(data["Value (mAU)"], [0.0, 5.0, 12.5, 1.0])
+
+    def test_retention_times(self, tmp_path):
+        data = read_chromatogram(_write_andi_chrom(tmp_path / "timed.cdf", with_times=True))
+        np.testing.assert_allclose(data["Time (min)"], [0.0, 0.5, 1.5, 2.0])
+
+    def test_tic_of_ms_file(self, ms_file):
+        data = read_chromatogram(ms_file)
+        np.testing.assert_allclose(data["Value (mAU)"], [30.0, 5.0, 0.0])
+
+    def test_kind(self, ms_file, chrom_file):
+        assert andi_kind(ms_file) == "ms"
+        assert andi_kind(chrom_file) == "chromatogram"
+
+    def test_not_netcdf(self, tmp_path):
+        path = tmp_path / "text.cdf"
+        path.write_text("0.0,1.0\n")
+        with pytest.raises(ValueError, match="Not a netCDF file"):
+            read_chromatogram(str(path))
+
+
+class TestLoadingDispatch:
+    def test_detect_by_extension_and_header(self, ms_file, tmp_path):
+        assert detect_ms_format(ms_file) == "ANDI"
+        renamed = tmp_path / "renamed.dat"
+        renamed.write_bytes(open(ms_file, "rb").read())
+        assert detect_ms_format(str(renamed)) == "ANDI"
+
+    def test_iter_ms_scans(self, ms_file):
+        progress = []
+        scans = list(iter_ms_scans(ms_file, progress_callback=progress.append))
+        assert len(scans) == 3
+        assert progress[-1] == 1.0
+        assert list(iter_ms2_scans(ms_file)) == []
+
+    def test_load_absorbance_data(self, chrom_file):
+        data = load_absorbance_data(chrom_file)
+        assert list(data.columns) == ["Time (min)", "Value (mAU)"]
+        assert len(data) == 4
@@ -0,0 +1,59 @@
+"""
+Tests for profile-to-centroid conversion in calculation/centroiding.py.
+"""
+
+import numpy as np
+import pytest
+
+from calculation.centroiding import centroid_spectrum
+
+
+def _profile(centers, heights, sigma=0.005, step=0.001, lo=99.9, hi=101.1):
+    mz = np.arange(lo, hi, step)
+    intensity = np.zeros_like(mz)
+    for center, height in zip(centers, heights):
+        intensity += height * np.exp(-0.5 * ((mz - center) / sigma) ** 2)
+    return mz, intensity
+
+
+class TestCentroidSpectrum:
+    @pytest.mark.parametrize("method", ["local_max", "gaussian"])
+    def test_recovers_peak_positions(self, method):
+        mz, intensity = _profile([100.0003, 100.5012], [1e5, 3e4])
+        c_mz, c_int = centroid_spectrum(mz, intensity, method=method, min_intensity=1.0)
+        assert len(c_mz) == 2
+        np.testing.assert_allclose(c_mz, [100.0003, 100.5012], atol=2e-4)
+        assert c_int[0] > c_int[1]
+
+    def test_intensity_is_conserved(self):
+        mz, intensity = _profile([100.2, 100.21], [1e4, 5e3], sigma=0.002)
+        c_mz, c_int = centroid_spectrum(mz, intensity)
+        assert c_int.sum() == pytest.approx(intensity.sum())
+
+    def test_adjacent_peaks_split_shared_valley(self):
+        mz = np.arange(9, dtype=np.float64)
+        intensity = np.array([0, 1, 3, 1, 0, 2, 5, 2, 0], dtype=np.float64)
+        c_mz, c_int = centroid_spectrum(mz, intensity)
+        np.testing.assert_allclose(c_int, [5, 9])
+        np.testing.assert_allclose(c_mz, [2.0, 6.0])
+
+    def test_min_intensity_filters(self):
+        mz = np.arange(9, dtype=np.float64)
+        intensity = np.array([0, 1, 3, 1, 0, 2, 5, 2, 0], dtype=np.float64)
+        c_mz, _ = centroid_spectrum(mz, intensity, min_intensity=6)
+        np.testing.assert_allclose(c_mz, [6.0])
+
+    def test_preserves_dtypes(self):
+        mz = np.linspace(100, 101, 50, dtype=np.float64)
+        intensity = np.sin(np.linspace(0, 3, 50)).astype(np.float32)
+        c_mz, c_int = centroid_spectrum(mz, intensity)
+        assert c_mz.dtype == np.float64
+        assert c_int.dtype == np.float32
+
+    def test_empty_spectrum(self):
+        c_mz, c_int = centroid_spectrum(np.array([]), np.array([]))
+        assert len(c_mz) == 0 and len(c_int) == 0
+
+    def test_unknown_method_raises(self):
+        with pytest.raises(ValueError):
+            centroid_spectrum(np.arange(5.0), np.ones(5), method="wavelet")
@@ -0,0 +1,76 @@
+"""
+Tests for charge states from isotope spacing in calculation/charge.py.
+
+Covers:
+- charge_from_isotopes() on singly and multiply charged envelopes, no isotopes
+- ion_charge_state() at the apex and the mismatch flag
+- peak_charge_states() using the charge of the ion's adduct label
+"""
+
+import numpy as np
+import pytest
+
+from calculation.charge import charge_from_isotopes, ion_charge_state, peak_charge_states
+from calculation.isotopes import ISOTOPE_SPACING
+from utils.classes import compounds_from_ion_list
+
+ION = 500.25
+
+
+def _envelope(mono, charge, n_peaks=4):
+    mz = mono + np.arange(n_peaks) * ISOTOPE_SPACING / charge
+    return mz, 100.0 * 0.6 ** np.arange(n_peaks)
+
+
+class _Times:
+    def __getitem__(self, rt):
+        mz, intensity = _envelope(ION, 2)
+        # A singly charged neighbour must not confuse the doubly charged ion
+        other_mz, other_intensity = _envelope(600.3, 1, 3)
+        return {
+            "m/z array": np.concatenate((other_mz, mz)),
+            "intensity array": np.concatenate((other_intensity, intensity)),
+        }
+
+
+class _Reader:
+    time = _Times()
+
+
+def _data(rt=1.0):
+    return {"RT": rt, "Integration Data": {"start_time": 0.9, "end_time": 1.1}}
+
+
+class TestChargeFromIsotopes:
+    @pytest.mark.parametrize("charge", [1, 2, 3, 4])
+    def test_spacing(self, charge):
+        mz, intensity = _envelope(ION, charge)
+        assert charge_from_isotopes(mz, intensity, ION) == (charge, 3)
+
+    def test_no_isotopes(self):
+        assert charge_from_isotopes(np.array([ION, ION + 0.7]), np.ones(2), ION) == (None, 0)
+        with pytest.raises(ValueError, match="Maximum charge"):
+            charge_from_isotopes(np.array([ION]), np.ones(1), ION, max_charge=0)
+
+
+class TestIonChargeState:
+    def test_mismatch(self):
+        state = ion_charge_state(_Reader(), ION, _data(), expected=1, mass_accuracy=1e-5)
+        assert state == {"charge": 2, "n_isotopes": 3, "expected": 1, "mismatch": True}
+
+    def test_without_peak(self):
+        assert ion_charge_state(_Reader(), ION, {"RT": 1.0}) is None
+        assert ion_charge_state(_Reader(), 250.0, _data()) is None
+
+
+class TestPeakChargeStates:
+    def test_expected_from_adduct(self):
+        compounds = compounds_from_ion_list(
+            {"Peptide": {"ions": [ION, 600.3], "info": ["[M+2H]2+", "[M+H]+"]}}
+        )
+        for data in compounds[0].ions.values():
+            data.update(_data())
+        assert peak_charge_states(compounds, _Reader(), mass_accuracy=1e-5) == 2
+        states = [data["Charge State"] for data in compounds[0].ions.values()]
+        assert [state["charge"] for state in states] == [2, 1]
+        assert not any(state["mismatch"] for state in states)
@@ -0,0 +1,104 @@
+"""
+Tests for the local compound database in utils/compound_db.py.
+
+Covers:
+- CompoundDatabase.add() and import_table() of CSV / TSV subsets
+- Accurate mass and m/z lookups, by name, formula and InChIKey
+- Ion list entries from the database
+- annotate_features() of plain and grouped feature tables
+"""
+
+import pandas as pd
+import pytest
+
+from utils.classes import compounds_from_ion_list
+from utils.compound_db import ANNOTATION_COLUMNS, CompoundDatabase, annotate_features
+
+CAFFEINE_KEY = "RYYVLZVUVIJVGH-UHFFFAOYSA-N"
+
+
+@pytest.fixture
+def database():
+    with CompoundDatabase() as database:
+        database.add(
+            [
+                {"name": "Caffeine", "formula": "C8H10N4O2", "inchikey": CAFFEINE_KEY},
+                {"name": "Theophylline", "formula": "C7H8N4O2", "source": "HMDB"},
+                {"name": "Paraxanthine", "formula": "C7H8N4O2", "monoisotopic_mass": 180.0647},
+            ]
+        )
+        yield database
+
+
+class TestCompoundDatabase:
+    def test_add(self, database):
+        assert len(database) == 3
+        (caffeine,) = database.by_name("caffeine")
+        assert caffeine["monoisotopic_mass"] == pytest.approx(194.0804, abs=1e-4)
+        with pytest.raises(ValueError, match="neither a mass nor a formula"):
+            database.add([{"name": "Unknown"}])
+        with pytest.raises(ValueError, match="without a name"):
+            database.add([{"formula": "C2H6O"}])
+
+    def test_search_mass(self, database):
+        hits = database.search_mass(180.0650, ppm=5.0)
+        assert [hit["name"] for hit in hits] == ["Theophylline", "Paraxanthine"]
+        assert hits[0]["error_ppm"] == pytest.approx(1.5, abs=0.5)
+        assert database.search_mass(180.0650, ppm=0.1) == []
+        assert len(database.search_mass(180.0650, limit=1)) == 1
+
+    def test_search_mz(self, database):
+        hits = database.search_mz(195.0877, adducts=["[M+H]+", "[M+Na]+"])
+        assert [(hit["name"], hit["adduct"]) for hit in hits] == [("Caffeine", "[M+H]+")]
+
+    def test_lookups(self, database):
+        assert len(database.by_formula("C7H8N4O2")) == 2
+        assert database.by_inchikey(CAFFEINE_KEY.lower())[0]["name"] == "Caffeine"
+        assert database.by_inchikey("RYYVLZVUVIJVGH")[0]["name"] == "Caffeine"
+
+    def test_ion_list(self, database):
+        ion_list = database.ion_list(["caffeine"], adducts=["[M+H]+"])
+        (compound,) = compounds_from_ion_list(ion_list)
+        assert compound.name == "Caffeine"
+        assert compound.target_list == pytest.approx([195.0877], abs=1e-4)
+        with pytest.raises(KeyError):
+            database.ion_list(["Unknown"])
+
+    def test_import_table(self, tmp_path):
+        path = tmp_path / "hmdb.tsv"
+        path.write_text(
+            "HMDB_ID\tNAME\tCHEMICAL_FORMULA\tMONISOTOPIC_MOLECULAR_WEIGHT\tINCHIKEY\n"
+            f"HMDB0001847\tCaffeine\tC8H10N4O2\t194.080375584\t{CAFFEINE_KEY}\n"
+            "HMDB0001889\tTheophylline\tC7H8N4O2\t\t\n"
+        )
+        with CompoundDatabase(tmp_path / "compounds.sqlite") as database:
+            assert database.import_table(path, source="HMDB") == 2
+            (theophylline,) = database.by_name("Theophylline")
+        assert theophylline["accession"] == "HMDB0001889"
+        assert theophylline["source"] == "HMDB"
+        assert theophylline["monoisotopic_mass"] == pytest.approx(180.0647, abs=1e-4)
+        with CompoundDatabase(tmp_path / "compounds.sqlite") as database:
+            assert len(database) == 2
+
+    def test_import_table_without_names_raises(self, tmp_path):
+        path = tmp_path / "masses.csv"
+        path.write_text("mass\n194.08\n")
+        with CompoundDatabase() as database, pytest.raises(ValueError, match="no name column"):
+            database.import_table(path)
+
+
+class TestAnnotateFeatures:
+    def test_plain_features(self, database):
+        features = pd.DataFrame({"mz": [195.0877, 300.0], "rt": [1.0, 2.0]})
+        table = annotate_features(features, database)
+        assert list(table.columns) == ["mz", "rt", *ANNOTATION_COLUMNS]
+        assert table.db_name.tolist() == ["Caffeine", None]
+        assert table.db_adduct.iloc[0] == "[M+H]+"
+        assert "db_name" not in features
+
+    def test_grouped_features(self, database):
+        features = pd.DataFrame(
+            {"mz": [217.0696], "rt": [1.0], "adduct": ["[M+Na]+"], "neutral_mass": [194.0804]}
+        )
+        table = annotate_features(features, database)
+        assert (table.db_name.iloc[0], table.db_adduct.iloc[0]) == ("Caffeine", "[M+Na]+")
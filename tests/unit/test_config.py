@@ -0,0 +1,85 @@
+"""
+Tests for the processing settings in calculation/config.py.
+
+Covers:
+- ProcessingConfig defaults and validated, normalized options
+- from_dict() and from_toml(), unknown and invalid options
+- coerce() and xic_options()
+- ProcessingWorker taking a config and keeping the effective one
+"""
+
+import pytest
+
+from calculation.config import XIC_OPTIONS, ProcessingConfig
+from calculation.smoothing import DEFAULT_SMOOTHING
+
+
+class TestProcessingConfig:
+    def test_defaults(self):
+        config = ProcessingConfig()
+        assert config.mass_accuracy == 0.0001
+        assert config.xic_mode == "sum" and config.precision == "float32"
+        assert config.file_ranges == {} and config.qc is False
+
+    def test_settings_are_normalized(self):
+        config = ProcessingConfig.from_dict(
+            {"smoothing": {"window": 7}, "qc": {"min_scan_rate": 1}}
+        )
+        assert config.smoothing == {**DEFAULT_SMOOTHING, "window": 7}
+        assert config.qc["min_scan_rate"] == 1
+        assert "max_mass_error_ppm" in config.qc
+
+    @pytest.mark.parametrize(
+        "options, match",
+        [
+            ({"mass_acuracy": 0.0001}, "mass_acuracy"),
+            ({"mass_accuracy": -1.0}, "mass_accuracy"),
+            ({"xic_mode": "mean"}, "Unknown XIC mode"),
+            ({"rt_alignment": "cubic"}, "Unknown RT alignment method"),
+            ({"polarity": "neutral"}, "Unknown polarity"),
+            ({"file_ranges": {"a.mzML": {"rt_range": (5.0, 1.0)}}}, "rt_range"),
+            ({"memory_budget": "lots"}, "Cannot parse memory size"),
+        ],
+    )
+    def test_invalid(self, options, match):
+        with pytest.raises(ValueError, match=match):
+            ProcessingConfig.from_dict(options)
+
+    def test_from_toml(self, tmp_path):
+        path = tmp_path / "method.toml"
+        path.write_text('mass_accuracy = 5e-6\nxic_mode = "max"\nsmoothing = { window = 9 }\n')
+        config = ProcessingConfig.from_toml(path)
+        assert config.mass_accuracy == 5e-6 and config.xic_mode == "max"
+        assert config.smoothing["window"] == 9
+        assert ProcessingConfig.coerce(str(path)) == config
+
+    def test_coerce_and_round_trip(self):
+        config = ProcessingConfig(isotopes=2)
+        assert ProcessingConfig.coerce(config) is config
+        assert ProcessingConfig.coerce(config.to_dict()) == config
+        assert ProcessingConfig.coerce(None) == ProcessingConfig()
+
+    def test_xic_options(self):
+        options = ProcessingConfig(link_ms2=True).xic_options()
+        assert tuple(options) == XIC_OPTIONS
+        assert options["link_ms2"] is True
+        assert options["mass_errors"] is False
+        # The QC median mass error needs the ions' mass errors
+        assert ProcessingConfig(qc=True).xic_options()["mass_errors"] is True
+
+
+class TestWorkerConfig:
+    def test_config_replaces_keywords(self):
+        from calculation.workers import ProcessingWorker
+
+        worker = ProcessingWorker(None, "MS Only", config={"xic_mode": "nearest", "isotopes": 1})
+        assert worker.xic_mode == "nearest" and worker.isotopes == 1
+        assert worker.config.to_dict()["xic_mode"] == "nearest"
+
+    def test_keywords_are_validated(self):
+        from calculation.workers import ProcessingWorker
+
+        worker = ProcessingWorker(None, "MS Only", 5e-6, gap_filling=True)
+        assert worker.config.mass_accuracy == 5e-6 and worker.config.gap_filling
+        with pytest.raises(ValueError, match="Unknown blank mode"):
+            ProcessingWorker(None, "MS Only", blank_mode="ignore")
@@ -216,6 +216,32 @@ class TestIonTable:
         assert items[0].target_list == []
         assert items[0].ion_info == []
 
+    def test_get_items_parses_tolerance(self, ion_table):
+        """get_items parses a loaded tolerance into the compound's mass_tolerance."""
+        ion_table.setRowCount(1)
+        ion_table.setItem(0, 0, QTableWidgetItem("Caffeine"))
+        ion_table.setItem(0, 1, QTableWidgetItem("195.0877, 217.0696"))
+        ion_table._compound_options["Caffeine"] = {"tolerance": "5 ppm"}
+
+        items = ion_table.get_items()
+
+        assert items[0].mass_tolerance == [(5.0, "ppm"), (5.0, "ppm")]
+
+    def test_get_items_keeps_expected_retention(self, ion_table):
+        """get_items carries the loaded rt and ri to the compound."""
+        ion_table.setRowCount(1)
+        ion_table.setItem(0, 0, QTableWidgetItem("Caffeine"))
+        ion_table.setItem(0, 1, QTableWidgetItem("195.0877"))
+        ion_table._compound_options["Caffeine"] = {
+            "rt": 4.2, "ri": 1250.0, "internal_standard": "Caffeine-d9"
+        }
+
+        (compound,) = ion_table.get_items()
+
+        assert compound.expected_rt == 4.2
+        assert compound.retention_index == 1250.0
+        assert compound.internal_standard == "Caffeine-d9"
+
     def test_clear_table(self, ion_table):
         """Table can be cleared."""
         ion_table.setRowCount(5)
@@ -0,0 +1,87 @@
+"""
+Tests for calculation/deconvolution.py.
+
+Covers:
+- validate_deconvolution() defaults and errors
+- deisotope() envelope and charge assignment
+- deconvolute_spectrum() merging of charge states to neutral masses
+"""
+
+import numpy as np
+import pytest
+
+from calculation.deconvolution import (
+    PROTON_MASS,
+    deconvolute_spectrum,
+    deisotope,
+    singly_charged_mz,
+    validate_deconvolution,
+)
+from calculation.isotopes import ISOTOPE_SPACING
+
+NEUTRAL_MASS = 1000.0
+
+
+def _envelope(charge, intensities):
+    mono = (NEUTRAL_MASS + charge * PROTON_MASS) / charge
+    return [
+        (mono + k * ISOTOPE_SPACING / charge, intensity)
+        for k, intensity in enumerate(intensities)
+    ]
+
+
+def _spectrum():
+    """[M+H]+ and [M+2H]2+ envelopes of a 1000 Da molecule plus a lone peak at m/z 300."""
+    peaks = sorted(_envelope(1, [100.0, 60.0, 20.0]) + _envelope(2, [200.0, 150.0, 50.0]))
+    peaks.append((300.0, 10.0))
+    mz, intensity = zip(*sorted(peaks))
+    return np.array(mz), np.array(intensity)
+
+
+class TestValidateDeconvolution:
+    def test_defaults(self):
+        assert validate_deconvolution(None) == {"ppm": 10.0, "max_charge": 6, "min_isotopes": 2}
+
+    @pytest.mark.parametrize(
+        "settings", [{"ppm": 0}, {"max_charge": 0}, {"min_isotopes": 1}]
+    )
+    def test_invalid_raises(self, settings):
+        with pytest.raises(ValueError):
+            validate_deconvolution(settings)
+
+
+class TestDeisotope:
+    def test_assigns_charges(self):
+        envelopes = deisotope(*_spectrum())
+        by_charge = {charge: (mz, total) for mz, charge, total in envelopes if mz > 400}
+        assert by_charge[2][0] == pytest.approx((NEUTRAL_MASS + 2 * PROTON_MASS) / 2)
+        assert by_charge[2][1] == pytest.approx(400.0)
+        assert by_charge[1][0] == pytest.approx(NEUTRAL_MASS + PROTON_MASS)
+        assert by_charge[1][1] == pytest.approx(180.0)
+
+    def test_lone_peak_is_singly_charged(self):
+        envelopes = deisotope(*_spectrum())
+        assert (300.0, 1, 10.0) in envelopes
+
+
+class TestDeconvoluteSpectrum:
+    def test_merges_charge_states(self):
+        masses, intensities = deconvolute_spectrum(*_spectrum())
+        assert len(masses) == 2
+        assert masses[1] == pytest.approx(NEUTRAL_MASS, abs=1e-6)
+        assert intensities[1] == pytest.approx(580.0)
+        assert masses[0] == pytest.approx(300.0 - PROTON_MASS)
+
+    def test_negative_mode(self):
+        mz = np.array([1000.0 - PROTON_MASS, 1000.0 - PROTON_MASS + ISOTOPE_SPACING])
+        masses, _ = deconvolute_spectrum(mz, np.array([100.0, 50.0]), polarity="negative")
+        assert masses[0] == pytest.approx(1000.0)
+
+    def test_singly_charged_round_trip(self):
+        assert singly_charged_mz(np.array([NEUTRAL_MASS]))[0] == pytest.approx(
+            NEUTRAL_MASS + PROTON_MASS
+        )
+
+    def test_empty_spectrum(self):
+        masses, intensities = deconvolute_spectrum(np.array([]), np.array([]))
+        assert len(masses) == 0 and len(intensities) == 0
@@ -0,0 +1,94 @@
+"""
+Tests for decoy-based FDR estimation in calculation/decoys.py.
+
+Covers:
+- decoy_library() precursor-shifted and fragment-shuffled decoys
+- q_values() and fdr_threshold() from target and decoy scores
+- match_with_decoys() on a small library
+"""
+
+import numpy as np
+import pytest
+
+from calculation.decoys import (
+    DECOY_PREFIX,
+    decoy_library,
+    fdr_threshold,
+    match_with_decoys,
+    q_values,
+)
+from utils.library import SpectralLibrary
+
+
+@pytest.fixture
+def library():
+    return SpectralLibrary.from_spectra(
+        [
+            {"precursor_mz": 195.0877, "mz": np.array([110.0713, 138.0662]),
+             "intensity": np.array([40.0, 100.0]), "metadata": {"name": "Caffeine"}},
+            {"precursor_mz": 181.0720, "mz": np.array([96.0556, 124.0505]),
+             "intensity": np.array([30.0, 100.0]), "metadata": {"name": "Theophylline"}},
+            {"precursor_mz": None, "mz": np.array([50.0]), "intensity": np.ones(1),
+             "metadata": {"name": "No precursor"}},
+        ]
+    )
+
+
+class TestDecoyLibrary:
+    def test_precursor_shift(self, library):
+        decoys = decoy_library(library, shift=10.0)
+        assert len(decoys) == 2
+        np.testing.assert_allclose(decoys.precursor_mz, [191.0720, 205.0877])
+        np.testing.assert_array_equal(decoys.spectrum(0)["mz"], library.spectrum(0)["mz"])
+        assert decoys.metadata[0]["name"] == f"{DECOY_PREFIX}Theophylline"
+        assert decoys.metadata[0]["decoy"] is True
+
+    def test_fragment_shuffle(self, library):
+        decoys = decoy_library(library, "fragment_shuffle", seed=1)
+        np.testing.assert_allclose(decoys.precursor_mz, library.precursor_mz[:2])
+        for index in range(2):
+            decoy, target = decoys.spectrum(index), library.spectrum(index)
+            assert np.all(np.isin(decoy["mz"], library.mz))
+            assert np.all(decoy["mz"] < decoy["precursor_mz"])
+            np.testing.assert_allclose(np.sort(decoy["intensity"]), np.sort(target["intensity"]))
+        again = decoy_library(library, "fragment_shuffle", seed=1)
+        np.testing.assert_array_equal(again.mz, decoys.mz)
+
+    def test_unknown_method_raises(self, library):
+        with pytest.raises(ValueError, match="Unknown decoy method"):
+            decoy_library(library, "reversed")
+
+
+class TestFdr:
+    def test_q_values(self):
+        q = q_values([0.9, 0.8, 0.5, 0.4], [0.6, 0.3])
+        np.testing.assert_allclose(q, [0.0, 0.0, 1 / 4, 1 / 4])
+
+    def test_q_values_are_monotone(self):
+        q = q_values([0.9, 0.7, 0.6, 0.5], [0.8, 0.4])
+        # 0.7 alone has an FDR of 1/2, but the threshold 0.5 accepts it at 1/4
+        np.testing.assert_allclose(q, [0.0, 1 / 4, 1 / 4, 1 / 4])
+
+    def test_threshold(self):
+        assert fdr_threshold([0.9, 0.8, 0.5, 0.4], [0.6, 0.3], fdr=0.01) == 0.8
+        assert fdr_threshold([0.9, 0.8, 0.5, 0.4], [0.6, 0.3], fdr=0.25) == 0.4
+        assert fdr_threshold([0.5], [0.9], fdr=0.5) is None
+        assert len(q_values([], [0.5])) == 0
+        with pytest.raises(ValueError):
+            fdr_threshold([0.5], [], fdr=2)
+
+
+def test_match_with_decoys(library):
+    spectra = [
+        {"precursor_mz": 195.0877, "mz": np.array([110.071, 138.066]),
+         "intensity": np.array([40.0, 100.0])},
+        {"precursor_mz": 181.0720, "mz": np.array([124.0505]), "intensity": np.ones(1)},
+        {"precursor_mz": 300.0, "mz": np.array([150.0]), "intensity": np.ones(1)},
+    ]
+    report = match_with_decoys(spectra, library, precursor_tolerance=0.01)
+    assert [(hit["spectrum"], hit["name"]) for hit in report["hits"]] == [
+        (0, "Caffeine"), (1, "Theophylline")
+    ]
+    assert report["decoy_scores"] == []
+    assert [hit["q_value"] for hit in report["hits"]] == [0.0, 0.0]
+    assert report["threshold"] == min(hit["score"] for hit in report["hits"])
@@ -0,0 +1,71 @@
+"""
+Tests for DIA pseudo-MS2 spectra in calculation/dia.py.
+
+Covers:
+- isolation_windows() of the MS2 scans
+- pseudo_ms2_spectrum() keeping the fragments coeluting with the precursor
+- pseudo_ms2_spectra() for several precursors, windows without precursor signal
+"""
+
+import numpy as np
+import pytest
+
+from calculation.dia import isolation_windows, pseudo_ms2_spectra, pseudo_ms2_spectrum
+
+CYCLES = 1.0 + 0.01 * np.arange(41)
+WINDOW = (195.0, 205.0)
+OTHER_WINDOW = (295.0, 305.0)
+
+
+def _profile(time, apex=1.2):
+    return 1000.0 * np.exp(-(((time - apex) / 0.05) ** 2) / 2)
+
+
+def _scans():
+    """iter_dia_scans tuples: one MS1 scan and two SWATH windows per cycle."""
+    scans = []
+    for time in CYCLES:
+        scans.append((time, 1, None, np.array([200.0, 300.0]), np.array([_profile(time), 10.0])))
+        # Two fragments of the precursor, one of an interference eluting later
+        ms2_time = time + 0.003
+        intensity = [_profile(ms2_time), _profile(ms2_time, 1.35), 0.5 * _profile(ms2_time)]
+        scans.append((ms2_time, 2, WINDOW, np.array([100.0, 120.0, 150.0]), np.array(intensity)))
+        scans.append(
+            (time + 0.006, 2, OTHER_WINDOW, np.array([80.0]), np.array([_profile(time + 0.006)]))
+        )
+    return scans
+
+
+def _split(scans):
+    ms1 = [(t, mz, i) for t, level, _, mz, i in scans if level == 1]
+    ms2 = [(t, w, mz, i) for t, level, w, mz, i in scans if level == 2]
+    return ms1, ms2
+
+
+class TestIsolationWindows:
+    def test_distinct_windows(self):
+        assert isolation_windows(_scans()) == [WINDOW, OTHER_WINDOW]
+
+
+class TestPseudoMs2Spectrum:
+    def test_coeluting_fragments(self):
+        spectrum = pseudo_ms2_spectrum(*_split(_scans()), precursor_mz=200.0, rt=1.2)
+        np.testing.assert_allclose(spectrum["mz"], [100.0, 150.0])
+        assert spectrum["intensity"][1] / spectrum["intensity"][0] == pytest.approx(0.5, rel=1e-3)
+        assert np.all(spectrum["correlation"] > 0.99)
+        assert spectrum["window"] == WINDOW
+        assert spectrum["scan_time"] == pytest.approx(1.2)
+
+    def test_no_precursor_signal(self):
+        assert pseudo_ms2_spectrum(*_split(_scans()), precursor_mz=250.0, rt=1.2) is None
+
+
+class TestPseudoMs2Spectra:
+    def test_several_precursors(self):
+        spectra = pseudo_ms2_spectra(_scans(), [(200.0, 1.2), (300.0, 1.2)], rt_window=0.2)
+        assert spectra[0]["precursor_mz"] == 200.0
+        # Flat precursor trace, nothing correlates with it
+        assert spectra[1] is None
+
+    def test_empty(self):
+        assert pseudo_ms2_spectra(_scans(), []) == []
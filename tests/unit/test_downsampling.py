@@ -0,0 +1,71 @@
+"""
+Tests for trace downsampling in calculation/downsampling.py.
+
+Covers:
+- LTTB and min/max point counts, order and kept endpoints
+- Peak apexes preserved through downsampling
+- Short traces and NaN traces returned unchanged
+- Argument validation
+"""
+
+import numpy as np
+import pytest
+
+from calculation.downsampling import downsample_trace, lttb_indices, minmax_indices
+
+
+def _trace(n=100_000):
+    x = np.linspace(0, 30, n)
+    y = 1e5 * np.exp(-((x - 12.3) ** 2) / 0.002) + 5e4 * np.exp(-((x - 20.0) ** 2) / 0.01)
+    return x, y + np.random.default_rng(0).random(n)
+
+
+class TestLttb:
+    def test_point_count_and_order(self):
+        x, y = _trace()
+        indices = lttb_indices(x, y, 1000)
+        assert len(indices) == 1000
+        assert indices[0] == 0 and indices[-1] == len(x) - 1
+        assert np.all(np.diff(indices) > 0)
+
+    def test_keeps_apex(self):
+        x, y = _trace()
+        dx, dy = downsample_trace(x, y, 2000, method="lttb")
+        assert dy.max() == pytest.approx(y.max(), rel=0.05)
+        assert dx[np.argmax(dy)] == pytest.approx(12.3, abs=0.01)
+
+
+class TestMinMax:
+    def test_point_count_and_order(self):
+        x, y = _trace()
+        indices = minmax_indices(y, 1000)
+        assert len(indices) <= 1000
+        assert np.all(np.diff(indices) > 0)
+
+    def test_keeps_extremes(self):
+        x, y = _trace(10_001)
+        dx, dy = downsample_trace(x, y, 500, method="minmax")
+        assert dy.max() == y.max()
+        assert dy.min() == y.min()
+
+
+class TestDownsampleTrace:
+    def test_short_trace_unchanged(self):
+        x, y = np.arange(10.0), np.arange(10.0)
+        dx, dy = downsample_trace(x, y, 100)
+        np.testing.assert_array_equal(dx, x)
+        np.testing.assert_array_equal(dy, y)
+
+    def test_nan_trace_unchanged(self):
+        x, y = _trace(1000)
+        y[5] = np.nan
+        dx, _ = downsample_trace(x, y, 100)
+        assert len(dx) == 1000
+
+    def test_unknown_method(self):
+        with pytest.raises(ValueError, match="Unknown downsampling method"):
+            downsample_trace(*_trace(100), method="nth")
+
+    def test_too_few_points(self):
+        with pytest.raises(ValueError, match="at least 3 points"):
+            downsample_trace(*_trace(100), n_points=2)
@@ -0,0 +1,142 @@
+"""
+Tests for drift and carryover detection in calculation/drift.py.
+
+Covers:
+- compound_area() and internal_standards()
+- detect_drift() on a trending and a stable compound, blanks left out
+- detect_carryover() after a high sample, consecutive blanks
+- batch_trends() and the injection order
+- correct_drift() on pooled QC injections, LOESS and spline
+"""
+
+import pytest
+
+from calculation.drift import (
+    batch_trends,
+    compound_area,
+    correct_drift,
+    detect_carryover,
+    detect_drift,
+    internal_standards,
+)
+from utils.classes import compounds_from_ion_list
+
+
+def _file(name, areas):
+    """Compounds of one file with the given ``{compound: area}``."""
+    compounds = compounds_from_ion_list(
+        {
+            "IS": {"ions": [150.0]},
+            "A": {"ions": [200.0], "internal_standard": "IS"},
+            "B": {"ions": [300.0]},
+        }
+    )
+    for compound in compounds:
+        compound.file = name
+        area = areas.get(compound.name, 0.0)
+        compound.ions[compound.target_list[0]]["Integration Data"] = {
+            "baseline_corrected_area": area
+        }
+    return compounds
+
+
+class TestAreas:
+    def test_compound_area(self):
+        compound = _file("a.mzML", {"B": 5.0})[2]
+        assert compound_area(compound) == 5.0
+
+    def test_internal_standards(self):
+        assert internal_standards(_file("a.mzML", {})) == {"IS"}
+
+
+class TestDrift:
+    def test_trend(self):
+        results = [
+            _file(f"s{i}.mzML", {"IS": 100.0 - 5 * i, "B": 50.0 + (-1) ** i}) for i in range(6)
+        ]
+        order = [f"s{i}.mzML" for i in range(6)]
+        drift = detect_drift(results, order, compounds={"IS", "B"})
+        assert drift["IS"]["drifting"] and drift["IS"]["rho"] == pytest.approx(-1.0)
+        assert drift["IS"]["slope"] == pytest.approx(-5 / 87.5 * 100)
+        assert not drift["B"]["drifting"]
+
+    def test_defaults_to_internal_standards(self):
+        results = [_file(f"s{i}.mzML", {"IS": 10.0 + i, "A": 5.0}) for i in range(4)]
+        drift = detect_drift(results, [f"s{i}.mzML" for i in range(4)])
+        assert set(drift) == {"IS"}
+
+    def test_blanks_left_out(self):
+        results = [_file(f"s{i}.mzML", {"IS": 10.0}) for i in range(3)]
+        results.append(_file("blank.mzML", {"IS": 1.0}))
+        order = ["s0.mzML", "blank.mzML", "s1.mzML", "s2.mzML"]
+        drift = detect_drift(results, order, blank_files={"blank.mzML"})
+        assert drift["IS"]["n"] == 3 and drift["IS"]["slope"] == pytest.approx(0.0, abs=1e-9)
+
+    def test_too_few_injections(self):
+        results = [_file(f"s{i}.mzML", {"IS": 10.0}) for i in range(2)]
+        assert detect_drift(results, ["s0.mzML", "s1.mzML"]) == {}
+
+
+class TestCarryover:
+    def test_blanks_after_sample(self):
+        results = [
+            _file("low.mzML", {"B": 10.0}),
+            _file("high.mzML", {"B": 1000.0}),
+            _file("blank1.mzML", {"B": 20.0}),
+            _file("blank2.mzML", {"B": 5.0}),
+        ]
+        order = ["blank2.mzML", "low.mzML", "high.mzML", "blank1.mzML"]
+        carryover = detect_carryover(results, order, {"blank1.mzML", "blank2.mzML"})
+        # blank2 was injected first, before any sample
+        assert carryover["B"]["blanks"] == {"blank1.mzML": {"after": "high.mzML", "percent": 2.0}}
+        assert carryover["B"]["max_percent"] == 2.0
+        assert "IS" not in carryover  # No peak in the sample
+
+    def test_consecutive_blanks(self):
+        results = [
+            _file("high.mzML", {"B": 100.0}),
+            _file("blank1.mzML", {"B": 4.0}),
+            _file("blank2.mzML", {"B": 1.0}),
+        ]
+        order = ["high.mzML", "blank1.mzML", "blank2.mzML"]
+        carryover = detect_carryover(results, order, {"blank1.mzML", "blank2.mzML"})
+        percents = {blank: entry["percent"] for blank, entry in carryover["B"]["blanks"].items()}
+        assert percents == {"blank1.mzML": 4.0, "blank2.mzML": 1.0}
+
+
+class TestBatchTrends:
+    def test_files_outside_order_ignored(self):
+        results = [_file(f"s{i}.mzML", {"IS": 10.0 * (i + 1)}) for i in range(4)]
+        trends = batch_trends(results, ["s0.mzML", "s1.mzML", "s2.mzML"])
+        assert trends["drift"]["IS"]["n"] == 3
+        assert trends["carryover"] == {}
+
+
+class TestCorrectDrift:
+    @pytest.mark.parametrize("method", ["loess", "spline"])
+    def test_removes_linear_drift(self, method):
+        # Signal decays 2 % per injection; QCs every other injection, same sample throughout
+        decay = [1.0 - 0.02 * i for i in range(11)]
+        names = [f"qc{i}.mzML" if i % 2 == 0 else f"s{i}.mzML" for i in range(11)]
+        results = [
+            _file(name, {"B": 100.0 * decay[i] if name.startswith("qc") else 50.0 * decay[i]})
+            for i, name in enumerate(names)
+        ]
+        factors = correct_drift(results, names, {n for n in names if n.startswith("qc")}, method)
+        areas = {compounds[2].file: compound_area(compounds[2]) for compounds in results}
+        for name in names:
+            expected = 90.0 if name.startswith("qc") else 45.0  # Median QC decay is 0.9
+            assert areas[name] == pytest.approx(expected, rel=0.02)
+        assert set(factors) == {"B"}
+        integration = results[1][2].ions[300.0]["Integration Data"]
+        assert integration["drift_uncorrected_area"] == pytest.approx(49.0)
+        assert integration["drift_factor"] == factors["B"]["s1.mzML"]
+
+    def test_too_few_qcs(self):
+        results = [_file(name, {"B": 10.0}) for name in ("qc0.mzML", "s1.mzML", "qc2.mzML")]
+        assert correct_drift(results, ["qc0.mzML", "s1.mzML", "qc2.mzML"], {"qc0.mzML"}) == {}
+        assert "drift_factor" not in results[1][2].ions[300.0]["Integration Data"]
+
+    def test_unknown_method(self):
+        with pytest.raises(ValueError, match="Unknown drift correction method"):
+            correct_drift([], [], (), method="mean")
@@ -0,0 +1,53 @@
+"""
+Tests for the exceptions in utils/errors.py.
+
+Covers:
+- File name in the message and pickling of FileParseError/ProcessingError
+- IonListError raised by compounds_from_ion_list(), still a ValueError
+- FileParseError from MSMeasurement/LCMeasurement on unreadable files
+"""
+
+import pickle
+
+import pytest
+
+from utils.classes import LCMeasurement, MSMeasurement, compounds_from_ion_list
+from utils.errors import FileParseError, IonListError, LCMSpectorError, ProcessingError
+
+
+class TestFileErrors:
+    @pytest.mark.parametrize("cls", [FileParseError, ProcessingError])
+    def test_message_and_pickling(self, cls):
+        error = cls("/data/run 1.mzML", "truncated file")
+        assert str(error) == "run 1.mzML: truncated file"
+        restored = pickle.loads(pickle.dumps(error))
+        assert type(restored) is cls
+        assert (restored.path, restored.message) == (error.path, error.message)
+
+    def test_unreadable_ms_file(self, tmp_path):
+        path = tmp_path / "broken.mzML"
+        path.write_text("<mzML><run><spectrumList>")
+        with pytest.raises(FileParseError, match="broken.mzML"):
+            MSMeasurement(str(path))
+
+    def test_unreadable_lc_file(self, tmp_path):
+        with pytest.raises(FileParseError):
+            LCMeasurement(str(tmp_path / "missing.txt"))
+
+    def test_lc_file_without_data(self, tmp_path):
+        path = tmp_path / "empty.txt"
+        path.write_text("Time\tAbsorbance\nn/a\tn/a\n")
+        with pytest.raises(FileParseError, match="no numeric"):
+            LCMeasurement(str(path))
+
+
+class TestIonListError:
+    def test_raised_for_malformed_entries(self):
+        with pytest.raises(IonListError, match="Leucine") as info:
+            compounds_from_ion_list({"Leucine": {"ions": ["abc"]}})
+        assert isinstance(info.value, ValueError)
+        assert isinstance(info.value, LCMSpectorError)
+
+    def test_compound_validation_is_wrapped(self):
+        with pytest.raises(IonListError, match="Caffeine"):
+            compounds_from_ion_list({"Caffeine": {"ions": [195.0877], "rt_min": 5, "rt_max": 2}})
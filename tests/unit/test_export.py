@@ -0,0 +1,196 @@
+"""
+Tests for the tidy result export in utils/export.py.
+
+Covers:
+- results_table() rows and columns per file, compound and ion
+- traces_table() rows per scan
+- peaks_table() rows per peak and the CSV / TSV export_long_table()
+- export_results() format validation and Parquet / Arrow round trips
+- consensus_table() compound x file matrix, gap filled, and its CSV export
+- The manifest of the measurements in every exported file
+"""
+
+from types import SimpleNamespace
+
+import numpy as np
+import pandas as pd
+import pytest
+
+from utils.classes import compounds_from_ion_list
+from utils.export import (
+    PEAK_COLUMNS,
+    RESULT_COLUMNS,
+    TRACE_COLUMNS,
+    consensus_table,
+    export_consensus_table,
+    export_long_table,
+    export_results,
+    peaks_table,
+    read_export_manifest,
+    results_table,
+    traces_table,
+)
+
+
+def _measurement(filename="sample"):
+    compounds = compounds_from_ion_list({"Alanine": {"ions": [90.055, 112.037]}, "Glycine": {"ions": [76.039]}})
+    for compound in compounds:
+        for ion, data in compound.ions.items():
+            data["MS Intensity"] = np.array([[0.1, 0.2, 0.3], [0.0, 5.0, 1.0]])
+            data["RT"] = 0.2
+            data["Integration Data"] = {"total_area": 10.0, "baseline_corrected_area": 8.0, "peak_height": 5.0}
+    compounds[1].ions[76.039]["MS Intensity"] = np.empty((2, 0))
+    compounds[1].ions[76.039]["Integration Data"] = None
+    return SimpleNamespace(filename=filename, xics=compounds)
+
+
+class TestResultsTable:
+    def test_one_row_per_ion(self):
+        table = results_table({"a": _measurement("a"), "b": _measurement("b")})
+        assert list(table.columns) == list(RESULT_COLUMNS)
+        assert len(table) == 6
+        row = table[(table.file == "a") & (table.ion_mz == 90.055)].iloc[0]
+        assert row.compound == "Alanine"
+        assert row.rt == 0.2
+        assert row.intensity_sum == 6.0
+        assert row.intensity_max == 5.0
+        assert row.peak_area == 10.0
+        assert row.peak_area_baseline_corrected == 8.0
+
+    def test_ion_without_scans(self):
+        table = results_table([_measurement()])
+        row = table[table.compound == "Glycine"].iloc[0]
+        assert pd.isna(row.intensity_sum)
+        assert pd.isna(row.peak_area)
+
+    def test_traces(self):
+        table = traces_table([_measurement()])
+        assert list(table.columns) == list(TRACE_COLUMNS)
+        assert len(table) == 6  # Two Alanine ions with 3 scans, Glycine empty
+        assert table.intensity.tolist()[:3] == [0.0, 5.0, 1.0]
+        assert table.baseline.isna().all()
+
+    def test_empty(self):
+        assert results_table([]).empty
+        assert list(traces_table([]).columns) == list(TRACE_COLUMNS)
+
+
+def _with_peaks(measurement):
+    data = measurement.xics[0].ions[90.055]
+    data["Peaks"] = [
+        {"apex_rt": 0.2, "start_time": 0.1, "end_time": 0.3, "height": 5.0, "area": 1.25,
+         "baseline_corrected_area": 1000.5, "fwhm": 0.05, "asymmetry": 1.0, "snr": float("nan")},
+        {"apex_rt": 0.3, "start_time": 0.25, "end_time": 0.3, "height": 1.0, "area": 0.1},
+    ]
+    return measurement
+
+
+class TestLongTable:
+    def test_peaks_table(self):
+        table = peaks_table([_with_peaks(_measurement())])
+        assert list(table.columns) == list(PEAK_COLUMNS)
+        assert len(table) == 4  # Two peaks of one ion, one row each for the others
+        assert table.peak.tolist()[:2] == [1, 2]
+        assert table.integrated.tolist() == [True, False, False, False]
+        assert pd.isna(table.apex_rt.iloc[2])
+
+    def test_csv(self, tmp_path):
+        path = export_long_table([_with_peaks(_measurement())], tmp_path / "peaks.csv")
+        lines = path.read_text().splitlines()
+        assert lines[0] == ",".join(PEAK_COLUMNS)
+        assert lines[1] == "sample,Alanine,90.055,,1,0.2,0.1,0.3,5.0,1.25,1000.5,0.05,1.0,,true"
+        assert len(lines) == 5
+        assert read_export_manifest(path)["measurements"] == {"sample": None}
+
+    def test_tsv_with_decimal_comma(self, tmp_path):
+        path = export_long_table(
+            [_with_peaks(_measurement())], tmp_path / "peaks.tsv", decimal=",", encoding="utf-16"
+        )
+        lines = path.read_text(encoding="utf-16").splitlines()
+        assert lines[1].split("\t")[5:8] == ["0,2", "0,1", "0,3"]
+
+    def test_per_ion_rows(self, tmp_path):
+        path = export_long_table([_measurement()], tmp_path / "ions.csv", peaks=False)
+        assert pd.read_csv(path).columns.tolist() == list(RESULT_COLUMNS)
+        assert len(pd.read_csv(path)) == 3
+
+    @pytest.mark.parametrize(
+        "options",
+        [
+            {"format": "xlsx"},
+            {"decimal": ";"},
+            {"decimal": ","},
+            {"encoding": "no-such-codec"},
+        ],
+    )
+    def test_invalid_options(self, tmp_path, options):
+        with pytest.raises(ValueError):
+            export_long_table([_measurement()], tmp_path / "out.csv", **options)
+
+
+class TestExportResults:
+    def test_unknown_format(self, tmp_path):
+        with pytest.raises(ValueError, match="Unknown export format"):
+            export_results([_measurement()], tmp_path / "out.csv", format="csv")
+
+    @pytest.mark.parametrize("fmt,suffix", [("parquet", ".parquet"), ("arrow", ".arrow")])
+    def test_round_trip(self, tmp_path, fmt, suffix):
+        pytest.importorskip("pyarrow")
+        path = tmp_path / f"batch{suffix}"
+        written = export_results([_measurement()], path, format=fmt, include_traces=True)
+        assert written == [path, tmp_path / f"batch_traces{suffix}"]
+        read = pd.read_parquet if fmt == "parquet" else pd.read_feather
+        results = read(path)
+        assert len(results) == 3
+        assert results.peak_area.tolist()[:2] == [10.0, 10.0]
+        assert len(read(written[1])) == 6
+
+    @pytest.mark.parametrize("fmt,suffix", [("parquet", ".parquet"), ("arrow", ".arrow")])
+    def test_manifest(self, tmp_path, fmt, suffix):
+        pytest.importorskip("pyarrow")
+        measurement = _measurement()
+        measurement.manifest = {"input_sha256": "abc", "config_hash": "def"}
+        path = tmp_path / f"batch{suffix}"
+        export_results([measurement], path, format=fmt)
+        manifest = read_export_manifest(path)
+        assert manifest["measurements"] == {"sample": measurement.manifest}
+        assert manifest["software"] == "LCMSpector"
+
+
+def _peak_measurement(filename, detected=True):
+    """One compound with a Gaussian peak at 2 min, integrated only if *detected*."""
+    times = np.linspace(0.0, 4.0, 81)
+    (compound,) = compounds_from_ion_list({"Alanine": {"ions": [90.055]}})
+    compound.file = f"{filename}.mzML"
+    data = compound.ions[90.055]
+    data["MS Intensity"] = np.array((times, 1.0 + 50 * np.exp(-(((times - 2.0) / 0.1) ** 2) / 2)))
+    data["RT"] = 2.0
+    data["Peaks"] = [{"apex_rt": 2.0}] if detected else []
+    data["Integration Data"] = (
+        {"baseline_corrected_area": 12.0, "start_time": 1.7, "end_time": 2.3} if detected else None
+    )
+    return SimpleNamespace(filename=filename, xics=[compound])
+
+
+class TestConsensusTable:
+    def test_compound_by_file(self):
+        table = consensus_table([_measurement("a"), _measurement("b")], rt_alignment=None)
+        assert list(table.columns) == ["compound", "rt", "a", "b"]
+        assert table.set_index("compound").loc["Alanine", ["a", "b"]].tolist() == [16.0, 16.0]
+        assert table.rt.tolist() == [0.2, 0.2]
+        assert table.set_index("compound").loc["Glycine", ["a", "b"]].isna().all()
+
+    def test_gap_filled(self):
+        measurements = [_peak_measurement("a"), _peak_measurement("b"), _peak_measurement("c", False)]
+        table = consensus_table(measurements, rt_alignment=None)
+        assert table.a[0] == 12.0 and table.c[0] > 0
+        assert np.isnan(consensus_table(measurements, rt_alignment=None, gap_filling=False).c[0])
+        # The measurements themselves are left as they are
+        assert measurements[2].xics[0].ions[90.055]["Integration Data"] is None
+
+    def test_csv_export(self, tmp_path):
+        path = export_consensus_table([_measurement("a")], tmp_path / "areas.csv", rt_alignment=None)
+        assert pd.read_csv(path).a.tolist()[0] == 16.0
+        assert read_export_manifest(path)["measurements"] == {"a": None}
+        with pytest.raises(ValueError, match="Unknown export format"):
+            export_consensus_table([_measurement("a")], tmp_path / "areas.xlsx", format="xlsx")
@@ -0,0 +1,113 @@
+"""
+Tests for calculation/features.py.
+
+Scans are fed through a monkeypatched iter_ms_scans so no mzML file is needed.
+
+Covers:
+- detect_mass_traces() ppm matching, gaps and minimum length
+- detect_features() feature table from synthetic LC-MS data
+- detect_features() charges from the isotope peaks of the apex scans
+"""
+
+import numpy as np
+import pandas as pd
+import pytest
+
+from calculation import features
+from calculation.features import FEATURE_COLUMNS, detect_features, detect_mass_traces
+from calculation.isotopes import ISOTOPE_SPACING
+
+
+def _gaussian(n_scans, center, sigma, height):
+    return height * np.exp(-0.5 * ((np.arange(n_scans) - center) / sigma) ** 2)
+
+
+def _spectra(n_scans=40):
+    """Two chromatographic peaks (m/z 300.1 and 500.2) plus low-level noise at m/z 150."""
+    rng = np.random.default_rng(0)
+    peak_a = _gaussian(n_scans, 12, 2.0, 1e5)
+    peak_b = _gaussian(n_scans, 28, 2.5, 5e4)
+    spectra = []
+    for i in range(n_scans):
+        mz = np.array([150.0, 300.1 * (1 + rng.normal(0, 1e-6)), 500.2])
+        intensity = np.array([5.0, peak_a[i], peak_b[i]])
+        spectra.append((mz, intensity))
+    return spectra
+
+
+class TestDetectMassTraces:
+    def test_chains_peaks_within_ppm(self):
+        spectra = [(np.array([200.0 * (1 + k * 2e-6)]), np.array([100.0])) for k in range(6)]
+        (trace,) = detect_mass_traces(spectra, ppm=10, min_scans=5)
+        assert trace.scans == list(range(6))
+        assert trace.mz == pytest.approx(200.0, rel=1e-5)
+
+    def test_splits_beyond_ppm(self):
+        spectra = [(np.array([200.0]), np.array([100.0]))] * 5 + [
+            (np.array([200.01]), np.array([100.0]))
+        ] * 5
+        traces = detect_mass_traces(spectra, ppm=10, min_scans=5)
+        assert sorted(round(trace.mz, 2) for trace in traces) == [200.0, 200.01]
+
+    def test_gap_tolerance(self):
+        present = (np.array([200.0]), np.array([100.0]))
+        missing = (np.array([]), np.array([]))
+        spectra = [present] * 3 + [missing] + [present] * 3
+        assert len(detect_mass_traces(spectra, min_scans=6, max_gap=1)) == 1
+        assert detect_mass_traces(spectra, min_scans=6, max_gap=0) == []
+
+    def test_noise_threshold(self):
+        spectra = [(np.array([200.0]), np.array([5.0]))] * 10
+        assert detect_mass_traces(spectra, min_intensity=10.0) == []
+
+
+class TestDetectFeatures:
+    @pytest.fixture
+    def patch_scans(self, monkeypatch):
+        seen_filters = []
+
+        def fake_iter_ms_scans(path, progress_callback=None, scan_filter=None, **filters):
+            seen_filters.append(scan_filter)
+            for i, (mz, intensity) in enumerate(_spectra()):
+                yield 0.1 * i, float(intensity.sum()), 1, mz, intensity
+
+        monkeypatch.setattr(features, "iter_ms_scans", fake_iter_ms_scans)
+        return seen_filters
+
+    def test_feature_table(self, patch_scans):
+        table = detect_features("fake.mzML", min_intensity=10.0)
+        assert list(table.columns) == list(FEATURE_COLUMNS)
+        assert len(table) == 2
+        first, second = table.iloc[0], table.iloc[1]
+        assert first["mz"] == pytest.approx(300.1, rel=5e-6)
+        assert first["rt"] == pytest.approx(1.2)
+        assert second["mz"] == pytest.approx(500.2)
+        assert second["rt"] == pytest.approx(2.8)
+        assert first["rt_min"] < first["rt"] < first["rt_max"]
+        assert first["height"] > second["height"]
+
+    def test_defaults_to_ms1(self, patch_scans):
+        detect_features("fake.mzML")
+        assert patch_scans[-1]["ms_levels"] == [1]
+        detect_features("fake.mzML", scan_filter={"ms_levels": [2]})
+        assert patch_scans[-1]["ms_levels"] == [2]
+
+    def test_charge_from_apex_scan(self, monkeypatch):
+        spectra = _spectra()
+        # A doubly charged isotope peak in the apex scan of m/z 300.1 only
+        mz, intensity = spectra[12]
+        spectra[12] = (np.append(mz, 300.1 + ISOTOPE_SPACING / 2), np.append(intensity, 4e4))
+        passes = []
+
+        def fake_iter_ms_scans(path, progress_callback=None, scan_filter=None, **filters):
+            passes.append(0)
+            for i, (mz, intensity) in enumerate(spectra):
+                passes[-1] += 1
+                yield 0.1 * i, float(intensity.sum()), 1, mz, intensity
+
+        monkeypatch.setattr(features, "iter_ms_scans", fake_iter_ms_scans)
+        table = detect_features("fake.mzML", min_intensity=10.0)
+        assert table["charge"].iloc[0] == 2
+        assert pd.isna(table["charge"].iloc[1])
+        # The second pass stops at the last apex scan
+        assert passes[0] == len(spectra) and passes[1] < len(spectra)
@@ -0,0 +1,96 @@
+"""
+Tests for molecular formula generation in calculation/formulas.py.
+
+Covers:
+- rdbe(), hill_formula() and neutral_mass() of adducts
+- generate_formulas() within the tolerance, element limits and RDBE filters
+- Ranking on the isotope pattern
+- validate_element_limits() and annotate_features()
+"""
+
+import pandas as pd
+import pytest
+
+from calculation.formulas import (
+    annotate_features,
+    generate_formulas,
+    hill_formula,
+    neutral_mass,
+    rdbe,
+    validate_element_limits,
+)
+from utils.theoretical_spectrum import expand_adducts, isotope_ratios, monoisotopic_mass
+
+CAFFEINE = "C8H10N4O2"
+
+
+def _mz(formula, adduct="[M+H]+"):
+    return expand_adducts(monoisotopic_mass(formula), [adduct])[adduct]
+
+
+class TestHelpers:
+    def test_rdbe(self):
+        assert rdbe({"C": 8, "H": 10, "N": 4, "O": 2}) == 6.0
+        assert rdbe({"C": 1, "H": 3}) == 0.5  # Methyl radical
+
+    def test_hill_formula(self):
+        assert hill_formula({"O": 2, "N": 4, "H": 10, "C": 8, "S": 0}) == CAFFEINE
+        assert hill_formula({"O": 1, "H": 2}) == "H2O"
+
+    @pytest.mark.parametrize("adduct", ["[M+H]+", "[M+Na]+", "[M-H]-", "[M+2H]2+", "[2M+H]+"])
+    def test_neutral_mass_inverts_adducts(self, adduct):
+        assert neutral_mass(_mz(CAFFEINE, adduct), adduct) == pytest.approx(
+            monoisotopic_mass(CAFFEINE), abs=1e-3
+        )
+
+    def test_unknown_adduct(self):
+        with pytest.raises(ValueError, match="Unknown adduct"):
+            neutral_mass(195.0, "[M+Li]+")
+
+
+class TestGenerateFormulas:
+    def test_finds_caffeine(self):
+        candidates = generate_formulas(_mz(CAFFEINE), ppm=5.0)
+        formulas = [candidate["formula"] for candidate in candidates]
+        assert CAFFEINE in formulas
+        assert all(abs(candidate["error_ppm"]) <= 5.0 for candidate in candidates)
+        assert all(candidate["rdbe"] == int(candidate["rdbe"]) for candidate in candidates)
+
+    def test_element_limits(self):
+        candidates = generate_formulas(
+            _mz(CAFFEINE), element_limits={"C": (0, 20), "H": (0, 40), "O": (0, 10)}
+        )
+        assert all("N" not in candidate["formula"] for candidate in candidates)
+
+    def test_rdbe_range(self):
+        candidates = generate_formulas(_mz(CAFFEINE), rdbe_range=(0.0, 5.0))
+        assert CAFFEINE not in [candidate["formula"] for candidate in candidates]
+
+    def test_isotope_ranking(self):
+        pattern = isotope_ratios(CAFFEINE, "[M+H]+")
+        candidates = generate_formulas(_mz(CAFFEINE), ppm=10.0, isotope_pattern=pattern)
+        assert candidates[0]["formula"] == CAFFEINE
+        assert candidates[0]["isotope_score"] == pytest.approx(1.0)
+
+    def test_invalid_tolerance(self):
+        with pytest.raises(ValueError, match="Mass tolerance"):
+            generate_formulas(195.0, ppm=0.0)
+
+
+class TestElementLimits:
+    def test_invalid(self):
+        with pytest.raises(ValueError, match="Unknown elements"):
+            validate_element_limits({"Xx": (0, 1)})
+        with pytest.raises(ValueError, match="Invalid limits"):
+            validate_element_limits({"C": (5, 2)})
+
+
+class TestAnnotateFeatures:
+    def test_best_formula(self):
+        features = pd.DataFrame({"mz": [_mz(CAFFEINE), 1.0], "rt": [2.0, 3.0]})
+        annotated = annotate_features(
+            features,
+            isotope_pattern=isotope_ratios(CAFFEINE, "[M+H]+"),
+        )
+        assert annotated.formula.tolist() == [CAFFEINE, None]
+        assert "formula" not in features
@@ -0,0 +1,83 @@
+"""
+Tests for gap filling across a batch in calculation/gap_filling.py.
+
+Covers:
+- peak_found() on integrated, undetected and failed peaks
+- fill_gaps() at the consensus boundaries, flagged as gap-filled
+- The minimum fraction of files and RT-shifted boundaries
+"""
+
+import numpy as np
+import pytest
+
+from calculation.alignment import RTShift
+from calculation.gap_filling import fill_gaps, peak_found
+from utils.classes import compounds_from_ion_list
+
+TIMES = np.linspace(0.0, 4.0, 81)
+
+
+def _file(name, apex=None, height=100.0, detected=True):
+    """One compound with a Gaussian peak at *apex* (None for a flat trace)."""
+    (compound,) = compounds_from_ion_list({"A": {"ions": [200.0]}})
+    compound.file = name
+    data = compound.ions[200.0]
+    signal = np.zeros_like(TIMES)
+    if apex is not None:
+        signal = height * np.exp(-(((TIMES - apex) / 0.1) ** 2) / 2)
+    data["MS Intensity"] = np.array((TIMES, signal + 1.0), dtype=np.float32)
+    data["Peaks"] = [{"apex_rt": apex}] if detected and apex is not None else []
+    if detected and apex is not None:
+        data["Integration Data"] = {
+            "baseline_corrected_area": 10.0,
+            "start_time": apex - 0.3,
+            "end_time": apex + 0.3,
+        }
+    else:
+        data["Integration Data"] = {
+            "baseline_corrected_area": 0.5, "start_time": 0.0, "end_time": 0.1,
+        }
+    return [compound]
+
+
+class TestPeakFound:
+    def test_found_and_missing(self):
+        assert peak_found(_file("a.mzML", apex=2.0)[0].ions[200.0])
+        assert not peak_found(_file("a.mzML", apex=2.0, detected=False)[0].ions[200.0])
+        assert not peak_found({"Integration Data": None})
+
+
+class TestFillGaps:
+    def test_fills_at_consensus_boundaries(self):
+        results = [_file("a.mzML", 2.0), _file("b.mzML", 2.0), _file("c.mzML", 2.0, 30.0, False)]
+        assert fill_gaps(results) == 1
+        data = results[2][0].ions[200.0]
+        integration = data["Integration Data"]
+        assert integration["gap_filled"] and integration["integration_method"] == "gap_filled"
+        assert integration["start_time"] == pytest.approx(1.7, abs=0.05)
+        assert integration["end_time"] == pytest.approx(2.3, abs=0.05)
+        assert integration["baseline_corrected_area"] > 0
+        assert data["MS Peak Area"] == integration
+        assert "gap_filled" not in results[0][0].ions[200.0]["Integration Data"]
+
+    def test_minimum_fraction(self):
+        results = [
+            _file("a.mzML", 2.0),
+            _file("b.mzML", 2.0, 5.0, detected=False),
+            _file("c.mzML", 2.0, 5.0, detected=False),
+        ]
+        assert fill_gaps(results, min_fraction=0.5) == 0
+        assert fill_gaps(results, min_fraction=0.3) == 2
+
+    def test_shifted_boundaries(self):
+        results = [_file("a.mzML", 2.0), _file("b.mzML", 2.0), _file("c.mzML", 2.5, 30.0, False)]
+        # c elutes 0.5 min late: its aligned RT is the observed one minus 0.5
+        shifts = {"c.mzML": RTShift(anchors=[(2.5, 2.0)])}
+        fill_gaps(results, rt_shifts=shifts)
+        integration = results[2][0].ions[200.0]["Integration Data"]
+        assert integration["start_time"] == pytest.approx(2.2, abs=0.05)
+        assert integration["end_time"] == pytest.approx(2.8, abs=0.05)
+
+    def test_invalid_fraction(self):
+        with pytest.raises(ValueError):
+            fill_gaps([], min_fraction=0)
@@ -0,0 +1,107 @@
+"""
+Tests for the GC-EI workflow in calculation/gcms.py.
+
+Covers:
+- identify_ei() library matching without a precursor
+- ei_ion_list() quantifier and qualifier ions from library spectra
+- qualifier_ratios() against the library ion ratios
+- identify_compounds() on the apex spectrum of processed compounds
+"""
+
+import numpy as np
+import pytest
+
+from calculation.gcms import (
+    QUALIFIER_LABEL,
+    QUANTIFIER_LABEL,
+    ei_ion_list,
+    identify_compounds,
+    identify_ei,
+    qualifier_ratios,
+)
+from utils.classes import compounds_from_ion_list
+from utils.library import SpectralLibrary
+
+NAPHTHALENE = (np.array([51.0, 64.0, 102.0, 127.0, 128.0]), np.array([5.0, 4.0, 7.0, 10.0, 100.0]))
+TOLUENE = (np.array([39.0, 65.0, 91.0, 92.0]), np.array([10.0, 12.0, 100.0, 60.0]))
+
+# (scan time, ..., MS level, m/z, intensity) as LoadedRun.scans yields them
+SCANS = [
+    (4.90, 0.0, 1, np.array([73.0]), np.array([20.0])),
+    (5.00, 0.0, 1, np.array([73.0, 102.0, 127.0, 128.0]), np.array([20.0, 70.0, 100.0, 1000.0])),
+]
+
+
+class FakeRun:
+    def scans(self, polarity=None, scan_filter=None):
+        return iter(SCANS)
+
+
+@pytest.fixture
+def library():
+    # EI library spectra have no precursor
+    return SpectralLibrary.from_spectra(
+        [
+            {"precursor_mz": None, "mz": mz, "intensity": intensity, "metadata": {"name": name}}
+            for name, (mz, intensity) in (("Naphthalene", NAPHTHALENE), ("Toluene", TOLUENE))
+        ]
+    )
+
+
+def test_identify_ei(library):
+    hits = identify_ei(np.array([127.1, 128.05]), np.array([10.0, 100.0]), library)
+    assert [hit["name"] for hit in hits] == ["Naphthalene", "Toluene"]
+    assert hits[0]["score"] > 0.95 and hits[0]["precursor_mz"] is None
+    assert hits[1]["score"] == 0.0
+    assert identify_ei(np.zeros(0), np.zeros(0), library) == []
+
+
+class TestEiIonList:
+    def test_most_intense_fragments(self, library):
+        ion_list = ei_ion_list(library, ["naphthalene", "Toluene"], n_ions=3, min_mz=60.0)
+        assert ion_list["Naphthalene"] == {
+            "ions": [128.0, 127.0, 102.0],
+            "info": [QUANTIFIER_LABEL, QUALIFIER_LABEL, QUALIFIER_LABEL],
+        }
+        assert ion_list["Toluene"]["ions"] == [91.0, 92.0, 65.0]
+        assert len(compounds_from_ion_list(ion_list)) == 2
+
+    def test_invalid(self, library):
+        with pytest.raises(KeyError):
+            ei_ion_list(library, ["Benzene"])
+        with pytest.raises(KeyError):
+            ei_ion_list(library, ["Toluene"], min_mz=100.0)
+        with pytest.raises(ValueError):
+            ei_ion_list(library, ["Toluene"], n_ions=0)
+
+
+def _naphthalene(library, areas):
+    (compound,) = compounds_from_ion_list(ei_ion_list(library, ["Naphthalene"], min_mz=60.0))
+    for data, area in zip(compound.ions.values(), areas):
+        data["RT"] = 5.0
+        data["Integration Data"] = (
+            None if area is None else {"baseline_corrected_area": area, "start_time": 4.95}
+        )
+    return compound
+
+
+def test_qualifier_ratios(library):
+    compound = _naphthalene(library, [1000.0, 100.0, None])
+    ratios = qualifier_ratios(compound, library.spectrum(0), tolerance=0.2)
+    assert ratios[127.0] == {
+        "ratio": 0.1, "expected": 0.1, "deviation": pytest.approx(0.0), "ok": True
+    }
+    assert ratios[102.0]["ratio"] is None and ratios[102.0]["ok"] is None
+    assert ratios[102.0]["expected"] == pytest.approx(0.07)
+    off = qualifier_ratios(_naphthalene(library, [1000.0, 200.0, 70.0]), library.spectrum(0))
+    assert off[127.0]["deviation"] == pytest.approx(1.0) and off[127.0]["ok"] is False
+
+
+def test_identify_compounds(library):
+    compound = _naphthalene(library, [1000.0, 100.0, 70.0])
+    (unintegrated,) = compounds_from_ion_list({"Toluene": {"ions": [91.0]}})
+    assert identify_compounds(FakeRun(), [compound, unintegrated], library) == 1
+    hits = compound.ions[128.0]["EI Hits"]
+    assert hits[0]["name"] == "Naphthalene"
+    assert compound.ions[127.0]["EI Hits"] is None
+    assert unintegrated.ions[91.0]["EI Hits"] is None
@@ -0,0 +1,66 @@
+"""
+Tests for the grouping of untargeted features in calculation/grouping.py.
+
+Covers:
+- Adducts, dimers and in-source losses of one compound grouped together
+- Co-elution and polarity limits
+- Argument validation
+"""
+
+import pandas as pd
+import pytest
+
+from calculation.grouping import GROUP_COLUMNS, group_features
+from utils.theoretical_spectrum import ADDUCT_DEFINITIONS, adduct_mz_from_mass, monoisotopic_mass
+
+CAFFEINE = 194.080376
+
+
+def _mz(label, mass=CAFFEINE):
+    return adduct_mz_from_mass(mass, ADDUCT_DEFINITIONS[label])
+
+
+def _features(rows):
+    return pd.DataFrame(rows, columns=["mz", "rt", "height"])
+
+
+class TestGroupFeatures:
+    def test_adducts_and_fragments(self):
+        protonated = _mz("[M+H]+")
+        table = group_features(
+            _features(
+                [
+                    (protonated, 5.00, 1e6),
+                    (_mz("[M+Na]+"), 5.01, 2e5),
+                    (_mz("[2M+H]+"), 4.99, 5e4),
+                    (protonated - monoisotopic_mass("NH3"), 5.00, 1e4),
+                    (250.0, 5.00, 1e5),  # Co-elutes, unrelated
+                    (_mz("[M+Na]+"), 9.00, 1e5),  # Related m/z, elutes elsewhere
+                ]
+            )
+        )
+        assert set(GROUP_COLUMNS) <= set(table.columns)
+        assert table["group"].iloc[:4].nunique() == 1
+        assert table["group"].nunique() == 3
+        assert list(table["adduct"].iloc[:3]) == ["[M+H]+", "[M+Na]+", "[2M+H]+"]
+        assert table["neutral_mass"].iloc[0] == pytest.approx(CAFFEINE, rel=1e-6)
+        assert table["fragment_of"].iloc[3] == 0 and table["neutral_loss"].iloc[3] == "NH3"
+        assert table["adduct"].iloc[4] is None and table["adduct"].iloc[5] is None
+
+    def test_negative_mode(self):
+        table = group_features(
+            _features([(_mz("[M-H]-"), 3.0, 1e5), (_mz("[M+Cl]-"), 3.0, 1e4)]),
+            polarity="negative",
+        )
+        assert list(table["adduct"]) == ["[M-H]-", "[M+Cl]-"]
+        positive = group_features(
+            _features([(_mz("[M-H]-"), 3.0, 1e5), (_mz("[M+Cl]-"), 3.0, 1e4)])
+        )
+        assert positive["group"].nunique() == 2
+
+    def test_invalid(self):
+        with pytest.raises(ValueError, match="Unknown polarity"):
+            group_features(_features([]), polarity="neutral")
+        with pytest.raises(ValueError, match="not a positive ion"):
+            group_features(_features([]), adducts=["[M-H]-"])
+        assert group_features(_features([])).empty
@@ -0,0 +1,122 @@
+"""
+Tests for the HDF5 export of processing results in utils/hdf5.py.
+
+Covers:
+- Round trip of Compound fields, calibration and per-ion results (traces,
+  peaks, MS2 spectra, nested dicts and None values)
+- Group layout per file and compound
+- The batch and per-file manifests
+- Rejection of files not written by export_hdf5
+"""
+
+import json
+from types import SimpleNamespace
+
+import numpy as np
+import pytest
+
+h5py = pytest.importorskip("h5py")
+
+from utils.classes import compounds_from_ion_list  # noqa: E402
+from utils.errors import FileParseError  # noqa: E402
+from utils.hdf5 import export_hdf5, load_hdf5  # noqa: E402
+
+
+def _measurement():
+    compounds = compounds_from_ion_list(
+        {"Alanine": {"ions": [90.055, 112.037], "info": ["[M+H]+", "[M+Na]+"], "tolerance": "5 ppm"},
+         "Na/K": {"ions": [60.0]}}
+    )
+    alanine = compounds[0]
+    alanine.concentration = 1.5
+    alanine.calibration_parameters = {"slope": 2.0, "residuals": [0.1, -0.1], "std_err": None}
+    data = alanine.ions[90.055]
+    data["RT"] = 2.0
+    data["MS Intensity"] = np.array([np.linspace(0, 4, 100), np.arange(100.0)])
+    data["Peaks"] = [
+        {"apex_rt": 2.0, "start_index": 3, "end_index": 9, "snr": float("nan")},
+        {"apex_rt": 3.0, "start_index": 10, "end_index": 12, "snr": 4.0},
+    ]
+    data["MS2"] = [{"scan_time": 2.0, "precursor_mz": 90.055, "mz": np.array([44.0]), "intensity": np.array([1.0])}]
+    data["Integration Data"] = {"total_area": 10.0, "integration_method": "auto"}
+    data["Peak Fit"] = [{"model": "gaussian", "success": True, "baseline": (0.0, 1.0), "components": []}]
+    data["Isotope Score"] = {"observed": np.array([1.0, 0.1]), "predicted": None, "score": 0.9}
+    return SimpleNamespace(
+        path="/data/sample.mzML",
+        filename="sample",
+        xics=compounds,
+        tic_times=np.arange(3.0),
+        manifest={"input_sha256": "abc", "config": {"xic_mode": "sum"}},
+    )
+
+
+@pytest.fixture
+def loaded(tmp_path):
+    path = export_hdf5({"sample": _measurement()}, tmp_path / "batch.h5")
+    return path, load_hdf5(path)
+
+
+class TestRoundTrip:
+    def test_measurement(self, loaded):
+        _, results = loaded
+        assert list(results) == ["sample"]
+        entry = results["sample"]
+        assert entry["path"] == "/data/sample.mzML"
+        np.testing.assert_array_equal(entry["tic_times"], np.arange(3.0))
+        assert "bpc_values" not in entry
+        assert entry["manifest"] == {"input_sha256": "abc", "config": {"xic_mode": "sum"}}
+
+    def test_batch_manifest(self, loaded):
+        path, _ = loaded
+        with h5py.File(path, "r") as f:
+            manifest = json.loads(f.attrs["@manifest"])
+        assert manifest["measurements"]["sample"]["input_sha256"] == "abc"
+        assert [c.name for c in entry["compounds"]] == ["Alanine", "Na/K"]
+
+    def test_compound(self, loaded):
+        alanine = loaded[1]["sample"]["compounds"][0]
+        assert alanine.target_list == [90.055, 112.037]
+        assert alanine.ion_info == ["[M+H]+", "[M+Na]+"]
+        assert alanine.mass_tolerance == [(5.0, "ppm"), (5.0, "ppm")]
+        assert alanine.concentration == 1.5
+        assert alanine.calibration_parameters == {"slope": 2.0, "residuals": [0.1, -0.1], "std_err": None}
+
+    def test_ion_results(self, loaded):
+        ions = loaded[1]["sample"]["compounds"][0].ions
+        assert list(ions) == [90.055, 112.037]
+        data = ions[90.055]
+        assert data["RT"] == 2.0
+        expected = _measurement().xics[0].ions[90.055]
+        np.testing.assert_array_equal(data["MS Intensity"], expected["MS Intensity"])
+        assert data["Peaks"][1] == {"apex_rt": 3.0, "start_index": 10, "end_index": 12, "snr": 4.0}
+        assert np.isnan(data["Peaks"][0]["snr"])
+        (spectrum,) = data["MS2"]
+        assert spectrum["scan_time"] == 2.0
+        np.testing.assert_array_equal(spectrum["mz"], [44.0])
+        assert data["Integration Data"] == {"total_area": 10.0, "integration_method": "auto"}
+        assert data["Peak Fit"] == [{"model": "gaussian", "success": True, "baseline": (0.0, 1.0), "components": []}]
+        assert data["Isotope Score"]["predicted"] is None
+        np.testing.assert_array_equal(data["Isotope Score"]["observed"], [1.0, 0.1])
+        assert data["MS Baseline"] is None
+        assert ions[112.037]["Peaks"] == []
+
+    def test_layout(self, loaded):
+        path, _ = loaded
+        with h5py.File(path, "r") as f:
+            compounds = f["files/sample/compounds"]
+            assert list(compounds) == ["Alanine", "Na%2FK"]
+            assert compounds["Alanine/ions/90.055/MS Intensity"].compression == "gzip"
+            assert compounds["Alanine/ions/90.055/Peaks"].dtype.names == (
+                "apex_rt", "start_index", "end_index", "snr",
+            )
+
+
+def test_not_a_results_file(tmp_path):
+    path = tmp_path / "other.h5"
+    with h5py.File(path, "w") as f:
+        f.create_group("data")
+    with pytest.raises(FileParseError, match="not an LCMSpector results file"):
+        load_hdf5(path)
+    (tmp_path / "text.h5").write_text("not hdf5")
+    with pytest.raises(FileParseError, match="cannot read HDF5 results"):
+        load_hdf5(tmp_path / "text.h5")
@@ -0,0 +1,171 @@
+"""
+Tests for imzML mass spectrometry imaging support.
+
+Covers:
+- iter_pixels() / read_layout() in imzml_reader.py, continuous and processed mode,
+  referenceable param groups, zlib compression
+- ion_images() / compound_ion_images() in calculation/imaging.py
+"""
+
+import uuid
+import zlib
+
+import numpy as np
+import pytest
+
+from calculation.imaging import compound_ion_images, ion_images
+from utils.errors import IonListError
+from utils.imzml_reader import iter_pixels, read_layout
+
+# (x, y) -> (mz, intensity); pixel (2, 2) was not acquired
+PIXELS = {
+    (1, 1): ([100.0, 200.0, 300.0], [1.0, 2.0, 3.0]),
+    (2, 1): ([100.0, 200.0, 300.0], [4.0, 0.0, 6.0]),
+    (1, 2): ([100.0, 200.0, 300.0], [7.0, 8.0, 9.0]),
+}
+
+_SPECTRUM = """\
+<spectrum id="pixel={index}" index="{index}" defaultArrayLength="{length}">
+  <cvParam cvRef="MS" accession="MS:1000130" name="positive scan"/>
+  <scanList count="1"><scan>
+    <cvParam cvRef="IMS" accession="IMS:1000050" name="position x" value="{x}"/>
+    <cvParam cvRef="IMS" accession="IMS:1000051" name="position y" value="{y}"/>
+  </scan></scanList>
+  <binaryDataArrayList count="2">
+    <binaryDataArray encodedLength="0">
+      <referenceableParamGroupRef ref="mzArray"/>
+      <cvParam cvRef="IMS" accession="IMS:1000103" name="external array length" value="{length}"/>
+      <cvParam cvRef="IMS" accession="IMS:1000104" name="external encoded length" value="{mz_bytes}"/>
+      <cvParam cvRef="IMS" accession="IMS:1000102" name="external offset" value="{mz_offset}"/>
+      <binary/>
+    </binaryDataArray>
+    <binaryDataArray encodedLength="0">
+      <referenceableParamGroupRef ref="intensityArray"/>
+      <cvParam cvRef="IMS" accession="IMS:1000103" name="external array length" value="{length}"/>
+      <cvParam cvRef="IMS" accession="IMS:1000104" name="external encoded length" value="{intensity_bytes}"/>
+      <cvParam cvRef="IMS" accession="IMS:1000102" name="external offset" value="{intensity_offset}"/>
+      <binary/>
+    </binaryDataArray>
+  </binaryDataArrayList>
+</spectrum>"""
+
+
+def _write_imzml(directory, pixels=PIXELS, continuous=True, compress=False):
+    """Write a minimal imzML/ibd pair, m/z as 64-bit and intensities as 32-bit floats."""
+    file_uuid = uuid.uuid4()
+    ibd = bytearray(file_uuid.bytes)
+
+    def append(values, dtype):
+        raw = np.asarray(values, dtype=dtype).tobytes()
+        if compress:
+            raw = zlib.compress(raw)
+        offset = len(ibd)
+        ibd.extend(raw)
+        return offset, len(raw)
+
+    spectra = []
+    shared_mz = None
+    for index, ((x, y), (mz, intensity)) in enumerate(pixels.items()):
+        if continuous:
+            shared_mz = shared_mz or append(mz, "<f8")
+            mz_offset, mz_bytes = shared_mz
+        else:
+            mz_offset, mz_bytes = append(mz, "<f8")
+        intensity_offset, intensity_bytes = append(intensity, "<f4")
+        spectra.append(
+            _SPECTRUM.format(
+                index=index, length=len(mz), x=x, y=y,
+                mz_offset=mz_offset, mz_bytes=mz_bytes,
+                intensity_offset=intensity_offset, intensity_bytes=intensity_bytes,
+            )
+        )
+    compression = (
+        '<cvParam cvRef="MS" accession="MS:1000574" name="zlib compression"/>'
+        if compress
+        else '<cvParam cvRef="MS" accession="MS:1000576" name="no compression"/>'
+    )
+    mode = "IMS:1000030" if continuous else "IMS:1000031"
+    imzml = f"""<?xml version="1.0" encoding="utf-8"?>
+<mzML xmlns="http://psi.hupo.org/ms/mzml" version="1.1">
+  <fileDescription><fileContent>
+    <cvParam cvRef="IMS" accession="{mode}" name="mode"/>
+    <cvParam cvRef="IMS" accession="IMS:1000080" name="universally unique identifier"
+      value="{{{file_uuid}}}"/>
+  </fileContent></fileDescription>
+  <referenceableParamGroupList count="2">
+    <referenceableParamGroup id="mzArray">
+      <cvParam cvRef="MS" accession="MS:1000514" name="m/z array"/>
+      <cvParam cvRef="MS" accession="MS:1000523" name="64-bit float"/>
+      {compression}
+    </referenceableParamGroup>
+    <referenceableParamGroup id="intensityArray">
+      <cvParam cvRef="MS" accession="MS:1000515" name="intensity array"/>
+      <cvParam cvRef="MS" accession="MS:1000521" name="32-bit float"/>
+      {compression}
+    </referenceableParamGroup>
+  </referenceableParamGroupList>
+  <run id="msi"><spectrumList count="{len(spectra)}">
+{chr(10).join(spectra)}
+  </spectrumList></run>
+</mzML>
+"""
+    path = directory / "tissue.imzML"
+    path.write_text(imzml)
+    path.with_suffix(".ibd").write_bytes(bytes(ibd))
+    return str(path)
+
+
+class TestImzMLReader:
+    @pytest.mark.parametrize("continuous", [True, False])
+    @pytest.mark.parametrize("compress", [False, True])
+    def test_iter_pixels(self, tmp_path, continuous, compress):
+        path = _write_imzml(tmp_path, continuous=continuous, compress=compress)
+        pixels = {(x, y): (mz, intensity) for x, y, mz, intensity in iter_pixels(path)}
+        assert set(pixels) == set(PIXELS)
+        for position, (mz, intensity) in PIXELS.items():
+            np.testing.assert_allclose(pixels[position][0], mz)
+            np.testing.assert_allclose(pixels[position][1], intensity)
+
+    def test_polarity(self, tmp_path):
+        path = _write_imzml(tmp_path)
+        assert len(list(iter_pixels(path, polarity="positive"))) == 3
+        assert list(iter_pixels(path, polarity="negative")) == []
+
+    def test_layout(self, tmp_path):
+        layout = read_layout(_write_imzml(tmp_path, continuous=False))
+        assert layout["mode"] == "processed"
+        assert (layout["width"], layout["height"]) == (2, 2)
+        assert layout["coordinates"].tolist() == [[1, 1], [2, 1], [1, 2]]
+
+    def test_missing_ibd(self, tmp_path):
+        path = _write_imzml(tmp_path)
+        (tmp_path / "tissue.ibd").unlink()
+        with pytest.raises(FileNotFoundError):
+            list(iter_pixels(path))
+
+
+class TestIonImages:
+    def test_ion_images(self, tmp_path):
+        images = ion_images(_write_imzml(tmp_path), [100.0, 300.0], mass_accuracy=1e-5)
+        assert images.shape == (2, 2, 2)
+        np.testing.assert_array_equal(images[0], [[1.0, 4.0], [7.0, np.nan]])
+        np.testing.assert_array_equal(images[1], [[3.0, 6.0], [9.0, np.nan]])
+
+    def test_custom_range(self, tmp_path):
+        images = ion_images(
+            _write_imzml(tmp_path), [200.0], custom_ranges={200.0: (99.0, 201.0)}
+        )
+        np.testing.assert_array_equal(images[0], [[3.0, 4.0], [15.0, np.nan]])
+
+    def test_compound_ion_images(self, tmp_path):
+        images = compound_ion_images(
+            _write_imzml(tmp_path, continuous=False),
+            {"A": {"ions": [100.0, 200.0]}, "B": {"ions": [300.0]}},
+        )
+        assert set(images) == {"A", "B"}
+        assert set(images["A"]) == {100.0, 200.0}
+        assert images["B"][300.0][1, 0] == 9.0
+
+    def test_no_compounds(self, tmp_path):
+        with pytest.raises(IonListError):
+            compound_ion_images(_write_imzml(tmp_path), {})
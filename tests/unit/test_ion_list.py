@@ -0,0 +1,237 @@
+"""
+Tests for building Compound objects from in-memory ion lists.
+
+Covers:
+- compounds_from_ion_list() in classes.py (config.json layout, list layout)
+- Adduct expansion of neutral masses and formulas
+- Charge state ranges of neutral masses
+- Internal standard references
+- Per-compound and per-ion mass tolerances
+- construct_xics() accepting a plain ion list
+"""
+
+import numpy as np
+import pytest
+
+from calculation import preprocessing
+from utils.classes import Compound, compounds_from_ion_list
+
+
+class TestCompoundsFromIonList:
+    def test_config_layout(self):
+        ion_list = {
+            "Caffeine": {"ions": [195.0877, 138.0662], "info": ["[M+H]+", "fragment"]},
+            "Formic acid": {"ions": [47.0128], "formula": "CH2O2"},
+        }
+        compounds = compounds_from_ion_list(ion_list)
+        assert [c.name for c in compounds] == ["Caffeine", "Formic acid"]
+        assert compounds[0].target_list == [195.0877, 138.0662]
+        assert compounds[0].ion_info == ["[M+H]+", "fragment"]
+        assert compounds[1].ion_info == []
+        assert set(compounds[1].ions) == {47.0128}
+        assert compounds[1].formula == "CH2O2"
+        assert compounds[0].formula is None
+
+    def test_metadata_keys_are_skipped(self):
+        compounds = compounds_from_ion_list(
+            {"_adducts": ["[M+H]+"], "Glycine": {"ions": [76.0393]}}
+        )
+        assert [c.name for c in compounds] == ["Glycine"]
+
+    def test_list_layout_with_legacy_strings(self):
+        compounds = compounds_from_ion_list(
+            [{"name": "Alanine", "ions": "90.0550, 44.0495", "info": "[M+H]+, "}]
+        )
+        assert compounds[0].target_list == [90.055, 44.0495]
+        assert compounds[0].ion_info == ["[M+H]+"]
+
+    def test_compounds_passed_through(self):
+        existing = Compound(name="Serine", target_list=[106.0499])
+        assert compounds_from_ion_list([existing])[0] is existing
+
+    def test_rt_window_keys(self):
+        (compound,) = compounds_from_ion_list(
+            {"Caffeine": {"ions": [195.0877], "rt_min": 3.5, "rt_max": 4.2}}
+        )
+        assert (compound.rt_min, compound.rt_max) == (3.5, 4.2)
+        assert compound.has_rt_window
+
+    def test_formula_expanded_into_adducts(self):
+        (compound,) = compounds_from_ion_list(
+            {"_adducts": ["[M+H]+", "[M-H]-"], "Ethyl acetate": {"formula": "C4H8O2"}}
+        )
+        # Monoisotopic mass 88.0524
+        assert compound.target_list == pytest.approx([89.0597, 87.0452], abs=1e-4)
+        assert compound.ion_info == ["[M+H]+", "[M-H]-"]
+        assert compound.formula == "C4H8O2"
+
+    def test_invalid_formula_raises(self):
+        with pytest.raises(ValueError, match="Ethyl acetate"):
+            compounds_from_ion_list({"Ethyl acetate": {"formula": "C4H8Xx2"}})
+
+    def test_srm_transitions_become_ions(self):
+        (compound,) = compounds_from_ion_list(
+            {"Caffeine": {"transitions": [[195.1, 138.1], [195.1, 110.1]]}}
+        )
+        assert compound.transitions == [(195.1, 138.1), (195.1, 110.1)]
+        assert compound.target_list == [138.1, 110.1]
+        assert compound.ion_info == ["195.1>138.1", "195.1>110.1"]
+
+    def test_transitions_must_match_ions(self):
+        with pytest.raises(ValueError):
+            compounds_from_ion_list(
+                {"Caffeine": {"ions": [138.1], "transitions": [[195.1, 138.1], [195.1, 110.1]]}}
+            )
+
+    def test_mobility_range_key(self):
+        (compound,) = compounds_from_ion_list(
+            {"Caffeine": {"ions": [195.0877], "mobility_range": [0.95, 1.05]}}
+        )
+        assert compound.mobility_range == (0.95, 1.05)
+
+    def test_neutral_mass_expanded_into_adducts(self):
+        (compound,) = compounds_from_ion_list(
+            {"Caffeine": {"mass": 194.0804, "adducts": ["[M+H]+", "[M+Na]+", "[M-H]-"]}}
+        )
+        assert compound.target_list == pytest.approx([195.0877, 217.0696, 193.0731], abs=1e-4)
+        assert compound.ion_info == ["[M+H]+", "[M+Na]+", "[M-H]-"]
+
+    def test_adducts_default_to_ion_list_setting(self):
+        (compound,) = compounds_from_ion_list(
+            {"_adducts": ["[M+K]+"], "Caffeine": {"ions": [138.0662], "mass": 194.0804}}
+        )
+        assert compound.target_list == pytest.approx([138.0662, 233.0436], abs=1e-4)
+        assert compound.ion_info == ["", "[M+K]+"]
+
+    def test_listed_adduct_not_duplicated(self):
+        (compound,) = compounds_from_ion_list(
+            [{"name": "Caffeine", "ions": [195.0877], "info": ["mine"], "mass": 194.0804}]
+        )
+        assert compound.target_list == pytest.approx([195.0877, 193.0731], abs=1e-4)
+        assert compound.ion_info == ["mine", "[M-H]-"]
+
+    def test_unknown_adduct_raises(self):
+        with pytest.raises(ValueError, match="Caffeine"):
+            compounds_from_ion_list({"Caffeine": {"mass": 194.0804, "adducts": ["[M+Xx]+"]}})
+
+    @pytest.mark.parametrize("charges", ["1-3", [1, 3]])
+    def test_charge_range_expanded_into_charge_states(self, charges):
+        (compound,) = compounds_from_ion_list({"Peptide": {"mass": 1000.0, "charges": charges}})
+        assert compound.target_list == pytest.approx([1001.0073, 501.0073, 334.3406], abs=1e-4)
+        assert compound.ion_info == ["[M+H]+", "[M+2H]2+", "[M+3H]3+"]
+        assert preprocessing._ion_charge(compound, 2) == 3
+
+    def test_negative_charge_range_with_adducts(self):
+        (compound,) = compounds_from_ion_list(
+            {
+                "Oligo": {
+                    "mass": 1000.0, "charges": 2, "polarity": "negative",
+                    "adducts": ["[M+Cl]-"],
+                }
+            }
+        )
+        assert compound.ion_info == ["[M+Cl]-", "[M-2H]2-"]
+        assert compound.target_list[1] == pytest.approx(498.9927, abs=1e-4)
+
+    def test_charge_range_from_formula(self):
+        (compound,) = compounds_from_ion_list(
+            {"Caffeine": {"formula": "C8H10N4O2", "charges": "1"}}
+        )
+        assert compound.target_list == pytest.approx([195.0877], abs=1e-4)
+
+    @pytest.mark.parametrize(
+        "entry",
+        [
+            {"mass": 1000.0, "charges": "3-1"},
+            {"mass": 1000.0, "charges": "0-2"},
+            {"mass": 1000.0, "charges": "one"},
+            {"mass": 1000.0, "charges": [1, 2, 3]},
+            {"charges": "1-3"},
+        ],
+    )
+    def test_invalid_charge_range_raises(self, entry):
+        with pytest.raises(ValueError, match="Peptide"):
+            compounds_from_ion_list({"Peptide": entry})
+
+    def test_internal_standard(self):
+        compounds = compounds_from_ion_list(
+            {
+                "Caffeine": {"ions": [195.0877], "internal_standard": "Caffeine-d9"},
+                "Caffeine-d9": {"ions": [204.1442]},
+            }
+        )
+        assert compounds[0].internal_standard == "Caffeine-d9"
+        assert compounds[1].internal_standard is None
+
+    @pytest.mark.parametrize("internal_standard", ["Caffeine", "Theobromine"])
+    def test_invalid_internal_standard_raises(self, internal_standard):
+        with pytest.raises(ValueError, match=internal_standard):
+            compounds_from_ion_list(
+                {"Caffeine": {"ions": [195.0877], "internal_standard": internal_standard}}
+            )
+
+    def test_tolerance_applies_to_all_ions(self):
+        (compound,) = compounds_from_ion_list(
+            {"Caffeine": {"ions": [195.0877, 138.0662], "tolerance": "2 mDa"}}
+        )
+        assert compound.mass_tolerance == [(2.0, "mDa"), (2.0, "mDa")]
+        assert compound.tolerance_ranges()[138.0662] == pytest.approx((138.0642, 138.0682))
+
+    def test_per_ion_tolerances(self):
+        (compound,) = compounds_from_ion_list(
+            {"Caffeine": {"ions": [195.0877, 138.0662], "tolerances": ["5 ppm", None]}}
+        )
+        assert compound.mass_tolerance == [(5.0, "ppm"), None]
+        assert list(compound.tolerance_ranges()) == [195.0877]
+
+    @pytest.mark.parametrize("tolerances", [["5 ppm"], ["5 ppm", "5 furlongs"]])
+    def test_invalid_tolerances_raise(self, tolerances):
+        with pytest.raises(ValueError):
+            compounds_from_ion_list(
+                {"Caffeine": {"ions": [195.0877, 138.0662], "tolerances": tolerances}}
+            )
+
+    def test_missing_name_raises(self):
+        with pytest.raises(ValueError):
+            compounds_from_ion_list([{"ions": [100.0]}])
+
+    def test_non_numeric_mz_raises(self):
+        with pytest.raises(ValueError, match="Leucine"):
+            compounds_from_ion_list({"Leucine": {"ions": ["abc"]}})
+
+
+class TestConstructXicsWithIonList:
+    def test_accepts_plain_ion_list(self, monkeypatch):
+        mz = np.array([100.0, 200.0])
+        intensity = np.array([10.0, 20.0])
+        scans = [(0.1 * i, 30.0, 1, mz, intensity * (i + 1)) for i in range(5)]
+        monkeypatch.setattr(
+            preprocessing,
+            "iter_ms_scans",
+            lambda path, progress_callback=None, **filters: iter(scans),
+        )
+
+        compounds = preprocessing.construct_xics(
+            "run.mzML", {"A": {"ions": [100.0]}, "B": {"ions": [200.0]}}
+        )
+        assert [c.name for c in compounds] == ["A", "B"]
+        assert compounds[0].file == "run.mzML"
+        np.testing.assert_allclose(compounds[1].ions[200.0]["MS Intensity"][1], [20, 40, 60, 80, 100])
+
+    def test_ion_tolerance_widens_window(self, monkeypatch):
+        # 1.5 mDa off, outside the global window; 195.0877 is not exact in float32
+        mz = np.array([195.0892, 200.003])
+        scans = [(0.1 * i, 30.0, 1, mz, np.array([10.0, 20.0])) for i in range(5)]
+        monkeypatch.setattr(
+            preprocessing,
+            "iter_ms_scans",
+            lambda path, progress_callback=None, **filters: iter(scans),
+        )
+
+        narrow, wide = preprocessing.construct_xics(
+            "run.mzML",
+            {"A": {"ions": [200.0]}, "B": {"ions": [195.0877], "tolerance": "2 mDa"}},
+            mass_accuracy=1e-6,
+        )
+        np.testing.assert_allclose(narrow.ions[200.0]["MS Intensity"][1], 0.0)
+        np.testing.assert_allclose(wide.ions[195.0877]["MS Intensity"][1], 10.0)
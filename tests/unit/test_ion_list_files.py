@@ -0,0 +1,66 @@
+"""
+Tests for ion list files in loading.py.
+
+Covers:
+- load_ion_list_file()/save_ion_list_file() round trips for JSON, TOML and CSV
+- Rejection of unknown extensions and malformed files
+"""
+
+import pytest
+
+from utils.classes import compounds_from_ion_list
+from utils.loading import load_ion_list_file, save_ion_list_file
+
+ION_LIST = {
+    "_adducts": ["[M+H]+"],
+    "Caffeine": {
+        "mass": 194.0804,
+        "formula": "C8H10N4O2",
+        "rt_min": 4.5,
+        "rt_max": 5.5,
+        "internal_standard": "Caffeine-d9",
+        "tolerance": "5 ppm",
+    },
+    "Caffeine-d9": {"ions": [204.1442, 138.0662], "info": ["[M+H]+", "fragment"]},
+    "Formic acid": {
+        "ions": [44.9982],
+        "polarity": "negative",
+        "smoothing": {"method": "savgol", "window": 7},
+        "transitions": [[44.9982, 44.9982]],
+    },
+}
+
+
+class TestIonListFiles:
+    @pytest.mark.parametrize("suffix", [".json", ".toml"])
+    def test_round_trip(self, tmp_path, suffix):
+        path = tmp_path / f"ions{suffix}"
+        save_ion_list_file(ION_LIST, path)
+        assert load_ion_list_file(path) == ION_LIST
+        assert len(compounds_from_ion_list(load_ion_list_file(path))) == 3
+
+    def test_csv_round_trip_keeps_flat_columns(self, tmp_path):
+        path = tmp_path / "ions.csv"
+        save_ion_list_file(ION_LIST, path)
+        loaded = load_ion_list_file(path)
+        assert loaded["Caffeine"] == ION_LIST["Caffeine"]
+        assert loaded["Caffeine-d9"] == ION_LIST["Caffeine-d9"]
+        assert loaded["Formic acid"] == {"ions": [44.9982], "polarity": "negative"}
+
+    def test_none_values_are_dropped(self, tmp_path):
+        path = tmp_path / "ions.toml"
+        save_ion_list_file({"A": {"ions": [100.0], "rt_min": None}}, path)
+        assert load_ion_list_file(path) == {"A": {"ions": [100.0]}}
+
+    def test_unknown_extension_raises(self, tmp_path):
+        with pytest.raises(ValueError, match="Unsupported"):
+            load_ion_list_file(tmp_path / "ions.yaml")
+
+    @pytest.mark.parametrize(
+        "suffix, content", [(".json", "{"), (".toml", "[A\nions = 1"), (".csv", "mz\n100\n")]
+    )
+    def test_malformed_file_raises(self, tmp_path, suffix, content):
+        path = tmp_path / f"ions{suffix}"
+        path.write_text(content)
+        with pytest.raises(ValueError):
+            load_ion_list_file(path)
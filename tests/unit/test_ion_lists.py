@@ -0,0 +1,102 @@
+"""
+Tests for the bundled ion list registry in utils/ion_lists.py.
+
+Covers:
+- list_ion_lists()/load_ion_list() on config.json and a custom config
+- Errors for missing, unknown and unreadable ion lists
+- construct_xics() taking an ion list name and refusing an empty one
+- validate_ion_list() errors and warnings
+"""
+
+import json
+
+import pytest
+
+from calculation.preprocessing import construct_xics
+from utils.errors import IonListError
+from utils.ion_lists import list_ion_lists, load_ion_list, validate_ion_list
+
+
+@pytest.fixture
+def config(tmp_path):
+    path = tmp_path / "config.json"
+    path.write_text(json.dumps({"B list": {"X": {"ions": [100.0]}}, "A list": {}}))
+    return str(path)
+
+
+class TestRegistry:
+    def test_bundled_lists(self):
+        names = list_ion_lists()
+        assert names == sorted(names)
+        assert "Short-chain fatty acids" in names
+        assert "Formic acid" in load_ion_list("Short-chain fatty acids")
+
+    def test_custom_config(self, config):
+        assert list_ion_lists(config) == ["A list", "B list"]
+        assert load_ion_list("B list", config) == {"X": {"ions": [100.0]}}
+
+    @pytest.mark.parametrize("name", [None, "", "C list"])
+    def test_missing_or_unknown_raises(self, config, name):
+        with pytest.raises(IonListError):
+            load_ion_list(name, config)
+
+    def test_unreadable_config_raises(self, tmp_path):
+        with pytest.raises(IonListError):
+            list_ion_lists(str(tmp_path / "missing.json"))
+
+
+class TestConstructXicsIonListChoice:
+    @pytest.mark.parametrize("compounds", [None, (), {}, ""])
+    def test_requires_an_ion_list(self, compounds):
+        with pytest.raises(IonListError):
+            construct_xics("run.mzML", compounds)
+
+    def test_unknown_name_raises(self):
+        with pytest.raises(IonListError, match="Unknown ion list"):
+            construct_xics("run.mzML", "No such list")
+
+
+class TestValidateIonList:
+    def test_clean_list(self):
+        report = validate_ion_list({"Caffeine": {"ions": [195.0877]}, "Theobromine": {"ions": [181.0720]}})
+        assert report.ok
+        assert report.issues == []
+
+    def test_collects_every_error(self):
+        report = validate_ion_list(
+            [
+                {"name": "A", "ions": [100.0]},
+                {"name": "A", "ions": [200.0]},
+                {"name": "B", "ions": ["abc"]},
+                {"name": "C"},
+                {"ions": [300.0]},
+                {"name": "D", "ions": [-5.0]},
+                {"name": "E", "ions": [400.0], "internal_standard": "Z"},
+            ]
+        )
+        assert not report.ok
+        assert {issue.compound for issue in report.errors} == {"A", "B", "C", None, "D", "E"}
+        assert report.warnings == []
+
+    def test_warnings(self):
+        report = validate_ion_list(
+            {
+                "A": {"ions": [100.0, 6000.0]},
+                "B": {"ions": [100.00005]},
+                "C": {"ions": [100.05], "tolerance": "1 mDa"},  # Clear of A with its own window
+            }
+        )
+        assert report.ok
+        messages = [str(issue) for issue in report.warnings]
+        assert "Warning: A: m/z 6000.0 outside 10-5000" in messages
+        assert [issue.compound for issue in report.warnings if "overlaps" in issue.message] == ["A"]
+
+    def test_duplicate_keys_in_file(self, tmp_path):
+        path = tmp_path / "ions.json"
+        path.write_text('{"A": {"ions": [100.0]}, "A": {"ions": [200.0]}}')
+        (error,) = validate_ion_list(path).errors
+        assert (error.compound, error.message) == ("A", "duplicate compound name")
+
+    def test_unreadable_file(self, tmp_path):
+        (error,) = validate_ion_list(tmp_path / "missing.json").errors
+        assert error.message.startswith("Cannot read ion list")
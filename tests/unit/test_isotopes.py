@@ -0,0 +1,62 @@
+"""
+Tests for calculation/isotopes.py.
+
+Covers:
+- isotopologue_mzs() spacing for singly and multiply charged ions
+- observed_isotope_ratios() over the whole trace and a window
+- isotope_pattern_score()
+"""
+
+import numpy as np
+import pytest
+
+from calculation.isotopes import (
+    ISOTOPE_SPACING,
+    isotope_pattern_score,
+    isotopologue_mzs,
+    observed_isotope_ratios,
+)
+
+
+def _xic(values, times=None):
+    values = np.asarray(values, dtype=np.float64)
+    if times is None:
+        times = np.arange(len(values), dtype=np.float64)
+    return np.array((times, values))
+
+
+class TestIsotopologueMzs:
+    def test_singly_charged(self):
+        expected = [100.0 + ISOTOPE_SPACING, 100.0 + 2 * ISOTOPE_SPACING]
+        assert isotopologue_mzs(100.0) == pytest.approx(expected)
+
+    def test_charge_divides_spacing(self):
+        expected = [500.0 + ISOTOPE_SPACING / 2]
+        assert isotopologue_mzs(500.0, charge=-2, n_isotopes=1) == pytest.approx(expected)
+
+
+class TestObservedIsotopeRatios:
+    def test_whole_trace(self):
+        mono = _xic([0, 10, 30, 10])
+        ratios = observed_isotope_ratios(mono, [_xic([0, 1, 3, 1]), _xic([0, 0.5, 0.5, 0])])
+        np.testing.assert_allclose(ratios, [1.0, 0.1, 0.02])
+
+    def test_window_restricts_sum(self):
+        mono = _xic([100, 10, 10, 100])
+        m1 = _xic([0, 5, 5, 0])
+        np.testing.assert_allclose(observed_isotope_ratios(mono, [m1], window=(1, 2)), [1.0, 0.5])
+
+    def test_no_signal_returns_none(self):
+        assert observed_isotope_ratios(_xic([0, 0]), [_xic([1, 1])]) is None
+
+
+class TestIsotopePatternScore:
+    def test_perfect_match(self):
+        assert isotope_pattern_score([1.0, 0.1, 0.02], [1.0, 0.1, 0.02]) == pytest.approx(1.0)
+
+    def test_deviation_lowers_score(self):
+        # |0.3 - 0.1| / 1.1
+        assert isotope_pattern_score([1.0, 0.3], [1.0, 0.1]) == pytest.approx(1 - 0.2 / 1.1)
+
+    def test_clipped_at_zero(self):
+        assert isotope_pattern_score([1.0, 5.0], [1.0, 0.1]) == 0.0
@@ -0,0 +1,264 @@
+"""
+Tests for the MS2 spectral library in utils/library.py.
+
+Covers:
+- MSP parsing (iter_msp)
+- Precursor lookup and cosine matching on SpectralLibrary
+- The .npz cache written by from_msp()
+- Merging spectra into a library by spectrum id
+- Searching several libraries with priorities (LibraryCollection)
+- download_msp() / update_library() with conditional refreshes
+"""
+
+import io
+import json
+import os
+import zipfile
+from urllib.error import HTTPError
+
+import numpy as np
+import pytest
+
+from utils import library as library_module
+from utils.library import (
+    LibraryCollection,
+    SpectralLibrary,
+    download_msp,
+    iter_msp,
+    spectrum_id,
+    update_library,
+)
+
+MSP_TEXT = """\
+Name: Caffeine
+Precursor_type: [M+H]+
+PrecursorMZ: 195.0877
+Num Peaks: 3
+138.0662 100
+110.0713\t40
+42.0338 5
+
+NAME: Theophylline
+PRECURSORMZ: 181.0720
+Num Peaks: 2
+124.0505 100; 96.0556 30 "annotated";
+Name: No precursor
+Num Peaks: 1
+50 1
+"""
+
+
+@pytest.fixture
+def msp_file(tmp_path):
+    path = tmp_path / "library.msp"
+    path.write_text(MSP_TEXT)
+    return str(path)
+
+
+class TestIterMsp:
+    def test_records(self, msp_file):
+        records = list(iter_msp(msp_file))
+        assert [r["metadata"]["name"] for r in records] == [
+            "Caffeine",
+            "Theophylline",
+            "No precursor",
+        ]
+        assert records[0]["precursor_mz"] == pytest.approx(195.0877)
+        assert records[0]["metadata"]["precursor_type"] == "[M+H]+"
+        np.testing.assert_allclose(records[0]["intensity"], [100.0, 40.0, 5.0])
+
+    def test_nist_style_peak_line(self, msp_file):
+        theophylline = list(iter_msp(msp_file))[1]
+        np.testing.assert_allclose(theophylline["mz"], [124.0505, 96.0556])
+
+    def test_missing_precursor(self, msp_file):
+        assert list(iter_msp(msp_file))[2]["precursor_mz"] is None
+
+
+class TestSpectralLibrary:
+    def test_sorted_by_precursor(self, msp_file):
+        library = SpectralLibrary.from_msp(msp_file, use_cache=False)
+        assert len(library) == 3
+        assert library.spectrum(0)["metadata"]["name"] == "Theophylline"
+        assert library.spectrum(2)["precursor_mz"] is None
+        # Peaks are m/z sorted within each spectrum
+        np.testing.assert_allclose(library.spectrum(1)["mz"], [42.0338, 110.0713, 138.0662])
+
+    def test_search(self, msp_file):
+        library = SpectralLibrary.from_msp(msp_file, use_cache=False)
+        assert list(library.search(195.088, tolerance=0.005)) == [1]
+        assert len(library.search(300.0)) == 0
+
+    def test_match_ranks_by_cosine(self, msp_file):
+        library = SpectralLibrary.from_msp(msp_file, use_cache=False)
+        hits = library.match(
+            np.array([138.066, 110.071]),
+            np.array([100.0, 40.0]),
+            precursor_mz=190.0,
+            precursor_tolerance=10.0,
+        )
+        assert [hit["name"] for hit in hits] == ["Caffeine", "Theophylline"]
+        assert hits[0]["score"] > 0.99
+        assert hits[0]["matched_peaks"] == 2
+        assert hits[1]["score"] == 0.0
+
+    def test_match_with_entropy_and_ppm(self, msp_file):
+        library = SpectralLibrary.from_msp(msp_file, use_cache=False)
+        hits = library.match(
+            np.array([138.0662, 110.0713]),
+            np.array([100.0, 40.0]),
+            precursor_mz=195.0877,
+            fragment_tolerance=5,
+            tolerance_unit="ppm",
+            method="entropy",
+        )
+        assert [hit["name"] for hit in hits] == ["Caffeine"]
+        assert hits[0]["matched_peaks"] == 2
+
+    def test_cache_round_trip(self, msp_file):
+        library = SpectralLibrary.from_msp(msp_file)
+        cache = f"{msp_file}.npz"
+        assert os.path.exists(cache)
+
+        cached = SpectralLibrary.load(cache)
+        np.testing.assert_array_equal(cached.precursor_mz, library.precursor_mz)
+        np.testing.assert_array_equal(cached.mz, library.mz)
+        assert cached.metadata == library.metadata
+
+    def test_stale_cache_is_rebuilt(self, msp_file):
+        SpectralLibrary.from_msp(msp_file)
+        cache = f"{msp_file}.npz"
+        os.utime(cache, (0, 0))
+        with open(msp_file, "a") as handle:
+            handle.write("\nName: Extra\nPrecursorMZ: 500\nNum Peaks: 1\n100 1\n")
+        assert len(SpectralLibrary.from_msp(msp_file)) == 4
+
+    def test_merged_replaces_by_id(self, msp_file):
+        library = SpectralLibrary.from_msp(msp_file, use_cache=False)
+        library.metadata[0]["db#"] = "MoNA-1"
+        update = SpectralLibrary.from_spectra(
+            [
+                {"precursor_mz": 181.0720, "mz": np.array([124.05]), "intensity": np.ones(1),
+                 "metadata": {"name": "Theophylline v2", "db#": "MoNA-1"}},
+                {"precursor_mz": 100.0, "mz": np.array([50.0, 60.0]), "intensity": np.ones(2),
+                 "metadata": {"name": "New"}},
+            ]
+        )
+        merged = library.merged(update)
+        assert len(merged) == 4
+        assert [m["name"] for m in merged.metadata] == [
+            "New", "Theophylline v2", "Caffeine", "No precursor"
+        ]
+        assert np.all(np.diff(merged.precursor_mz[:3]) > 0)
+        np.testing.assert_array_equal(merged.spectrum(0)["mz"], [50.0, 60.0])
+        assert spectrum_id({"accession": "A1"}) == "A1" and spectrum_id({}) is None
+
+
+def _caffeine_library(name):
+    return SpectralLibrary.from_spectra(
+        [
+            {"precursor_mz": 195.0877, "mz": np.array([110.0713, 138.0662]),
+             "intensity": np.array([40.0, 100.0]), "metadata": {"name": name}},
+        ]
+    )
+
+
+class TestLibraryCollection:
+    def test_hits_labelled_and_ranked(self, msp_file):
+        libraries = LibraryCollection()
+        libraries.add_file(msp_file, name="MoNA")
+        libraries.add("in-house", _caffeine_library("Caffeine standard"), priority=1)
+        assert libraries.names == ["in-house", "MoNA"]
+        hits = libraries.match(
+            np.array([138.066, 110.071]),
+            np.array([100.0, 40.0]),
+            precursor_mz=190.0,
+            precursor_tolerance=10.0,
+        )
+        # Equal scores: the higher priority library first
+        assert [(hit["library"], hit["name"]) for hit in hits] == [
+            ("in-house", "Caffeine standard"), ("MoNA", "Caffeine"), ("MoNA", "Theophylline")
+        ]
+        assert hits[0]["priority"] == 1 and hits[0]["index"] == 0
+        assert len(libraries.match(np.array([138.066]), np.ones(1), 195.0877, top_n=1)) == 1
+
+    def test_add_file_formats_and_names(self, msp_file, tmp_path):
+        mgf = tmp_path / "standards.mgf"
+        mgf.write_text("BEGIN IONS\nTITLE=Caffeine\nPEPMASS=195.0877\n138.0662 100\nEND IONS\n")
+        libraries = LibraryCollection()
+        libraries.add_file(msp_file, use_cache=False)
+        assert len(libraries.add_file(mgf)) == 1
+        assert libraries.names == ["library", "standards"] and "standards" in libraries
+        with pytest.raises(ValueError, match="registered already"):
+            libraries.add_file(mgf)
+        with pytest.raises(ValueError, match="Unknown library format"):
+            libraries.add_file(tmp_path / "library.json")
+        libraries.remove("library")
+        assert len(libraries) == 1
+        with pytest.raises(KeyError):
+            libraries.library("library")
+
+
+class _Response(io.BytesIO):
+    def __init__(self, body, headers):
+        super().__init__(body)
+        self.headers = headers
+
+
+def _zipped(text, name="export.msp"):
+    buffer = io.BytesIO()
+    with zipfile.ZipFile(buffer, "w") as zf:
+        zf.writestr(name, text)
+    return buffer.getvalue()
+
+
+class TestDownload:
+    @pytest.fixture
+    def server(self, monkeypatch):
+        requests = []
+        state = {"body": _zipped(MSP_TEXT), "etag": '"v1"'}
+
+        def urlopen(request, **kwargs):
+            requests.append(request)
+            if request.get_header("If-none-match") == state["etag"]:
+                raise HTTPError(request.full_url, 304, "Not Modified", {}, None)
+            headers = {"content-length": str(len(state["body"])), "ETag": state["etag"]}
+            return _Response(state["body"], headers)
+
+        monkeypatch.setattr(library_module, "urlopen", urlopen)
+        return requests, state
+
+    def test_download_and_index(self, tmp_path, server):
+        percentages = []
+        library, downloaded = update_library(
+            tmp_path, url="https://host/ms2.zip", progress=percentages.append
+        )
+        assert downloaded and len(library) == 3
+        assert percentages[-1] == 100
+        msp_path = tmp_path / library_module.MSP_FILENAME
+        assert os.path.exists(f"{msp_path}.npz")
+        source = tmp_path / (msp_path.name + ".source.json")
+        assert json.loads(source.read_text())["etag"] == '"v1"'
+        assert sorted(p.name for p in tmp_path.iterdir() if p.name.endswith(".part")) == []
+
+    def test_unchanged_library_not_fetched(self, tmp_path, server):
+        requests, state = server
+        download_msp(tmp_path, url="https://host/ms2.zip")
+        assert download_msp(tmp_path, url="https://host/ms2.zip")[1] is False
+        assert requests[-1].get_header("If-none-match") == '"v1"'
+
+        extra = "\nName: Extra\nPrecursorMZ: 500\nNum Peaks: 1\n100 1\n"
+        state["body"], state["etag"] = _zipped(MSP_TEXT + extra), '"v2"'
+        library, downloaded = update_library(tmp_path, url="https://host/ms2.zip")
+        assert downloaded and len(library) == 4
+        assert download_msp(tmp_path, url="https://host/ms2.zip", force=True)[1] is True
+
+    def test_archive_without_msp_raises(self, tmp_path, server):
+        _, state = server
+        state["body"] = _zipped("x", name="readme.txt")
+        with pytest.raises(ValueError, match="does not contain an .msp file"):
+            download_msp(tmp_path, url="https://host/ms2.zip")
+        state["body"] = b"not a zip"
+        with pytest.raises(ValueError, match="not a zip archive"):
+            download_msp(tmp_path, url="https://host/ms2.zip")
+        assert list(tmp_path.iterdir()) == []
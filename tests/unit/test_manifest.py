@@ -0,0 +1,94 @@
+"""
+Tests for the reproducibility manifests in utils/manifest.py.
+
+Covers:
+- file_checksum() of files and directory formats, unreadable files
+- config_hash() and ion_list_hash() stability
+- build_manifest() and batch_manifest() contents
+- write_manifest()/read_manifest() sidecars
+"""
+
+import hashlib
+from types import SimpleNamespace
+
+import numpy as np
+
+from utils.classes import compounds_from_ion_list
+from utils.manifest import (
+    SOFTWARE_NAME,
+    batch_manifest,
+    build_manifest,
+    config_hash,
+    file_checksum,
+    ion_list_hash,
+    manifest_path,
+    read_manifest,
+    write_manifest,
+)
+
+ION_LIST = {"Caffeine": {"ions": [195.0877]}, "Glycine": {"ions": [76.039]}}
+
+
+class TestFileChecksum:
+    def test_file(self, tmp_path):
+        path = tmp_path / "run.mzML"
+        path.write_bytes(b"<mzML/>")
+        assert file_checksum(path) == hashlib.sha256(b"<mzML/>").hexdigest()
+        path.write_bytes(b"<mzML></mzML>")  # Changed size, hashed again
+        assert file_checksum(path) == hashlib.sha256(b"<mzML></mzML>").hexdigest()
+
+    def test_directory(self, tmp_path):
+        run = tmp_path / "run.d"
+        run.mkdir()
+        (run / "analysis.tdf").write_bytes(b"a")
+        first = file_checksum(run)
+        (run / "analysis.tdf").write_bytes(b"b")
+        assert file_checksum(run) != first
+
+    def test_missing(self, tmp_path):
+        assert file_checksum(tmp_path / "gone.mzML") is None
+
+
+class TestHashes:
+    def test_config_hash_ignores_key_order(self):
+        assert config_hash({"a": 1, "b": (1, 2)}) == config_hash({"b": [1, 2], "a": 1})
+        assert config_hash({"a": np.float64(1.0)}) == config_hash({"a": 1.0})
+        assert config_hash({"a": 1}) != config_hash({"a": 2})
+        assert config_hash(None) is None
+
+    def test_ion_list_hash(self):
+        compounds = compounds_from_ion_list(ION_LIST)
+        assert ion_list_hash(compounds) == ion_list_hash(compounds_from_ion_list(ION_LIST))
+        compounds[0].ions[195.0877]["RT"] = 1.0  # Results do not count
+        assert ion_list_hash(compounds) == ion_list_hash(compounds_from_ion_list(ION_LIST))
+        other = compounds_from_ion_list({"Caffeine": {"ions": [195.0878]}})
+        assert ion_list_hash(other) != ion_list_hash(compounds[:1])
+
+
+class TestManifest:
+    def test_build_manifest(self, tmp_path):
+        path = tmp_path / "run.mzML"
+        path.write_bytes(b"data")
+        manifest = build_manifest(path, compounds_from_ion_list(ION_LIST), {"xic_mode": "sum"})
+        assert manifest["software"] == SOFTWARE_NAME and manifest["version"]
+        assert manifest["input_file"] == "run.mzML"
+        assert manifest["input_sha256"] == hashlib.sha256(b"data").hexdigest()
+        assert manifest["config_hash"] == config_hash({"xic_mode": "sum"})
+        assert manifest["config"] == {"xic_mode": "sum"}
+        # A checksum computed elsewhere, e.g. in the pool process, is kept
+        assert build_manifest(path, (), input_sha256="0")["input_sha256"] == "0"
+
+    def test_batch_manifest(self):
+        measurements = [
+            SimpleNamespace(filename="a", manifest={"input_sha256": "0"}),
+            SimpleNamespace(filename="b"),
+        ]
+        manifest = batch_manifest(measurements)
+        assert manifest["measurements"] == {"a": {"input_sha256": "0"}, "b": None}
+
+    def test_sidecar(self, tmp_path):
+        path = tmp_path / "areas.csv"
+        assert read_manifest(path) is None
+        assert write_manifest(path, {"version": "1.0"}) == manifest_path(path)
+        assert manifest_path(path).name == "areas.csv.manifest.json"
+        assert read_manifest(path) == {"version": "1.0"}
@@ -0,0 +1,48 @@
+"""
+Tests for the memory-mapped input files in utils/mapped_file.py.
+
+Covers:
+- open_input() reading, seeking and line iteration, mapped or not
+- view() slices, zero-copy when mapped
+- falling back to a plain file when mapping fails (empty files)
+"""
+
+import pytest
+
+from utils.mapped_file import open_input
+
+DATA = b"BEGIN IONS\nPEPMASS=195.08\nEND IONS\n"
+
+
+@pytest.fixture
+def path(tmp_path):
+    path = tmp_path / "run.mgf"
+    path.write_bytes(DATA)
+    return path
+
+
+class TestOpenInput:
+    @pytest.mark.parametrize("use_mmap", [True, False])
+    def test_read_and_seek(self, path, use_mmap):
+        with open_input(path, use_mmap) as handle:
+            assert handle.mapped is use_mmap
+            assert handle.read(5) == b"BEGIN" and handle.tell() == 5
+            assert handle.seek(0) == 0
+            assert list(handle) == DATA.splitlines(keepends=True)
+            assert handle.size() == len(DATA)
+        assert handle.closed
+
+    @pytest.mark.parametrize("use_mmap", [True, False])
+    def test_view(self, path, use_mmap):
+        with open_input(path, use_mmap) as handle:
+            view = handle.view(11, 7)
+            assert bytes(view) == b"PEPMASS"
+            assert isinstance(view, memoryview) is use_mmap
+            assert bytes(handle.view()) == DATA
+
+    def test_empty_file_falls_back(self, tmp_path):
+        path = tmp_path / "empty.ibd"
+        path.write_bytes(b"")
+        with open_input(path) as handle:
+            assert not handle.mapped
+            assert handle.read() == b"" and handle.size() == 0
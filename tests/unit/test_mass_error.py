@@ -0,0 +1,82 @@
+"""
+Tests for per-ion mass error statistics in calculation/mass_error.py.
+
+Covers:
+- observed_mz() picking the most intense peak in the window, or in a custom one
+- ion_mass_error() statistics across the integrated peak and at its apex
+- peak_mass_errors() setting "Mass Error" and skipping ions without a peak
+"""
+
+import numpy as np
+import pytest
+
+from calculation.mass_error import ion_mass_error, observed_mz, peak_mass_errors
+from utils.classes import compounds_from_ion_list
+
+TIMES = np.round(np.arange(0.0, 2.0, 0.1), 1)
+ION = 200.0
+# 1 to 5 ppm across the peak, 3 ppm at its apex
+ERRORS = {0.8: 1.0, 0.9: 2.0, 1.0: 3.0, 1.1: 4.0, 1.2: 5.0}
+
+
+class _Times:
+    """``.time[rt]`` of an indexed reader: the spectrum of the nearest scan."""
+
+    def __getitem__(self, rt):
+        ppm = ERRORS.get(round(rt, 1))
+        if ppm is None:
+            return {"m/z array": np.array([ION + 1.0]), "intensity array": np.array([1.0])}
+        return {
+            "m/z array": np.array([ION * (1 + ppm * 1e-6), ION + 0.05, ION + 1.0]),
+            "intensity array": np.array([100.0, 10.0, 1.0]),
+        }
+
+
+class _Reader:
+    time = _Times()
+
+
+def _compounds():
+    compounds = compounds_from_ion_list({"A": {"ions": [ION, 300.0]}})
+    data = compounds[0].ions[ION]
+    data["MS Intensity"] = np.array((TIMES, np.ones_like(TIMES)))
+    data["RT"] = 1.0
+    data["Integration Data"] = {"baseline_corrected_area": 1.0, "start_time": 0.8, "end_time": 1.2}
+    return compounds
+
+
+class TestObservedMz:
+    def test_most_intense_in_window(self):
+        spectrum = _Times()[1.0]
+        assert observed_mz(spectrum, ION, 1e-4) == pytest.approx(ION * (1 + 3e-6))
+
+    def test_nothing_in_window(self):
+        assert observed_mz(_Times()[0.0], ION, 1e-4) is None
+
+    def test_custom_window(self):
+        spectrum = _Times()[1.0]
+        assert observed_mz(spectrum, ION, 1e-4, window=(ION + 0.01, ION + 0.1)) == ION + 0.05
+
+
+class TestIonMassError:
+    def test_statistics(self):
+        data = _compounds()[0].ions[ION]
+        error = ion_mass_error(_Reader(), ION, data, mass_accuracy=1e-4)
+        assert error["n"] == 5
+        assert error["apex_mz"] == pytest.approx(ION * (1 + 3e-6))
+        assert error["apex_error_ppm"] == pytest.approx(3.0, abs=1e-6)
+        assert error["mean_ppm"] == pytest.approx(3.0, abs=1e-6)
+        assert error["median_ppm"] == pytest.approx(3.0, abs=1e-6)
+        assert error["sd_ppm"] == pytest.approx(np.std([1, 2, 3, 4, 5], ddof=1), abs=1e-6)
+
+    def test_not_integrated(self):
+        data = _compounds()[0].ions[300.0]
+        assert ion_mass_error(_Reader(), 300.0, data) is None
+
+
+class TestPeakMassErrors:
+    def test_sets_mass_error(self):
+        compounds = _compounds()
+        assert peak_mass_errors(compounds, _Reader(), mass_accuracy=1e-4) == 1
+        assert compounds[0].ions[ION]["Mass Error"]["n"] == 5
+        assert compounds[0].ions[300.0]["Mass Error"] is None
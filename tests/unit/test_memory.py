@@ -0,0 +1,67 @@
+"""
+Tests for the memory budget in utils/memory.py.
+
+Covers:
+- parse_memory_size() / set_memory_budget() / memory_budget()
+- files_in_parallel() limiting the files run together
+"""
+
+import pytest
+
+from utils import memory
+from utils.memory import files_in_parallel, memory_budget, parse_memory_size, set_memory_budget
+
+
+@pytest.fixture
+def files(tmp_path):
+    paths = []
+    for name, size in (("a.mzML", 400), ("b.mzML", 300), ("c.mzML", 200), ("d.mzML", 100)):
+        path = tmp_path / name
+        path.write_bytes(b"x" * size)
+        paths.append(str(path))
+    return paths
+
+
+@pytest.fixture(autouse=True)
+def no_global_budget():
+    yield
+    set_memory_budget(None)
+
+
+class TestParseMemorySize:
+    @pytest.mark.parametrize(
+        "size,expected",
+        [(2048, 2048), ("512", 512), ("1k", 1024), ("1.5 MB", 1572864), ("8GiB", 8 * 1024**3)],
+    )
+    def test_sizes(self, size, expected):
+        assert parse_memory_size(size) == expected
+
+    @pytest.mark.parametrize("size", ["lots", "5 PB", 0, -1])
+    def test_invalid(self, size):
+        with pytest.raises(ValueError):
+            parse_memory_size(size)
+
+    def test_global_budget(self):
+        assert memory_budget() is None
+        set_memory_budget("2GB")
+        assert memory_budget() == 2 * 1024**3
+        assert memory_budget(100) == 100
+
+
+class TestFilesInParallel:
+    def test_no_budget(self, files):
+        assert files_in_parallel(files, 4) == 4
+
+    def test_largest_files_fit(self, files, monkeypatch):
+        monkeypatch.setattr(memory, "FILE_MEMORY_FACTOR", 1.0)
+        assert files_in_parallel(files, 4, budget=700) == 2
+        assert files_in_parallel(files, 4, budget=10_000) == 4
+        assert files_in_parallel(files, 2, budget=10_000) == 2
+
+    def test_at_least_one(self, files):
+        assert files_in_parallel(files, 4, budget=10) == 1
+
+    def test_global_budget(self, files, monkeypatch):
+        monkeypatch.setattr(memory, "FILE_MEMORY_FACTOR", 1.0)
+        set_memory_budget(900)
+        assert files_in_parallel(files, 4) == 3
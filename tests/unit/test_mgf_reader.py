@@ -0,0 +1,113 @@
+"""
+Tests for MGF support.
+
+Covers:
+- iter_spectra() / iter_scans() / iter_ms2_scans() / find_nearest_ms2() in mgf_reader.py
+- detect_ms_format() and iter_ms_scans() dispatch in loading.py
+"""
+
+import numpy as np
+import pytest
+
+from utils.loading import detect_ms_format, find_nearest_ms2, iter_ms2_scans, iter_ms_scans
+from utils.mgf_reader import iter_spectra
+
+MGF_TEXT = """\
+# exported by a test
+COM=global parameters are ignored
+
+BEGIN IONS
+TITLE=caffeine
+PEPMASS=195.0877 12000
+CHARGE=1+
+RTINSECONDS=90
+138.0662 100
+110.0713 40 1+
+END IONS
+
+BEGIN IONS
+TITLE=anion
+PEPMASS=179.0350
+CHARGE=2-
+RTINSECONDS=300-312
+89.0244 55.5
+END IONS
+
+BEGIN IONS
+TITLE=no rt
+PEPMASS=195.0877
+50.0 1
+END IONS
+"""
+
+
+@pytest.fixture
+def mgf_file(tmp_path):
+    path = tmp_path / "library.mgf"
+    path.write_text(MGF_TEXT)
+    return str(path)
+
+
+class TestIterSpectra:
+    def test_parses_header_and_peaks(self, mgf_file):
+        spectra = list(iter_spectra(mgf_file))
+        assert [s["title"] for s in spectra] == ["caffeine", "anion", "no rt"]
+
+        caffeine = spectra[0]
+        assert caffeine["precursor_mz"] == pytest.approx(195.0877)
+        assert caffeine["charge"] == 1
+        assert caffeine["rt"] == pytest.approx(1.5)
+        np.testing.assert_allclose(caffeine["mz"], [138.0662, 110.0713])
+        np.testing.assert_allclose(caffeine["intensity"], [100.0, 40.0])
+
+    def test_negative_charge_and_rt_range(self, mgf_file):
+        anion = list(iter_spectra(mgf_file))[1]
+        assert anion["charge"] == -2
+        assert anion["rt"] == pytest.approx(5.0)
+
+    def test_missing_fields_are_none(self, mgf_file):
+        last = list(iter_spectra(mgf_file))[2]
+        assert last["charge"] is None
+        assert last["rt"] is None
+
+    def test_unterminated_block_dropped(self, tmp_path):
+        path = tmp_path / "cut.mgf"
+        path.write_text("BEGIN IONS\nPEPMASS=100\n50 1\n")
+        assert list(iter_spectra(str(path))) == []
+
+
+class TestLoadingDispatch:
+    def test_detect_by_extension_and_header(self, mgf_file, tmp_path):
+        assert detect_ms_format(mgf_file) == "MGF"
+        path = tmp_path / "peaks.txt"
+        path.write_text(MGF_TEXT)
+        assert detect_ms_format(str(path)) == "MGF"
+
+    def test_iter_ms_scans_reports_ms2(self, mgf_file):
+        scans = list(iter_ms_scans(mgf_file))
+        assert len(scans) == 3
+        assert {scan[2] for scan in scans} == {2}
+        assert scans[0][1] == pytest.approx(140.0)
+        assert scans[2][0] == 0.0
+
+    def test_iter_ms_scans_with_progress(self, mgf_file):
+        fractions = []
+        scans = list(iter_ms_scans(mgf_file, progress_callback=fractions.append))
+        assert len(scans) == 3
+        assert fractions[-1] == 1.0
+
+    def test_iter_ms_scans_polarity_from_charge_sign(self, mgf_file):
+        # "no rt" has no charge and is kept for either polarity
+        assert len(list(iter_ms_scans(mgf_file, polarity="positive"))) == 2
+        negative = list(iter_ms_scans(mgf_file, polarity="negative"))
+        assert [scan[0] for scan in negative] == pytest.approx([5.0, 0.0])
+
+    def test_find_nearest_ms2(self, mgf_file):
+        scan_time, mz, _ = find_nearest_ms2(mgf_file, 195.09, 1.4, mz_tolerance=0.01)
+        assert scan_time == pytest.approx(1.5)
+        np.testing.assert_allclose(mz, [138.0662, 110.0713])
+        assert find_nearest_ms2(mgf_file, 500.0, 1.4) is None
+
+    def test_iter_ms2_scans_skips_spectra_without_rt(self, mgf_file):
+        scans = list(iter_ms2_scans(mgf_file))
+        assert [scan[1] for scan in scans] == pytest.approx([195.0877, 179.0350])
@@ -0,0 +1,590 @@
+"""
+Tests for the lxml-based mzML reader on small synthetic files.
+
+Covers:
+- Binary array decoding (zlib / uncompressed, 32 / 64 bit, MS-Numpress)
+- iter_scans() (incl. polarity and scan filters, ion mobility, precursors, threaded
+  decoding), iter_ms2_scans(),
+  iter_dia_scans() and extract_tic_chromatogram() in mzml_reader.py
+- Scan acquisition metadata (iter_scan_metadata) and filtering on it
+- Progress reporting in loading.iter_ms_scans()
+- TIC / BPC extraction (extract_chromatogram_data)
+- SRM chromatograms (iter_srm_chromatograms)
+- UV/DAD and other detector chromatograms (iter_detector_chromatograms)
+- In-memory spectra export (load_spectra_data)
+"""
+
+import base64
+import zlib
+
+import numpy as np
+import pytest
+
+from utils import numpress
+from utils.loading import (
+    extract_chromatogram_data,
+    iter_detector_chromatograms,
+    iter_ms_scans,
+    iter_scan_metadata,
+    iter_srm_chromatograms,
+    load_spectra_data,
+)
+from utils.mzml_reader import (
+    extract_tic_chromatogram,
+    iter_dia_scans,
+    iter_ms2_scans,
+    iter_scans,
+)
+
+_NUMPRESS_ACCESSIONS = {"linear": "MS:1002312", "pic": "MS:1002313", "slof": "MS:1002314"}
+
+
+def _binary_array(values, accession, precision=64, compress=False, numpress_method=None):
+    dtype = "<f8" if precision == 64 else "<f4"
+    params = ["MS:1000523" if precision == 64 else "MS:1000521", accession]
+    if numpress_method is not None:
+        encoder = getattr(numpress, f"encode_{numpress_method}")
+        raw = encoder(np.asarray(values, dtype=np.float64))
+        params.append(_NUMPRESS_ACCESSIONS[numpress_method])
+    else:
+        raw = np.asarray(values, dtype=dtype).tobytes()
+    if compress:
+        raw = zlib.compress(raw)
+        params.append("MS:1000574")
+    elif numpress_method is None:
+        params.append("MS:1000576")
+    cv = "".join(f'<cvParam cvRef="MS" accession="{acc}" name="" value=""/>' for acc in params)
+    return (
+        f"<binaryDataArray>{cv}"
+        f"<binary>{base64.b64encode(raw).decode('ascii')}</binary></binaryDataArray>"
+    )
+
+
+def _spectrum(
+    index,
+    rt,
+    mz,
+    intensity,
+    ms_level=1,
+    tic=None,
+    intensity_kwargs=None,
+    precursor_mz=None,
+    polarity=None,
+    filter_string=None,
+    mobility=None,
+    faims_cv=None,
+    isolation_window=None,
+    monoisotopic_mz=None,
+    injection_time=None,
+    nce=None,
+    **array_kwargs,
+):
+    tic = float(np.sum(intensity)) if tic is None else tic
+    polarity_cv = ""
+    if polarity is not None:
+        accession = "MS:1000130" if polarity == "positive" else "MS:1000129"
+        polarity_cv = f'<cvParam cvRef="MS" accession="{accession}" name="{polarity} scan" value=""/>'
+    precursor = ""
+    if precursor_mz is not None:
+        precursor = (
+            '<selectedIonList count="1"><selectedIon>'
+            f'<cvParam cvRef="MS" accession="MS:1000744" name="selected ion m/z" value="{precursor_mz}"/>'
+            "</selectedIon></selectedIonList>"
+        )
+    if isolation_window is not None:
+        # (target, lower offset, upper offset)
+        target, lower, upper = isolation_window
+        precursor = (
+            "<isolationWindow>"
+            f'<cvParam cvRef="MS" accession="MS:1000827" name="isolation window target m/z" value="{target}"/>'
+            f'<cvParam cvRef="MS" accession="MS:1000828" name="isolation window lower offset" value="{lower}"/>'
+            f'<cvParam cvRef="MS" accession="MS:1000829" name="isolation window upper offset" value="{upper}"/>'
+            f"</isolationWindow>{precursor}"
+        )
+    if nce is not None:
+        precursor += (
+            "<activation>"
+            f'<cvParam cvRef="MS" accession="MS:1000138" name="normalized collision energy" value="{nce}"/>'
+            "</activation>"
+        )
+    if precursor:
+        precursor = f'<precursorList count="1"><precursor>{precursor}</precursor></precursorList>'
+    filter_cv = ""
+    if filter_string is not None:
+        filter_cv = f'<cvParam cvRef="MS" accession="MS:1000512" name="filter string" value="{filter_string}"/>'
+    mobility_array = ""
+    if mobility is not None:
+        mobility_array = _binary_array(mobility, "MS:1003006", **array_kwargs)
+    if faims_cv is not None:
+        filter_cv += f'<cvParam cvRef="MS" accession="MS:1001581" name="FAIMS CV" value="{faims_cv}"/>'
+    if injection_time is not None:
+        filter_cv += (
+            f'<cvParam cvRef="MS" accession="MS:1000927" name="ion injection time" value="{injection_time}"/>'
+            '<userParam name="[Thermo Trailer Extra]FT Resolution:" value="60000"/>'
+        )
+    if monoisotopic_mz is not None:
+        filter_cv += (
+            f'<userParam name="[Thermo Trailer Extra]Monoisotopic M/Z:" value="{monoisotopic_mz}"/>'
+        )
+    return f"""
+      <spectrum index="{index}" id="scan={index + 1}" defaultArrayLength="{len(mz)}">
+        <cvParam cvRef="MS" accession="MS:1000511" name="ms level" value="{ms_level}"/>
+        <cvParam cvRef="MS" accession="MS:1000285" name="total ion current" value="{tic}"/>
+        {polarity_cv}
+        <scanList count="1"><scan>
+          <cvParam cvRef="MS" accession="MS:1000016" name="scan start time" value="{rt}" unitName="minute"/>
+          {filter_cv}
+        </scan></scanList>
+        {precursor}
+        <binaryDataArrayList count="2">
+          {_binary_array(mz, "MS:1000514", **array_kwargs)}
+          {_binary_array(intensity, "MS:1000515", **(intensity_kwargs or array_kwargs))}
+          {mobility_array}
+        </binaryDataArrayList>
+      </spectrum>"""
+
+
+def build_mzml(path, spectra, chromatograms=""):
+    """Write a minimal mzML document with the given <spectrum> snippets."""
+    path.write_text(
+        '<?xml version="1.0" encoding="utf-8"?>\n'
+        '<mzML xmlns="http://psi.hupo.org/ms/mzml" version="1.1.0">\n'
+        '  <run id="run">\n'
+        f'    <spectrumList count="{len(spectra)}">{"".join(spectra)}\n    </spectrumList>\n'
+        f"    {chromatograms}\n"
+        "  </run>\n"
+        "</mzML>\n"
+    )
+    return str(path)
+
+
+class TestBinaryDecoding:
+    def test_uncompressed_float64_arrays(self, tmp_path):
+        path = build_mzml(
+            tmp_path / "plain.mzML",
+            [_spectrum(0, 1.0, [100.0, 200.0], [5.0, 6.0])],
+        )
+        scan_time, tic, ms_level, mz, intensity = next(iter_scans(path))
+        assert scan_time == 1.0
+        assert ms_level == 1
+        np.testing.assert_allclose(mz, [100.0, 200.0])
+        np.testing.assert_allclose(intensity, [5.0, 6.0])
+
+    def test_zlib_compressed_float64_arrays(self, tmp_path):
+        path = build_mzml(
+            tmp_path / "zlib.mzML",
+            [_spectrum(0, 2.0, [101.5, 202.25, 303.125], [1.0, 2.0, 3.0], compress=True)],
+        )
+        _, _, _, mz, intensity = next(iter_scans(path))
+        assert mz.dtype == np.float64
+        np.testing.assert_allclose(mz, [101.5, 202.25, 303.125])
+        np.testing.assert_allclose(intensity, [1.0, 2.0, 3.0])
+
+    def test_zlib_compressed_float32_arrays(self, tmp_path):
+        path = build_mzml(
+            tmp_path / "zlib32.mzML",
+            [_spectrum(0, 2.0, [150.0, 250.0], [7.0, 8.0], precision=32, compress=True)],
+        )
+        _, _, _, mz, intensity = next(iter_scans(path))
+        assert mz.dtype == np.float32
+        np.testing.assert_allclose(mz, [150.0, 250.0])
+        np.testing.assert_allclose(intensity, [7.0, 8.0])
+
+    def test_numpress_linear_and_slof(self, tmp_path):
+        mz = [100.0, 100.01, 100.02, 350.5]
+        intensity = [10.0, 2000.0, 35000.0, 12.0]
+        path = build_mzml(
+            tmp_path / "numpress.mzML",
+            [
+                _spectrum(
+                    0,
+                    1.0,
+                    mz,
+                    intensity,
+                    numpress_method="linear",
+                    intensity_kwargs={"numpress_method": "slof"},
+                )
+            ],
+        )
+        _, _, _, mz_out, intensity_out = next(iter_scans(path))
+        np.testing.assert_allclose(mz_out, mz, atol=1e-6)
+        np.testing.assert_allclose(intensity_out, intensity, rtol=2e-4)
+
+    def test_numpress_pic_with_zlib(self, tmp_path):
+        intensity = [0.0, 5.0, 123456.0]
+        path = build_mzml(
+            tmp_path / "numpress_zlib.mzML",
+            [
+                _spectrum(
+                    0,
+                    1.0,
+                    [100.0, 200.0, 300.0],
+                    intensity,
+                    intensity_kwargs={"numpress_method": "pic", "compress": True},
+                )
+            ],
+        )
+        _, _, _, _, intensity_out = next(iter_scans(path))
+        np.testing.assert_array_equal(intensity_out, intensity)
+
+
+class TestIterScans:
+    def test_yields_every_spectrum_in_order(self, tmp_path):
+        path = build_mzml(
+            tmp_path / "multi.mzML",
+            [
+                _spectrum(0, 0.5, [100.0], [1.0]),
+                _spectrum(1, 0.6, [50.0], [2.0], ms_level=2),
+                _spectrum(2, 0.7, [100.0], [3.0], compress=True),
+            ],
+        )
+        scans = list(iter_scans(path))
+        assert [s[0] for s in scans] == pytest.approx([0.5, 0.6, 0.7])
+        assert [s[2] for s in scans] == [1, 2, 1]
+
+    def test_decode_threads(self, tmp_path):
+        spectra = [
+            _spectrum(i, 0.1 * i, [100.0 + i, 200.0], [float(i), 2.0], compress=i % 2 == 0)
+            for i in range(40)
+        ]
+        path = build_mzml(tmp_path / "threads.mzML", spectra)
+        serial = list(iter_scans(path))
+        threaded = list(iter_scans(path, decode_threads=3))
+        assert [s[0] for s in threaded] == [s[0] for s in serial]
+        for a, b in zip(serial, threaded):
+            np.testing.assert_array_equal(a[3], b[3])
+            np.testing.assert_array_equal(a[4], b[4])
+        # Stopping early leaves no threads behind
+        scans = iter_scans(path, decode_threads=2)
+        assert next(scans)[0] == 0.0
+        scans.close()
+        # Passed on for mzML files only
+        assert len(list(iter_ms_scans(path, decode_threads=2))) == 40
+
+    def test_polarity_filter(self, tmp_path):
+        path = build_mzml(
+            tmp_path / "switching.mzML",
+            [
+                _spectrum(0, 0.5, [100.0], [1.0], polarity="positive"),
+                _spectrum(1, 0.6, [100.0], [2.0], polarity="negative"),
+                _spectrum(2, 0.7, [100.0], [3.0]),  # no polarity term: always kept
+            ],
+        )
+        assert len(list(iter_scans(path))) == 3
+        assert [s[0] for s in iter_scans(path, polarity="positive")] == pytest.approx([0.5, 0.7])
+        assert [s[0] for s in iter_ms_scans(path, polarity="negative")] == pytest.approx([0.6, 0.7])
+        with pytest.raises(ValueError):
+            list(iter_scans(path, polarity="both"))
+
+    def test_scan_filter(self, tmp_path):
+        path = build_mzml(
+            tmp_path / "mixed.mzML",
+            [
+                _spectrum(0, 0.5, [100.0], [1.0], filter_string="FTMS + p ESI Full ms"),
+                _spectrum(1, 0.6, [100.0], [2.0], filter_string="FTMS + p ESI SIM ms"),
+                _spectrum(2, 0.7, [50.0], [3.0], ms_level=2, precursor_mz=195.09),
+                _spectrum(3, 0.8, [50.0], [4.0], ms_level=2, precursor_mz=300.0),
+            ],
+        )
+        by_regex = iter_ms_scans(path, scan_filter={"filter_regex": "SIM"})
+        assert [s[0] for s in by_regex] == pytest.approx([0.6])
+        by_level = iter_ms_scans(path, scan_filter={"ms_levels": [1]})
+        assert [s[0] for s in by_level] == pytest.approx([0.5, 0.6])
+        by_precursor = iter_ms_scans(path, scan_filter={"precursor_range": (195.0, 195.2)})
+        assert [s[0] for s in by_precursor] == pytest.approx([0.7])
+
+    def test_with_precursor(self, tmp_path):
+        path = build_mzml(
+            tmp_path / "dda.mzML",
+            [
+                _spectrum(0, 0.5, [100.0], [1.0]),
+                _spectrum(1, 0.6, [50.0], [2.0], ms_level=2, precursor_mz=195.09),
+            ],
+        )
+        ms1, ms2 = iter_scans(path, with_precursor=True)
+        assert (len(ms1), ms1[5], ms2[5]) == (6, None, 195.09)
+        (scan,) = iter_ms_scans(path, scan_filter={"ms_levels": [2]}, with_precursor=True)
+        assert scan[5] == 195.09
+        mobility, precursor = next(iter_scans(path, with_mobility=True, with_precursor=True))[5:]
+        assert mobility is None and precursor is None
+        with pytest.raises(ValueError, match="scan cache"):
+            list(iter_ms_scans(path, cache=True, with_precursor=True))
+
+    def test_acquisition_filter(self, tmp_path):
+        path = build_mzml(
+            tmp_path / "dda.mzML",
+            [
+                _spectrum(0, 0.5, [100.0], [1.0], injection_time=5.0),
+                _spectrum(1, 0.6, [50.0], [2.0], ms_level=2, precursor_mz=195.09, nce=30),
+                _spectrum(2, 0.7, [50.0], [3.0], ms_level=2, precursor_mz=195.09, nce=50),
+            ],
+        )
+        by_time = iter_ms_scans(path, scan_filter={"injection_time_range": (0, 10)})
+        assert [s[0] for s in by_time] == pytest.approx([0.5])
+        by_energy = iter_ms_scans(path, scan_filter={"collision_energy_range": (25, 35)})
+        assert [s[0] for s in by_energy] == pytest.approx([0.6])
+
+    def test_mobility_arrays_and_faims_cv(self, tmp_path):
+        path = build_mzml(
+            tmp_path / "mobility.mzML",
+            [
+                _spectrum(0, 0.5, [100.0, 200.0], [1.0, 2.0], mobility=[0.8, 1.1]),
+                _spectrum(1, 0.6, [100.0], [3.0], faims_cv=-45.0),
+                _spectrum(2, 0.7, [100.0], [4.0]),
+            ],
+        )
+        assert all(len(scan) == 5 for scan in iter_scans(path))
+        tims, faims, plain = iter_scans(path, with_mobility=True)
+        np.testing.assert_allclose(tims[5], [0.8, 1.1])
+        np.testing.assert_allclose(faims[5], [-45.0])
+        assert plain[5] is None
+
+    def test_no_tic_chromatogram_returns_none(self, tmp_path):
+        path = build_mzml(tmp_path / "notic.mzML", [_spectrum(0, 0.5, [100.0], [1.0])])
+        assert extract_tic_chromatogram(path) is None
+
+
+class TestIterScanMetadata:
+    def test_acquisition_metadata(self, tmp_path):
+        path = build_mzml(
+            tmp_path / "dda.mzML",
+            [
+                _spectrum(
+                    0, 0.5, [100.0], [1.0], polarity="positive", injection_time=12.5,
+                    filter_string="FTMS + p ESI Full ms",
+                ),
+                _spectrum(1, 0.6, [50.0], [2.0], ms_level=2, precursor_mz=195.09, nce=30),
+            ],
+        )
+        survey, fragment = iter_scan_metadata(path)
+        assert survey["id"] == "scan=1" and survey["scan_time"] == pytest.approx(0.5)
+        assert survey["polarity"] == "positive"
+        assert survey["filter_string"] == "FTMS + p ESI Full ms"
+        assert survey["injection_time"] == 12.5 and survey["resolution"] == 60000.0
+        assert survey["precursor_mz"] is None and survey["normalized_collision_energy"] is None
+        assert fragment["ms_level"] == 2 and fragment["precursor_mz"] == 195.09
+        assert fragment["normalized_collision_energy"] == 30.0
+        assert fragment["injection_time"] is None
+
+    def test_other_formats_yield_nothing(self, tmp_path):
+        path = tmp_path / "run.mgf"
+        path.write_text("BEGIN IONS\nPEPMASS=195.09\n50.0 1.0\nEND IONS\n")
+        assert list(iter_scan_metadata(str(path))) == []
+
+
+class TestIterMs2Scans:
+    def test_yields_only_ms2_with_precursor(self, tmp_path):
+        path = build_mzml(
+            tmp_path / "dda.mzML",
+            [
+                _spectrum(0, 1.0, [100.0, 200.0], [5.0, 6.0]),
+                _spectrum(1, 1.01, [50.0, 75.0], [1.0, 2.0], ms_level=2, precursor_mz=200.0),
+                _spectrum(2, 1.02, [60.0], [3.0], ms_level=2),
+            ],
+        )
+        (scan,) = list(iter_ms2_scans(path))
+        scan_time, precursor_mz, mz, intensity = scan
+        assert scan_time == pytest.approx(1.01)
+        assert precursor_mz == 200.0
+        np.testing.assert_allclose(intensity, [1.0, 2.0])
+
+    def test_precursor_info(self, tmp_path):
+        path = build_mzml(
+            tmp_path / "dda.mzML",
+            [
+                _spectrum(
+                    0, 1.0, [50.0], [1.0], ms_level=2, precursor_mz=201.0,
+                    isolation_window=(201.0, 0.8, 0.8), monoisotopic_mz=200.0,
+                ),
+                _spectrum(1, 1.01, [60.0], [3.0], ms_level=2, precursor_mz=300.0),
+            ],
+        )
+        first, second = iter_ms2_scans(path, with_precursor_info=True)
+        assert first[4] == {
+            "selected_mz": 201.0,
+            "monoisotopic_mz": 200.0,
+            "isolation_window": (pytest.approx(200.2), pytest.approx(201.8)),
+        }
+        assert second[4]["isolation_window"] is None and second[4]["monoisotopic_mz"] is None
+        assert len(next(iter_ms2_scans(path))) == 4
+
+
+class TestIterDiaScans:
+    def test_yields_isolation_windows(self, tmp_path):
+        path = build_mzml(
+            tmp_path / "swath.mzML",
+            [
+                _spectrum(0, 1.0, [100.0, 200.0], [5.0, 6.0]),
+                _spectrum(1, 1.01, [50.0], [1.0], ms_level=2, isolation_window=(412.5, 12.5, 12.5)),
+                _spectrum(2, 1.02, [60.0], [3.0], ms_level=2),
+            ],
+        )
+        scans = list(iter_dia_scans(path))
+        assert [scan[1] for scan in scans] == [1, 2, 2]
+        assert scans[0][2] is None and scans[2][2] is None
+        assert scans[1][2] == (400.0, 425.0)
+        np.testing.assert_allclose(scans[1][3], [50.0])
+
+
+class TestProgressCallback:
+    def test_reports_increasing_fractions_ending_at_one(self, tmp_path):
+        path = build_mzml(
+            tmp_path / "progress.mzML",
+            [_spectrum(i, 0.1 * i, [100.0, 200.0], [1.0, 2.0]) for i in range(20)],
+        )
+        fractions = []
+        scans = list(iter_ms_scans(path, progress_callback=fractions.append))
+        assert len(scans) == 20
+        assert fractions[-1] == 1.0
+        assert fractions == sorted(fractions)
+
+    def test_scans_identical_with_and_without_callback(self, tmp_path):
+        path = build_mzml(
+            tmp_path / "same.mzML",
+            [_spectrum(0, 0.5, [100.0], [1.0]), _spectrum(1, 0.6, [101.0], [2.0])],
+        )
+        plain = [s[0] for s in iter_ms_scans(path)]
+        tracked = [s[0] for s in iter_ms_scans(path, progress_callback=lambda f: None)]
+        assert plain == tracked
+
+
+def _chromatogram(chrom_id, accession, times, intensities):
+    return f"""
+      <chromatogram index="0" id="{chrom_id}" defaultArrayLength="{len(times)}">
+        <cvParam cvRef="MS" accession="{accession}" name="" value=""/>
+        <binaryDataArrayList count="2">
+          {_binary_array(times, "MS:1000595")}
+          {_binary_array(intensities, "MS:1000515")}
+        </binaryDataArrayList>
+      </chromatogram>"""
+
+
+def _srm_chromatogram(index, q1, q3, times, intensities, seconds=False):
+    time_array = _binary_array(times, "MS:1000595")
+    if seconds:
+        time_array = time_array.replace(
+            'accession="MS:1000595" name="" value=""',
+            'accession="MS:1000595" name="" value="" unitAccession="UO:0000010"',
+        )
+
+    def target(mz):
+        return (
+            '<isolationWindow><cvParam cvRef="MS" accession="MS:1000827" '
+            f'name="isolation window target m/z" value="{mz}"/></isolationWindow>'
+        )
+
+    return f"""
+      <chromatogram index="{index}" id="SRM SIC Q1={q1} Q3={q3}" defaultArrayLength="{len(times)}">
+        <cvParam cvRef="MS" accession="MS:1001473" name="" value=""/>
+        <precursor>{target(q1)}</precursor>
+        <product>{target(q3)}</product>
+        <binaryDataArrayList count="2">
+          {time_array}
+          {_binary_array(intensities, "MS:1000515")}
+        </binaryDataArrayList>
+      </chromatogram>"""
+
+
+class TestSrmChromatograms:
+    def test_reads_transitions_and_skips_summary_chromatograms(self, tmp_path):
+        chromatograms = (
+            '<chromatogramList count="3">'
+            + _chromatogram("TIC", "MS:1000235", [0.1, 0.2], [5.0, 6.0])
+            + _srm_chromatogram(1, 195.1, 138.1, [0.1, 0.2], [1.0, 2.0])
+            + _srm_chromatogram(2, 195.1, 110.1, [6.0, 12.0], [3.0, 4.0], seconds=True)
+            + "</chromatogramList>"
+        )
+        path = build_mzml(tmp_path / "qqq.mzML", [], chromatograms)
+        srm = list(iter_srm_chromatograms(path))
+        assert [(c["q1"], c["q3"]) for c in srm] == [(195.1, 138.1), (195.1, 110.1)]
+        np.testing.assert_allclose(srm[0]["intensity"], [1.0, 2.0])
+        # Seconds are converted to minutes
+        np.testing.assert_allclose(srm[1]["time"], [0.1, 0.2])
+
+
+class TestDetectorChromatograms:
+    def test_reads_uv_channels_and_skips_ms_chromatograms(self, tmp_path):
+        dad = _chromatogram("DAD1 A, Sig=254,4 Ref=360,100", "MS:1000812", [0.1, 0.2], [1.0, 2.0])
+        dad = dad.replace(
+            'accession="MS:1000515" name="" value=""',
+            'accession="MS:1000515" name="" value="" unitName="absorbance unit"',
+        )
+        chromatograms = (
+            '<chromatogramList count="5">'
+            + _chromatogram("TIC", "MS:1000235", [0.1, 0.2], [5.0, 6.0])
+            + _srm_chromatogram(1, 195.1, 138.1, [0.1, 0.2], [1.0, 2.0])
+            + dad
+            + _chromatogram("UV 280nm", "MS:1000812", [0.1, 0.2], [3.0, 4.0])
+            + _chromatogram("Pump pressure", "MS:1000626", [0.1, 0.2], [400.0, 410.0])
+            + "</chromatogramList>"
+        )
+        path = build_mzml(tmp_path / "lcuv.mzML", [], chromatograms)
+        channels = list(iter_detector_chromatograms(path))
+        assert [c["id"] for c in channels] == [
+            "DAD1 A, Sig=254,4 Ref=360,100", "UV 280nm", "Pump pressure",
+        ]
+        assert [c["kind"] for c in channels] == ["absorption", "absorption", "other"]
+        assert [c["wavelength"] for c in channels] == [254.0, 280.0, None]
+        assert channels[0]["unit"] == "absorbance unit"
+        np.testing.assert_allclose(channels[1]["intensity"], [3.0, 4.0])
+        np.testing.assert_allclose(channels[2]["time"], [0.1, 0.2])
+
+    def test_other_formats_yield_nothing(self, tmp_path):
+        path = tmp_path / "run.mgf"
+        path.write_text("BEGIN IONS\nPEPMASS=100\n50 1\nEND IONS\n")
+        assert list(iter_detector_chromatograms(str(path))) == []
+
+
+class TestSummaryChromatograms:
+    def _spectra(self):
+        return [
+            _spectrum(0, 0.5, [100.0, 200.0], [1.0, 4.0], tic=0.0),
+            _spectrum(1, 0.6, [100.0, 200.0], [6.0, 2.0]),
+        ]
+
+    def test_computed_from_scans_when_missing(self, tmp_path):
+        path = build_mzml(tmp_path / "nochrom.mzML", self._spectra())
+        times, tic, bpc = extract_chromatogram_data(path)
+        np.testing.assert_allclose(times, [0.5, 0.6])
+        # Missing TIC attribute falls back to the summed intensities
+        np.testing.assert_allclose(tic, [5.0, 8.0])
+        np.testing.assert_allclose(bpc, [4.0, 6.0])
+
+    def test_uses_stored_chromatograms(self, tmp_path):
+        chromatograms = (
+            '<chromatogramList count="2">'
+            + _chromatogram("TIC", "MS:1000235", [0.5, 0.6], [50.0, 80.0])
+            + _chromatogram("BPC", "MS:1000628", [0.5, 0.6], [40.0, 60.0])
+            + "</chromatogramList>"
+        )
+        path = build_mzml(tmp_path / "chrom.mzML", self._spectra(), chromatograms)
+        times, tic, bpc = extract_chromatogram_data(path)
+        np.testing.assert_allclose(tic, [50.0, 80.0])
+        np.testing.assert_allclose(bpc, [40.0, 60.0])
+
+
+class TestLoadSpectraData:
+    def _path(self, tmp_path):
+        return build_mzml(
+            tmp_path / "spectra.mzML",
+            [
+                _spectrum(0, 0.5, [100.0, 150.0, 200.0, 250.0], [4.0, 1.0, 3.0, 2.0]),
+                _spectrum(1, 0.6, [50.0], [9.0], ms_level=2),
+                _spectrum(2, 0.7, [100.0, 200.0], [5.0, 6.0]),
+            ],
+        )
+
+    def test_keyed_by_scan_index(self, tmp_path):
+        spectra = load_spectra_data(self._path(tmp_path))
+        assert sorted(spectra) == [0, 1, 2]
+        scan_time, mz, intensity = spectra[2]
+        assert scan_time == pytest.approx(0.7)
+        np.testing.assert_allclose(mz, [100.0, 200.0])
+
+    def test_ms_level_filter(self, tmp_path):
+        assert sorted(load_spectra_data(self._path(tmp_path), ms_level=1)) == [0, 2]
+
+    def test_downsampling_keeps_most_intense_in_mz_order(self, tmp_path):
+        _, mz, intensity = load_spectra_data(self._path(tmp_path), max_points=2)[0]
+        np.testing.assert_allclose(mz, [100.0, 200.0])
+        np.testing.assert_allclose(intensity, [4.0, 3.0])
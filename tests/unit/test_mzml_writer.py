@@ -0,0 +1,115 @@
+"""
+Tests for the mzML writer in utils/mzml_writer.py.
+
+Covers:
+- Spectra read back with mzml_reader (iter_scans, iter_ms2_scans), zlib and uncompressed
+- Chromatograms read back as TIC and SRM chromatograms
+- xic_chromatograms() from processed compounds
+- Software version and manifest sidecar
+- Argument validation
+"""
+
+from types import SimpleNamespace
+
+import numpy as np
+import pytest
+from lxml import etree
+
+from utils.classes import compounds_from_ion_list
+from utils.manifest import read_manifest, software_version
+from utils.mzml_reader import extract_tic_chromatogram, iter_ms2_scans, iter_scans, iter_srm_chromatograms
+from utils.mzml_writer import write_mzml, xic_chromatograms
+
+_NS = {"m": "http://psi.hupo.org/ms/mzml"}
+
+SPECTRA = [
+    {"scan_time": 1.5, "mz": [100.0, 200.0], "intensity": [10.0, 20.0], "polarity": "positive"},
+    {
+        "scan_time": 1.6,
+        "mz": [50.0, 75.5],
+        "intensity": [3.0, 4.0],
+        "ms_level": 2,
+        "precursor_mz": 200.0,
+        "centroided": True,
+    },
+]
+
+
+class TestSpectra:
+    @pytest.mark.parametrize("compression", ["zlib", None])
+    def test_round_trip(self, tmp_path, compression):
+        path = write_mzml(tmp_path / "out.mzML", spectra=SPECTRA, compression=compression)
+        scans = list(iter_scans(str(path)))
+        assert [(scan[0], scan[1], scan[2]) for scan in scans] == [(1.5, 30.0, 1), (1.6, 7.0, 2)]
+        np.testing.assert_array_equal(scans[0][3], [100.0, 200.0])
+        np.testing.assert_array_equal(scans[0][4], [10.0, 20.0])
+        ((scan_time, precursor_mz, mz, _),) = iter_ms2_scans(str(path))
+        assert (scan_time, precursor_mz) == (1.6, 200.0)
+        np.testing.assert_array_equal(mz, [50.0, 75.5])
+
+    def test_polarity(self, tmp_path):
+        path = write_mzml(tmp_path / "out.mzML", spectra=SPECTRA)
+        assert len(list(iter_scans(str(path), polarity="negative"))) == 1  # The MS2 has no polarity
+
+    def test_document(self, tmp_path):
+        path = write_mzml(tmp_path / "out.mzML", spectra=SPECTRA, run_id="run1")
+        root = etree.parse(str(path)).getroot()
+        assert root.get("version") == "1.1.0"
+        assert root.find("m:run", _NS).get("id") == "run1"
+        spectrum_list = root.find("m:run/m:spectrumList", _NS)
+        assert spectrum_list.get("count") == "2"
+        names = [cv.get("name") for cv in spectrum_list[1].findall("m:cvParam", _NS)]
+        assert "centroid spectrum" in names and "MSn spectrum" in names
+
+    def test_manifest(self, tmp_path):
+        measurement = SimpleNamespace(filename="sample", manifest={"input_sha256": "0"})
+        path = write_mzml(tmp_path / "out.mzML", spectra=SPECTRA, measurements=[measurement])
+        software = etree.parse(str(path)).getroot().find("m:softwareList/m:software", _NS)
+        assert software.get("version") == software_version()
+        assert read_manifest(path)["measurements"] == {"sample": {"input_sha256": "0"}}
+
+    def test_mismatched_arrays(self, tmp_path):
+        with pytest.raises(ValueError, match="2 m/z values for 1 intensities"):
+            write_mzml(tmp_path / "out.mzML", spectra=[{"mz": [1.0, 2.0], "intensity": [1.0]}])
+
+    def test_unknown_compression(self, tmp_path):
+        with pytest.raises(ValueError, match="Unknown compression"):
+            write_mzml(tmp_path / "out.mzML", compression="gzip")
+
+
+class TestChromatograms:
+    def test_tic_and_srm(self, tmp_path):
+        path = write_mzml(
+            tmp_path / "out.mzML",
+            chromatograms=[
+                {"id": "TIC", "time": [0.1, 0.2], "intensity": [5.0, 6.0]},
+                {"time": [0.1, 0.2], "intensity": [1.0, 2.0], "precursor_mz": 300.0, "product_mz": 150.0},
+            ],
+        )
+        times, intensities = extract_tic_chromatogram(str(path))
+        np.testing.assert_array_equal(times, [0.1, 0.2])
+        np.testing.assert_array_equal(intensities, [5.0, 6.0])
+        (srm,) = iter_srm_chromatograms(str(path))
+        assert (srm["q1"], srm["q3"]) == (300.0, 150.0)
+        np.testing.assert_array_equal(srm["time"], [0.1, 0.2])  # Written in minutes
+
+    def test_unknown_kind(self, tmp_path):
+        with pytest.raises(ValueError, match="Unknown chromatogram kind"):
+            write_mzml(tmp_path / "out.mzML", chromatograms=[{"time": [], "intensity": [], "kind": "xic"}])
+
+    def test_xic_chromatograms(self, tmp_path):
+        compounds = compounds_from_ion_list({"Alanine": {"ions": [90.055, 112.037]}})
+        ions = compounds[0].ions
+        ions[90.055]["MS Intensity"] = np.array([[0.1, 0.2], [1.0, 3.0]])
+        ions[90.055]["MS Intensity Smoothed"] = np.array([[0.1, 0.2], [2.0, 2.0]])
+        ions[112.037]["MS Intensity"] = np.empty((2, 0))
+        (xic,) = xic_chromatograms(compounds)
+        assert xic["id"] == "Alanine 90.0550"
+        assert xic["precursor_mz"] == 90.055
+        np.testing.assert_array_equal(xic["intensity"], [1.0, 3.0])
+        np.testing.assert_array_equal(xic_chromatograms(compounds, smoothed=True)[0]["intensity"], [2.0, 2.0])
+
+        path = write_mzml(tmp_path / "xics.mzML", chromatograms=[xic])
+        root = etree.parse(str(path)).getroot()
+        chromatogram = root.find("m:run/m:chromatogramList/m:chromatogram", _NS)
+        assert chromatogram.find("m:cvParam", _NS).get("name") == "selected ion monitoring chromatogram"
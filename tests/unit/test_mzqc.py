@@ -0,0 +1,90 @@
+"""
+Tests for the mzQC export in utils/mzqc.py.
+
+Covers:
+- Run qualities of the files with QC metrics in mzqc_document()
+- PSI-MS and local metric terms, units, seconds and NaN as null
+- Input file formats and checksums from the manifests
+- export_mzqc() output file and its error without QC metrics
+"""
+
+import json
+from types import SimpleNamespace
+
+import numpy as np
+import pytest
+
+from calculation.status import FileStatus
+from utils.mzqc import MZQC_VERSION, export_mzqc, mzqc_document
+
+QC = {
+    "ms1_scans": 120,
+    "rt_range": [0.0, 2.0],
+    "ms1_scan_rate": np.float64(1.0),
+    "median_mass_error_ppm": None,
+    "tic_cv": np.nan,
+    "tic_median_change": 0.01,
+    "peak_width": {"median": 0.1, "q1": 0.05, "q3": 0.2, "n": 2},
+    "dropouts": [{"start_time": 1.0, "end_time": 1.5}],
+    "flags": ["TIC dropout at 1.00-1.50 min"],
+}
+
+
+def _statuses():
+    return {
+        "a.mzML": FileStatus("/data/a.mzML", "warnings", qc=QC),
+        "b.mzML": FileStatus("/data/b.mzML"),
+    }
+
+
+def _metrics(run):
+    return {metric["accession"]: metric for metric in run["qualityMetrics"]}
+
+
+class TestMzqcDocument:
+    def test_runs_with_qc(self):
+        document = mzqc_document(_statuses())["mzQC"]
+        assert document["version"] == MZQC_VERSION
+        (run,) = document["runQualities"]
+        assert run["metadata"]["label"] == "a"
+        (input_file,) = run["metadata"]["inputFiles"]
+        assert input_file["location"] == "file:///data/a.mzML"
+        assert input_file["fileFormat"]["accession"] == "MS:1000584"
+        assert input_file["fileProperties"] == []
+        assert run["metadata"]["analysisSoftware"][0]["name"] == "LCMSpector"
+        assert len(document["controlledVocabularies"]) == 3
+
+    def test_metrics(self):
+        (run,) = mzqc_document(_statuses())["mzQC"]["runQualities"]
+        metrics = _metrics(run)
+        assert metrics["MS:4000059"]["value"] == 120
+        assert metrics["MS:4000070"]["value"] == [0.0, 120.0]
+        assert metrics["MS:4000070"]["unit"]["name"] == "second"
+        assert metrics["LCMSPECTOR:0000001"]["value"] == 1.0
+        assert metrics["LCMSPECTOR:0000002"]["value"] is None
+        assert metrics["LCMSPECTOR:0000003"]["value"] is None
+        assert metrics["LCMSPECTOR:0000005"]["value"] == pytest.approx(
+            {"median": 6.0, "q1": 3.0, "q3": 12.0, "n": 2}
+        )
+        assert metrics["LCMSPECTOR:0000006"]["value"] == {"start_time": [60.0], "end_time": [90.0]}
+        assert metrics["LCMSPECTOR:0000007"]["value"] == QC["flags"]
+        json.dumps(run, allow_nan=False)
+
+    def test_checksum_from_manifest(self):
+        measurements = [SimpleNamespace(filename="a.mzML", manifest={"input_sha256": "abc"})]
+        (run,) = mzqc_document(_statuses(), measurements)["mzQC"]["runQualities"]
+        (checksum,) = run["metadata"]["inputFiles"][0]["fileProperties"]
+        assert checksum == {"accession": "MS:1003151", "name": "SHA-256", "value": "abc"}
+
+
+class TestExportMzqc:
+    def test_export(self, tmp_path):
+        path = export_mzqc(_statuses(), tmp_path / "batch.mzQC", description="nightly QC")
+        with open(path) as f:
+            document = json.load(f)
+        assert document["mzQC"]["description"] == "nightly QC"
+        assert len(document["mzQC"]["runQualities"]) == 1
+
+    def test_without_qc_raises(self, tmp_path):
+        with pytest.raises(ValueError, match="No file has QC metrics"):
+            export_mzqc([FileStatus("/data/b.mzML")], tmp_path / "batch.mzQC")
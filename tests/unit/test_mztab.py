@@ -0,0 +1,157 @@
+"""
+Tests for the mzTab-M export in utils/mztab.py.
+
+Covers:
+- Metadata, SML, SMF and SME sections of mztab_lines()
+- Software version, run hashes and settings from the manifests
+- Feature areas and summed or calibrated SML abundances
+- Adduct labels and charges from the ion list
+- Library identification of linked MS2 spectra, scored on their RT
+- export_mztab() output file
+"""
+
+from types import SimpleNamespace
+
+import numpy as np
+import pytest
+
+from utils.classes import compounds_from_ion_list
+from utils.library import SpectralLibrary
+from utils.mztab import export_mztab, mztab_lines
+
+FRAGMENTS = np.array([50.0, 70.0, 90.0])
+
+
+def _measurement(filename, area, concentration=None, ms2=False):
+    compounds = compounds_from_ion_list(
+        {
+            "Caffeine": {"ions": [195.0877, 217.0696], "info": ["[M+H]+", "[M+Na]+"], "formula": "C8H10N4O2"},
+            "Unknown": {"ions": [300.0]},
+        }
+    )
+    for compound in compounds:
+        compound.concentration = concentration
+        for ion, data in compound.ions.items():
+            data["RT"] = 2.0
+            data["Integration Data"] = {"baseline_corrected_area": area, "start_time": 1.9, "end_time": 2.1}
+    if ms2:
+        compounds[0].ions[195.0877]["MS2"] = [
+            {"scan_time": 2.0, "precursor_mz": 195.0877, "mz": FRAGMENTS, "intensity": np.ones(3)}
+        ]
+    return SimpleNamespace(path=f"/data/{filename}.mzML", filename=filename, xics=compounds)
+
+
+def _section(lines, prefix):
+    header = {"SML": "SMH", "SMF": "SFH", "SME": "SEH"}[prefix]
+    (columns,) = [line.split("\t") for line in lines if line.startswith(header)]
+    return [dict(zip(columns[1:], line.split("\t")[1:])) for line in lines if line.startswith(prefix + "\t")]
+
+
+def _metadata(lines):
+    return dict(line.split("\t")[1:] for line in lines if line.startswith("MTD"))
+
+
+class TestMzTabLines:
+    def test_metadata(self):
+        mtd = _metadata(mztab_lines([_measurement("a", 10.0), _measurement("b", 30.0)], title="batch"))
+        assert mtd["mzTab-version"] == "2.0.0-M"
+        assert mtd["mzTab-ID"] == "batch"
+        assert mtd["ms_run[2]-location"] == "file:///data/b.mzML"
+        assert mtd["ms_run[1]-format"] == "[MS, MS:1000584, mzML format, ]"
+        assert mtd["assay[2]"] == "b"
+        assert mtd["study_variable[1]-assay_refs"] == "assay[1]|assay[2]"
+        assert "feature area" in mtd["small_molecule-quantification_unit"]
+        assert "ms_run[1]-hash" not in mtd
+
+    def test_manifest(self):
+        measurement = _measurement("a", 10.0)
+        measurement.manifest = {"input_sha256": "abc", "config_hash": "c0", "ion_list_hash": "i0"}
+        mtd = _metadata(mztab_lines([measurement, _measurement("b", 30.0)]))
+        assert mtd["ms_run[1]-hash"] == "abc"
+        assert mtd["ms_run[1]-hash_method"] == "[MS, MS:1003151, SHA-256, ]"
+        assert "ms_run[2]-hash" not in mtd
+        assert mtd["software[1]-setting[1]"] == "config_hash=c0"
+        assert mtd["software[1]-setting[2]"] == "ion_list_hash=i0"
+
+    def test_features(self):
+        lines = mztab_lines([_measurement("a", 10.0), _measurement("b", 30.0)])
+        smf = _section(lines, "SMF")
+        assert len(smf) == 3
+        assert smf[0]["adduct_ion"] == "[M+H]+"
+        assert smf[0]["charge"] == "1"
+        assert smf[1]["adduct_ion"] == "[M+Na]+"
+        assert smf[2]["adduct_ion"] == "null"
+        assert smf[0]["retention_time_in_seconds"] == "120"
+        assert float(smf[0]["retention_time_in_seconds_start"]) == pytest.approx(114.0)
+        assert smf[0]["abundance_assay[1]"] == "10"
+        assert smf[0]["abundance_assay[2]"] == "30"
+
+    def test_negative_charge(self):
+        smf = _section(mztab_lines([_measurement("a", 1.0)], polarity="negative"), "SMF")
+        assert smf[0]["charge"] == "1"  # From the [M+H]+ label
+        assert smf[2]["charge"] == "-1"
+
+    def test_small_molecules(self):
+        sml = _section(mztab_lines([_measurement("a", 10.0), _measurement("b", 30.0)]), "SML")
+        caffeine, unknown = sml
+        assert caffeine["chemical_name"] == "Caffeine"
+        assert caffeine["SMF_ID_REFS"] == "1|2"
+        assert caffeine["chemical_formula"] == "C8H10N4O2"
+        assert float(caffeine["theoretical_neutral_mass"]) == pytest.approx(194.0804, abs=1e-3)
+        assert caffeine["adduct_ions"] == "[M+H]+|[M+Na]+"
+        assert caffeine["abundance_assay[1]"] == "20"
+        assert caffeine["abundance_study_variable[1]"] == "40"
+        assert caffeine["reliability"] == "3"
+        assert unknown["SMF_ID_REFS"] == "3"
+        assert unknown["theoretical_neutral_mass"] == "null"
+
+    def test_calibrated(self):
+        lines = mztab_lines([_measurement("a", 10.0, concentration=1.5), _measurement("b", 30.0, concentration=4.5)])
+        assert "concentration" in _metadata(lines)["small_molecule-quantification_unit"]
+        caffeine = _section(lines, "SML")[0]
+        assert caffeine["abundance_assay[1]"] == "1.5"
+        assert caffeine["abundance_study_variable[1]"] == "3"
+        assert caffeine["reliability"] == "1"
+
+    def test_evidence_without_library(self):
+        sme = _section(mztab_lines([_measurement("a", 10.0, ms2=True)]), "SME")
+        assert [row["evidence_input_id"] for row in sme] == ["1", "2", "3"]
+        assert sme[0]["chemical_name"] == "Caffeine"
+        assert sme[0]["spectra_ref"] == "ms_run[1]:scan_time=120.000"
+        assert sme[0]["ms_level"] == "[MS, MS:1000511, ms level, 2]"
+        assert sme[1]["spectra_ref"] == "null"
+        assert sme[0]["id_confidence_measure[1]"] == "null"
+
+    def test_library_identification(self):
+        library = SpectralLibrary.from_spectra(
+            [{"precursor_mz": 195.0877, "mz": FRAGMENTS, "intensity": np.ones(3), "metadata": {"name": "caffeine"}}]
+        )
+        lines = mztab_lines([_measurement("a", 10.0, ms2=True)], library=library)
+        sme = _section(lines, "SME")
+        assert sme[0]["chemical_name"] == "caffeine"
+        assert float(sme[0]["id_confidence_measure[1]"]) == pytest.approx(1.0)
+        caffeine = _section(lines, "SML")[0]
+        assert caffeine["reliability"] == "2"
+        assert float(caffeine["best_id_confidence_value"]) == pytest.approx(1.0)
+
+    def test_library_retention_scored(self):
+        # The linked MS2 spectrum is at 2 min, far from the library's 5 min
+        library = SpectralLibrary.from_spectra(
+            [{"precursor_mz": 195.0877, "mz": FRAGMENTS, "intensity": np.ones(3),
+              "metadata": {"name": "caffeine", "retentiontime": "5.0 min"}}]
+        )
+        lines = mztab_lines([_measurement("a", 10.0, ms2=True)], library=library)
+        sme = _section(lines, "SME")
+        assert float(sme[0]["id_confidence_measure[1]"]) == pytest.approx(0.8)
+
+    def test_unknown_polarity(self):
+        with pytest.raises(ValueError, match="Unknown polarity"):
+            mztab_lines([], polarity="both")
+
+
+def test_export_mztab(tmp_path):
+    path = export_mztab({"a": _measurement("a", 10.0)}, tmp_path / "batch.mzTab")
+    lines = path.read_text().splitlines()
+    assert lines[0] == "MTD\tmzTab-version\t2.0.0-M"
+    assert _metadata(lines)["mzTab-ID"] == "batch"
+    assert lines.count("") == 3  # Between the four sections
@@ -0,0 +1,152 @@
+"""
+Tests for mzXML support.
+
+Covers:
+- iter_scans() / iter_ms2_scans() / find_nearest_ms2() in mzxml_reader.py
+- detect_ms_format() and iter_ms_scans() dispatch in loading.py
+"""
+
+import base64
+import zlib
+
+import numpy as np
+import pytest
+
+from utils.loading import detect_ms_format, iter_ms_scans, find_nearest_ms2
+from utils.mzxml_reader import iter_ms2_scans, iter_scans, _parse_retention_time
+
+
+def _encode_peaks(mz, intensity, precision=32, compress=False):
+    dtype = ">f4" if precision == 32 else ">f8"
+    pairs = np.empty(len(mz) * 2, dtype=dtype)
+    pairs[0::2] = mz
+    pairs[1::2] = intensity
+    raw = pairs.tobytes()
+    if compress:
+        raw = zlib.compress(raw)
+    return base64.b64encode(raw).decode("ascii")
+
+
+def _peaks_elem(mz, intensity, precision=32, compress=False):
+    compression = "zlib" if compress else "none"
+    return (
+        f'<peaks precision="{precision}" byteOrder="network" '
+        f'contentType="m/z-int" compressionType="{compression}">'
+        f"{_encode_peaks(mz, intensity, precision, compress)}</peaks>"
+    )
+
+
+@pytest.fixture
+def mzxml_file(tmp_path):
+    """Two MS1 survey scans, the first with a nested MS2 scan."""
+    ms1_a = _peaks_elem([100.0, 150.0, 200.0], [10.0, 20.0, 30.0])
+    ms2 = _peaks_elem([50.0, 75.0], [5.0, 7.0], precision=64, compress=True)
+    ms1_b = _peaks_elem([100.0, 150.0], [40.0, 50.0], compress=True)
+    content = f"""<?xml version="1.0" encoding="ISO-8859-1"?>
+<mzXML xmlns="http://sashimi.sourceforge.net/schema_revision/mzXML_3.2">
+  <msRun scanCount="3">
+    <scan num="1" msLevel="1" peaksCount="3" retentionTime="PT60.0S" totIonCurrent="60">
+      {ms1_a}
+      <scan num="2" msLevel="2" peaksCount="2" retentionTime="PT61.2S" totIonCurrent="12">
+        <precursorMz precursorIntensity="20">150.0</precursorMz>
+        {ms2}
+      </scan>
+    </scan>
+    <scan num="3" msLevel="1" peaksCount="2" retentionTime="PT90.0S" totIonCurrent="90">
+      {ms1_b}
+    </scan>
+  </msRun>
+</mzXML>
+"""
+    path = tmp_path / "sample.mzXML"
+    path.write_text(content)
+    return path
+
+
+class TestRetentionTimeParsing:
+    def test_seconds_duration(self):
+        assert _parse_retention_time("PT90S") == pytest.approx(1.5)
+
+    def test_minutes_duration(self):
+        assert _parse_retention_time("PT2.5M") == pytest.approx(2.5)
+
+    def test_plain_seconds(self):
+        assert _parse_retention_time("30") == pytest.approx(0.5)
+
+    def test_missing_value(self):
+        assert _parse_retention_time(None) == 0.0
+
+
+class TestIterScans:
+    def test_yields_all_scans_in_acquisition_order(self, mzxml_file):
+        scans = list(iter_scans(str(mzxml_file)))
+        assert [s[2] for s in scans] == [1, 2, 1]
+        times = [s[0] for s in scans]
+        assert times == pytest.approx([1.0, 1.02, 1.5])
+
+    def test_decodes_uncompressed_float32_peaks(self, mzxml_file):
+        scan_time, tic, ms_level, mz, intensity = next(iter_scans(str(mzxml_file)))
+        assert tic == 60.0
+        np.testing.assert_allclose(mz, [100.0, 150.0, 200.0])
+        np.testing.assert_allclose(intensity, [10.0, 20.0, 30.0])
+
+    def test_decodes_zlib_float64_peaks(self, mzxml_file):
+        ms2 = list(iter_scans(str(mzxml_file)))[1]
+        assert ms2[3].dtype == np.float64
+        np.testing.assert_allclose(ms2[3], [50.0, 75.0])
+        np.testing.assert_allclose(ms2[4], [5.0, 7.0])
+
+
+    def test_polarity_filter(self, tmp_path):
+        peaks = _peaks_elem([100.0], [1.0])
+        path = tmp_path / "switching.mzXML"
+        path.write_text(
+            '<mzXML xmlns="http://sashimi.sourceforge.net/schema_revision/mzXML_3.2"><msRun>'
+            f'<scan num="1" msLevel="1" polarity="+" retentionTime="PT60S">{peaks}</scan>'
+            f'<scan num="2" msLevel="1" polarity="-" retentionTime="PT66S">{peaks}</scan>'
+            f'<scan num="3" msLevel="1" retentionTime="PT72S">{peaks}</scan>'
+            "</msRun></mzXML>"
+        )
+        times = [s[0] for s in iter_scans(str(path), polarity="negative")]
+        assert times == pytest.approx([1.1, 1.2])
+
+
+class TestFormatDetection:
+    def test_detect_by_extension(self, tmp_path):
+        assert detect_ms_format(str(tmp_path / "run.mzXML")) == "mzXML"
+        assert detect_ms_format(str(tmp_path / "run.mzml")) == "mzML"
+
+    def test_detect_by_header_sniffing(self, tmp_path):
+        path = tmp_path / "run.xml"
+        path.write_text('<?xml version="1.0"?>\n<mzXML xmlns="x"></mzXML>')
+        assert detect_ms_format(str(path)) == "mzXML"
+
+    def test_unknown_format_raises(self, tmp_path):
+        path = tmp_path / "run.xml"
+        path.write_text("<foo></foo>")
+        with pytest.raises(ValueError):
+            detect_ms_format(str(path))
+
+    def test_iter_ms_scans_dispatches_to_mzxml(self, mzxml_file):
+        assert len(list(iter_ms_scans(str(mzxml_file)))) == 3
+
+
+class TestFindNearestMS2:
+    def test_finds_nested_ms2(self, mzxml_file):
+        result = find_nearest_ms2(str(mzxml_file), precursor_mz=150.0, target_rt=1.0)
+        assert result is not None
+        scan_time, mz, intensity = result
+        assert scan_time == pytest.approx(1.02)
+        np.testing.assert_allclose(mz, [50.0, 75.0])
+
+    def test_returns_none_for_unmatched_precursor(self, mzxml_file):
+        assert find_nearest_ms2(str(mzxml_file), 999.0, 1.0) is None
+
+
+class TestIterMs2Scans:
+    def test_yields_precursor_and_fragments(self, mzxml_file):
+        (scan,) = list(iter_ms2_scans(str(mzxml_file)))
+        scan_time, precursor_mz, mz, intensity = scan
+        assert scan_time == pytest.approx(1.02)
+        assert precursor_mz == 150.0
+        np.testing.assert_allclose(mz, [50.0, 75.0])
@@ -0,0 +1,45 @@
+"""
+Tests for calculation/noise.py.
+
+Covers:
+- validate_noise_threshold() defaults and errors
+- above_noise() with absolute, relative and combined thresholds
+"""
+
+import numpy as np
+import pytest
+
+from calculation.noise import above_noise, validate_noise_threshold
+
+
+class TestValidateNoiseThreshold:
+    def test_disabled(self):
+        assert validate_noise_threshold(None) is None
+        assert validate_noise_threshold({"absolute": 0}) is None
+
+    def test_defaults(self):
+        assert validate_noise_threshold({"relative": 1}) == {"absolute": 0.0, "relative": 1.0}
+
+    @pytest.mark.parametrize(
+        "settings", [{"absolute": -1.0}, {"relative": 150.0}, {"percent": 1.0}]
+    )
+    def test_invalid(self, settings):
+        with pytest.raises(ValueError):
+            validate_noise_threshold(settings)
+
+
+class TestAboveNoise:
+    INTENSITY = np.array([5.0, 50.0, 500.0, 1000.0])
+
+    def test_absolute(self):
+        assert above_noise(self.INTENSITY, absolute=50.0).tolist() == [False, True, True, True]
+
+    def test_relative_to_base_peak(self):
+        assert above_noise(self.INTENSITY, relative=10.0).tolist() == [False, False, True, True]
+
+    def test_higher_threshold_wins(self):
+        mask = above_noise(self.INTENSITY, absolute=600.0, relative=1.0)
+        assert mask.tolist() == [False, False, False, True]
+
+    def test_empty_scan(self):
+        assert len(above_noise(np.zeros(0), relative=5.0)) == 0
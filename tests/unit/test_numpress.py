@@ -0,0 +1,76 @@
+"""
+Tests for the MS-Numpress codecs in utils/numpress.py.
+"""
+
+import numpy as np
+import pytest
+
+from utils import numpress
+
+
+class TestHalfByteIntegers:
+    @pytest.mark.parametrize(
+        "value", [0, 1, 15, 16, 255, 0x12345678, 0x7FFFFFFF, 0xFFFFFFFF, 0xFFFFFFF0]
+    )
+    def test_int_round_trip(self, value):
+        packed = numpress._pack_nibbles(numpress._encode_int(value))
+        assert numpress._decode_ints(packed) == [value]
+
+    def test_odd_nibble_count_is_padded(self):
+        # 0 encodes as a single half byte (head = 8), so three zeros need a
+        # trailing pad nibble
+        nibbles = numpress._encode_int(0) + numpress._encode_int(0) + numpress._encode_int(0)
+        assert len(nibbles) % 2 == 1
+        assert numpress._decode_ints(numpress._pack_nibbles(nibbles)) == [0, 0, 0]
+
+    def test_truncated_stream_raises(self):
+        # Head 0 announces eight more half bytes that are not there
+        with pytest.raises(numpress.NumpressError):
+            numpress._decode_ints(bytes([0x01, 0x23]))
+
+
+class TestLinear:
+    def test_round_trip_mz_values(self):
+        mz = np.array([100.0, 100.0105, 100.0211, 100.0318, 250.12345, 250.2])
+        encoded = numpress.encode_linear(mz)
+        decoded = numpress.decode_linear(encoded)
+        np.testing.assert_allclose(decoded, mz, atol=1e-6)
+
+    def test_explicit_fixed_point(self):
+        values = np.array([1.0, 2.0, 3.5, 3.0])
+        decoded = numpress.decode_linear(numpress.encode_linear(values, fixed_point=1000.0))
+        np.testing.assert_allclose(decoded, values, atol=1e-3)
+
+    @pytest.mark.parametrize("values", [[], [42.5], [42.5, 43.0]])
+    def test_short_arrays(self, values):
+        decoded = numpress.decode_linear(numpress.encode_linear(np.array(values)))
+        np.testing.assert_allclose(decoded, values, atol=1e-6)
+
+
+class TestPic:
+    def test_round_trip_counts(self):
+        counts = np.array([0, 1, 17, 350, 12000, 65535, 1e7])
+        decoded = numpress.decode_pic(numpress.encode_pic(counts))
+        np.testing.assert_array_equal(decoded, counts)
+
+    def test_rounds_to_nearest_integer(self):
+        decoded = numpress.decode_pic(numpress.encode_pic(np.array([1.4, 1.6])))
+        np.testing.assert_array_equal(decoded, [1.0, 2.0])
+
+
+class TestSlof:
+    def test_round_trip_relative_error(self):
+        intensities = np.array([0.0, 10.0, 1234.5, 5.6e5, 2.1e7])
+        decoded = numpress.decode_slof(numpress.encode_slof(intensities))
+        np.testing.assert_allclose(decoded, intensities, rtol=2e-4, atol=1e-3)
+
+
+class TestDispatch:
+    def test_decode_by_name(self):
+        counts = np.array([3.0, 4.0])
+        encoded = numpress.encode_pic(counts)
+        np.testing.assert_array_equal(numpress.decode(encoded, "pic"), counts)
+
+    def test_unknown_method_raises(self):
+        with pytest.raises(ValueError):
+            numpress.decode(b"", "bogus")
@@ -0,0 +1,80 @@
+"""
+Tests for detect_peaks() in calculation/peak_detection.py on synthetic Gaussians,
+and for its noise estimate, estimate_noise() in calculation/peak_integration.py.
+"""
+
+import numpy as np
+import pytest
+
+from calculation.peak_detection import detect_peaks
+from calculation.peak_integration import estimate_noise
+
+SQRT_2PI = np.sqrt(2 * np.pi)
+FWHM_PER_SIGMA = 2 * np.sqrt(2 * np.log(2))
+
+
+def _gaussian(times, center, sigma, height):
+    return height * np.exp(-0.5 * ((times - center) / sigma) ** 2)
+
+
+@pytest.fixture
+def times():
+    return np.linspace(0, 10, 1001)
+
+
+class TestDetectPeaks:
+    def test_two_separated_peaks(self, times):
+        trace = _gaussian(times, 3.0, 0.1, 1000) + _gaussian(times, 6.0, 0.2, 500)
+        peaks = detect_peaks(times, trace)
+
+        assert [p["apex_rt"] for p in peaks] == pytest.approx([3.0, 6.0])
+        assert peaks[0]["height"] == pytest.approx(1000, rel=0.05)
+        assert peaks[0]["fwhm"] == pytest.approx(0.1 * FWHM_PER_SIGMA, rel=0.02)
+        assert peaks[1]["fwhm"] == pytest.approx(0.2 * FWHM_PER_SIGMA, rel=0.02)
+        for p in peaks:
+            assert p["area"] == pytest.approx(1000 * 0.1 * SQRT_2PI, rel=0.02)
+            assert p["start_time"] < p["apex_rt"] < p["end_time"]
+            assert p["asymmetry"] == pytest.approx(1.0, abs=0.05)
+
+    def test_tailing_peak_asymmetry(self, times):
+        trace = np.where(
+            times < 5.0,
+            _gaussian(times, 5.0, 0.1, 1000),
+            _gaussian(times, 5.0, 0.3, 1000),
+        )
+        (peak,) = detect_peaks(times, trace)
+        assert peak["asymmetry"] == pytest.approx(3.0, rel=0.05)
+
+    def test_max_peaks_keeps_most_intense(self, times):
+        trace = _gaussian(times, 3.0, 0.1, 200) + _gaussian(times, 6.0, 0.1, 900)
+        (peak,) = detect_peaks(times, trace, max_peaks=1)
+        assert peak["apex_rt"] == pytest.approx(6.0)
+
+    def test_flat_trace_has_no_peaks(self, times):
+        assert detect_peaks(times, np.zeros_like(times)) == []
+
+    def test_too_short_trace(self):
+        assert detect_peaks(np.array([0.0, 0.1]), np.array([1.0, 2.0])) == []
+
+    def test_length_mismatch_raises(self, times):
+        with pytest.raises(ValueError):
+            detect_peaks(times, np.ones(10))
+
+
+class TestSignalToNoise:
+    def test_snr_against_gaussian_noise(self, times):
+        rng = np.random.default_rng(1)
+        trace = 100 + _gaussian(times, 5.0, 0.1, 1000) + rng.normal(0, 10, len(times))
+        (peak,) = detect_peaks(times, trace)
+        assert peak["snr"] == pytest.approx(100, rel=0.2)
+
+    def test_estimate_ignores_peaks(self):
+        rng = np.random.default_rng(2)
+        trace = rng.normal(0, 5, 1000)
+        trace[400:450] += 1e4
+        assert estimate_noise(trace, [(400, 449)]) == pytest.approx(5, rel=0.15)
+        # Local estimate around the peak
+        assert estimate_noise(trace, [(400, 449)], 400, 449) == pytest.approx(5, rel=0.4)
+
+    def test_estimate_needs_peak_free_points(self):
+        assert estimate_noise(np.ones(10), [(0, 7)]) is None
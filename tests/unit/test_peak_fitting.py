@@ -0,0 +1,105 @@
+"""
+Tests for calculation/peak_fitting.py on synthetic peaks.
+
+Covers:
+- gaussian()/emg() area normalization
+- fit_peaks() splitting coeluting Gaussian and tailing EMG peaks
+- overlapping_groups() and fit_overlapping_peaks() grouping
+"""
+
+import numpy as np
+import pytest
+
+from calculation.peak_fitting import (
+    SQRT_2PI,
+    emg,
+    fit_overlapping_peaks,
+    fit_peaks,
+    gaussian,
+    overlapping_groups,
+)
+
+
+@pytest.fixture
+def times():
+    return np.linspace(4.0, 7.0, 301)
+
+
+def _peak(start, end, apex_rt=5.0, height=100.0, fwhm=0.3):
+    return {
+        "start_index": start,
+        "end_index": end,
+        "apex_rt": apex_rt,
+        "height": height,
+        "fwhm": fwhm,
+    }
+
+
+class TestShapes:
+    @pytest.mark.parametrize("tau", [0.01, 0.2, 1.0])
+    def test_emg_area(self, tau):
+        t = np.linspace(0, 30, 30001)
+        assert np.trapezoid(emg(t, 5.0, 5.0, 0.2, tau), t) == pytest.approx(5.0, rel=1e-3)
+
+    def test_gaussian_area(self):
+        t = np.linspace(0, 10, 10001)
+        assert np.trapezoid(gaussian(t, 2.0, 5.0, 0.3), t) == pytest.approx(2.0, rel=1e-4)
+
+    def test_emg_is_finite_far_out(self):
+        t = np.array([-100.0, 5.0, 100.0])
+        assert np.all(np.isfinite(emg(t, 1.0, 5.0, 0.01, 0.05)))
+
+
+class TestFitPeaks:
+    def test_splits_coeluting_gaussians(self, times):
+        trace = (
+            50
+            + 1000 * np.exp(-0.5 * ((times - 5.0) / 0.15) ** 2)
+            + 500 * np.exp(-0.5 * ((times - 5.4) / 0.15) ** 2)
+        )
+        guesses = [_peak(0, 300, 5.0, 900, 0.3), _peak(0, 300, 5.45, 450, 0.3)]
+        fit = fit_peaks(times, trace, guesses)
+        assert fit["success"]
+        first, second = fit["components"]
+        assert first["area"] == pytest.approx(1000 * 0.15 * SQRT_2PI, rel=0.02)
+        assert second["area"] == pytest.approx(500 * 0.15 * SQRT_2PI, rel=0.02)
+        assert second["apex_rt"] == pytest.approx(5.4, abs=0.02)
+        assert fit["baseline"][0] == pytest.approx(50, rel=0.05)
+        assert fit["r_squared"] > 0.999
+
+    def test_emg_fits_tailing_peak(self, times):
+        trace = emg(times, 100.0, 5.0, 0.05, 0.3)
+        fit = fit_peaks(times, trace, [_peak(0, 300, 5.2, trace.max(), 0.3)], model="emg")
+        (component,) = fit["components"]
+        assert component["area"] == pytest.approx(100.0, rel=0.02)
+        assert component["tau"] == pytest.approx(0.3, rel=0.05)
+        assert fit["rmse"] < 0.01 * trace.max()
+
+    def test_invalid_input_raises(self, times):
+        with pytest.raises(ValueError):
+            fit_peaks(times, np.ones_like(times), [_peak(0, 300)], model="lorentzian")
+        with pytest.raises(ValueError):
+            fit_peaks(times, np.ones_like(times), [])
+        with pytest.raises(ValueError):
+            fit_peaks(times[:4], np.ones(4), [_peak(0, 3)])
+
+
+class TestOverlappingPeaks:
+    def test_groups(self):
+        peaks = [_peak(50, 70), _peak(0, 20), _peak(15, 40)]
+        groups = overlapping_groups(peaks)
+        assert [[p["start_index"] for p in group] for group in groups] == [[0, 15], [50]]
+
+    def test_only_overlapping_groups_are_fitted(self, times):
+        trace = gaussian(times, 30.0, 5.0, 0.1) + gaussian(times, 15.0, 5.25, 0.1)
+        trace += gaussian(times, 30.0, 6.5, 0.1)
+        peaks = [
+            _peak(60, 120, 5.0, trace[100], 0.24),
+            _peak(100, 160, 5.25, trace[125], 0.24),
+            _peak(220, 280, 6.5, trace[250], 0.24),
+        ]
+        (fit,) = fit_overlapping_peaks(times, trace, peaks)
+        assert len(fit["components"]) == 2
+        assert fit["start_time"] == pytest.approx(times[60])
+        assert fit["end_time"] == pytest.approx(times[160])
+        assert [c["area"] for c in fit["components"]] == pytest.approx([30.0, 15.0], rel=0.03)
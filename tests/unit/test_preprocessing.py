@@ -0,0 +1,750 @@
+"""
+Tests for XIC extraction in calculation/preprocessing.py.
+
+Scans are fed through a monkeypatched iter_ms_scans so no mzML file is needed.
+
+Covers:
+- build_xics() window summing, vectorized sum_mz_windows() against the per-window loop
+- Cancellation via cancel_event
+- Per-compound retention time windows
+- Per-file retention time and scan index ranges
+- float32 / float64 extraction precision
+- Optional XIC smoothing and baseline subtraction
+- Centroiding of profile scans before extraction
+- XIC intensity per scan: window sum, maximum or nearest peak
+- Linking of DDA MS2 scans to integrated ions, by isolation window or monoisotopic m/z
+- Isotopologue XICs and isotope pattern scoring
+- Global and per-compound polarity filters
+- Scan filters passed on to the reader
+- SRM transitions read from chromatograms
+- Per-compound ion mobility windows
+- Charge-state deconvolution before extraction
+- Lock-mass recalibration before extraction
+- Noise thresholds applied to the scans before extraction
+- Chunked extraction of one file in several threads
+- Processed spectra across the integrated peaks, and the peak purity, mass errors and
+  charge states computed on them
+"""
+
+import threading
+
+import numpy as np
+import pytest
+
+from calculation import preprocessing
+from calculation.preprocessing import (
+    ProcessingCancelled,
+    build_xics,
+    construct_xics,
+    extract_mz_windows,
+    sum_mz_windows,
+    validate_file_range,
+)
+from utils.classes import Compound
+
+
+def _fake_scans(n_scans, mz=(100.0, 200.0), intensity=(10.0, 20.0)):
+    mz = np.asarray(mz, dtype=np.float64)
+    intensity = np.asarray(intensity, dtype=np.float64)
+    return [(0.1 * i, float(intensity.sum()), 1, mz, intensity) for i in range(n_scans)]
+
+
+@pytest.fixture
+def patch_scans(monkeypatch):
+    """Replace iter_ms_scans with a generator over the given scan tuples."""
+
+    def _patch(scans):
+        def fake_iter_ms_scans(path, progress_callback=None, **filters):
+            yield from scans
+
+        monkeypatch.setattr(preprocessing, "iter_ms_scans", fake_iter_ms_scans)
+
+    return _patch
+
+
+class TestBuildXics:
+    def test_sums_intensity_within_window(self, patch_scans):
+        patch_scans(_fake_scans(3))
+        intensities, times = build_xics("fake.mzML", [100.0, 200.0, 300.0], 0.0001)
+        assert intensities.shape == (3, 3)
+        np.testing.assert_allclose(intensities[:, 0], 10.0)
+        np.testing.assert_allclose(intensities[:, 1], 20.0)
+        np.testing.assert_allclose(intensities[:, 2], 0.0)
+        np.testing.assert_allclose(times, [0.0, 0.1, 0.2], atol=1e-6)
+
+
+class TestPrecision:
+    def _scans(self):
+        mz = np.array([1500.00005])
+        return [(0.1 * i, 1.0, 1, mz, np.array([1.0])) for i in range(3)]
+
+    def test_float64_resolves_high_mz(self, patch_scans):
+        patch_scans(self._scans())
+        single, _ = build_xics("fake.mzML", [1500.00005], 1e-8)
+        double, times = build_xics("fake.mzML", [1500.00005], 1e-8, precision="float64")
+        np.testing.assert_array_equal(single[:, 0], 0.0)  # Window collapses onto 1500.0
+        np.testing.assert_array_equal(double[:, 0], 1.0)
+        assert double.dtype == times.dtype == np.float64
+
+    def test_construct_xics_stores_float64(self, patch_scans):
+        patch_scans(_fake_scans(5))
+        (compound,) = construct_xics(
+            "fake.mzML", (Compound(name="c", target_list=[100.0]),), precision="float64"
+        )
+        assert compound.ions[100.0]["MS Intensity"].dtype == np.float64
+
+    def test_unknown_precision(self, patch_scans):
+        patch_scans(_fake_scans(1))
+        with pytest.raises(ValueError, match="Unknown precision"):
+            build_xics("fake.mzML", [100.0], 0.0001, precision="float16")
+
+
+class TestSumMzWindows:
+    def test_matches_loop(self):
+        rng = np.random.default_rng(1)
+        mz = np.sort(rng.uniform(100.0, 1000.0, 5000))
+        intensity = rng.exponential(1e5, 5000)
+        targets = rng.uniform(100.0, 1000.0, 300)
+        lower, upper = targets - 0.05, targets + 0.05
+        left = np.searchsorted(mz, lower, side="left")
+        right = np.searchsorted(mz, upper, side="right")
+        np.testing.assert_allclose(
+            sum_mz_windows(mz, intensity, lower, upper),
+            preprocessing._sum_mz_windows_loop(intensity, left, right),
+            rtol=1e-6,
+        )
+
+    def test_empty_and_reversed_windows(self):
+        mz = np.array([100.0, 200.0])
+        intensity = np.array([10.0, 20.0])
+        row = sum_mz_windows(mz, intensity, np.array([150.0, 210.0, 99.0]), np.array([160.0, 190.0, 201.0]))
+        np.testing.assert_array_equal(row, [0.0, 0.0, 30.0])
+
+    def test_nan_stays_in_its_window(self):
+        mz = np.array([100.0, 200.0, 300.0])
+        intensity = np.array([10.0, np.nan, 30.0])
+        row = sum_mz_windows(mz, intensity, mz - 1, mz + 1)
+        assert row[0] == 10.0 and np.isnan(row[1]) and row[2] == 30.0
+
+
+class TestCancellation:
+    def test_set_event_raises(self, patch_scans):
+        patch_scans(_fake_scans(10))
+        event = threading.Event()
+        event.set()
+        with pytest.raises(ProcessingCancelled):
+            build_xics("fake.mzML", [100.0], 0.0001, cancel_event=event)
+
+    def test_unset_event_processes_all_scans(self, patch_scans):
+        patch_scans(_fake_scans(120))
+        intensities, times = build_xics(
+            "fake.mzML", [100.0], 0.0001, cancel_event=threading.Event()
+        )
+        assert len(times) == 120
+
+
+class TestRetentionTimeWindows:
+    def test_rt_range_skips_scans_outside(self, patch_scans):
+        patch_scans(_fake_scans(10))
+        intensities, times = build_xics("fake.mzML", [100.0], 0.0001, rt_range=(0.25, 0.55))
+        np.testing.assert_allclose(times, [0.3, 0.4, 0.5], atol=1e-6)
+        assert intensities.shape == (3, 1)
+
+    def test_open_ended_rt_range(self, patch_scans):
+        patch_scans(_fake_scans(10))
+        _, times = build_xics("fake.mzML", [100.0], 0.0001, rt_range=(None, 0.15))
+        np.testing.assert_allclose(times, [0.0, 0.1], atol=1e-6)
+
+    def test_construct_xics_restricts_each_compound(self, patch_scans):
+        mz = np.array([100.0])
+        # Two isobaric peaks at 0.2 and 0.7 min
+        trace = [1, 5, 50, 5, 1, 1, 5, 80, 5, 1]
+        patch_scans([(0.1 * i, float(v), 1, mz, np.array([float(v)])) for i, v in enumerate(trace)])
+        early = Compound(name="early", target_list=[100.0], rt_min=0.0, rt_max=0.45)
+        late = Compound(name="late", target_list=[100.0], rt_min=0.45, rt_max=1.0)
+        unbounded = Compound(name="all", target_list=[100.0])
+
+        early, late, unbounded = construct_xics("fake.mzML", (early, late, unbounded))
+
+        assert early.ions[100.0]["RT"] == pytest.approx(0.2)
+        assert late.ions[100.0]["RT"] == pytest.approx(0.7)
+        assert early.ions[100.0]["MS Intensity"].shape == (2, 5)
+        assert unbounded.ions[100.0]["MS Intensity"].shape == (2, 10)
+
+    def test_empty_window_leaves_rt_unset(self, patch_scans):
+        patch_scans(_fake_scans(5))
+        (compound,) = construct_xics(
+            "fake.mzML", (Compound(name="absent", target_list=[100.0], rt_min=5.0, rt_max=6.0),)
+        )
+        assert compound.ions[100.0]["RT"] is None
+        assert compound.ions[100.0]["MS Intensity"].shape == (2, 0)
+
+    def test_inverted_window_rejected(self):
+        with pytest.raises(ValueError):
+            Compound(name="bad", target_list=[100.0], rt_min=2.0, rt_max=1.0)
+
+
+class TestFileRanges:
+    def test_scan_range(self, patch_scans):
+        patch_scans(_fake_scans(10))
+        _, times = build_xics("fake.mzML", [100.0], 0.0001, scan_range=(2, 4))
+        np.testing.assert_allclose(times, [0.2, 0.3, 0.4], atol=1e-6)
+
+    def test_stops_reading_after_range(self, monkeypatch):
+        consumed = []
+
+        def fake_iter_ms_scans(path, progress_callback=None, **filters):
+            for scan in _fake_scans(100):
+                consumed.append(scan[0])
+                yield scan
+
+        monkeypatch.setattr(preprocessing, "iter_ms_scans", fake_iter_ms_scans)
+        build_xics("fake.mzML", [100.0], 0.0001, rt_range=(None, 0.25))
+        assert len(consumed) == 4
+        consumed.clear()
+        build_xics("fake.mzML", [100.0], 0.0001, scan_range=(None, 9))
+        assert len(consumed) == 11
+
+    def test_construct_xics_clips_compound_windows(self, patch_scans):
+        patch_scans(_fake_scans(10))
+        early = Compound(name="early", target_list=[100.0], rt_min=0.0, rt_max=0.45)
+        unbounded = Compound(name="all", target_list=[100.0])
+        (early,) = construct_xics("fake.mzML", (early,), rt_range=(0.25, 0.75))
+        (unbounded,) = construct_xics("fake.mzML", (unbounded,), rt_range=(0.25, 0.75))
+        assert early.ions[100.0]["MS Intensity"].shape == (2, 2)
+        assert unbounded.ions[100.0]["MS Intensity"].shape == (2, 5)
+
+    def test_construct_xics_scan_range(self, patch_scans):
+        patch_scans(_fake_scans(10))
+        (compound,) = construct_xics(
+            "fake.mzML", (Compound(name="c", target_list=[100.0]),), scan_range=(5, None)
+        )
+        assert compound.ions[100.0]["MS Intensity"].shape == (2, 5)
+
+    def test_validate(self):
+        assert validate_file_range((1, "2.5"), [0, None]) == ((1.0, 2.5), (0, None))
+        assert validate_file_range((None, None), None) == (None, None)
+
+    @pytest.mark.parametrize(
+        "rt_range,scan_range,message",
+        [
+            ((2.0, 1.0), None, "greater than its end"),
+            ((1.0,), None, "pair"),
+            (None, (-1, 5), "must not be negative"),
+        ],
+    )
+    def test_invalid(self, rt_range, scan_range, message):
+        with pytest.raises(ValueError, match=message):
+            validate_file_range(rt_range, scan_range)
+
+
+class TestSmoothing:
+    def _peak_scans(self):
+        mz = np.array([100.0])
+        trace = np.exp(-0.5 * ((np.arange(30) - 15) / 3.0) ** 2) * 1000
+        trace[::2] += 200  # alternating noise
+        return [(0.1 * i, float(v), 1, mz, np.array([v])) for i, v in enumerate(trace)]
+
+    def test_no_smoothing_by_default(self, patch_scans):
+        patch_scans(self._peak_scans())
+        (compound,) = construct_xics("fake.mzML", (Compound(name="c", target_list=[100.0]),))
+        assert compound.ions[100.0]["MS Intensity Smoothed"] is None
+
+    def test_global_smoothing_keeps_raw_trace(self, patch_scans):
+        patch_scans(self._peak_scans())
+        (compound,) = construct_xics(
+            "fake.mzML",
+            (Compound(name="c", target_list=[100.0]),),
+            smoothing={"method": "moving_average", "window": 3},
+        )
+        raw = compound.ions[100.0]["MS Intensity"]
+        smoothed = compound.ions[100.0]["MS Intensity Smoothed"]
+        assert smoothed.shape == raw.shape
+        np.testing.assert_array_equal(smoothed[0], raw[0])
+        assert np.abs(np.diff(smoothed[1], 2)).max() < np.abs(np.diff(raw[1], 2)).max()
+
+    def test_compound_override_disables_smoothing(self, patch_scans):
+        patch_scans(self._peak_scans())
+        (compound,) = construct_xics(
+            "fake.mzML",
+            (Compound(name="c", target_list=[100.0], smoothing={"method": "none"}),),
+            smoothing={"method": "savgol"},
+        )
+        assert compound.ions[100.0]["MS Intensity Smoothed"] is None
+
+
+class TestBaselineSubtraction:
+    def test_baseline_stored_and_removed_before_integration(self, patch_scans):
+        mz = np.array([100.0])
+        times = np.arange(60) * 0.1
+        trace = 500 + 100 * times + 5000 * np.exp(-0.5 * ((times - 3.0) / 0.2) ** 2)
+        patch_scans([(t, float(v), 1, mz, np.array([v])) for t, v in zip(times, trace)])
+
+        (compound,) = construct_xics(
+            "fake.mzML",
+            (Compound(name="c", target_list=[100.0]),),
+            baseline={"method": "rolling_min", "window": 21},
+        )
+        ion = compound.ions[100.0]
+        assert ion["MS Baseline"].shape == ion["MS Intensity"].shape
+        # Raw trace is untouched, the baseline sits under it
+        assert np.all(ion["MS Baseline"][1] <= ion["MS Intensity"][1] + 1e-3)
+        assert ion["RT"] == pytest.approx(3.0)
+
+
+class TestCentroiding:
+    def test_profile_peak_sums_fully_after_centroiding(self, patch_scans):
+        # Profile peak sampled every 0.001 m/z; the +-0.003 extraction window
+        # only reaches +-1.5 sigma of it
+        mz = np.arange(99.99, 100.02, 0.001)
+        intensity = 1000 * np.exp(-0.5 * ((mz - 100.003) / 0.002) ** 2)
+        patch_scans([(0.0, float(intensity.sum()), 1, mz, intensity)])
+
+        raw, _ = build_xics("fake.mzML", [100.003], 0.00001)
+        centroided, _ = build_xics("fake.mzML", [100.003], 0.00001, centroiding="local_max")
+        assert centroided[0, 0] == pytest.approx(intensity.sum(), rel=1e-4)
+        assert raw[0, 0] < centroided[0, 0]
+
+
+class TestXicModes:
+    # Three peaks in the +-0.03 window of m/z 100, the closest one not the largest
+    SCAN = (0.0, 60.0, 1, np.array([99.98, 100.001, 100.02]), np.array([10.0, 20.0, 30.0]))
+
+    @pytest.mark.parametrize("mode,expected", [("sum", 60.0), ("max", 30.0), ("nearest", 20.0)])
+    def test_modes(self, patch_scans, mode, expected):
+        patch_scans([self.SCAN])
+        intensities, _ = build_xics("fake.mzML", [100.0, 300.0], 0.0001, xic_mode=mode)
+        np.testing.assert_allclose(intensities[0], [expected, 0.0])
+
+    def test_nearest_defaults_to_window_centre(self):
+        row = extract_mz_windows(
+            self.SCAN[3], self.SCAN[4], np.array([99.97]), np.array([99.99]), mode="nearest"
+        )
+        np.testing.assert_allclose(row, [10.0])
+
+    def test_unknown_mode(self, patch_scans):
+        patch_scans([self.SCAN])
+        with pytest.raises(ValueError, match="Unknown XIC mode"):
+            build_xics("fake.mzML", [100.0], 0.0001, xic_mode="mean")
+
+
+class TestMs2Linking:
+    def _setup(self, patch_scans, monkeypatch, ms2_scans):
+        mz = np.array([100.0])
+        times = np.arange(40) * 0.1
+        trace = 10 + 5000 * np.exp(-0.5 * ((times - 2.0) / 0.15) ** 2)
+        patch_scans([(t, float(v), 1, mz, np.array([v])) for t, v in zip(times, trace)])
+
+        def fake_iter_ms2_scans(path, with_precursor_info=False):
+            yield from ms2_scans
+
+        monkeypatch.setattr(preprocessing, "iter_ms2_scans", fake_iter_ms2_scans)
+
+    def test_disabled_by_default(self, patch_scans, monkeypatch):
+        self._setup(patch_scans, monkeypatch, [(2.0, 100.0, np.array([50.0]), np.array([1.0]))])
+        (compound,) = construct_xics("fake.mzML", (Compound(name="c", target_list=[100.0]),))
+        assert compound.ions[100.0]["MS2"] == []
+
+    def test_matches_precursor_and_peak_range(self, patch_scans, monkeypatch):
+        fragments = np.array([50.0, 60.0])
+        self._setup(
+            patch_scans,
+            monkeypatch,
+            [
+                (1.95, 100.0, fragments, np.array([1.0, 2.0])),
+                (2.05, 100.0, fragments, np.array([3.0, 4.0])),
+                (2.01, 250.0, fragments, np.array([5.0, 6.0])),  # other precursor
+                (3.80, 100.0, fragments, np.array([7.0, 8.0])),  # outside the peak
+            ],
+        )
+        (compound,) = construct_xics(
+            "fake.mzML", (Compound(name="c", target_list=[100.0]),), link_ms2=True
+        )
+        linked = compound.ions[100.0]["MS2"]
+        assert [spectrum["scan_time"] for spectrum in linked] == [2.05, 1.95]
+        np.testing.assert_allclose(linked[0]["intensity"], [3.0, 4.0])
+        assert linked[0]["precursor_mz"] == 100.0
+
+    def test_isolation_window(self, patch_scans, monkeypatch):
+        info = {"selected_mz": 100.6, "monoisotopic_mz": None, "isolation_window": (99.6, 101.6)}
+        other = {**info, "isolation_window": (100.5, 102.5)}
+        fragments, intensity = np.array([50.0]), np.array([1.0])
+        self._setup(
+            patch_scans,
+            monkeypatch,
+            [(2.0, 100.6, fragments, intensity, info), (2.05, 100.6, fragments, intensity, other)],
+        )
+        (compound,) = construct_xics(
+            "fake.mzML", (Compound(name="c", target_list=[100.0]),), link_ms2=True
+        )
+        # Selected ion outside the XIC window, but the ion was isolated with it
+        (linked,) = compound.ions[100.0]["MS2"]
+        assert linked["scan_time"] == 2.0 and linked["isolation_window"] == (99.6, 101.6)
+        assert linked["selected_mz"] == 100.6 and linked["monoisotopic_mz"] is None
+
+    def test_monoisotopic_precursor(self, patch_scans, monkeypatch):
+        # Selected on the M+1 isotope, corrected by the instrument
+        info = {"selected_mz": 101.0034, "monoisotopic_mz": 100.0, "isolation_window": None}
+        self._setup(
+            patch_scans, monkeypatch, [(2.0, 101.0034, np.array([50.0]), np.array([1.0]), info)]
+        )
+        (compound,) = construct_xics(
+            "fake.mzML", (Compound(name="c", target_list=[100.0]),), link_ms2=True
+        )
+        (linked,) = compound.ions[100.0]["MS2"]
+        assert linked["precursor_mz"] == 101.0034 and linked["monoisotopic_mz"] == 100.0
+
+    def test_keeps_closest_to_apex(self, patch_scans, monkeypatch):
+        scans = [(2.0 + 0.01 * k, 100.0, np.array([50.0]), np.array([1.0])) for k in range(6)]
+        self._setup(patch_scans, monkeypatch, scans[::-1])
+        (compound,) = construct_xics(
+            "fake.mzML", (Compound(name="c", target_list=[100.0]),), link_ms2=True
+        )
+        times = [spectrum["scan_time"] for spectrum in compound.ions[100.0]["MS2"]]
+        assert times == pytest.approx([2.0, 2.01, 2.02])
+
+
+class TestIsotopes:
+    @pytest.fixture(autouse=True)
+    def _scans(self, patch_scans):
+        mz = np.array([100.0, 101.00335, 102.00671])
+        times = np.arange(40) * 0.1
+        trace = 5000 * np.exp(-0.5 * ((times - 2.0) / 0.15) ** 2)
+        patch_scans(
+            [
+                (t, float(v), 1, mz, np.array([v, 0.2 * v, 0.05 * v]))
+                for t, v in zip(times, trace)
+            ]
+        )
+
+    def test_disabled_by_default(self):
+        (compound,) = construct_xics("fake.mzML", (Compound(name="c", target_list=[100.0]),))
+        assert compound.ions[100.0]["Isotopes"] is None
+        assert compound.ions[100.0]["Isotope Score"] is None
+
+    def test_extracts_isotopologues_without_formula(self):
+        (compound,) = construct_xics(
+            "fake.mzML", (Compound(name="c", target_list=[100.0]),), isotopes=2
+        )
+        ion = compound.ions[100.0]
+        assert set(ion["Isotopes"]) == {"M+1", "M+2"}
+        assert ion["Isotopes"]["M+1"].shape == ion["MS Intensity"].shape
+        score = ion["Isotope Score"]
+        np.testing.assert_allclose(score["observed"], [1.0, 0.2, 0.05], rtol=1e-4)
+        assert score["predicted"] is None
+        assert score["score"] is None
+
+    def test_scores_against_formula(self, monkeypatch):
+        calls = []
+
+        def fake_isotope_ratios(formula, adduct_type, n_isotopes):
+            calls.append((formula, adduct_type, n_isotopes))
+            return np.array([1.0, 0.2, 0.05])
+
+        monkeypatch.setattr(preprocessing, "isotope_ratios", fake_isotope_ratios)
+        compound = Compound(
+            name="c", target_list=[100.0], ion_info=["[M+H]+"], formula="C5H9NO"
+        )
+        (compound,) = construct_xics("fake.mzML", (compound,), isotopes=2)
+        assert calls == [("C5H9NO", "[M+H]+", 2)]
+        assert compound.ions[100.0]["Isotope Score"]["score"] == pytest.approx(1.0, abs=1e-3)
+
+
+class TestPolarity:
+    @pytest.fixture
+    def calls(self, monkeypatch):
+        """Record the polarity of every pass; negative scans carry twice the intensity."""
+        calls = []
+
+        def fake_iter_ms_scans(path, progress_callback=None, polarity=None, **filters):
+            calls.append(polarity)
+            value = 20.0 if polarity == "negative" else 10.0
+            for i in range(5):
+                yield 0.1 * i, value, 1, np.array([100.0]), np.array([value])
+
+        monkeypatch.setattr(preprocessing, "iter_ms_scans", fake_iter_ms_scans)
+        return calls
+
+    def test_global_polarity_passed_to_reader(self, calls):
+        (compound,) = construct_xics(
+            "fake.mzML", (Compound(name="c", target_list=[100.0]),), polarity="negative"
+        )
+        assert calls == ["negative"]
+        np.testing.assert_allclose(compound.ions[100.0]["MS Intensity"][1], 20.0)
+
+    def test_compound_polarity_overrides_global(self, calls):
+        compounds = (
+            Compound(name="pos", target_list=[100.0]),
+            Compound(name="neg", target_list=[100.0], polarity="negative"),
+        )
+        pos, neg = construct_xics("fake.mzML", compounds, polarity="positive")
+        assert calls == ["positive", "negative"]
+        np.testing.assert_allclose(pos.ions[100.0]["MS Intensity"][1], 10.0)
+        np.testing.assert_allclose(neg.ions[100.0]["MS Intensity"][1], 20.0)
+
+    def test_invalid_polarity_raises(self, calls):
+        with pytest.raises(ValueError):
+            construct_xics("fake.mzML", (Compound(name="c", target_list=[100.0]),), polarity="+")
+
+
+class TestScanFilter:
+    def test_validated_filter_passed_to_reader(self, monkeypatch):
+        seen = []
+
+        def fake_iter_ms_scans(path, progress_callback=None, scan_filter=None, **filters):
+            seen.append(scan_filter)
+            yield from _fake_scans(3)
+
+        monkeypatch.setattr(preprocessing, "iter_ms_scans", fake_iter_ms_scans)
+        construct_xics(
+            "fake.mzML", (Compound(name="c", target_list=[100.0]),), scan_filter={"ms_levels": 1}
+        )
+        assert seen == [{"ms_levels": (1,), "filter_regex": None, "precursor_range": None}]
+
+    def test_invalid_filter_raises_before_reading(self):
+        with pytest.raises(ValueError):
+            construct_xics(
+                "fake.mzML", (Compound(name="c", target_list=[100.0]),), scan_filter={"level": 1}
+            )
+
+
+class TestSrmTransitions:
+    @pytest.fixture(autouse=True)
+    def _chromatograms(self, monkeypatch):
+        times = np.arange(40) * 0.1
+        peak = 1000 * np.exp(-0.5 * ((times - 2.0) / 0.15) ** 2)
+
+        def fake_iter_srm_chromatograms(path):
+            yield {"id": "a", "q1": 195.1, "q3": 138.1, "time": times, "intensity": peak}
+            yield {"id": "b", "q1": 195.1, "q3": 110.1, "time": times, "intensity": peak / 2}
+
+        def no_scans(path, progress_callback=None, **filters):
+            raise AssertionError("SRM-only compounds must not read the spectra")
+
+        monkeypatch.setattr(preprocessing, "iter_srm_chromatograms", fake_iter_srm_chromatograms)
+        monkeypatch.setattr(preprocessing, "iter_ms_scans", no_scans)
+
+    def test_transitions_matched_within_tolerance(self):
+        compound = Compound(
+            name="Caffeine",
+            target_list=[138.0662, 110.0713],
+            transitions=[(195.0877, 138.0662), (195.0877, 110.0713)],
+        )
+        (compound,) = construct_xics("qqq.mzML", (compound,))
+        first, second = compound.ions[138.0662], compound.ions[110.0713]
+        assert first["RT"] == pytest.approx(2.0)
+        assert first["MS Intensity"][1].max() == pytest.approx(1000.0)
+        assert second["MS Intensity"][1].max() == pytest.approx(500.0)
+        assert first.get("Integration Data") is not None
+
+    def test_unmatched_transition_left_empty(self):
+        compound = Compound(name="X", target_list=[50.0], transitions=[(300.0, 50.0)])
+        (compound,) = construct_xics("qqq.mzML", (compound,))
+        assert compound.ions[50.0]["MS Intensity"].shape == (2, 0)
+        assert compound.ions[50.0]["RT"] is None
+
+
+class TestMobilityWindows:
+    @pytest.fixture
+    def requested(self, monkeypatch):
+        """Frames with peaks listed by mobility, not m/z, as in TIMS data."""
+        requested = []
+
+        def fake_iter_ms_scans(path, progress_callback=None, with_mobility=False, **filters):
+            requested.append(with_mobility)
+            mz = np.array([200.0, 100.0, 100.0])
+            intensity = np.array([9.0, 5.0, 7.0])
+            mobility = np.array([0.9, 0.8, 1.1])
+            for i in range(5):
+                scan = (0.1 * i, 21.0, 1, mz, intensity)
+                yield (*scan, mobility) if with_mobility else scan
+
+        monkeypatch.setattr(preprocessing, "iter_ms_scans", fake_iter_ms_scans)
+        return requested
+
+    def test_mobility_only_read_when_needed(self, requested):
+        (compound,) = construct_xics("fake.mzML", (Compound(name="c", target_list=[100.0]),))
+        assert requested == [False]
+        np.testing.assert_allclose(compound.ions[100.0]["MS Intensity"][1], 12.0)
+
+    def test_window_restricts_summed_peaks(self, requested):
+        compounds = (
+            Compound(name="a", target_list=[100.0], mobility_range=(1.0, 1.2)),
+            Compound(name="b", target_list=[200.0]),
+        )
+        a, b = construct_xics("fake.mzML", compounds)
+        assert requested == [True]
+        np.testing.assert_allclose(a.ions[100.0]["MS Intensity"][1], 7.0)
+        np.testing.assert_allclose(b.ions[200.0]["MS Intensity"][1], 9.0)
+
+    def test_reversed_range_rejected(self):
+        with pytest.raises(ValueError):
+            Compound(name="a", target_list=[100.0], mobility_range=(1.2, 1.0))
+
+
+class TestDeconvolution:
+    def test_xic_sums_charge_states(self, patch_scans):
+        from calculation.deconvolution import PROTON_MASS, validate_deconvolution
+        from calculation.isotopes import ISOTOPE_SPACING
+
+        mass = 1000.0
+        mz = np.array(
+            [(mass + z * PROTON_MASS) / z + k * ISOTOPE_SPACING / z for z in (2, 1) for k in range(2)]
+        )
+        intensity = np.array([200.0, 100.0, 80.0, 40.0])
+        order = np.argsort(mz)
+        patch_scans([(0.1 * i, 420.0, 1, mz[order], intensity[order]) for i in range(3)])
+
+        target = [mass + PROTON_MASS]
+        plain, _ = build_xics("fake.mzML", target, 1e-6)
+        deconvoluted, _ = build_xics(
+            "fake.mzML", target, 1e-6, deconvolution=validate_deconvolution({})
+        )
+        np.testing.assert_allclose(plain[:, 0], 80.0)
+        np.testing.assert_allclose(deconvoluted[:, 0], 420.0, rtol=1e-5)
+
+
+class TestLockMass:
+    def test_recalibrated_scans_hit_narrow_window(self, patch_scans):
+        from calculation.recalibration import validate_lock_mass
+
+        lock_mass, target = 556.2771, 300.0
+        drift = 1 + 8e-6  # 8 ppm, outside a 3 ppm extraction window
+        mz = np.array([target, lock_mass]) * drift
+        patch_scans([(0.1 * i, 110.0, 1, mz, np.array([10.0, 100.0])) for i in range(3)])
+
+        plain, _ = build_xics("fake.mzML", [target], 1e-6)
+        corrected, _ = build_xics(
+            "fake.mzML", [target], 1e-6,
+            lock_mass=validate_lock_mass({"lock_masses": [lock_mass]}),
+        )
+        np.testing.assert_allclose(plain[:, 0], 0.0)
+        np.testing.assert_allclose(corrected[:, 0], 10.0)
+
+
+class TestNoiseThreshold:
+    def test_peaks_below_threshold_not_summed(self, patch_scans):
+        # Noise peak next to the target, inside its 0.01 relative window
+        mz = np.array([99.5, 100.0, 200.0])
+        patch_scans([(0.1 * i, 0.0, 1, mz, np.array([3.0, 10.0, 1000.0])) for i in range(3)])
+        plain, _ = build_xics("fake.mzML", [100.0], 0.01)
+        absolute, _ = build_xics(
+            "fake.mzML", [100.0], 0.01, noise_threshold={"absolute": 5.0, "relative": 0.0}
+        )
+        relative, _ = build_xics(
+            "fake.mzML", [100.0], 0.01, noise_threshold={"absolute": 0.0, "relative": 2.0}
+        )
+        np.testing.assert_allclose(plain[:, 0], 13.0)
+        np.testing.assert_allclose(absolute[:, 0], 10.0)
+        np.testing.assert_allclose(relative[:, 0], 0.0)  # 2 % of the 1000 base peak
+
+    def test_validated_by_construct_xics(self, patch_scans):
+        patch_scans(_fake_scans(3))
+        with pytest.raises(ValueError):
+            construct_xics("fake.mzML", {"A": {"ions": [100.0]}}, noise_threshold={"relative": -1})
+
+
+class TestParallelExtraction:
+    def _scans(self, n_scans):
+        rng = np.random.default_rng(0)
+        return [
+            (0.01 * i, 0.0, 1, np.sort(rng.uniform(99.0, 301.0, 200)), rng.uniform(0, 100, 200))
+            for i in range(n_scans)
+        ]
+
+    @pytest.mark.parametrize("n_workers", [2, 4])
+    def test_matches_serial_extraction(self, patch_scans, n_workers):
+        patch_scans(self._scans(preprocessing._SCAN_CHUNK_SIZE * 5 + 7))
+        targets = [100.0, 150.0, 200.0, 250.0]
+        serial, serial_times = build_xics("fake.mzML", targets, 0.01)
+        parallel, parallel_times = build_xics("fake.mzML", targets, 0.01, n_workers=n_workers)
+        np.testing.assert_array_equal(parallel, serial)
+        np.testing.assert_array_equal(parallel_times, serial_times)
+
+    def test_cancellation(self, patch_scans):
+        patch_scans(self._scans(200))
+        cancelled = threading.Event()
+        cancelled.set()
+        with pytest.raises(ProcessingCancelled):
+            build_xics("fake.mzML", [100.0], 0.01, cancel_event=cancelled, n_workers=2)
+
+
+class TestPeakAnalyses:
+    def _scans(self, interference=0.0):
+        times = np.round(np.arange(0.0, 4.0, 0.02), 2)
+        signal = 1e5 * np.exp(-0.5 * ((times - 2.0) / 0.1) ** 2)
+        # Rises on the tail of the peak, 1.5 Da above it
+        tail = interference * np.exp(-0.5 * ((times - 2.3) / 0.1) ** 2)
+        mz = np.array([100.0, 101.0, 101.5, 150.0])
+        return [
+            (t, 0.0, 1, mz, np.array([s, 0.1 * s, i, 50.0]))
+            for t, s, i in zip(times, signal, tail)
+        ]
+
+    def test_peak_spectra_cover_integrated_peaks(self, patch_scans):
+        patch_scans(self._scans())
+        (compound,) = construct_xics("fake.mzML", {"A": {"ions": [100.0]}})
+        integration = compound.ions[100.0]["Integration Data"]
+        spectra = preprocessing.peak_spectra("fake.mzML", (compound,), 0.0001)
+        assert len(spectra) > 0
+        assert spectra.times.min() >= integration["start_time"] - 1e-6
+        assert spectra.times.max() <= integration["end_time"] + 1e-6
+        # m/z 150 is outside PEAK_SPECTRUM_WINDOW of the ion
+        np.testing.assert_allclose(spectra.time[2.0]["m/z array"], [100.0, 101.0, 101.5])
+
+    def test_peak_purity(self, patch_scans):
+        ion_list = {"A": {"ions": [100.0]}}
+        patch_scans(self._scans())
+        (pure,) = construct_xics("fake.mzML", ion_list, peak_purity=True)
+        (unchecked,) = construct_xics("fake.mzML", ion_list)
+        patch_scans(self._scans(interference=1e6))
+        (impure,) = construct_xics("fake.mzML", ion_list, peak_purity=True)
+        assert pure.ions[100.0]["Purity"]["impure"] is False
+        assert impure.ions[100.0]["Purity"]["impure"] is True
+        assert unchecked.ions[100.0]["Purity"] is None
+
+    def test_mass_errors_after_lock_mass_correction(self, patch_scans):
+        lock_mass, target = 556.2771, 300.0
+        drift = 1 + 8e-6
+        times = np.round(np.arange(0.0, 4.0, 0.02), 2)
+        signal = 1e5 * np.exp(-0.5 * ((times - 2.0) / 0.1) ** 2)
+        mz = np.array([target, lock_mass]) * drift
+        patch_scans([(t, 0.0, 1, mz, np.array([v, 1e5])) for t, v in zip(times, signal)])
+        ion_list = {"A": {"ions": [target]}}
+
+        (plain,) = construct_xics("fake.mzML", ion_list, mass_accuracy=1e-5, mass_errors=True)
+        (corrected,) = construct_xics(
+            "fake.mzML", ion_list, mass_accuracy=1e-5, mass_errors=True,
+            lock_mass={"lock_masses": [lock_mass]},
+        )
+        assert plain.ions[target]["Mass Error"]["median_ppm"] == pytest.approx(8.0, abs=0.01)
+        assert corrected.ions[target]["Mass Error"]["median_ppm"] == pytest.approx(0.0, abs=0.01)
+
+    def test_mass_errors_in_ion_tolerance_window(self, patch_scans):
+        # 1.5 mDa off: outside the 3 ppm global window, inside the ion's own
+        times = np.round(np.arange(0.0, 4.0, 0.02), 2)
+        signal = 1e5 * np.exp(-0.5 * ((times - 2.0) / 0.1) ** 2)
+        mz = np.array([195.0892])
+        patch_scans([(t, 0.0, 1, mz, np.array([v])) for t, v in zip(times, signal)])
+        (compound,) = construct_xics(
+            "fake.mzML", {"A": {"ions": [195.0877], "tolerance": "2 mDa"}},
+            mass_accuracy=1e-6, mass_errors=True,
+        )
+        error = compound.ions[195.0877]["Mass Error"]
+        assert error["apex_mz"] == pytest.approx(195.0892)
+        assert error["median_ppm"] == pytest.approx(0.0015 / 195.0877 * 1e6, rel=1e-3)
+
+    def test_charge_states(self, patch_scans):
+        from calculation.isotopes import ISOTOPE_SPACING
+
+        times = np.round(np.arange(0.0, 4.0, 0.02), 2)
+        signal = 1e5 * np.exp(-0.5 * ((times - 2.0) / 0.1) ** 2)
+        # Doubly charged envelope of an ion listed as [M+H]+
+        mz = 500.25 + np.arange(4) * ISOTOPE_SPACING / 2
+        patch_scans([(t, 0.0, 1, mz, v * 0.6 ** np.arange(4)) for t, v in zip(times, signal)])
+        (compound,) = construct_xics(
+            "fake.mzML", {"A": {"ions": [500.25], "info": ["[M+H]+"]}},
+            mass_accuracy=1e-5, charge_states=True,
+        )
+        state = compound.ions[500.25]["Charge State"]
+        assert state == {"charge": 2, "n_isotopes": 3, "expected": 1, "mismatch": True}
@@ -0,0 +1,105 @@
+"""
+Tests for peak purity in calculation/purity.py.
+
+Covers:
+- edge_times() at half height and at the integration boundaries
+- ion_purity() of a pure peak and of one with a coeluting interference
+- ms2_consistency() of the linked MS2 spectra
+- peak_purity() counts and the impure peak warning of result_warnings()
+"""
+
+import numpy as np
+import pytest
+
+from calculation.purity import edge_times, ion_purity, ms2_consistency, peak_purity
+from calculation.status import result_warnings
+from utils.classes import compounds_from_ion_list
+
+TIMES = np.round(np.arange(0.0, 4.0, 0.02), 2)
+ION = 200.0
+
+
+def _gaussian(apex=2.0, width=0.1):
+    return np.exp(-(((TIMES - apex) / width) ** 2) / 2)
+
+
+class _Times:
+    """``.time[rt]`` of an indexed reader: the spectrum of the nearest scan."""
+
+    def __init__(self, interference=None):
+        self.signal = 100 * _gaussian()
+        # Coelutes on the tail of the peak, 1 Da above it
+        self.interference = np.zeros_like(TIMES) if interference is None else interference
+
+    def __getitem__(self, rt):
+        i = int(np.argmin(np.abs(TIMES - rt)))
+        return {
+            "m/z array": np.array([ION, ION + 1.00335, ION + 1.5, ION + 10.0]),
+            "intensity array": np.array(
+                [self.signal[i], 0.1 * self.signal[i], self.interference[i], 50.0]
+            ),
+        }
+
+
+class _Reader:
+    def __init__(self, interference=None):
+        self.time = _Times(interference)
+
+
+def _compounds():
+    compounds = compounds_from_ion_list({"A": {"ions": [ION]}})
+    data = compounds[0].ions[ION]
+    data["MS Intensity"] = np.array((TIMES, 100 * _gaussian()))
+    data["RT"] = 2.0
+    data["Integration Data"] = {"baseline_corrected_area": 10.0, "start_time": 1.6, "end_time": 2.4}
+    return compounds
+
+
+class TestEdgeTimes:
+    def test_half_height(self):
+        leading, tailing = edge_times(TIMES, _gaussian(), 1.6, 2.4, 2.0)
+        # Half height of a Gaussian is 1.18 sigma from the apex, the scans within it
+        assert leading == pytest.approx(1.90) and tailing == pytest.approx(2.10)
+
+    def test_boundaries(self):
+        assert edge_times(TIMES, _gaussian(), 1.95, 2.05, 2.0) == pytest.approx((1.96, 2.04))
+
+
+class TestIonPurity:
+    def test_pure_peak(self):
+        data = _compounds()[0].ions[ION]
+        purity = ion_purity(_Reader(), ION, data)
+        assert purity["purity"] == pytest.approx(1.0)
+        assert not purity["impure"] and purity["ms2"] is None
+
+    def test_interference_on_the_tail(self):
+        data = _compounds()[0].ions[ION]
+        purity = ion_purity(_Reader(interference=100 * _gaussian(2.15)), ION, data)
+        assert purity["leading"] > purity["tailing"]
+        assert purity["purity"] == purity["tailing"] and purity["impure"]
+
+    def test_not_integrated(self):
+        data = _compounds()[0].ions[ION]
+        data["Integration Data"] = None
+        assert ion_purity(_Reader(), ION, data) is None
+
+
+class TestMs2Consistency:
+    def test_lowest_score(self):
+        spectrum = {"mz": np.array([100.0, 150.0]), "intensity": np.array([1.0, 2.0])}
+        other = {"mz": np.array([100.0, 120.0]), "intensity": np.array([1.0, 2.0])}
+        assert ms2_consistency([spectrum, spectrum]) == pytest.approx(1.0)
+        assert ms2_consistency([spectrum, spectrum, other]) < 0.5
+        assert ms2_consistency([spectrum]) is None
+
+
+class TestPeakPurity:
+    def test_counts_and_warns(self):
+        compounds = _compounds()
+        assert peak_purity(compounds, _Reader(interference=100 * _gaussian(2.15))) == 1
+        assert compounds[0].ions[ION]["Purity"]["impure"]
+        assert result_warnings(compounds)[0].startswith("possible coelution under A")
+
+    def test_min_purity(self):
+        with pytest.raises(ValueError, match="Minimum purity"):
+            peak_purity(_compounds(), _Reader(), min_purity=0.0)
@@ -0,0 +1,121 @@
+"""
+Tests for per-file QC metrics in calculation/qc.py.
+
+Covers:
+- scan_rate(), ms1_tic(), tic_stability() and spray_dropouts()
+- mass_errors() from the ions' Mass Error and peak_width_distribution()
+- qc_metrics() flags and validate_qc_thresholds()
+- file_status() picking up the QC flags
+"""
+
+import numpy as np
+import pytest
+
+from calculation.qc import (
+    QC_THRESHOLDS,
+    mass_errors,
+    ms1_tic,
+    peak_width_distribution,
+    qc_metrics,
+    scan_rate,
+    spray_dropouts,
+    tic_stability,
+    validate_qc_thresholds,
+)
+from calculation.status import file_status
+from utils.classes import compounds_from_ion_list
+
+TIMES = np.arange(120) / 60.0  # One MS1 scan per second for two minutes
+
+
+def _compounds(fwhm=0.1, rt=1.0, ppm=None):
+    """Processed compounds; with *ppm*, their ions' Mass Error as construct_xics sets it."""
+    compounds = compounds_from_ion_list({"A": {"ions": [200.0]}, "B": {"ions": [400.0]}})
+    for compound in compounds:
+        data = compound.ions[compound.target_list[0]]
+        data["MS Intensity"] = np.array((TIMES, np.ones_like(TIMES)), dtype=np.float32)
+        data["RT"] = rt
+        data["Peaks"] = [{"apex_rt": rt, "fwhm": fwhm}, {"apex_rt": 0.2, "fwhm": 5.0}]
+        data["Integration Data"] = {"baseline_corrected_area": 1.0}
+        if ppm is not None:
+            data["Mass Error"] = {"apex_error_ppm": ppm, "median_ppm": ppm, "n": 5}
+    return compounds
+
+
+class TestMetrics:
+    def test_scan_rate(self):
+        assert scan_rate(TIMES) == pytest.approx(1.0, rel=1e-3)
+        assert np.isnan(scan_rate(TIMES[:1]))
+
+    def test_ms1_tic_drops_ms2_points(self):
+        # MS1 scans interleaved with low-TIC MS2 scans
+        tic_times = np.sort(np.concatenate((TIMES, TIMES + 0.005)))
+        tic_values = np.tile([100.0, 1.0], len(TIMES))
+        times, values = ms1_tic(tic_times, tic_values, TIMES)
+        np.testing.assert_allclose(times, TIMES)
+        assert np.all(values == 100.0)
+
+    def test_ms1_tic_falls_back_to_whole_tic(self):
+        times, _ = ms1_tic(TIMES + 0.003, np.ones(len(TIMES)), TIMES)
+        np.testing.assert_allclose(times, TIMES + 0.003)
+
+    def test_tic_stability(self):
+        stability = tic_stability(np.full(10, 5.0))
+        assert stability == {"tic_cv": 0.0, "tic_median_change": 0.0}
+        assert tic_stability([1.0, 2.0, 1.0])["tic_median_change"] == pytest.approx(0.75)
+
+    def test_spray_dropouts(self):
+        tic = np.full(len(TIMES), 100.0)
+        tic[30:35] = 1.0  # Long enough
+        tic[60:62] = 1.0  # Too short
+        assert spray_dropouts(TIMES, tic) == [
+            {"start_time": pytest.approx(0.5), "end_time": pytest.approx(34 / 60)}
+        ]
+
+    def test_mass_errors(self):
+        compounds = _compounds(ppm=3.0)
+        # No peak in the apex spectrum
+        compounds[1].ions[400.0]["Mass Error"]["apex_error_ppm"] = None
+        np.testing.assert_allclose(mass_errors(compounds), [3.0], atol=1e-6)
+        assert len(mass_errors(_compounds())) == 0
+
+    def test_peak_widths_of_apex_peaks(self):
+        widths = peak_width_distribution(_compounds(fwhm=0.2))
+        assert widths["n"] == 2 and widths["median"] == pytest.approx(0.2)
+
+
+class TestQcMetrics:
+    def test_clean_file(self):
+        qc = qc_metrics(_compounds(ppm=1.0), TIMES, np.full(len(TIMES), 100.0))
+        assert qc["ms1_scan_rate"] == pytest.approx(1.0, rel=1e-3)
+        assert qc["ms1_scans"] == 120
+        assert qc["rt_range"] == pytest.approx([0.0, 119 / 60.0])
+        assert qc["median_mass_error_ppm"] == pytest.approx(1.0, abs=1e-6)
+        assert qc["dropouts"] == [] and qc["flags"] == []
+
+    def test_flags(self):
+        tic = np.full(len(TIMES), 100.0)
+        tic[10:20] = 0.0
+        qc = qc_metrics(_compounds(fwhm=1.0, ppm=25.0), TIMES, tic)
+        assert len(qc["flags"]) == 3
+        assert qc["flags"][0] == "median mass error of 25.0 ppm"
+        assert qc["flags"][1].startswith("broad peaks")
+        assert qc["flags"][2].startswith("TIC dropout at 0.17")
+
+    def test_without_tic_and_mass_errors(self):
+        qc = qc_metrics(_compounds(), thresholds={"min_scan_rate": 2.0})
+        assert qc["median_mass_error_ppm"] is None and np.isnan(qc["tic_cv"])
+        assert qc["flags"] == ["low MS1 scan rate (1.00 Hz)"]
+
+    def test_validate_thresholds(self):
+        assert validate_qc_thresholds() == QC_THRESHOLDS
+        with pytest.raises(ValueError, match="Unknown QC threshold"):
+            validate_qc_thresholds({"max_ppm": 5.0})
+        with pytest.raises(ValueError, match="must not be negative"):
+            validate_qc_thresholds({"max_peak_width": -1.0})
+
+    def test_flags_in_file_status(self):
+        compounds = _compounds(fwhm=1.0)
+        status = file_status("/data/a.mzML", compounds, qc=qc_metrics(compounds))
+        assert status.status == "warnings"
+        assert status.warnings == status.qc["flags"]
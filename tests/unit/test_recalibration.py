@@ -0,0 +1,77 @@
+"""
+Tests for calculation/recalibration.py.
+
+Covers:
+- validate_lock_mass() defaults and errors
+- lock_mass_error() and recalibrate_mz()
+- LockMassCorrector carrying the last error over scans
+"""
+
+import numpy as np
+import pytest
+
+from calculation.recalibration import (
+    LockMassCorrector,
+    lock_mass_error,
+    recalibrate_mz,
+    validate_lock_mass,
+)
+
+LOCK_MASS = 556.2771
+
+
+def _shifted(mz, ppm):
+    return np.asarray(mz, dtype=np.float64) * (1 + ppm * 1e-6)
+
+
+class TestValidateLockMass:
+    def test_no_lock_masses_means_disabled(self):
+        assert validate_lock_mass(None) is None
+        assert validate_lock_mass({"lock_masses": []}) is None
+
+    def test_defaults(self):
+        assert validate_lock_mass({"lock_masses": [LOCK_MASS]}) == {
+            "lock_masses": (LOCK_MASS,),
+            "window_ppm": 20.0,
+            "min_intensity": 0.0,
+        }
+
+    @pytest.mark.parametrize(
+        "settings",
+        [{"lock_masses": [-1.0]}, {"lock_masses": [LOCK_MASS], "window_ppm": 0}],
+    )
+    def test_invalid_raises(self, settings):
+        with pytest.raises(ValueError):
+            validate_lock_mass(settings)
+
+
+class TestLockMassError:
+    def test_measures_and_corrects_error(self):
+        mz = _shifted([300.0, LOCK_MASS, 800.0], 5.0)
+        error = lock_mass_error(mz, np.array([10.0, 1000.0, 10.0]), [LOCK_MASS])
+        assert error == pytest.approx(5.0, abs=1e-6)
+        np.testing.assert_allclose(recalibrate_mz(mz, error), [300.0, LOCK_MASS, 800.0])
+
+    def test_most_intense_peak_in_window(self):
+        mz = np.array([LOCK_MASS * (1 - 10e-6), LOCK_MASS * (1 + 3e-6)])
+        error = lock_mass_error(mz, np.array([5.0, 500.0]), [LOCK_MASS])
+        assert error == pytest.approx(3.0, abs=1e-6)
+
+    def test_missing_or_weak_lock_mass(self):
+        mz = np.array([300.0, LOCK_MASS])
+        intensity = np.array([10.0, 1.0])
+        assert lock_mass_error(mz, intensity, [600.0]) is None
+        assert lock_mass_error(mz, intensity, [LOCK_MASS], min_intensity=5.0) is None
+
+
+class TestLockMassCorrector:
+    def test_carries_last_error(self):
+        corrector = LockMassCorrector(validate_lock_mass({"lock_masses": [LOCK_MASS]}))
+        before = corrector(np.array([300.0]), np.array([10.0]))
+        with_lock = corrector(_shifted([300.0, LOCK_MASS], 4.0), np.array([10.0, 100.0]))
+        without_lock = corrector(_shifted([300.0], 4.0), np.array([10.0]))
+        np.testing.assert_allclose(before, [300.0])
+        np.testing.assert_allclose(with_lock, [300.0, LOCK_MASS])
+        np.testing.assert_allclose(without_lock, [300.0])
+        assert corrector.errors[0] is None and corrector.errors[2] is None
+        assert "1/3 scans" in corrector.summary()
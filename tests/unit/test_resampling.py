@@ -0,0 +1,81 @@
+"""
+Tests for the m/z resampling in calculation/resampling.py.
+
+Covers:
+- mz_bin_edges() with linear and ppm spacing, invalid bins
+- resample_spectra() sum and max statistics, default and given edges
+- resample_spectrum() of one spectrum, peaks outside the bins
+"""
+
+import numpy as np
+import pytest
+
+from calculation.resampling import (
+    bin_centers,
+    mz_bin_edges,
+    resample_spectra,
+    resample_spectrum,
+)
+
+
+class TestMzBinEdges:
+    def test_linear(self):
+        edges = mz_bin_edges(100.0, 101.0, bin_width=0.25)
+        np.testing.assert_allclose(edges, [100.0, 100.25, 100.5, 100.75, 101.0])
+        np.testing.assert_allclose(bin_centers(edges), [100.125, 100.375, 100.625, 100.875])
+
+    def test_ppm_bins_have_constant_relative_width(self):
+        edges = mz_bin_edges(100.0, 1000.0, spacing="ppm", bin_ppm=10.0)
+        widths = np.diff(edges[:-1]) / edges[:-2]
+        np.testing.assert_allclose(widths, 10e-6, rtol=1e-6)
+        assert edges[0] == 100.0 and edges[-1] >= 1000.0
+
+    @pytest.mark.parametrize(
+        "kwargs, match",
+        [
+            ({"spacing": "log"}, "Unknown bin spacing"),
+            ({"bin_width": 0.0}, "Bin width must be positive"),
+            ({"bin_width": 1e-9}, "exceed the maximum"),
+        ],
+    )
+    def test_invalid(self, kwargs, match):
+        with pytest.raises(ValueError, match=match):
+            mz_bin_edges(100.0, 200.0, **kwargs)
+        with pytest.raises(ValueError, match="must be less than"):
+            mz_bin_edges(200.0, 100.0)
+
+
+class TestResampleSpectra:
+    def test_common_axis(self):
+        edges = mz_bin_edges(100.0, 103.0, bin_width=1.0)
+        spectra = [
+            (np.array([100.2, 100.4, 102.5]), np.array([1.0, 2.0, 5.0])),
+            (np.array([101.1]), np.array([4.0])),
+        ]
+        centers, matrix = resample_spectra(spectra, edges)
+        np.testing.assert_allclose(centers, [100.5, 101.5, 102.5])
+        np.testing.assert_allclose(matrix, [[3.0, 0.0, 5.0], [0.0, 4.0, 0.0]])
+        _, maxima = resample_spectra(spectra, edges, statistic="max")
+        np.testing.assert_allclose(maxima[0], [2.0, 0.0, 5.0])
+
+    def test_default_edges_cover_every_peak(self):
+        spectra = [([500.0, 500.004], [1.0, 1.0]), ([700.0], [2.0])]
+        centers, matrix = resample_spectra(spectra, spacing="ppm", bin_ppm=20.0)
+        assert matrix.shape == (2, len(centers))
+        assert matrix[0].sum() == 2.0 and matrix[1].sum() == 2.0
+        assert np.count_nonzero(matrix[0]) == 1  # 8 ppm apart, one bin
+
+    def test_single_spectrum_and_outside_peaks(self):
+        edges = mz_bin_edges(100.0, 102.0, bin_width=1.0)
+        np.testing.assert_allclose(
+            resample_spectrum(np.array([99.0, 100.5, 102.0, 150.0]), np.ones(4), edges),
+            [1.0, 1.0],
+        )
+
+    def test_invalid(self):
+        with pytest.raises(ValueError, match="Unknown resampling statistic"):
+            resample_spectra([([100.0], [1.0])], statistic="mean")
+        with pytest.raises(ValueError, match="intensities"):
+            resample_spectra([([100.0, 101.0], [1.0])])
+        centers, matrix = resample_spectra([([], [])])
+        assert centers.size == 0 and matrix.shape == (1, 0)
@@ -0,0 +1,149 @@
+"""
+Tests for retention agreement in calculation/retention.py.
+
+Covers:
+- library_retention() metadata parsing and units
+- retention_index() interpolation and extrapolation
+- rt_score() / identification_score() / retention_agreement()
+- compound_rt_scores() on ion list compounds with expected RTs and indices,
+  after alignment
+- SpectralLibrary.match() with an RT component
+"""
+
+import math
+
+import numpy as np
+import pytest
+
+from calculation.retention import (
+    compound_rt_scores,
+    identification_score,
+    library_retention,
+    reference_points,
+    retention_agreement,
+    retention_index,
+    rt_score,
+)
+from utils.classes import compounds_from_ion_list
+from utils.library import SpectralLibrary
+
+
+class TestLibraryRetention:
+    @pytest.mark.parametrize(
+        "metadata, expected",
+        [
+            ({"retentiontime": "5.2 min"}, (5.2, None)),
+            ({"retentiontime": "312 s", "ri": "1450"}, (5.2, 1450.0)),
+            ({"rtinseconds": "90"}, (1.5, None)),
+            ({"retention_index": 812.5}, (None, 812.5)),
+            ({"retentiontime": "n/a"}, (None, None)),
+        ],
+    )
+    def test_metadata(self, metadata, expected):
+        rt, ri = library_retention(metadata)
+        assert rt == (None if expected[0] is None else pytest.approx(expected[0]))
+        assert ri == expected[1]
+
+
+class TestRetentionIndex:
+    references = {800.0: 2.0, 900.0: 4.0, 1000.0: 5.0}
+
+    def test_interpolated(self):
+        assert retention_index(3.0, self.references) == pytest.approx(850.0)
+        assert retention_index(4.5, self.references) == pytest.approx(950.0)
+
+    def test_extrapolated(self):
+        assert retention_index(1.0, self.references) == pytest.approx(750.0)
+        assert retention_index(6.0, self.references) == pytest.approx(1100.0)
+
+    @pytest.mark.parametrize("references", [{800.0: 2.0}, {800.0: 4.0, 900.0: 2.0}])
+    def test_invalid_references_raise(self, references):
+        with pytest.raises(ValueError):
+            retention_index(3.0, references)
+
+
+class TestScores:
+    def test_rt_score(self):
+        assert rt_score(5.0, 5.0, 0.2) == 1.0
+        assert rt_score(5.2, 5.0, 0.2) == pytest.approx(math.exp(-0.5))
+        with pytest.raises(ValueError):
+            rt_score(5.0, 5.0, 0)
+
+    def test_identification_score(self):
+        assert identification_score(0.9, None) == 0.9
+        assert identification_score(0.9, 0.5, rt_weight=0.5) == pytest.approx(0.7)
+        with pytest.raises(ValueError):
+            identification_score(0.9, 0.5, rt_weight=2)
+
+    def test_agreement_prefers_retention_index(self):
+        agreement = retention_agreement(5.0, 4.0, ri=905.0, expected_ri=900.0)
+        assert agreement["unit"] == "RI" and agreement["error"] == pytest.approx(5.0)
+        assert retention_agreement(5.0, 4.9)["unit"] == "min"
+        assert retention_agreement(5.0, None) is None
+
+
+def _processed(ion_list, rts):
+    compounds = compounds_from_ion_list(ion_list)
+    for compound, rt in zip(compounds, rts):
+        for data in compound.ions.values():
+            data["RT"] = rt
+            data["Integration Data"] = {"baseline_corrected_area": 1.0}
+    return compounds
+
+
+class TestCompoundRtScores:
+    def test_expected_rt(self):
+        compounds = _processed(
+            {"Caffeine": {"ions": [195.0877], "rt": 5.0}, "Other": {"ions": [200.0]}}, [5.1, 3.0]
+        )
+        assert compounds[0].expected_rt == 5.0
+        assert compound_rt_scores(compounds, rt_tolerance=0.1) == 1
+        score = compounds[0].ions[195.0877]["RT Score"]
+        assert score["error"] == pytest.approx(0.1)
+        assert score["score"] == pytest.approx(math.exp(-0.5))
+        assert compounds[1].ions[200.0]["RT Score"] is None
+
+    def test_expected_rt_after_alignment(self):
+        compounds = _processed({"Caffeine": {"ions": [195.0877], "rt": 5.0}}, [5.4])
+        compounds[0].ions[195.0877]["Aligned RT"] = 5.0
+        compound_rt_scores(compounds)
+        score = compounds[0].ions[195.0877]["RT Score"]
+        assert score["observed"] == pytest.approx(5.0)
+        assert score["score"] == pytest.approx(1.0)
+
+    def test_retention_index_from_references(self):
+        compounds = _processed(
+            {
+                "Standard A": {"ions": [100.0], "ri": 800},
+                "Standard B": {"ions": [150.0], "ri": 900},
+                "Analyte": {"ions": [195.0877], "ri": 850, "rt": 9.0},
+            },
+            [2.2, 4.2, 3.2],
+        )
+        assert reference_points(compounds) == {800.0: 2.2, 900.0: 4.2, 850.0: 3.2}
+        # Only the standards: the analyte's own index is not a reference
+        compound_rt_scores(compounds, references={800.0: 2.2, 900.0: 4.2})
+        score = compounds[2].ions[195.0877]["RT Score"]
+        assert score["unit"] == "RI"
+        assert score["observed"] == pytest.approx(850.0)
+        assert score["score"] == pytest.approx(1.0)
+
+
+def test_library_match_with_rt():
+    spectra = [
+        {"precursor_mz": 195.0877, "mz": np.array([110.0713, 138.0662]),
+         "intensity": np.array([40.0, 100.0]), "metadata": {"name": name, "retentiontime": rt}}
+        for name, rt in (("Early", "2.0"), ("Late", "6.0"))
+    ] + [
+        {"precursor_mz": 195.0877, "mz": np.array([138.0662]), "intensity": np.ones(1),
+         "metadata": {"name": "No RT"}},
+    ]
+    library = SpectralLibrary.from_spectra(spectra)
+    query = (np.array([110.0713, 138.0662]), np.array([40.0, 100.0]), 195.0877)
+    assert "rt_score" not in library.match(*query)[0]
+    hits = library.match(*query, rt=6.0, rt_weight=0.5)
+    # Candidates without a retention keep their spectral score
+    assert [hit["name"] for hit in hits] == ["Late", "No RT", "Early"]
+    assert hits[0]["rt_score"] == pytest.approx(1.0)
+    assert hits[1]["rt_score"] is None and hits[1]["score"] == hits[1]["spectral_score"]
+    assert hits[2]["score"] == pytest.approx(0.5 * hits[2]["spectral_score"], abs=1e-6)
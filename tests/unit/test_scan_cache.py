@@ -0,0 +1,148 @@
+"""
+Tests for the on-disk scan cache in utils/scan_cache.py.
+
+Covers:
+- Round trip of scans (incl. mobility) through write_scan_cache()/read_scan_cache()
+- Invalidation on source size/mtime change and per-selection entries
+- No entry left behind by an interrupted pass
+- loading.iter_ms_scans(cache=True) skipping the reader on the second pass
+- SpilledScans round trip, clean-up, pickling and deepcopy
+"""
+
+import copy
+import os
+import pickle
+
+import numpy as np
+import pytest
+
+from utils import loading
+from utils.scan_cache import (
+    SpilledScans,
+    cache_dir,
+    clear_scan_cache,
+    read_scan_cache,
+    write_scan_cache,
+)
+
+
+def _scans():
+    return [
+        (0.1, 30.0, 1, np.array([100.0, 200.0]), np.array([10.0, 20.0]), None),
+        (0.2, 5.0, 2, np.array([150.0]), np.array([5.0]), None),
+        (0.3, 0.0, 1, np.array([]), np.array([]), None),
+        (0.4, 7.0, 1, np.array([300.0]), np.array([7.0]), np.array([0.85])),
+    ]
+
+
+@pytest.fixture
+def source(tmp_path):
+    path = tmp_path / "run.mzML"
+    path.write_bytes(b"<mzML/>")
+    return str(path)
+
+
+def _fill(source, **selection):
+    return list(write_scan_cache(source, iter(_scans()), with_mobility=True, **selection))
+
+
+class TestScanCache:
+    def test_round_trip(self, source):
+        assert read_scan_cache(source) is None
+        assert len(_fill(source)) == 4
+        cached = list(read_scan_cache(source, with_mobility=True))
+        assert len(cached) == 4
+        for (rt, tic, level, mz, intensity, mobility), expected in zip(cached, _scans()):
+            assert (rt, tic, level) == expected[:3]
+            np.testing.assert_array_equal(mz, expected[3])
+            np.testing.assert_array_equal(intensity, expected[4])
+            if expected[5] is None:
+                assert mobility is None
+            else:
+                np.testing.assert_array_equal(mobility, expected[5])
+
+    def test_without_mobility_yields_five_items(self, source):
+        passed = list(write_scan_cache(source, iter(_scans())))
+        assert {len(scan) for scan in passed} == {5}
+        assert {len(scan) for scan in read_scan_cache(source)} == {5}
+
+    def test_invalidated_by_source_change(self, source):
+        _fill(source)
+        stat = os.stat(source)
+        os.utime(source, ns=(stat.st_atime_ns, stat.st_mtime_ns + 1_000_000_000))
+        assert read_scan_cache(source) is None
+
+    def test_entries_per_selection(self, source):
+        _fill(source, polarity="positive")
+        assert read_scan_cache(source) is None
+        assert read_scan_cache(source, polarity="positive") is not None
+
+    def test_interrupted_pass_leaves_no_entry(self, source):
+        scans = write_scan_cache(source, iter(_scans()))
+        next(scans)
+        scans.close()
+        assert read_scan_cache(source) is None
+        assert list(cache_dir(source).iterdir()) == []
+
+    def test_clear(self, source):
+        _fill(source)
+        clear_scan_cache(source)
+        assert not cache_dir(source).exists()
+
+
+def test_iter_ms_scans_reads_cache_on_second_pass(source, monkeypatch):
+    calls = []
+
+    class FakeReader:
+        @staticmethod
+        def iter_scans(path, polarity=None, scan_filter=None, with_mobility=False):
+            calls.append(path)
+            for scan in _scans():
+                yield scan if with_mobility else scan[:5]
+
+    monkeypatch.setattr(loading, "_get_reader_module", lambda path: FakeReader)
+    first = list(loading.iter_ms_scans(source, cache=True))
+    second = list(loading.iter_ms_scans(source, cache=True))
+    assert len(calls) == 1
+    assert [scan[0] for scan in second] == [scan[0] for scan in first]
+    np.testing.assert_array_equal(second[0][3], first[0][3])
+
+
+class TestSpilledScans:
+    def test_round_trip(self, tmp_path):
+        spilled = SpilledScans(directory=tmp_path)
+        for scan in _scans():
+            spilled.append(scan)
+        spilled.finish()
+        assert len(spilled) == 4
+        for expected, scan in zip(_scans(), spilled):
+            assert scan[:3] == expected[:3]
+            np.testing.assert_array_equal(scan[3], expected[3])
+            np.testing.assert_array_equal(scan[4], expected[4])
+            assert (scan[5] is None) == (expected[5] is None)
+        assert len(list(spilled)) == 4  # Can be iterated again
+
+    def test_release_deletes_files(self, tmp_path):
+        spilled = SpilledScans(directory=tmp_path)
+        spilled.append(_scans()[0])
+        spilled.finish()
+        spilled.release()
+        assert not spilled.directory.exists()
+
+    def test_no_peaks(self, tmp_path):
+        spilled = SpilledScans(directory=tmp_path)
+        spilled.append(_scans()[2])
+        assert [scan[0] for scan in spilled] == [0.3]
+
+    @pytest.mark.parametrize("clone", [copy.deepcopy, lambda s: pickle.loads(pickle.dumps(s))])
+    def test_copies_to_new_files(self, tmp_path, clone):
+        spilled = SpilledScans(directory=tmp_path)
+        for scan in _scans():
+            spilled.append(scan)
+        spilled.finish()
+        copied = clone(spilled)
+        assert copied.directory != spilled.directory
+        spilled.release()
+        assert [scan[0] for scan in copied] == [scan[0] for scan in _scans()]
+        np.testing.assert_array_equal(list(copied)[0][3], _scans()[0][3])
+        copied.release()
@@ -0,0 +1,77 @@
+"""
+Tests for utils/scan_filter.py.
+
+Covers:
+- validate_scan_filter() normalization and errors
+- scan_matches() on MS level, filter string and precursor m/z
+- scan_matches() on injection time and collision energy ranges
+"""
+
+import pytest
+
+from utils.scan_filter import scan_matches, validate_scan_filter
+
+
+class TestValidateScanFilter:
+    def test_empty_means_no_filter(self):
+        assert validate_scan_filter(None) is None
+        assert validate_scan_filter({}) is None
+        assert validate_scan_filter({"ms_levels": None, "filter_regex": ""}) is None
+
+    def test_normalizes_values(self):
+        scan_filter = validate_scan_filter({"ms_levels": 1, "precursor_range": [200, 201]})
+        assert scan_filter == {
+            "ms_levels": (1,),
+            "filter_regex": None,
+            "precursor_range": (200.0, 201.0),
+            "injection_time_range": None,
+            "collision_energy_range": None,
+        }
+        assert validate_scan_filter(scan_filter) == scan_filter
+
+    @pytest.mark.parametrize(
+        "settings",
+        [
+            {"ms_level": 1},
+            {"ms_levels": [0]},
+            {"ms_levels": []},
+            {"filter_regex": "SIM ("},
+            {"precursor_range": (300.0, 200.0)},
+            {"injection_time_range": (50.0, 10.0)},
+        ],
+    )
+    def test_invalid_settings_raise(self, settings):
+        with pytest.raises(ValueError):
+            validate_scan_filter(settings)
+
+
+class TestScanMatches:
+    def test_no_filter_accepts_everything(self):
+        assert scan_matches(None, 3)
+
+    def test_ms_levels(self):
+        scan_filter = validate_scan_filter({"ms_levels": [1, 2]})
+        assert scan_matches(scan_filter, 2)
+        assert not scan_matches(scan_filter, 3)
+
+    def test_filter_regex_searches_filter_string(self):
+        scan_filter = validate_scan_filter({"filter_regex": r"SIM ms \[194"})
+        assert scan_matches(scan_filter, 1, "FTMS + p ESI SIM ms [194.00-196.00]")
+        assert not scan_matches(scan_filter, 1, "FTMS + p ESI Full ms [100.00-1000.00]")
+        assert not scan_matches(scan_filter, 1, None)
+
+    def test_precursor_range(self):
+        scan_filter = validate_scan_filter({"precursor_range": (195.0, 195.2)})
+        assert scan_matches(scan_filter, 2, precursor_mz=195.0877)
+        assert not scan_matches(scan_filter, 2, precursor_mz=138.0662)
+        assert not scan_matches(scan_filter, 1)  # survey scans have no precursor
+
+    def test_acquisition_ranges(self):
+        scan_filter = validate_scan_filter(
+            {"injection_time_range": (0, 50), "collision_energy_range": (25, 35)}
+        )
+        hcd = {"injection_time": 22.0, "collision_energy": None, "normalized_collision_energy": 30}
+        assert scan_matches(scan_filter, 2, acquisition=hcd)
+        assert scan_matches(scan_filter, 2, acquisition={"injection_time": 5, "collision_energy": 30})
+        assert not scan_matches(scan_filter, 2, acquisition={**hcd, "injection_time": 80.0})
+        assert not scan_matches(scan_filter, 2)  # nothing recorded
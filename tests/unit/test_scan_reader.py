@@ -0,0 +1,70 @@
+"""
+Tests for the scan iterator in utils/scan_reader.py.
+
+Covers:
+- ScanReader tuples (rt, mz, intensity, ms_level, precursor_mz)
+- MS level and retention time selection
+- Closing and restarting a reader
+"""
+
+import numpy as np
+import pytest
+
+from utils.scan_reader import ScanReader
+
+MGF_TEXT = """\
+BEGIN IONS
+TITLE=caffeine
+PEPMASS=195.0877
+RTINSECONDS=60
+138.0662 100
+110.0713 40
+END IONS
+
+BEGIN IONS
+TITLE=theophylline
+PEPMASS=181.0720
+RTINSECONDS=120
+124.0505 100
+END IONS
+"""
+
+
+@pytest.fixture
+def mgf_file(tmp_path):
+    path = tmp_path / "run.mgf"
+    path.write_text(MGF_TEXT)
+    return path
+
+
+def test_yields_scans_with_precursors(mgf_file):
+    with ScanReader(mgf_file) as reader:
+        scans = list(reader)
+    assert len(scans) == 2
+    rt, mz, intensity, ms_level, precursor_mz = scans[0]
+    assert rt == pytest.approx(1.0)
+    assert mz.dtype == np.float64
+    np.testing.assert_allclose(intensity, [100.0, 40.0])
+    assert (ms_level, precursor_mz) == (2, pytest.approx(195.0877))
+
+
+def test_selection(mgf_file):
+    assert list(ScanReader(mgf_file, ms_levels=1)) == []
+    assert [scan[0] for scan in ScanReader(mgf_file, ms_levels=[2], rt_range=(1.5, 3.0))] == [
+        pytest.approx(2.0)
+    ]
+    with pytest.raises(ValueError):
+        ScanReader(mgf_file, rt_range=(3.0, 1.0))
+    with pytest.raises(ValueError):
+        ScanReader(mgf_file, scan_filter={"unknown": 1})
+
+
+def test_close_restarts(mgf_file):
+    reader = ScanReader(mgf_file)
+    assert next(reader)[0] == pytest.approx(1.0)
+    reader.close()
+    assert next(reader)[0] == pytest.approx(1.0)
+    assert iter(reader) is reader
+    assert next(reader)[0] == pytest.approx(2.0)
+    with pytest.raises(StopIteration):
+        next(reader)
@@ -0,0 +1,68 @@
+"""
+Tests for MS2 neutral loss and fragment screening in calculation/screening.py.
+
+Covers:
+- screen_spectra() neutral losses from the precursor and diagnostic fragments
+- Tolerance, relative intensity threshold and the "all" mode
+- Input validation
+"""
+
+import numpy as np
+import pytest
+
+from calculation.screening import COMMON_NEUTRAL_LOSSES, screen_spectra
+
+PRECURSOR = 500.0
+
+
+def _spectrum(scan_time, mz, intensity, precursor=PRECURSOR):
+    return scan_time, precursor, np.array(mz), np.array(intensity)
+
+
+SPECTRA = [
+    # Phosphate loss and the phosphocholine head group
+    _spectrum(1.0, [184.0733, PRECURSOR - 97.9769, 300.0], [50.0, 100.0, 10.0]),
+    # Only the head group, 3 mDa off
+    _spectrum(2.0, [184.0763, 250.0], [20.0, 100.0]),
+    # Nothing
+    _spectrum(3.0, [120.0, 250.0], [20.0, 100.0]),
+]
+
+
+class TestScreenSpectra:
+    def test_losses_and_fragments(self):
+        hits = screen_spectra(
+            SPECTRA, {"H3PO4": COMMON_NEUTRAL_LOSSES["H3PO4"]}, {"phosphocholine": 184.0733}
+        )
+        assert [hit["scan_time"] for hit in hits] == [1.0, 2.0]
+        loss = hits[0]["neutral_losses"][0]
+        assert loss["name"] == "H3PO4" and loss["mz"] == pytest.approx(402.0231)
+        assert loss["intensity"] == 1.0
+        assert hits[0]["fragments"][0]["intensity"] == 0.5
+        assert hits[1]["neutral_losses"] == [] and hits[1]["precursor_mz"] == PRECURSOR
+
+    def test_sequence_of_masses(self):
+        hits = screen_spectra(SPECTRA, fragments=[184.0733])
+        assert hits[0]["fragments"][0]["name"] == "184.0733"
+
+    def test_tolerance(self):
+        hits = screen_spectra(SPECTRA, fragments=[184.0733], tolerance="2 mDa")
+        assert [hit["scan_time"] for hit in hits] == [1.0]
+
+    def test_min_relative_intensity(self):
+        hits = screen_spectra(SPECTRA, fragments=[300.0], min_relative_intensity=0.2)
+        assert hits == []
+
+    def test_all_mode(self):
+        hits = screen_spectra(SPECTRA, [97.9769], [184.0733], mode="all")
+        assert [hit["scan_time"] for hit in hits] == [1.0]
+
+    def test_loss_needs_precursor(self):
+        spectra = [_spectrum(1.0, [402.0231], [1.0], precursor=None)]
+        assert screen_spectra(spectra, [97.9769]) == []
+
+    def test_invalid(self):
+        with pytest.raises(ValueError, match="Nothing to screen for"):
+            screen_spectra(SPECTRA)
+        with pytest.raises(ValueError, match="Unknown screening mode"):
+            screen_spectra(SPECTRA, fragments=[184.0], mode="most")
@@ -0,0 +1,96 @@
+"""
+Tests for re-extraction from scans held in memory (calculation/session.py).
+
+Covers:
+- LoadedRun.extract() reading the file once for several ion lists
+- Separate scan selections per polarity
+- Spilling scans to disk over the memory budget
+- Pickling and copying a run without its scans
+"""
+
+import copy
+import pickle
+
+import numpy as np
+
+from calculation import session
+from calculation.session import LoadedRun
+from utils.scan_cache import SpilledScans
+
+
+def _patch_reads(monkeypatch):
+    reads = []
+    mz = np.array([100.0, 200.0])
+    scans = [(0.1 * i, 30.0, 1, mz, np.array([10.0, 20.0]) * (i + 1), None) for i in range(5)]
+
+    def fake_iter_ms_scans(path, progress_callback=None, **filters):
+        reads.append(filters.get("polarity"))
+        yield from scans
+
+    monkeypatch.setattr(session, "iter_ms_scans", fake_iter_ms_scans)
+    return reads
+
+
+class TestLoadedRun:
+    def test_extract_reads_file_once(self, monkeypatch):
+        reads = _patch_reads(monkeypatch)
+        run = LoadedRun("run.mzML")
+        (a,) = run.extract({"A": {"ions": [100.0]}})
+        (b,) = run.extract({"B": {"ions": [200.0]}}, mass_accuracy=0.0002)
+        assert reads == [None]
+        np.testing.assert_allclose(a.ions[100.0]["MS Intensity"][1], [10, 20, 30, 40, 50])
+        np.testing.assert_allclose(b.ions[200.0]["MS Intensity"][1], [20, 40, 60, 80, 100])
+        assert b.file == "run.mzML"
+
+    def test_selections_are_read_separately(self, monkeypatch):
+        reads = _patch_reads(monkeypatch)
+        run = LoadedRun("run.mzML")
+        run.extract({"A": {"ions": [100.0]}})
+        run.extract({"A": {"ions": [100.0]}}, polarity="negative")
+        run.extract({"A": {"ions": [100.0]}}, polarity="negative")
+        assert reads == [None, "negative"]
+
+    def test_release(self, monkeypatch):
+        reads = _patch_reads(monkeypatch)
+        run = LoadedRun("run.mzML")
+        run.extract({"A": {"ions": [100.0]}})
+        run.release()
+        run.extract({"A": {"ions": [100.0]}})
+        assert len(reads) == 2
+
+    def test_spills_over_memory_budget(self, monkeypatch):
+        _patch_reads(monkeypatch)
+        run = LoadedRun("run.mzML", memory_budget=100)  # Each scan holds 32 bytes of arrays
+        (a,) = run.extract({"A": {"ions": [100.0]}})
+        (selection,) = run._scans.values()
+        assert isinstance(selection, SpilledScans)
+        np.testing.assert_allclose(a.ions[100.0]["MS Intensity"][1], [10, 20, 30, 40, 50])
+        directory = selection.directory
+        run.release()
+        assert not directory.exists()
+
+    def test_within_memory_budget(self, monkeypatch):
+        _patch_reads(monkeypatch)
+        run = LoadedRun("run.mzML", memory_budget="1MB")
+        run.extract({"A": {"ions": [100.0]}})
+        (selection,) = run._scans.values()
+        assert isinstance(selection, list)
+
+    def test_pickle_drops_scans(self, monkeypatch):
+        reads = _patch_reads(monkeypatch)
+        run = LoadedRun("run.mzML", memory_budget=100)
+        run.extract({"A": {"ions": [100.0]}})
+        restored = pickle.loads(pickle.dumps(run))
+        assert restored.path == "run.mzML" and restored.memory_budget == 100
+        assert "0 scan selections" in repr(restored)
+        (a,) = restored.extract({"A": {"ions": [100.0]}})
+        assert len(reads) == 2
+        np.testing.assert_allclose(a.ions[100.0]["MS Intensity"][1], [10, 20, 30, 40, 50])
+        assert "1 scan selections" in repr(run)  # The original keeps its scans
+
+    def test_deepcopy(self, monkeypatch):
+        reads = _patch_reads(monkeypatch)
+        run = LoadedRun("run.mzML")
+        run.extract({"A": {"ions": [100.0]}})
+        copy.deepcopy(run).extract({"A": {"ions": [100.0]}})
+        assert len(reads) == 2
@@ -0,0 +1,186 @@
+"""
+Tests for session files in utils/session_file.py.
+
+Covers:
+- save_session() / load_session() round trip of measurements, compounds and settings
+- Results, data frames and records stored as data, without pickles
+- Raw file readers left out of the file, and reopened on load
+- Rejection of foreign, corrupt, pickled and newer session files
+"""
+
+import json
+
+import numpy as np
+import pandas as pd
+import pytest
+
+from calculation.alignment import RTShift
+from calculation.status import FileStatus
+from utils import session_file
+from utils.classes import MSMeasurement, compounds_from_ion_list
+from utils.errors import FileParseError
+from utils.session_file import SESSION_FORMAT, load_session, save_session
+
+
+def _measurement(path):
+    """An MS measurement with one processed compound, without reading *path*."""
+    (compound,) = compounds_from_ion_list({"Alanine": {"ions": [90.055]}})
+    compound.concentration = 2.5
+    compound.file = "sample"
+    data = compound.ions[90.055]
+    data["MS Intensity"] = np.array([[0.1, 0.2], [1.0, 3.0]])
+    data["RT"] = np.float64(0.15)
+    data["Integration Data"] = {"start_time": 0.1, "end_time": 0.2, "baseline_corrected_area": 4.0}
+    data["Peaks"] = [{"apex_rt": 0.15, "area": 4.0}]
+    compound.custom_mz_ranges = {90.055: (90.05, 90.06)}
+    measurement = MSMeasurement.__new__(MSMeasurement)
+    measurement.__dict__.update(
+        path=str(path),
+        filename="sample",
+        xics=[compound],
+        manifest=None,
+        tic_times=np.array([0.1, 0.2]),
+        spectra_data={0: (0.1, np.array([90.055]), np.array([1.0]))},
+    )
+    measurement.data = lambda: None
+    return measurement
+
+
+def _write_state(path, state):
+    members = {"session": np.frombuffer(json.dumps(state).encode(), dtype=np.uint8)}
+    with open(path, "wb") as f:
+        np.savez_compressed(f, **members)
+
+
+@pytest.fixture
+def raw_file(tmp_path):
+    path = tmp_path / "sample.mzML"
+    path.write_text("<mzML/>")
+    return path
+
+
+class TestRoundTrip:
+    def test_contents(self, tmp_path, raw_file, monkeypatch):
+        monkeypatch.setattr(session_file, "load_ms_data", lambda path: f"reader of {path}")
+        measurement = _measurement(raw_file)
+        (compound,) = compounds_from_ion_list({"Alanine": {"ions": [90.055], "rt": 0.2}})
+        save_session(
+            tmp_path / "batch.lcms",
+            {"sample": measurement},
+            compounds=(compound,),
+            settings={"mass_accuracy": 0.0002, "smoothing": {"method": "savgol"}},
+        )
+        session = load_session(tmp_path / "batch.lcms")
+
+        restored = session.ms_measurements["sample"]
+        assert isinstance(restored, MSMeasurement)
+        (xic,) = restored.xics
+        assert xic.concentration == 2.5
+        assert xic.file == "sample"
+        data = xic.ions[90.055]
+        np.testing.assert_array_equal(data["MS Intensity"], [[0.1, 0.2], [1.0, 3.0]])
+        assert data["RT"] == 0.15
+        assert data["Integration Data"]["baseline_corrected_area"] == 4.0
+        assert data["Peaks"] == [{"apex_rt": 0.15, "area": 4.0}]
+        assert xic.custom_mz_ranges == {90.055: (90.05, 90.06)}
+        np.testing.assert_array_equal(restored.tic_times, [0.1, 0.2])
+        scan_time, mz, _ = restored.spectra_data[0]
+        assert scan_time == 0.1 and mz.tolist() == [90.055]
+        assert restored.data == f"reader of {raw_file}"
+        assert session.lc_measurements == {}
+        (restored_compound,) = session.compounds
+        assert restored_compound.name == "Alanine" and restored_compound.expected_rt == 0.2
+        assert session.settings == {"mass_accuracy": 0.0002, "smoothing": {"method": "savgol"}}
+        assert callable(measurement.data)  # The saved measurement keeps its reader
+
+    def test_no_pickles(self, tmp_path, raw_file):
+        path = save_session(tmp_path / "batch.lcms", {"sample": _measurement(raw_file)})
+        with np.load(path, allow_pickle=False) as archive:
+            assert json.loads(archive["session"].tobytes())["format"] == SESSION_FORMAT
+            assert all(archive[name].dtype != object for name in archive.files)
+
+    def test_frames_and_records(self, tmp_path):
+        feature_table = pd.DataFrame({"mz": [100.0, 200.0], "name": ["a", None]})
+        feature_table.attrs["source"] = "detect_features"
+        settings = {
+            "feature_tables": {"sample": feature_table},
+            "rt_shifts": {"sample": RTShift("linear", [(1.0, 1.1), (5.0, 5.2)])},
+            "file_statuses": {"sample": FileStatus("/data/sample.mzML", warnings=["no MS2"])},
+            "file_ranges": {"sample": {"rt_range": (1.0, 2.0)}},
+        }
+        save_session(tmp_path / "batch.lcms", {}, settings=settings)
+        restored = load_session(tmp_path / "batch.lcms").settings
+
+        pd.testing.assert_frame_equal(restored["feature_tables"]["sample"], feature_table)
+        assert restored["feature_tables"]["sample"].attrs == {"source": "detect_features"}
+        assert restored["rt_shifts"]["sample"].anchors == [(1.0, 1.1), (5.0, 5.2)]
+        assert restored["file_statuses"]["sample"] == settings["file_statuses"]["sample"]
+        assert restored["file_ranges"] == {"sample": {"rt_range": (1.0, 2.0)}}
+
+    def test_unsupported_value(self, tmp_path):
+        with pytest.raises(TypeError, match="Cannot save object"):
+            save_session(tmp_path / "batch.lcms", {}, settings={"callback": object()})
+        assert not (tmp_path / "batch.lcms").exists()
+
+    def test_missing_raw_file(self, tmp_path, raw_file):
+        save_session(tmp_path / "batch.lcms", {"sample": _measurement(raw_file)})
+        raw_file.unlink()
+        assert load_session(tmp_path / "batch.lcms").ms_measurements["sample"].data is None
+
+    def test_no_reopen(self, tmp_path, raw_file):
+        save_session(tmp_path / "batch.lcms", {"sample": _measurement(raw_file)})
+        session = load_session(tmp_path / "batch.lcms", reopen=False)
+        assert session.ms_measurements["sample"].data is None
+
+    def test_overwrite(self, tmp_path, raw_file):
+        path = tmp_path / "batch.lcms"
+        save_session(path, {"sample": _measurement(raw_file)})
+        save_session(path, {})
+        assert load_session(path).ms_measurements == {}
+        assert not (tmp_path / "batch.lcms.tmp").exists()
+
+
+class TestInvalidFiles:
+    def test_not_a_session(self, tmp_path):
+        path = tmp_path / "other.lcms"
+        path.write_bytes(b"\x89HDF\r\n\x1a\n")
+        with pytest.raises(FileParseError, match="not an LCMSpector session file"):
+            load_session(path)
+
+    def test_corrupt(self, tmp_path):
+        path = tmp_path / "broken.lcms"
+        path.write_bytes(b"PK\x03\x04garbage")
+        with pytest.raises(FileParseError, match="corrupt session file"):
+            load_session(path)
+
+    def test_pickled_members_rejected(self, tmp_path):
+        path = tmp_path / "pickled.lcms"
+        state = {
+            "format": SESSION_FORMAT, "version": 2, "ms_measurements": {}, "lc_measurements": {},
+            "compounds": [], "settings": {"x": {"@array": "a0"}},
+        }
+        with open(path, "wb") as f:
+            np.savez(
+                f,
+                session=np.frombuffer(json.dumps(state).encode(), dtype=np.uint8),
+                a0=np.array([object()], dtype=object),
+            )
+        with pytest.raises(FileParseError, match="corrupt session file"):
+            load_session(path)
+
+    def test_foreign_archive(self, tmp_path):
+        path = tmp_path / "arrays.npz"
+        with open(path, "wb") as f:
+            np.savez(f, x=np.zeros(3))
+        with pytest.raises(FileParseError, match="not an LCMSpector session file"):
+            load_session(path)
+
+    def test_newer_version(self, tmp_path):
+        path = tmp_path / "future.lcms"
+        _write_state(path, {"format": SESSION_FORMAT, "version": 99})
+        with pytest.raises(FileParseError, match="newer version"):
+            load_session(path)
+
+    def test_missing_file(self, tmp_path):
+        with pytest.raises(FileParseError, match="cannot read session"):
+            load_session(tmp_path / "missing.lcms")
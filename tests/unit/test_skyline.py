@@ -0,0 +1,144 @@
+"""
+Tests for the Skyline interchange in utils/skyline.py.
+
+Covers:
+- read_skyline_transition_list() for small molecule, SRM and peptide lists
+- Adduct labels, retention time windows and polarity of transition lists
+- skyline_report() columns, areas and background
+- export_skyline_report() output file
+"""
+
+import csv
+from types import SimpleNamespace
+
+import pandas as pd
+import pytest
+
+from utils.classes import compounds_from_ion_list
+from utils.errors import IonListError
+from utils.skyline import (
+    SKYLINE_REPORT_COLUMNS,
+    export_skyline_report,
+    read_skyline_transition_list,
+    skyline_report,
+)
+
+
+def _write(tmp_path, text, name="transitions.csv"):
+    path = tmp_path / name
+    path.write_text(text)
+    return path
+
+
+class TestReadTransitionList:
+    def test_small_molecules(self, tmp_path):
+        path = _write(
+            tmp_path,
+            "Molecule List Name,Precursor Name,Molecule Formula,Precursor Adduct,Precursor m/z,"
+            "Precursor Charge,Product m/z,Explicit Retention Time,Explicit Retention Time Window\n"
+            "Stimulants,Caffeine,C8H10N4O2,[M+H],195.0877,1,195.0877,5.0,1.0\n"
+            "Stimulants,Caffeine,C8H10N4O2,[M+Na],217.0696,1,217.0696,5.0,1.0\n"
+            "Acids,Citrate,C6H8O7,[M-H],191.0197,-1,191.0197,,\n",
+        )
+        ion_list = read_skyline_transition_list(path)
+        assert ion_list["Caffeine"] == {
+            "ions": [195.0877, 217.0696],
+            "info": ["[M+H]+", "[M+Na]+"],
+            "formula": "C8H10N4O2",
+            "rt_min": 4.5,
+            "rt_max": 5.5,
+        }
+        assert ion_list["Citrate"]["info"] == ["[M-H]-"]
+        assert ion_list["Citrate"]["polarity"] == "negative"
+        assert "rt_min" not in ion_list["Citrate"]
+        assert len(compounds_from_ion_list(ion_list)) == 2
+
+    def test_srm_transitions(self, tmp_path):
+        path = _write(
+            tmp_path,
+            "Molecule Name,Precursor Mz,Product Mz\nCaffeine,195.1,138.1\nCaffeine,195.1,110.1\n",
+        )
+        ion_list = read_skyline_transition_list(path)
+        assert ion_list == {"Caffeine": {"transitions": [[195.1, 138.1], [195.1, 110.1]]}}
+        (compound,) = compounds_from_ion_list(ion_list)
+        assert compound.target_list == [138.1, 110.1]
+
+    def test_formula_only_rows_expanded(self, tmp_path):
+        path = _write(
+            tmp_path,
+            "Molecule Name,Molecular Formula,Precursor Adduct\nCaffeine,C8H10N4O2,M+Na\n",
+        )
+        (compound,) = compounds_from_ion_list(read_skyline_transition_list(path))
+        assert compound.ion_info == ["[M+Na]+"]
+        assert compound.target_list == pytest.approx([217.0696], abs=1e-4)
+
+    def test_peptides_tab_separated(self, tmp_path):
+        path = _write(
+            tmp_path,
+            "Protein Name\tPeptide Modified Sequence\tPrecursor Mz\tPrecursor Charge\n"
+            "P1\tPEPTIDEK\t464.7300\t2\nP1\tPEPTIDEK\t310.1558\t3\n",
+            "peptides.tsv",
+        )
+        entry = read_skyline_transition_list(path)["PEPTIDEK"]
+        assert entry["ions"] == [464.73, 310.1558]
+        assert entry["info"] == ["[M+2H]2+", "[M+3H]3+"]
+
+    @pytest.mark.parametrize(
+        "text",
+        [
+            "Precursor Mz,Product Mz\n195.1,138.1\n",
+            "Molecule Name,Precursor Mz\n,195.1\n",
+            "Molecule Name,Precursor Mz\nCaffeine,abc\n",
+            "Molecule Name,Precursor Charge\nCaffeine,1\n",
+        ],
+    )
+    def test_malformed_list_raises(self, tmp_path, text):
+        with pytest.raises(IonListError):
+            read_skyline_transition_list(_write(tmp_path, text))
+
+
+def _measurement(filename):
+    compounds = compounds_from_ion_list(
+        {
+            "Caffeine": {"ions": [195.0877, 217.0696], "info": ["[M+H]+", "[M+Na]+"]},
+            "Quantifier": {"transitions": [[195.1, 138.1]]},
+        }
+    )
+    compounds[0].concentration = 2.5
+    for compound in compounds:
+        for data in compound.ions.values():
+            data["RT"] = 5.0
+            data["Integration Data"] = {
+                "total_area": 120.0, "baseline_corrected_area": 100.0, "start_time": 4.9,
+                "end_time": 5.1, "peak_height": 40.0,
+            }
+    compounds[0].ions[217.0696]["Integration Data"] = None
+    return SimpleNamespace(filename=filename, xics=compounds)
+
+
+class TestSkylineReport:
+    def test_rows(self):
+        report = skyline_report([_measurement("a.mzML"), _measurement("b.mzML")])
+        assert list(report.columns) == list(SKYLINE_REPORT_COLUMNS)
+        assert len(report) == 6
+        first = report.iloc[0]
+        assert first["Replicate Name"] == "a"
+        assert first["Precursor Adduct"] == "[M+H]+"
+        assert first["Precursor Charge"] == 1
+        assert first["Precursor Mz"] == first["Product Mz"] == 195.0877
+        assert (first["Area"], first["Background"], first["Height"]) == (100.0, 20.0, 40.0)
+        assert first["Calculated Concentration"] == 2.5
+
+    def test_missing_peak_and_srm_transition(self):
+        report = skyline_report([_measurement("a.mzML")])
+        missing, srm = report.iloc[1], report.iloc[2]
+        assert pd.isna(missing["Area"]) and pd.isna(missing["Retention Time"])
+        assert (srm["Precursor Mz"], srm["Product Mz"]) == (195.1, 138.1)
+
+    def test_export(self, tmp_path):
+        path = export_skyline_report([_measurement("a.mzML")], tmp_path / "report.csv")
+        with open(path, newline="") as f:
+            rows = list(csv.DictReader(f))
+        assert len(rows) == 3
+        assert rows[1]["Area"] == "#N/A"
+        assert (tmp_path / "report.csv.manifest.json").exists()
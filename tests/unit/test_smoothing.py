@@ -0,0 +1,71 @@
+"""
+Tests for XIC smoothing in calculation/smoothing.py.
+"""
+
+import numpy as np
+import pytest
+
+from calculation.smoothing import smooth_trace, validate_smoothing
+
+
+@pytest.fixture
+def noisy_peak():
+    times = np.linspace(0, 2, 201)
+    clean = 1000 * np.exp(-0.5 * ((times - 1.0) / 0.1) ** 2)
+    noise = np.random.default_rng(0).normal(0, 30, len(times))
+    return clean, np.clip(clean + noise, 0, None)
+
+
+class TestSmoothTrace:
+    @pytest.mark.parametrize("method", ["savgol", "moving_average"])
+    def test_reduces_noise(self, noisy_peak, method):
+        clean, noisy = noisy_peak
+        smoothed = smooth_trace(noisy, method=method, window=7, polyorder=2)
+        assert smoothed.shape == noisy.shape
+        assert np.std(smoothed - clean) < np.std(noisy - clean)
+
+    def test_savgol_preserves_apex(self, noisy_peak):
+        clean, noisy = noisy_peak
+        smoothed = smooth_trace(noisy, window=9, polyorder=3)
+        assert abs(int(np.argmax(smoothed)) - int(np.argmax(clean))) <= 2
+        assert smoothed.min() >= 0
+
+    def test_moving_average_of_constant_is_constant(self):
+        trace = np.full(20, 5.0)
+        np.testing.assert_allclose(smooth_trace(trace, method="moving_average"), trace)
+
+    def test_keeps_float32_dtype(self):
+        trace = np.arange(10, dtype=np.float32)
+        assert smooth_trace(trace).dtype == np.float32
+
+    def test_short_trace_unchanged(self):
+        trace = np.array([1.0, 5.0, 1.0])
+        np.testing.assert_array_equal(smooth_trace(trace, window=5), trace)
+
+    def test_none_returns_copy(self):
+        trace = np.array([1.0, 2.0, 3.0, 4.0, 5.0])
+        result = smooth_trace(trace, method="none")
+        np.testing.assert_array_equal(result, trace)
+        assert result is not trace
+
+
+class TestValidateSmoothing:
+    def test_fills_defaults(self):
+        assert validate_smoothing({"method": "moving_average"}) == {
+            "method": "moving_average",
+            "window": 5,
+            "polyorder": 2,
+        }
+
+    @pytest.mark.parametrize(
+        "settings",
+        [
+            {"method": "gaussian"},
+            {"window": 4},
+            {"window": 1},
+            {"window": 5, "polyorder": 5},
+        ],
+    )
+    def test_invalid_settings_raise(self, settings):
+        with pytest.raises(ValueError):
+            validate_smoothing(settings)
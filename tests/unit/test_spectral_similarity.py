@@ -0,0 +1,122 @@
+"""
+Tests for calculation/spectral_similarity.py.
+
+Covers:
+- cosine_similarity() scores and greedy one-to-one peak matching
+- modified cosine, spectral entropy and ppm tolerances
+- match_ms2_spectra() ranking
+"""
+
+import numpy as np
+import pytest
+
+from calculation.spectral_similarity import (
+    cosine_similarity,
+    entropy_similarity,
+    match_ms2_spectra,
+    modified_cosine_similarity,
+)
+
+
+class TestCosineSimilarity:
+    def test_identical_spectra_score_one(self):
+        mz = np.array([50.0, 75.0, 100.0])
+        intensity = np.array([1.0, 5.0, 2.0])
+        score, matched = cosine_similarity(mz, intensity, mz, intensity)
+        assert score == pytest.approx(1.0)
+        assert matched == 3
+
+    def test_disjoint_spectra_score_zero(self):
+        score, matched = cosine_similarity([50.0], [1.0], [60.0], [1.0])
+        assert score == 0.0
+        assert matched == 0
+
+    def test_tolerance(self):
+        assert cosine_similarity([50.0], [1.0], [50.005], [1.0], tolerance=0.01)[1] == 1
+        assert cosine_similarity([50.0], [1.0], [50.005], [1.0], tolerance=0.001)[1] == 0
+
+    def test_each_peak_matched_once(self):
+        # Both library peaks lie within tolerance of the single query peak
+        score, matched = cosine_similarity(
+            [100.0], [1.0], [99.995, 100.004], [3.0, 4.0], tolerance=0.01
+        )
+        assert matched == 1
+        assert score == pytest.approx(4.0 / 5.0)
+
+    def test_unsorted_input(self):
+        score, _ = cosine_similarity([100.0, 50.0], [2.0, 1.0], [50.0, 100.0], [1.0, 2.0])
+        assert score == pytest.approx(1.0)
+
+
+class TestModifiedCosine:
+    def test_shifted_fragments_match(self):
+        # Analogue with a +14.016 modification carried by the 150 fragment
+        query = ([50.0, 150.0], [1.0, 1.0])
+        analogue = ([50.0, 164.016], [1.0, 1.0])
+        plain, _ = cosine_similarity(*query, *analogue)
+        modified, matched = modified_cosine_similarity(
+            *query, *analogue, precursor_a=200.0, precursor_b=214.016
+        )
+        assert plain == pytest.approx(0.5)
+        assert modified == pytest.approx(1.0)
+        assert matched == 2
+
+    def test_without_precursors_equals_cosine(self):
+        args = ([50.0, 150.0], [1.0, 2.0], [50.0, 164.0], [1.0, 2.0])
+        assert modified_cosine_similarity(*args, None, None) == cosine_similarity(*args)
+
+
+class TestEntropySimilarity:
+    def test_identical_spectra_score_one(self):
+        mz = [50.0, 75.0, 100.0]
+        intensity = [1.0, 5.0, 2.0]
+        score, matched = entropy_similarity(mz, intensity, mz, intensity)
+        assert score == pytest.approx(1.0)
+        assert matched == 3
+
+    def test_partial_overlap_between_zero_and_one(self):
+        score, matched = entropy_similarity([50.0, 75.0], [1.0, 1.0], [50.0, 90.0], [1.0, 1.0])
+        assert 0.0 < score < 1.0
+        assert matched == 1
+
+
+class TestPpmTolerance:
+    def test_ppm_scales_with_mz(self):
+        # 0.005 Da is 5 ppm at m/z 1000 but 50 ppm at m/z 100
+        assert cosine_similarity([1000.0], [1.0], [1000.005], [1.0], 10, "ppm")[1] == 1
+        assert cosine_similarity([100.0], [1.0], [100.005], [1.0], 10, "ppm")[1] == 0
+
+    def test_unknown_unit_rejected(self):
+        with pytest.raises(ValueError):
+            match_ms2_spectra([50.0], [1.0], [], tolerance_unit="mDa")
+
+
+class TestMatchMs2Spectra:
+    def _candidates(self):
+        return [
+            {"mz": np.array([60.0]), "intensity": np.array([1.0])},
+            {"mz": np.array([50.0, 75.0]), "intensity": np.array([1.0, 1.0])},
+            {"mz": np.array([50.0]), "intensity": np.array([1.0])},
+        ]
+
+    @pytest.mark.parametrize("method", ["cosine", "modified_cosine", "entropy"])
+    def test_ranked_best_first(self, method):
+        results = match_ms2_spectra(
+            np.array([50.0, 75.0]), np.array([1.0, 1.0]), self._candidates(), method=method
+        )
+        assert [r["index"] for r in results] == [1, 2, 0]
+        assert results[0]["score"] == pytest.approx(1.0)
+
+    def test_filters(self):
+        results = match_ms2_spectra(
+            np.array([50.0, 75.0]),
+            np.array([1.0, 1.0]),
+            self._candidates(),
+            min_matched_peaks=1,
+            top_n=1,
+        )
+        assert [r["index"] for r in results] == [1]
+
+    def test_unknown_method_rejected(self):
+        with pytest.raises(ValueError):
+            match_ms2_spectra([50.0], [1.0], [], method="dot")
@@ -0,0 +1,62 @@
+"""
+Tests for per-file processing status in calculation/status.py.
+
+Covers:
+- result_warnings() for missing scans, compounds without signal, failed
+  integration and missing MS2 scans
+- file_status() and FileStatus.failed()
+"""
+
+import numpy as np
+
+from calculation.status import FileStatus, file_status, result_warnings
+from utils.classes import compounds_from_ion_list
+
+
+def _compounds(*traces, integrated=True):
+    compounds = compounds_from_ion_list(
+        {f"C{i}": {"ions": [100.0 + i]} for i in range(len(traces))}
+    )
+    for compound, trace in zip(compounds, traces):
+        data = compound.ions[compound.target_list[0]]
+        data["MS Intensity"] = np.array(trace, dtype=np.float32).reshape(2, -1)
+        data["Integration Data"] = {"peak_area": 1.0} if integrated else None
+    return compounds
+
+
+SIGNAL = [[0.1, 0.2, 0.3], [0.0, 5.0, 0.0]]
+FLAT = [[0.1, 0.2, 0.3], [0.0, 0.0, 0.0]]
+
+
+class TestResultWarnings:
+    def test_clean_result(self):
+        assert result_warnings(_compounds(SIGNAL)) == []
+
+    def test_no_scans(self):
+        assert result_warnings(_compounds([[], []], [[], []])) == ["no scans were extracted"]
+
+    def test_compound_without_signal(self):
+        assert result_warnings(_compounds(SIGNAL, FLAT)) == ["compound C1 matched 0 scans"]
+
+    def test_failed_integration(self):
+        (warning,) = result_warnings(_compounds(SIGNAL, integrated=False))
+        assert warning.startswith("peak integration failed for C0")
+
+    def test_missing_ms2(self):
+        compounds = _compounds(SIGNAL)
+        assert result_warnings(compounds, link_ms2=True) == ["no MS2 scans found"]
+        compounds[0].ions[100.0]["MS2"] = [{"scan_time": 0.2}]
+        assert result_warnings(compounds, link_ms2=True) == []
+
+
+class TestFileStatus:
+    def test_ok_and_warnings(self):
+        assert file_status("/data/a.mzML", _compounds(SIGNAL)).status == "ok"
+        status = file_status("/data/a.mzML", _compounds(FLAT))
+        assert status.status == "warnings"
+        assert status.filename == "a.mzML"
+        assert status.warnings == ["compound C0 matched 0 scans"]
+
+    def test_failed(self):
+        status = FileStatus.failed("/data/a.mzML", RuntimeError("boom"))
+        assert (status.status, status.error, status.warnings) == ("failed", "boom", [])
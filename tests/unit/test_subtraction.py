@@ -0,0 +1,65 @@
+"""
+Tests for background subtraction in calculation/subtraction.py.
+
+Covers:
+- subtract_spectrum() matching within Da / ppm tolerances, scaling, clipping
+- background_subtracted_spectrum() pre-peak background windows
+- Argument validation
+"""
+
+import numpy as np
+import pytest
+
+from calculation.subtraction import background_subtracted_spectrum, subtract_spectrum
+
+SCANS = [
+    (0.80, 0.0, 1, np.array([200.0]), np.array([5.0])),
+    (0.85, 0.0, 1, np.array([200.0]), np.array([5.0])),
+    (1.00, 0.0, 1, np.array([150.0, 200.0]), np.array([10.0, 6.0])),
+]
+
+
+class FakeRun:
+    def scans(self, polarity=None, scan_filter=None):
+        return iter(SCANS)
+
+
+class TestSubtractSpectrum:
+    def test_nearest_background_peak(self):
+        sample = (np.array([300.0, 100.0, 200.0]), np.array([5.0, 10.0, 4.0]))
+        background = (np.array([100.0005, 199.0]), np.array([4.0, 100.0]), 3)
+        mz, intensity = subtract_spectrum(sample, background, tolerance=10.0)
+        np.testing.assert_allclose(mz, [100.0, 200.0, 300.0])
+        np.testing.assert_allclose(intensity, [6.0, 4.0, 5.0])  # 199.0 is too far off
+
+    def test_scale_and_clipping(self):
+        sample = ([100.0, 200.0], [10.0, 4.0])
+        background = ([100.0, 200.0], [2.0, 2.0])
+        mz, intensity = subtract_spectrum(
+            sample, background, scale=2.5, tolerance=0.01, tolerance_unit="Da"
+        )
+        np.testing.assert_allclose(mz, [100.0])
+        np.testing.assert_allclose(intensity, [5.0])
+
+    def test_invalid(self):
+        with pytest.raises(ValueError, match="scale"):
+            subtract_spectrum(([100.0], [1.0]), ([100.0], [1.0]), scale=-1.0)
+        with pytest.raises(ValueError, match="tolerance unit"):
+            subtract_spectrum(([100.0], [1.0]), ([100.0], [1.0]), tolerance_unit="mDa")
+
+
+class TestBackgroundSubtractedSpectrum:
+    def test_pre_peak_background(self):
+        mz, intensity = background_subtracted_spectrum(
+            FakeRun(), apex_rt=1.0, start_time=0.9, background_width=0.2
+        )
+        np.testing.assert_allclose(mz, [150.0, 200.0])
+        np.testing.assert_allclose(intensity, [10.0, 1.0])
+
+    def test_without_background_scans(self):
+        mz, intensity = background_subtracted_spectrum(FakeRun(), apex_rt=1.0, start_time=0.5)
+        np.testing.assert_allclose(intensity, [10.0, 6.0])
+
+    def test_invalid_window(self):
+        with pytest.raises(ValueError, match="Window widths"):
+            background_subtracted_spectrum(FakeRun(), 1.0, apex_window=0.0)
@@ -228,6 +228,110 @@ class TestComputeAdductMz:
         assert "[M+H]+" in result
 
 
+class TestMonoisotopicMass:
+    """Tests for monoisotopic_mass()."""
+
+    def test_exact_masses(self):
+        from utils.theoretical_spectrum import monoisotopic_mass
+
+        assert monoisotopic_mass("C4H8O2") == pytest.approx(88.05243, abs=1e-5)
+        assert monoisotopic_mass(" C8H10N4O2 ") == pytest.approx(194.08038, abs=1e-5)
+
+    @pytest.mark.parametrize("formula", ["", "C4H8Xx2", "not a formula"])
+    def test_invalid_formula_raises(self, formula):
+        from utils.theoretical_spectrum import monoisotopic_mass
+
+        with pytest.raises(ValueError):
+            monoisotopic_mass(formula)
+
+
+class TestExpandAdducts:
+    """Tests for adduct_mz_from_mass() and expand_adducts()."""
+
+    def test_proton_and_deprotonation(self):
+        from utils.theoretical_spectrum import expand_adducts
+
+        result = expand_adducts(194.0804, ["[M+H]+", "[M-H]-"])
+        # Proton mass 1.007276
+        assert result["[M+H]+"] == pytest.approx(195.0877, abs=1e-4)
+        assert result["[M-H]-"] == pytest.approx(193.0731, abs=1e-4)
+
+    def test_matches_formula_path_within_electron_mass(self):
+        from utils.theoretical_spectrum import (
+            ADDUCT_DEFINITIONS,
+            calculate_monoisotopic_mz,
+            expand_adducts,
+        )
+        from pyteomics.mass import Composition
+
+        neutral = Composition(formula="C8H10N4O2").mass()
+        labels = list(ADDUCT_DEFINITIONS)
+        from_formula = calculate_monoisotopic_mz("C8H10N4O2", labels)
+        from_mass = expand_adducts(neutral, labels)
+        for label in labels:
+            charge = ADDUCT_DEFINITIONS[label].charge
+            assert from_mass[label] == pytest.approx(from_formula[label], abs=0.0006 / charge + 1e-4)
+
+    def test_defaults_and_order(self):
+        from utils.theoretical_spectrum import DEFAULT_ADDUCTS, expand_adducts
+
+        assert list(expand_adducts(100.0)) == DEFAULT_ADDUCTS
+        assert list(expand_adducts(100.0, ["[M+Na]+", "[M+H]+"])) == ["[M+Na]+", "[M+H]+"]
+
+    def test_unknown_label_raises(self):
+        from utils.theoretical_spectrum import expand_adducts
+
+        with pytest.raises(ValueError):
+            expand_adducts(100.0, ["[M+Xx]+"])
+
+    def test_charge_state_labels(self):
+        from utils.theoretical_spectrum import adduct_definition, expand_adducts
+
+        defn = adduct_definition("[M+5H]5+")
+        assert (defn.charge, defn.polarity, defn.add_formula) == (5, "positive", "H5")
+        assert adduct_definition("[M-3H]3-").subtract_formula == "H3"
+        assert adduct_definition("[M+H]+") is not None
+        for label in ("[M+3H]2+", "[M+3H]3-", "[M+0H]0+", "[M+Xx]+", ""):
+            assert adduct_definition(label) is None
+        assert expand_adducts(1000.0, ["[M+4H]4+"])["[M+4H]4+"] == pytest.approx(251.0073, abs=1e-4)
+
+    def test_charge_state_adducts(self):
+        from utils.theoretical_spectrum import charge_state_adducts
+
+        assert charge_state_adducts(1, 3) == ["[M+H]+", "[M+2H]2+", "[M+3H]3+"]
+        assert charge_state_adducts(2, 2, "negative") == ["[M-2H]2-"]
+        with pytest.raises(ValueError):
+            charge_state_adducts(2, 1)
+
+
+class TestIsotopeRatios:
+    """Tests for isotope_ratios()."""
+
+    def test_caffeine_m_plus_1(self):
+        from utils.theoretical_spectrum import isotope_ratios
+
+        # 8 C x 1.07 % dominate M+1, N/H/O add about 1.6 %
+        ratios = isotope_ratios("C8H10N4O2", "[M+H]+")
+        assert len(ratios) == 3
+        assert ratios[0] == 1.0
+        assert ratios[1] == pytest.approx(0.102, abs=0.01)
+        assert 0 < ratios[2] < ratios[1]
+
+    def test_adduct_atoms_count(self):
+        from utils.theoretical_spectrum import isotope_ratios
+
+        # [2M+H]+ doubles the carbon count, so roughly doubles M+1
+        single = isotope_ratios("C8H10N4O2", "[M+H]+", n_isotopes=1)
+        dimer = isotope_ratios("C8H10N4O2", "[2M+H]+", n_isotopes=1)
+        assert dimer[1] == pytest.approx(2 * single[1], rel=0.05)
+
+    def test_invalid_formula_raises(self):
+        from utils.theoretical_spectrum import isotope_ratios
+
+        with pytest.raises(ValueError):
+            isotope_ratios("not a formula")
+
+
 class TestCalculateTheoreticalSpectrum:
     """Tests for calculate_theoretical_spectrum()."""
 
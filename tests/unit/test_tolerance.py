@@ -0,0 +1,49 @@
+"""
+Tests for utils/tolerance.py.
+
+Covers:
+- parse_tolerance() accepted forms and unit normalization
+- parse_tolerance() rejecting malformed or non-positive tolerances
+- tolerance_window() for ppm and mDa
+"""
+
+import pytest
+
+from utils.tolerance import parse_tolerance, tolerance_window
+
+
+class TestParseTolerance:
+    @pytest.mark.parametrize(
+        "value, expected",
+        [
+            ("5 ppm", (5.0, "ppm")),
+            ("2mDa", (2.0, "mDa")),
+            ("0.002 Da", (2.0, "mDa")),
+            (10, (10.0, "ppm")),
+            ("7.5", (7.5, "ppm")),
+            ({"value": 3, "unit": "mDa"}, (3.0, "mDa")),
+            ((4, "ppm"), (4.0, "ppm")),
+        ],
+    )
+    def test_accepted_forms(self, value, expected):
+        amount, unit = parse_tolerance(value)
+        assert amount == pytest.approx(expected[0])
+        assert unit == expected[1]
+
+    @pytest.mark.parametrize("value", [None, "", "  "])
+    def test_empty_is_none(self, value):
+        assert parse_tolerance(value) is None
+
+    @pytest.mark.parametrize("value", ["5 furlongs", "ppm", 0, "-2 mDa", {"unit": "ppm"}])
+    def test_invalid_raises(self, value):
+        with pytest.raises(ValueError):
+            parse_tolerance(value)
+
+
+class TestToleranceWindow:
+    def test_ppm_scales_with_mass(self):
+        assert tolerance_window(1000.0, (5.0, "ppm")) == pytest.approx((999.995, 1000.005))
+
+    def test_mda_is_absolute(self):
+        assert tolerance_window(1000.0, (2.0, "mDa")) == pytest.approx((999.998, 1000.002))
+        assert tolerance_window(100.0, (2.0, "mDa")) == pytest.approx((99.998, 100.002))
@@ -2,8 +2,11 @@
 Widget interaction tests for UploadTab.
 
 Tests browse buttons, file list operations, clear buttons,
-ion table operations, process button, and mass accuracy slider.
+ion table operations (including the expansion of loaded ion list entries),
+process and cancel buttons, and mass accuracy slider.
 """
+import json
+
 import pytest
 from PySide6.QtCore import Qt
 from PySide6.QtWidgets import QTableWidgetItem
@@ -264,6 +267,55 @@ class TestIonTableOperations:
         assert len(items) == 1
         assert items[0].ion_info == []
 
+    @staticmethod
+    def _load_entries(upload_tab, ion_list):
+        """Add *ion_list* to the config as "Entries", select it and return the table rows."""
+        data = json.loads(upload_tab.config_path.read_text())
+        data["Entries"] = ion_list
+        upload_tab.config_path.write_text(json.dumps(data))
+        upload_tab._load_ion_config_names()
+        upload_tab.comboBoxIonLists.setCurrentIndex(upload_tab.comboBoxIonLists.findText("Entries"))
+        table = upload_tab.ionTable
+        return {
+            table.item(row, 0).text(): (
+                [float(mz) for mz in table.item(row, 1).text().split(",")],
+                [label.strip() for label in table.item(row, 2).text().split(",")],
+            )
+            for row in range(table.rowCount())
+        }
+
+    def test_load_mass_entry(self, upload_tab):
+        """A mass-only entry is expanded with its adducts, else the checked ones."""
+        rows = self._load_entries(
+            upload_tab,
+            {
+                "_adducts": ["[M+H]+"],
+                "Caffeine": {"mass": 194.080376},
+                "Theophylline": {"mass": 180.064726, "adducts": ["[M+H]+", "[M+Na]+"]},
+            },
+        )
+        assert rows["Caffeine"] == (pytest.approx([195.0877], abs=1e-3), ["[M+H]+"])
+        mzs, labels = rows["Theophylline"]
+        assert mzs == pytest.approx([181.0720, 203.0539], abs=1e-3)
+        assert labels == ["[M+H]+", "[M+Na]+"]
+
+    def test_load_charge_range_entry(self, upload_tab):
+        """A charge range adds the [M+zH]z+ ions of a mass or formula entry."""
+        rows = self._load_entries(
+            upload_tab,
+            {
+                "_adducts": ["[M+Na]+"],
+                "Peptide": {"mass": 1000.0, "charges": "1-3"},
+                "Caffeine": {"formula": "C8H10N4O2", "adducts": ["[M+Na]+"], "charges": 2},
+            },
+        )
+        mzs, labels = rows["Peptide"]
+        assert mzs == pytest.approx([1001.0073, 501.0073, 334.3406], abs=1e-3)
+        assert labels == ["[M+H]+", "[M+2H]2+", "[M+3H]3+"]
+        mzs, labels = rows["Caffeine"]
+        assert mzs == pytest.approx([217.0696, 98.0475], abs=1e-3)
+        assert labels == ["[M+Na]+", "[M+2H]2+"]
+
     def test_select_empty_ion_list(self, upload_tab):
         """Selecting 'Empty List' clears the table."""
         # First populate with data
@@ -319,6 +371,18 @@ class TestProcessButton:
         assert upload_tab.processButton.isDefault() is True
 
 
+class TestCancelButton:
+    """Test cancel button behavior."""
+
+    def test_cancel_button_initially_hidden(self, upload_tab):
+        """Cancel button only shows while processing."""
+        assert upload_tab.cancelButton.isHidden() is True
+
+    def test_cancel_button_object_name(self, upload_tab):
+        """Cancel button has correct object name."""
+        assert upload_tab.cancel_button.objectName() == "cancelButton"
+
+
 class TestMassAccuracySlider:
     """Test mass accuracy slider behavior."""
 
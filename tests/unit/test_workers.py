@@ -0,0 +1,116 @@
+"""
+Tests for the core budget of the background workers.
+
+Covers:
+- set_num_threads()/num_threads() in workers.py
+- Worker processes lowering their priority with low_priority, on Windows too
+- Per-file error reporting (_file_error)
+- process_files_iter() yielding files in order of completion
+- _process_file() hashing the raw file next to its processing
+"""
+
+import ctypes
+import hashlib
+import logging
+import os
+import threading
+from concurrent.futures import ThreadPoolExecutor
+from types import SimpleNamespace
+
+import pytest
+
+from calculation import workers
+from utils.errors import FileParseError, ProcessingError
+
+
+@pytest.fixture(autouse=True)
+def reset_num_threads():
+    yield
+    workers.set_num_threads(None)
+
+
+class TestNumThreads:
+    def test_defaults_to_all_cores(self):
+        assert workers.num_threads() == (os.cpu_count() or 1)
+
+    def test_global_limit_and_override(self):
+        workers.set_num_threads(2)
+        assert workers.num_threads() == 2
+        assert workers.num_threads(n_workers=3) == 3
+
+    def test_invalid_limit_raises(self):
+        with pytest.raises(ValueError):
+            workers.set_num_threads(0)
+
+
+@pytest.mark.skipif(not hasattr(os, "nice"), reason="os.nice is POSIX only")
+def test_low_priority_pool():
+    with workers._process_pool(n_workers=1, low_priority=True) as executor:
+        niceness = executor.submit(os.nice, 0).result()
+    assert niceness == min(os.nice(0) + workers._LOW_PRIORITY_NICENESS, 19)
+
+
+def test_low_priority_on_windows(monkeypatch, caplog):
+    classes = []
+    kernel32 = SimpleNamespace(
+        GetCurrentProcess=lambda: "process",
+        SetPriorityClass=lambda process, priority: classes.append((process, priority)) or 1,
+    )
+    monkeypatch.setattr(workers.sys, "platform", "win32")
+    monkeypatch.setattr(ctypes, "windll", SimpleNamespace(kernel32=kernel32), raising=False)
+    workers._lower_priority()
+    assert classes == [("process", workers._BELOW_NORMAL_PRIORITY_CLASS)]
+
+    monkeypatch.delattr(ctypes, "windll")
+    with caplog.at_level(logging.WARNING, logger=workers.__name__):
+        workers._lower_priority()  # Reported, the pool still runs
+    assert "Cannot lower the priority" in caplog.text
+
+
+class TestFileError:
+    def test_expected_errors_pass_through(self):
+        error = FileParseError("run.mzML", "truncated")
+        assert workers._file_error("run.mzML", error) is error
+
+    def test_unexpected_errors_are_wrapped(self):
+        error = workers._file_error("/data/run.mzML", KeyError("scan"))
+        assert isinstance(error, ProcessingError)
+        assert str(error) == "run.mzML: KeyError: 'scan'"
+
+
+class TestProcessFilesIter:
+    def test_yields_files_as_they_finish(self, monkeypatch):
+        slow_may_finish = threading.Event()
+
+        def fake_construct_xics(path, compounds, mass_accuracy, **options):
+            if path == "slow.mzML":
+                assert slow_may_finish.wait(5)
+            if path == "broken.mzML":
+                raise KeyError("scan")
+            return (path, compounds, mass_accuracy, options)
+
+        monkeypatch.setattr(workers, "construct_xics", fake_construct_xics)
+        with ThreadPoolExecutor(3) as pool:
+            results = workers.process_files_iter(
+                ["slow.mzML", "fast.mzML", "broken.mzML"], ("compounds",), 0.0002,
+                pool=pool, polarity="positive",
+            )
+            done = dict([next(results), next(results)])
+            slow_may_finish.set()
+            done.update(results)
+
+        assert set(done) == {"slow.mzML", "fast.mzML", "broken.mzML"}
+        assert done["fast.mzML"] == ("fast.mzML", ("compounds",), 0.0002, {"polarity": "positive"})
+        assert isinstance(done["broken.mzML"], ProcessingError)
+
+    def test_no_files(self):
+        assert list(workers.process_files_iter([], ())) == []
+
+
+def test_process_file_returns_checksum(tmp_path, monkeypatch):
+    path = tmp_path / "run.mzML"
+    path.write_bytes(b"data")
+    monkeypatch.setattr(workers, "construct_xics", lambda path, compounds, **options: compounds)
+    result, input_sha256 = workers._process_file(path, ("compounds",), polarity="positive")
+    assert result == ("compounds",)
+    assert input_sha256 == hashlib.sha256(b"data").hexdigest()